@@ -0,0 +1,150 @@
+//! Implementation of the blockchain interface on top of the blocking Esplora
+//! backend of BDK.
+
+use bdk::blockchain::{Blockchain as _, EsploraBlockchain, GetBlockHash, GetHeight, GetTx};
+use bitcoin::{Block, BlockHeader, Network, OutPoint, Transaction, Txid};
+use dlc_manager::error::Error;
+use dlc_manager::{Blockchain, ChainTip};
+
+type Result<T> = core::result::Result<T, Error>;
+
+fn to_blockchain_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::BlockchainError(e.to_string())
+}
+
+/// Blockchain provider using a BDK [`EsploraBlockchain`], which can also be
+/// used to synchronize a [`crate::BdkWallet`].
+pub struct BdkEsploraBlockchain {
+    blockchain: EsploraBlockchain,
+    network: Network,
+}
+
+impl BdkEsploraBlockchain {
+    /// Creates a provider using the given Esplora backend, connected to a
+    /// server for the given network.
+    pub fn new(blockchain: EsploraBlockchain, network: Network) -> Self {
+        Self {
+            blockchain,
+            network,
+        }
+    }
+
+    /// Returns the underlying BDK backend, e.g. to synchronize a wallet with
+    /// [`crate::BdkWallet::sync`].
+    pub fn get_bdk_blockchain(&self) -> &EsploraBlockchain {
+        &self.blockchain
+    }
+
+    fn get_header_at_height(&self, height: u64) -> Result<BlockHeader> {
+        let hash = self
+            .blockchain
+            .get_block_hash(height)
+            .map_err(to_blockchain_error)?;
+        self.blockchain
+            .get_header_by_hash(&hash)
+            .map_err(to_blockchain_error)
+    }
+}
+
+impl Blockchain for BdkEsploraBlockchain {
+    fn send_transaction(&self, transaction: &Transaction) -> Result<()> {
+        self.blockchain
+            .broadcast(transaction)
+            .map_err(to_blockchain_error)
+    }
+
+    fn get_network(&self) -> Result<Network> {
+        Ok(self.network)
+    }
+
+    fn get_blockchain_height(&self) -> Result<u64> {
+        Ok(self.blockchain.get_height().map_err(to_blockchain_error)? as u64)
+    }
+
+    /// Esplora does not provide raw blocks, so the block is rebuilt by
+    /// retrieving each of its transactions.
+    fn get_block_at_height(&self, height: u64) -> Result<Block> {
+        let hash = self
+            .blockchain
+            .get_block_hash(height)
+            .map_err(to_blockchain_error)?;
+        let header = self
+            .blockchain
+            .get_header_by_hash(&hash)
+            .map_err(to_blockchain_error)?;
+        let mut txdata = Vec::new();
+        while let Some(txid) = self
+            .blockchain
+            .get_txid_at_block_index(&hash, txdata.len())
+            .map_err(to_blockchain_error)?
+        {
+            txdata.push(
+                self.blockchain
+                    .get_tx_no_opt(&txid)
+                    .map_err(to_blockchain_error)?,
+            );
+        }
+        Ok(Block { header, txdata })
+    }
+
+    fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction> {
+        self.blockchain
+            .get_tx(tx_id)
+            .map_err(to_blockchain_error)?
+            .ok_or_else(|| Error::BlockchainError(format!("Transaction {} not found", tx_id)))
+    }
+
+    fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32> {
+        let block_height = match self
+            .blockchain
+            .get_tx_status(tx_id)
+            .map_err(to_blockchain_error)?
+        {
+            Some(status) if status.confirmed => status.block_height.ok_or_else(|| {
+                Error::BlockchainError(format!("Missing block height for confirmed {}", tx_id))
+            })?,
+            _ => return Ok(0),
+        };
+        let tip_height = self.blockchain.get_height().map_err(to_blockchain_error)?;
+        Ok(tip_height.max(block_height) - block_height + 1)
+    }
+
+    fn get_fee_rate(&self, confirmation_target: u32) -> Result<u64> {
+        let fee_rate = self
+            .blockchain
+            .estimate_fee(confirmation_target as usize)
+            .map_err(to_blockchain_error)?;
+        Ok(fee_rate.as_sat_per_vb().ceil() as u64)
+    }
+
+    fn get_mempool_spend(&self, outpoint: &OutPoint) -> Result<Option<Transaction>> {
+        let output_status = self
+            .blockchain
+            .get_output_status(&outpoint.txid, outpoint.vout as u64)
+            .map_err(to_blockchain_error)?;
+        match output_status {
+            Some(status) if status.spent && !status.status.map_or(false, |x| x.confirmed) => {
+                match status.txid {
+                    Some(txid) => Ok(Some(self.get_transaction(&txid)?)),
+                    None => Ok(None),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn get_blockchain_tip(&self) -> Result<ChainTip> {
+        let height = self.get_blockchain_height()?;
+        Ok(ChainTip {
+            height,
+            header: self.get_header_at_height(height)?,
+        })
+    }
+
+    fn get_headers_since(&self, height: u64) -> Result<Vec<BlockHeader>> {
+        let tip_height = self.get_blockchain_height()?;
+        (height + 1..=tip_height)
+            .map(|h| self.get_header_at_height(h))
+            .collect()
+    }
+}
@@ -0,0 +1,275 @@
+//! # bdk-wallet-provider
+//! Implementation of the wallet interface required by the dlc-manager on top of
+//! a [BDK](https://github.com/bitcoindevkit/bdk) wallet, and with the `esplora`
+//! feature of the blockchain interface on top of its Esplora backend.
+
+#![deny(missing_docs)]
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bdk::blockchain::{GetHeight, WalletSync};
+use bdk::database::BatchDatabase;
+use bdk::wallet::AddressIndex;
+use bdk::{SignOptions, SyncOptions};
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{Address, EcdsaSighashType, OutPoint, Script, Transaction, TxOut, Witness};
+use dlc_manager::error::Error;
+use dlc_manager::key_derivation::{ContractKeyId, DerivedKeysSigner};
+use dlc_manager::utxo_reservation::UtxoReservations;
+use dlc_manager::zeroizing::ZeroizingSecretKey;
+use dlc_manager::{ContractId, ContractSigner, Utxo, Wallet};
+use rust_bitcoin_coin_selection::select_coins;
+use secp256k1_zkp::{PublicKey, SecretKey};
+
+#[cfg(feature = "esplora")]
+mod esplora;
+#[cfg(feature = "esplora")]
+pub use esplora::BdkEsploraBlockchain;
+
+type Result<T> = core::result::Result<T, Error>;
+
+fn to_wallet_error(e: bdk::Error) -> Error {
+    Error::WalletError(Box::new(e))
+}
+
+/// Wallet funding contracts with the UTXOs of a BDK wallet. The funding inputs
+/// are signed by the BDK wallet, while the keys used in contracts and channels
+/// are derived by a [`DerivedKeysSigner`].
+pub struct BdkWallet<D: BatchDatabase> {
+    wallet: Mutex<bdk::Wallet<D>>,
+    contract_signer: DerivedKeysSigner,
+    reserved_utxos: Mutex<HashSet<OutPoint>>,
+    reservations: UtxoReservations,
+}
+
+impl<D: BatchDatabase> BdkWallet<D> {
+    /// Creates a new wallet using the given BDK wallet to manage on-chain
+    /// funds, and the given signer to derive contract keys.
+    pub fn new(wallet: bdk::Wallet<D>, contract_signer: DerivedKeysSigner) -> Self {
+        Self {
+            wallet: Mutex::new(wallet),
+            contract_signer,
+            reserved_utxos: Mutex::new(HashSet::new()),
+            reservations: UtxoReservations::new(),
+        }
+    }
+
+    /// Returns the signer deriving the keys of contracts and channels, e.g. to
+    /// recover them using [`DerivedKeysSigner::recover_keys_from_storage`]
+    /// after restarting.
+    pub fn get_contract_signer(&self) -> &DerivedKeysSigner {
+        &self.contract_signer
+    }
+
+    /// Synchronizes the BDK wallet with the given chain backend, and releases
+    /// the reservation of the UTXOs that were spent or whose reservation
+    /// expired.
+    pub fn sync<B: WalletSync + GetHeight>(&self, blockchain: &B) -> Result<()> {
+        let wallet = self.wallet.lock().unwrap();
+        wallet
+            .sync(blockchain, SyncOptions::default())
+            .map_err(to_wallet_error)?;
+        let unspent = wallet
+            .list_unspent()
+            .map_err(to_wallet_error)?
+            .into_iter()
+            .map(|x| x.outpoint)
+            .collect::<HashSet<_>>();
+        self.reserved_utxos
+            .lock()
+            .unwrap()
+            .retain(|x| unspent.contains(x));
+        self.reservations.remove_expired();
+        Ok(())
+    }
+
+    /// Returns the sum of the values of the wallet UTXOs.
+    pub fn get_balance(&self) -> Result<u64> {
+        Ok(self
+            .wallet
+            .lock()
+            .unwrap()
+            .get_balance()
+            .map_err(to_wallet_error)?
+            .get_total())
+    }
+
+    /// Releases the UTXOs locked by [`Wallet::get_utxos_for_amount`] so that
+    /// they can be used to fund other contracts.
+    pub fn unreserve_utxos(&self, outpoints: &[OutPoint]) {
+        let mut reserved_utxos = self.reserved_utxos.lock().unwrap();
+        for outpoint in outpoints {
+            reserved_utxos.remove(outpoint);
+        }
+    }
+}
+
+impl<D: BatchDatabase> ContractSigner for BdkWallet<D> {
+    fn get_new_secret_key(&self) -> Result<SecretKey> {
+        self.contract_signer.get_new_secret_key()
+    }
+
+    fn derive_contract_secret_key(&self, key_id: &ContractKeyId) -> Result<SecretKey> {
+        self.contract_signer.derive_contract_secret_key(key_id)
+    }
+
+    fn sign_tx_input(
+        &self,
+        tx: &mut Transaction,
+        input_index: usize,
+        tx_out: &TxOut,
+        redeem_script: Option<Script>,
+    ) -> Result<()> {
+        self.sign_tx_input_with_sighash_type(
+            tx,
+            input_index,
+            tx_out,
+            redeem_script,
+            EcdsaSighashType::All,
+        )
+    }
+
+    /// Signs the input using a PSBT in which only the signed input has its
+    /// previous output set, so that the BDK wallet leaves the other ones
+    /// untouched.
+    fn sign_tx_input_with_sighash_type(
+        &self,
+        tx: &mut Transaction,
+        input_index: usize,
+        tx_out: &TxOut,
+        redeem_script: Option<Script>,
+        sighash_type: EcdsaSighashType,
+    ) -> Result<()> {
+        let mut unsigned_tx = tx.clone();
+        for input in unsigned_tx.input.iter_mut() {
+            input.script_sig = Script::new();
+            input.witness = Witness::new();
+        }
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned_tx)
+            .map_err(|e| Error::WalletError(Box::new(e)))?;
+        let psbt_input = psbt.inputs.get_mut(input_index).ok_or_else(|| {
+            Error::InvalidParameters(format!("No input at index {}", input_index))
+        })?;
+        psbt_input.witness_utxo = Some(tx_out.clone());
+        psbt_input.redeem_script = redeem_script.filter(|x| !x.is_empty());
+        psbt_input.sighash_type = Some(sighash_type.into());
+
+        let sign_options = SignOptions {
+            trust_witness_utxo: true,
+            allow_all_sighashes: sighash_type != EcdsaSighashType::All,
+            ..Default::default()
+        };
+        self.wallet
+            .lock()
+            .unwrap()
+            .sign(&mut psbt, sign_options)
+            .map_err(to_wallet_error)?;
+
+        let psbt_input = &psbt.inputs[input_index];
+        let witness = psbt_input.final_script_witness.clone().ok_or_else(|| {
+            Error::InvalidState(format!(
+                "Wallet could not sign input spending {}",
+                tx.input[input_index].previous_output
+            ))
+        })?;
+        if let Some(script_sig) = &psbt_input.final_script_sig {
+            tx.input[input_index].script_sig = script_sig.clone();
+        }
+        tx.input[input_index].witness = witness;
+        Ok(())
+    }
+
+    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<ZeroizingSecretKey> {
+        self.contract_signer.get_secret_key_for_pubkey(pubkey)
+    }
+}
+
+impl<D: BatchDatabase> Wallet for BdkWallet<D> {
+    fn get_new_address(&self) -> Result<Address> {
+        Ok(self
+            .wallet
+            .lock()
+            .unwrap()
+            .get_address(AddressIndex::New)
+            .map_err(to_wallet_error)?
+            .address)
+    }
+
+    fn get_utxos_for_amount(
+        &self,
+        amount: u64,
+        _fee_rate: Option<u64>,
+        lock_utxos: bool,
+    ) -> Result<Vec<Utxo>> {
+        let wallet = self.wallet.lock().unwrap();
+        let network = wallet.network();
+        let mut reserved_utxos = self.reserved_utxos.lock().unwrap();
+        let mut utxo_pool = wallet
+            .list_unspent()
+            .map_err(to_wallet_error)?
+            .into_iter()
+            .filter(|x| {
+                !reserved_utxos.contains(&x.outpoint) && !self.reservations.is_reserved(&x.outpoint)
+            })
+            .map(|x| {
+                let address =
+                    Address::from_script(&x.txout.script_pubkey, network).ok_or_else(|| {
+                        Error::InvalidState(format!("Unsupported script for utxo {}", x.outpoint))
+                    })?;
+                Ok(UtxoWrap(Utxo {
+                    tx_out: x.txout,
+                    outpoint: x.outpoint,
+                    address,
+                    redeem_script: Script::new(),
+                    reserved: false,
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let selection = select_coins(amount, 20, &mut utxo_pool)
+            .ok_or_else(|| Error::InvalidState("Not enough fund in utxos".to_string()))?;
+
+        if lock_utxos {
+            reserved_utxos.extend(selection.iter().map(|x| x.0.outpoint));
+        }
+
+        Ok(selection
+            .into_iter()
+            .map(|x| Utxo {
+                reserved: lock_utxos,
+                ..x.0
+            })
+            .collect())
+    }
+
+    /// Addresses of a BDK wallet are derived from its descriptors, so there is
+    /// nothing to import.
+    fn import_address(&self, _: &Address) -> Result<()> {
+        Ok(())
+    }
+
+    fn reserve_utxos(
+        &self,
+        temporary_id: &ContractId,
+        utxos: &[OutPoint],
+        ttl: Duration,
+    ) -> Result<()> {
+        self.reservations.reserve(temporary_id, utxos, ttl);
+        Ok(())
+    }
+
+    fn release_utxos(&self, temporary_id: &ContractId) -> Result<()> {
+        self.reservations.release(temporary_id);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct UtxoWrap(Utxo);
+
+impl rust_bitcoin_coin_selection::Utxo for UtxoWrap {
+    fn get_value(&self) -> u64 {
+        self.0.tx_out.value
+    }
+}
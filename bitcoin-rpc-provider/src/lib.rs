@@ -1,30 +1,42 @@
 //! # Bitcoin rpc provider
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use bitcoin::consensus::encode::Error as EncodeError;
+use bitcoin::hashes::hex::FromHex;
 use bitcoin::secp256k1::rand::thread_rng;
-use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
 use bitcoin::{
-    consensus::Decodable, network::constants::Network, Amount, PrivateKey, Script, Transaction,
-    Txid,
+    consensus::Decodable, network::constants::Network, Amount, BlockHeader, PrivateKey, Script,
+    Transaction, Txid,
 };
-use bitcoin::{Address, OutPoint, TxOut};
+use bitcoin::{Address, EcdsaSighashType, OutPoint, TxOut};
+use bitcoincore_rpc::jsonrpc::{self, simple_http};
 use bitcoincore_rpc::{json, Auth, Client, RpcApi};
 use bitcoincore_rpc_json::AddressType;
 use dlc_manager::error::Error as ManagerError;
-use dlc_manager::{Blockchain, Signer, Utxo, Wallet};
+use dlc_manager::retry::{CircuitBreaker, CircuitState, RequestError, RetryConfig};
+use dlc_manager::utxo_reservation::UtxoReservations;
+use dlc_manager::zeroizing::ZeroizingSecretKey;
+use dlc_manager::{Blockchain, ChainTip, ContractId, ContractSigner, Utxo, Wallet};
 use json::EstimateMode;
 use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
 use log::error;
 use rust_bitcoin_coin_selection::select_coins;
 
+#[cfg(feature = "zmq")]
+pub mod zmq_notifier;
+
 /// The minimum feerate we are allowed to send, as specify by LDK.
 const MIN_FEERATE: u32 = 253;
 
+/// The rpc error code returned while bitcoind is starting up.
+const RPC_IN_WARMUP: i32 = -28;
+
 #[derive(Clone, Eq, Hash, PartialEq)]
 pub enum Target {
     Background,
@@ -37,6 +49,26 @@ pub struct BitcoinCoreProvider {
     // Used to implement the FeeEstimator interface, heavily inspired by
     // https://github.com/lightningdevkit/ldk-sample/blob/main/src/bitcoind_client.rs#L26
     fees: Arc<HashMap<Target, AtomicU32>>,
+    breaker: CircuitBreaker,
+    reservations: UtxoReservations,
+}
+
+/// Configuration of the requests made to bitcoind.
+#[derive(Clone, Debug)]
+pub struct RpcConfig {
+    /// How long to wait for the response to a request.
+    pub timeout: Duration,
+    /// How requests failing because bitcoind could not be reached are retried.
+    pub retry: RetryConfig,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        RpcConfig {
+            timeout: Duration::from_secs(15),
+            retry: RetryConfig::default(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -45,6 +77,8 @@ pub enum Error {
     NotEnoughCoins,
     BitcoinError,
     InvalidState,
+    #[cfg(feature = "zmq")]
+    ZmqError(zmq::Error),
 }
 
 impl From<bitcoincore_rpc::Error> for Error {
@@ -59,6 +93,13 @@ impl From<Error> for ManagerError {
     }
 }
 
+#[cfg(feature = "zmq")]
+impl From<zmq::Error> for Error {
+    fn from(e: zmq::Error) -> Error {
+        Error::ZmqError(e)
+    }
+}
+
 impl From<EncodeError> for Error {
     fn from(_e: EncodeError) -> Error {
         Error::BitcoinError
@@ -74,6 +115,8 @@ impl std::fmt::Display for Error {
             }
             Error::BitcoinError => write!(f, "Bitcoin related error"),
             Error::InvalidState => write!(f, "Unexpected state was encountered"),
+            #[cfg(feature = "zmq")]
+            Error::ZmqError(e) => write!(f, "ZMQ error: {}", e),
         }
     }
 }
@@ -82,6 +125,8 @@ impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
             Error::RpcError(ref e) => Some(e),
+            #[cfg(feature = "zmq")]
+            Error::ZmqError(ref e) => Some(e),
             _ => None,
         }
     }
@@ -94,15 +139,64 @@ impl BitcoinCoreProvider {
         wallet: Option<String>,
         rpc_user: String,
         rpc_password: String,
+    ) -> Result<Self, Error> {
+        Self::new_with_auth(host, port, wallet, Auth::UserPass(rpc_user, rpc_password))
+    }
+
+    /// Creates a provider authenticating with the cookie file that bitcoind
+    /// writes in its data directory when no rpc password is configured.
+    pub fn new_with_cookie_file(
+        host: String,
+        port: u16,
+        wallet: Option<String>,
+        cookie_file: PathBuf,
+    ) -> Result<Self, Error> {
+        Self::new_with_auth(host, port, wallet, Auth::CookieFile(cookie_file))
+    }
+
+    /// Creates a provider using the given authentication. If a wallet name is
+    /// given, requests are sent to its endpoint and the wallet is loaded if it
+    /// is not already.
+    pub fn new_with_auth(
+        host: String,
+        port: u16,
+        wallet: Option<String>,
+        auth: Auth,
+    ) -> Result<Self, Error> {
+        Self::new_with_config(host, port, wallet, auth, RpcConfig::default())
+    }
+
+    /// Creates a provider using the given authentication, with the given
+    /// request timeout and retry policy.
+    pub fn new_with_config(
+        host: String,
+        port: u16,
+        wallet: Option<String>,
+        auth: Auth,
+        config: RpcConfig,
     ) -> Result<Self, Error> {
         let rpc_base = format!("http://{}:{}", host, port);
-        let rpc_url = if let Some(wallet_name) = wallet {
+        let rpc_url = if let Some(wallet_name) = &wallet {
             format!("{}/wallet/{}", rpc_base, wallet_name)
         } else {
             rpc_base
         };
-        let auth = Auth::UserPass(rpc_user, rpc_password);
-        Ok(Self::new_from_rpc_client(Client::new(&rpc_url, auth)?))
+        let mut builder = simple_http::Builder::new()
+            .url(&rpc_url)
+            .map_err(|e| bitcoincore_rpc::Error::JsonRpc(e.into()))?
+            .timeout(config.timeout);
+        if let (Some(user), pass) = auth.get_user_pass()? {
+            builder = builder.auth(user, pass);
+        }
+        let client = Client::from_jsonrpc(jsonrpc::Client::with_transport(builder.build()));
+        if let Some(wallet_name) = &wallet {
+            if !client.list_wallets()?.contains(wallet_name) {
+                client.load_wallet(wallet_name)?;
+            }
+        }
+        let mut provider = Self::new_from_rpc_client(client);
+        provider.breaker = CircuitBreaker::new(config.retry);
+        Ok(provider)
     }
 
     pub fn new_from_rpc_client(rpc_client: Client) -> Self {
@@ -113,7 +207,54 @@ impl BitcoinCoreProvider {
         fees.insert(Target::HighPriority, AtomicU32::new(5000));
         let fees = Arc::new(fees);
         poll_for_fee_estimates(client.clone(), fees.clone());
-        BitcoinCoreProvider { client, fees }
+        BitcoinCoreProvider {
+            client,
+            fees,
+            breaker: CircuitBreaker::new(RetryConfig::default()),
+            reservations: UtxoReservations::new(),
+        }
+    }
+
+    /// Unlocks in bitcoind the UTXOs whose reservation has expired.
+    fn unlock_expired_utxos(&self) {
+        self.unlock_utxos(&self.reservations.remove_expired());
+    }
+
+    /// Unlocks the given UTXOs in bitcoind one at a time, as it refuses to
+    /// unlock outputs that were spent in the meantime and would unlock all of
+    /// them given an empty list.
+    fn unlock_utxos(&self, utxos: &[OutPoint]) {
+        for utxo in utxos {
+            if let Err(e) = self.call(|client| client.unlock_unspent(&[*utxo])) {
+                error!("Could not unlock utxo {}: {}", utxo, e);
+            }
+        }
+    }
+
+    /// Sends a request with `f`, retrying it if bitcoind could not be reached.
+    fn call<T, F>(&self, f: F) -> Result<T, ManagerError>
+    where
+        F: Fn(&Client) -> Result<T, bitcoincore_rpc::Error>,
+    {
+        self.breaker.call(|| {
+            f(&self.client.lock().unwrap()).map_err(|e| {
+                if is_transient(&e) {
+                    RequestError::Transient(rpc_err_to_manager_err(e))
+                } else {
+                    RequestError::Permanent(rpc_err_to_manager_err(e))
+                }
+            })
+        })
+    }
+}
+
+fn is_transient(e: &bitcoincore_rpc::Error) -> bool {
+    match e {
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(_)) => true,
+        bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(rpc_error)) => {
+            rpc_error.code == RPC_IN_WARMUP
+        }
+        _ => false,
     }
 }
 
@@ -143,6 +284,47 @@ impl rust_bitcoin_coin_selection::Utxo for UtxoWrap {
     }
 }
 
+/// Returns whether the wallet is a descriptor wallet, which does not support
+/// the legacy import and dump rpcs.
+fn is_descriptor_wallet(client: &Client) -> Result<bool, Error> {
+    let info: serde_json::Value = client.call("getwalletinfo", &[])?;
+    Ok(info["descriptors"].as_bool().unwrap_or(false))
+}
+
+/// Imports the given descriptor, only scanning for transactions from now on.
+fn import_descriptor(client: &Client, descriptor: &str) -> Result<(), Error> {
+    let info = client.get_descriptor_info(descriptor)?;
+    let request = serde_json::json!([{
+        "desc": format!("{}#{}", descriptor, info.checksum),
+        "timestamp": "now",
+    }]);
+    let res: Vec<serde_json::Value> = client.call("importdescriptors", &[request])?;
+    if res.iter().all(|r| r["success"].as_bool() == Some(true)) {
+        Ok(())
+    } else {
+        error!("Failed importing descriptor: {:?}", res);
+        Err(Error::InvalidState)
+    }
+}
+
+/// Looks up the private key of the given public key among the single key
+/// descriptors of the wallet.
+fn find_descriptor_secret_key(client: &Client, pubkey: &PublicKey) -> Result<SecretKey, Error> {
+    let res: serde_json::Value = client.call("listdescriptors", &[true.into()])?;
+    let descriptors = res["descriptors"].as_array().ok_or(Error::InvalidState)?;
+    let secp = Secp256k1::signing_only();
+    descriptors
+        .iter()
+        .filter_map(|d| {
+            let desc = d["desc"].as_str()?;
+            let wif = desc.strip_prefix("wpkh(")?.split(')').next()?;
+            PrivateKey::from_wif(wif).ok()
+        })
+        .find(|k| k.public_key(&secp).inner == *pubkey)
+        .map(|k| k.inner)
+        .ok_or(Error::InvalidState)
+}
+
 fn rpc_err_to_manager_err(e: bitcoincore_rpc::Error) -> ManagerError {
     Error::RpcError(e).into()
 }
@@ -151,8 +333,31 @@ fn enc_err_to_manager_err(_e: EncodeError) -> ManagerError {
     Error::BitcoinError.into()
 }
 
-impl Signer for BitcoinCoreProvider {
-    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<SecretKey, ManagerError> {
+impl ContractSigner for BitcoinCoreProvider {
+    fn get_new_secret_key(&self) -> Result<SecretKey, ManagerError> {
+        let sk = SecretKey::new(&mut thread_rng());
+        let network = self.get_network()?;
+        let private_key = PrivateKey {
+            compressed: true,
+            network,
+            inner: sk,
+        };
+        let client = self.client.lock().unwrap();
+        if is_descriptor_wallet(&client)? {
+            import_descriptor(&client, &format!("wpkh({})", private_key.to_wif()))?;
+        } else {
+            client
+                .import_private_key(&private_key, None, Some(false))
+                .map_err(rpc_err_to_manager_err)?;
+        }
+
+        Ok(sk)
+    }
+
+    fn get_secret_key_for_pubkey(
+        &self,
+        pubkey: &PublicKey,
+    ) -> Result<ZeroizingSecretKey, ManagerError> {
         let b_pubkey = bitcoin::PublicKey {
             compressed: true,
             inner: *pubkey,
@@ -160,13 +365,14 @@ impl Signer for BitcoinCoreProvider {
         let address =
             Address::p2wpkh(&b_pubkey, self.get_network()?).or(Err(Error::BitcoinError))?;
 
-        let pk = self
-            .client
-            .lock()
-            .unwrap()
+        let client = self.client.lock().unwrap();
+        if is_descriptor_wallet(&client)? {
+            return Ok(find_descriptor_secret_key(&client, pubkey)?.into());
+        }
+        let pk = client
             .dump_private_key(&address)
             .map_err(rpc_err_to_manager_err)?;
-        Ok(pk.inner)
+        Ok(pk.inner.into())
     }
 
     fn sign_tx_input(
@@ -175,6 +381,23 @@ impl Signer for BitcoinCoreProvider {
         input_index: usize,
         tx_out: &TxOut,
         redeem_script: Option<Script>,
+    ) -> Result<(), ManagerError> {
+        self.sign_tx_input_with_sighash_type(
+            tx,
+            input_index,
+            tx_out,
+            redeem_script,
+            EcdsaSighashType::All,
+        )
+    }
+
+    fn sign_tx_input_with_sighash_type(
+        &self,
+        tx: &mut Transaction,
+        input_index: usize,
+        tx_out: &TxOut,
+        redeem_script: Option<Script>,
+        sighash_type: EcdsaSighashType,
     ) -> Result<(), ManagerError> {
         let outpoint = &tx.input[input_index].previous_output;
 
@@ -190,7 +413,7 @@ impl Signer for BitcoinCoreProvider {
             .client
             .lock()
             .unwrap()
-            .sign_raw_transaction_with_wallet(&*tx, Some(&[input]), None)
+            .sign_raw_transaction_with_wallet(&*tx, Some(&[input]), Some(sighash_type.into()))
             .map_err(rpc_err_to_manager_err)?;
         let signed_tx = Transaction::consensus_decode(&mut sign_result.hex.as_slice())
             .map_err(enc_err_to_manager_err)?;
@@ -211,32 +434,13 @@ impl Wallet for BitcoinCoreProvider {
             .map_err(rpc_err_to_manager_err)
     }
 
-    fn get_new_secret_key(&self) -> Result<SecretKey, ManagerError> {
-        let sk = SecretKey::new(&mut thread_rng());
-        let network = self.get_network()?;
-        self.client
-            .lock()
-            .unwrap()
-            .import_private_key(
-                &PrivateKey {
-                    compressed: true,
-                    network,
-                    inner: sk,
-                },
-                None,
-                Some(false),
-            )
-            .map_err(rpc_err_to_manager_err)?;
-
-        Ok(sk)
-    }
-
     fn get_utxos_for_amount(
         &self,
         amount: u64,
         _fee_rate: Option<u64>,
         lock_utxos: bool,
     ) -> Result<Vec<Utxo>, ManagerError> {
+        self.unlock_expired_utxos();
         let client = self.client.lock().unwrap();
         let utxo_res = client
             .list_unspent(None, None, None, Some(false), None)
@@ -273,32 +477,50 @@ impl Wallet for BitcoinCoreProvider {
         Ok(selection.into_iter().map(|x| x.0).collect())
     }
 
+    /// Uses `importdescriptors` with an `addr` descriptor for descriptor
+    /// wallets, which cannot import addresses directly.
     fn import_address(&self, address: &Address) -> Result<(), ManagerError> {
-        self.client
-            .lock()
-            .unwrap()
+        let client = self.client.lock().unwrap();
+        if is_descriptor_wallet(&client)? {
+            return Ok(import_descriptor(&client, &format!("addr({})", address))?);
+        }
+        client
             .import_address(address, None, Some(false))
             .map_err(rpc_err_to_manager_err)
     }
+
+    /// Reserved UTXOs are locked in bitcoind, so that they are also excluded
+    /// from the transactions it creates.
+    fn reserve_utxos(
+        &self,
+        temporary_id: &ContractId,
+        utxos: &[OutPoint],
+        ttl: Duration,
+    ) -> Result<(), ManagerError> {
+        self.unlock_expired_utxos();
+        self.unlock_utxos(&self.reservations.release(temporary_id));
+        if !utxos.is_empty() {
+            self.call(|client| client.lock_unspent(utxos))?;
+        }
+        self.reservations.reserve(temporary_id, utxos, ttl);
+        Ok(())
+    }
+
+    fn release_utxos(&self, temporary_id: &ContractId) -> Result<(), ManagerError> {
+        self.unlock_utxos(&self.reservations.release(temporary_id));
+        Ok(())
+    }
 }
 
 impl Blockchain for BitcoinCoreProvider {
     fn send_transaction(&self, transaction: &Transaction) -> Result<(), ManagerError> {
-        self.client
-            .lock()
-            .unwrap()
-            .send_raw_transaction(transaction)
-            .map_err(rpc_err_to_manager_err)?;
+        self.call(|client| client.send_raw_transaction(transaction))?;
         Ok(())
     }
 
     fn get_network(&self) -> Result<Network, ManagerError> {
         let network = match self
-            .client
-            .lock()
-            .unwrap()
-            .get_blockchain_info()
-            .map_err(rpc_err_to_manager_err)?
+            .call(|client| client.get_blockchain_info())?
             .chain
             .as_ref()
         {
@@ -317,58 +539,95 @@ impl Blockchain for BitcoinCoreProvider {
     }
 
     fn get_blockchain_height(&self) -> Result<u64, ManagerError> {
-        self.client
-            .lock()
-            .unwrap()
-            .get_block_count()
-            .map_err(rpc_err_to_manager_err)
+        self.call(|client| client.get_block_count())
     }
 
     fn get_block_at_height(&self, height: u64) -> Result<bitcoin::Block, ManagerError> {
-        let client = self.client.lock().unwrap();
-        let hash = client
-            .get_block_hash(height)
-            .map_err(rpc_err_to_manager_err)?;
-        client.get_block(&hash).map_err(rpc_err_to_manager_err)
+        self.call(|client| {
+            let hash = client.get_block_hash(height)?;
+            client.get_block(&hash)
+        })
     }
 
     fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, ManagerError> {
-        let tx_info = self
-            .client
-            .lock()
-            .unwrap()
-            .get_transaction(tx_id, None)
-            .map_err(rpc_err_to_manager_err)?;
+        let tx_info = self.call(|client| client.get_transaction(tx_id, None))?;
         let tx = Transaction::consensus_decode(&mut tx_info.hex.as_slice())
             .or(Err(Error::BitcoinError))?;
         Ok(tx)
     }
 
     fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32, ManagerError> {
-        let tx_info_res = self.client.lock().unwrap().get_transaction(tx_id, None);
-        match tx_info_res {
+        self.call(|client| match client.get_transaction(tx_id, None) {
             Ok(tx_info) => Ok(tx_info.info.confirmations as u32),
-            Err(e) => match e {
-                bitcoincore_rpc::Error::JsonRpc(json_rpc_err) => match json_rpc_err {
-                    bitcoincore_rpc::jsonrpc::Error::Rpc(rpc_error) => {
-                        if rpc_error.code == -5
-                            && rpc_error.message == *"Invalid or non-wallet transaction id"
-                        {
-                            return Ok(0);
-                        }
-
-                        Err(rpc_err_to_manager_err(bitcoincore_rpc::Error::JsonRpc(
-                            bitcoincore_rpc::jsonrpc::Error::Rpc(rpc_error),
-                        )))
-                    }
-                    other => Err(rpc_err_to_manager_err(bitcoincore_rpc::Error::JsonRpc(
-                        other,
-                    ))),
-                },
-                _ => Err(rpc_err_to_manager_err(e)),
-            },
+            Err(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(rpc_error)))
+                if rpc_error.code == -5
+                    && rpc_error.message == *"Invalid or non-wallet transaction id" =>
+            {
+                Ok(0)
+            }
+            Err(e) => Err(e),
+        })
+    }
+
+    fn get_fee_rate(&self, confirmation_target: u32) -> Result<u64, ManagerError> {
+        // bitcoind only accepts targets between 1 and 1008 blocks.
+        let conf_target = confirmation_target.clamp(1, 1008) as u16;
+        let resp = self.call(|client| {
+            client.estimate_smart_fee(conf_target, Some(EstimateMode::Conservative))
+        })?;
+        match resp.fee_rate {
+            // The estimate is given per kvB.
+            Some(fee_rate) => Ok(std::cmp::max(
+                (fee_rate.to_sat() as f64 / 1000.0).ceil() as u64,
+                1,
+            )),
+            None => Err(ManagerError::BlockchainError(format!(
+                "No fee estimate available for {} blocks: {:?}",
+                conf_target, resp.errors
+            ))),
         }
     }
+
+    /// Requires bitcoind 24.0 or later.
+    fn get_mempool_spend(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, ManagerError> {
+        let request = serde_json::json!([{
+            "txid": outpoint.txid.to_string(),
+            "vout": outpoint.vout,
+        }]);
+        self.call(|client| {
+            let res: Vec<serde_json::Value> =
+                client.call("gettxspendingprevout", &[request.clone()])?;
+            match res.first().and_then(|r| r["spendingtxid"].as_str()) {
+                Some(spending_txid) => {
+                    let spending_txid = Txid::from_hex(spending_txid)?;
+                    Ok(Some(client.get_raw_transaction(&spending_txid, None)?))
+                }
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn get_blockchain_tip(&self) -> Result<ChainTip, ManagerError> {
+        self.call(|client| {
+            let hash = client.get_best_block_hash()?;
+            let height = client.get_block_header_info(&hash)?.height as u64;
+            let header = client.get_block_header(&hash)?;
+            Ok(ChainTip { height, header })
+        })
+    }
+
+    fn get_headers_since(&self, height: u64) -> Result<Vec<BlockHeader>, ManagerError> {
+        self.call(|client| {
+            let tip_height = client.get_block_count()?;
+            (height + 1..=tip_height)
+                .map(|h| client.get_block_header(&client.get_block_hash(h)?))
+                .collect()
+        })
+    }
+
+    fn get_circuit_state(&self) -> CircuitState {
+        self.breaker.get_state()
+    }
 }
 
 impl FeeEstimator for BitcoinCoreProvider {
@@ -0,0 +1,247 @@
+//! # ZMQ notifications
+//! Subscription to the `rawblock` and `rawtx` ZMQ notifications of bitcoind,
+//! converted into [`ChainNotification`] that can be processed by the manager
+//! instead of polling for confirmations.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use bitcoin::consensus::deserialize;
+use bitcoin::{Block, OutPoint, Transaction, Txid};
+use bitcoincore_rpc::{Client, RpcApi};
+use dlc_manager::ChainNotification;
+use log::{error, warn};
+
+use crate::{BitcoinCoreProvider, Error};
+
+const RAW_BLOCK_TOPIC: &[u8] = b"rawblock";
+const RAW_TX_TOPIC: &[u8] = b"rawtx";
+/// How often the notification thread checks whether it should stop.
+const RECEIVE_TIMEOUT_MS: i32 = 1000;
+
+/// The endpoints on which bitcoind publishes its notifications, as set with
+/// its `zmqpubrawblock` and `zmqpubrawtx` options.
+#[derive(Clone, Debug)]
+pub struct ZmqConfig {
+    /// The endpoint publishing the connected blocks, e.g. `tcp://127.0.0.1:28332`.
+    pub rawblock_endpoint: String,
+    /// The endpoint publishing the transactions entering the mempool or
+    /// included in a connected block. Can be the same as the block one.
+    pub rawtx_endpoint: String,
+}
+
+#[derive(Default)]
+struct Watched {
+    txids: HashSet<Txid>,
+    outpoints: HashSet<OutPoint>,
+}
+
+/// Active subscription to the notifications of bitcoind. A
+/// [`ChainNotification::BlockConnected`] is emitted for every block, while
+/// confirmations and spends are only reported for the watched transactions
+/// and outpoints. Spends are reported both when the spending transaction
+/// enters the mempool and when it is confirmed. The subscription stops when
+/// dropped.
+pub struct ZmqSubscription {
+    watched: Arc<Mutex<Watched>>,
+    receiver: Receiver<ChainNotification>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl BitcoinCoreProvider {
+    /// Subscribes to the ZMQ notifications published by bitcoind at the
+    /// given endpoints.
+    pub fn subscribe_zmq(&self, config: &ZmqConfig) -> Result<ZmqSubscription, Error> {
+        let context = zmq::Context::new();
+        let socket = context.socket(zmq::SUB)?;
+        socket.set_rcvtimeo(RECEIVE_TIMEOUT_MS)?;
+        socket.connect(&config.rawblock_endpoint)?;
+        if config.rawtx_endpoint != config.rawblock_endpoint {
+            socket.connect(&config.rawtx_endpoint)?;
+        }
+        socket.set_subscribe(RAW_BLOCK_TOPIC)?;
+        socket.set_subscribe(RAW_TX_TOPIC)?;
+
+        let watched = Arc::new(Mutex::new(Watched::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+        let handle = {
+            let watched = watched.clone();
+            let stop = stop.clone();
+            let client = self.client.clone();
+            std::thread::spawn(move || {
+                let mut notifier = Notifier {
+                    client,
+                    watched,
+                    sender,
+                    sequences: HashMap::new(),
+                };
+                while !stop.load(Ordering::Acquire) {
+                    match socket.recv_multipart(0) {
+                        Ok(message) => {
+                            if !notifier.on_message(&message) {
+                                return;
+                            }
+                        }
+                        Err(zmq::Error::EAGAIN) => {}
+                        Err(e) => {
+                            error!("Error receiving ZMQ notification: {}", e);
+                            return;
+                        }
+                    }
+                }
+            })
+        };
+
+        Ok(ZmqSubscription {
+            watched,
+            receiver,
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl ZmqSubscription {
+    /// Reports the confirmation of the transaction with the given id.
+    pub fn watch_transaction(&self, txid: Txid) {
+        self.watched.lock().unwrap().txids.insert(txid);
+    }
+
+    /// Reports the spend of the given outpoint, in the mempool or in a block.
+    pub fn watch_outpoint(&self, outpoint: OutPoint) {
+        self.watched.lock().unwrap().outpoints.insert(outpoint);
+    }
+
+    /// Stops reporting on the given transaction and outpoint.
+    pub fn unwatch(&self, txid: &Txid, outpoint: &OutPoint) {
+        let mut watched = self.watched.lock().unwrap();
+        watched.txids.remove(txid);
+        watched.outpoints.remove(outpoint);
+    }
+
+    /// Returns the next notification if one is available.
+    pub fn try_recv(&self) -> Option<ChainNotification> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits for the next notification for at most the given duration.
+    /// Returns `None` on timeout or if the subscription failed, in which case
+    /// the manager should fall back to polling.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<ChainNotification> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(notification) => Some(notification),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+impl Drop for ZmqSubscription {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Notifier {
+    client: Arc<Mutex<Client>>,
+    watched: Arc<Mutex<Watched>>,
+    sender: Sender<ChainNotification>,
+    /// The sequence number of the last message received on each topic.
+    sequences: HashMap<Vec<u8>, u32>,
+}
+
+impl Notifier {
+    /// Processes a message made of a topic, a body and a sequence number.
+    /// Returns false if the subscription was dropped.
+    fn on_message(&mut self, message: &[Vec<u8>]) -> bool {
+        let (topic, body, sequence) = match message {
+            [topic, body, sequence] if sequence.len() == 4 => {
+                let mut bytes = [0u8; 4];
+                bytes.copy_from_slice(sequence);
+                (topic, body, u32::from_le_bytes(bytes))
+            }
+            _ => {
+                warn!("Ignoring malformed ZMQ notification");
+                return true;
+            }
+        };
+        if let Some(prev) = self.sequences.insert(topic.clone(), sequence) {
+            if sequence != prev.wrapping_add(1) {
+                warn!(
+                    "Missed {} ZMQ notifications, a periodic check is required",
+                    String::from_utf8_lossy(topic)
+                );
+            }
+        }
+
+        let notifications = match topic.as_slice() {
+            RAW_BLOCK_TOPIC => match deserialize::<Block>(body) {
+                Ok(block) => self.on_block(&block),
+                Err(e) => {
+                    error!("Could not decode ZMQ block: {}", e);
+                    return true;
+                }
+            },
+            RAW_TX_TOPIC => match deserialize::<Transaction>(body) {
+                Ok(tx) => self.get_spends(&tx),
+                Err(e) => {
+                    error!("Could not decode ZMQ transaction: {}", e);
+                    return true;
+                }
+            },
+            _ => return true,
+        };
+        notifications
+            .into_iter()
+            .all(|notification| self.sender.send(notification).is_ok())
+    }
+
+    fn on_block(&self, block: &Block) -> Vec<ChainNotification> {
+        let block_hash = block.block_hash();
+        let height = match block.bip34_block_height().ok().or_else(|| {
+            self.client
+                .lock()
+                .unwrap()
+                .get_block_header_info(&block_hash)
+                .ok()
+                .map(|info| info.height as u64)
+        }) {
+            Some(height) => height,
+            None => {
+                error!("Could not get the height of block {}", block_hash);
+                return Vec::new();
+            }
+        };
+
+        let mut notifications = vec![ChainNotification::BlockConnected { height, block_hash }];
+        for tx in &block.txdata {
+            let txid = tx.txid();
+            if self.watched.lock().unwrap().txids.contains(&txid) {
+                notifications.push(ChainNotification::TransactionConfirmed { txid, height });
+            }
+            notifications.extend(self.get_spends(tx));
+        }
+        notifications
+    }
+
+    fn get_spends(&self, tx: &Transaction) -> Vec<ChainNotification> {
+        let watched = self.watched.lock().unwrap();
+        let spending_txid = tx.txid();
+        tx.input
+            .iter()
+            .filter(|input| watched.outpoints.contains(&input.previous_output))
+            .map(|input| ChainNotification::OutpointSpent {
+                outpoint: input.previous_output,
+                spending_txid,
+            })
+            .collect()
+    }
+}
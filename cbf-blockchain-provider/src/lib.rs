@@ -0,0 +1,630 @@
+//! # Compact block filter blockchain provider
+//! Light client implementation of the [`Blockchain`] trait relying on the
+//! compact block filters of BIP157/158. Headers are validated locally and
+//! filter headers are cross checked between all the configured peers, so that
+//! funding outputs can be watched and their spends detected without trusting
+//! a server with the list of scripts of interest.
+
+use std::cmp::min;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bitcoin::blockdata::constants::{genesis_block, max_target};
+use bitcoin::hashes::Hash;
+use bitcoin::util::bip158::BlockFilter;
+use bitcoin::util::uint::Uint256;
+use bitcoin::{
+    Block, BlockHash, BlockHeader, FilterHeader, Network, OutPoint, Script, Transaction, Txid,
+};
+use dlc_manager::{error::Error, Blockchain, ChainTip};
+use log::{debug, warn};
+
+mod peer;
+
+use peer::Peer;
+
+/// Maximum number of headers returned by a peer for a single request.
+const MAX_HEADERS: usize = 2000;
+/// Maximum number of filter headers that can be requested at once.
+const MAX_CFHEADERS: u32 = 2000;
+/// Maximum number of filters that can be requested at once.
+const MAX_CFILTERS: u32 = 1000;
+/// Number of blocks between two difficulty adjustments.
+const DIFFCHANGE_INTERVAL: u32 = 2016;
+
+/// Configuration of a [`CbfBlockchainProvider`].
+#[derive(Clone, Debug)]
+pub struct CbfConfig {
+    /// The network to connect to.
+    pub network: Network,
+    /// The addresses of the nodes to download headers, filters and blocks
+    /// from. Filter headers are only accepted if all of them agree, so using
+    /// nodes run by different parties protects against being served
+    /// incorrect filters.
+    pub peers: Vec<SocketAddr>,
+    /// A trusted block to start from instead of the genesis block, as a
+    /// height and header pair. Transactions confirmed before it are not
+    /// detected.
+    pub checkpoint: Option<(u32, BlockHeader)>,
+    /// The connection and read timeout used for the peers.
+    pub timeout: Duration,
+}
+
+impl CbfConfig {
+    /// Creates a configuration syncing from the genesis block of the given
+    /// network using the given peers.
+    pub fn new(network: Network, peers: Vec<SocketAddr>) -> Self {
+        CbfConfig {
+            network,
+            peers,
+            checkpoint: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Blockchain provider downloading headers and compact block filters from
+/// bitcoin nodes, and only the blocks whose filter match a watched script.
+pub struct CbfBlockchainProvider {
+    config: CbfConfig,
+    state: Mutex<ChainState>,
+}
+
+struct ChainState {
+    peers: Vec<Peer>,
+    /// The height of the first header of the chain.
+    base_height: u32,
+    headers: Vec<BlockHeader>,
+    /// The filter header of each block of the chain, in the same order.
+    filter_headers: Vec<FilterHeader>,
+    /// The height up to which the filters were matched against the watched
+    /// scripts.
+    scanned_height: u32,
+    watched_scripts: HashSet<Script>,
+    watched_outpoints: HashMap<OutPoint, Script>,
+    /// The transactions of interest with the height at which they were
+    /// confirmed if any.
+    transactions: HashMap<Txid, (Transaction, Option<u32>)>,
+    spends: HashMap<OutPoint, Txid>,
+}
+
+impl CbfBlockchainProvider {
+    /// Creates a new provider with the given configuration. Peers are
+    /// connected to on first use.
+    pub fn new(config: CbfConfig) -> Result<Self, Error> {
+        if config.peers.is_empty() {
+            return Err(Error::InvalidParameters(
+                "At least one peer is required".to_string(),
+            ));
+        }
+        let (base_height, base_header) = match config.checkpoint {
+            Some((height, header)) => {
+                header
+                    .validate_pow(&header.target())
+                    .map_err(|e| Error::InvalidParameters(format!("Invalid checkpoint: {}", e)))?;
+                (height, header)
+            }
+            None => (0, genesis_block(config.network).header),
+        };
+
+        Ok(CbfBlockchainProvider {
+            state: Mutex::new(ChainState {
+                peers: Vec::new(),
+                base_height,
+                headers: vec![base_header],
+                filter_headers: Vec::new(),
+                scanned_height: base_height,
+                watched_scripts: HashSet::new(),
+                watched_outpoints: HashMap::new(),
+                transactions: HashMap::new(),
+                spends: HashMap::new(),
+            }),
+            config,
+        })
+    }
+
+    /// Downloads the new headers, filter headers and filters, and the blocks
+    /// whose filter match a watched script.
+    pub fn sync(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.connect_peers(&self.config)?;
+        let res = state.sync(self.config.network);
+        if res.is_err() {
+            // Reconnect on next use, the failing peer might be unavailable
+            // or misbehaving.
+            state.peers.clear();
+        }
+        res
+    }
+
+    /// Detects the transactions paying to the given script, and the spends of
+    /// their outputs, in the blocks that have not been scanned yet.
+    pub fn watch_script(&self, script: Script) {
+        self.state.lock().unwrap().watched_scripts.insert(script);
+    }
+
+    /// Detects the spend of the given outpoint, locking the given script, in
+    /// the blocks that have not been scanned yet.
+    pub fn watch_outpoint(&self, outpoint: OutPoint, script_pubkey: Script) {
+        self.state
+            .lock()
+            .unwrap()
+            .watched_outpoints
+            .insert(outpoint, script_pubkey);
+    }
+
+    /// Tracks the confirmation of the given transaction by watching the
+    /// scripts of its outputs.
+    pub fn watch_transaction(&self, transaction: &Transaction) {
+        let mut state = self.state.lock().unwrap();
+        state.watch_transaction(transaction);
+    }
+
+    /// Makes the next synchronization scan the filters again from the given
+    /// height, to detect transactions involving newly watched scripts that
+    /// were confirmed in the past.
+    pub fn rescan(&self, height: u64) {
+        let mut state = self.state.lock().unwrap();
+        let height = (height as u32).saturating_sub(1).max(state.base_height);
+        state.scanned_height = min(state.scanned_height, height);
+    }
+
+    /// Returns the transaction spending the given watched outpoint, if one was
+    /// detected.
+    pub fn get_spending_transaction(&self, outpoint: &OutPoint) -> Option<Transaction> {
+        let state = self.state.lock().unwrap();
+        state
+            .spends
+            .get(outpoint)
+            .and_then(|txid| state.transactions.get(txid))
+            .map(|(tx, _)| tx.clone())
+    }
+}
+
+impl ChainState {
+    fn connect_peers(&mut self, config: &CbfConfig) -> Result<(), Error> {
+        if !self.peers.is_empty() {
+            return Ok(());
+        }
+        for addr in &config.peers {
+            match Peer::connect(addr, config.network, config.timeout) {
+                Ok(peer) => self.peers.push(peer),
+                Err(e) => warn!("Could not connect to {}: {}", addr, e),
+            }
+        }
+        if self.peers.is_empty() {
+            return Err(Error::BlockchainError(
+                "Could not connect to any peer".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self, network: Network) -> Result<(), Error> {
+        self.sync_headers(network)?;
+        self.sync_filter_headers()?;
+        self.scan_filters()
+    }
+
+    fn tip_height(&self) -> u32 {
+        self.base_height + self.headers.len() as u32 - 1
+    }
+
+    fn header_at(&self, height: u32) -> Option<&BlockHeader> {
+        height
+            .checked_sub(self.base_height)
+            .and_then(|i| self.headers.get(i as usize))
+    }
+
+    fn height_of(&self, block_hash: &BlockHash) -> Option<u32> {
+        self.headers
+            .iter()
+            .rposition(|h| h.block_hash() == *block_hash)
+            .map(|i| self.base_height + i as u32)
+    }
+
+    /// Returns the hashes of the chain from the tip, densely for the last ten
+    /// blocks and then exponentially spaced, so that a peer can find the
+    /// last block in common with its own chain.
+    fn locator(&self) -> Vec<BlockHash> {
+        let mut locator = Vec::new();
+        let mut index = self.headers.len() - 1;
+        let mut step = 1;
+        loop {
+            locator.push(self.headers[index].block_hash());
+            if index == 0 {
+                return locator;
+            }
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            index = index.saturating_sub(step);
+        }
+    }
+
+    /// Downloads headers from each peer, switching to the branch with the
+    /// most work if they are on different ones.
+    fn sync_headers(&mut self, network: Network) -> Result<(), Error> {
+        let mut peers = std::mem::take(&mut self.peers);
+        for peer in peers.iter_mut() {
+            loop {
+                let locator = self.locator();
+                let headers = peer.get_headers(locator)?;
+                let count = headers.len();
+                if count == 0 {
+                    break;
+                }
+                self.connect_headers(headers, network)?;
+                if count < MAX_HEADERS {
+                    break;
+                }
+            }
+        }
+        self.peers = peers;
+        Ok(())
+    }
+
+    fn connect_headers(
+        &mut self,
+        mut headers: Vec<BlockHeader>,
+        network: Network,
+    ) -> Result<(), Error> {
+        let mut fork_height = self.height_of(&headers[0].prev_blockhash).ok_or_else(|| {
+            Error::BlockchainError("Headers do not connect to the known chain".to_string())
+        })?;
+        let known = headers
+            .iter()
+            .enumerate()
+            .take_while(|(i, h)| self.header_at(fork_height + 1 + *i as u32) == Some(h))
+            .count();
+        headers.drain(..known);
+        fork_height += known as u32;
+        if headers.is_empty() {
+            return Ok(());
+        }
+
+        let zero = Uint256::from_u64(0).unwrap();
+        let mut prev = *self.header_at(fork_height).unwrap();
+        let mut new_work = zero;
+        for (i, header) in headers.iter().enumerate() {
+            let height = fork_height + 1 + i as u32;
+            if header.prev_blockhash != prev.block_hash() {
+                return Err(Error::BlockchainError(format!(
+                    "Header at height {} does not connect to its predecessor",
+                    height
+                )));
+            }
+            check_header(header, &prev, height, network)?;
+            new_work = new_work + header.work();
+            prev = *header;
+        }
+
+        let fork_index = (fork_height - self.base_height) as usize;
+        if fork_index + 1 < self.headers.len() {
+            let cur_work = self.headers[fork_index + 1..]
+                .iter()
+                .fold(zero, |acc, h| acc + h.work());
+            if new_work <= cur_work {
+                debug!("Ignoring branch forking at height {}", fork_height);
+                return Ok(());
+            }
+            warn!("Reorganization of the chain from height {}", fork_height);
+            self.disconnect(fork_height);
+        }
+        self.headers.extend(headers);
+        Ok(())
+    }
+
+    /// Removes the blocks above the given height, marking the transactions
+    /// that were confirmed in them as unconfirmed.
+    fn disconnect(&mut self, fork_height: u32) {
+        let len = (fork_height - self.base_height + 1) as usize;
+        self.headers.truncate(len);
+        self.filter_headers.truncate(len);
+        self.scanned_height = min(self.scanned_height, fork_height);
+        for (_, height) in self.transactions.values_mut() {
+            if height.map_or(false, |h| h > fork_height) {
+                *height = None;
+            }
+        }
+    }
+
+    /// Downloads the filter headers of the new blocks from every peer,
+    /// failing if they do not all agree.
+    fn sync_filter_headers(&mut self) -> Result<(), Error> {
+        while self.filter_headers.len() < self.headers.len() {
+            let start_index = self.filter_headers.len();
+            let stop_index = min(start_index + MAX_CFHEADERS as usize, self.headers.len()) - 1;
+            let start_height = self.base_height + start_index as u32;
+            let stop_hash = self.headers[stop_index].block_hash();
+
+            let mut agreed: Option<Vec<FilterHeader>> = None;
+            for peer in self.peers.iter_mut() {
+                let cfheaders = peer.get_cfheaders(start_height, stop_hash)?;
+                if cfheaders.filter_hashes.len() != stop_index - start_index + 1 {
+                    return Err(Error::BlockchainError(format!(
+                        "Peer {} returned an unexpected number of filter headers",
+                        peer.addr()
+                    )));
+                }
+                let mut prev = cfheaders.previous_filter_header;
+                let expected_prev = match self.filter_headers.last() {
+                    Some(last) => Some(*last),
+                    None if start_height == 0 => Some(FilterHeader::all_zeros()),
+                    None => None,
+                };
+                if expected_prev.map_or(false, |expected| expected != prev) {
+                    return Err(Error::BlockchainError(format!(
+                        "Peer {} returned filter headers not connecting to the known ones",
+                        peer.addr()
+                    )));
+                }
+                let filter_headers: Vec<_> = cfheaders
+                    .filter_hashes
+                    .iter()
+                    .map(|filter_hash| {
+                        prev = filter_hash.filter_header(&prev);
+                        prev
+                    })
+                    .collect();
+                match &agreed {
+                    None => agreed = Some(filter_headers),
+                    Some(agreed) if *agreed != filter_headers => {
+                        return Err(Error::BlockchainError(format!(
+                            "Peers disagree on the filter headers from height {}",
+                            start_height
+                        )));
+                    }
+                    _ => {}
+                }
+            }
+            self.filter_headers.extend(agreed.unwrap());
+        }
+        Ok(())
+    }
+
+    /// Matches the filters of the blocks that have not been scanned yet
+    /// against the watched scripts, processing the blocks that match.
+    fn scan_filters(&mut self) -> Result<(), Error> {
+        let tip_height = self.tip_height();
+        while self.scanned_height < tip_height {
+            let start_height = self.scanned_height + 1;
+            let stop_height = min(self.scanned_height + MAX_CFILTERS, tip_height);
+            let stop_hash = self.header_at(stop_height).unwrap().block_hash();
+            let filters = self.peers[0].get_cfilters(
+                start_height,
+                stop_hash,
+                (stop_height - start_height + 1) as usize,
+            )?;
+
+            for (height, cfilter) in (start_height..).zip(filters) {
+                let block_hash = self.header_at(height).unwrap().block_hash();
+                if cfilter.block_hash != block_hash {
+                    return Err(Error::BlockchainError(format!(
+                        "Received filter for unexpected block {}",
+                        cfilter.block_hash
+                    )));
+                }
+                let filter = BlockFilter::new(&cfilter.filter);
+                let index = (height - self.base_height) as usize;
+                if filter.filter_header(&self.filter_headers[index - 1])
+                    != self.filter_headers[index]
+                {
+                    return Err(Error::BlockchainError(format!(
+                        "Filter of block {} does not match its filter header",
+                        block_hash
+                    )));
+                }
+                if self.matches(&filter, &block_hash)? {
+                    debug!("Filter of block {} matched", block_hash);
+                    let block = self.get_block(height)?;
+                    self.process_block(&block, height);
+                }
+                self.scanned_height = height;
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&self, filter: &BlockFilter, block_hash: &BlockHash) -> Result<bool, Error> {
+        let mut query = self
+            .watched_scripts
+            .iter()
+            .chain(self.watched_outpoints.values())
+            .map(|script| script.as_bytes())
+            .peekable();
+        if query.peek().is_none() {
+            return Ok(false);
+        }
+        filter
+            .match_any(block_hash, &mut query)
+            .map_err(|e| Error::BlockchainError(e.to_string()))
+    }
+
+    /// Downloads the block at the given height, checking that it matches the
+    /// header of the chain.
+    fn get_block(&mut self, height: u32) -> Result<Block, Error> {
+        let block_hash = self
+            .header_at(height)
+            .ok_or_else(|| Error::InvalidParameters(format!("Unknown block height {}", height)))?
+            .block_hash();
+        let block = self.peers[0].get_block(&block_hash)?;
+        if !block.check_merkle_root() || !block.check_witness_commitment() {
+            return Err(Error::BlockchainError(format!(
+                "Block {} does not match its header",
+                block_hash
+            )));
+        }
+        Ok(block)
+    }
+
+    /// Records the transactions of the block paying to a watched script or
+    /// spending a watched outpoint. Outputs paying to a watched script are
+    /// watched so that their spend is detected as well.
+    fn process_block(&mut self, block: &Block, height: u32) {
+        for tx in &block.txdata {
+            let txid = tx.txid();
+            let mut relevant = false;
+            for input in &tx.input {
+                if self.watched_outpoints.contains_key(&input.previous_output) {
+                    self.spends.insert(input.previous_output, txid);
+                    relevant = true;
+                }
+            }
+            for (vout, output) in tx.output.iter().enumerate() {
+                if self.watched_scripts.contains(&output.script_pubkey) {
+                    self.watched_outpoints.insert(
+                        OutPoint::new(txid, vout as u32),
+                        output.script_pubkey.clone(),
+                    );
+                    relevant = true;
+                }
+            }
+            if relevant {
+                self.transactions.insert(txid, (tx.clone(), Some(height)));
+            }
+        }
+    }
+
+    fn watch_transaction(&mut self, transaction: &Transaction) {
+        for output in &transaction.output {
+            self.watched_scripts.insert(output.script_pubkey.clone());
+        }
+        self.transactions
+            .entry(transaction.txid())
+            .or_insert_with(|| (transaction.clone(), None));
+    }
+}
+
+/// Checks the proof of work of the given header, and on mainnet that the
+/// difficulty only changes at adjustment boundaries.
+fn check_header(
+    header: &BlockHeader,
+    prev: &BlockHeader,
+    height: u32,
+    network: Network,
+) -> Result<(), Error> {
+    let target = header.target();
+    if target > max_target(network) {
+        return Err(Error::BlockchainError(format!(
+            "Header at height {} has a target above the maximum",
+            height
+        )));
+    }
+    header.validate_pow(&target).map_err(|e| {
+        Error::BlockchainError(format!("Invalid header at height {}: {}", height, e))
+    })?;
+    if network == Network::Bitcoin && height % DIFFCHANGE_INTERVAL != 0 && header.bits != prev.bits
+    {
+        return Err(Error::BlockchainError(format!(
+            "Unexpected difficulty change at height {}",
+            height
+        )));
+    }
+    Ok(())
+}
+
+impl Blockchain for CbfBlockchainProvider {
+    fn send_transaction(&self, transaction: &Transaction) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.watch_transaction(transaction);
+        state.connect_peers(&self.config)?;
+        let mut sent = false;
+        for peer in state.peers.iter_mut() {
+            match peer.broadcast(transaction) {
+                Ok(()) => sent = true,
+                Err(e) => warn!("Could not broadcast to {}: {}", peer.addr(), e),
+            }
+        }
+        if !sent {
+            state.peers.clear();
+            return Err(Error::BlockchainError(format!(
+                "Could not broadcast transaction {}",
+                transaction.txid()
+            )));
+        }
+        Ok(())
+    }
+
+    fn get_network(&self) -> Result<Network, Error> {
+        Ok(self.config.network)
+    }
+
+    /// Synchronizes with the peers before returning the height of the tip.
+    fn get_blockchain_height(&self) -> Result<u64, Error> {
+        self.sync()?;
+        Ok(self.state.lock().unwrap().tip_height() as u64)
+    }
+
+    fn get_block_at_height(&self, height: u64) -> Result<Block, Error> {
+        let mut state = self.state.lock().unwrap();
+        state.connect_peers(&self.config)?;
+        let res = state.get_block(height as u32);
+        if res.is_err() {
+            state.peers.clear();
+        }
+        res
+    }
+
+    /// Only transactions that were broadcast through this provider or
+    /// involve a watched script or outpoint are known.
+    fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, Error> {
+        self.state
+            .lock()
+            .unwrap()
+            .transactions
+            .get(tx_id)
+            .map(|(tx, _)| tx.clone())
+            .ok_or_else(|| Error::BlockchainError(format!("Unknown transaction {}", tx_id)))
+    }
+
+    /// Returns zero for transactions that are not watched.
+    fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32, Error> {
+        let state = self.state.lock().unwrap();
+        match state.transactions.get(tx_id) {
+            Some((_, Some(height))) => Ok(state.tip_height() - height + 1),
+            _ => Ok(0),
+        }
+    }
+
+    /// Compact block filters give no access to the mempool, fee rates have to
+    /// be obtained from another source.
+    fn get_fee_rate(&self, _confirmation_target: u32) -> Result<u64, Error> {
+        Err(Error::BlockchainError(
+            "Fee estimation is not supported by the compact block filter provider".to_string(),
+        ))
+    }
+
+    fn get_blockchain_tip(&self) -> Result<ChainTip, Error> {
+        self.sync()?;
+        let state = self.state.lock().unwrap();
+        Ok(ChainTip {
+            height: state.tip_height() as u64,
+            header: *state.headers.last().expect("to have at least one header"),
+        })
+    }
+
+    /// Headers below the checkpoint the chain was synced from are not known
+    /// and not returned.
+    fn get_headers_since(&self, height: u64) -> Result<Vec<BlockHeader>, Error> {
+        self.sync()?;
+        let state = self.state.lock().unwrap();
+        let start = (height + 1).saturating_sub(state.base_height as u64) as usize;
+        Ok(state.headers.iter().skip(start).cloned().collect())
+    }
+
+    /// Peers do not relay their mempool, only the transactions broadcast
+    /// through this provider are known before being confirmed.
+    fn get_mempool_spend(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .transactions
+            .values()
+            .find(|(tx, height)| {
+                height.is_none() && tx.input.iter().any(|i| i.previous_output == *outpoint)
+            })
+            .map(|(tx, _)| tx.clone()))
+    }
+}
@@ -0,0 +1,225 @@
+//! # Peer
+//! Minimal blocking connection to a bitcoin node serving compact block
+//! filters (BIP157).
+
+use std::io::{BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bitcoin::consensus::encode::{serialize, Decodable};
+use bitcoin::hashes::Hash;
+use bitcoin::network::address::Address;
+use bitcoin::network::constants::{Network, ServiceFlags};
+use bitcoin::network::message::{NetworkMessage, RawNetworkMessage};
+use bitcoin::network::message_blockdata::{GetHeadersMessage, Inventory};
+use bitcoin::network::message_filter::{CFHeaders, CFilter, GetCFHeaders, GetCFilters};
+use bitcoin::network::message_network::VersionMessage;
+use bitcoin::{Block, BlockHash, BlockHeader, Transaction};
+use dlc_manager::error::Error;
+use log::debug;
+
+/// The filter type of the basic filters defined in BIP158.
+pub(crate) const BASIC_FILTER_TYPE: u8 = 0;
+
+const USER_AGENT: &str = "/rust-dlc:0.1.0/";
+
+pub(crate) struct Peer {
+    addr: SocketAddr,
+    network: Network,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// The height of the chain advertised by the peer during the handshake.
+    pub(crate) start_height: i32,
+}
+
+impl Peer {
+    /// Connects to the node at the given address and performs the version
+    /// handshake, failing if the node does not serve compact block filters.
+    pub(crate) fn connect(
+        addr: &SocketAddr,
+        network: Network,
+        timeout: Duration,
+    ) -> Result<Peer, Error> {
+        let stream = TcpStream::connect_timeout(addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        let mut peer = Peer {
+            addr: *addr,
+            network,
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            start_height: 0,
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards");
+        peer.send(NetworkMessage::Version(VersionMessage::new(
+            ServiceFlags::NONE,
+            now.as_secs() as i64,
+            Address::new(addr, ServiceFlags::NONE),
+            Address::new(&([0, 0, 0, 0], 0).into(), ServiceFlags::NONE),
+            now.subsec_nanos() as u64,
+            USER_AGENT.to_string(),
+            0,
+        )))?;
+
+        let mut got_version = false;
+        let mut got_verack = false;
+        while !got_version || !got_verack {
+            match peer.recv()? {
+                NetworkMessage::Version(version) => {
+                    if !version.services.has(ServiceFlags::COMPACT_FILTERS) {
+                        return Err(peer.error("does not serve compact block filters"));
+                    }
+                    peer.start_height = version.start_height;
+                    peer.send(NetworkMessage::Verack)?;
+                    got_version = true;
+                }
+                NetworkMessage::Verack => got_verack = true,
+                _ => {}
+            }
+        }
+
+        debug!("Connected to {} at height {}", addr, peer.start_height);
+        Ok(peer)
+    }
+
+    /// Returns the headers following the first hash of the locator found in
+    /// the peer's best chain, at most 2000 of them.
+    pub(crate) fn get_headers(
+        &mut self,
+        locator: Vec<BlockHash>,
+    ) -> Result<Vec<BlockHeader>, Error> {
+        self.send(NetworkMessage::GetHeaders(GetHeadersMessage::new(
+            locator,
+            BlockHash::all_zeros(),
+        )))?;
+        self.recv_matching(|message| match message {
+            NetworkMessage::Headers(headers) => Some(headers),
+            _ => None,
+        })
+    }
+
+    /// Returns the filter hashes of the blocks from `start_height` to the
+    /// block with hash `stop_hash`, at most 2000 of them.
+    pub(crate) fn get_cfheaders(
+        &mut self,
+        start_height: u32,
+        stop_hash: BlockHash,
+    ) -> Result<CFHeaders, Error> {
+        self.send(NetworkMessage::GetCFHeaders(GetCFHeaders {
+            filter_type: BASIC_FILTER_TYPE,
+            start_height,
+            stop_hash,
+        }))?;
+        let cfheaders = self.recv_matching(|message| match message {
+            NetworkMessage::CFHeaders(cfheaders) if cfheaders.stop_hash == stop_hash => {
+                Some(cfheaders)
+            }
+            _ => None,
+        })?;
+        if cfheaders.filter_type != BASIC_FILTER_TYPE {
+            return Err(self.error("returned filter headers of an unexpected type"));
+        }
+        Ok(cfheaders)
+    }
+
+    /// Returns the filters of the `count` blocks from `start_height` to the
+    /// block with hash `stop_hash`, at most 1000 of them.
+    pub(crate) fn get_cfilters(
+        &mut self,
+        start_height: u32,
+        stop_hash: BlockHash,
+        count: usize,
+    ) -> Result<Vec<CFilter>, Error> {
+        self.send(NetworkMessage::GetCFilters(GetCFilters {
+            filter_type: BASIC_FILTER_TYPE,
+            start_height,
+            stop_hash,
+        }))?;
+        let mut filters = Vec::with_capacity(count);
+        while filters.len() < count {
+            let filter = self.recv_matching(|message| match message {
+                NetworkMessage::CFilter(filter) if filter.filter_type == BASIC_FILTER_TYPE => {
+                    Some(filter)
+                }
+                _ => None,
+            })?;
+            filters.push(filter);
+        }
+        Ok(filters)
+    }
+
+    /// Returns the block with the given hash, including witness data.
+    pub(crate) fn get_block(&mut self, block_hash: &BlockHash) -> Result<Block, Error> {
+        self.send(NetworkMessage::GetData(vec![Inventory::WitnessBlock(
+            *block_hash,
+        )]))?;
+        let block = self.recv_matching(|message| match message {
+            NetworkMessage::Block(block) if block.block_hash() == *block_hash => Some(Some(block)),
+            NetworkMessage::NotFound(inventory)
+                if inventory.contains(&Inventory::WitnessBlock(*block_hash)) =>
+            {
+                Some(None)
+            }
+            _ => None,
+        })?;
+        block.ok_or_else(|| self.error(&format!("does not have block {}", block_hash)))
+    }
+
+    /// Relays the given transaction to the peer.
+    pub(crate) fn broadcast(&mut self, transaction: &Transaction) -> Result<(), Error> {
+        self.send(NetworkMessage::Tx(transaction.clone()))
+    }
+
+    pub(crate) fn addr(&self) -> &SocketAddr {
+        &self.addr
+    }
+
+    fn send(&mut self, payload: NetworkMessage) -> Result<(), Error> {
+        let message = RawNetworkMessage {
+            magic: self.network.magic(),
+            payload,
+        };
+        self.writer.write_all(&serialize(&message))?;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<NetworkMessage, Error> {
+        loop {
+            let message = RawNetworkMessage::consensus_decode(&mut self.reader)
+                .map_err(|e| self.error(&format!("sent an invalid message: {}", e)))?;
+            if message.magic != self.network.magic() {
+                return Err(self.error("is on a different network"));
+            }
+            match message.payload {
+                NetworkMessage::Ping(nonce) => self.send(NetworkMessage::Pong(nonce))?,
+                payload => return Ok(payload),
+            }
+        }
+    }
+
+    /// Receives messages until one is accepted by `f`, ignoring unsolicited
+    /// ones such as inventory announcements.
+    fn recv_matching<T, F>(&mut self, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut(NetworkMessage) -> Option<T>,
+    {
+        loop {
+            if let Some(res) = f(self.recv()?) {
+                return Ok(res);
+            }
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::BlockchainError(format!("Peer {} {}", self.addr, message))
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        let _ = self.writer.shutdown(Shutdown::Both);
+    }
+}
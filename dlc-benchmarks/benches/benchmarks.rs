@@ -0,0 +1,214 @@
+use std::rc::Rc;
+
+use bitcoin::EcdsaSighashType;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dlc::DlcTransactions;
+use dlc_benchmarks::{
+    accept_secret_key, contract_info, contract_input, dlc_transactions, oracle_announcements,
+    Party, CONTRACT_SIZES, TOTAL_COLLATERAL,
+};
+use dlc_manager::contract::contract_info::ContractInfo;
+use dlc_manager::contract::offered_contract::OfferedContract;
+use dlc_manager::contract::{AdaptorInfo, ContractDescriptor};
+use dlc_manager::contract_updater::{
+    accept_contract, offer_contract, verify_accepted_and_sign_contract, verify_signed_contract,
+};
+use dlc_manager::manager::REFUND_DELAY;
+use dlc_trie::multi_oracle_trie::MultiOracleTrie;
+use dlc_trie::DlcTrie;
+use mocks::mock_blockchain::MockBlockchain;
+use mocks::mock_time::MockTime;
+use secp256k1_zkp::{global::SECP256K1, EcdsaAdaptorSignature, PublicKey, Secp256k1};
+
+fn get_signed_transactions(
+    contract_info: &ContractInfo,
+) -> (DlcTransactions, AdaptorInfo, Vec<EcdsaAdaptorSignature>) {
+    let dlc_transactions = dlc_transactions(&contract_info.get_payouts(TOTAL_COLLATERAL).unwrap());
+    let (adaptor_info, adaptor_signatures) = contract_info
+        .get_adaptor_info(
+            SECP256K1,
+            TOTAL_COLLATERAL,
+            &accept_secret_key(),
+            &dlc_transactions.funding_script_pubkey,
+            dlc_transactions.get_fund_output().value,
+            &dlc_transactions.cets,
+            0,
+        )
+        .unwrap();
+    (dlc_transactions, adaptor_info, adaptor_signatures)
+}
+
+/// Benchmark to measure the creation time of the CET adaptor signatures.
+pub fn adaptor_sign_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("adaptor_sign");
+    group.sample_size(10);
+    for size in &CONTRACT_SIZES {
+        let contract_info = contract_info(size);
+        let dlc_transactions =
+            dlc_transactions(&contract_info.get_payouts(TOTAL_COLLATERAL).unwrap());
+        let fund_output_value = dlc_transactions.get_fund_output().value;
+        let seckey = accept_secret_key();
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
+            b.iter(|| {
+                black_box(
+                    contract_info
+                        .get_adaptor_info(
+                            SECP256K1,
+                            TOTAL_COLLATERAL,
+                            &seckey,
+                            &dlc_transactions.funding_script_pubkey,
+                            fund_output_value,
+                            &dlc_transactions.cets,
+                            0,
+                        )
+                        .unwrap(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Benchmark to measure the verification time of the CET adaptor signatures.
+pub fn adaptor_verify_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("adaptor_verify");
+    group.sample_size(10);
+    for size in &CONTRACT_SIZES {
+        let contract_info = contract_info(size);
+        let (dlc_transactions, adaptor_info, adaptor_signatures) =
+            get_signed_transactions(&contract_info);
+        let pubkey = PublicKey::from_secret_key(SECP256K1, &accept_secret_key());
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
+            b.iter(|| {
+                black_box(
+                    contract_info
+                        .verify_adaptor_info(
+                            SECP256K1,
+                            &pubkey,
+                            &dlc_transactions.funding_script_pubkey,
+                            dlc_transactions.get_fund_output().value,
+                            &dlc_transactions.cets,
+                            &adaptor_signatures,
+                            0,
+                            &adaptor_info,
+                        )
+                        .unwrap(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Benchmark to measure the construction time of the trie mapping outcomes to
+/// CETs.
+pub fn trie_construction_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trie_construction");
+    for size in &CONTRACT_SIZES {
+        let descriptor = match dlc_benchmarks::contract_descriptor(size) {
+            ContractDescriptor::Numerical(n) => n,
+            _ => unreachable!(),
+        };
+        let range_payouts = descriptor.get_range_payouts(TOTAL_COLLATERAL).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, size| {
+            b.iter(|| {
+                let mut trie =
+                    MultiOracleTrie::new(&descriptor.oracle_numeric_infos, size.threshold).unwrap();
+                black_box(trie.generate(0, &range_payouts).unwrap())
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Benchmark to measure the generation time of the payouts and CETs.
+pub fn cet_generation_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cet_generation");
+    for size in &CONTRACT_SIZES {
+        let contract_info = contract_info(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
+            b.iter(|| {
+                black_box(dlc_transactions(
+                    &contract_info.get_payouts(TOTAL_COLLATERAL).unwrap(),
+                ))
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Benchmark to measure the time for both parties to go from an offer to a
+/// signed contract, including the creation and verification of all
+/// signatures.
+pub fn accept_sign_round_trip_bench(c: &mut Criterion) {
+    let secp = Secp256k1::new();
+    let blockchain = Rc::new(MockBlockchain::new());
+    let time = Rc::new(MockTime {});
+    let offer_party = Party::new(&blockchain);
+    let accept_party = Party::new(&blockchain);
+
+    let mut group = c.benchmark_group("accept_sign_round_trip");
+    group.sample_size(10);
+    for size in &CONTRACT_SIZES {
+        let announcements = oracle_announcements(size);
+        let (offered_contract, _) = offer_contract(
+            &secp,
+            &contract_input(size, &announcements),
+            vec![announcements],
+            REFUND_DELAY,
+            &accept_party.node_id,
+            &offer_party.wallet,
+            &offer_party.wallet,
+            &blockchain,
+            &time,
+        )
+        .unwrap();
+        let received_contract = OfferedContract {
+            is_offer_party: false,
+            counter_party: offer_party.node_id,
+            ..offered_contract.clone()
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
+            b.iter(|| {
+                let (accepted_contract, accept_msg) = accept_contract(
+                    &secp,
+                    &received_contract,
+                    &accept_party.wallet,
+                    &accept_party.wallet,
+                    &blockchain,
+                )
+                .unwrap();
+                let (_, sign_msg) = verify_accepted_and_sign_contract(
+                    &secp,
+                    &offered_contract,
+                    &accept_msg,
+                    &offer_party.wallet,
+                    EcdsaSighashType::All,
+                )
+                .unwrap();
+                black_box(
+                    verify_signed_contract(
+                        &secp,
+                        &accepted_contract,
+                        &sign_msg,
+                        &accept_party.wallet,
+                        EcdsaSighashType::All,
+                    )
+                    .unwrap(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default();
+    targets = adaptor_sign_bench,
+        adaptor_verify_bench,
+        trie_construction_bench,
+        cet_generation_bench,
+        accept_sign_round_trip_bench
+}
+criterion_main!(benches);
@@ -0,0 +1,282 @@
+//! # dlc-benchmarks
+//! Fixtures used by the benchmarks of contract establishment. Contracts are
+//! numerical ones whose payout varies linearly over a quarter of the possible
+//! outcomes, so that the number of CETs grows with the number of digits
+//! attested by the oracles.
+
+#![deny(missing_docs)]
+
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{OutPoint, Script, WPubkeyHash};
+use dlc::{create_dlc_transactions, DlcTransactions, PartyParams, Payout, TxInputInfo};
+use dlc_manager::contract::contract_info::ContractInfo;
+use dlc_manager::contract::contract_input::{ContractInput, ContractInputInfo, OracleInput};
+use dlc_manager::contract::numerical_descriptor::NumericalDescriptor;
+use dlc_manager::contract::ContractDescriptor;
+use dlc_manager::payout_curve::{
+    PayoutFunction, PayoutFunctionPiece, PayoutPoint, PolynomialPayoutCurvePiece, RoundingInterval,
+    RoundingIntervals,
+};
+use dlc_messages::oracle_msgs::{
+    DigitDecompositionEventDescriptor, EventDescriptor, OracleAnnouncement, OracleEvent,
+};
+use mocks::mock_blockchain::MockBlockchain;
+use mocks::mock_wallet::MockWallet;
+use secp256k1_zkp::{
+    global::SECP256K1, rand::thread_rng, schnorr::Signature, KeyPair, PublicKey, SecretKey,
+    XOnlyPublicKey,
+};
+
+/// The base in which the outcome values are decomposed.
+pub const BASE: usize = 2;
+/// The collateral of each party.
+pub const COLLATERAL: u64 = 1000000;
+/// The total collateral locked in the contracts.
+pub const TOTAL_COLLATERAL: u64 = 2 * COLLATERAL;
+/// The fee rate of the contract transactions.
+pub const FEE_RATE: u64 = 2;
+/// The id of the event.
+pub const EVENT_ID: &str = "Test";
+/// The number of UTXOs held by the wallet of each party, enough to fund
+/// [`COLLATERAL`].
+const NB_UTXOS: u16 = 10;
+
+/// The contract sizes used by the benchmarks, from a few dozens to several
+/// thousands of CETs.
+pub const CONTRACT_SIZES: [ContractSize; 4] = [
+    ContractSize {
+        nb_digits: 8,
+        nb_oracles: 1,
+        threshold: 1,
+    },
+    ContractSize {
+        nb_digits: 12,
+        nb_oracles: 1,
+        threshold: 1,
+    },
+    ContractSize {
+        nb_digits: 16,
+        nb_oracles: 1,
+        threshold: 1,
+    },
+    ContractSize {
+        nb_digits: 12,
+        nb_oracles: 3,
+        threshold: 2,
+    },
+];
+
+/// The size of a contract, in terms of the number of digits of the outcome
+/// and of the number of oracles attesting to it.
+#[derive(Clone, Copy, Debug)]
+pub struct ContractSize {
+    /// The number of digits attested by each oracle.
+    pub nb_digits: usize,
+    /// The number of oracles.
+    pub nb_oracles: usize,
+    /// The number of oracles required to agree on the outcome.
+    pub threshold: usize,
+}
+
+impl ContractSize {
+    fn max_value(&self) -> u64 {
+        (BASE as u64).pow(self.nb_digits as u32) - 1
+    }
+}
+
+impl fmt::Display for ContractSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}_digits_{}_of_{}",
+            self.nb_digits, self.threshold, self.nb_oracles
+        )
+    }
+}
+
+fn constant_piece(from: u64, to: u64, payout: u64) -> PayoutFunctionPiece {
+    linear_piece(from, payout, to, payout)
+}
+
+fn linear_piece(from: u64, from_payout: u64, to: u64, to_payout: u64) -> PayoutFunctionPiece {
+    PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+        PolynomialPayoutCurvePiece::new(vec![
+            PayoutPoint {
+                event_outcome: from,
+                outcome_payout: from_payout,
+                extra_precision: 0,
+            },
+            PayoutPoint {
+                event_outcome: to,
+                outcome_payout: to_payout,
+                extra_precision: 0,
+            },
+        ])
+        .unwrap(),
+    )
+}
+
+/// Returns the descriptor of a contract of the given size.
+pub fn contract_descriptor(size: &ContractSize) -> ContractDescriptor {
+    let max_value = size.max_value();
+    let floor = max_value / 8 * 3;
+    let cap = max_value / 8 * 5;
+    ContractDescriptor::Numerical(NumericalDescriptor {
+        payout_function: PayoutFunction::new(vec![
+            constant_piece(0, floor, 0),
+            linear_piece(floor, 0, cap, TOTAL_COLLATERAL),
+            constant_piece(cap, max_value, TOTAL_COLLATERAL),
+        ])
+        .unwrap(),
+        rounding_intervals: RoundingIntervals {
+            intervals: vec![RoundingInterval {
+                begin_interval: 0,
+                rounding_mod: 1,
+            }],
+        },
+        difference_params: None,
+        oracle_numeric_infos: dlc_trie::OracleNumericInfo {
+            base: BASE,
+            nb_digits: vec![size.nb_digits; size.nb_oracles],
+        },
+        truncated_digits: Vec::new(),
+    })
+}
+
+fn get_schnorr_pubkey() -> XOnlyPublicKey {
+    XOnlyPublicKey::from_keypair(&KeyPair::new(SECP256K1, &mut thread_rng())).0
+}
+
+/// Returns announcements of random oracles for a contract of the given size.
+/// The announcements are not signed, contract establishment not checking
+/// their signatures.
+pub fn oracle_announcements(size: &ContractSize) -> Vec<OracleAnnouncement> {
+    (0..size.nb_oracles)
+        .map(|_| OracleAnnouncement {
+            announcement_signature: Signature::from_str("859833d34b9cbd7c0a898693a289af434c74ad1d65e15c67d1b1d3bf74d9ee85cbd5258da5e91815da9989185c8bc9b026ce6f6598c1b2fb127c1bb1a6bef74a").unwrap(),
+            oracle_public_key: get_schnorr_pubkey(),
+            oracle_event: OracleEvent {
+                oracle_nonces: (0..size.nb_digits).map(|_| get_schnorr_pubkey()).collect(),
+                event_maturity_epoch: 1234567,
+                event_descriptor: EventDescriptor::DigitDecompositionEvent(
+                    DigitDecompositionEventDescriptor {
+                        base: BASE as u16,
+                        is_signed: false,
+                        unit: "sats/sec".to_owned(),
+                        precision: 0,
+                        nb_digits: size.nb_digits as u16,
+                    },
+                ),
+                event_id: EVENT_ID.to_string(),
+            },
+        })
+        .collect()
+}
+
+/// Returns the information of a contract of the given size.
+pub fn contract_info(size: &ContractSize) -> ContractInfo {
+    ContractInfo {
+        contract_descriptor: contract_descriptor(size),
+        oracle_announcements: oracle_announcements(size),
+        threshold: size.threshold,
+    }
+}
+
+/// Returns the input from which to offer a contract of the given size using
+/// the given announcements.
+pub fn contract_input(size: &ContractSize, announcements: &[OracleAnnouncement]) -> ContractInput {
+    ContractInput {
+        offer_collateral: COLLATERAL,
+        accept_collateral: COLLATERAL,
+        fee_rate: FEE_RATE,
+        contract_infos: vec![ContractInputInfo {
+            contract_descriptor: contract_descriptor(size),
+            oracles: OracleInput {
+                public_keys: announcements.iter().map(|x| x.oracle_public_key).collect(),
+                event_id: EVENT_ID.to_string(),
+                event_ids: Vec::new(),
+                threshold: size.threshold as u16,
+            },
+        }],
+        metadata: None,
+        payout_script_pubkey: None,
+        change_script_pubkey: None,
+        additional_payout_outputs: Vec::new(),
+    }
+}
+
+/// The secret key of the offering party when creating transactions directly.
+pub fn offer_secret_key() -> SecretKey {
+    "c3b1634c6a13019f372722db0ec0435df11fb2dd6b0b5c647503ef6b5e4656ec"
+        .parse()
+        .unwrap()
+}
+
+/// The secret key of the accepting party when creating transactions directly.
+pub fn accept_secret_key() -> SecretKey {
+    "c0296e3059b34c9707f05dc54ec008de90c0ce52841ff54b98e51487de031e6d"
+        .parse()
+        .unwrap()
+}
+
+fn get_party_params(secret_key: &SecretKey, serial_id: u64) -> PartyParams {
+    let fund_pubkey = PublicKey::from_secret_key(SECP256K1, secret_key);
+    let script_pubkey = Script::new_v0_p2wpkh(&WPubkeyHash::hash(&fund_pubkey.serialize()));
+    PartyParams {
+        fund_pubkey,
+        change_script_pubkey: script_pubkey.clone(),
+        change_serial_id: serial_id,
+        payout_script_pubkey: script_pubkey,
+        payout_serial_id: serial_id,
+        inputs: vec![TxInputInfo {
+            outpoint: OutPoint::default(),
+            redeem_script: Script::new(),
+            max_witness_len: 108,
+            serial_id,
+        }],
+        input_amount: 3 * COLLATERAL,
+        collateral: COLLATERAL,
+        additional_payout_outputs: Vec::new(),
+    }
+}
+
+/// Creates the transactions of a contract with the given payouts between the
+/// parties owning [`offer_secret_key`] and [`accept_secret_key`].
+pub fn dlc_transactions(payouts: &[Payout]) -> DlcTransactions {
+    create_dlc_transactions(
+        &get_party_params(&offer_secret_key(), 1),
+        &get_party_params(&accept_secret_key(), 2),
+        payouts,
+        1000,
+        FEE_RATE,
+        0,
+        1000,
+        3,
+    )
+    .unwrap()
+}
+
+/// A party to a contract, backed by the mocks. The wallets of both parties
+/// hold the same UTXOs and do not sign funding inputs, which does not matter
+/// as the fund transaction is never broadcast.
+pub struct Party {
+    /// The wallet funding the contract and signing its transactions.
+    pub wallet: Rc<MockWallet>,
+    /// The node id of the party.
+    pub node_id: PublicKey,
+}
+
+impl Party {
+    /// Creates a party whose wallet funding transactions are sent to the
+    /// given blockchain.
+    pub fn new(blockchain: &Rc<MockBlockchain>) -> Self {
+        Party {
+            wallet: Rc::new(MockWallet::new(blockchain, NB_UTXOS)),
+            node_id: PublicKey::from_secret_key(SECP256K1, &SecretKey::new(&mut thread_rng())),
+        }
+    }
+}
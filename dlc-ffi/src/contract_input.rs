@@ -0,0 +1,297 @@
+//! Builders producing the JSON encoded [`ContractInput`] expected by
+//! [`crate::DlcManager::send_offer`] from flat parameters, so that
+//! applications do not need to replicate the contract descriptor format.
+
+use std::str::FromStr;
+
+use dlc::{EnumerationPayout, Payout};
+use dlc_manager::contract::contract_input::{ContractInput, ContractInputInfo, OracleInput};
+use dlc_manager::contract::enum_descriptor::EnumDescriptor;
+use dlc_manager::contract::numerical_descriptor::NumericalDescriptor;
+use dlc_manager::contract::ContractDescriptor;
+use dlc_manager::payout_curve::builder::{OptionType, PayoutCurveBuilder};
+use dlc_trie::OracleNumericInfo;
+use secp256k1_zkp::XOnlyPublicKey;
+
+use crate::DlcFfiError;
+
+type Result<T> = core::result::Result<T, DlcFfiError>;
+
+/// The oracles attesting to the outcome of a contract.
+#[derive(Clone, Debug)]
+pub struct OracleParams {
+    /// The hex encoded public keys of the oracles.
+    pub public_keys: Vec<String>,
+    /// The id of the event used by all the oracles.
+    pub event_id: String,
+    /// The number of oracles required to close the contract.
+    pub threshold: u16,
+}
+
+/// A point of a piecewise linear payout curve.
+#[derive(Clone, Debug)]
+pub struct CurvePoint {
+    /// The outcome at which the point is located.
+    pub outcome: u64,
+    /// The payout of the offer party for the outcome.
+    pub payout: u64,
+}
+
+/// The direction of an option, see [`OptionType`].
+#[derive(Clone, Copy, Debug)]
+pub enum OptionKind {
+    /// Pays out when the outcome is above the strike.
+    Call,
+    /// Pays out when the outcome is below the strike.
+    Put,
+}
+
+impl From<OptionKind> for OptionType {
+    fn from(option_kind: OptionKind) -> Self {
+        match option_kind {
+            OptionKind::Call => OptionType::Call,
+            OptionKind::Put => OptionType::Put,
+        }
+    }
+}
+
+/// The payout curve of a numerical contract, built with the corresponding
+/// method of [`PayoutCurveBuilder`].
+#[derive(Clone, Debug)]
+pub enum PayoutCurveParams {
+    /// See [`PayoutCurveBuilder::covered_call`].
+    CoveredCall {
+        /// The strike of the call.
+        strike: u64,
+    },
+    /// See [`PayoutCurveBuilder::put`].
+    Put {
+        /// The strike of the put.
+        strike: u64,
+        /// The payout per unit of outcome below the strike.
+        notional: u64,
+    },
+    /// See [`PayoutCurveBuilder::vertical_spread`].
+    VerticalSpread {
+        /// Whether this is a call or a put spread.
+        option_kind: OptionKind,
+        /// The lower strike of the spread.
+        lower_strike: u64,
+        /// The upper strike of the spread.
+        upper_strike: u64,
+    },
+    /// See [`PayoutCurveBuilder::capped_linear_cfd`], the offer collateral of
+    /// the contract being used.
+    CappedLinearCfd {
+        /// The outcome at which the offer party gets back its collateral.
+        entry_outcome: u64,
+        /// The gain per unit of outcome above the entry.
+        notional: u64,
+    },
+    /// See [`PayoutCurveBuilder::binary_option`].
+    BinaryOption {
+        /// Whether the option pays out above or below the strike.
+        option_kind: OptionKind,
+        /// The strike of the option.
+        strike: u64,
+    },
+    /// See [`PayoutCurveBuilder::inverse_future`].
+    InverseFuture {
+        /// The price at which the position is entered.
+        entry_price: u64,
+        /// The value of the position in satoshis at the entry price.
+        notional: u64,
+        /// The leverage of the offer party.
+        leverage: u64,
+    },
+    /// See [`PayoutCurveBuilder::linear`].
+    Linear {
+        /// The points of the curve, from outcome zero to the maximum outcome.
+        points: Vec<CurvePoint>,
+    },
+}
+
+/// Parameters of a contract over a numerical outcome, attested in base 2.
+#[derive(Clone, Debug)]
+pub struct NumericalContractParams {
+    /// The collateral of the offer party.
+    pub offer_collateral: u64,
+    /// The collateral of the accept party.
+    pub accept_collateral: u64,
+    /// The fee rate in satoshis per virtual byte, zero to use the estimated
+    /// one.
+    pub fee_rate: u64,
+    /// The oracles attesting to the outcome.
+    pub oracle: OracleParams,
+    /// The number of binary digits of the attested outcome.
+    pub nb_digits: u16,
+    /// The rounding modulus applied to the payouts, 1 for no rounding.
+    pub rounding_mod: u64,
+    /// The payout curve of the contract.
+    pub payout_curve: PayoutCurveParams,
+}
+
+/// The payouts of the parties for an outcome of an enumerated contract.
+#[derive(Clone, Debug)]
+pub struct EnumOutcomePayout {
+    /// The outcome as attested by the oracles.
+    pub outcome: String,
+    /// The payout of the offer party.
+    pub offer_payout: u64,
+    /// The payout of the accept party.
+    pub accept_payout: u64,
+}
+
+/// Parameters of a contract over an enumerated outcome.
+#[derive(Clone, Debug)]
+pub struct EnumContractParams {
+    /// The collateral of the offer party.
+    pub offer_collateral: u64,
+    /// The collateral of the accept party.
+    pub accept_collateral: u64,
+    /// The fee rate in satoshis per virtual byte, zero to use the estimated
+    /// one.
+    pub fee_rate: u64,
+    /// The oracles attesting to the outcome.
+    pub oracle: OracleParams,
+    /// The payouts for each possible outcome.
+    pub outcome_payouts: Vec<EnumOutcomePayout>,
+}
+
+fn get_oracle_input(oracle: OracleParams) -> Result<OracleInput> {
+    let public_keys = oracle
+        .public_keys
+        .iter()
+        .map(|x| XOnlyPublicKey::from_str(x).map_err(DlcFfiError::invalid_parameters))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(OracleInput {
+        public_keys,
+        event_id: oracle.event_id,
+        event_ids: Vec::new(),
+        threshold: oracle.threshold,
+    })
+}
+
+fn to_json(
+    offer_collateral: u64,
+    accept_collateral: u64,
+    fee_rate: u64,
+    contract_info: ContractInputInfo,
+) -> Result<String> {
+    let contract_input = ContractInput {
+        offer_collateral,
+        accept_collateral,
+        fee_rate,
+        contract_infos: vec![contract_info],
+        metadata: None,
+        payout_script_pubkey: None,
+        change_script_pubkey: None,
+        additional_payout_outputs: Vec::new(),
+    };
+    contract_input.validate()?;
+    serde_json::to_string(&contract_input).map_err(|e| DlcFfiError::Other {
+        message: e.to_string(),
+    })
+}
+
+/// Builds the JSON encoded input of a numerical contract.
+pub fn build_numerical_contract_input(params: NumericalContractParams) -> Result<String> {
+    if params.nb_digits == 0 || params.nb_digits > 63 {
+        return Err(DlcFfiError::invalid_parameters(
+            "Number of digits must be between 1 and 63",
+        ));
+    }
+    let total_collateral = params
+        .offer_collateral
+        .checked_add(params.accept_collateral)
+        .ok_or_else(|| DlcFfiError::invalid_parameters("Total collateral overflows"))?;
+    let max_outcome = (1u64 << params.nb_digits) - 1;
+    let builder =
+        PayoutCurveBuilder::new(max_outcome, total_collateral).rounding_mod(params.rounding_mod);
+    let curve = match params.payout_curve {
+        PayoutCurveParams::CoveredCall { strike } => builder.covered_call(strike),
+        PayoutCurveParams::Put { strike, notional } => builder.put(strike, notional),
+        PayoutCurveParams::VerticalSpread {
+            option_kind,
+            lower_strike,
+            upper_strike,
+        } => builder.vertical_spread(option_kind.into(), lower_strike, upper_strike),
+        PayoutCurveParams::CappedLinearCfd {
+            entry_outcome,
+            notional,
+        } => builder.capped_linear_cfd(entry_outcome, params.offer_collateral, notional),
+        PayoutCurveParams::BinaryOption {
+            option_kind,
+            strike,
+        } => builder.binary_option(option_kind.into(), strike),
+        PayoutCurveParams::InverseFuture {
+            entry_price,
+            notional,
+            leverage,
+        } => builder.inverse_future(entry_price, notional, leverage, None),
+        PayoutCurveParams::Linear { points } => builder.linear(
+            &points
+                .iter()
+                .map(|x| (x.outcome, x.payout))
+                .collect::<Vec<_>>(),
+        ),
+    }?;
+
+    let nb_oracles = params.oracle.public_keys.len();
+    let contract_descriptor = ContractDescriptor::Numerical(NumericalDescriptor {
+        payout_function: curve.payout_function,
+        rounding_intervals: curve.rounding_intervals,
+        difference_params: None,
+        oracle_numeric_infos: OracleNumericInfo {
+            base: 2,
+            nb_digits: vec![params.nb_digits as usize; nb_oracles],
+        },
+        truncated_digits: Vec::new(),
+    });
+    to_json(
+        params.offer_collateral,
+        params.accept_collateral,
+        params.fee_rate,
+        ContractInputInfo {
+            contract_descriptor,
+            oracles: get_oracle_input(params.oracle)?,
+        },
+    )
+}
+
+/// Builds the JSON encoded input of an enumerated contract.
+pub fn build_enum_contract_input(params: EnumContractParams) -> Result<String> {
+    let total_collateral = params
+        .offer_collateral
+        .checked_add(params.accept_collateral)
+        .ok_or_else(|| DlcFfiError::invalid_parameters("Total collateral overflows"))?;
+    if params
+        .outcome_payouts
+        .iter()
+        .any(|x| x.offer_payout.checked_add(x.accept_payout) != Some(total_collateral))
+    {
+        return Err(DlcFfiError::invalid_parameters(
+            "Outcome payouts must add up to the total collateral",
+        ));
+    }
+    let outcome_payouts = params
+        .outcome_payouts
+        .into_iter()
+        .map(|x| EnumerationPayout {
+            outcome: x.outcome,
+            payout: Payout {
+                offer: x.offer_payout,
+                accept: x.accept_payout,
+            },
+        })
+        .collect();
+    to_json(
+        params.offer_collateral,
+        params.accept_collateral,
+        params.fee_rate,
+        ContractInputInfo {
+            contract_descriptor: ContractDescriptor::Enum(EnumDescriptor { outcome_payouts }),
+            oracles: get_oracle_input(params.oracle)?,
+        },
+    )
+}
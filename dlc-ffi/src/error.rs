@@ -0,0 +1,96 @@
+//! Errors surfaced to, and raised by, the foreign language code.
+
+use std::fmt;
+
+use dlc_manager::error::Error;
+
+/// An error returned by the bindings, or thrown by the callback interfaces
+/// implemented by the application.
+#[derive(Debug)]
+pub enum DlcFfiError {
+    /// Some invalid parameters were provided.
+    InvalidParameters {
+        /// A description of the error.
+        message: String,
+    },
+    /// The operation is not possible in the current state of the contract.
+    InvalidState {
+        /// A description of the error.
+        message: String,
+    },
+    /// An error occurred in the wallet provided by the application.
+    Wallet {
+        /// A description of the error.
+        message: String,
+    },
+    /// An error occurred in the blockchain provided by the application.
+    Blockchain {
+        /// A description of the error.
+        message: String,
+    },
+    /// An error occurred in the oracle provided by the application.
+    Oracle {
+        /// A description of the error.
+        message: String,
+    },
+    /// An error occurred while accessing the contract database.
+    Storage {
+        /// A description of the error.
+        message: String,
+    },
+    /// Any other error, e.g. a protocol error or an unexpected exception
+    /// thrown by a callback.
+    Other {
+        /// A description of the error.
+        message: String,
+    },
+}
+
+impl DlcFfiError {
+    pub(crate) fn invalid_parameters<E: fmt::Display>(e: E) -> Self {
+        DlcFfiError::InvalidParameters {
+            message: e.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DlcFfiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DlcFfiError::InvalidParameters { message } => {
+                write!(f, "Invalid parameters: {}", message)
+            }
+            DlcFfiError::InvalidState { message } => write!(f, "Invalid state: {}", message),
+            DlcFfiError::Wallet { message } => write!(f, "Wallet error: {}", message),
+            DlcFfiError::Blockchain { message } => write!(f, "Blockchain error: {}", message),
+            DlcFfiError::Oracle { message } => write!(f, "Oracle error: {}", message),
+            DlcFfiError::Storage { message } => write!(f, "Storage error: {}", message),
+            DlcFfiError::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for DlcFfiError {}
+
+impl From<Error> for DlcFfiError {
+    fn from(e: Error) -> Self {
+        let message = e.to_string();
+        match e {
+            Error::InvalidParameters(_) => DlcFfiError::InvalidParameters { message },
+            Error::InvalidState(_) => DlcFfiError::InvalidState { message },
+            Error::WalletError(_) => DlcFfiError::Wallet { message },
+            Error::BlockchainError(_) | Error::ServiceUnavailable(_) => {
+                DlcFfiError::Blockchain { message }
+            }
+            Error::OracleError(_) | Error::UntrustedOracles(_) => DlcFfiError::Oracle { message },
+            Error::StorageError(_) => DlcFfiError::Storage { message },
+            _ => DlcFfiError::Other { message },
+        }
+    }
+}
+
+impl From<uniffi::UnexpectedUniFFICallbackError> for DlcFfiError {
+    fn from(e: uniffi::UnexpectedUniFFICallbackError) -> Self {
+        DlcFfiError::Other { message: e.reason }
+    }
+}
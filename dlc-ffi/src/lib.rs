@@ -0,0 +1,375 @@
+//! # dlc-ffi
+//! Kotlin and Swift bindings for the dlc-manager, generated with
+//! [uniffi](https://mozilla.github.io/uniffi-rs/) from `src/dlc_ffi.udl`.
+//! The wallet, blockchain and oracles are implemented by the application
+//! through callback interfaces, while contracts are persisted in a SQLite
+//! database and their keys derived from a seed.
+//!
+//! Messages to and from peers are exchanged as byte arrays in their wire
+//! format (type prefixed), contract ids as hex strings and public keys in
+//! their hex encoding.
+
+#![deny(missing_docs)]
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::util::bip32::ExtendedPrivKey;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::Network;
+use dlc_manager::contract::offered_contract::OfferedContract;
+use dlc_manager::contract::{Contract, ContractState};
+use dlc_manager::key_derivation::DerivedKeysSigner;
+use dlc_manager::manager::Manager;
+use dlc_manager::{ContractId, Storage, SystemTimeProvider};
+use dlc_messages::Message;
+use dlc_sqlite_storage_provider::SqliteStorageProvider;
+use secp256k1_zkp::{PublicKey, XOnlyPublicKey};
+
+mod contract_input;
+mod error;
+mod providers;
+
+pub use contract_input::{
+    build_enum_contract_input, build_numerical_contract_input, CurvePoint, EnumContractParams,
+    EnumOutcomePayout, NumericalContractParams, OptionKind, OracleParams, PayoutCurveParams,
+};
+pub use error::DlcFfiError;
+pub use providers::{BlockchainProvider, FfiUtxo, OracleProvider, WalletProvider};
+
+use providers::{FfiBlockchain, FfiOracle, FfiWallet};
+
+uniffi::include_scaffolding!("dlc_ffi");
+
+type Result<T> = core::result::Result<T, DlcFfiError>;
+
+type FfiManager = Manager<
+    Arc<FfiWallet>,
+    Arc<FfiBlockchain>,
+    Box<SqliteStorageProvider>,
+    Arc<FfiOracle>,
+    Arc<SystemTimeProvider>,
+    Arc<FfiBlockchain>,
+    Arc<DerivedKeysSigner>,
+>;
+
+/// The state of a contract, see [`ContractState`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContractStatus {
+    /// See [`Contract::Offered`].
+    Offered,
+    /// See [`Contract::Accepted`].
+    Accepted,
+    /// See [`Contract::Signed`].
+    Signed,
+    /// See [`Contract::Confirmed`].
+    Confirmed,
+    /// See [`Contract::PreClosed`].
+    PreClosed,
+    /// See [`Contract::Closed`].
+    Closed,
+    /// See [`Contract::Refunded`].
+    Refunded,
+    /// See [`Contract::FailedAccept`].
+    FailedAccept,
+    /// See [`Contract::FailedSign`].
+    FailedSign,
+    /// See [`Contract::Rejected`].
+    Rejected,
+}
+
+impl From<ContractState> for ContractStatus {
+    fn from(state: ContractState) -> Self {
+        match state {
+            ContractState::Offered => ContractStatus::Offered,
+            ContractState::Accepted => ContractStatus::Accepted,
+            ContractState::Signed => ContractStatus::Signed,
+            ContractState::Confirmed => ContractStatus::Confirmed,
+            ContractState::PreClosed => ContractStatus::PreClosed,
+            ContractState::Closed => ContractStatus::Closed,
+            ContractState::Refunded => ContractStatus::Refunded,
+            ContractState::FailedAccept => ContractStatus::FailedAccept,
+            ContractState::FailedSign => ContractStatus::FailedSign,
+            ContractState::Rejected => ContractStatus::Rejected,
+        }
+    }
+}
+
+/// Information about a contract for display by the application.
+#[derive(Clone, Debug)]
+pub struct ContractSummary {
+    /// The id of the contract, equal to its temporary id until it is
+    /// accepted.
+    pub id: String,
+    /// The temporary id of the contract.
+    pub temporary_id: String,
+    /// The state of the contract.
+    pub status: ContractStatus,
+    /// The public key of the counter party.
+    pub counter_party: String,
+    /// The ids of the events the contract relies on. Empty once the contract
+    /// is closed.
+    pub event_ids: Vec<String>,
+    /// Whether the local party offered the contract, unknown once the
+    /// contract is closed.
+    pub is_offer_party: Option<bool>,
+    /// The collateral of the offer party, unknown once the contract is
+    /// closed.
+    pub offer_collateral: Option<u64>,
+    /// The total collateral locked in the contract, unknown once the
+    /// contract is closed.
+    pub total_collateral: Option<u64>,
+    /// The profit and loss of the local party, only set once the contract is
+    /// closed.
+    pub pnl: Option<i64>,
+}
+
+fn get_offered_contract(contract: &Contract) -> Option<&OfferedContract> {
+    match contract {
+        Contract::Offered(c) | Contract::Rejected(c) => Some(c),
+        Contract::Accepted(c) => Some(&c.offered_contract),
+        Contract::Signed(c) | Contract::Confirmed(c) | Contract::Refunded(c) => {
+            Some(&c.accepted_contract.offered_contract)
+        }
+        Contract::PreClosed(c) => Some(&c.signed_contract.accepted_contract.offered_contract),
+        Contract::FailedAccept(c) => Some(&c.offered_contract),
+        Contract::FailedSign(c) => Some(&c.accepted_contract.offered_contract),
+        Contract::Closed(_) => None,
+    }
+}
+
+impl From<&Contract> for ContractSummary {
+    fn from(contract: &Contract) -> Self {
+        let offered_contract = get_offered_contract(contract);
+        ContractSummary {
+            id: contract.get_id().to_hex(),
+            temporary_id: contract.get_temporary_id().to_hex(),
+            status: contract.get_state().into(),
+            counter_party: contract.get_counter_party_id().to_string(),
+            event_ids: offered_contract
+                .map(|c| {
+                    c.contract_info
+                        .iter()
+                        .flat_map(|x| x.oracle_announcements.iter())
+                        .map(|x| x.oracle_event.event_id.clone())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            is_offer_party: offered_contract.map(|c| c.is_offer_party),
+            offer_collateral: offered_contract.map(|c| c.offer_params.collateral),
+            total_collateral: offered_contract.map(|c| c.total_collateral),
+            pnl: match contract {
+                Contract::Closed(c) => Some(c.pnl),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// A message to send to a peer.
+#[derive(Clone, Debug)]
+pub struct OutboundMessage {
+    /// The public key of the peer.
+    pub counter_party: String,
+    /// The type prefixed serialization of the message.
+    pub message: Vec<u8>,
+}
+
+impl OutboundMessage {
+    fn new(message: Message, counter_party: PublicKey) -> Self {
+        OutboundMessage {
+            counter_party: counter_party.to_string(),
+            message: message.encode_with_type(),
+        }
+    }
+}
+
+fn parse_contract_id(contract_id: &str) -> Result<ContractId> {
+    let bytes = Vec::<u8>::from_hex(contract_id).map_err(DlcFfiError::invalid_parameters)?;
+    bytes
+        .try_into()
+        .map_err(|_| DlcFfiError::invalid_parameters("Contract id must be 32 bytes long"))
+}
+
+fn parse_network(network: &str) -> Result<Network> {
+    match network {
+        "bitcoin" => Ok(Network::Bitcoin),
+        "testnet" => Ok(Network::Testnet),
+        "signet" => Ok(Network::Signet),
+        "regtest" => Ok(Network::Regtest),
+        _ => Err(DlcFfiError::invalid_parameters(format!(
+            "Unknown network {}",
+            network
+        ))),
+    }
+}
+
+fn parse_public_key(public_key: &str) -> Result<PublicKey> {
+    PublicKey::from_str(public_key).map_err(DlcFfiError::invalid_parameters)
+}
+
+/// Manages the contracts of the application, wrapping a dlc-manager
+/// [`Manager`].
+pub struct DlcManager {
+    manager: Mutex<FfiManager>,
+}
+
+impl DlcManager {
+    /// Creates a manager for the given network (`bitcoin`, `testnet`,
+    /// `signet` or `regtest`), storing contracts in the SQLite database at
+    /// `db_path` and deriving their keys from `seed`. The same seed must be
+    /// provided on each start for the keys of existing contracts to be
+    /// recovered.
+    pub fn new(
+        seed: Vec<u8>,
+        network: String,
+        db_path: String,
+        wallet: Box<dyn WalletProvider>,
+        blockchain: Box<dyn BlockchainProvider>,
+        oracle: Box<dyn OracleProvider>,
+        oracle_public_keys: Vec<String>,
+    ) -> Result<Self> {
+        let network = parse_network(&network)?;
+        let xpriv =
+            ExtendedPrivKey::new_master(network, &seed).map_err(DlcFfiError::invalid_parameters)?;
+        let store = Box::new(SqliteStorageProvider::new(&db_path)?);
+        let signer = Arc::new(DerivedKeysSigner::new(xpriv));
+        signer.recover_keys_from_storage(&store)?;
+
+        let oracle: Arc<dyn OracleProvider> = Arc::from(oracle);
+        let oracles = oracle_public_keys
+            .iter()
+            .map(|x| {
+                let public_key =
+                    XOnlyPublicKey::from_str(x).map_err(DlcFfiError::invalid_parameters)?;
+                Ok((
+                    public_key,
+                    Arc::new(FfiOracle::new(public_key, oracle.clone())),
+                ))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        let blockchain = Arc::new(FfiBlockchain::new(blockchain, network));
+
+        let manager = Manager::new_with_signer(
+            Arc::new(FfiWallet::new(wallet, network)),
+            signer,
+            blockchain.clone(),
+            store,
+            oracles,
+            Arc::new(SystemTimeProvider {}),
+            blockchain,
+        )?;
+        Ok(DlcManager {
+            manager: Mutex::new(manager),
+        })
+    }
+
+    /// Creates an offer for the given JSON encoded
+    /// [`dlc_manager::contract::contract_input::ContractInput`], as returned
+    /// by the contract input builders, and returns the message to send to
+    /// the counter party.
+    pub fn send_offer(&self, contract_input: String, counter_party: String) -> Result<Vec<u8>> {
+        let contract_input =
+            serde_json::from_str(&contract_input).map_err(DlcFfiError::invalid_parameters)?;
+        let offer = self
+            .manager
+            .lock()
+            .unwrap()
+            .send_offer(&contract_input, parse_public_key(&counter_party)?)?;
+        Ok(Message::Offer(offer).encode_with_type())
+    }
+
+    /// Accepts the offered contract with the given id.
+    pub fn accept_contract_offer(&self, contract_id: String) -> Result<OutboundMessage> {
+        let (_, counter_party, accept) = self
+            .manager
+            .lock()
+            .unwrap()
+            .accept_contract_offer(&parse_contract_id(&contract_id)?)?;
+        Ok(OutboundMessage::new(Message::Accept(accept), counter_party))
+    }
+
+    /// Processes a message received from the given peer, returning the reply
+    /// to send back if any.
+    pub fn on_dlc_message(
+        &self,
+        message: Vec<u8>,
+        counter_party: String,
+    ) -> Result<Option<OutboundMessage>> {
+        let counter_party = parse_public_key(&counter_party)?;
+        let message = Message::read_with_type(&mut Cursor::new(&message))
+            .map_err(DlcFfiError::invalid_parameters)?;
+        Ok(self
+            .manager
+            .lock()
+            .unwrap()
+            .on_dlc_message(&message, counter_party)?
+            .map(|x| OutboundMessage::new(x, counter_party)))
+    }
+
+    /// Returns the serialized PSBT in which the application wallet must sign
+    /// its funding inputs.
+    pub fn get_funding_psbt(&self, contract_id: String) -> Result<Vec<u8>> {
+        let psbt = self
+            .manager
+            .lock()
+            .unwrap()
+            .get_funding_psbt(&parse_contract_id(&contract_id)?)?;
+        Ok(serialize(&psbt))
+    }
+
+    /// Processes the funding PSBT signed by the application wallet, returning
+    /// the sign message to send to the counter party when the local party
+    /// offered the contract.
+    pub fn on_signed_funding_psbt(
+        &self,
+        contract_id: String,
+        psbt: Vec<u8>,
+    ) -> Result<Option<OutboundMessage>> {
+        let psbt: PartiallySignedTransaction =
+            deserialize(&psbt).map_err(DlcFfiError::invalid_parameters)?;
+        Ok(self
+            .manager
+            .lock()
+            .unwrap()
+            .on_signed_funding_psbt(&parse_contract_id(&contract_id)?, &psbt)?
+            .map(|(sign, counter_party)| OutboundMessage::new(Message::Sign(sign), counter_party)))
+    }
+
+    /// Checks the state of the contracts against the blockchain and oracles,
+    /// closing those for which attestations are available and refunding the
+    /// expired ones. Should be called regularly, e.g. on each new block.
+    pub fn periodic_check(&self) -> Result<()> {
+        Ok(self.manager.lock().unwrap().periodic_check()?)
+    }
+
+    /// Returns a summary of all the contracts.
+    pub fn get_contracts(&self) -> Result<Vec<ContractSummary>> {
+        Ok(self
+            .manager
+            .lock()
+            .unwrap()
+            .get_store()
+            .get_contracts()?
+            .iter()
+            .map(ContractSummary::from)
+            .collect())
+    }
+
+    /// Returns a summary of the contract with the given id if it exists.
+    pub fn get_contract(&self, contract_id: String) -> Result<Option<ContractSummary>> {
+        Ok(self
+            .manager
+            .lock()
+            .unwrap()
+            .get_store()
+            .get_contract(&parse_contract_id(&contract_id)?)?
+            .as_ref()
+            .map(ContractSummary::from))
+    }
+}
@@ -0,0 +1,340 @@
+//! Callback interfaces implemented by the application, and their adaptation to
+//! the interfaces required by the dlc-manager.
+
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::{Address, Block, Network, OutPoint, Script, Transaction, TxOut, Txid};
+use dlc_manager::error::Error;
+use dlc_manager::utxo_reservation::UtxoReservations;
+use dlc_manager::{Blockchain, ContractId, Oracle, Utxo, Wallet};
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+use lightning::util::ser::Readable;
+use rust_bitcoin_coin_selection::select_coins;
+use secp256k1_zkp::XOnlyPublicKey;
+
+use crate::DlcFfiError;
+
+type Result<T> = core::result::Result<T, Error>;
+
+const MIN_FEERATE: u32 = 253;
+
+/// An unspent output of the application wallet.
+#[derive(Clone, Debug)]
+pub struct FfiUtxo {
+    /// The id of the transaction containing the output.
+    pub txid: String,
+    /// The index of the output in the transaction.
+    pub vout: u32,
+    /// The value of the output in satoshis.
+    pub value: u64,
+    /// The address the output pays to.
+    pub address: String,
+}
+
+/// Wallet of the application, providing addresses and funds for contracts.
+/// The funding inputs are signed by the application through
+/// [`crate::DlcManager::get_funding_psbt`].
+pub trait WalletProvider: Send + Sync {
+    /// Returns a new address of the wallet.
+    fn get_new_address(&self) -> core::result::Result<String, DlcFfiError>;
+    /// Returns the confirmed unspent outputs of the wallet.
+    fn list_utxos(&self) -> core::result::Result<Vec<FfiUtxo>, DlcFfiError>;
+}
+
+/// Access to the blockchain. Blocks and transactions are exchanged in their
+/// consensus serialization.
+pub trait BlockchainProvider: Send + Sync {
+    /// Broadcasts the given transaction.
+    fn send_transaction(&self, transaction: Vec<u8>) -> core::result::Result<(), DlcFfiError>;
+    /// Returns the height of the blockchain tip.
+    fn get_blockchain_height(&self) -> core::result::Result<u64, DlcFfiError>;
+    /// Returns the block at the given height.
+    fn get_block_at_height(&self, height: u64) -> core::result::Result<Vec<u8>, DlcFfiError>;
+    /// Returns the transaction with the given id.
+    fn get_transaction(&self, txid: String) -> core::result::Result<Vec<u8>, DlcFfiError>;
+    /// Returns the number of confirmations of the transaction with the given
+    /// id, zero if it is unconfirmed.
+    fn get_transaction_confirmations(&self, txid: String)
+        -> core::result::Result<u32, DlcFfiError>;
+    /// Returns the estimated fee rate in satoshis per virtual byte for a
+    /// confirmation within the given number of blocks.
+    fn get_fee_rate(&self, confirmation_target: u32) -> core::result::Result<u64, DlcFfiError>;
+    /// Returns the unconfirmed transaction spending the given output if any.
+    fn get_mempool_spend(
+        &self,
+        txid: String,
+        vout: u32,
+    ) -> core::result::Result<Option<Vec<u8>>, DlcFfiError>;
+}
+
+/// Source of oracle announcements and attestations, serialized in the format
+/// of the DLC specifications.
+pub trait OracleProvider: Send + Sync {
+    /// Returns the announcement of the given event by the given oracle.
+    fn get_announcement(
+        &self,
+        oracle_public_key: String,
+        event_id: String,
+    ) -> core::result::Result<Vec<u8>, DlcFfiError>;
+    /// Returns the attestation of the given event by the given oracle.
+    fn get_attestation(
+        &self,
+        oracle_public_key: String,
+        event_id: String,
+    ) -> core::result::Result<Vec<u8>, DlcFfiError>;
+}
+
+pub(crate) struct FfiWallet {
+    provider: Box<dyn WalletProvider>,
+    network: Network,
+    locked_utxos: Mutex<HashSet<OutPoint>>,
+    reservations: UtxoReservations,
+}
+
+impl FfiWallet {
+    pub(crate) fn new(provider: Box<dyn WalletProvider>, network: Network) -> Self {
+        FfiWallet {
+            provider,
+            network,
+            locked_utxos: Mutex::new(HashSet::new()),
+            reservations: UtxoReservations::new(),
+        }
+    }
+
+    fn parse_address(&self, address: &str) -> Result<Address> {
+        let address = Address::from_str(address).map_err(|e| Error::WalletError(Box::new(e)))?;
+        if !address.is_valid_for_network(self.network) {
+            return Err(Error::InvalidState(format!(
+                "Address {} is not valid for {}",
+                address, self.network
+            )));
+        }
+        Ok(address)
+    }
+}
+
+impl Wallet for FfiWallet {
+    fn get_new_address(&self) -> Result<Address> {
+        let address = self
+            .provider
+            .get_new_address()
+            .map_err(|e| Error::WalletError(Box::new(e)))?;
+        self.parse_address(&address)
+    }
+
+    fn get_utxos_for_amount(
+        &self,
+        amount: u64,
+        _fee_rate: Option<u64>,
+        lock_utxos: bool,
+    ) -> Result<Vec<Utxo>> {
+        let utxos = self
+            .provider
+            .list_utxos()
+            .map_err(|e| Error::WalletError(Box::new(e)))?
+            .into_iter()
+            .map(|x| {
+                let address = self.parse_address(&x.address)?;
+                let txid = Txid::from_str(&x.txid).map_err(|e| Error::WalletError(Box::new(e)))?;
+                Ok(Utxo {
+                    tx_out: TxOut {
+                        value: x.value,
+                        script_pubkey: address.script_pubkey(),
+                    },
+                    outpoint: OutPoint::new(txid, x.vout),
+                    address,
+                    redeem_script: Script::new(),
+                    reserved: false,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut locked_utxos = self.locked_utxos.lock().unwrap();
+        // Outputs that are no longer listed were spent and can be forgotten.
+        let unspent = utxos.iter().map(|x| x.outpoint).collect::<HashSet<_>>();
+        locked_utxos.retain(|x| unspent.contains(x));
+
+        let mut utxo_pool = utxos
+            .into_iter()
+            .filter(|x| {
+                !locked_utxos.contains(&x.outpoint) && !self.reservations.is_reserved(&x.outpoint)
+            })
+            .map(UtxoWrap)
+            .collect::<Vec<_>>();
+        let selection = select_coins(amount, 20, &mut utxo_pool)
+            .ok_or_else(|| Error::InvalidState("Not enough fund in utxos".to_string()))?;
+
+        if lock_utxos {
+            locked_utxos.extend(selection.iter().map(|x| x.0.outpoint));
+        }
+
+        Ok(selection
+            .into_iter()
+            .map(|x| Utxo {
+                reserved: lock_utxos,
+                ..x.0
+            })
+            .collect())
+    }
+
+    /// Watching addresses is left to the application wallet.
+    fn import_address(&self, _: &Address) -> Result<()> {
+        Ok(())
+    }
+
+    fn reserve_utxos(
+        &self,
+        temporary_id: &ContractId,
+        utxos: &[OutPoint],
+        ttl: Duration,
+    ) -> Result<()> {
+        self.reservations.reserve(temporary_id, utxos, ttl);
+        Ok(())
+    }
+
+    fn release_utxos(&self, temporary_id: &ContractId) -> Result<()> {
+        self.reservations.release(temporary_id);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct UtxoWrap(Utxo);
+
+impl rust_bitcoin_coin_selection::Utxo for UtxoWrap {
+    fn get_value(&self) -> u64 {
+        self.0.tx_out.value
+    }
+}
+
+fn to_blockchain_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::BlockchainError(e.to_string())
+}
+
+pub(crate) struct FfiBlockchain {
+    provider: Box<dyn BlockchainProvider>,
+    network: Network,
+}
+
+impl FfiBlockchain {
+    pub(crate) fn new(provider: Box<dyn BlockchainProvider>, network: Network) -> Self {
+        FfiBlockchain { provider, network }
+    }
+}
+
+impl Blockchain for FfiBlockchain {
+    fn send_transaction(&self, transaction: &Transaction) -> Result<()> {
+        self.provider
+            .send_transaction(serialize(transaction))
+            .map_err(to_blockchain_error)
+    }
+
+    fn get_network(&self) -> Result<Network> {
+        Ok(self.network)
+    }
+
+    fn get_blockchain_height(&self) -> Result<u64> {
+        self.provider
+            .get_blockchain_height()
+            .map_err(to_blockchain_error)
+    }
+
+    fn get_block_at_height(&self, height: u64) -> Result<Block> {
+        let block = self
+            .provider
+            .get_block_at_height(height)
+            .map_err(to_blockchain_error)?;
+        deserialize(&block).map_err(to_blockchain_error)
+    }
+
+    fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction> {
+        let tx = self
+            .provider
+            .get_transaction(tx_id.to_string())
+            .map_err(to_blockchain_error)?;
+        deserialize(&tx).map_err(to_blockchain_error)
+    }
+
+    fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32> {
+        self.provider
+            .get_transaction_confirmations(tx_id.to_string())
+            .map_err(to_blockchain_error)
+    }
+
+    fn get_fee_rate(&self, confirmation_target: u32) -> Result<u64> {
+        self.provider
+            .get_fee_rate(confirmation_target)
+            .map_err(to_blockchain_error)
+    }
+
+    fn get_mempool_spend(&self, outpoint: &OutPoint) -> Result<Option<Transaction>> {
+        self.provider
+            .get_mempool_spend(outpoint.txid.to_string(), outpoint.vout)
+            .map_err(to_blockchain_error)?
+            .map(|tx| deserialize(&tx).map_err(to_blockchain_error))
+            .transpose()
+    }
+}
+
+/// Fee rates are queried from the application each time, using the same
+/// confirmation targets as the bitcoin-rpc-provider, and falling back to the
+/// minimum relay fee when the estimation fails.
+impl FeeEstimator for FfiBlockchain {
+    fn get_est_sat_per_1000_weight(&self, confirmation_target: ConfirmationTarget) -> u32 {
+        let confirmation_target = match confirmation_target {
+            ConfirmationTarget::Background => 144,
+            ConfirmationTarget::Normal => 18,
+            ConfirmationTarget::HighPriority => 6,
+        };
+        self.provider
+            .get_fee_rate(confirmation_target)
+            .map(|x| std::cmp::max((x * 250) as u32, MIN_FEERATE))
+            .unwrap_or(MIN_FEERATE)
+    }
+}
+
+fn to_oracle_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::OracleError(e.to_string())
+}
+
+pub(crate) struct FfiOracle {
+    public_key: XOnlyPublicKey,
+    provider: Arc<dyn OracleProvider>,
+}
+
+impl FfiOracle {
+    pub(crate) fn new(public_key: XOnlyPublicKey, provider: Arc<dyn OracleProvider>) -> Self {
+        FfiOracle {
+            public_key,
+            provider,
+        }
+    }
+}
+
+impl Oracle for FfiOracle {
+    fn get_public_key(&self) -> XOnlyPublicKey {
+        self.public_key
+    }
+
+    fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement> {
+        let announcement = self
+            .provider
+            .get_announcement(self.public_key.to_string(), event_id.to_string())
+            .map_err(to_oracle_error)?;
+        OracleAnnouncement::read(&mut Cursor::new(&announcement)).map_err(to_oracle_error)
+    }
+
+    fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation> {
+        let attestation = self
+            .provider
+            .get_attestation(self.public_key.to_string(), event_id.to_string())
+            .map_err(to_oracle_error)?;
+        OracleAttestation::read(&mut Cursor::new(&attestation)).map_err(to_oracle_error)
+    }
+}
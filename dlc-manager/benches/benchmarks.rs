@@ -11,6 +11,7 @@ use dlc::TxInputInfo;
 use dlc_manager::contract::contract_info::ContractInfo;
 use dlc_manager::contract::numerical_descriptor::DifferenceParams;
 use dlc_manager::contract::numerical_descriptor::NumericalDescriptor;
+use dlc_manager::contract::point_cache;
 use dlc_manager::contract::ContractDescriptor;
 use dlc_manager::payout_curve::PayoutFunction;
 use dlc_manager::payout_curve::PayoutFunctionPiece;
@@ -127,6 +128,7 @@ fn create_contract_descriptor() -> ContractDescriptor {
                 .collect::<Vec<_>>(),
         },
         difference_params,
+        truncated_digits: Vec::new(),
     })
 }
 
@@ -191,6 +193,7 @@ fn create_transactions(payouts: &[Payout]) -> DlcTransactions {
         inputs: create_txinputinfo_vec(),
         input_amount: 300000000,
         collateral: 100000000,
+        additional_payout_outputs: Vec::new(),
     };
 
     let accept_params = PartyParams {
@@ -202,6 +205,7 @@ fn create_transactions(payouts: &[Payout]) -> DlcTransactions {
         inputs: create_txinputinfo_vec(),
         input_amount: 300000000,
         collateral: 100000000,
+        additional_payout_outputs: Vec::new(),
     };
     create_dlc_transactions(&offer_params, &accept_params, payouts, 1000, 2, 0, 1000, 3).unwrap()
 }
@@ -284,9 +288,52 @@ pub fn verify_bench(c: &mut Criterion) {
     });
 }
 
+/// Benchmark to measure the adaptor signature verification time when the
+/// anticipation points of the oracles are not cached, as when verifying the
+/// first contract using a set of announcements.
+pub fn verify_uncached_bench(c: &mut Criterion) {
+    let contract_info = create_contract_info();
+    let dlc_transactions = create_transactions(&contract_info.get_payouts(200000000).unwrap());
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+
+    let seckey = accept_seckey();
+    let pubkey = secp256k1_zkp::PublicKey::from_secret_key(SECP256K1, &seckey);
+    let adaptor_info = contract_info
+        .get_adaptor_info(
+            SECP256K1,
+            TOTAL_COLLATERAL,
+            &seckey,
+            &dlc_transactions.funding_script_pubkey,
+            fund_output_value,
+            &dlc_transactions.cets,
+            0,
+        )
+        .unwrap();
+    let adaptor_signatures = &adaptor_info.1;
+    c.bench_function("verify_uncached", |b| {
+        b.iter(|| {
+            point_cache::clear();
+            black_box(
+                contract_info
+                    .verify_adaptor_info(
+                        SECP256K1,
+                        &pubkey,
+                        &dlc_transactions.funding_script_pubkey,
+                        fund_output_value,
+                        &dlc_transactions.cets,
+                        adaptor_signatures,
+                        0,
+                        &adaptor_info.0,
+                    )
+                    .unwrap(),
+            );
+        });
+    });
+}
+
 criterion_group! {
     name = sign_verify_bench;
     config = Criterion::default().measurement_time(std::time::Duration::new(120, 0)).sample_size(10);
-    targets = sign_bench, verify_bench
+    targets = sign_bench, verify_bench, verify_uncached_bench
 }
 criterion_main!(sign_verify_bench);
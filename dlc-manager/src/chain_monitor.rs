@@ -82,6 +82,10 @@ impl ChainMonitor {
         self.watched_tx.remove(txid);
     }
 
+    pub(crate) fn get_watched_tx(&self, txid: &Txid) -> Option<ChannelInfo> {
+        self.watched_tx.get(txid).cloned()
+    }
+
     pub(crate) fn process_block(
         &self,
         block: &Block,
@@ -73,6 +73,8 @@ impl OfferedChannel {
             fee_rate_per_vb: offered_contract.fee_rate_per_vb,
             fund_output_serial_id: offered_contract.fund_output_serial_id,
             cet_nsequence: crate::manager::CET_NSEQUENCE,
+            features: Some(offered_contract.get_features()),
+            unknown_tlvs: Vec::new(),
         }
     }
 
@@ -117,6 +119,8 @@ impl OfferedChannel {
                 collateral: offer_channel.offer_collateral,
                 inputs,
                 input_amount,
+                // Channel messages do not carry additional payout outputs.
+                additional_payout_outputs: Vec::new(),
             },
             cet_locktime: offer_channel.cet_locktime,
             refund_locktime: offer_channel.refund_locktime,
@@ -128,6 +132,10 @@ impl OfferedChannel {
                 .map(|x| x.into())
                 .collect(),
             total_collateral: offer_channel.contract_info.get_total_collateral(),
+            shared_funding_input: None,
+            shared_funding_accept_share: None,
+            metadata: None,
+            rollover: None,
         };
 
         Ok((channel, contract))
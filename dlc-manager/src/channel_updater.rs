@@ -19,10 +19,12 @@ use crate::{
         verify_signed_contract_internal,
     },
     error::Error,
+    key_derivation::{ContractKeyId, ContractKeyType},
     utils::get_new_temporary_id,
-    Blockchain, Signer, Time, Wallet,
+    zeroizing::ZeroizingSecretKey,
+    Blockchain, ContractSigner, Time, Wallet,
 };
-use bitcoin::{OutPoint, Script, Sequence, Transaction, TxIn, Witness};
+use bitcoin::{EcdsaSighashType, OutPoint, Script, Sequence, Transaction, TxIn, Witness};
 use dlc::{
     channel::{get_tx_adaptor_signature, verify_tx_adaptor_signature, DlcChannelTransactions},
     PartyParams,
@@ -35,9 +37,7 @@ use dlc_messages::{
     oracle_msgs::{OracleAnnouncement, OracleAttestation},
     FundingSignatures,
 };
-use lightning::ln::chan_utils::{
-    build_commitment_secret, derive_private_key, CounterpartyCommitmentSecrets,
-};
+use lightning::ln::chan_utils::{build_commitment_secret, CounterpartyCommitmentSecrets};
 use secp256k1_zkp::{All, EcdsaAdaptorSignature, PublicKey, Secp256k1, SecretKey, Signing};
 
 const INITIAL_UPDATE_NUMBER: u64 = (1 << 48) - 1;
@@ -64,9 +64,20 @@ macro_rules! get_signed_channel_state {
 }
 pub(crate) use get_signed_channel_state;
 
+/// Returns an error if the given contract input splits the payout of the offer
+/// party across additional outputs, which channel messages do not carry.
+fn check_no_additional_payout_outputs(contract_input: &ContractInput) -> Result<(), Error> {
+    if !contract_input.additional_payout_outputs.is_empty() {
+        return Err(Error::InvalidParameters(
+            "Additional payout outputs are not supported within channels.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 /// Creates an [`OfferedChannel`] and an associated [`OfferedContract`] using
 /// the given parameter.
-pub fn offer_channel<C: Signing, W: Deref, B: Deref, T: Deref>(
+pub fn offer_channel<C: Signing, W: Deref, S: Deref, B: Deref, T: Deref>(
     secp: &Secp256k1<C>,
     contract: &ContractInput,
     counter_party: &PublicKey,
@@ -74,22 +85,33 @@ pub fn offer_channel<C: Signing, W: Deref, B: Deref, T: Deref>(
     cet_nsequence: u32,
     refund_delay: u32,
     wallet: &W,
+    signer: &S,
     blockchain: &B,
     time: &T,
 ) -> Result<(OfferedChannel, OfferedContract), Error>
 where
     W::Target: Wallet,
+    S::Target: ContractSigner,
     B::Target: Blockchain,
     T::Target: Time,
 {
+    check_no_additional_payout_outputs(contract)?;
+    let temporary_channel_id = get_new_temporary_id();
+
     let (offer_params, _, funding_inputs_info) = crate::utils::get_party_params(
         secp,
+        counter_party,
+        &temporary_channel_id,
         contract.offer_collateral,
         contract.fee_rate,
+        contract.payout_script_pubkey.as_ref(),
+        contract.change_script_pubkey.as_ref(),
         wallet,
+        signer,
         blockchain,
     )?;
-    let party_points = crate::utils::get_party_base_points(secp, wallet)?;
+    let party_points =
+        crate::utils::get_party_base_points(secp, counter_party, &temporary_channel_id, signer)?;
 
     let offered_contract = OfferedContract::new(
         contract,
@@ -101,9 +123,13 @@ where
         time.unix_time_now() as u32,
     );
 
-    let temporary_channel_id = get_new_temporary_id();
-
-    let per_update_seed = wallet.get_new_secret_key()?;
+    let per_update_seed: ZeroizingSecretKey = signer
+        .derive_contract_secret_key(&ContractKeyId {
+            counter_party: *counter_party,
+            temporary_id: temporary_channel_id,
+            key_type: ContractKeyType::PerUpdateSeed,
+        })?
+        .into();
 
     let first_per_update_point = PublicKey::from_secret_key(
         secp,
@@ -131,15 +157,17 @@ where
 /// Move the given [`OfferedChannel`] and [`OfferedContract`] to an [`AcceptedChannel`]
 /// and [`AcceptedContract`], returning them as well as the [`AcceptChannel`]
 /// message to be sent to the counter party.
-pub fn accept_channel_offer<W: Deref, B: Deref>(
+pub fn accept_channel_offer<W: Deref, S: Deref, B: Deref>(
     secp: &Secp256k1<All>,
     offered_channel: &OfferedChannel,
     offered_contract: &OfferedContract,
     wallet: &W,
+    signer: &S,
     blockchain: &B,
 ) -> Result<(AcceptedChannel, AcceptedContract, AcceptChannel), Error>
 where
     W::Target: Wallet,
+    S::Target: ContractSigner,
     B::Target: Blockchain,
 {
     assert_eq!(offered_channel.offered_contract_id, offered_contract.id);
@@ -148,13 +176,24 @@ where
 
     let (accept_params, _, funding_inputs) = crate::utils::get_party_params(
         secp,
+        &offered_contract.counter_party,
+        &offered_channel.temporary_channel_id,
         total_collateral - offered_contract.offer_params.collateral,
         offered_contract.fee_rate_per_vb,
+        None,
+        None,
         wallet,
+        signer,
         blockchain,
     )?;
 
-    let per_update_seed = wallet.get_new_secret_key()?;
+    let per_update_seed: ZeroizingSecretKey = signer
+        .derive_contract_secret_key(&ContractKeyId {
+            counter_party: offered_contract.counter_party,
+            temporary_id: offered_channel.temporary_channel_id,
+            key_type: ContractKeyType::PerUpdateSeed,
+        })?
+        .into();
 
     let first_per_update_point = PublicKey::from_secret_key(
         secp,
@@ -165,7 +204,12 @@ where
         .expect("to have generated a valid secret key."),
     );
 
-    let accept_points = crate::utils::get_party_base_points(secp, wallet)?;
+    let accept_points = crate::utils::get_party_base_points(
+        secp,
+        &offered_contract.counter_party,
+        &offered_channel.temporary_channel_id,
+        signer,
+    )?;
 
     let accept_revoke_params = accept_points.get_revokable_params(
         secp,
@@ -199,9 +243,12 @@ where
         Sequence(offered_channel.cet_nsequence),
     )?;
 
-    let own_base_secret_key = wallet.get_secret_key_for_pubkey(&accept_points.own_basepoint)?;
-
-    let own_secret_key = derive_private_key(secp, &first_per_update_point, &own_base_secret_key);
+    let own_secret_key = crate::utils::derive_own_secret_key(
+        secp,
+        signer,
+        &accept_points.own_basepoint,
+        &first_per_update_point,
+    )?;
 
     let channel_id = crate::utils::compute_id(
         dlc_transactions.fund.txid(),
@@ -209,7 +256,7 @@ where
         &offered_channel.temporary_channel_id,
     );
 
-    let own_fund_sk = wallet.get_secret_key_for_pubkey(&accept_params.fund_pubkey)?;
+    let own_fund_sk = signer.get_secret_key_for_pubkey(&accept_params.fund_pubkey)?;
 
     let buffer_adaptor_signature = get_tx_adaptor_signature(
         secp,
@@ -268,7 +315,7 @@ pub fn verify_and_sign_accepted_channel<S: Deref>(
     signer: &S,
 ) -> Result<(SignedChannel, SignedContract, SignChannel), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let (tx_input_infos, input_amount) =
         crate::conversion_utils::get_tx_input_infos(&accept_channel.funding_inputs)?;
@@ -282,6 +329,8 @@ where
         inputs: tx_input_infos,
         input_amount,
         collateral: accept_channel.accept_collateral,
+        // Channel messages do not carry additional payout outputs.
+        additional_payout_outputs: Vec::new(),
     };
 
     let accept_points = PartyBasePoints {
@@ -290,14 +339,12 @@ where
         publish_basepoint: accept_channel.publish_basepoint,
     };
 
-    let offer_own_base_secret =
-        signer.get_secret_key_for_pubkey(&offered_channel.party_points.own_basepoint)?;
-
-    let offer_own_sk = derive_private_key(
+    let offer_own_sk = crate::utils::derive_own_secret_key(
         secp,
+        signer,
+        &offered_channel.party_points.own_basepoint,
         &offered_channel.per_update_point,
-        &offer_own_base_secret,
-    );
+    )?;
 
     let offer_fund_sk =
         signer.get_secret_key_for_pubkey(&offered_contract.offer_params.fund_pubkey)?;
@@ -359,6 +406,7 @@ where
         Some(accept_revoke_params.own_pk.inner),
         &dlc_transactions,
         Some(channel_id),
+        EcdsaSighashType::All,
     )?;
 
     verify_tx_adaptor_signature(
@@ -437,7 +485,7 @@ pub fn verify_signed_channel<S: Deref>(
     signer: &S,
 ) -> Result<(SignedChannel, SignedContract), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let own_publish_pk = accepted_channel
         .accept_base_points
@@ -469,6 +517,7 @@ where
         Some(counter_own_pk),
         signer,
         Some(accepted_channel.channel_id),
+        EcdsaSighashType::All,
     )?;
 
     let signed_channel = SignedChannel {
@@ -522,7 +571,7 @@ pub fn settle_channel_offer<C: Signing, S: Deref, T: Deref>(
     time: &T,
 ) -> Result<SettleOffer, Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
     T::Target: Time,
 {
     if let SignedChannelState::Established { .. } = channel.state {
@@ -599,7 +648,7 @@ pub fn settle_channel_accept<S: Deref, T: Deref>(
     time: &T,
 ) -> Result<SettleAccept, Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
     T::Target: Time,
 {
     let (own_payout, counter_next_per_update_point) = if let SignedChannelState::SettledReceived {
@@ -688,7 +737,7 @@ pub fn settle_channel_confirm<T: Deref, S: Deref>(
 ) -> Result<SettleConfirm, Error>
 where
     T::Target: Time,
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let (counter_payout, next_per_update_point) = match channel.state {
         SignedChannelState::SettledOffered {
@@ -776,7 +825,7 @@ pub fn settle_channel_finalize<S: Deref>(
     signer: &S,
 ) -> Result<SettleFinalize, Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let (
         own_next_per_update_point,
@@ -964,9 +1013,10 @@ pub fn renew_offer<C: Signing, S: Deref, T: Deref>(
     time: &T,
 ) -> Result<(RenewOffer, OfferedContract), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
     T::Target: Time,
 {
+    check_no_additional_payout_outputs(contract_input)?;
     let mut offered_contract = OfferedContract::new(
         contract_input,
         oracle_announcements,
@@ -1047,6 +1097,10 @@ pub fn on_renew_offer(
         fee_rate_per_vb: signed_channel.fee_rate_per_vb,
         cet_locktime: renew_offer.cet_locktime,
         refund_locktime: renew_offer.refund_locktime,
+        shared_funding_input: None,
+        shared_funding_accept_share: None,
+        metadata: None,
+        rollover: None,
     };
 
     let mut state = SignedChannelState::RenewOffered {
@@ -1078,7 +1132,7 @@ pub fn accept_channel_renewal<S: Deref, T: Deref>(
     time: &T,
 ) -> Result<(AcceptedContract, RenewAccept), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
     T::Target: Time,
 {
     let (offer_next_per_update_point, own_payout) = match signed_channel.state {
@@ -1095,8 +1149,6 @@ where
     };
 
     let own_fund_sk = signer.get_secret_key_for_pubkey(&signed_channel.own_params.fund_pubkey)?;
-    let own_base_secret_key =
-        signer.get_secret_key_for_pubkey(&signed_channel.own_points.own_basepoint)?;
     let per_update_seed = signer.get_secret_key_for_pubkey(&signed_channel.own_per_update_seed)?;
 
     let total_collateral = offered_contract.total_collateral;
@@ -1147,7 +1199,12 @@ where
         &offer_revoke_params.publish_pk.inner,
     )?;
 
-    let own_secret_key = derive_private_key(secp, &accept_per_update_point, &own_base_secret_key);
+    let own_secret_key = crate::utils::derive_own_secret_key(
+        secp,
+        signer,
+        &signed_channel.own_points.own_basepoint,
+        &accept_per_update_point,
+    )?;
 
     let (accepted_contract, adaptor_sigs) = accept_contract_internal(
         secp,
@@ -1199,14 +1256,11 @@ pub fn verify_renew_accept_and_confirm<S: Deref, T: Deref>(
     time: &T,
 ) -> Result<(SignedContract, RenewConfirm), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
     T::Target: Time,
 {
     let own_fund_sk = signer.get_secret_key_for_pubkey(&signed_channel.own_params.fund_pubkey)?;
 
-    let own_base_secret_key =
-        signer.get_secret_key_for_pubkey(&signed_channel.own_points.own_basepoint)?;
-
     let per_update_seed = signer.get_secret_key_for_pubkey(&signed_channel.own_per_update_seed)?;
 
     let prev_per_update_secret = SecretKey::from_slice(&build_commitment_secret(
@@ -1251,7 +1305,12 @@ where
         Sequence(cet_nsequence),
     )?;
 
-    let offer_own_sk = derive_private_key(secp, &offer_per_update_point, &own_base_secret_key);
+    let offer_own_sk = crate::utils::derive_own_secret_key(
+        secp,
+        signer,
+        &signed_channel.own_points.own_basepoint,
+        &offer_per_update_point,
+    )?;
     let cet_adaptor_signatures: Vec<_> = (&renew_accept.cet_adaptor_signatures).into();
 
     let (signed_contract, cet_adaptor_signatures) = verify_accepted_and_sign_contract_internal(
@@ -1268,6 +1327,7 @@ where
         Some(accept_revoke_params.own_pk.inner),
         &dlc_transactions,
         Some(signed_channel.channel_id),
+        EcdsaSighashType::All,
     )?;
 
     verify_tx_adaptor_signature(
@@ -1326,7 +1386,7 @@ pub fn verify_renew_confirm_and_finalize<S: Deref>(
     signer: &S,
 ) -> Result<(SignedContract, RenewFinalize), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let (
         offer_per_update_point,
@@ -1374,6 +1434,7 @@ where
         Some(counter_own_pk),
         signer,
         Some(signed_channel.channel_id),
+        EcdsaSighashType::All,
     )?;
 
     signed_channel.state = SignedChannelState::Established {
@@ -1492,7 +1553,7 @@ pub fn offer_collaborative_close<C: Signing, S: Deref, T: Deref>(
     time: &T,
 ) -> Result<(CollaborativeCloseOffer, Transaction), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
     T::Target: Time,
 {
     if counter_payout
@@ -1610,7 +1671,7 @@ pub fn accept_collaborative_close_offer<C: Signing, S: Deref>(
     signer: &S,
 ) -> Result<Transaction, Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let (offer_signature, close_tx) = get_signed_channel_state!(
         signed_channel,
@@ -1770,7 +1831,7 @@ pub fn initiate_unilateral_close_established_channel<S: Deref>(
     signer: &S,
 ) -> Result<(), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let (buffer_adaptor_signature, buffer_transaction) = get_signed_channel_state!(
         signed_channel,
@@ -1780,14 +1841,12 @@ where
 
     let mut buffer_transaction = buffer_transaction.clone();
 
-    let publish_base_secret =
-        signer.get_secret_key_for_pubkey(&signed_channel.own_points.publish_basepoint)?;
-
-    let publish_sk = derive_private_key(
+    let publish_sk = crate::utils::derive_own_secret_key(
         secp,
+        signer,
+        &signed_channel.own_points.publish_basepoint,
         &signed_channel.own_per_update_point,
-        &publish_base_secret,
-    );
+    )?;
 
     let counter_buffer_signature = buffer_adaptor_signature.decrypt(&publish_sk)?;
 
@@ -1867,8 +1926,8 @@ where
         )
     };
 
-    let base_secret = signer.get_secret_key_for_pubkey(own_basepoint)?;
-    let own_sk = derive_private_key(secp, own_per_update_point, &base_secret);
+    let own_sk =
+        crate::utils::derive_own_secret_key(secp, signer, own_basepoint, own_per_update_point)?;
 
     dlc::channel::sign_cet(
         secp,
@@ -1899,7 +1958,7 @@ pub fn close_settled_channel<C: Signing, S: Deref>(
     signer: &S,
 ) -> Result<Transaction, Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let (counter_settle_adaptor_signature, settle_tx) = get_signed_channel_state!(
         signed_channel,
@@ -1909,14 +1968,12 @@ where
 
     let mut settle_tx = settle_tx.clone();
 
-    let publish_base_secret =
-        signer.get_secret_key_for_pubkey(&signed_channel.own_points.publish_basepoint)?;
-
-    let publish_sk = derive_private_key(
+    let publish_sk = crate::utils::derive_own_secret_key(
         secp,
+        signer,
+        &signed_channel.own_points.publish_basepoint,
         &signed_channel.own_per_update_point,
-        &publish_base_secret,
-    );
+    )?;
 
     let counter_settle_signature = counter_settle_adaptor_signature.decrypt(&publish_sk)?;
 
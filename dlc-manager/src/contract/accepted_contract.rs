@@ -1,10 +1,11 @@
 //! # AcceptedContract
 
 use super::offered_contract::OfferedContract;
-use super::{AdaptorInfo, FundingInputInfo};
+use super::{AdaptorInfo, CetForOutcome, FundingInputInfo, Outcome};
+use crate::error::Error;
 use bitcoin::Transaction;
 use dlc::{DlcTransactions, PartyParams};
-use dlc_messages::AcceptDlc;
+use dlc_messages::{AcceptDlc, AdditionalPayoutOutputs};
 use secp256k1_zkp::ecdsa::Signature;
 use secp256k1_zkp::EcdsaAdaptorSignature;
 
@@ -71,9 +72,51 @@ impl AcceptedContract {
             cet_adaptor_signatures: ecdsa_adaptor_signatures.into(),
             refund_signature: self.accept_refund_signature,
             negotiation_fields: None,
+            ownership_proofs: None,
+            additional_payout_outputs: AdditionalPayoutOutputs::from_payout_outputs(
+                &self.accept_params.additional_payout_outputs,
+            ),
+            unknown_tlvs: Vec::new(),
         }
     }
 
+    /// Returns the CET that would be used to close the contract if the oracles
+    /// of the contract info at the given index attested to the given outcome.
+    pub fn get_cet_for_outcome(
+        &self,
+        contract_info_index: usize,
+        outcome: &Outcome,
+    ) -> Result<Option<CetForOutcome>, Error> {
+        let contract_info = self
+            .offered_contract
+            .contract_info
+            .get(contract_info_index)
+            .ok_or_else(|| Error::InvalidParameters("Unknown contract info index.".to_string()))?;
+        let adaptor_info = self
+            .adaptor_infos
+            .get(contract_info_index)
+            .ok_or_else(|| Error::InvalidState("Missing adaptor info.".to_string()))?;
+
+        let range_info =
+            match contract_info.get_range_info_for_outcome_value(adaptor_info, outcome)? {
+                Some(range_info) => range_info,
+                None => return Ok(None),
+            };
+
+        let cet = self
+            .dlc_transactions
+            .cets
+            .get(range_info.cet_index)
+            .ok_or_else(|| Error::InvalidState("Could not find CET for outcome.".to_string()))?
+            .clone();
+
+        Ok(Some(CetForOutcome {
+            cet_index: range_info.cet_index,
+            range_info,
+            cet,
+        }))
+    }
+
     /// Compute the profit and loss for this contract and an assciated cet index
     pub fn compute_pnl(&self, cet: &Transaction) -> i64 {
         let offer = &self.offered_contract;
@@ -118,4 +161,31 @@ mod tests {
             -100000000
         );
     }
+
+    #[test]
+    fn get_cet_for_outcome_test() {
+        let buf = include_bytes!("../../test_inputs/Accepted");
+        let accepted_contract: AcceptedContract = Readable::read(&mut Cursor::new(&buf)).unwrap();
+        let cets = &accepted_contract.dlc_transactions.cets;
+
+        let first = accepted_contract
+            .get_cet_for_outcome(0, &Outcome::Numerical(0))
+            .unwrap()
+            .expect("to find a CET");
+        let last = accepted_contract
+            .get_cet_for_outcome(0, &Outcome::Numerical(u64::MAX))
+            .unwrap()
+            .expect("to find a CET");
+
+        assert_eq!(0, first.cet_index);
+        assert_eq!(cets[0], first.cet);
+        assert_eq!(cets.len() - 1, last.cet_index);
+        assert_eq!(cets[cets.len() - 1], last.cet);
+        accepted_contract
+            .get_cet_for_outcome(0, &Outcome::Enum("A".to_string()))
+            .expect_err("outcome type should not match");
+        accepted_contract
+            .get_cet_for_outcome(1, &Outcome::Numerical(0))
+            .expect_err("contract info index should be invalid");
+    }
 }
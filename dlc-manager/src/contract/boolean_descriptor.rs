@@ -0,0 +1,425 @@
+//! #BooleanEnumDescriptor
+
+use super::contract_info::OracleIndexAndPrefixLength;
+use super::AdaptorInfo;
+use crate::error::Error;
+use bitcoin::{Script, Transaction};
+use dlc::{OracleInfo, Payout};
+use dlc_messages::oracle_msgs::{EventDescriptor, OracleAnnouncement};
+use dlc_trie::RangeInfo;
+use secp256k1_zkp::{
+    All, EcdsaAdaptorSignature, Message, PublicKey, Secp256k1, SecretKey, Verification,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A condition on the outcomes attested by the oracles of a contract, each
+/// oracle attesting to its own enumerated event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum EnumCondition {
+    /// Satisfied if the oracle at the given index attests to one of the given
+    /// outcomes.
+    Outcome {
+        /// The index of the oracle within the contract announcements.
+        oracle_index: usize,
+        /// The outcomes satisfying the condition.
+        outcomes: Vec<String>,
+    },
+    /// Satisfied if all of the conditions are.
+    And(Vec<EnumCondition>),
+    /// Satisfied if any of the conditions is.
+    Or(Vec<EnumCondition>),
+}
+
+impl EnumCondition {
+    /// Returns whether the condition holds for the given outcomes, one for
+    /// each oracle of the contract.
+    pub fn evaluate(&self, outcomes: &[&String]) -> bool {
+        match self {
+            EnumCondition::Outcome {
+                oracle_index,
+                outcomes: expected,
+            } => matches!(outcomes.get(*oracle_index), Some(o) if expected.contains(o)),
+            EnumCondition::And(conditions) => conditions.iter().all(|c| c.evaluate(outcomes)),
+            EnumCondition::Or(conditions) => conditions.iter().any(|c| c.evaluate(outcomes)),
+        }
+    }
+
+    /// Checks that the condition only refers to existing oracles and outcomes,
+    /// marking the oracles it refers to.
+    fn validate(&self, event_outcomes: &[&Vec<String>], used: &mut [bool]) -> Result<(), Error> {
+        match self {
+            EnumCondition::Outcome {
+                oracle_index,
+                outcomes,
+            } => {
+                let event_outcomes = event_outcomes.get(*oracle_index).ok_or_else(|| {
+                    Error::InvalidParameters(format!(
+                        "Condition refers to unknown oracle {}.",
+                        oracle_index
+                    ))
+                })?;
+                if let Some(outcome) = outcomes.iter().find(|x| !event_outcomes.contains(x)) {
+                    return Err(Error::InvalidParameters(format!(
+                        "Outcome {} is not an outcome of the event of oracle {}.",
+                        outcome, oracle_index
+                    )));
+                }
+                used[*oracle_index] = true;
+                Ok(())
+            }
+            EnumCondition::And(conditions) | EnumCondition::Or(conditions) => {
+                if conditions.is_empty() {
+                    return Err(Error::InvalidParameters(
+                        "Condition combination cannot be empty.".to_string(),
+                    ));
+                }
+                for condition in conditions {
+                    condition.validate(event_outcomes, used)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A descriptor for a contract paying out depending on whether a boolean
+/// combination of enumerated outcomes from different oracles holds, for example
+/// "team X wins AND turnout is above Y". Each oracle attests to its own event,
+/// and attestations from all of them are required to close the contract.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct BooleanEnumDescriptor {
+    /// The condition on the attested outcomes.
+    pub condition: EnumCondition,
+    /// The payout if the condition is satisfied.
+    pub true_payout: Payout,
+    /// The payout if the condition is not satisfied.
+    pub false_payout: Payout,
+}
+
+/// Index of the CET used when the condition is satisfied.
+const TRUE_CET_INDEX: usize = 0;
+/// Index of the CET used when the condition is not satisfied.
+const FALSE_CET_INDEX: usize = 1;
+
+impl BooleanEnumDescriptor {
+    /// Returns the set of payouts, the first one being used if the condition is
+    /// satisfied.
+    pub fn get_payouts(&self) -> Vec<Payout> {
+        vec![self.true_payout.clone(), self.false_payout.clone()]
+    }
+
+    /// Validate that the condition refers to the outcomes of the events of the
+    /// given announcements, and that each oracle is used in the condition.
+    pub fn validate(&self, announcements: &[OracleAnnouncement]) -> Result<(), Error> {
+        let event_outcomes = get_event_outcomes(announcements)?;
+        let mut used = vec![false; event_outcomes.len()];
+        self.condition.validate(&event_outcomes, &mut used)?;
+        if let Some(index) = used.iter().position(|x| !x) {
+            return Err(Error::InvalidParameters(format!(
+                "Oracle {} is not used in the contract condition.",
+                index
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the `RangeInfo` that matches the given set of outcomes if any.
+    /// An outcome is required from every oracle.
+    pub fn get_range_info_for_outcome(
+        &self,
+        announcements: &[OracleAnnouncement],
+        outcomes: &[(usize, &Vec<String>)],
+        adaptor_sig_start: usize,
+    ) -> Option<(OracleIndexAndPrefixLength, RangeInfo)> {
+        let event_outcomes = get_event_outcomes(announcements).ok()?;
+        let mut attested = Vec::with_capacity(event_outcomes.len());
+        let mut combination_index = 0;
+        for (i, event_outcomes) in event_outcomes.iter().enumerate() {
+            let outcome = match outcomes.iter().find(|x| x.0 == i)?.1.as_slice() {
+                [outcome] => outcome,
+                _ => return None,
+            };
+            let pos = event_outcomes.iter().position(|x| x == outcome)?;
+            combination_index = combination_index * event_outcomes.len() + pos;
+            attested.push(outcome);
+        }
+
+        let cet_index = if self.condition.evaluate(&attested) {
+            TRUE_CET_INDEX
+        } else {
+            FALSE_CET_INDEX
+        };
+
+        Some((
+            (0..event_outcomes.len()).map(|x| (x, 1)).collect(),
+            RangeInfo {
+                cet_index,
+                adaptor_index: adaptor_sig_start + combination_index,
+            },
+        ))
+    }
+
+    /// Verify the given set adaptor signatures.
+    pub fn verify_adaptor_info(
+        &self,
+        secp: &Secp256k1<All>,
+        announcements: &[OracleAnnouncement],
+        fund_pubkey: &PublicKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+        cets: &[Transaction],
+        adaptor_sigs: &[EcdsaAdaptorSignature],
+        adaptor_sig_start: usize,
+    ) -> Result<usize, Error> {
+        let sig_hashes =
+            dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
+        let mut adaptor_sig_index = adaptor_sig_start;
+        let mut callback =
+            |adaptor_point: &PublicKey, cet_index: usize| -> Result<(), dlc::Error> {
+                let sig = adaptor_sigs
+                    .get(adaptor_sig_index)
+                    .ok_or(dlc::Error::InvalidArgument)?;
+                adaptor_sig_index += 1;
+                dlc::verify_cet_adaptor_sig_from_sig_hash(
+                    secp,
+                    sig,
+                    &sig_hashes[cet_index],
+                    adaptor_point,
+                    fund_pubkey,
+                )
+            };
+
+        self.iter_outcomes(secp, announcements, &mut callback)?;
+
+        Ok(adaptor_sig_index)
+    }
+
+    /// Verify the given set of adaptor signature and generates the adaptor info.
+    pub fn verify_and_get_adaptor_info(
+        &self,
+        secp: &Secp256k1<All>,
+        announcements: &[OracleAnnouncement],
+        fund_pubkey: &PublicKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+        cets: &[Transaction],
+        adaptor_sigs: &[EcdsaAdaptorSignature],
+        adaptor_sig_start: usize,
+    ) -> Result<(AdaptorInfo, usize), Error> {
+        let adaptor_sig_index = self.verify_adaptor_info(
+            secp,
+            announcements,
+            fund_pubkey,
+            funding_script_pubkey,
+            fund_output_value,
+            cets,
+            adaptor_sigs,
+            adaptor_sig_start,
+        )?;
+
+        Ok((AdaptorInfo::Enum, adaptor_sig_index))
+    }
+
+    /// Generate the set of adaptor signatures and return the adaptor info.
+    pub fn get_adaptor_info(
+        &self,
+        secp: &Secp256k1<All>,
+        announcements: &[OracleAnnouncement],
+        fund_privkey: &SecretKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+        cets: &[Transaction],
+    ) -> Result<(AdaptorInfo, Vec<EcdsaAdaptorSignature>), Error> {
+        let adaptor_sigs = self.get_adaptor_signatures(
+            secp,
+            announcements,
+            cets,
+            fund_privkey,
+            funding_script_pubkey,
+            fund_output_value,
+        )?;
+
+        Ok((AdaptorInfo::Enum, adaptor_sigs))
+    }
+
+    /// Generate the set of adaptor signatures, one for each combination of
+    /// outcomes of the oracles.
+    pub fn get_adaptor_signatures(
+        &self,
+        secp: &Secp256k1<All>,
+        announcements: &[OracleAnnouncement],
+        cets: &[Transaction],
+        fund_privkey: &SecretKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+    ) -> Result<Vec<EcdsaAdaptorSignature>, Error> {
+        let sig_hashes =
+            dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
+        let mut adaptor_sigs = Vec::new();
+        let mut callback =
+            |adaptor_point: &PublicKey, cet_index: usize| -> Result<(), dlc::Error> {
+                let sig = dlc::create_cet_adaptor_sig_from_sig_hash(
+                    secp,
+                    &sig_hashes[cet_index],
+                    adaptor_point,
+                    fund_privkey,
+                );
+                adaptor_sigs.push(sig);
+                Ok(())
+            };
+
+        self.iter_outcomes(secp, announcements, &mut callback)?;
+
+        Ok(adaptor_sigs)
+    }
+
+    /// Calls the callback with the adaptor point and CET index of every
+    /// combination of outcomes, the outcome of the last oracle changing first.
+    fn iter_outcomes<C: Verification, F>(
+        &self,
+        secp: &Secp256k1<C>,
+        announcements: &[OracleAnnouncement],
+        callback: &mut F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&PublicKey, usize) -> Result<(), dlc::Error>,
+    {
+        let event_outcomes = get_event_outcomes(announcements)?;
+        if event_outcomes.iter().any(|x| x.is_empty()) {
+            return Err(Error::InvalidParameters(
+                "Enum events must have at least one outcome.".to_string(),
+            ));
+        }
+        let oracle_infos: Vec<OracleInfo> = announcements.iter().map(|x| x.into()).collect();
+        let messages: Vec<Vec<Message>> = event_outcomes
+            .iter()
+            .map(|outcomes| {
+                outcomes
+                    .iter()
+                    .map(|x| {
+                        Message::from_hashed_data::<secp256k1_zkp::hashes::sha256::Hash>(
+                            x.as_bytes(),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut positions = vec![0; event_outcomes.len()];
+        loop {
+            let attested: Vec<&String> = positions
+                .iter()
+                .zip(event_outcomes.iter())
+                .map(|(pos, outcomes)| &outcomes[*pos])
+                .collect();
+            let cet_index = if self.condition.evaluate(&attested) {
+                TRUE_CET_INDEX
+            } else {
+                FALSE_CET_INDEX
+            };
+            let msgs: Vec<Vec<Message>> = positions
+                .iter()
+                .zip(messages.iter())
+                .map(|(pos, msgs)| vec![msgs[*pos]])
+                .collect();
+            let adaptor_point =
+                dlc::get_adaptor_point_from_oracle_info(secp, &oracle_infos, &msgs)?;
+            callback(&adaptor_point, cet_index)?;
+
+            // Move to the next combination, stopping once all were visited.
+            let mut i = positions.len();
+            loop {
+                if i == 0 {
+                    return Ok(());
+                }
+                i -= 1;
+                positions[i] += 1;
+                if positions[i] < event_outcomes[i].len() {
+                    break;
+                }
+                positions[i] = 0;
+            }
+        }
+    }
+}
+
+fn get_event_outcomes(announcements: &[OracleAnnouncement]) -> Result<Vec<&Vec<String>>, Error> {
+    announcements
+        .iter()
+        .map(|x| match &x.oracle_event.event_descriptor {
+            EventDescriptor::EnumEvent(e) => Ok(&e.outcomes),
+            _ => Err(Error::InvalidParameters(
+                "Expected enum event descriptor.".to_string(),
+            )),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(oracle_index: usize, outcomes: &[&str]) -> EnumCondition {
+        EnumCondition::Outcome {
+            oracle_index,
+            outcomes: outcomes.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    fn evaluate(condition: &EnumCondition, outcomes: &[&str]) -> bool {
+        let outcomes: Vec<String> = outcomes.iter().map(|x| x.to_string()).collect();
+        condition.evaluate(&outcomes.iter().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn evaluate_condition_test() {
+        let condition = EnumCondition::Or(vec![
+            EnumCondition::And(vec![outcome(0, &["x"]), outcome(1, &["high", "medium"])]),
+            outcome(2, &["draw"]),
+        ]);
+
+        assert!(evaluate(&condition, &["x", "high", "y"]));
+        assert!(evaluate(&condition, &["x", "medium", "y"]));
+        assert!(!evaluate(&condition, &["x", "low", "y"]));
+        assert!(!evaluate(&condition, &["y", "high", "x"]));
+        assert!(evaluate(&condition, &["y", "low", "draw"]));
+    }
+
+    #[test]
+    fn validate_condition_test() {
+        let event_outcomes = vec![
+            vec!["x".to_string(), "y".to_string()],
+            vec!["high".to_string(), "low".to_string()],
+        ];
+        let event_outcomes = event_outcomes.iter().collect::<Vec<_>>();
+        let validate = |condition: EnumCondition| {
+            let mut used = vec![false; 2];
+            condition.validate(&event_outcomes, &mut used).map(|_| used)
+        };
+
+        assert_eq!(
+            vec![true, true],
+            validate(EnumCondition::And(vec![
+                outcome(0, &["x"]),
+                outcome(1, &["low"])
+            ]))
+            .unwrap()
+        );
+        assert_eq!(vec![true, false], validate(outcome(0, &["y"])).unwrap());
+        validate(outcome(2, &["x"])).expect_err("oracle index out of range");
+        validate(outcome(1, &["medium"])).expect_err("unknown outcome");
+        validate(EnumCondition::Or(vec![])).expect_err("empty combination");
+    }
+}
@@ -0,0 +1,684 @@
+//! Compact serialization of the tries stored in [`super::AdaptorInfo`].
+//!
+//! Integers are written as LEB128 varints, node indexes are encoded as deltas
+//! from the index of the node referencing them and range information as
+//! deltas from the previously written one. The encoded payload is preceded by
+//! a version and a set of flags, indicating for example whether it was
+//! compressed.
+//!
+//! Version 2 adds the difference parameters specific to pairs of oracles at
+//! the end of multi trie dumps.
+
+use std::io::Read;
+
+use dlc_messages::ser_impls::BigSize;
+use dlc_trie::digit_trie::{DigitNodeData, DigitTrieDump};
+use dlc_trie::multi_oracle_trie::{MultiOracleTrie, MultiOracleTrieDump};
+use dlc_trie::multi_oracle_trie_with_diff::{MultiOracleTrieWithDiff, MultiOracleTrieWithDiffDump};
+use dlc_trie::multi_trie::{MultiTrieDump, MultiTrieNodeData, OraclePairParams, TrieNodeInfo};
+use dlc_trie::{OracleNumericInfo, RangeInfo};
+use lightning::ln::msgs::DecodeError;
+use lightning::util::ser::{Readable, Writeable, Writer};
+
+/// The current version of the compact format.
+const COMPACT_FORMAT_VERSION: u8 = 2;
+/// The oldest version of the compact format that can still be read.
+const MIN_COMPACT_FORMAT_VERSION: u8 = 1;
+/// Flag set when the payload is compressed using deflate.
+const FLAG_COMPRESSED: u8 = 1;
+/// All the flags known by this implementation.
+const KNOWN_FLAGS: u8 = FLAG_COMPRESSED;
+/// Upper bound on the size of a decoded payload, to avoid allocating
+/// arbitrary amounts of memory when reading corrupted data.
+const MAX_PAYLOAD_SIZE: u64 = 1 << 32;
+
+const NODE_HAS_DATA: u8 = 1;
+const NODE_HAS_CHILDREN: u8 = 2;
+
+#[derive(Default)]
+struct CompactWriter {
+    buf: Vec<u8>,
+    prev_cet_index: usize,
+    prev_adaptor_index: usize,
+}
+
+impl CompactWriter {
+    fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn write_varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0x80);
+        }
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_varint(value as u64);
+    }
+
+    fn write_delta(&mut self, value: usize, reference: usize) {
+        let delta = value as i64 - reference as i64;
+        self.write_varint(((delta << 1) ^ (delta >> 63)) as u64);
+    }
+
+    fn write_option_usize(&mut self, value: Option<usize>) {
+        self.write_varint(value.map_or(0, |x| x as u64 + 1));
+    }
+
+    fn write_usizes(&mut self, values: &[usize]) {
+        self.write_usize(values.len());
+        for value in values {
+            self.write_usize(*value);
+        }
+    }
+
+    fn write_range_info(&mut self, range_info: &RangeInfo) {
+        self.write_delta(range_info.cet_index, self.prev_cet_index);
+        self.write_delta(range_info.adaptor_index, self.prev_adaptor_index);
+        self.prev_cet_index = range_info.cet_index;
+        self.prev_adaptor_index = range_info.adaptor_index;
+    }
+
+    fn write_range_infos(&mut self, range_infos: &[RangeInfo]) {
+        self.write_usize(range_infos.len());
+        for range_info in range_infos {
+            self.write_range_info(range_info);
+        }
+    }
+
+    fn write_trie_node_infos(&mut self, infos: &[TrieNodeInfo]) {
+        self.write_usize(infos.len());
+        for info in infos {
+            self.write_usize(info.trie_index);
+            self.write_usize(info.store_index);
+        }
+    }
+
+    fn write_oracle_numeric_infos(&mut self, infos: &OracleNumericInfo) {
+        self.write_usize(infos.base);
+        self.write_usizes(&infos.nb_digits);
+    }
+
+    fn write_digit_trie<T, F>(&mut self, dump: &DigitTrieDump<T>, write_data: &F)
+    where
+        T: Clone,
+        F: Fn(&mut Self, &T),
+    {
+        self.write_usize(dump.base);
+        self.write_option_usize(dump.root);
+        self.write_usize(dump.node_data.len());
+        for (index, node) in dump.node_data.iter().enumerate() {
+            self.write_node(index, dump.base, node, write_data);
+        }
+    }
+
+    fn write_node<T, F>(
+        &mut self,
+        index: usize,
+        base: usize,
+        node: &DigitNodeData<T>,
+        write_data: &F,
+    ) where
+        F: Fn(&mut Self, &T),
+    {
+        let mut header = 0;
+        if node.data.is_some() {
+            header |= NODE_HAS_DATA;
+        }
+        if node.children.is_some() {
+            header |= NODE_HAS_CHILDREN;
+        }
+        self.write_u8(header);
+        self.write_usizes(&node.prefix);
+        if let Some(children) = &node.children {
+            let mut bitmap = vec![0u8; (base + 7) / 8];
+            for (i, child) in children.iter().enumerate() {
+                if child.is_some() {
+                    bitmap[i / 8] |= 1 << (i % 8);
+                }
+            }
+            self.buf.extend_from_slice(&bitmap);
+            for child in children.iter().flatten() {
+                self.write_delta(*child, index);
+            }
+        }
+        if let Some(data) = &node.data {
+            write_data(self, data);
+        }
+    }
+
+    fn write_multi_trie(&mut self, dump: &MultiTrieDump<RangeInfo>) {
+        self.write_usize(dump.node_data.len());
+        for node in &dump.node_data {
+            match node {
+                MultiTrieNodeData::Leaf(d) => {
+                    self.write_u8(0);
+                    self.write_digit_trie(d, &|w: &mut Self, x: &RangeInfo| w.write_range_info(x));
+                }
+                MultiTrieNodeData::Node(d) => {
+                    self.write_u8(1);
+                    self.write_digit_trie(d, &|w: &mut Self, x: &Vec<TrieNodeInfo>| {
+                        w.write_trie_node_infos(x)
+                    });
+                }
+            }
+        }
+        self.write_usize(dump.nb_tries);
+        self.write_usize(dump.nb_required);
+        self.write_usize(dump.min_support_exp);
+        self.write_usize(dump.max_error_exp);
+        self.write_u8(dump.maximize_coverage as u8);
+        self.write_oracle_numeric_infos(&dump.oracle_numeric_infos);
+        self.write_usize(dump.pair_params.len());
+        for params in &dump.pair_params {
+            self.write_usize(params.first);
+            self.write_usize(params.second);
+            self.write_usize(params.min_support_exp);
+            self.write_usize(params.max_error_exp);
+        }
+    }
+}
+
+struct CompactReader<'a> {
+    buf: &'a [u8],
+    version: u8,
+    prev_cet_index: usize,
+    prev_adaptor_index: usize,
+}
+
+impl<'a> CompactReader<'a> {
+    fn new(buf: &'a [u8], version: u8) -> Self {
+        CompactReader {
+            buf,
+            version,
+            prev_cet_index: 0,
+            prev_adaptor_index: 0,
+        }
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        let (first, rest) = self.buf.split_first().ok_or(DecodeError::ShortRead)?;
+        self.buf = rest;
+        Ok(*first)
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.buf.len() < len {
+            return Err(DecodeError::ShortRead);
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    fn read_varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::InvalidValue)
+    }
+
+    fn read_usize(&mut self) -> Result<usize, DecodeError> {
+        Ok(self.read_varint()? as usize)
+    }
+
+    fn read_len(&mut self) -> Result<usize, DecodeError> {
+        let len = self.read_usize()?;
+        // Each element takes at least one byte.
+        if len > self.buf.len() {
+            return Err(DecodeError::ShortRead);
+        }
+        Ok(len)
+    }
+
+    fn read_delta(&mut self, reference: usize) -> Result<usize, DecodeError> {
+        let encoded = self.read_varint()?;
+        let delta = ((encoded >> 1) as i64) ^ -((encoded & 1) as i64);
+        let value = reference as i64 + delta;
+        if value < 0 {
+            return Err(DecodeError::InvalidValue);
+        }
+        Ok(value as usize)
+    }
+
+    fn read_option_usize(&mut self) -> Result<Option<usize>, DecodeError> {
+        Ok(match self.read_usize()? {
+            0 => None,
+            x => Some(x - 1),
+        })
+    }
+
+    fn read_usizes(&mut self) -> Result<Vec<usize>, DecodeError> {
+        let len = self.read_len()?;
+        (0..len).map(|_| self.read_usize()).collect()
+    }
+
+    fn read_bool(&mut self) -> Result<bool, DecodeError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::InvalidValue),
+        }
+    }
+
+    fn read_range_info(&mut self) -> Result<RangeInfo, DecodeError> {
+        let cet_index = self.read_delta(self.prev_cet_index)?;
+        let adaptor_index = self.read_delta(self.prev_adaptor_index)?;
+        self.prev_cet_index = cet_index;
+        self.prev_adaptor_index = adaptor_index;
+        Ok(RangeInfo {
+            cet_index,
+            adaptor_index,
+        })
+    }
+
+    fn read_range_infos(&mut self) -> Result<Vec<RangeInfo>, DecodeError> {
+        let len = self.read_len()?;
+        (0..len).map(|_| self.read_range_info()).collect()
+    }
+
+    fn read_trie_node_infos(&mut self) -> Result<Vec<TrieNodeInfo>, DecodeError> {
+        let len = self.read_len()?;
+        (0..len)
+            .map(|_| {
+                Ok(TrieNodeInfo {
+                    trie_index: self.read_usize()?,
+                    store_index: self.read_usize()?,
+                })
+            })
+            .collect()
+    }
+
+    fn read_oracle_numeric_infos(&mut self) -> Result<OracleNumericInfo, DecodeError> {
+        Ok(OracleNumericInfo {
+            base: self.read_usize()?,
+            nb_digits: self.read_usizes()?,
+        })
+    }
+
+    fn read_digit_trie<T, F>(&mut self, read_data: &F) -> Result<DigitTrieDump<T>, DecodeError>
+    where
+        T: Clone,
+        F: Fn(&mut Self) -> Result<T, DecodeError>,
+    {
+        let base = self.read_usize()?;
+        if base < 2 {
+            return Err(DecodeError::InvalidValue);
+        }
+        let root = self.read_option_usize()?;
+        let len = self.read_len()?;
+        let node_data = (0..len)
+            .map(|index| self.read_node(index, base, read_data))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DigitTrieDump {
+            node_data,
+            root,
+            base,
+        })
+    }
+
+    fn read_node<T, F>(
+        &mut self,
+        index: usize,
+        base: usize,
+        read_data: &F,
+    ) -> Result<DigitNodeData<T>, DecodeError>
+    where
+        F: Fn(&mut Self) -> Result<T, DecodeError>,
+    {
+        let header = self.read_u8()?;
+        if header & !(NODE_HAS_DATA | NODE_HAS_CHILDREN) != 0 {
+            return Err(DecodeError::InvalidValue);
+        }
+        let prefix = self.read_usizes()?;
+        let children = if header & NODE_HAS_CHILDREN != 0 {
+            let bitmap = self.read_bytes((base + 7) / 8)?;
+            let mut children = Vec::with_capacity(base);
+            for i in 0..base {
+                if bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                    children.push(Some(self.read_delta(index)?));
+                } else {
+                    children.push(None);
+                }
+            }
+            Some(children)
+        } else {
+            None
+        };
+        let data = if header & NODE_HAS_DATA != 0 {
+            Some(read_data(self)?)
+        } else {
+            None
+        };
+        Ok(DigitNodeData {
+            data,
+            prefix,
+            children,
+        })
+    }
+
+    fn read_range_infos_trie(&mut self) -> Result<DigitTrieDump<Vec<RangeInfo>>, DecodeError> {
+        self.read_digit_trie(&|r: &mut Self| r.read_range_infos())
+    }
+
+    fn read_multi_trie(&mut self) -> Result<MultiTrieDump<RangeInfo>, DecodeError> {
+        let len = self.read_len()?;
+        let node_data = (0..len)
+            .map(|_| match self.read_u8()? {
+                0 => Ok(MultiTrieNodeData::Leaf(
+                    self.read_digit_trie(&|r: &mut Self| r.read_range_info())?,
+                )),
+                1 => Ok(MultiTrieNodeData::Node(
+                    self.read_digit_trie(&|r: &mut Self| r.read_trie_node_infos())?,
+                )),
+                _ => Err(DecodeError::InvalidValue),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MultiTrieDump {
+            node_data,
+            nb_tries: self.read_usize()?,
+            nb_required: self.read_usize()?,
+            min_support_exp: self.read_usize()?,
+            max_error_exp: self.read_usize()?,
+            maximize_coverage: self.read_bool()?,
+            oracle_numeric_infos: self.read_oracle_numeric_infos()?,
+            pair_params: self.read_pair_params()?,
+        })
+    }
+
+    fn read_pair_params(&mut self) -> Result<Vec<OraclePairParams>, DecodeError> {
+        if self.version < 2 {
+            return Ok(Vec::new());
+        }
+        let len = self.read_len()?;
+        (0..len)
+            .map(|_| {
+                Ok(OraclePairParams {
+                    first: self.read_usize()?,
+                    second: self.read_usize()?,
+                    min_support_exp: self.read_usize()?,
+                    max_error_exp: self.read_usize()?,
+                })
+            })
+            .collect()
+    }
+
+    fn finish(self) -> Result<(), DecodeError> {
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodeError::InvalidValue)
+        }
+    }
+}
+
+#[cfg(feature = "compression")]
+fn compress(payload: Vec<u8>) -> Result<(Vec<u8>, u8), std::io::Error> {
+    use std::io::Write;
+    let mut encoder =
+        flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&payload)?;
+    Ok((encoder.finish()?, FLAG_COMPRESSED))
+}
+
+#[cfg(not(feature = "compression"))]
+fn compress(payload: Vec<u8>) -> Result<(Vec<u8>, u8), std::io::Error> {
+    Ok((payload, 0))
+}
+
+#[cfg(feature = "compression")]
+fn decompress(payload: Vec<u8>) -> Result<Vec<u8>, DecodeError> {
+    let mut decompressed = Vec::new();
+    flate2::read::DeflateDecoder::new(&payload[..])
+        .take(MAX_PAYLOAD_SIZE)
+        .read_to_end(&mut decompressed)
+        .map_err(|_| DecodeError::InvalidValue)?;
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_: Vec<u8>) -> Result<Vec<u8>, DecodeError> {
+    Err(DecodeError::UnknownRequiredFeature)
+}
+
+fn write_payload<W: Writer>(payload: Vec<u8>, writer: &mut W) -> Result<(), std::io::Error> {
+    let (payload, flags) = compress(payload)?;
+    COMPACT_FORMAT_VERSION.write(writer)?;
+    flags.write(writer)?;
+    BigSize(payload.len() as u64).write(writer)?;
+    writer.write_all(&payload)
+}
+
+fn read_payload<R: Read>(reader: &mut R) -> Result<(u8, Vec<u8>), DecodeError> {
+    let version: u8 = Readable::read(reader)?;
+    if !(MIN_COMPACT_FORMAT_VERSION..=COMPACT_FORMAT_VERSION).contains(&version) {
+        return Err(DecodeError::UnknownVersion);
+    }
+    let flags: u8 = Readable::read(reader)?;
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(DecodeError::UnknownRequiredFeature);
+    }
+    let len: BigSize = Readable::read(reader)?;
+    if len.0 > MAX_PAYLOAD_SIZE {
+        return Err(DecodeError::InvalidValue);
+    }
+    let mut payload = Vec::new();
+    reader.take(len.0).read_to_end(&mut payload)?;
+    if payload.len() as u64 != len.0 {
+        return Err(DecodeError::ShortRead);
+    }
+    let payload = if flags & FLAG_COMPRESSED != 0 {
+        decompress(payload)?
+    } else {
+        payload
+    };
+    Ok((version, payload))
+}
+
+pub(crate) fn write_multi_oracle_trie<W: Writer>(
+    trie: &MultiOracleTrie,
+    writer: &mut W,
+) -> Result<(), std::io::Error> {
+    let dump = trie.dump();
+    let mut w = CompactWriter::default();
+    w.write_digit_trie(
+        &dump.digit_trie_dump,
+        &|w: &mut CompactWriter, x: &Vec<RangeInfo>| w.write_range_infos(x),
+    );
+    w.write_usize(dump.threshold);
+    w.write_oracle_numeric_infos(&dump.oracle_numeric_infos);
+    match &dump.extra_cover_trie_dump {
+        None => w.write_u8(0),
+        Some(extra) => {
+            w.write_u8(1);
+            w.write_multi_trie(extra);
+        }
+    }
+    write_payload(w.buf, writer)
+}
+
+pub(crate) fn read_multi_oracle_trie<R: Read>(
+    reader: &mut R,
+) -> Result<MultiOracleTrie, DecodeError> {
+    let (version, payload) = read_payload(reader)?;
+    let mut r = CompactReader::new(&payload, version);
+    let digit_trie_dump = r.read_range_infos_trie()?;
+    let threshold = r.read_usize()?;
+    let oracle_numeric_infos = r.read_oracle_numeric_infos()?;
+    let extra_cover_trie_dump = if r.read_bool()? {
+        Some(r.read_multi_trie()?)
+    } else {
+        None
+    };
+    r.finish()?;
+    Ok(MultiOracleTrie::from_dump(MultiOracleTrieDump {
+        digit_trie_dump,
+        threshold,
+        oracle_numeric_infos,
+        extra_cover_trie_dump,
+    }))
+}
+
+pub(crate) fn write_multi_oracle_trie_with_diff<W: Writer>(
+    trie: &MultiOracleTrieWithDiff,
+    writer: &mut W,
+) -> Result<(), std::io::Error> {
+    let dump = trie.dump();
+    let mut w = CompactWriter::default();
+    w.write_multi_trie(&dump.multi_trie_dump);
+    w.write_oracle_numeric_infos(&dump.oracle_numeric_infos);
+    write_payload(w.buf, writer)
+}
+
+pub(crate) fn read_multi_oracle_trie_with_diff<R: Read>(
+    reader: &mut R,
+) -> Result<MultiOracleTrieWithDiff, DecodeError> {
+    let (version, payload) = read_payload(reader)?;
+    let mut r = CompactReader::new(&payload, version);
+    let multi_trie_dump = r.read_multi_trie()?;
+    let oracle_numeric_infos = r.read_oracle_numeric_infos()?;
+    r.finish()?;
+    Ok(MultiOracleTrieWithDiff::from_dump(
+        MultiOracleTrieWithDiffDump {
+            multi_trie_dump,
+            oracle_numeric_infos,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::ser::Serializable;
+    use crate::contract::ser::{multi_oracle_trie_dump, multi_oracle_trie_with_diff_dump};
+    use crate::contract::AdaptorInfo;
+    use dlc::{Payout, RangePayout};
+    use dlc_trie::DlcTrie;
+
+    fn get_range_payouts() -> Vec<RangePayout> {
+        (0..20)
+            .map(|i| RangePayout {
+                start: i * 50,
+                count: if i == 19 { 74 } else { 50 },
+                payout: Payout {
+                    offer: i as u64 * 1000,
+                    accept: 20000 - i as u64 * 1000,
+                },
+            })
+            .collect()
+    }
+
+    fn get_oracle_numeric_infos(nb_digits: Vec<usize>) -> OracleNumericInfo {
+        OracleNumericInfo { base: 2, nb_digits }
+    }
+
+    fn round_trip(adaptor_info: &AdaptorInfo) -> AdaptorInfo {
+        let serialized = adaptor_info.serialize().unwrap();
+        AdaptorInfo::deserialize(&mut std::io::Cursor::new(&serialized)).unwrap()
+    }
+
+    fn legacy_trie_bytes(trie: &MultiOracleTrie) -> Vec<u8> {
+        let mut buf = Vec::new();
+        multi_oracle_trie_dump::write(&trie.dump(), &mut buf).unwrap();
+        buf
+    }
+
+    fn legacy_trie_with_diff_bytes(trie: &MultiOracleTrieWithDiff) -> Vec<u8> {
+        let mut buf = Vec::new();
+        multi_oracle_trie_with_diff_dump::write(&trie.dump(), &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn multi_oracle_trie_round_trip_test() {
+        let mut trie =
+            MultiOracleTrie::new(&get_oracle_numeric_infos(vec![10, 11, 10]), 2).unwrap();
+        trie.generate(0, &get_range_payouts()).unwrap();
+        let adaptor_info = AdaptorInfo::Numerical(trie);
+
+        let decoded = round_trip(&adaptor_info);
+
+        match (&adaptor_info, decoded) {
+            (AdaptorInfo::Numerical(expected), AdaptorInfo::Numerical(actual)) => {
+                let legacy = legacy_trie_bytes(expected);
+                assert_eq!(legacy, legacy_trie_bytes(&actual));
+                assert!(adaptor_info.serialize().unwrap().len() < legacy.len());
+            }
+            _ => panic!("Unexpected adaptor info type"),
+        }
+    }
+
+    #[test]
+    fn multi_oracle_trie_with_diff_round_trip_test() {
+        let pair_params = vec![OraclePairParams {
+            first: 0,
+            second: 2,
+            min_support_exp: 2,
+            max_error_exp: 5,
+        }];
+        let mut trie = MultiOracleTrieWithDiff::new_with_pair_params(
+            &get_oracle_numeric_infos(vec![10, 10, 10]),
+            2,
+            2,
+            4,
+            pair_params.clone(),
+        )
+        .unwrap();
+        trie.generate(0, &get_range_payouts()).unwrap();
+        let adaptor_info = AdaptorInfo::NumericalWithDifference(trie);
+
+        let decoded = round_trip(&adaptor_info);
+
+        match (&adaptor_info, decoded) {
+            (
+                AdaptorInfo::NumericalWithDifference(expected),
+                AdaptorInfo::NumericalWithDifference(actual),
+            ) => {
+                let legacy = legacy_trie_with_diff_bytes(expected);
+                assert_eq!(legacy, legacy_trie_with_diff_bytes(&actual));
+                assert_eq!(pair_params, actual.dump().multi_trie_dump.pair_params);
+                assert!(adaptor_info.serialize().unwrap().len() * 4 < legacy.len());
+            }
+            _ => panic!("Unexpected adaptor info type"),
+        }
+    }
+
+    #[test]
+    fn legacy_format_can_be_read_test() {
+        let mut trie = MultiOracleTrie::new(&get_oracle_numeric_infos(vec![10]), 1).unwrap();
+        trie.generate(0, &get_range_payouts()).unwrap();
+        let legacy = legacy_trie_bytes(&trie);
+        let mut serialized = vec![0u8];
+        serialized.extend_from_slice(&legacy);
+
+        match AdaptorInfo::deserialize(&mut std::io::Cursor::new(&serialized)).unwrap() {
+            AdaptorInfo::Numerical(decoded) => assert_eq!(legacy, legacy_trie_bytes(&decoded)),
+            _ => panic!("Unexpected adaptor info type"),
+        }
+    }
+
+    #[test]
+    fn unknown_version_is_rejected_test() {
+        let mut trie = MultiOracleTrie::new(&get_oracle_numeric_infos(vec![10]), 1).unwrap();
+        trie.generate(0, &get_range_payouts()).unwrap();
+        let mut serialized = AdaptorInfo::Numerical(trie).serialize().unwrap();
+        serialized[1] = COMPACT_FORMAT_VERSION + 1;
+
+        assert!(matches!(
+            AdaptorInfo::deserialize(&mut std::io::Cursor::new(&serialized)),
+            Err(DecodeError::UnknownVersion)
+        ));
+    }
+}
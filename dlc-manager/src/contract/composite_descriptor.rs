@@ -0,0 +1,586 @@
+//! #CompositeDescriptor
+
+use super::contract_info::OracleIndexAndPrefixLength;
+use super::numerical_descriptor::validate_digit_announcements;
+use super::AdaptorInfo;
+use crate::error::Error;
+use crate::payout_curve::{PayoutFunction, RoundingIntervals};
+use bitcoin::{Script, Transaction};
+use dlc::{Payout, RangePayout};
+use dlc_messages::oracle_msgs::OracleAnnouncement;
+use dlc_trie::digit_decomposition::{compose_value, decompose_value};
+use dlc_trie::{OracleNumericInfo, RangeInfo};
+use secp256k1_zkp::{All, EcdsaAdaptorSignature, PublicKey, Secp256k1, SecretKey};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The operation used to combine the outcomes of the two events of a composite
+/// contract into the outcome given to the payout function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum CompositeOperation {
+    /// `first - second + max_second`, where `max_second` is the largest
+    /// outcome of the second event, so that the result is never negative.
+    Difference,
+    /// `first * ratio_precision / second`, truncated. The largest combined
+    /// outcome is used when `second` is zero.
+    Ratio,
+}
+
+/// Contains information about a contract based on the combination of the
+/// outcomes of two numerical events, each attested by its own oracle, for
+/// example the spread between the prices reported by two exchanges.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct CompositeDescriptor {
+    /// How the outcomes of the two events are combined.
+    pub operation: CompositeOperation,
+    /// The factor by which ratios are multiplied before being truncated.
+    /// Ignored for differences.
+    pub ratio_precision: u64,
+    /// The function representing the payouts over the combined outcomes.
+    pub payout_function: PayoutFunction,
+    /// Rounding intervals enabling reducing the precision of the payout values.
+    pub rounding_intervals: RoundingIntervals,
+    /// Base and number of digits of the oracles attesting to the first and
+    /// second events, in that order.
+    pub oracle_numeric_infos: OracleNumericInfo,
+}
+
+/// A region of the outcome space of a composite contract, given by a digit
+/// prefix for each event, within which all outcomes have the same payout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompositeLeaf {
+    /// The prefix of the digits of the outcome of the first event.
+    pub first_prefix: Vec<usize>,
+    /// The prefix of the digits of the outcome of the second event.
+    pub second_prefix: Vec<usize>,
+    /// The CET and adaptor signature used for the outcomes of the region.
+    pub range_info: RangeInfo,
+}
+
+/// Adaptor information of a composite contract, containing the regions
+/// covering the outcome space in adaptor signature order.
+#[derive(Clone, Debug)]
+pub struct CompositeAdaptorInfo {
+    /// The regions covering the outcome space.
+    pub leaves: Vec<CompositeLeaf>,
+}
+
+impl CompositeDescriptor {
+    /// Returns the largest outcome of the first and second events.
+    fn get_event_max_values(&self) -> Result<(u64, u64), Error> {
+        let base = self.oracle_numeric_infos.base as u64;
+        let max_value = |nb_digits: usize| {
+            base.checked_pow(nb_digits as u32)
+                .map(|x| x - 1)
+                .ok_or_else(|| Error::InvalidParameters("Could not compute max value".to_string()))
+        };
+        match self.oracle_numeric_infos.nb_digits.as_slice() {
+            [first, second] => Ok((max_value(*first)?, max_value(*second)?)),
+            _ => Err(Error::InvalidParameters(
+                "Composite descriptor requires exactly two events.".to_string(),
+            )),
+        }
+    }
+
+    /// Returns the largest combined outcome.
+    pub fn get_max_outcome(&self) -> Result<u64, Error> {
+        let (first_max, second_max) = self.get_event_max_values()?;
+        match self.operation {
+            CompositeOperation::Difference => first_max.checked_add(second_max),
+            CompositeOperation::Ratio => first_max.checked_mul(self.ratio_precision),
+        }
+        .ok_or_else(|| Error::InvalidParameters("Combined outcome range is too large.".to_string()))
+    }
+
+    /// Combines the outcomes of the two events into the outcome of the payout
+    /// function. Outcomes exceeding the range of an event are considered as
+    /// equal to its maximum value.
+    pub fn combine_outcomes(&self, first: u64, second: u64) -> Result<u64, Error> {
+        let (first_max, second_max) = self.get_event_max_values()?;
+        let first = u64::min(first, first_max);
+        let second = u64::min(second, second_max);
+        match self.operation {
+            CompositeOperation::Difference => Ok(first + second_max - second),
+            CompositeOperation::Ratio if second == 0 => self.get_max_outcome(),
+            CompositeOperation::Ratio => {
+                Ok((first as u128 * self.ratio_precision as u128 / second as u128) as u64)
+            }
+        }
+    }
+
+    /// Decodes the attested digits of both events and returns the combined
+    /// outcome, if both oracles attested.
+    pub fn get_combined_outcome(
+        &self,
+        outcomes: &[(usize, Vec<usize>)],
+    ) -> Result<Option<u64>, Error> {
+        let base = self.oracle_numeric_infos.base;
+        let find = |index: usize| {
+            outcomes
+                .iter()
+                .find(|(i, _)| *i == index)
+                .map(|(_, digits)| compose_value(digits, base) as u64)
+        };
+        match (find(0), find(1)) {
+            (Some(first), Some(second)) => Ok(Some(self.combine_outcomes(first, second)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Validate that the descriptor covers all possible combined outcomes.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.operation == CompositeOperation::Ratio && self.ratio_precision == 0 {
+            return Err(Error::InvalidParameters(
+                "Ratio precision must be greater than zero.".to_string(),
+            ));
+        }
+        let max_outcome = self.get_max_outcome()?;
+        self.rounding_intervals.validate_for_outcomes(max_outcome)?;
+        self.payout_function.validate(max_outcome)
+    }
+
+    /// Validate that the given announcements match the oracle numeric infos of
+    /// the descriptor. Differences are only meaningful between events using
+    /// the same unit and precision, which is not required for ratios.
+    pub fn validate_announcements(
+        &self,
+        announcements: &[OracleAnnouncement],
+    ) -> Result<(), Error> {
+        validate_digit_announcements(
+            &self.oracle_numeric_infos,
+            announcements,
+            self.operation == CompositeOperation::Difference,
+            &[],
+        )
+    }
+
+    /// Returns the set of RangePayout for the descriptor generated from the
+    /// payout function.
+    pub fn get_range_payouts(&self, total_collateral: u64) -> Result<Vec<RangePayout>, Error> {
+        self.payout_function
+            .to_range_payouts(total_collateral, &self.rounding_intervals)
+    }
+
+    /// Returns the set of payouts for the descriptor, one per CET.
+    pub fn get_payouts(&self, total_collateral: u64) -> Result<Vec<Payout>, Error> {
+        Ok(self
+            .get_range_payouts(total_collateral)?
+            .iter()
+            .map(|x| x.payout.clone())
+            .collect())
+    }
+
+    /// Splits the outcome space into regions within which the payout is
+    /// constant, each region being identified by a digit prefix for each
+    /// event. Regions are generated in a deterministic order so that both
+    /// parties assign the same adaptor signature index to each of them.
+    pub fn get_leaves(
+        &self,
+        total_collateral: u64,
+        adaptor_index_start: usize,
+    ) -> Result<Vec<CompositeLeaf>, Error> {
+        let range_payouts = self.get_range_payouts(total_collateral)?;
+        let base = self.oracle_numeric_infos.base;
+        let mut leaves = Vec::new();
+        // Requiring at least one digit from each oracle ensures that both
+        // attestations are needed to close the contract.
+        for first in 0..base {
+            for second in 0..base {
+                self.split_region(&range_payouts, vec![first], vec![second], &mut leaves)?;
+            }
+        }
+
+        for (i, leaf) in leaves.iter_mut().enumerate() {
+            leaf.range_info.adaptor_index = adaptor_index_start + i;
+        }
+
+        Ok(leaves)
+    }
+
+    fn split_region(
+        &self,
+        range_payouts: &[RangePayout],
+        first_prefix: Vec<usize>,
+        second_prefix: Vec<usize>,
+        leaves: &mut Vec<CompositeLeaf>,
+    ) -> Result<(), Error> {
+        let base = self.oracle_numeric_infos.base;
+        let nb_digits = &self.oracle_numeric_infos.nb_digits;
+        let (first_low, first_high) = get_prefix_bounds(&first_prefix, base, nb_digits[0]);
+        let (second_low, second_high) = get_prefix_bounds(&second_prefix, base, nb_digits[1]);
+
+        // Combined outcomes increase with the first outcome and decrease with
+        // the second one, so the corners give the bounds of the region.
+        let low = self.combine_outcomes(first_low, second_high)?;
+        let high = self.combine_outcomes(first_high, second_low)?;
+        let index = range_payouts
+            .binary_search_by(|x| {
+                if ((x.start + x.count) as u64) <= low {
+                    std::cmp::Ordering::Less
+                } else if x.start as u64 > low {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .map_err(|_| {
+                Error::InvalidParameters(format!("No payout for combined outcome {}.", low))
+            })?;
+        let range = &range_payouts[index];
+        if high < (range.start + range.count) as u64 {
+            leaves.push(CompositeLeaf {
+                first_prefix,
+                second_prefix,
+                range_info: RangeInfo {
+                    cet_index: index,
+                    adaptor_index: 0,
+                },
+            });
+            return Ok(());
+        }
+
+        // A region containing a single pair of outcomes always has a single
+        // payout, so at least one of the prefixes can be extended here.
+        let split_first = nb_digits[0] - first_prefix.len() >= nb_digits[1] - second_prefix.len();
+        for digit in 0..base {
+            let (mut first, mut second) = (first_prefix.clone(), second_prefix.clone());
+            if split_first {
+                first.push(digit);
+            } else {
+                second.push(digit);
+            }
+            self.split_region(range_payouts, first, second, leaves)?;
+        }
+
+        Ok(())
+    }
+
+    /// Generate the adaptor info and adaptor signatures for the contract.
+    pub fn get_adaptor_info(
+        &self,
+        secp: &Secp256k1<All>,
+        total_collateral: u64,
+        fund_priv_key: &SecretKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+        precomputed_points: &[Vec<Vec<PublicKey>>],
+        cets: &[Transaction],
+        adaptor_index_start: usize,
+    ) -> Result<(AdaptorInfo, Vec<EcdsaAdaptorSignature>), Error> {
+        let adaptor_info = CompositeAdaptorInfo {
+            leaves: self.get_leaves(total_collateral, adaptor_index_start)?,
+        };
+        let sigs = adaptor_info.sign(
+            secp,
+            fund_priv_key,
+            funding_script_pubkey,
+            fund_output_value,
+            cets,
+            precomputed_points,
+        )?;
+        Ok((AdaptorInfo::Composite(adaptor_info), sigs))
+    }
+
+    /// Verify the given set of adaptor signatures and generate the adaptor info.
+    pub fn verify_and_get_adaptor_info(
+        &self,
+        secp: &Secp256k1<All>,
+        total_collateral: u64,
+        fund_pubkey: &PublicKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+        precomputed_points: &[Vec<Vec<PublicKey>>],
+        cets: &[Transaction],
+        adaptor_sigs: &[EcdsaAdaptorSignature],
+        adaptor_sig_start: usize,
+    ) -> Result<(AdaptorInfo, usize), Error> {
+        let adaptor_info = CompositeAdaptorInfo {
+            leaves: self.get_leaves(total_collateral, adaptor_sig_start)?,
+        };
+        let index = adaptor_info.verify(
+            secp,
+            fund_pubkey,
+            funding_script_pubkey,
+            fund_output_value,
+            adaptor_sigs,
+            cets,
+            precomputed_points,
+        )?;
+        Ok((AdaptorInfo::Composite(adaptor_info), index))
+    }
+}
+
+impl CompositeAdaptorInfo {
+    fn get_adaptor_points(
+        &self,
+        precomputed_points: &[Vec<Vec<PublicKey>>],
+    ) -> Result<Vec<PublicKey>, Error> {
+        if precomputed_points.len() != 2 {
+            return Err(Error::InvalidParameters(
+                "Composite contracts require points for exactly two oracles.".to_string(),
+            ));
+        }
+        self.leaves
+            .iter()
+            .map(|leaf| {
+                let keys: Vec<&PublicKey> = precomputed_points[0]
+                    .iter()
+                    .zip(leaf.first_prefix.iter())
+                    .chain(precomputed_points[1].iter().zip(leaf.second_prefix.iter()))
+                    .map(|(points, digit)| &points[*digit])
+                    .collect();
+                PublicKey::combine_keys(&keys).map_err(Error::from)
+            })
+            .collect()
+    }
+
+    /// Generate the adaptor signatures for each region, in order.
+    pub fn sign(
+        &self,
+        secp: &Secp256k1<All>,
+        fund_privkey: &SecretKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+        cets: &[Transaction],
+        precomputed_points: &[Vec<Vec<PublicKey>>],
+    ) -> Result<Vec<EcdsaAdaptorSignature>, Error> {
+        let sig_hashes =
+            dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
+        let adaptor_points = self.get_adaptor_points(precomputed_points)?;
+        self.leaves
+            .iter()
+            .zip(adaptor_points.iter())
+            .map(|(leaf, adaptor_point)| {
+                sig_hashes
+                    .get(leaf.range_info.cet_index)
+                    .map(|sig_hash| {
+                        dlc::create_cet_adaptor_sig_from_sig_hash(
+                            secp,
+                            sig_hash,
+                            adaptor_point,
+                            fund_privkey,
+                        )
+                    })
+                    .ok_or_else(|| {
+                        Error::InvalidParameters("Missing CET for composite region.".to_string())
+                    })
+            })
+            .collect()
+    }
+
+    /// Verify the adaptor signatures of each region, returning the index
+    /// following the last verified signature.
+    pub fn verify(
+        &self,
+        secp: &Secp256k1<All>,
+        fund_pubkey: &PublicKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+        adaptor_sigs: &[EcdsaAdaptorSignature],
+        cets: &[Transaction],
+        precomputed_points: &[Vec<Vec<PublicKey>>],
+    ) -> Result<usize, Error> {
+        let sig_hashes =
+            dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
+        let adaptor_points = self.get_adaptor_points(precomputed_points)?;
+        let mut next_index = 0;
+        for (leaf, adaptor_point) in self.leaves.iter().zip(adaptor_points.iter()) {
+            let RangeInfo {
+                cet_index,
+                adaptor_index,
+            } = leaf.range_info;
+            let (sig_hash, adaptor_sig) =
+                match (sig_hashes.get(cet_index), adaptor_sigs.get(adaptor_index)) {
+                    (Some(sig_hash), Some(adaptor_sig)) => (sig_hash, adaptor_sig),
+                    _ => {
+                        return Err(Error::InvalidParameters(
+                            "Missing CET or adaptor signature for composite region.".to_string(),
+                        ))
+                    }
+                };
+            dlc::verify_cet_adaptor_sig_from_sig_hash(
+                secp,
+                adaptor_sig,
+                sig_hash,
+                adaptor_point,
+                fund_pubkey,
+            )?;
+            next_index = adaptor_index + 1;
+        }
+
+        Ok(next_index)
+    }
+
+    /// Returns the region containing the attested outcomes, together with the
+    /// number of signatures required from each oracle.
+    pub fn look_up(
+        &self,
+        outcomes: &[(usize, Vec<usize>)],
+    ) -> Option<(OracleIndexAndPrefixLength, RangeInfo)> {
+        let find = |index: usize| outcomes.iter().find(|(i, _)| *i == index).map(|x| &x.1);
+        let (first, second) = (find(0)?, find(1)?);
+        let leaf = self.leaves.iter().find(|leaf| {
+            first.starts_with(&leaf.first_prefix) && second.starts_with(&leaf.second_prefix)
+        })?;
+
+        Some((
+            vec![(0, leaf.first_prefix.len()), (1, leaf.second_prefix.len())],
+            leaf.range_info.clone(),
+        ))
+    }
+}
+
+/// Returns the smallest and largest values starting with the given digits.
+fn get_prefix_bounds(prefix: &[usize], base: usize, nb_digits: usize) -> (u64, u64) {
+    let remaining = (base as u64).pow((nb_digits - prefix.len()) as u32);
+    let low = compose_value(prefix, base) as u64 * remaining;
+    (low, low + remaining - 1)
+}
+
+/// Returns the digits of the given value, capped at the maximum value that can
+/// be represented.
+pub(super) fn decompose_capped(value: u64, base: usize, nb_digits: usize) -> Vec<usize> {
+    let max_value = (base as u64).pow(nb_digits as u32) - 1;
+    decompose_value(u64::min(value, max_value) as usize, base, nb_digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payout_curve::{
+        PayoutFunctionPiece, PayoutPoint, PolynomialPayoutCurvePiece, RoundingInterval,
+    };
+
+    const TOTAL_COLLATERAL: u64 = 1000;
+
+    fn point(event_outcome: u64, outcome_payout: u64) -> PayoutPoint {
+        PayoutPoint {
+            event_outcome,
+            outcome_payout,
+            extra_precision: 0,
+        }
+    }
+
+    fn get_descriptor(operation: CompositeOperation, max_outcome: u64) -> CompositeDescriptor {
+        let linear = |left: PayoutPoint, right: PayoutPoint| {
+            PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+                PolynomialPayoutCurvePiece::new(vec![left, right]).unwrap(),
+            )
+        };
+        CompositeDescriptor {
+            operation,
+            ratio_precision: 4,
+            payout_function: PayoutFunction::new(vec![
+                linear(point(0, 0), point(10, 0)),
+                linear(point(10, 0), point(20, TOTAL_COLLATERAL)),
+                linear(
+                    point(20, TOTAL_COLLATERAL),
+                    point(max_outcome, TOTAL_COLLATERAL),
+                ),
+            ])
+            .unwrap(),
+            rounding_intervals: RoundingIntervals {
+                intervals: vec![RoundingInterval {
+                    begin_interval: 0,
+                    rounding_mod: 100,
+                }],
+            },
+            oracle_numeric_infos: OracleNumericInfo {
+                base: 2,
+                nb_digits: vec![4, 5],
+            },
+        }
+    }
+
+    fn check_leaves(descriptor: &CompositeDescriptor) {
+        descriptor.validate().unwrap();
+        let range_payouts = descriptor.get_range_payouts(TOTAL_COLLATERAL).unwrap();
+        let leaves = descriptor.get_leaves(TOTAL_COLLATERAL, 3).unwrap();
+        let adaptor_info = CompositeAdaptorInfo {
+            leaves: leaves.clone(),
+        };
+
+        assert!(leaves
+            .iter()
+            .enumerate()
+            .all(|(i, leaf)| leaf.range_info.adaptor_index == i + 3));
+
+        for first in 0..16 {
+            for second in 0..32 {
+                let outcomes = vec![
+                    (0, decompose_value(first, 2, 4)),
+                    (1, decompose_value(second, 2, 5)),
+                ];
+                let matching = leaves
+                    .iter()
+                    .filter(|leaf| {
+                        outcomes[0].1.starts_with(&leaf.first_prefix)
+                            && outcomes[1].1.starts_with(&leaf.second_prefix)
+                    })
+                    .count();
+                assert_eq!(1, matching);
+
+                let (prefix_lengths, range_info) = adaptor_info.look_up(&outcomes).unwrap();
+                assert!(prefix_lengths.iter().all(|(_, len)| *len > 0));
+                let combined = descriptor.get_combined_outcome(&outcomes).unwrap().unwrap();
+                let range = &range_payouts[range_info.cet_index];
+                assert!(
+                    range.start as u64 <= combined && combined < (range.start + range.count) as u64
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn difference_leaves_cover_outcome_space_test() {
+        let descriptor = get_descriptor(CompositeOperation::Difference, 46);
+        assert_eq!(46, descriptor.get_max_outcome().unwrap());
+        assert_eq!(31, descriptor.combine_outcomes(15, 15).unwrap());
+        check_leaves(&descriptor);
+    }
+
+    #[test]
+    fn ratio_leaves_cover_outcome_space_test() {
+        let descriptor = get_descriptor(CompositeOperation::Ratio, 60);
+        assert_eq!(60, descriptor.get_max_outcome().unwrap());
+        assert_eq!(6, descriptor.combine_outcomes(12, 8).unwrap());
+        assert_eq!(60, descriptor.combine_outcomes(1, 0).unwrap());
+        check_leaves(&descriptor);
+    }
+
+    #[test]
+    fn look_up_requires_both_oracles_test() {
+        let descriptor = get_descriptor(CompositeOperation::Difference, 46);
+        let adaptor_info = CompositeAdaptorInfo {
+            leaves: descriptor.get_leaves(TOTAL_COLLATERAL, 0).unwrap(),
+        };
+
+        assert!(adaptor_info
+            .look_up(&[(0, decompose_value(3, 2, 4))])
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_composite_descriptor_test() {
+        let mut descriptor = get_descriptor(CompositeOperation::Difference, 40);
+        descriptor
+            .validate()
+            .expect_err("payout function should cover combined outcomes");
+
+        descriptor = get_descriptor(CompositeOperation::Ratio, 60);
+        descriptor.ratio_precision = 0;
+        descriptor
+            .validate()
+            .expect_err("ratio precision should not be zero");
+    }
+}
@@ -1,15 +1,22 @@
 //! #ContractInfo
 
+use super::composite_descriptor::decompose_capped;
+use super::point_cache;
 use super::AdaptorInfo;
 use super::ContractDescriptor;
+use super::Outcome;
 use crate::error::Error;
 use bitcoin::{Script, Transaction};
 use dlc::{OracleInfo, Payout};
 use dlc_messages::oracle_msgs::{EventDescriptor, OracleAnnouncement};
-use dlc_trie::{DlcTrie, RangeInfo};
+use dlc_trie::{
+    digit_decomposition::decompose_value, truncate_outcomes, DlcTrie, RangeInfo, TrieStats,
+};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use secp256k1_zkp::{
     hashes::sha256, All, EcdsaAdaptorSignature, Message, PublicKey, Secp256k1, SecretKey,
-    Verification,
+    Verification, XOnlyPublicKey,
 };
 
 pub(super) type OracleIndexAndPrefixLength = Vec<(usize, usize)>;
@@ -37,6 +44,8 @@ impl ContractInfo {
         match &self.contract_descriptor {
             ContractDescriptor::Enum(e) => Ok(e.get_payouts()),
             ContractDescriptor::Numerical(n) => n.get_payouts(total_collateral),
+            ContractDescriptor::Composite(c) => c.get_payouts(total_collateral),
+            ContractDescriptor::BooleanEnum(b) => Ok(b.get_payouts()),
         }
     }
 
@@ -49,6 +58,14 @@ impl ContractInfo {
             ));
         }
 
+        if let ContractDescriptor::BooleanEnum(_) = &self.contract_descriptor {
+            if self.threshold != self.oracle_announcements.len() {
+                return Err(Error::InvalidParameters(
+                    "Boolean enum contracts require attestations from all oracles.".to_string(),
+                ));
+            }
+        }
+
         self.contract_descriptor
             .validate(&self.oracle_announcements)
     }
@@ -81,6 +98,14 @@ impl ContractInfo {
                     funding_script_pubkey,
                     fund_output_value,
                 ),
+                ContractDescriptor::BooleanEnum(b) => b.get_adaptor_signatures(
+                    secp,
+                    &self.oracle_announcements,
+                    cets,
+                    fund_privkey,
+                    funding_script_pubkey,
+                    fund_output_value,
+                ),
                 _ => unreachable!(),
             },
             AdaptorInfo::Numerical(trie) => Ok(trie.sign(
@@ -99,6 +124,14 @@ impl ContractInfo {
                 cets,
                 &self.precompute_points(secp)?,
             )?),
+            AdaptorInfo::Composite(c) => c.sign(
+                secp,
+                fund_privkey,
+                funding_script_pubkey,
+                fund_output_value,
+                cets,
+                &self.precompute_points(secp)?,
+            ),
         }
     }
 
@@ -140,6 +173,27 @@ impl ContractInfo {
                 adaptor_sigs,
                 adaptor_sig_start,
             )?),
+            ContractDescriptor::Composite(c) => c.verify_and_get_adaptor_info(
+                secp,
+                total_collateral,
+                fund_pubkey,
+                funding_script_pubkey,
+                fund_output_value,
+                &self.precompute_points(secp)?,
+                cets,
+                adaptor_sigs,
+                adaptor_sig_start,
+            ),
+            ContractDescriptor::BooleanEnum(b) => b.verify_and_get_adaptor_info(
+                secp,
+                &self.oracle_announcements,
+                fund_pubkey,
+                funding_script_pubkey,
+                fund_output_value,
+                cets,
+                adaptor_sigs,
+                adaptor_sig_start,
+            ),
         }
     }
 
@@ -158,26 +212,104 @@ impl ContractInfo {
                     outcomes,
                     adaptor_sig_start,
                 ),
+                ContractDescriptor::BooleanEnum(b) => b.get_range_info_for_outcome(
+                    &self.oracle_announcements,
+                    outcomes,
+                    adaptor_sig_start,
+                ),
                 _ => unreachable!(),
             },
             AdaptorInfo::Numerical(n) => {
-                let res = n.look_up(&outcomes_to_digits(outcomes))?;
+                let res = n.look_up(&self.get_truncated_digits_outcomes(outcomes))?;
                 Some((
                     res.1.iter().map(|(x, y)| (*x, y.len())).collect(),
                     res.0.clone(),
                 ))
             }
             AdaptorInfo::NumericalWithDifference(n) => {
-                let res = n.multi_trie.look_up(&outcomes_to_digits(outcomes))?;
+                let res = n
+                    .multi_trie
+                    .look_up(&self.get_truncated_digits_outcomes(outcomes))?;
 
                 Some((
                     res.1.iter().map(|(x, y)| (*x, y.len())).collect(),
                     res.0.clone(),
                 ))
             }
+            AdaptorInfo::Composite(c) => match &self.contract_descriptor {
+                ContractDescriptor::Composite(d) => {
+                    c.look_up(&outcomes_to_digits(outcomes, d.oracle_numeric_infos.base))
+                }
+                _ => unreachable!(),
+            },
         }
     }
 
+    /// Returns the `RangeInfo` of the CET that would be used to close the
+    /// contract if all the oracles attested to the given outcome, if any.
+    /// Numerical values exceeding the range of an oracle are considered as
+    /// attested to its maximum value.
+    pub fn get_range_info_for_outcome_value(
+        &self,
+        adaptor_info: &AdaptorInfo,
+        outcome: &Outcome,
+    ) -> Result<Option<RangeInfo>, Error> {
+        let attested_outcomes = match (&self.contract_descriptor, outcome) {
+            (ContractDescriptor::Enum(_), Outcome::Enum(o)) => {
+                vec![vec![o.clone()]; self.oracle_announcements.len()]
+            }
+            (ContractDescriptor::Numerical(n), Outcome::Numerical(value)) => {
+                let oracle_numeric_infos = n.get_normalized_numeric_infos()?;
+                let base = oracle_numeric_infos.base;
+                oracle_numeric_infos
+                    .nb_digits
+                    .iter()
+                    .enumerate()
+                    .map(|(i, nb_digits)| {
+                        let max_value = base.checked_pow(*nb_digits as u32).ok_or_else(|| {
+                            Error::InvalidParameters("Could not compute max value".to_string())
+                        })? - 1;
+                        let value = std::cmp::min(*value as usize, max_value);
+                        // Truncated digits are ignored, so any value can be used.
+                        let truncated = n.truncated_digits.get(i).cloned().unwrap_or(0);
+                        Ok(decompose_value(value, base, *nb_digits)
+                            .iter()
+                            .chain(std::iter::repeat(&0).take(truncated))
+                            .map(|d| d.to_string())
+                            .collect())
+                    })
+                    .collect::<Result<Vec<Vec<String>>, Error>>()?
+            }
+            (ContractDescriptor::Composite(c), Outcome::Composite(first, second)) => {
+                let base = c.oracle_numeric_infos.base;
+                [*first, *second]
+                    .iter()
+                    .zip(c.oracle_numeric_infos.nb_digits.iter())
+                    .map(|(value, nb_digits)| {
+                        decompose_capped(*value, base, *nb_digits)
+                            .iter()
+                            .map(|d| d.to_string())
+                            .collect()
+                    })
+                    .collect()
+            }
+            (ContractDescriptor::BooleanEnum(_), Outcome::EnumCombination(o)) => {
+                o.iter().map(|x| vec![x.clone()]).collect()
+            }
+            _ => {
+                return Err(Error::InvalidParameters(
+                    "Outcome type does not match the contract descriptor.".to_string(),
+                ))
+            }
+        };
+
+        let outcomes = attested_outcomes.iter().enumerate().collect::<Vec<_>>();
+
+        Ok(self
+            .get_range_info_for_outcome(adaptor_info, &outcomes, 0)
+            .map(|(_, range_info)| range_info))
+    }
+
     /// Verifies the given adaptor signatures are valid with respect to the given
     /// adaptor info.
     pub fn verify_adaptor_info(
@@ -204,8 +336,18 @@ impl ContractInfo {
                 adaptor_sigs,
                 adaptor_sig_start,
             )?),
+            ContractDescriptor::BooleanEnum(b) => b.verify_adaptor_info(
+                secp,
+                &self.oracle_announcements,
+                fund_pubkey,
+                funding_script_pubkey,
+                fund_output_value,
+                cets,
+                adaptor_sigs,
+                adaptor_sig_start,
+            ),
             ContractDescriptor::Numerical(_) => match adaptor_info {
-                AdaptorInfo::Enum => unreachable!(),
+                AdaptorInfo::Enum | AdaptorInfo::Composite(_) => unreachable!(),
                 AdaptorInfo::Numerical(trie) => Ok(trie.verify(
                     secp,
                     fund_pubkey,
@@ -225,6 +367,29 @@ impl ContractInfo {
                     &self.precompute_points(secp)?,
                 )?),
             },
+            ContractDescriptor::Composite(_) => match adaptor_info {
+                AdaptorInfo::Composite(c) => c.verify(
+                    secp,
+                    fund_pubkey,
+                    funding_script_pubkey,
+                    fund_output_value,
+                    adaptor_sigs,
+                    cets,
+                    &self.precompute_points(secp)?,
+                ),
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    /// Returns information about the size of the trie used to store the adaptor
+    /// information of a numerical contract, or `None` for other contracts.
+    pub fn get_trie_stats(&self, total_collateral: u64) -> Result<Option<TrieStats>, Error> {
+        match &self.contract_descriptor {
+            ContractDescriptor::Numerical(n) => {
+                Ok(Some(n.get_trie_stats(total_collateral, self.threshold)?))
+            }
+            _ => Ok(None),
         }
     }
 
@@ -252,6 +417,14 @@ impl ContractInfo {
                     cets,
                 )?)
             }
+            ContractDescriptor::BooleanEnum(b) => b.get_adaptor_info(
+                secp,
+                &self.oracle_announcements,
+                fund_priv_key,
+                funding_script_pubkey,
+                fund_output_value,
+                cets,
+            ),
             ContractDescriptor::Numerical(n) => Ok(n.get_adaptor_info(
                 secp,
                 total_collateral,
@@ -263,6 +436,35 @@ impl ContractInfo {
                 cets,
                 adaptor_index_start,
             )?),
+            ContractDescriptor::Composite(c) => c.get_adaptor_info(
+                secp,
+                total_collateral,
+                fund_priv_key,
+                funding_script_pubkey,
+                fund_output_value,
+                &self.precompute_points(secp)?,
+                cets,
+                adaptor_index_start,
+            ),
+        }
+    }
+
+    /// Converts the given attested outcomes to digits, dropping the digits
+    /// that are ignored by the contract descriptor if any.
+    fn get_truncated_digits_outcomes(
+        &self,
+        outcomes: &[(usize, &Vec<String>)],
+    ) -> Vec<(usize, Vec<usize>)> {
+        match &self.contract_descriptor {
+            ContractDescriptor::Numerical(n) => {
+                let digits = outcomes_to_digits(outcomes, n.oracle_numeric_infos.base);
+                if n.truncated_digits.is_empty() {
+                    digits
+                } else {
+                    truncate_outcomes(&digits, &n.truncated_digits)
+                }
+            }
+            _ => unreachable!(),
         }
     }
 
@@ -284,21 +486,17 @@ impl ContractInfo {
                                 "Number of digits and nonces must be equal".to_string(),
                             ));
                         }
-                        let mut d_points = Vec::with_capacity(nb_digits);
-                        for nonce in nonces {
-                            let mut points = Vec::with_capacity(base);
-                            for j in 0..base {
-                                let msg = Message::from_hashed_data::<sha256::Hash>(
-                                    j.to_string().as_bytes(),
-                                );
-                                let sig_point = dlc::secp_utils::schnorrsig_compute_sig_point(
-                                    secp, pubkey, nonce, &msg,
-                                )?;
-                                points.push(sig_point);
-                            }
-                            d_points.push(points);
-                        }
-                        Ok(d_points)
+                        #[cfg(not(feature = "parallel"))]
+                        let nonces_iter = nonces.iter();
+                        #[cfg(feature = "parallel")]
+                        let nonces_iter = nonces.par_iter();
+                        nonces_iter
+                            .map(|nonce| {
+                                point_cache::get_or_compute(pubkey, nonce, base, || {
+                                    compute_digit_points(secp, pubkey, nonce, base)
+                                })
+                            })
+                            .collect()
                     }
                     _ => Err(Error::InvalidParameters(
                         "Expected digit decomposition event.".to_string(),
@@ -309,22 +507,40 @@ impl ContractInfo {
     }
 }
 
-fn get_digits_outcome(input: &[String]) -> Result<Vec<usize>, crate::error::Error> {
+/// Computes the signature points for each possible value of a digit attested
+/// with the given nonce.
+fn compute_digit_points<C: Verification>(
+    secp: &Secp256k1<C>,
+    pubkey: &XOnlyPublicKey,
+    nonce: &XOnlyPublicKey,
+    base: usize,
+) -> Result<Vec<PublicKey>, Error> {
+    (0..base)
+        .map(|j| {
+            let msg = Message::from_hashed_data::<sha256::Hash>(j.to_string().as_bytes());
+            Ok(dlc::secp_utils::schnorrsig_compute_sig_point(
+                secp, pubkey, nonce, &msg,
+            )?)
+        })
+        .collect()
+}
+
+fn get_digits_outcome(input: &[String], base: usize) -> Result<Vec<usize>, crate::error::Error> {
     input
         .iter()
-        .map(|x| {
-            x.parse::<usize>().map_err(|_| {
-                crate::error::Error::InvalidParameters(
-                    "Invalid outcome, {} is not a valid number.".to_string(),
-                )
-            })
+        .map(|x| match x.parse::<usize>() {
+            Ok(d) if d < base => Ok(d),
+            _ => Err(crate::error::Error::InvalidParameters(format!(
+                "Invalid outcome, {} is not a valid digit in base {}.",
+                x, base
+            ))),
         })
         .collect::<Result<Vec<usize>, crate::error::Error>>()
 }
 
-fn outcomes_to_digits(outcomes: &[(usize, &Vec<String>)]) -> Vec<(usize, Vec<usize>)> {
+fn outcomes_to_digits(outcomes: &[(usize, &Vec<String>)], base: usize) -> Vec<(usize, Vec<usize>)> {
     outcomes
         .iter()
-        .filter_map(|(x, path)| Some((*x, get_digits_outcome(path).ok()?)))
+        .filter_map(|(x, path)| Some((*x, get_digits_outcome(path, base).ok()?)))
         .collect()
 }
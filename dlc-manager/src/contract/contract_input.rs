@@ -3,12 +3,15 @@
 use crate::error::Error;
 
 use super::ContractDescriptor;
+use bitcoin::Script;
+use dlc::PayoutOutput;
+use dlc_messages::OfferMetadata;
 use secp256k1_zkp::XOnlyPublicKey;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 /// Oracle information required for the initial creation of a contract.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -17,9 +20,12 @@ use serde::{Deserialize, Serialize};
 pub struct OracleInput {
     /// The set of public keys for each of the used oracles.
     pub public_keys: Vec<XOnlyPublicKey>,
-    /// The id of the event being used for the contract. Note that at the moment
-    /// a single event id is used, while multiple ids would be preferable.
+    /// The id of the event being used for the contract.
     pub event_id: String,
+    /// The ids of the events used for each oracle, for contracts combining
+    /// different events. When empty, `event_id` is used for all oracles.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub event_ids: Vec<String>,
     /// The number of oracles that need to provide attestations satisfying the
     /// contract conditions to be able to close the contract.
     pub threshold: u16,
@@ -40,12 +46,23 @@ impl OracleInput {
             ));
         }
 
+        if !self.event_ids.is_empty() && self.event_ids.len() != self.public_keys.len() {
+            return Err(Error::InvalidParameters(
+                "OracleInput must have one event id per oracle.".to_string(),
+            ));
+        }
+
         Ok(())
     }
+
+    /// Returns the id of the event used by the oracle at the given index.
+    pub fn get_event_id(&self, index: usize) -> &str {
+        self.event_ids.get(index).unwrap_or(&self.event_id)
+    }
 }
 
 /// Represents the contract specifications.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -58,7 +75,21 @@ pub struct ContractInputInfo {
     pub oracles: OracleInput,
 }
 
-#[derive(Debug)]
+/// An additional output receiving a share of the payout of the offering party.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct PayoutOutputInput {
+    /// The script pubkey receiving the share of the payout.
+    pub script_pubkey: Script,
+    /// The share of the payout sent to the output, in basis points.
+    pub share_bps: u16,
+}
+
+#[derive(Clone, Debug)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -70,11 +101,30 @@ pub struct ContractInput {
     pub offer_collateral: u64,
     /// The collateral for the accepting party.
     pub accept_collateral: u64,
-    /// The fee rate used to construct the transactions.
+    /// The fee rate used to construct the transactions. When zero, the fee
+    /// rate estimated by the blockchain provider is used.
     pub fee_rate: u64,
     /// The set of contract that make up the DLC (a single DLC can be based
     /// on multiple contracts).
     pub contract_infos: Vec<ContractInputInfo>,
+    /// Human readable information to attach to the offer.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Option<OfferMetadata>,
+    /// The script pubkey receiving the payout of the offering party, e.g. to
+    /// send it directly to cold storage. When not set, the one returned by
+    /// [`crate::Wallet::get_payout_script_pubkey`] is used.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub payout_script_pubkey: Option<Script>,
+    /// The script pubkey receiving the change of the funding inputs of the
+    /// offering party. When not set, the one returned by
+    /// [`crate::Wallet::get_change_script_pubkey`] is used.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub change_script_pubkey: Option<Script>,
+    /// Additional outputs receiving a share of the payout of the offering
+    /// party, the remainder being sent to its payout script pubkey. Not
+    /// supported for contracts within channels.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub additional_payout_outputs: Vec<PayoutOutputInput>,
 }
 
 impl ContractInput {
@@ -86,8 +136,51 @@ impl ContractInput {
             ));
         }
 
-        for contract_info in &self.contract_infos {
+        if let Some(metadata) = &self.metadata {
+            metadata.validate().map_err(|_| {
+                Error::InvalidParameters("Offer metadata fields are too long".to_string())
+            })?;
+        }
+
+        let is_empty = |x: &Option<Script>| x.as_ref().map_or(false, |x| x.is_empty());
+        if is_empty(&self.payout_script_pubkey) || is_empty(&self.change_script_pubkey) {
+            return Err(Error::InvalidParameters(
+                "Payout and change script pubkeys cannot be empty".to_string(),
+            ));
+        }
+
+        // Serial ids are only assigned when creating the offer, a placeholder
+        // is used to validate the shares and script pubkeys.
+        let payout_outputs = self
+            .additional_payout_outputs
+            .iter()
+            .enumerate()
+            .map(|(i, x)| PayoutOutput {
+                script_pubkey: x.script_pubkey.clone(),
+                serial_id: i as u64 + 1,
+                share_bps: x.share_bps,
+            })
+            .collect::<Vec<_>>();
+        dlc::validate_payout_outputs(&payout_outputs, 0).map_err(|_| {
+            Error::InvalidParameters("Invalid additional payout outputs".to_string())
+        })?;
+
+        for (i, contract_info) in self.contract_infos.iter().enumerate() {
             contract_info.oracles.validate()?;
+            let (rounding_intervals, max_outcome) = match &contract_info.contract_descriptor {
+                ContractDescriptor::Enum(_) | ContractDescriptor::BooleanEnum(_) => continue,
+                ContractDescriptor::Numerical(n) => (&n.rounding_intervals, n.get_max_outcome()?),
+                ContractDescriptor::Composite(c) => (&c.rounding_intervals, c.get_max_outcome()?),
+            };
+            rounding_intervals
+                .validate_for_outcomes(max_outcome)
+                .map_err(|e| match e {
+                    Error::InvalidParameters(s) => Error::InvalidParameters(format!(
+                        "Invalid rounding intervals for contract info {}: {}",
+                        i, s
+                    )),
+                    e => e,
+                })?;
         }
 
         dlc::util::validate_fee_rate(self.fee_rate)
@@ -101,6 +194,12 @@ mod tests {
     use secp256k1_zkp::{KeyPair, SECP256K1};
 
     use crate::contract::enum_descriptor::EnumDescriptor;
+    use crate::contract::numerical_descriptor::NumericalDescriptor;
+    use crate::payout_curve::{
+        PayoutFunction, PayoutFunctionPiece, PayoutPoint, PolynomialPayoutCurvePiece,
+        RoundingInterval, RoundingIntervals,
+    };
+    use dlc_trie::OracleNumericInfo;
 
     use super::*;
 
@@ -137,9 +236,14 @@ mod tests {
                         .0,
                     ],
                     event_id: "1234".to_string(),
+                    event_ids: Vec::new(),
                     threshold: 1,
                 },
             }],
+            metadata: None,
+            payout_script_pubkey: None,
+            change_script_pubkey: None,
+            additional_payout_outputs: Vec::new(),
         }
     }
 
@@ -167,6 +271,41 @@ mod tests {
             .expect_err("the contract input to be invalid.");
     }
 
+    #[test]
+    fn invalid_additional_payout_outputs_contract_input_is_not_valid() {
+        let mut input = get_base_input();
+        let script_pubkey = bitcoin::Address::p2wpkh(
+            &bitcoin::PublicKey::new(secp256k1_zkp::PublicKey::from_secret_key(
+                SECP256K1,
+                &secp256k1_zkp::ONE_KEY,
+            )),
+            bitcoin::Network::Regtest,
+        )
+        .unwrap()
+        .script_pubkey();
+        input.additional_payout_outputs = vec![PayoutOutputInput {
+            script_pubkey,
+            share_bps: 6000,
+        }];
+        input.validate().expect("the contract input to be valid.");
+
+        input
+            .additional_payout_outputs
+            .push(input.additional_payout_outputs[0].clone());
+        input
+            .validate()
+            .expect_err("the contract input to be invalid.");
+    }
+
+    #[test]
+    fn empty_payout_script_pubkey_contract_input_is_not_valid() {
+        let mut input = get_base_input();
+        input.payout_script_pubkey = Some(Script::new());
+        input
+            .validate()
+            .expect_err("the contract input to be invalid.");
+    }
+
     #[test]
     fn no_public_keys_oracle_input_contract_input_is_not_valid() {
         let mut input = get_base_input();
@@ -184,4 +323,60 @@ mod tests {
             .validate()
             .expect_err("the contract input to be invalid.");
     }
+
+    #[test]
+    fn overlapping_rounding_intervals_contract_input_is_not_valid() {
+        let mut input = get_base_input();
+        let oracles = OracleInput {
+            public_keys: input.contract_infos[0].oracles.public_keys.clone(),
+            event_id: "5678".to_string(),
+            event_ids: Vec::new(),
+            threshold: 1,
+        };
+        let rounding_interval = |begin_interval| RoundingInterval {
+            begin_interval,
+            rounding_mod: 10,
+        };
+        input.contract_infos.push(ContractInputInfo {
+            contract_descriptor: ContractDescriptor::Numerical(NumericalDescriptor {
+                payout_function: PayoutFunction::new(vec![
+                    PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+                        PolynomialPayoutCurvePiece::new(vec![
+                            PayoutPoint {
+                                event_outcome: 0,
+                                outcome_payout: 0,
+                                extra_precision: 0,
+                            },
+                            PayoutPoint {
+                                event_outcome: 1023,
+                                outcome_payout: 3000000,
+                                extra_precision: 0,
+                            },
+                        ])
+                        .unwrap(),
+                    ),
+                ])
+                .unwrap(),
+                rounding_intervals: RoundingIntervals {
+                    intervals: vec![
+                        rounding_interval(0),
+                        rounding_interval(500),
+                        rounding_interval(500),
+                    ],
+                },
+                difference_params: None,
+                oracle_numeric_infos: OracleNumericInfo {
+                    base: 2,
+                    nb_digits: vec![10],
+                },
+                truncated_digits: Vec::new(),
+            }),
+            oracles,
+        });
+
+        let err = input
+            .validate()
+            .expect_err("the contract input to be invalid.");
+        assert!(err.to_string().contains("contract info 1"));
+    }
 }
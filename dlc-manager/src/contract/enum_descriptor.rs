@@ -121,19 +121,19 @@ impl EnumDescriptor {
         adaptor_sigs: &[EcdsaAdaptorSignature],
         adaptor_sig_start: usize,
     ) -> Result<usize, dlc::Error> {
+        let sig_hashes =
+            dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
         let mut adaptor_sig_index = adaptor_sig_start;
         let mut callback =
             |adaptor_point: &PublicKey, cet_index: usize| -> Result<(), dlc::Error> {
                 let sig = adaptor_sigs[adaptor_sig_index];
                 adaptor_sig_index += 1;
-                dlc::verify_cet_adaptor_sig_from_point(
+                dlc::verify_cet_adaptor_sig_from_sig_hash(
                     secp,
                     &sig,
-                    &cets[cet_index],
+                    &sig_hashes[cet_index],
                     adaptor_point,
                     fund_pubkey,
-                    funding_script_pubkey,
-                    fund_output_value,
                 )?;
                 Ok(())
             };
@@ -206,17 +206,17 @@ impl EnumDescriptor {
         funding_script_pubkey: &Script,
         fund_output_value: u64,
     ) -> Result<Vec<EcdsaAdaptorSignature>, Error> {
+        let sig_hashes =
+            dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
         let mut adaptor_sigs = Vec::new();
         let mut callback =
             |adaptor_point: &PublicKey, cet_index: usize| -> Result<(), dlc::Error> {
-                let sig = dlc::create_cet_adaptor_sig_from_point(
+                let sig = dlc::create_cet_adaptor_sig_from_sig_hash(
                     secp,
-                    &cets[cet_index],
+                    &sig_hashes[cet_index],
                     adaptor_point,
                     fund_privkey,
-                    funding_script_pubkey,
-                    fund_output_value,
-                )?;
+                );
                 adaptor_sigs.push(sig);
                 Ok(())
             };
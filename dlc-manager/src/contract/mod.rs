@@ -2,13 +2,14 @@
 
 use crate::error::Error;
 use crate::ContractId;
-use bitcoin::{Address, Transaction};
+use bitcoin::{Address, Script, Transaction, Txid};
 use dlc_messages::{
     oracle_msgs::{EventDescriptor, OracleAnnouncement, OracleAttestation},
     AcceptDlc, FundingInput, SignDlc,
 };
 use dlc_trie::multi_oracle_trie::MultiOracleTrie;
 use dlc_trie::multi_oracle_trie_with_diff::MultiOracleTrieWithDiff;
+use dlc_trie::RangeInfo;
 use secp256k1_zkp::PublicKey;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -17,14 +18,21 @@ use signed_contract::SignedContract;
 use self::utils::unordered_equal;
 
 pub mod accepted_contract;
+pub mod boolean_descriptor;
+mod compact_ser;
+pub mod composite_descriptor;
 pub mod contract_info;
 pub mod contract_input;
 pub mod enum_descriptor;
+pub mod multi_party_contract;
+pub mod novation_contract;
 pub mod numerical_descriptor;
 pub mod offered_contract;
+pub mod point_cache;
 pub mod ser;
 pub mod signed_contract;
 pub(crate) mod utils;
+pub mod versioned_ser;
 
 #[derive(Clone)]
 /// Enum representing the possible states of a DLC.
@@ -51,6 +59,32 @@ pub enum Contract {
     Rejected(offered_contract::OfferedContract),
 }
 
+/// The state of a [`Contract`], used to filter the contracts retrieved from
+/// the storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContractState {
+    /// See [`Contract::Offered`].
+    Offered,
+    /// See [`Contract::Accepted`].
+    Accepted,
+    /// See [`Contract::Signed`].
+    Signed,
+    /// See [`Contract::Confirmed`].
+    Confirmed,
+    /// See [`Contract::PreClosed`].
+    PreClosed,
+    /// See [`Contract::Closed`].
+    Closed,
+    /// See [`Contract::Refunded`].
+    Refunded,
+    /// See [`Contract::FailedAccept`].
+    FailedAccept,
+    /// See [`Contract::FailedSign`].
+    FailedSign,
+    /// See [`Contract::Rejected`].
+    Rejected,
+}
+
 impl std::fmt::Debug for Contract {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let state = match self {
@@ -101,6 +135,52 @@ impl Contract {
         }
     }
 
+    /// Returns the state of the contract.
+    pub fn get_state(&self) -> ContractState {
+        match self {
+            Contract::Offered(_) => ContractState::Offered,
+            Contract::Accepted(_) => ContractState::Accepted,
+            Contract::Signed(_) => ContractState::Signed,
+            Contract::Confirmed(_) => ContractState::Confirmed,
+            Contract::PreClosed(_) => ContractState::PreClosed,
+            Contract::Closed(_) => ContractState::Closed,
+            Contract::Refunded(_) => ContractState::Refunded,
+            Contract::FailedAccept(_) => ContractState::FailedAccept,
+            Contract::FailedSign(_) => ContractState::FailedSign,
+            Contract::Rejected(_) => ContractState::Rejected,
+        }
+    }
+
+    /// Returns the ids of the oracle events the contract depends on. Returns
+    /// an empty list for closed contracts as they do not retain their
+    /// contract information.
+    pub fn get_oracle_event_ids(&self) -> Vec<String> {
+        let contract_info = match self {
+            Contract::Offered(o) | Contract::Rejected(o) => &o.contract_info,
+            Contract::Accepted(a) => &a.offered_contract.contract_info,
+            Contract::Signed(s) | Contract::Confirmed(s) | Contract::Refunded(s) => {
+                &s.accepted_contract.offered_contract.contract_info
+            }
+            Contract::PreClosed(c) => {
+                &c.signed_contract
+                    .accepted_contract
+                    .offered_contract
+                    .contract_info
+            }
+            Contract::FailedAccept(f) => &f.offered_contract.contract_info,
+            Contract::FailedSign(f) => &f.accepted_contract.offered_contract.contract_info,
+            Contract::Closed(_) => return Vec::new(),
+        };
+        let mut event_ids = contract_info
+            .iter()
+            .flat_map(|info| info.oracle_announcements.iter())
+            .map(|announcement| announcement.oracle_event.event_id.clone())
+            .collect::<Vec<_>>();
+        event_ids.sort();
+        event_ids.dedup();
+        event_ids
+    }
+
     /// Returns the public key of the counter party's node.
     pub fn get_counter_party_id(&self) -> PublicKey {
         match self {
@@ -136,6 +216,21 @@ pub struct FundingInputInfo {
     pub address: Option<Address>,
 }
 
+/// Information about an output jointly owned by both parties through a 2-of-2
+/// multisig, used as the sole funding input of a contract.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct SharedFundingInput {
+    /// The 2-of-2 multisig witness script locking the shared output.
+    pub funding_script: Script,
+    /// The public key of the local party within the multisig script.
+    pub own_pubkey: PublicKey,
+}
+
 /// Information about a contract that failed while verifying an accept message.
 #[derive(Clone)]
 pub struct FailedAcceptContract {
@@ -184,6 +279,103 @@ pub struct ClosedContract {
     pub counter_party_id: PublicKey,
     /// The profit and loss for the given contract
     pub pnl: i64,
+    /// The time at which the contract was closed, as a unix timestamp. Zero
+    /// for contracts closed before it was recorded.
+    pub closed_at: u64,
+}
+
+/// A compact record of a contract in a final state, kept once the contract
+/// has been pruned from the storage.
+#[derive(Clone, Debug)]
+pub struct ArchivedContract {
+    /// The id of the contract.
+    pub contract_id: ContractId,
+    /// The temporary id of the contract.
+    pub temporary_contract_id: ContractId,
+    /// The public key of the counter-party's node.
+    pub counter_party_id: PublicKey,
+    /// The final state of the contract.
+    pub state: ContractState,
+    /// The id of the fund transaction if it was broadcast and is known.
+    pub fund_txid: Option<Txid>,
+    /// The id of the transaction that closed the contract if any.
+    pub closing_txid: Option<Txid>,
+    /// The profit and loss for the contract.
+    pub pnl: i64,
+    /// The unix timestamp from which the age of the contract is measured:
+    /// the closing time for closed contracts, the refund locktime for the
+    /// others.
+    pub final_time: u64,
+}
+
+impl ArchivedContract {
+    /// Creates an archival record for the given contract, returning `None`
+    /// if the contract is not in a final state.
+    pub fn from_contract(contract: &Contract) -> Option<ArchivedContract> {
+        let (fund_txid, closing_txid, pnl, final_time) = match contract {
+            Contract::Closed(c) => (
+                None,
+                c.signed_cet.as_ref().map(|cet| cet.txid()),
+                c.pnl,
+                c.closed_at,
+            ),
+            Contract::Refunded(s) => {
+                let accepted_contract = &s.accepted_contract;
+                let refund = &accepted_contract.dlc_transactions.refund;
+                (
+                    Some(accepted_contract.dlc_transactions.fund.txid()),
+                    Some(refund.txid()),
+                    accepted_contract.compute_pnl(refund),
+                    accepted_contract.offered_contract.refund_locktime as u64,
+                )
+            }
+            Contract::FailedAccept(f) => (None, None, 0, f.offered_contract.refund_locktime as u64),
+            Contract::FailedSign(f) => (
+                None,
+                None,
+                0,
+                f.accepted_contract.offered_contract.refund_locktime as u64,
+            ),
+            Contract::Rejected(o) => (None, None, 0, o.refund_locktime as u64),
+            _ => return None,
+        };
+        Some(ArchivedContract {
+            contract_id: contract.get_id(),
+            temporary_contract_id: contract.get_temporary_id(),
+            counter_party_id: contract.get_counter_party_id(),
+            state: contract.get_state(),
+            fund_txid,
+            closing_txid,
+            pnl,
+            final_time,
+        })
+    }
+}
+
+/// An outcome that the oracle(s) of a contract can attest to.
+#[derive(Clone, Debug)]
+pub enum Outcome {
+    /// An outcome of an enumeration event.
+    Enum(String),
+    /// The value of a numerical event.
+    Numerical(u64),
+    /// The values of the first and second events of a composite contract.
+    Composite(u64, u64),
+    /// The outcomes of the enumeration events of each oracle of a contract
+    /// based on a boolean combination of them.
+    EnumCombination(Vec<String>),
+}
+
+/// Information about the CET that would be used to close a contract for a
+/// given outcome.
+#[derive(Clone, Debug)]
+pub struct CetForOutcome {
+    /// The index of the CET within the contract transactions.
+    pub cet_index: usize,
+    /// The range info associated with the outcome.
+    pub range_info: RangeInfo,
+    /// The (unsigned) CET.
+    pub cet: Transaction,
 }
 
 /// Information about the adaptor signatures and the CET for which they are
@@ -197,9 +389,13 @@ pub enum AdaptorInfo {
     /// For numerical outcome DLC where oracles are allowed to diverge to some
     /// extent in the outcome value, a trie of trie is used to store the information.
     NumericalWithDifference(MultiOracleTrieWithDiff),
+    /// For composite DLC, the regions of the combined outcome space are stored.
+    Composite(composite_descriptor::CompositeAdaptorInfo),
 }
 
-/// The descriptor of a contract.
+/// The descriptor of a contract. In JSON, it is represented as an object with a
+/// single `enum`, `numerical`, `composite` or `booleanEnum` field containing the descriptor, see
+/// [`crate::payout_curve`] for the representation of numerical payouts.
 #[derive(Clone, Debug)]
 #[cfg_attr(
     feature = "serde",
@@ -211,6 +407,10 @@ pub enum ContractDescriptor {
     Enum(enum_descriptor::EnumDescriptor),
     /// Case for numerical outcome DLC.
     Numerical(numerical_descriptor::NumericalDescriptor),
+    /// Case for DLC based on the combination of two numerical events.
+    Composite(composite_descriptor::CompositeDescriptor),
+    /// Case for DLC based on a boolean combination of enumeration events.
+    BooleanEnum(boolean_descriptor::BooleanEnumDescriptor),
 }
 
 impl ContractDescriptor {
@@ -219,6 +419,17 @@ impl ContractDescriptor {
         match self {
             ContractDescriptor::Enum(_) => None,
             ContractDescriptor::Numerical(n) => n.difference_params.clone(),
+            ContractDescriptor::Composite(_) | ContractDescriptor::BooleanEnum(_) => None,
+        }
+    }
+
+    /// Whether the payouts of the contract are computed using hyperbola
+    /// payout curve pieces.
+    pub(crate) fn has_hyperbola_pieces(&self) -> bool {
+        match self {
+            ContractDescriptor::Numerical(n) => n.payout_function.has_hyperbola_pieces(),
+            ContractDescriptor::Composite(c) => c.payout_function.has_hyperbola_pieces(),
+            ContractDescriptor::Enum(_) | ContractDescriptor::BooleanEnum(_) => false,
         }
     }
 
@@ -228,6 +439,11 @@ impl ContractDescriptor {
         &self,
         announcements: &Vec<OracleAnnouncement>,
     ) -> Result<(), crate::error::Error> {
+        // Each oracle attests to a different event, so the outcomes are not
+        // expected to be the same across announcements.
+        if let ContractDescriptor::BooleanEnum(b) = self {
+            return b.validate(announcements);
+        }
         let first = announcements
             .first()
             .expect("to have at least one element.");
@@ -258,15 +474,12 @@ impl ContractDescriptor {
             }
             EventDescriptor::DigitDecompositionEvent(_) => match self {
                 ContractDescriptor::Numerical(n) => {
-                    let min_nb_digits = n.oracle_numeric_infos.get_min_nb_digits();
-                    let max_value = n
-                        .oracle_numeric_infos
-                        .base
-                        .checked_pow(min_nb_digits as u32)
-                        .ok_or_else(|| {
-                            Error::InvalidParameters("Could not compute max value".to_string())
-                        })?;
-                    n.validate((max_value - 1) as u64)
+                    n.validate_announcements(announcements)?;
+                    n.validate(n.get_max_outcome()?)
+                }
+                ContractDescriptor::Composite(c) => {
+                    c.validate_announcements(announcements)?;
+                    c.validate()
                 }
                 _ => Err(Error::InvalidParameters(
                     "Event descriptor from contract and oracle differ.".to_string(),
@@ -0,0 +1,244 @@
+//! #MultiPartyContract
+
+use crate::conversion_utils::get_tx_input_infos;
+use crate::error::Error;
+use crate::ContractId;
+use bitcoin::Transaction;
+use dlc::{DlcTransactions, OracleInfo, PartyParams};
+use dlc_messages::multi_party::{MultiPartyOffer, MultiPartyOutcome, MultiPartyParams};
+use dlc_messages::{CetAdaptorSignatures, FundingSignatures};
+use secp256k1_zkp::{ecdsa::Signature, Message, PublicKey, XOnlyPublicKey};
+
+/// Contains information about a multi party contract to be offered by the
+/// coordinator.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartyContractInput {
+    /// The collateral of each party, the coordinator first.
+    pub collaterals: Vec<u64>,
+    /// The fee rate used to construct the transactions.
+    pub fee_rate: u64,
+    /// The possible outcomes of the event and the payouts of each party, in
+    /// the same order as the collaterals.
+    pub outcomes: Vec<MultiPartyOutcome>,
+    /// The public key of the oracle attesting to the outcome.
+    pub oracle_public_key: XOnlyPublicKey,
+    /// The id of the event attested by the oracle.
+    pub event_id: String,
+}
+
+impl MultiPartyContractInput {
+    /// Validates that the payouts of each outcome add up to the total
+    /// collateral and that the fee rate is not too high.
+    pub fn validate(&self) -> Result<(), Error> {
+        let nb_parties = self.collaterals.len();
+        if nb_parties < 2 || nb_parties > dlc::multi_party::MAX_PARTIES {
+            return Err(Error::InvalidParameters(format!(
+                "Number of parties must be between 2 and {}.",
+                dlc::multi_party::MAX_PARTIES
+            )));
+        }
+        if self.outcomes.is_empty() {
+            return Err(Error::InvalidParameters(
+                "At least one outcome is required.".to_string(),
+            ));
+        }
+        let total_collateral = self
+            .collaterals
+            .iter()
+            .try_fold(0u64, |acc, x| acc.checked_add(*x))
+            .ok_or_else(|| Error::InvalidParameters("Total collateral overflows.".to_string()))?;
+        for outcome in &self.outcomes {
+            let total_payout = outcome
+                .payouts
+                .iter()
+                .try_fold(0u64, |acc, x| acc.checked_add(*x));
+            if outcome.payouts.len() != nb_parties || total_payout != Some(total_collateral) {
+                return Err(Error::InvalidParameters(format!(
+                    "Payouts of outcome {} don't add up to the total collateral.",
+                    outcome.outcome
+                )));
+            }
+        }
+        dlc::util::validate_fee_rate(self.fee_rate)
+            .map_err(|_| Error::InvalidParameters("Fee rate too high.".to_string()))
+    }
+}
+
+/// The state of a [`MultiPartyContract`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum MultiPartyContractState {
+    /// The offer was sent by the coordinator, which collects the parameters
+    /// of the participants, or received by a participant.
+    Offered,
+    /// The offer was accepted by the participant, which waits for the
+    /// parameters of all parties.
+    Accepted,
+    /// The parameters of all parties are known and the signatures of the
+    /// contract transactions are being collected.
+    SetUp,
+    /// The signatures of all parties for the contract transactions are known.
+    /// The coordinator collects the signatures of the funding inputs.
+    Signed,
+    /// The fund transaction was broadcast by the coordinator.
+    Broadcast,
+    /// The fund transaction reached the required number of confirmations.
+    Confirmed,
+    /// The CET of the outcome attested by the oracle was broadcast.
+    Closed,
+    /// The refund transaction was broadcast, the oracle not having attested
+    /// to the outcome before the refund locktime.
+    Refunded,
+}
+
+/// A contract between more than two parties, established under the lead of a
+/// coordinator. The contract pays out to each party according to an outcome of
+/// an enumerated event attested by a single oracle.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartyContract {
+    /// The temporary id of the contract.
+    pub temporary_id: ContractId,
+    /// The id of the contract, equal to the temporary id until the parameters
+    /// of all parties are known.
+    pub id: ContractId,
+    /// The node id of the coordinator, `None` if we are the coordinator.
+    pub coordinator: Option<PublicKey>,
+    /// The offer of the contract, in which `party_index` is our own index.
+    pub offer: MultiPartyOffer,
+    /// The state of the contract.
+    pub state: MultiPartyContractState,
+    /// The parameters of each party, once known.
+    pub party_params: Vec<Option<MultiPartyParams>>,
+    /// The CET adaptor signatures of each party, once known.
+    pub cet_adaptor_signatures: Vec<Option<CetAdaptorSignatures>>,
+    /// The refund signature of each party, once known.
+    pub refund_signatures: Vec<Option<Signature>>,
+    /// The signatures of the funding inputs of each party, once known.
+    pub funding_signatures: Vec<Option<FundingSignatures>>,
+    /// The CET or refund transaction spending the fund output, once the
+    /// contract is closed or refunded.
+    pub closing_tx: Option<Transaction>,
+}
+
+impl MultiPartyContract {
+    /// Creates a contract in the [`MultiPartyContractState::Offered`] state
+    /// from the given offer, in which the parameters of the coordinator are
+    /// known.
+    pub fn new(offer: MultiPartyOffer, coordinator: Option<PublicKey>) -> Self {
+        let nb_parties = offer.nb_parties();
+        let mut party_params = vec![None; nb_parties];
+        party_params[0] = Some(offer.offer_params.clone());
+        MultiPartyContract {
+            temporary_id: offer.temporary_contract_id,
+            id: offer.temporary_contract_id,
+            coordinator,
+            offer,
+            state: MultiPartyContractState::Offered,
+            party_params,
+            cet_adaptor_signatures: vec![None; nb_parties],
+            refund_signatures: vec![None; nb_parties],
+            funding_signatures: vec![None; nb_parties],
+            closing_tx: None,
+        }
+    }
+
+    /// Whether we are the coordinator of the contract.
+    pub fn is_coordinator(&self) -> bool {
+        self.coordinator.is_none()
+    }
+
+    /// Returns our own index among the parties.
+    pub fn own_index(&self) -> usize {
+        self.offer.party_index as usize
+    }
+
+    /// Returns the index of the participant with the given node id, the
+    /// coordinator being at index zero.
+    pub fn get_party_index(&self, node_id: &PublicKey) -> Option<usize> {
+        if self.coordinator.as_ref() == Some(node_id) {
+            return Some(0);
+        }
+        self.offer
+            .participants
+            .iter()
+            .position(|x| x == node_id)
+            .map(|i| i + 1)
+    }
+
+    /// Returns the parameters of all parties, or an error if some are not
+    /// known yet.
+    pub fn get_party_params(&self) -> Result<Vec<PartyParams>, Error> {
+        self.party_params
+            .iter()
+            .zip(self.offer.collaterals.iter())
+            .map(|(params, collateral)| {
+                let params = params.as_ref().ok_or_else(|| {
+                    Error::InvalidState("Missing parameters of a party.".to_string())
+                })?;
+                let (inputs, input_amount) = get_tx_input_infos(&params.funding_inputs)?;
+                Ok(PartyParams {
+                    fund_pubkey: params.funding_pubkey,
+                    change_script_pubkey: params.change_spk.clone(),
+                    change_serial_id: params.change_serial_id,
+                    payout_script_pubkey: params.payout_spk.clone(),
+                    payout_serial_id: params.payout_serial_id,
+                    inputs,
+                    input_amount,
+                    collateral: *collateral,
+                    additional_payout_outputs: Vec::new(),
+                })
+            })
+            .collect()
+    }
+
+    /// Builds the transactions of the contract, with one CET per outcome.
+    pub fn get_dlc_transactions(&self) -> Result<DlcTransactions, Error> {
+        let payouts = self
+            .offer
+            .outcomes
+            .iter()
+            .map(|x| x.payouts.clone())
+            .collect::<Vec<_>>();
+        Ok(dlc::multi_party::create_dlc_transactions(
+            &self.get_party_params()?,
+            &payouts,
+            self.offer.refund_locktime,
+            self.offer.fee_rate_per_vb,
+            0,
+            self.offer.cet_locktime,
+            self.offer.fund_output_serial_id,
+        )?)
+    }
+
+    /// Returns the information of the oracle attesting to the outcome.
+    pub fn get_oracle_infos(&self) -> Vec<OracleInfo> {
+        vec![(&self.offer.oracle_announcement).into()]
+    }
+
+    /// Returns the messages attested by the oracle for each CET.
+    pub fn get_cet_messages(&self) -> Vec<Vec<Vec<Message>>> {
+        self.offer
+            .outcomes
+            .iter()
+            .map(|x| {
+                vec![vec![Message::from_hashed_data::<
+                    secp256k1_zkp::hashes::sha256::Hash,
+                >(x.outcome.as_bytes())]]
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,82 @@
+//! #NovationContract
+
+use crate::ContractId;
+use bitcoin::Transaction;
+use dlc_messages::novation::{NovationAccept, NovationOffer};
+use secp256k1_zkp::PublicKey;
+
+use super::signed_contract::SignedContract;
+
+/// The state of a [`NovationContract`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum NovationState {
+    /// The offer was sent by the transferor or received by the transferee.
+    Offered,
+    /// The offer was accepted by the transferee and forwarded to the
+    /// remaining party by the transferor.
+    Accepted,
+    /// The remaining party signed the novated contract and the novation
+    /// transaction.
+    Confirmed,
+    /// The novation transaction was signed and broadcast by the transferee.
+    Broadcast,
+    /// The novation transaction was confirmed and the novated contract
+    /// replaced the original one.
+    Completed,
+}
+
+/// The role of a party in a [`NovationContract`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NovationRole {
+    /// The party leaving the contract.
+    Transferor,
+    /// The counter party of the transferor, which stays in the contract.
+    RemainingParty,
+    /// The party taking over the side of the transferor.
+    Transferee,
+}
+
+/// The transfer of the side of a party in a confirmed contract to a new party.
+/// The node id of each party is recorded, except our own.
+#[derive(Clone)]
+pub struct NovationContract {
+    /// The id of the contract being transferred.
+    pub contract_id: ContractId,
+    /// The state of the transfer.
+    pub state: NovationState,
+    /// The node id of the transferor, `None` if we are the transferor.
+    pub transferor: Option<PublicKey>,
+    /// The node id of the remaining party, `None` if we are the remaining
+    /// party.
+    pub remaining_party: Option<PublicKey>,
+    /// The node id of the transferee, `None` if we are the transferee.
+    pub transferee: Option<PublicKey>,
+    /// The offer describing the contract and the terms of the transfer.
+    pub offer: NovationOffer,
+    /// The parameters and signatures of the transferee, once known.
+    pub accept: Option<NovationAccept>,
+    /// The novation transaction, once the parameters of the transferee are
+    /// known. Only fully signed for the transferee.
+    pub novation_transaction: Option<Transaction>,
+    /// The contract spending the output of the novation transaction, kept by
+    /// the remaining party and the transferee.
+    pub novated_contract: Option<SignedContract>,
+}
+
+impl NovationContract {
+    /// Returns our own role in the transfer.
+    pub fn role(&self) -> NovationRole {
+        if self.transferor.is_none() {
+            NovationRole::Transferor
+        } else if self.remaining_party.is_none() {
+            NovationRole::RemainingParty
+        } else {
+            NovationRole::Transferee
+        }
+    }
+}
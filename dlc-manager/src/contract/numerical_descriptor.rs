@@ -5,9 +5,10 @@ use crate::error::Error;
 use crate::payout_curve::{PayoutFunction, RoundingIntervals};
 use bitcoin::{Script, Transaction};
 use dlc::{Payout, RangePayout};
+use dlc_messages::oracle_msgs::{EventDescriptor, OracleAnnouncement};
 use dlc_trie::multi_oracle_trie::MultiOracleTrie;
 use dlc_trie::multi_oracle_trie_with_diff::MultiOracleTrieWithDiff;
-use dlc_trie::{DlcTrie, OracleNumericInfo};
+use dlc_trie::{DlcTrie, OracleNumericInfo, TrieStats};
 use secp256k1_zkp::{All, EcdsaAdaptorSignature, PublicKey, Secp256k1, SecretKey};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -50,6 +51,12 @@ pub struct NumericalDescriptor {
     pub difference_params: Option<DifferenceParams>,
     /// Information about base and number of digits for each oracle.
     pub oracle_numeric_infos: OracleNumericInfo,
+    /// For each oracle, the number of least significant digits to ignore so
+    /// that oracles attesting with different precisions can be combined. The
+    /// precision of an oracle plus its number of ignored digits must be the
+    /// same for all oracles. Empty if all oracles use the same precision.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub truncated_digits: Vec<usize>,
 }
 
 impl NumericalDescriptor {
@@ -63,10 +70,57 @@ impl NumericalDescriptor {
     /// Validate that the descriptor covers all possible outcomes of the given
     /// digit decomposition event descriptor.
     pub fn validate(&self, max_value: u64) -> Result<(), Error> {
-        self.rounding_intervals.validate()?;
+        self.rounding_intervals.validate_for_outcomes(max_value)?;
         self.payout_function.validate(max_value)
     }
 
+    /// Validate that the base and number of digits of the descriptor match the
+    /// event descriptors of the given announcements, and that all the
+    /// announcements use the same unit and, once truncated, the same
+    /// precision, so that attested digits are interpreted in the same way by
+    /// both parties.
+    pub fn validate_announcements(
+        &self,
+        announcements: &[OracleAnnouncement],
+    ) -> Result<(), Error> {
+        if self.difference_params.is_some() && self.oracle_numeric_infos.base != 2 {
+            return Err(Error::InvalidParameters(
+                "Difference parameters are only supported for base 2 events.".to_string(),
+            ));
+        }
+
+        validate_digit_announcements(
+            &self.oracle_numeric_infos,
+            announcements,
+            true,
+            &self.truncated_digits,
+        )
+    }
+
+    /// Returns the base and number of digits of each oracle once their
+    /// truncated digits are ignored, used to build the adaptor signature tries.
+    pub fn get_normalized_numeric_infos(&self) -> Result<OracleNumericInfo, Error> {
+        self.oracle_numeric_infos
+            .truncate(&self.truncated_digits)
+            .map_err(|_| {
+                Error::InvalidParameters(
+                    "Truncated digits must be given for each oracle and leave at least one digit."
+                        .to_string(),
+                )
+            })
+    }
+
+    /// Returns the largest outcome that can be attested by all the oracles.
+    pub fn get_max_outcome(&self) -> Result<u64, Error> {
+        let oracle_numeric_infos = self.get_normalized_numeric_infos()?;
+        let min_nb_digits = oracle_numeric_infos.get_min_nb_digits();
+        let max_value = oracle_numeric_infos
+            .base
+            .checked_pow(min_nb_digits as u32)
+            .ok_or_else(|| Error::InvalidParameters("Could not compute max value".to_string()))?;
+        Ok((max_value - 1) as u64)
+    }
+
     /// Returns the set of payouts for the descriptor generated from the payout
     /// function.
     pub fn get_payouts(&self, total_collateral: u64) -> Result<Vec<Payout>, Error> {
@@ -77,6 +131,34 @@ impl NumericalDescriptor {
             .collect())
     }
 
+    /// Returns information about the size of the trie that would be built for
+    /// the descriptor, without creating any adaptor signature.
+    pub fn get_trie_stats(
+        &self,
+        total_collateral: u64,
+        threshold: usize,
+    ) -> Result<TrieStats, Error> {
+        let oracle_numeric_infos = self.get_normalized_numeric_infos()?;
+        let range_payouts = self.get_range_payouts(total_collateral)?;
+        match &self.difference_params {
+            Some(params) => {
+                let mut multi_trie = MultiOracleTrieWithDiff::new(
+                    &oracle_numeric_infos,
+                    threshold,
+                    params.min_support_exp,
+                    params.max_error_exp,
+                )?;
+                multi_trie.generate(0, &range_payouts)?;
+                Ok(multi_trie.stats())
+            }
+            None => {
+                let mut trie = MultiOracleTrie::new(&oracle_numeric_infos, threshold)?;
+                trie.generate(0, &range_payouts)?;
+                Ok(trie.stats())
+            }
+        }
+    }
+
     /// Verify the given set of adaptor signatures and generate the adaptor info.
     pub fn verify_and_get_adaptor_info(
         &self,
@@ -94,7 +176,7 @@ impl NumericalDescriptor {
         match &self.difference_params {
             Some(params) => {
                 let mut multi_trie = MultiOracleTrieWithDiff::new(
-                    &self.oracle_numeric_infos,
+                    &self.get_normalized_numeric_infos()?,
                     threshold,
                     params.min_support_exp,
                     params.max_error_exp,
@@ -113,7 +195,8 @@ impl NumericalDescriptor {
                 Ok((AdaptorInfo::NumericalWithDifference(multi_trie), index))
             }
             None => {
-                let mut trie = MultiOracleTrie::new(&self.oracle_numeric_infos, threshold)?;
+                let mut trie =
+                    MultiOracleTrie::new(&self.get_normalized_numeric_infos()?, threshold)?;
                 let index = trie.generate_verify(
                     secp,
                     fund_pubkey,
@@ -146,7 +229,7 @@ impl NumericalDescriptor {
         match &self.difference_params {
             Some(params) => {
                 let mut multi_trie = MultiOracleTrieWithDiff::new(
-                    &self.oracle_numeric_infos,
+                    &self.get_normalized_numeric_infos()?,
                     threshold,
                     params.min_support_exp,
                     params.max_error_exp,
@@ -168,7 +251,8 @@ impl NumericalDescriptor {
             }
 
             None => {
-                let mut trie = MultiOracleTrie::new(&self.oracle_numeric_infos, threshold)?;
+                let mut trie =
+                    MultiOracleTrie::new(&self.get_normalized_numeric_infos()?, threshold)?;
                 let sigs = trie.generate_sign(
                     secp,
                     fund_priv_key,
@@ -184,3 +268,146 @@ impl NumericalDescriptor {
         }
     }
 }
+
+/// Validate that the given announcements are for unsigned digit decomposition
+/// events matching the base and number of digits of `oracle_numeric_infos`,
+/// and if `same_unit` is set that they all use the same unit and precision,
+/// the precision of each oracle being increased by its number of
+/// `truncated_digits` if any.
+pub(super) fn validate_digit_announcements(
+    oracle_numeric_infos: &OracleNumericInfo,
+    announcements: &[OracleAnnouncement],
+    same_unit: bool,
+    truncated_digits: &[usize],
+) -> Result<(), Error> {
+    if !truncated_digits.is_empty() && !truncated_digits.contains(&0) {
+        return Err(Error::InvalidParameters(
+            "At least one oracle must be used without truncation.".to_string(),
+        ));
+    }
+
+    if oracle_numeric_infos.base < 2 {
+        return Err(Error::InvalidParameters(format!(
+            "Invalid base {}.",
+            oracle_numeric_infos.base
+        )));
+    }
+
+    if announcements.len() != oracle_numeric_infos.nb_digits.len() {
+        return Err(Error::InvalidParameters(format!(
+            "Descriptor has digit information for {} oracles but {} announcements were provided.",
+            oracle_numeric_infos.nb_digits.len(),
+            announcements.len()
+        )));
+    }
+
+    let mut unit_and_precision = None;
+    for (i, (announcement, nb_digits)) in announcements
+        .iter()
+        .zip(oracle_numeric_infos.nb_digits.iter())
+        .enumerate()
+    {
+        let event = &announcement.oracle_event;
+        let descriptor = match &event.event_descriptor {
+            EventDescriptor::DigitDecompositionEvent(d) => d,
+            _ => {
+                return Err(Error::InvalidParameters(format!(
+                    "Announcement {} is not for a digit decomposition event.",
+                    i
+                )))
+            }
+        };
+        if descriptor.is_signed {
+            return Err(Error::InvalidParameters(format!(
+                "Announcement {} is for a signed event which is not supported.",
+                i
+            )));
+        }
+        if descriptor.base as usize != oracle_numeric_infos.base {
+            return Err(Error::InvalidParameters(format!(
+                "Announcement {} uses base {} but descriptor uses base {}.",
+                i, descriptor.base, oracle_numeric_infos.base
+            )));
+        }
+        if descriptor.nb_digits as usize != *nb_digits || event.oracle_nonces.len() != *nb_digits {
+            return Err(Error::InvalidParameters(format!(
+                "Announcement {} has {} digits and {} nonces but descriptor expects {} digits.",
+                i,
+                descriptor.nb_digits,
+                event.oracle_nonces.len(),
+                nb_digits
+            )));
+        }
+        let truncated = truncated_digits.get(i).cloned().unwrap_or(0) as i32;
+        let precision = descriptor.precision + truncated;
+        match unit_and_precision {
+            None => unit_and_precision = Some((&descriptor.unit, precision)),
+            Some((unit, expected)) => {
+                if same_unit && (unit != &descriptor.unit || precision != expected) {
+                    return Err(Error::InvalidParameters(format!(
+                        "Announcement {} uses unit {} with precision {} (truncated by {} digits) while previous ones use unit {} with precision {}.",
+                        i, descriptor.unit, descriptor.precision, truncated, unit, expected
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dlc_messages::oracle_msgs::{DigitDecompositionEventDescriptor, OracleEvent};
+    use secp256k1_zkp::{schnorr::Signature, XOnlyPublicKey};
+    use std::str::FromStr;
+
+    fn create_announcement(nb_digits: u16, precision: i32) -> OracleAnnouncement {
+        let xonly_pk = XOnlyPublicKey::from_str(
+            "e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443",
+        )
+        .unwrap();
+
+        OracleAnnouncement {
+            announcement_signature: Signature::from_str("6470FD1303DDA4FDA717B9837153C24A6EAB377183FC438F939E0ED2B620E9EE5077C4A8B8DCA28963D772A94F5F0DDF598E1C47C137F91933274C7C3EDADCE8").unwrap(),
+            oracle_public_key: xonly_pk,
+            oracle_event: OracleEvent {
+                oracle_nonces: vec![xonly_pk; nb_digits as usize],
+                event_maturity_epoch: 0,
+                event_descriptor: EventDescriptor::DigitDecompositionEvent(
+                    DigitDecompositionEventDescriptor {
+                        base: 10,
+                        is_signed: false,
+                        unit: "usd".to_string(),
+                        precision,
+                        nb_digits,
+                    },
+                ),
+                event_id: "01".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn validate_announcements_with_truncated_digits_test() {
+        let oracle_numeric_infos = OracleNumericInfo {
+            base: 10,
+            nb_digits: vec![5, 7],
+        };
+        let announcements = vec![create_announcement(5, 0), create_announcement(7, -2)];
+
+        validate_digit_announcements(&oracle_numeric_infos, &announcements, true, &[])
+            .expect_err("precisions should differ");
+        validate_digit_announcements(&oracle_numeric_infos, &announcements, true, &[0, 2])
+            .expect("truncated precisions to match");
+        validate_digit_announcements(&oracle_numeric_infos, &announcements, true, &[0, 1])
+            .expect_err("truncated precisions should differ");
+        validate_digit_announcements(&oracle_numeric_infos, &announcements, true, &[1, 3])
+            .expect_err("one oracle should not be truncated");
+        assert_eq!(
+            vec![5, 5],
+            oracle_numeric_infos.truncate(&[0, 2]).unwrap().nb_digits
+        );
+    }
+}
@@ -7,10 +7,11 @@ use crate::utils::get_new_serial_id;
 
 use super::contract_info::ContractInfo;
 use super::contract_input::ContractInput;
-use super::{ContractDescriptor, FundingInputInfo};
+use super::{ContractDescriptor, FundingInputInfo, SharedFundingInput};
 use dlc::PartyParams;
+use dlc_messages::features::{Feature, Features};
 use dlc_messages::oracle_msgs::OracleAnnouncement;
-use dlc_messages::OfferDlc;
+use dlc_messages::{AdditionalPayoutOutputs, OfferDlc, OfferMetadata, RolloverInfo};
 use secp256k1_zkp::PublicKey;
 
 /// Contains information about a contract that was offered.
@@ -44,6 +45,19 @@ pub struct OfferedContract {
     pub cet_locktime: u32,
     /// The time at which the contract becomes refundable.
     pub refund_locktime: u32,
+    /// The shared output funding the contract, if the contract is built on top
+    /// of an output jointly owned by both parties.
+    pub shared_funding_input: Option<SharedFundingInput>,
+    /// The part of the shared output funding the contract that is attributed
+    /// to the accepting party, if the contract is funded by such an output
+    /// outside of a rollover.
+    pub shared_funding_accept_share: Option<u64>,
+    /// Human readable information about the contract provided by the offer
+    /// party.
+    pub metadata: Option<OfferMetadata>,
+    /// The contract rolled over by this contract, whose fund output funds it,
+    /// and the part of it attributed to the accepting party, if any.
+    pub rollover: Option<RolloverInfo>,
 }
 
 impl OfferedContract {
@@ -54,11 +68,17 @@ impl OfferedContract {
             crate::error::Error::InvalidParameters("Fee rate is too high".to_string())
         })?;
 
+        self.offer_params.validate_payout_outputs().map_err(|_| {
+            crate::error::Error::InvalidParameters("Invalid additional payout outputs".to_string())
+        })?;
+
         for info in &self.contract_info {
             info.validate()?;
             let payouts = match &info.contract_descriptor {
                 ContractDescriptor::Enum(e) => e.get_payouts(),
                 ContractDescriptor::Numerical(e) => e.get_payouts(self.total_collateral)?,
+                ContractDescriptor::Composite(c) => c.get_payouts(self.total_collateral)?,
+                ContractDescriptor::BooleanEnum(b) => b.get_payouts(),
             };
             let valid = payouts
                 .iter()
@@ -73,6 +93,43 @@ impl OfferedContract {
         Ok(())
     }
 
+    /// Returns the features to advertise when offering the contract. Fixed
+    /// point evaluation of hyperbola pieces is required when the contract uses
+    /// them.
+    pub(crate) fn get_features(&self) -> Features {
+        let mut features = Features::supported();
+        if self.uses_hyperbola_pieces() {
+            features.set_required(Feature::FixedPointHyperbola);
+        }
+        features
+    }
+
+    /// Returns an error if the contract uses hyperbola payout curve pieces
+    /// while the given features of the offer party don't require evaluating
+    /// them with fixed point arithmetic, as the payouts computed by the offer
+    /// party could then differ.
+    pub(crate) fn check_hyperbola_feature(
+        &self,
+        features: &Option<Features>,
+    ) -> Result<(), crate::error::Error> {
+        let requires_fixed_point = features
+            .as_ref()
+            .map_or(false, |x| x.requires(Feature::FixedPointHyperbola));
+        if self.uses_hyperbola_pieces() && !requires_fixed_point {
+            return Err(crate::error::Error::InvalidParameters(
+                "Offer uses hyperbola pieces without requiring fixed point evaluation.".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn uses_hyperbola_pieces(&self) -> bool {
+        self.contract_info
+            .iter()
+            .any(|x| x.contract_descriptor.has_hyperbola_pieces())
+    }
+
     /// Creates a new [`OfferedContract`] from the given parameters.
     pub fn new(
         contract: &ContractInput,
@@ -113,6 +170,10 @@ impl OfferedContract {
             cet_locktime,
             refund_locktime: latest_maturity + refund_delay,
             counter_party: *counter_party,
+            shared_funding_input: None,
+            shared_funding_accept_share: None,
+            metadata: contract.metadata.clone(),
+            rollover: None,
         }
     }
 
@@ -137,6 +198,9 @@ impl OfferedContract {
                 collateral: offer_dlc.offer_collateral,
                 inputs,
                 input_amount,
+                additional_payout_outputs: AdditionalPayoutOutputs::to_payout_outputs(
+                    &offer_dlc.additional_payout_outputs,
+                ),
             },
             cet_locktime: offer_dlc.cet_locktime,
             refund_locktime: offer_dlc.refund_locktime,
@@ -145,6 +209,10 @@ impl OfferedContract {
             funding_inputs_info: offer_dlc.funding_inputs.iter().map(|x| x.into()).collect(),
             total_collateral: offer_dlc.contract_info.get_total_collateral(),
             counter_party,
+            shared_funding_input: None,
+            shared_funding_accept_share: offer_dlc.shared_funding_accept_share,
+            rollover: offer_dlc.rollover.clone(),
+            metadata: offer_dlc.metadata.clone(),
         })
     }
 }
@@ -172,6 +240,16 @@ impl From<&OfferedContract> for OfferDlc {
             refund_locktime: offered_contract.refund_locktime,
             fee_rate_per_vb: offered_contract.fee_rate_per_vb,
             fund_output_serial_id: offered_contract.fund_output_serial_id,
+            features: Some(offered_contract.get_features()),
+            metadata: offered_contract.metadata.clone(),
+            ownership_proofs: None,
+            rollover: offered_contract.rollover.clone(),
+            shared_funding_accept_share: offered_contract.shared_funding_accept_share,
+            additional_payout_outputs: AdditionalPayoutOutputs::from_payout_outputs(
+                &offered_contract.offer_params.additional_payout_outputs,
+            ),
+            signature: None,
+            unknown_tlvs: Vec::new(),
         }
     }
 }
@@ -247,4 +325,64 @@ mod tests {
             "../../test_inputs/offer_numerical_empty_rounding_interval.json"
         ));
     }
+
+    #[test]
+    fn offer_numerical_nb_digits_mismatch() {
+        validate_offer_test_common(include_str!(
+            "../../test_inputs/offer_numerical_nb_digits_mismatch.json"
+        ));
+    }
+
+    #[test]
+    fn offer_with_hyperbola_pieces_requires_fixed_point_feature() {
+        use crate::payout_curve::{
+            HyperbolaPayoutCurvePiece, PayoutFunction, PayoutFunctionPiece, PayoutPoint,
+        };
+
+        let mut offer: OfferedContract = serde_json::from_str(include_str!(
+            "../../test_inputs/offer_numerical_non_continuous.json"
+        ))
+        .unwrap();
+        offer
+            .check_hyperbola_feature(&None)
+            .expect("no feature to be required without hyperbola pieces");
+
+        if let ContractDescriptor::Numerical(n) = &mut offer.contract_info[0].contract_descriptor {
+            n.payout_function =
+                PayoutFunction::new(vec![PayoutFunctionPiece::HyperbolaPayoutCurvePiece(
+                    HyperbolaPayoutCurvePiece::new(
+                        PayoutPoint {
+                            event_outcome: 0,
+                            outcome_payout: 0,
+                            extra_precision: 0,
+                        },
+                        PayoutPoint {
+                            event_outcome: 1023,
+                            outcome_payout: 0,
+                            extra_precision: 0,
+                        },
+                        true,
+                        50.0,
+                        50.0,
+                        5.0,
+                        -1.0,
+                        0.0,
+                        1.0,
+                    )
+                    .unwrap(),
+                )])
+                .unwrap();
+        } else {
+            panic!("Expected a numerical contract descriptor");
+        }
+
+        offer
+            .check_hyperbola_feature(&Some(Features::supported()))
+            .expect_err("fixed point evaluation to be required");
+        let features = offer.get_features();
+        assert!(features.requires(Feature::FixedPointHyperbola));
+        offer
+            .check_hyperbola_feature(&Some(features))
+            .expect("offer requiring fixed point evaluation to be accepted");
+    }
 }
@@ -0,0 +1,109 @@
+//! #PointCache
+//! Process wide cache of the anticipation points of oracle nonces, shared by
+//! all the contracts relying on the same announcements so that the points are
+//! only computed once per nonce.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use secp256k1_zkp::{PublicKey, XOnlyPublicKey};
+
+use crate::error::Error;
+
+/// The maximum number of nonces whose points are kept in the cache. The cache
+/// is emptied when the limit is reached.
+const MAX_CACHED_NONCES: usize = 10_000;
+
+/// Oracle public key, nonce and base for which the points were computed.
+type CacheKey = (XOnlyPublicKey, XOnlyPublicKey, usize);
+
+static CACHE: Mutex<Option<HashMap<CacheKey, Vec<PublicKey>>>> = Mutex::new(None);
+
+fn lock_cache() -> MutexGuard<'static, Option<HashMap<CacheKey, Vec<PublicKey>>>> {
+    // The cache cannot be left in an inconsistent state, so a poisoned lock
+    // can safely be reused.
+    CACHE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns the points for each possible digit value attested by `pubkey`
+/// with `nonce`, using `compute` to compute them if they are not cached.
+pub(crate) fn get_or_compute<F>(
+    pubkey: &XOnlyPublicKey,
+    nonce: &XOnlyPublicKey,
+    base: usize,
+    compute: F,
+) -> Result<Vec<PublicKey>, Error>
+where
+    F: FnOnce() -> Result<Vec<PublicKey>, Error>,
+{
+    let key = (*pubkey, *nonce, base);
+    if let Some(points) = lock_cache().as_ref().and_then(|x| x.get(&key)) {
+        return Ok(points.clone());
+    }
+
+    // Computed without holding the lock so that points for different nonces
+    // can be computed concurrently.
+    let points = compute()?;
+
+    let mut cache = lock_cache();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if cache.len() >= MAX_CACHED_NONCES {
+        cache.clear();
+    }
+    cache.insert(key, points.clone());
+    Ok(points)
+}
+
+/// Returns the number of nonces whose points are currently cached.
+pub fn nb_cached_nonces() -> usize {
+    lock_cache().as_ref().map_or(0, |x| x.len())
+}
+
+/// Removes all the points from the cache.
+pub fn clear() {
+    *lock_cache() = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1_zkp::{KeyPair, SecretKey, SECP256K1};
+
+    fn get_key(i: u8) -> XOnlyPublicKey {
+        let sk = SecretKey::from_slice(&[i; 32]).unwrap();
+        XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(SECP256K1, &sk)).0
+    }
+
+    #[test]
+    fn points_are_computed_once_per_nonce() {
+        let pubkey = get_key(200);
+        let nonce = get_key(201);
+        let point =
+            PublicKey::from_secret_key(SECP256K1, &SecretKey::from_slice(&[3; 32]).unwrap());
+        let mut nb_computed = 0;
+
+        for _ in 0..3 {
+            let points = get_or_compute(&pubkey, &nonce, 2, || {
+                nb_computed += 1;
+                Ok(vec![point, point])
+            })
+            .unwrap();
+            assert_eq!(vec![point, point], points);
+        }
+
+        assert_eq!(1, nb_computed);
+        assert!(nb_cached_nonces() >= 1);
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let pubkey = get_key(202);
+        let nonce = get_key(203);
+
+        get_or_compute(&pubkey, &nonce, 2, || {
+            Err(Error::InvalidState("test".to_string()))
+        })
+        .expect_err("error to be returned");
+        get_or_compute(&pubkey, &nonce, 2, || Ok(Vec::new())).expect("points to be computed");
+    }
+}
@@ -2,25 +2,35 @@
 //! to be converted to byte arrays.
 
 use crate::contract::accepted_contract::AcceptedContract;
+use crate::contract::boolean_descriptor::{BooleanEnumDescriptor, EnumCondition};
+use crate::contract::compact_ser;
+use crate::contract::composite_descriptor::{
+    CompositeAdaptorInfo, CompositeDescriptor, CompositeLeaf, CompositeOperation,
+};
 use crate::contract::contract_info::ContractInfo;
 use crate::contract::enum_descriptor::EnumDescriptor;
+use crate::contract::multi_party_contract::{MultiPartyContract, MultiPartyContractState};
+use crate::contract::novation_contract::{NovationContract, NovationState};
 use crate::contract::numerical_descriptor::{DifferenceParams, NumericalDescriptor};
 use crate::contract::offered_contract::OfferedContract;
 use crate::contract::signed_contract::SignedContract;
 use crate::contract::AdaptorInfo;
 use crate::contract::{
-    ClosedContract, ContractDescriptor, FailedAcceptContract, FailedSignContract, FundingInputInfo,
-    PreClosedContract,
+    ArchivedContract, ClosedContract, ContractDescriptor, ContractState, FailedAcceptContract,
+    FailedSignContract, FundingInputInfo, PreClosedContract, SharedFundingInput,
 };
 use crate::payout_curve::{
-    HyperbolaPayoutCurvePiece, PayoutFunction, PayoutFunctionPiece, PayoutPoint,
-    PolynomialPayoutCurvePiece, RoundingInterval, RoundingIntervals,
+    HyperbolaPayoutCurvePiece, MonotoneCubicPayoutCurvePiece, PayoutFunction, PayoutFunctionPiece,
+    PayoutPoint, PolynomialPayoutCurvePiece, RoundingInterval, RoundingIntervals,
+    StepPayoutCurvePiece,
 };
 use dlc::DlcTransactions;
 use dlc_messages::ser_impls::{
-    read_ecdsa_adaptor_signatures, read_option_cb, read_usize, read_vec, read_vec_cb,
-    write_ecdsa_adaptor_signatures, write_option_cb, write_usize, write_vec, write_vec_cb,
+    read_ecdsa_adaptor_signatures, read_option, read_option_cb, read_usize, read_vec, read_vec_cb,
+    write_ecdsa_adaptor_signatures, write_option, write_option_cb, write_usize, write_vec,
+    write_vec_cb,
 };
+use dlc_messages::{AcceptDlc, SignDlc};
 use dlc_trie::digit_trie::{DigitNodeData, DigitTrieDump};
 use dlc_trie::multi_oracle_trie::{MultiOracleTrie, MultiOracleTrieDump};
 use dlc_trie::multi_oracle_trie_with_diff::{MultiOracleTrieWithDiff, MultiOracleTrieWithDiffDump};
@@ -60,12 +70,16 @@ impl_dlc_writeable!(PayoutPoint, { (event_outcome, writeable), (outcome_payout,
 impl_dlc_writeable_enum!(
     PayoutFunctionPiece,
     (0, PolynomialPayoutCurvePiece),
-    (1, HyperbolaPayoutCurvePiece);;;
+    (1, HyperbolaPayoutCurvePiece),
+    (2, StepPayoutCurvePiece),
+    (3, MonotoneCubicPayoutCurvePiece);;;
 );
 impl_dlc_writeable!(RoundingInterval, { (begin_interval, writeable), (rounding_mod, writeable) });
 impl_dlc_writeable!(PayoutFunction, { (payout_function_pieces, vec) });
-impl_dlc_writeable!(NumericalDescriptor, { (payout_function, writeable), (rounding_intervals, writeable), (difference_params, option), (oracle_numeric_infos, {cb_writeable, oracle_params::write, oracle_params::read}) });
+impl_dlc_writeable!(NumericalDescriptor, { (payout_function, writeable), (rounding_intervals, writeable), (difference_params, option), (oracle_numeric_infos, {cb_writeable, oracle_params::write, oracle_params::read}), (truncated_digits, {vec_cb, write_usize, read_usize}) });
 impl_dlc_writeable!(PolynomialPayoutCurvePiece, { (payout_points, vec) });
+impl_dlc_writeable!(StepPayoutCurvePiece, { (payout_points, vec) });
+impl_dlc_writeable!(MonotoneCubicPayoutCurvePiece, { (payout_points, vec) });
 impl_dlc_writeable!(RoundingIntervals, { (intervals, vec) });
 impl_dlc_writeable!(DifferenceParams, { (max_error_exp, usize), (min_support_exp, usize), (maximize_coverage, writeable) });
 impl_dlc_writeable!(HyperbolaPayoutCurvePiece, {
@@ -79,9 +93,27 @@ impl_dlc_writeable!(HyperbolaPayoutCurvePiece, {
     (c, float),
     (d, float)
 });
-impl_dlc_writeable_enum!(ContractDescriptor, (0, Enum), (1, Numerical);;;);
+impl_dlc_writeable_enum!(CompositeOperation,;;; (0, Difference), (1, Ratio));
+impl_dlc_writeable!(CompositeDescriptor, {
+    (operation, writeable),
+    (ratio_precision, writeable),
+    (payout_function, writeable),
+    (rounding_intervals, writeable),
+    (oracle_numeric_infos, {cb_writeable, oracle_params::write, oracle_params::read})
+});
+impl_dlc_writeable_enum!(EnumCondition,;
+    (0, Outcome, {(oracle_index, usize), (outcomes, {cb_writeable, dlc_messages::ser_impls::write_strings, dlc_messages::ser_impls::read_strings})});
+    (1, And, write_vec, read_vec), (2, Or, write_vec, read_vec);
+);
+impl_dlc_writeable!(BooleanEnumDescriptor, {
+    (condition, writeable),
+    (true_payout, {cb_writeable, dlc_messages::ser_impls::payout::write, dlc_messages::ser_impls::payout::read}),
+    (false_payout, {cb_writeable, dlc_messages::ser_impls::payout::write, dlc_messages::ser_impls::payout::read})
+});
+impl_dlc_writeable_enum!(ContractDescriptor, (0, Enum), (1, Numerical), (2, Composite), (3, BooleanEnum);;;);
 impl_dlc_writeable!(ContractInfo, { (contract_descriptor, writeable), (oracle_announcements, vec), (threshold, usize)});
 impl_dlc_writeable!(FundingInputInfo, { (funding_input, writeable), (address, {option_cb, dlc_messages::ser_impls::write_address, dlc_messages::ser_impls::read_address}) });
+impl_dlc_writeable!(SharedFundingInput, { (funding_script, writeable), (own_pubkey, writeable) });
 impl_dlc_writeable!(EnumDescriptor, {
     (
         outcome_payouts,
@@ -99,10 +131,64 @@ impl_dlc_writeable!(OfferedContract, {
     (fee_rate_per_vb, writeable),
     (cet_locktime, writeable),
     (refund_locktime, writeable),
-    (counter_party, writeable)
+    (counter_party, writeable),
+    (shared_funding_input, option),
+    (metadata, option),
+    (shared_funding_accept_share, option),
+    (rollover, option)
 });
 impl_dlc_writeable_external!(RangeInfo, range_info, { (cet_index, usize), (adaptor_index, usize)});
-impl_dlc_writeable_enum!(AdaptorInfo,;; (0, Numerical, write_multi_oracle_trie, read_multi_oracle_trie), (1, NumericalWithDifference, write_multi_oracle_trie_with_diff, read_multi_oracle_trie_with_diff); (2, Enum));
+impl_dlc_writeable!(CompositeLeaf, {
+    (first_prefix, {vec_cb, write_usize, read_usize}),
+    (second_prefix, {vec_cb, write_usize, read_usize}),
+    (range_info, {cb_writeable, range_info::write, range_info::read})
+});
+impl_dlc_writeable!(CompositeAdaptorInfo, { (leaves, vec) });
+
+// Numerical adaptor infos were initially written using the same format as
+// other structures (ids 0 and 1). They are now written in the compact format
+// (ids 4 and 5), but the initial format can still be read.
+impl Writeable for AdaptorInfo {
+    fn write<W: Writer>(&self, w: &mut W) -> Result<(), ::std::io::Error> {
+        match self {
+            AdaptorInfo::Enum => 2u8.write(w),
+            AdaptorInfo::Composite(c) => {
+                3u8.write(w)?;
+                c.write(w)
+            }
+            AdaptorInfo::Numerical(trie) => {
+                4u8.write(w)?;
+                compact_ser::write_multi_oracle_trie(trie, w)
+            }
+            AdaptorInfo::NumericalWithDifference(trie) => {
+                5u8.write(w)?;
+                compact_ser::write_multi_oracle_trie_with_diff(trie, w)
+            }
+        }
+    }
+}
+
+impl Readable for AdaptorInfo {
+    fn read<R: Read>(r: &mut R) -> Result<Self, DecodeError> {
+        let id: u8 = Readable::read(r)?;
+        match id {
+            0 => Ok(AdaptorInfo::Numerical(read_multi_oracle_trie(r)?)),
+            1 => Ok(AdaptorInfo::NumericalWithDifference(
+                read_multi_oracle_trie_with_diff(r)?,
+            )),
+            2 => Ok(AdaptorInfo::Enum),
+            3 => Ok(AdaptorInfo::Composite(Readable::read(r)?)),
+            4 => Ok(AdaptorInfo::Numerical(compact_ser::read_multi_oracle_trie(
+                r,
+            )?)),
+            5 => Ok(AdaptorInfo::NumericalWithDifference(
+                compact_ser::read_multi_oracle_trie_with_diff(r)?,
+            )),
+            _ => Err(DecodeError::UnknownRequiredFeature),
+        }
+    }
+}
+
 impl_dlc_writeable_external!(
     DlcTransactions, dlc_transactions,
     { (fund, writeable),
@@ -137,10 +223,47 @@ impl_dlc_writeable!(ClosedContract, {
     (contract_id, writeable),
     (temporary_contract_id, writeable),
     (counter_party_id, writeable),
-    (pnl, i64)
+    (pnl, i64),
+    (closed_at, writeable)
+});
+impl_dlc_writeable_enum!(ContractState,;;; (0, Offered), (1, Accepted), (2, Signed), (3, Confirmed), (4, PreClosed), (5, Closed), (6, Refunded), (7, FailedAccept), (8, FailedSign), (9, Rejected));
+impl_dlc_writeable!(ArchivedContract, {
+    (contract_id, writeable),
+    (temporary_contract_id, writeable),
+    (counter_party_id, writeable),
+    (state, writeable),
+    (fund_txid, option),
+    (closing_txid, option),
+    (pnl, i64),
+    (final_time, writeable)
+});
+impl_dlc_writeable!(FailedAcceptContract, {(offered_contract, writeable), (accept_message, {cb_writeable, AcceptDlc::write_without_tlv_stream, AcceptDlc::read_without_tlv_stream}), (error_message, string)});
+impl_dlc_writeable!(FailedSignContract, {(accepted_contract, writeable), (sign_message, {cb_writeable, SignDlc::write_without_tlv_stream, SignDlc::read_without_tlv_stream}), (error_message, string)});
+impl_dlc_writeable_enum!(MultiPartyContractState,;;; (0, Offered), (1, Accepted), (2, SetUp), (3, Signed), (4, Broadcast), (5, Confirmed), (6, Closed), (7, Refunded));
+impl_dlc_writeable!(MultiPartyContract, {
+    (temporary_id, writeable),
+    (id, writeable),
+    (coordinator, option),
+    (offer, writeable),
+    (state, writeable),
+    (party_params, {vec_cb, write_option, read_option}),
+    (cet_adaptor_signatures, {vec_cb, write_option, read_option}),
+    (refund_signatures, {vec_cb, write_option, read_option}),
+    (funding_signatures, {vec_cb, write_option, read_option}),
+    (closing_tx, writeable)
+});
+impl_dlc_writeable_enum!(NovationState,;;; (0, Offered), (1, Accepted), (2, Confirmed), (3, Broadcast), (4, Completed));
+impl_dlc_writeable!(NovationContract, {
+    (contract_id, writeable),
+    (state, writeable),
+    (transferor, option),
+    (remaining_party, option),
+    (transferee, option),
+    (offer, writeable),
+    (accept, option),
+    (novation_transaction, option),
+    (novated_contract, option)
 });
-impl_dlc_writeable!(FailedAcceptContract, {(offered_contract, writeable), (accept_message, writeable), (error_message, string)});
-impl_dlc_writeable!(FailedSignContract, {(accepted_contract, writeable), (sign_message, writeable), (error_message, string)});
 
 impl_dlc_writeable_external!(DigitTrieDump<Vec<RangeInfo> >, digit_trie_dump_vec_range, { (node_data, {vec_cb, write_digit_node_data_vec_range, read_digit_node_data_vec_range}), (root, {option_cb, write_usize, read_usize}), (base, usize)});
 impl_dlc_writeable_external!(DigitTrieDump<RangeInfo>, digit_trie_dump_range, { (node_data, {vec_cb, write_digit_node_data_range, read_digit_node_data_range}), (root, {option_cb, write_usize, read_usize}), (base, usize)});
@@ -153,10 +276,43 @@ impl_dlc_writeable_external_enum!(
     (0, Leaf, digit_trie_dump_range),
     (1, Node, digit_trie_dump_trie)
 );
-impl_dlc_writeable_external!(MultiTrieDump<RangeInfo>, multi_trie_dump, { (node_data, {vec_cb, multi_trie_node_data::write, multi_trie_node_data::read}), (nb_tries, usize), (nb_required, usize), (min_support_exp, usize), (max_error_exp, usize), (maximize_coverage, writeable), (oracle_numeric_infos, {cb_writeable, oracle_params::write, oracle_params::read}) });
 impl_dlc_writeable_external!(MultiOracleTrieWithDiffDump, multi_oracle_trie_with_diff_dump, { (multi_trie_dump, {cb_writeable, multi_trie_dump::write, multi_trie_dump::read}), (oracle_numeric_infos, {cb_writeable, oracle_params::write, oracle_params::read}) });
 impl_dlc_writeable_external!(TrieNodeInfo, trie_node_info, { (trie_index, usize), (store_index, usize) });
 
+/// Legacy encoding of [`MultiTrieDump`], which predates oracle pair specific
+/// difference parameters and thus does not include them.
+pub mod multi_trie_dump {
+    use super::*;
+
+    /// Function to write multi_trie_dump
+    pub fn write<W: Writer>(
+        multi_trie_dump: &MultiTrieDump<RangeInfo>,
+        w: &mut W,
+    ) -> Result<(), ::std::io::Error> {
+        write_vec_cb(&multi_trie_dump.node_data, w, &multi_trie_node_data::write)?;
+        write_usize(&multi_trie_dump.nb_tries, w)?;
+        write_usize(&multi_trie_dump.nb_required, w)?;
+        write_usize(&multi_trie_dump.min_support_exp, w)?;
+        write_usize(&multi_trie_dump.max_error_exp, w)?;
+        multi_trie_dump.maximize_coverage.write(w)?;
+        oracle_params::write(&multi_trie_dump.oracle_numeric_infos, w)
+    }
+
+    /// Function to read multi_trie_dump
+    pub fn read<R: Read>(r: &mut R) -> Result<MultiTrieDump<RangeInfo>, DecodeError> {
+        Ok(MultiTrieDump {
+            node_data: read_vec_cb(r, &multi_trie_node_data::read)?,
+            nb_tries: read_usize(r)?,
+            nb_required: read_usize(r)?,
+            min_support_exp: read_usize(r)?,
+            max_error_exp: read_usize(r)?,
+            maximize_coverage: Readable::read(r)?,
+            oracle_numeric_infos: oracle_params::read(r)?,
+            pair_params: Vec::new(),
+        })
+    }
+}
+
 fn write_digit_node_data_trie<W: Writer>(
     input: &DigitNodeData<Vec<TrieNodeInfo>>,
     writer: &mut W,
@@ -249,25 +405,11 @@ where
     })
 }
 
-fn write_multi_oracle_trie<W: Writer>(
-    trie: &MultiOracleTrie,
-    w: &mut W,
-) -> Result<(), ::std::io::Error> {
-    multi_oracle_trie_dump::write(&trie.dump(), w)
-}
-
 fn read_multi_oracle_trie<R: Read>(reader: &mut R) -> Result<MultiOracleTrie, DecodeError> {
     let dump = multi_oracle_trie_dump::read(reader)?;
     Ok(MultiOracleTrie::from_dump(dump))
 }
 
-fn write_multi_oracle_trie_with_diff<W: Writer>(
-    trie: &MultiOracleTrieWithDiff,
-    w: &mut W,
-) -> Result<(), ::std::io::Error> {
-    multi_oracle_trie_with_diff_dump::write(&trie.dump(), w)
-}
-
 fn read_multi_oracle_trie_with_diff<R: Read>(
     reader: &mut R,
 ) -> Result<MultiOracleTrieWithDiff, DecodeError> {
@@ -44,6 +44,7 @@ impl SignedContract {
             },
             refund_signature: self.offer_refund_signature,
             funding_signatures: self.funding_signatures.clone(),
+            unknown_tlvs: Vec::new(),
         }
     }
 }
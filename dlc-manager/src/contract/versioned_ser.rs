@@ -0,0 +1,438 @@
+//! Deserialization of contracts and channels written with previous versions of
+//! the serialization format, enabling storage providers to read and upgrade
+//! persisted records.
+
+use crate::channel::accepted_channel::AcceptedChannel;
+use crate::channel::offered_channel::OfferedChannel;
+use crate::channel::signed_channel::SignedChannel;
+use crate::channel::{FailedAccept, FailedSign};
+use crate::contract::accepted_contract::AcceptedContract;
+use crate::contract::contract_info::ContractInfo;
+use crate::contract::numerical_descriptor::NumericalDescriptor;
+use crate::contract::offered_contract::OfferedContract;
+use crate::contract::ser::{dlc_transactions, oracle_params, Serializable};
+use crate::contract::signed_contract::SignedContract;
+use crate::contract::{
+    ClosedContract, ContractDescriptor, FailedAcceptContract, FailedSignContract, PreClosedContract,
+};
+use crate::contract_updater::get_prev_output;
+use dlc::PartyParams;
+use dlc_messages::ser_impls::{
+    party_params, read_ecdsa_adaptor_signatures, read_i64, read_option, read_option_cb,
+    read_string, read_usize, read_vec, read_vec_cb, tx_input_info,
+};
+use dlc_messages::{AcceptDlc, SignDlc};
+use lightning::ln::msgs::DecodeError;
+use lightning::util::ser::Readable;
+use std::io::Read;
+
+/// The version of the format currently used to serialize contracts and
+/// channels.
+pub const SERIALIZATION_VERSION: u8 = 4;
+
+/// The format used before offered contracts included the share of the
+/// accepting party in the shared output funding them and the contract they
+/// roll over.
+pub const PRE_SHARED_FUNDING_SHARE_VERSION: u8 = 3;
+
+/// The format used before closed contracts included their closing time.
+pub const PRE_CLOSING_TIME_VERSION: u8 = 2;
+
+/// The format used before offered contracts included metadata.
+pub const PRE_METADATA_VERSION: u8 = 1;
+
+/// The format of the initial release, before offered contracts included a
+/// shared funding input, party parameters included additional payout outputs
+/// and numerical descriptors included truncated digits.
+pub const INITIAL_VERSION: u8 = 0;
+
+/// Trait used to deserialize an object written with any supported version of
+/// the serialization format.
+pub trait VersionedSerializable: Serializable {
+    /// Deserialize an object written with the given version of the format.
+    fn deserialize_versioned<R: Read>(reader: &mut R, version: u8) -> Result<Self, DecodeError>;
+}
+
+macro_rules! impl_versioned_serializable_unchanged {
+    ($($st: ident),*) => {
+        $(
+            impl VersionedSerializable for $st {
+                fn deserialize_versioned<R: Read>(
+                    reader: &mut R,
+                    version: u8,
+                ) -> Result<Self, DecodeError> {
+                    check_version(version)?;
+                    Self::deserialize(reader)
+                }
+            }
+        )*
+    };
+}
+
+impl_versioned_serializable_unchanged!(OfferedChannel, AcceptedChannel, FailedAccept, FailedSign);
+
+fn check_version(version: u8) -> Result<(), DecodeError> {
+    if version > SERIALIZATION_VERSION {
+        return Err(DecodeError::UnknownVersion);
+    }
+    Ok(())
+}
+
+fn read_party_params<R: Read>(r: &mut R, version: u8) -> Result<PartyParams, DecodeError> {
+    if version > INITIAL_VERSION {
+        return party_params::read(r);
+    }
+
+    Ok(PartyParams {
+        fund_pubkey: Readable::read(r)?,
+        change_script_pubkey: Readable::read(r)?,
+        change_serial_id: Readable::read(r)?,
+        payout_script_pubkey: Readable::read(r)?,
+        payout_serial_id: Readable::read(r)?,
+        inputs: read_vec_cb(r, &tx_input_info::read)?,
+        input_amount: Readable::read(r)?,
+        collateral: Readable::read(r)?,
+        additional_payout_outputs: Vec::new(),
+    })
+}
+
+fn read_numerical_descriptor<R: Read>(
+    r: &mut R,
+    version: u8,
+) -> Result<NumericalDescriptor, DecodeError> {
+    if version > INITIAL_VERSION {
+        return Readable::read(r);
+    }
+
+    Ok(NumericalDescriptor {
+        payout_function: Readable::read(r)?,
+        rounding_intervals: Readable::read(r)?,
+        difference_params: read_option(r)?,
+        oracle_numeric_infos: oracle_params::read(r)?,
+        truncated_digits: Vec::new(),
+    })
+}
+
+fn read_contract_descriptor<R: Read>(
+    r: &mut R,
+    version: u8,
+) -> Result<ContractDescriptor, DecodeError> {
+    if version > INITIAL_VERSION {
+        return Readable::read(r);
+    }
+
+    let id: u8 = Readable::read(r)?;
+    match id {
+        0 => Ok(ContractDescriptor::Enum(Readable::read(r)?)),
+        1 => Ok(ContractDescriptor::Numerical(read_numerical_descriptor(
+            r, version,
+        )?)),
+        _ => Err(DecodeError::UnknownRequiredFeature),
+    }
+}
+
+fn read_contract_info<R: Read>(r: &mut R, version: u8) -> Result<ContractInfo, DecodeError> {
+    Ok(ContractInfo {
+        contract_descriptor: read_contract_descriptor(r, version)?,
+        oracle_announcements: read_vec(r)?,
+        threshold: read_usize(r)?,
+    })
+}
+
+impl VersionedSerializable for OfferedContract {
+    fn deserialize_versioned<R: Read>(r: &mut R, version: u8) -> Result<Self, DecodeError> {
+        check_version(version)?;
+        if version > PRE_SHARED_FUNDING_SHARE_VERSION {
+            return Self::deserialize(r);
+        }
+
+        let mut offered_contract = OfferedContract {
+            id: Readable::read(r)?,
+            is_offer_party: Readable::read(r)?,
+            contract_info: read_vec_cb(r, &|r: &mut R| read_contract_info(r, version))?,
+            offer_params: read_party_params(r, version)?,
+            total_collateral: Readable::read(r)?,
+            funding_inputs_info: read_vec(r)?,
+            fund_output_serial_id: Readable::read(r)?,
+            fee_rate_per_vb: Readable::read(r)?,
+            cet_locktime: Readable::read(r)?,
+            refund_locktime: Readable::read(r)?,
+            counter_party: Readable::read(r)?,
+            shared_funding_input: if version > INITIAL_VERSION {
+                read_option(r)?
+            } else {
+                None
+            },
+            shared_funding_accept_share: None,
+            metadata: if version > PRE_METADATA_VERSION {
+                read_option(r)?
+            } else {
+                None
+            },
+            rollover: None,
+        };
+        offered_contract.shared_funding_accept_share =
+            get_legacy_shared_funding_accept_share(&offered_contract)?;
+
+        Ok(offered_contract)
+    }
+}
+
+/// Returns the share of the accepting party in the shared output funding the
+/// given contract, written before it was recorded. The offer party was then
+/// attributed a part of the funding inputs when rolling over a contract, the
+/// rest going to the accepting party, or all of them otherwise, the accepting
+/// party contributing its collateral and fee share out of them.
+fn get_legacy_shared_funding_accept_share(
+    offered_contract: &OfferedContract,
+) -> Result<Option<u64>, DecodeError> {
+    if offered_contract.shared_funding_input.is_none() {
+        return Ok(None);
+    }
+
+    let total_input = offered_contract
+        .funding_inputs_info
+        .iter()
+        .map(|x| get_prev_output(&x.funding_input).map(|o| o.value))
+        .sum::<Result<u64, _>>()
+        .map_err(|_| DecodeError::InvalidValue)?;
+
+    let offer_amount = offered_contract.offer_params.input_amount;
+    if offer_amount < total_input {
+        return Ok(Some(total_input - offer_amount));
+    }
+
+    Ok(Some(crate::utils::get_shared_funding_accept_amount(
+        offered_contract.total_collateral - offered_contract.offer_params.collateral,
+        offered_contract.fee_rate_per_vb,
+    )))
+}
+
+impl VersionedSerializable for AcceptedContract {
+    fn deserialize_versioned<R: Read>(r: &mut R, version: u8) -> Result<Self, DecodeError> {
+        check_version(version)?;
+        if version > PRE_SHARED_FUNDING_SHARE_VERSION {
+            return Self::deserialize(r);
+        }
+
+        Ok(AcceptedContract {
+            offered_contract: OfferedContract::deserialize_versioned(r, version)?,
+            accept_params: read_party_params(r, version)?,
+            funding_inputs: read_vec(r)?,
+            adaptor_infos: read_vec(r)?,
+            adaptor_signatures: read_option_cb(r, &read_ecdsa_adaptor_signatures)?,
+            accept_refund_signature: Readable::read(r)?,
+            dlc_transactions: dlc_transactions::read(r)?,
+        })
+    }
+}
+
+impl VersionedSerializable for SignedContract {
+    fn deserialize_versioned<R: Read>(r: &mut R, version: u8) -> Result<Self, DecodeError> {
+        check_version(version)?;
+        if version > PRE_SHARED_FUNDING_SHARE_VERSION {
+            return Self::deserialize(r);
+        }
+
+        Ok(SignedContract {
+            accepted_contract: AcceptedContract::deserialize_versioned(r, version)?,
+            adaptor_signatures: read_option_cb(r, &read_ecdsa_adaptor_signatures)?,
+            offer_refund_signature: Readable::read(r)?,
+            funding_signatures: Readable::read(r)?,
+            channel_id: read_option(r)?,
+        })
+    }
+}
+
+impl VersionedSerializable for PreClosedContract {
+    fn deserialize_versioned<R: Read>(r: &mut R, version: u8) -> Result<Self, DecodeError> {
+        check_version(version)?;
+        if version > PRE_SHARED_FUNDING_SHARE_VERSION {
+            return Self::deserialize(r);
+        }
+
+        Ok(PreClosedContract {
+            signed_contract: SignedContract::deserialize_versioned(r, version)?,
+            attestations: read_option_cb(r, &read_vec)?,
+            signed_cet: Readable::read(r)?,
+        })
+    }
+}
+
+impl VersionedSerializable for ClosedContract {
+    fn deserialize_versioned<R: Read>(r: &mut R, version: u8) -> Result<Self, DecodeError> {
+        check_version(version)?;
+        if version > PRE_CLOSING_TIME_VERSION {
+            return Self::deserialize(r);
+        }
+
+        Ok(ClosedContract {
+            attestations: read_option_cb(r, &read_vec)?,
+            signed_cet: Readable::read(r)?,
+            contract_id: Readable::read(r)?,
+            temporary_contract_id: Readable::read(r)?,
+            counter_party_id: Readable::read(r)?,
+            pnl: read_i64(r)?,
+            closed_at: 0,
+        })
+    }
+}
+
+impl VersionedSerializable for SignedChannel {
+    fn deserialize_versioned<R: Read>(r: &mut R, version: u8) -> Result<Self, DecodeError> {
+        check_version(version)?;
+        if version > INITIAL_VERSION {
+            return Self::deserialize(r);
+        }
+
+        Ok(SignedChannel {
+            channel_id: Readable::read(r)?,
+            counter_party: Readable::read(r)?,
+            temporary_channel_id: Readable::read(r)?,
+            fund_output_index: read_usize(r)?,
+            own_points: Readable::read(r)?,
+            own_params: read_party_params(r, version)?,
+            own_per_update_point: Readable::read(r)?,
+            counter_points: Readable::read(r)?,
+            counter_per_update_point: Readable::read(r)?,
+            counter_params: read_party_params(r, version)?,
+            state: Readable::read(r)?,
+            update_idx: Readable::read(r)?,
+            fund_tx: Readable::read(r)?,
+            fund_script_pubkey: Readable::read(r)?,
+            roll_back_state: read_option(r)?,
+            own_per_update_seed: Readable::read(r)?,
+            counter_party_commitment_secrets: Readable::read(r)?,
+            fee_rate_per_vb: Readable::read(r)?,
+        })
+    }
+}
+
+// Messages were written without their TLV stream before unknown TLV records
+// were preserved, so the current message encoding can read all versions.
+impl VersionedSerializable for FailedAcceptContract {
+    fn deserialize_versioned<R: Read>(r: &mut R, version: u8) -> Result<Self, DecodeError> {
+        check_version(version)?;
+        if version > PRE_SHARED_FUNDING_SHARE_VERSION {
+            return Self::deserialize(r);
+        }
+
+        Ok(FailedAcceptContract {
+            offered_contract: OfferedContract::deserialize_versioned(r, version)?,
+            accept_message: AcceptDlc::read_without_tlv_stream(r)?,
+            error_message: read_string(r)?,
+        })
+    }
+}
+
+impl VersionedSerializable for FailedSignContract {
+    fn deserialize_versioned<R: Read>(r: &mut R, version: u8) -> Result<Self, DecodeError> {
+        check_version(version)?;
+        if version > PRE_SHARED_FUNDING_SHARE_VERSION {
+            return Self::deserialize(r);
+        }
+
+        Ok(FailedSignContract {
+            accepted_contract: AcceptedContract::deserialize_versioned(r, version)?,
+            sign_message: SignDlc::read_without_tlv_stream(r)?,
+            error_message: read_string(r)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::SharedFundingInput;
+    use bitcoin::Script;
+    use lightning::util::ser::Writeable;
+    use std::io::Cursor;
+
+    fn deserialize_all<T: VersionedSerializable>(buf: &[u8], version: u8) -> T {
+        let mut cursor = Cursor::new(buf);
+        let res = T::deserialize_versioned(&mut cursor, version).expect("to be able to read");
+        assert_eq!(buf.len() as u64, cursor.position());
+        res
+    }
+
+    #[test]
+    fn initial_version_can_be_read() {
+        let buf = include_bytes!("../../test_inputs/v0/Accepted");
+        let contract: AcceptedContract = deserialize_all(buf, INITIAL_VERSION);
+        assert!(contract.offered_contract.shared_funding_input.is_none());
+        assert!(contract.accept_params.additional_payout_outputs.is_empty());
+    }
+
+    #[test]
+    fn pre_metadata_version_can_be_read() {
+        let buf = include_bytes!("../../test_inputs/Accepted");
+        let offered_contract = AcceptedContract::deserialize(&mut Cursor::new(&buf[..]))
+            .unwrap()
+            .offered_contract;
+        assert!(offered_contract.metadata.is_none());
+        let mut serialized = offered_contract.serialize().unwrap();
+        // Absent metadata, shared funding share and rollover information are
+        // written as single zero bytes.
+        assert_eq!(Some(0), serialized.pop());
+        assert_eq!(Some(0), serialized.pop());
+        assert_eq!(Some(0), serialized.pop());
+
+        let deserialized: OfferedContract = deserialize_all(&serialized, PRE_METADATA_VERSION);
+        assert_eq!(offered_contract.encode(), deserialized.encode());
+    }
+
+    #[test]
+    fn pre_shared_funding_share_version_can_be_read() {
+        let buf = include_bytes!("../../test_inputs/Accepted");
+        let mut offered_contract = AcceptedContract::deserialize(&mut Cursor::new(&buf[..]))
+            .unwrap()
+            .offered_contract;
+        let total_input = offered_contract
+            .funding_inputs_info
+            .iter()
+            .map(|x| get_prev_output(&x.funding_input).unwrap().value)
+            .sum::<u64>();
+        offered_contract.shared_funding_input = Some(SharedFundingInput {
+            funding_script: Script::new(),
+            own_pubkey: offered_contract.offer_params.fund_pubkey,
+        });
+        offered_contract.offer_params.input_amount = total_input - 1000;
+        let mut serialized = offered_contract.serialize().unwrap();
+        assert_eq!(Some(0), serialized.pop());
+        assert_eq!(Some(0), serialized.pop());
+
+        let deserialized: OfferedContract =
+            deserialize_all(&serialized, PRE_SHARED_FUNDING_SHARE_VERSION);
+        assert_eq!(Some(1000), deserialized.shared_funding_accept_share);
+    }
+
+    #[test]
+    fn pre_closing_time_version_can_be_read() {
+        let buf = include_bytes!("../../test_inputs/Accepted");
+        let accepted = AcceptedContract::deserialize(&mut Cursor::new(&buf[..])).unwrap();
+        let closed = ClosedContract {
+            attestations: None,
+            signed_cet: None,
+            contract_id: accepted.get_contract_id(),
+            temporary_contract_id: accepted.offered_contract.id,
+            counter_party_id: accepted.offered_contract.counter_party,
+            pnl: -10,
+            closed_at: 0,
+        };
+        let mut serialized = closed.serialize().unwrap();
+        serialized.truncate(serialized.len() - 8);
+
+        let deserialized: ClosedContract = deserialize_all(&serialized, PRE_CLOSING_TIME_VERSION);
+        assert_eq!(closed.encode(), deserialized.encode());
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let buf = include_bytes!("../../test_inputs/Accepted");
+        AcceptedContract::deserialize_versioned(
+            &mut Cursor::new(&buf[..]),
+            SERIALIZATION_VERSION + 1,
+        )
+        .expect_err("unknown versions not to be readable");
+    }
+}
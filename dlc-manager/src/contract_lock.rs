@@ -0,0 +1,85 @@
+//! #ContractLocks
+//! Serializes the processing of messages relating to the same contract, while
+//! letting messages for different contracts be processed concurrently.
+
+use std::collections::HashSet;
+use std::sync::{Condvar, Mutex};
+
+use crate::ContractId;
+
+/// The ids of the contracts being currently updated.
+#[derive(Default)]
+pub(crate) struct ContractLocks {
+    locked: Mutex<HashSet<ContractId>>,
+    released: Condvar,
+}
+
+/// Keeps the contract with the given id locked until dropped.
+pub(crate) struct ContractLockGuard<'a> {
+    locks: &'a ContractLocks,
+    id: ContractId,
+}
+
+impl ContractLocks {
+    /// Locks the contract with the given id, waiting for it to be released if
+    /// another thread holds the lock.
+    pub(crate) fn lock(&self, id: &ContractId) -> ContractLockGuard<'_> {
+        let mut locked = self.locked.lock().unwrap();
+        while locked.contains(id) {
+            locked = self.released.wait(locked).unwrap();
+        }
+        locked.insert(*id);
+        ContractLockGuard {
+            locks: self,
+            id: *id,
+        }
+    }
+}
+
+impl Drop for ContractLockGuard<'_> {
+    fn drop(&mut self) {
+        // Avoid panicking while unwinding if another thread poisoned the lock.
+        if let Ok(mut locked) = self.locks.locked.lock() {
+            locked.remove(&self.id);
+        }
+        self.locks.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn same_contract_is_locked_until_released() {
+        let locks = Arc::new(ContractLocks::default());
+        let acquired = Arc::new(AtomicBool::new(false));
+        let guard = locks.lock(&[1; 32]);
+
+        let handle = {
+            let locks = locks.clone();
+            let acquired = acquired.clone();
+            thread::spawn(move || {
+                let _guard = locks.lock(&[1; 32]);
+                acquired.store(true, Ordering::SeqCst);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!acquired.load(Ordering::SeqCst));
+        drop(guard);
+        handle.join().unwrap();
+        assert!(acquired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn different_contracts_are_locked_independently() {
+        let locks = ContractLocks::default();
+        let _first = locks.lock(&[1; 32]);
+        let _second = locks.lock(&[2; 32]);
+    }
+}
@@ -2,63 +2,241 @@
 
 use std::ops::Deref;
 
-use bitcoin::{consensus::Decodable, Script, Transaction, Witness};
+use bitcoin::{
+    consensus::Decodable,
+    util::{psbt::PartiallySignedTransaction, sighash::SighashCache},
+    EcdsaSig, EcdsaSighashType, OutPoint, Script, Transaction, TxOut, Witness,
+};
+use dlc::ownership_proof::{
+    get_ownership_proof_message, get_ownership_proof_tx, is_ownership_proof_supported,
+    verify_ownership_proof,
+};
 use dlc::{DlcTransactions, PartyParams};
 use dlc_messages::{
     oracle_msgs::{OracleAnnouncement, OracleAttestation},
-    AcceptDlc, FundingSignature, FundingSignatures, OfferDlc, SignDlc, WitnessElement,
+    AcceptDlc, AdditionalPayoutOutputs, FundingInput, FundingSignature, FundingSignatures,
+    OfferDlc, OwnershipProof, OwnershipProofs, RolloverInfo, SignDlc, WitnessElement,
 };
 use secp256k1_zkp::{
-    ecdsa::Signature, All, EcdsaAdaptorSignature, PublicKey, Secp256k1, SecretKey, Signing,
+    ecdsa::Signature, All, EcdsaAdaptorSignature, Message, PublicKey, Secp256k1, SecretKey,
+    Signing, Verification,
 };
 
 use crate::{
     contract::{
         accepted_contract::AcceptedContract, contract_info::ContractInfo,
         contract_input::ContractInput, offered_contract::OfferedContract,
-        signed_contract::SignedContract, AdaptorInfo, FundingInputInfo,
+        signed_contract::SignedContract, AdaptorInfo, FundingInputInfo, SharedFundingInput,
     },
     conversion_utils::get_tx_input_infos,
     error::Error,
-    Blockchain, ChannelId, Signer, Time, Wallet,
+    Blockchain, ChannelId, ContractSigner, Time, Utxo, Wallet,
 };
 
 /// Creates an [`OfferedContract`] and [`OfferDlc`] message from the provided
 /// contract and oracle information.
-pub fn offer_contract<C: Signing, W: Deref, B: Deref, T: Deref>(
+pub fn offer_contract<C: Signing, W: Deref, S: Deref, B: Deref, T: Deref>(
     secp: &Secp256k1<C>,
     contract_input: &ContractInput,
     oracle_announcements: Vec<Vec<OracleAnnouncement>>,
     refund_delay: u32,
     counter_party: &PublicKey,
     wallet: &W,
+    signer: &S,
     blockchain: &B,
     time: &T,
 ) -> Result<(OfferedContract, OfferDlc), Error>
 where
     W::Target: Wallet,
+    S::Target: ContractSigner,
     B::Target: Blockchain,
     T::Target: Time,
 {
     contract_input.validate()?;
 
+    let temporary_contract_id = crate::utils::get_new_temporary_id();
+
     let (party_params, _, funding_inputs_info) = crate::utils::get_party_params(
         secp,
+        counter_party,
+        &temporary_contract_id,
         contract_input.offer_collateral,
         contract_input.fee_rate,
+        contract_input.payout_script_pubkey.as_ref(),
+        contract_input.change_script_pubkey.as_ref(),
         wallet,
+        signer,
         blockchain,
     )?;
 
-    let offered_contract = OfferedContract::new(
+    create_offer(
         contract_input,
         oracle_announcements,
+        refund_delay,
+        counter_party,
+        temporary_contract_id,
         &party_params,
         &funding_inputs_info,
+        signer,
+        time,
+    )
+}
+
+/// Creates an [`OfferedContract`] and [`OfferDlc`] message funded by the given
+/// UTXOs, which must cover the offer collateral and fees.
+pub fn offer_contract_with_utxos<C: Signing, W: Deref, S: Deref, B: Deref, T: Deref>(
+    secp: &Secp256k1<C>,
+    contract_input: &ContractInput,
+    oracle_announcements: Vec<Vec<OracleAnnouncement>>,
+    refund_delay: u32,
+    counter_party: &PublicKey,
+    utxos: Vec<Utxo>,
+    wallet: &W,
+    signer: &S,
+    blockchain: &B,
+    time: &T,
+) -> Result<(OfferedContract, OfferDlc), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+    B::Target: Blockchain,
+    T::Target: Time,
+{
+    contract_input.validate()?;
+
+    let temporary_contract_id = crate::utils::get_new_temporary_id();
+
+    let (party_params, _, funding_inputs_info) = crate::utils::get_party_params_from_utxos(
+        secp,
+        counter_party,
+        &temporary_contract_id,
+        contract_input.offer_collateral,
+        utxos,
+        contract_input.payout_script_pubkey.as_ref(),
+        contract_input.change_script_pubkey.as_ref(),
+        wallet,
+        signer,
+        blockchain,
+    )?;
+
+    create_offer(
+        contract_input,
+        oracle_announcements,
+        refund_delay,
+        counter_party,
+        temporary_contract_id,
+        &party_params,
+        &funding_inputs_info,
+        signer,
+        time,
+    )
+}
+
+fn create_offer<S: Deref, T: Deref>(
+    contract_input: &ContractInput,
+    oracle_announcements: Vec<Vec<OracleAnnouncement>>,
+    refund_delay: u32,
+    counter_party: &PublicKey,
+    temporary_contract_id: [u8; 32],
+    party_params: &PartyParams,
+    funding_inputs_info: &[FundingInputInfo],
+    signer: &S,
+    time: &T,
+) -> Result<(OfferedContract, OfferDlc), Error>
+where
+    S::Target: ContractSigner,
+    T::Target: Time,
+{
+    let mut offered_contract = OfferedContract::new(
+        contract_input,
+        oracle_announcements,
+        party_params,
+        funding_inputs_info,
         counter_party,
         refund_delay,
         time.unix_time_now() as u32,
     );
+    offered_contract.id = temporary_contract_id;
+    offered_contract.offer_params.additional_payout_outputs =
+        crate::utils::get_additional_payout_outputs(contract_input);
+
+    let mut offer_msg: OfferDlc = (&offered_contract).into();
+    if signer.can_sign_funding_inputs() {
+        offer_msg.ownership_proofs = Some(get_ownership_proofs(
+            &offered_contract.id,
+            funding_inputs_info,
+            signer,
+        )?);
+    }
+
+    Ok((offered_contract, offer_msg))
+}
+
+/// Creates an [`OfferedContract`] and [`OfferDlc`] message for a contract funded
+/// solely by the given output jointly owned with the counter party. Out of the
+/// output, `accept_share` is attributed to the counter party and the rest to us,
+/// each party receiving what is not used as collateral and fees in a change
+/// output.
+pub fn offer_contract_with_shared_funding<C: Signing, W: Deref, S: Deref, T: Deref>(
+    secp: &Secp256k1<C>,
+    contract_input: &ContractInput,
+    oracle_announcements: Vec<Vec<OracleAnnouncement>>,
+    refund_delay: u32,
+    counter_party: &PublicKey,
+    prev_tx: &Transaction,
+    prev_tx_vout: u32,
+    shared_funding_input: &SharedFundingInput,
+    accept_share: u64,
+    wallet: &W,
+    signer: &S,
+    time: &T,
+) -> Result<(OfferedContract, OfferDlc), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+    T::Target: Time,
+{
+    contract_input.validate()?;
+
+    let temporary_contract_id = crate::utils::get_new_temporary_id();
+
+    let (party_params, _, funding_inputs_info) = crate::utils::get_party_params_for_shared_funding(
+        secp,
+        counter_party,
+        &temporary_contract_id,
+        contract_input.offer_collateral,
+        prev_tx,
+        prev_tx_vout,
+        shared_funding_input,
+        contract_input.payout_script_pubkey.as_ref(),
+        contract_input.change_script_pubkey.as_ref(),
+        wallet,
+        signer,
+    )?;
+
+    let mut offered_contract = OfferedContract::new(
+        contract_input,
+        oracle_announcements,
+        &party_params,
+        &funding_inputs_info,
+        counter_party,
+        refund_delay,
+        time.unix_time_now() as u32,
+    );
+    offered_contract.id = temporary_contract_id;
+    offered_contract.offer_params.additional_payout_outputs =
+        crate::utils::get_additional_payout_outputs(contract_input);
+    offered_contract.shared_funding_input = Some(shared_funding_input.clone());
+    offered_contract.shared_funding_accept_share = Some(accept_share);
+    offered_contract.offer_params.input_amount = party_params
+        .input_amount
+        .checked_sub(accept_share)
+        .filter(|x| *x > 0)
+        .ok_or_else(|| {
+            Error::InvalidParameters(
+                "Both parties must be attributed part of the shared output.".to_string(),
+            )
+        })?;
 
     let offer_msg: OfferDlc = (&offered_contract).into();
 
@@ -67,44 +245,125 @@ where
 
 /// Creates an [`AcceptedContract`] and produces
 /// the accepting party's cet adaptor signatures.
-pub fn accept_contract<W: Deref, B: Deref>(
+pub fn accept_contract<W: Deref, S: Deref, B: Deref>(
     secp: &Secp256k1<All>,
     offered_contract: &OfferedContract,
     wallet: &W,
+    signer: &S,
     blockchain: &B,
 ) -> Result<(AcceptedContract, AcceptDlc), crate::Error>
 where
     W::Target: Wallet,
+    S::Target: ContractSigner,
     B::Target: Blockchain,
 {
     let total_collateral = offered_contract.total_collateral;
 
     let (accept_params, fund_secret_key, funding_inputs) = crate::utils::get_party_params(
         secp,
+        &offered_contract.counter_party,
+        &offered_contract.id,
         total_collateral - offered_contract.offer_params.collateral,
         offered_contract.fee_rate_per_vb,
+        None,
+        None,
         wallet,
+        signer,
         blockchain,
     )?;
 
-    let dlc_transactions = dlc::create_dlc_transactions(
-        &offered_contract.offer_params,
+    let dlc_transactions = create_contract_dlc_transactions(offered_contract, &accept_params)?;
+
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+
+    let (accepted_contract, adaptor_sigs) = accept_contract_internal(
+        secp,
+        offered_contract,
         &accept_params,
-        &offered_contract.contract_info[0].get_payouts(total_collateral)?,
-        offered_contract.refund_locktime,
-        offered_contract.fee_rate_per_vb,
+        &funding_inputs,
+        &fund_secret_key,
+        fund_output_value,
+        None,
+        &dlc_transactions,
+    )?;
+
+    let mut accept_msg: AcceptDlc = accepted_contract.get_accept_contract_msg(&adaptor_sigs);
+    if signer.can_sign_funding_inputs() {
+        accept_msg.ownership_proofs = Some(get_ownership_proofs(
+            &offered_contract.id,
+            &funding_inputs,
+            signer,
+        )?);
+    }
+
+    Ok((accepted_contract, accept_msg))
+}
+
+/// Creates an [`AcceptedContract`] for an offer funded by an output jointly
+/// owned with the offering party, and produces the accepting party's cet adaptor
+/// signatures. No funding input is provided by the accepting party, which is
+/// attributed `accept_share` out of the shared output. The offer is rejected if
+/// it attributes it a different amount.
+pub fn accept_contract_with_shared_funding<W: Deref, S: Deref>(
+    secp: &Secp256k1<All>,
+    offered_contract: &OfferedContract,
+    shared_funding_input: &SharedFundingInput,
+    accept_share: u64,
+    wallet: &W,
+    signer: &S,
+) -> Result<(AcceptedContract, AcceptDlc), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+{
+    if get_shared_funding_accept_share(offered_contract) != Some(accept_share) {
+        return Err(Error::InvalidParameters(
+            "Offer does not attribute the expected share of the shared output.".to_string(),
+        ));
+    }
+
+    let mut nb_shared_inputs = 0;
+    for funding_input_info in &offered_contract.funding_inputs_info {
+        if is_shared_funding_input(&funding_input_info.funding_input, shared_funding_input)? {
+            nb_shared_inputs += 1;
+        }
+    }
+
+    if nb_shared_inputs != 1 {
+        return Err(Error::InvalidParameters(
+            "Offer funding inputs do not contain a single shared funding input".to_string(),
+        ));
+    }
+
+    crate::utils::get_shared_funding_counter_pubkey(
+        &shared_funding_input.funding_script,
+        &shared_funding_input.own_pubkey,
+    )?;
+
+    let mut offered_contract = offered_contract.clone();
+    offered_contract.shared_funding_input = Some(shared_funding_input.clone());
+
+    let (accept_params, fund_secret_key) = crate::utils::get_party_params_without_inputs(
+        secp,
+        &offered_contract.counter_party,
+        &offered_contract.id,
+        offered_contract.total_collateral - offered_contract.offer_params.collateral,
         0,
-        offered_contract.cet_locktime,
-        offered_contract.fund_output_serial_id,
+        None,
+        None,
+        wallet,
+        signer,
     )?;
 
+    let dlc_transactions = create_contract_dlc_transactions(&offered_contract, &accept_params)?;
+
     let fund_output_value = dlc_transactions.get_fund_output().value;
 
     let (accepted_contract, adaptor_sigs) = accept_contract_internal(
         secp,
-        offered_contract,
+        &offered_contract,
         &accept_params,
-        &funding_inputs,
+        &[],
         &fund_secret_key,
         fund_output_value,
         None,
@@ -116,6 +375,451 @@ where
     Ok((accepted_contract, accept_msg))
 }
 
+/// Creates the DLC transactions for the given offered contract and accepting
+/// party parameters. When the contract is funded by a shared output, the value
+/// of the output is split so that the accepting party contributes its collateral
+/// and fee share out of it.
+fn create_contract_dlc_transactions(
+    offered_contract: &OfferedContract,
+    accept_params: &PartyParams,
+) -> Result<DlcTransactions, Error> {
+    let mut offer_params = offered_contract.offer_params.clone();
+    let mut accept_params = accept_params.clone();
+
+    if offered_contract.shared_funding_input.is_some() {
+        let (offer_amount, accept_amount) = get_shared_funding_amounts(offered_contract)?;
+        offer_params.input_amount = offer_amount;
+        accept_params.input_amount = accept_amount;
+    }
+
+    Ok(dlc::create_dlc_transactions(
+        &offer_params,
+        &accept_params,
+        &offered_contract.contract_info[0].get_payouts(offered_contract.total_collateral)?,
+        offered_contract.refund_locktime,
+        offered_contract.fee_rate_per_vb,
+        0,
+        offered_contract.cet_locktime,
+        offered_contract.fund_output_serial_id,
+    )?)
+}
+
+/// Returns the part of the shared output funding the given contract that is
+/// attributed to the accepting party. When rolling over a contract, it is the
+/// part of the rolled over contract attributed to the accepting party, and the
+/// share recorded in the offer otherwise.
+fn get_shared_funding_accept_share(offered_contract: &OfferedContract) -> Option<u64> {
+    match &offered_contract.rollover {
+        Some(rollover) => Some(rollover.counter_payout),
+        None => offered_contract.shared_funding_accept_share,
+    }
+}
+
+/// Returns the parts of the funding inputs of the given contract, which include
+/// a shared output, that are attributed to the offer and accepting parties. The
+/// accepting party is attributed its share of the shared output, and the offer
+/// party the rest of the inputs.
+pub(crate) fn get_shared_funding_amounts(
+    offered_contract: &OfferedContract,
+) -> Result<(u64, u64), Error> {
+    let accept_amount = get_shared_funding_accept_share(offered_contract).ok_or_else(|| {
+        Error::InvalidState(
+            "Share of the shared output of the accepting party is unknown.".to_string(),
+        )
+    })?;
+    let total_input = offered_contract
+        .funding_inputs_info
+        .iter()
+        .map(|x| get_prev_output(&x.funding_input).map(|o| o.value))
+        .sum::<Result<u64, Error>>()?;
+
+    let offer_amount = total_input.checked_sub(accept_amount).ok_or_else(|| {
+        Error::InvalidParameters("Shared output value is too low to fund the contract".to_string())
+    })?;
+    Ok((offer_amount, accept_amount))
+}
+
+/// Returns the shared funding input spending the fund output of the given
+/// contract.
+fn get_rollover_shared_funding_input(contract: &SignedContract) -> SharedFundingInput {
+    let accepted_contract = &contract.accepted_contract;
+    let offered_contract = &accepted_contract.offered_contract;
+    let own_pubkey = if offered_contract.is_offer_party {
+        offered_contract.offer_params.fund_pubkey
+    } else {
+        accepted_contract.accept_params.fund_pubkey
+    };
+    SharedFundingInput {
+        funding_script: accepted_contract
+            .dlc_transactions
+            .funding_script_pubkey
+            .clone(),
+        own_pubkey,
+    }
+}
+
+fn check_rollover_counter_payout(
+    contract: &SignedContract,
+    counter_payout: u64,
+) -> Result<u64, Error> {
+    if contract.channel_id.is_some() {
+        return Err(Error::InvalidParameters(
+            "Contracts within channels cannot be rolled over.".to_string(),
+        ));
+    }
+    let fund_output_value = contract
+        .accepted_contract
+        .dlc_transactions
+        .get_fund_output()
+        .value;
+    // The counter party can be attributed nothing, but the fund output must
+    // at least partly fund our side of the new contract.
+    if counter_payout >= fund_output_value {
+        return Err(Error::InvalidParameters(
+            "Counter payout must be lower than the value of the rolled over contract.".to_string(),
+        ));
+    }
+    Ok(fund_output_value)
+}
+
+/// Creates an [`OfferedContract`] and [`OfferDlc`] message for a contract funded
+/// solely by the fund output of the given confirmed contract, which is settled
+/// by the funding transaction of the offered contract. Out of the fund output,
+/// `counter_payout` is attributed to the counter party and the rest to us, each
+/// party receiving what is not used as collateral and fees in a change output.
+pub fn offer_contract_rollover<C: Signing, W: Deref, S: Deref, T: Deref>(
+    secp: &Secp256k1<C>,
+    contract: &SignedContract,
+    contract_input: &ContractInput,
+    oracle_announcements: Vec<Vec<OracleAnnouncement>>,
+    refund_delay: u32,
+    counter_payout: u64,
+    wallet: &W,
+    signer: &S,
+    time: &T,
+) -> Result<(OfferedContract, OfferDlc), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+    T::Target: Time,
+{
+    check_rollover_counter_payout(contract, counter_payout)?;
+    let accepted_contract = &contract.accepted_contract;
+    let dlc_transactions = &accepted_contract.dlc_transactions;
+
+    let (mut offered_contract, _) = offer_contract_with_shared_funding(
+        secp,
+        contract_input,
+        oracle_announcements,
+        refund_delay,
+        &accepted_contract.offered_contract.counter_party,
+        &dlc_transactions.fund,
+        dlc_transactions.get_fund_output_index() as u32,
+        &get_rollover_shared_funding_input(contract),
+        counter_payout,
+        wallet,
+        signer,
+        time,
+    )?;
+
+    // The share of the counter party is given by the rollover information.
+    offered_contract.shared_funding_accept_share = None;
+    offered_contract.rollover = Some(RolloverInfo {
+        contract_id: accepted_contract.get_contract_id(),
+        counter_payout,
+    });
+    let offer_msg: OfferDlc = (&offered_contract).into();
+
+    Ok((offered_contract, offer_msg))
+}
+
+/// Sets up a contract offered by the counter party to be funded by the fund
+/// output of the given contract, which it rolls over as described by
+/// `rollover`. The other funding inputs of the offer top up the collateral of
+/// the counter party.
+pub(crate) fn apply_rollover(
+    offered_contract: &mut OfferedContract,
+    contract: &SignedContract,
+    rollover: &RolloverInfo,
+) -> Result<(), Error> {
+    check_rollover_counter_payout(contract, rollover.counter_payout)?;
+    let dlc_transactions = &contract.accepted_contract.dlc_transactions;
+    let fund_outpoint = OutPoint {
+        txid: dlc_transactions.fund.txid(),
+        vout: dlc_transactions.get_fund_output_index() as u32,
+    };
+    let mut total_input = 0;
+    let mut nb_fund_inputs = 0;
+    for funding_input_info in &offered_contract.funding_inputs_info {
+        let (outpoint, tx_out) = get_prev_outpoint_and_output(&funding_input_info.funding_input)?;
+        if outpoint == fund_outpoint {
+            nb_fund_inputs += 1;
+        }
+        total_input += tx_out.value;
+    }
+    if nb_fund_inputs != 1 {
+        return Err(Error::InvalidParameters(
+            "Offer must be funded by the fund output of the rolled over contract".to_string(),
+        ));
+    }
+
+    offered_contract.shared_funding_input = Some(get_rollover_shared_funding_input(contract));
+    offered_contract.shared_funding_accept_share = None;
+    offered_contract.rollover = Some(rollover.clone());
+    offered_contract.offer_params.input_amount = total_input - rollover.counter_payout;
+
+    Ok(())
+}
+
+/// Creates an [`OfferedContract`] and [`OfferDlc`] message for a contract
+/// topping up the collateral of the given confirmed contract, which it rolls
+/// over. The contract is funded by the fund output of the given contract and
+/// by new inputs from our wallet, the collateral of the counter party being
+/// unchanged. A top-up also settles the given contract: out of its fund
+/// output, `counter_payout` is attributed to the counter party and the rest to
+/// us, the counter party receiving what is not used as its collateral and fee
+/// share in a change output.
+pub fn offer_contract_top_up<C: Signing, W: Deref, S: Deref, B: Deref, T: Deref>(
+    secp: &Secp256k1<C>,
+    contract: &SignedContract,
+    contract_input: &ContractInput,
+    oracle_announcements: Vec<Vec<OracleAnnouncement>>,
+    refund_delay: u32,
+    counter_payout: u64,
+    wallet: &W,
+    signer: &S,
+    blockchain: &B,
+    time: &T,
+) -> Result<(OfferedContract, OfferDlc), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+    B::Target: Blockchain,
+    T::Target: Time,
+{
+    let accepted_contract = &contract.accepted_contract;
+    let (own_collateral, counter_collateral) = if accepted_contract.offered_contract.is_offer_party
+    {
+        (
+            accepted_contract.offered_contract.offer_params.collateral,
+            accepted_contract.accept_params.collateral,
+        )
+    } else {
+        (
+            accepted_contract.accept_params.collateral,
+            accepted_contract.offered_contract.offer_params.collateral,
+        )
+    };
+    if contract_input.accept_collateral != counter_collateral
+        || contract_input.offer_collateral <= own_collateral
+    {
+        return Err(Error::InvalidParameters(
+            "A top-up must only increase our own collateral.".to_string(),
+        ));
+    }
+
+    // The counter party does not provide inputs, so its collateral and fee
+    // share are taken out of its payout.
+    let counter_required_amount = crate::utils::get_approximate_required_amount(
+        contract_input.accept_collateral,
+        contract_input.fee_rate,
+    );
+    if counter_payout < counter_required_amount {
+        return Err(Error::InvalidParameters(
+            "Counter payout does not cover the collateral of the counter party.".to_string(),
+        ));
+    }
+
+    let (mut offered_contract, _) = offer_contract_rollover(
+        secp,
+        contract,
+        contract_input,
+        oracle_announcements,
+        refund_delay,
+        counter_payout,
+        wallet,
+        signer,
+        time,
+    )?;
+
+    let own_share = offered_contract.offer_params.input_amount;
+    let required_amount = crate::utils::get_approximate_required_amount(
+        contract_input.offer_collateral,
+        contract_input.fee_rate,
+    );
+    let missing_amount = required_amount
+        .checked_sub(own_share)
+        .filter(|x| *x > 0)
+        .ok_or_else(|| {
+            Error::InvalidParameters(
+                "The fund output covers the new collateral, the contract can be rolled over."
+                    .to_string(),
+            )
+        })?;
+    let utxos =
+        wallet.get_utxos_for_amount(missing_amount, Some(contract_input.fee_rate), false)?;
+    let (funding_inputs_info, funding_tx_info, total_input) =
+        crate::utils::get_funding_inputs_from_utxos(utxos, blockchain)?;
+
+    offered_contract
+        .funding_inputs_info
+        .extend(funding_inputs_info);
+    offered_contract.offer_params.inputs.extend(funding_tx_info);
+    offered_contract.offer_params.input_amount += total_input;
+
+    let offer_msg: OfferDlc = (&offered_contract).into();
+
+    Ok((offered_contract, offer_msg))
+}
+
+/// Returns whether the given funding input spends the given shared output.
+fn is_shared_funding_input(
+    funding_input: &FundingInput,
+    shared_funding_input: &SharedFundingInput,
+) -> Result<bool, Error> {
+    Ok(get_prev_output(funding_input)?.script_pubkey
+        == shared_funding_input.funding_script.to_v0_p2wsh())
+}
+
+pub(crate) fn get_prev_output(funding_input: &FundingInput) -> Result<TxOut, Error> {
+    get_prev_outpoint_and_output(funding_input).map(|(_, tx_out)| tx_out)
+}
+
+fn get_prev_outpoint_and_output(funding_input: &FundingInput) -> Result<(OutPoint, TxOut), Error> {
+    let tx =
+        Transaction::consensus_decode(&mut funding_input.prev_tx.as_slice()).map_err(|_| {
+            Error::InvalidParameters(
+                "Could not decode funding input previous tx parameter".to_string(),
+            )
+        })?;
+    let vout = funding_input.prev_tx_vout;
+    let tx_out = tx.output.get(vout as usize).cloned().ok_or_else(|| {
+        Error::InvalidParameters(format!("Previous tx output not found at index {}", vout))
+    })?;
+    Ok((
+        OutPoint {
+            txid: tx.txid(),
+            vout,
+        },
+        tx_out,
+    ))
+}
+
+/// Produces proofs that the signer controls the given funding inputs. Inputs
+/// whose script type does not support ownership proofs are skipped.
+fn get_ownership_proofs<S: Deref>(
+    temporary_contract_id: &[u8; 32],
+    funding_inputs_info: &[FundingInputInfo],
+    signer: &S,
+) -> Result<OwnershipProofs, Error>
+where
+    S::Target: ContractSigner,
+{
+    let mut proofs = Vec::new();
+    for funding_input_info in funding_inputs_info {
+        let funding_input = &funding_input_info.funding_input;
+        let (outpoint, prev_output) = get_prev_outpoint_and_output(funding_input)?;
+        if !is_ownership_proof_supported(&prev_output.script_pubkey, &funding_input.redeem_script) {
+            continue;
+        }
+
+        let message = get_ownership_proof_message(temporary_contract_id, &outpoint);
+        let (mut tx, to_spend_output) =
+            get_ownership_proof_tx(&message, &prev_output.script_pubkey);
+        let redeem_script = if funding_input.redeem_script.is_empty() {
+            None
+        } else {
+            Some(funding_input.redeem_script.clone())
+        };
+        signer.sign_tx_input(&mut tx, 0, &to_spend_output, redeem_script)?;
+
+        proofs.push(OwnershipProof {
+            input_serial_id: funding_input.input_serial_id,
+            witness_elements: tx.input[0]
+                .witness
+                .to_vec()
+                .into_iter()
+                .map(|witness| WitnessElement { witness })
+                .collect(),
+        });
+    }
+
+    Ok(OwnershipProofs { proofs })
+}
+
+/// Verifies the ownership proofs provided by a peer for its funding inputs.
+/// Peers that do not provide proofs are accepted for backward compatibility,
+/// but a peer providing them must include a valid proof for each of its inputs
+/// that support them.
+pub(crate) fn verify_ownership_proofs<C: Verification>(
+    secp: &Secp256k1<C>,
+    temporary_contract_id: &[u8; 32],
+    funding_inputs: &[FundingInput],
+    ownership_proofs: &Option<OwnershipProofs>,
+) -> Result<(), Error> {
+    let proofs = match ownership_proofs {
+        Some(ownership_proofs) => &ownership_proofs.proofs,
+        None => return Ok(()),
+    };
+
+    if proofs.iter().any(|proof| {
+        !funding_inputs
+            .iter()
+            .any(|input| input.input_serial_id == proof.input_serial_id)
+    }) {
+        return Err(Error::InvalidParameters(
+            "Ownership proof provided for unknown funding input".to_string(),
+        ));
+    }
+
+    for funding_input in funding_inputs {
+        let (outpoint, prev_output) = get_prev_outpoint_and_output(funding_input)?;
+        let proof = match proofs
+            .iter()
+            .find(|proof| proof.input_serial_id == funding_input.input_serial_id)
+        {
+            Some(proof) => proof,
+            None if !is_ownership_proof_supported(
+                &prev_output.script_pubkey,
+                &funding_input.redeem_script,
+            ) =>
+            {
+                continue
+            }
+            None => {
+                return Err(Error::InvalidParameters(format!(
+                    "Missing ownership proof for funding input {}",
+                    funding_input.input_serial_id
+                )))
+            }
+        };
+
+        let witness = Witness::from_vec(
+            proof
+                .witness_elements
+                .iter()
+                .map(|x| x.witness.clone())
+                .collect(),
+        );
+        let message = get_ownership_proof_message(temporary_contract_id, &outpoint);
+        verify_ownership_proof(
+            secp,
+            &message,
+            &prev_output.script_pubkey,
+            &funding_input.redeem_script,
+            &witness,
+        )
+        .map_err(|_| {
+            Error::InvalidParameters(format!(
+                "Invalid ownership proof for funding input {}",
+                funding_input.input_serial_id
+            ))
+        })?;
+    }
+
+    Ok(())
+}
+
 pub(crate) fn accept_contract_internal(
     secp: &Secp256k1<All>,
     offered_contract: &OfferedContract,
@@ -157,15 +861,13 @@ pub(crate) fn accept_contract_internal(
     for contract_info in offered_contract.contract_info.iter().skip(1) {
         let payouts = contract_info.get_payouts(total_collateral)?;
 
-        let tmp_cets = dlc::create_cets(
+        let tmp_cets = dlc::create_party_cets(
             &cet_input,
-            &offered_contract.offer_params.payout_script_pubkey,
-            offered_contract.offer_params.payout_serial_id,
-            &accept_params.payout_script_pubkey,
-            accept_params.payout_serial_id,
+            &offered_contract.offer_params,
+            accept_params,
             &payouts,
             0,
-        );
+        )?;
 
         let (adaptor_info, adaptor_sig) = contract_info.get_adaptor_info(
             secp,
@@ -215,47 +917,19 @@ pub(crate) fn accept_contract_internal(
 
 /// Verifies the information of the accepting party [`Accept` message](dlc_messages::AcceptDlc),
 /// creates a [`SignedContract`], and generates the offering party CET adaptor signatures.
+/// The offering party funding inputs are signed using the provided `funding_sighash_type`.
 pub fn verify_accepted_and_sign_contract<S: Deref>(
     secp: &Secp256k1<All>,
     offered_contract: &OfferedContract,
     accept_msg: &AcceptDlc,
     signer: &S,
+    funding_sighash_type: EcdsaSighashType,
 ) -> Result<(SignedContract, SignDlc), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
-    let (tx_input_infos, input_amount) = get_tx_input_infos(&accept_msg.funding_inputs)?;
-
-    let accept_params = PartyParams {
-        fund_pubkey: accept_msg.funding_pubkey,
-        change_script_pubkey: accept_msg.change_spk.clone(),
-        change_serial_id: accept_msg.change_serial_id,
-        payout_script_pubkey: accept_msg.payout_spk.clone(),
-        payout_serial_id: accept_msg.payout_serial_id,
-        inputs: tx_input_infos,
-        input_amount,
-        collateral: accept_msg.accept_collateral,
-    };
-
-    let cet_adaptor_signatures = accept_msg
-        .cet_adaptor_signatures
-        .ecdsa_adaptor_signatures
-        .iter()
-        .map(|x| x.signature)
-        .collect::<Vec<_>>();
-
-    let total_collateral = offered_contract.total_collateral;
-
-    let dlc_transactions = dlc::create_dlc_transactions(
-        &offered_contract.offer_params,
-        &accept_params,
-        &offered_contract.contract_info[0].get_payouts(total_collateral)?,
-        offered_contract.refund_locktime,
-        offered_contract.fee_rate_per_vb,
-        0,
-        offered_contract.cet_locktime,
-        offered_contract.fund_output_serial_id,
-    )?;
+    let (accept_params, cet_adaptor_signatures, dlc_transactions) =
+        get_accept_params(secp, offered_contract, accept_msg)?;
     let fund_output_value = dlc_transactions.get_fund_output().value;
     let fund_privkey =
         signer.get_secret_key_for_pubkey(&offered_contract.offer_params.fund_pubkey)?;
@@ -277,11 +951,166 @@ where
         None,
         &dlc_transactions,
         None,
+        funding_sighash_type,
+    )?;
+
+    let signed_msg: SignDlc = signed_contract.get_sign_dlc(adaptor_sigs);
+
+    Ok((signed_contract, signed_msg))
+}
+
+/// Verifies the information of the accepting party [`Accept` message](dlc_messages::AcceptDlc)
+/// and creates the offering party [`AcceptedContract`], without signing the
+/// funding inputs. These are then signed externally using the PSBT returned by
+/// [`get_funding_psbt`], and the contract signed using
+/// [`sign_accepted_contract_with_psbt`].
+pub fn verify_accepted_contract(
+    secp: &Secp256k1<All>,
+    offered_contract: &OfferedContract,
+    accept_msg: &AcceptDlc,
+) -> Result<AcceptedContract, Error> {
+    let (accept_params, cet_adaptor_signatures, dlc_transactions) =
+        get_accept_params(secp, offered_contract, accept_msg)?;
+    let input_value = dlc_transactions.get_fund_output().value;
+    let input_script_pubkey = dlc_transactions.funding_script_pubkey.clone();
+    verify_accepted_contract_internal(
+        secp,
+        offered_contract,
+        &accept_params,
+        &accept_msg
+            .funding_inputs
+            .iter()
+            .map(|x| x.into())
+            .collect::<Vec<_>>(),
+        &accept_msg.refund_signature,
+        &cet_adaptor_signatures,
+        input_value,
+        &input_script_pubkey,
+        &accept_params.fund_pubkey,
+        &dlc_transactions,
+    )
+}
+
+/// Creates the offering party [`SignedContract`] and [`SignDlc`] message for
+/// an [`AcceptedContract`] obtained from [`verify_accepted_contract`], using
+/// the funding input signatures found in the given PSBT.
+pub fn sign_accepted_contract_with_psbt<S: Deref>(
+    secp: &Secp256k1<All>,
+    accepted_contract: &AcceptedContract,
+    psbt: &PartiallySignedTransaction,
+    signer: &S,
+) -> Result<(SignedContract, SignDlc), Error>
+where
+    S::Target: ContractSigner,
+{
+    let offered_contract = &accepted_contract.offered_contract;
+    let mut accepted_contract = accepted_contract.clone();
+    let witnesses = crate::psbt::apply_funding_psbt(
+        &mut accepted_contract.dlc_transactions.fund,
+        psbt,
+        &offered_contract.funding_inputs_info,
+    )?;
+    let input_value = accepted_contract.dlc_transactions.get_fund_output().value;
+    let input_script_pubkey = accepted_contract
+        .dlc_transactions
+        .funding_script_pubkey
+        .clone();
+    let fund_privkey =
+        signer.get_secret_key_for_pubkey(&offered_contract.offer_params.fund_pubkey)?;
+    let (signed_contract, adaptor_sigs) = sign_accepted_contract_internal(
+        secp,
+        accepted_contract,
+        witnesses,
+        &fund_privkey,
+        &input_script_pubkey,
+        input_value,
+        None,
     )?;
 
-    let signed_msg: SignDlc = signed_contract.get_sign_dlc(adaptor_sigs);
+    let signed_msg = signed_contract.get_sign_dlc(adaptor_sigs);
+
+    Ok((signed_contract, signed_msg))
+}
+
+/// Returns a PSBT of the fund transaction of the given contract, in which the
+/// funding inputs of the local party are to be signed using the provided
+/// `funding_sighash_type`. Inputs already signed by the counter party are
+/// finalized.
+pub fn get_funding_psbt(
+    accepted_contract: &AcceptedContract,
+    funding_sighash_type: EcdsaSighashType,
+) -> Result<PartiallySignedTransaction, Error> {
+    dlc::util::validate_funding_sighash_type(funding_sighash_type)?;
+    let offered_contract = &accepted_contract.offered_contract;
+    let (own_inputs, counter_inputs) = if offered_contract.is_offer_party {
+        (
+            &offered_contract.funding_inputs_info,
+            &accepted_contract.funding_inputs,
+        )
+    } else {
+        (
+            &accepted_contract.funding_inputs,
+            &offered_contract.funding_inputs_info,
+        )
+    };
+    crate::psbt::create_funding_psbt(
+        &accepted_contract.dlc_transactions.fund,
+        counter_inputs,
+        own_inputs,
+        funding_sighash_type,
+    )
+}
+
+/// Verifies the ownership proofs of an [`AcceptDlc`] message and returns the
+/// accepting party parameters, CET adaptor signatures and the DLC transactions
+/// that it implies.
+fn get_accept_params(
+    secp: &Secp256k1<All>,
+    offered_contract: &OfferedContract,
+    accept_msg: &AcceptDlc,
+) -> Result<(PartyParams, Vec<EcdsaAdaptorSignature>, DlcTransactions), Error> {
+    if offered_contract.shared_funding_input.is_some() && !accept_msg.funding_inputs.is_empty() {
+        return Err(Error::InvalidParameters(
+            "Accept message cannot contain funding inputs for a shared funding output".to_string(),
+        ));
+    }
+
+    verify_ownership_proofs(
+        secp,
+        &offered_contract.id,
+        &accept_msg.funding_inputs,
+        &accept_msg.ownership_proofs,
+    )?;
+
+    let (tx_input_infos, input_amount) = get_tx_input_infos(&accept_msg.funding_inputs)?;
+
+    let accept_params = PartyParams {
+        fund_pubkey: accept_msg.funding_pubkey,
+        change_script_pubkey: accept_msg.change_spk.clone(),
+        change_serial_id: accept_msg.change_serial_id,
+        payout_script_pubkey: accept_msg.payout_spk.clone(),
+        payout_serial_id: accept_msg.payout_serial_id,
+        inputs: tx_input_infos,
+        input_amount,
+        collateral: accept_msg.accept_collateral,
+        additional_payout_outputs: AdditionalPayoutOutputs::to_payout_outputs(
+            &accept_msg.additional_payout_outputs,
+        ),
+    };
+    accept_params
+        .validate_payout_outputs()
+        .map_err(|_| Error::InvalidParameters("Invalid additional payout outputs".to_string()))?;
+
+    let cet_adaptor_signatures = accept_msg
+        .cet_adaptor_signatures
+        .ecdsa_adaptor_signatures
+        .iter()
+        .map(|x| x.signature)
+        .collect::<Vec<_>>();
 
-    Ok((signed_contract, signed_msg))
+    let dlc_transactions = create_contract_dlc_transactions(offered_contract, &accept_params)?;
+
+    Ok((accept_params, cet_adaptor_signatures, dlc_transactions))
 }
 
 pub(crate) fn verify_accepted_and_sign_contract_internal<S: Deref>(
@@ -298,10 +1127,64 @@ pub(crate) fn verify_accepted_and_sign_contract_internal<S: Deref>(
     counter_adaptor_pk: Option<PublicKey>,
     dlc_transactions: &DlcTransactions,
     channel_id: Option<ChannelId>,
+    funding_sighash_type: EcdsaSighashType,
 ) -> Result<(SignedContract, Vec<EcdsaAdaptorSignature>), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
+    dlc::util::validate_funding_sighash_type(funding_sighash_type)?;
+
+    let input_script_pubkey =
+        input_script_pubkey.unwrap_or_else(|| dlc_transactions.funding_script_pubkey.clone());
+    let counter_adaptor_pk = counter_adaptor_pk.unwrap_or(accept_params.fund_pubkey);
+
+    let mut accepted_contract = verify_accepted_contract_internal(
+        secp,
+        offered_contract,
+        accept_params,
+        funding_inputs_info,
+        refund_signature,
+        cet_adaptor_signatures,
+        input_value,
+        &input_script_pubkey,
+        &counter_adaptor_pk,
+        dlc_transactions,
+    )?;
+
+    let witnesses = sign_offer_funding_inputs(
+        secp,
+        offered_contract,
+        accept_params,
+        &mut accepted_contract.dlc_transactions.fund,
+        signer,
+        funding_sighash_type,
+    )?;
+
+    sign_accepted_contract_internal(
+        secp,
+        accepted_contract,
+        witnesses,
+        adaptor_secret,
+        &input_script_pubkey,
+        input_value,
+        channel_id,
+    )
+}
+
+/// Verifies the refund signature and CET adaptor signatures of the accepting
+/// party and returns the resulting [`AcceptedContract`] of the offering party.
+fn verify_accepted_contract_internal(
+    secp: &Secp256k1<All>,
+    offered_contract: &OfferedContract,
+    accept_params: &PartyParams,
+    funding_inputs_info: &[FundingInputInfo],
+    refund_signature: &Signature,
+    cet_adaptor_signatures: &[EcdsaAdaptorSignature],
+    input_value: u64,
+    input_script_pubkey: &Script,
+    counter_adaptor_pk: &PublicKey,
+    dlc_transactions: &DlcTransactions,
+) -> Result<AcceptedContract, Error> {
     let DlcTransactions {
         fund,
         cets,
@@ -309,28 +1192,24 @@ where
         funding_script_pubkey,
     } = dlc_transactions;
 
-    let mut fund = fund.clone();
     let mut cets = cets.clone();
 
-    let input_script_pubkey = input_script_pubkey.unwrap_or_else(|| funding_script_pubkey.clone());
-    let counter_adaptor_pk = counter_adaptor_pk.unwrap_or(accept_params.fund_pubkey);
-
     dlc::verify_tx_input_sig(
         secp,
         refund_signature,
         refund,
         0,
-        &input_script_pubkey,
+        input_script_pubkey,
         input_value,
-        &counter_adaptor_pk,
+        counter_adaptor_pk,
     )?;
 
     let (adaptor_info, mut adaptor_index) = offered_contract.contract_info[0]
         .verify_and_get_adaptor_info(
             secp,
             offered_contract.total_collateral,
-            &counter_adaptor_pk,
-            &input_script_pubkey,
+            counter_adaptor_pk,
+            input_script_pubkey,
             input_value,
             &cets,
             cet_adaptor_signatures,
@@ -346,15 +1225,13 @@ where
     for contract_info in offered_contract.contract_info.iter().skip(1) {
         let payouts = contract_info.get_payouts(total_collateral)?;
 
-        let tmp_cets = dlc::create_cets(
+        let tmp_cets = dlc::create_party_cets(
             &cet_input,
-            &offered_contract.offer_params.payout_script_pubkey,
-            offered_contract.offer_params.payout_serial_id,
-            &accept_params.payout_script_pubkey,
-            accept_params.payout_serial_id,
+            &offered_contract.offer_params,
+            accept_params,
             &payouts,
             0,
-        );
+        )?;
 
         let (adaptor_info, tmp_adaptor_index) = contract_info.verify_and_get_adaptor_info(
             secp,
@@ -374,24 +1251,37 @@ where
         adaptor_infos.push(adaptor_info);
     }
 
-    let mut own_signatures: Vec<EcdsaAdaptorSignature> = Vec::new();
+    let dlc_transactions = DlcTransactions {
+        fund: fund.clone(),
+        cets,
+        refund: refund.clone(),
+        funding_script_pubkey: funding_script_pubkey.clone(),
+    };
 
-    for (contract_info, adaptor_info) in offered_contract
-        .contract_info
-        .iter()
-        .zip(adaptor_infos.iter())
-    {
-        let sigs = contract_info.get_adaptor_signatures(
-            secp,
-            adaptor_info,
-            adaptor_secret,
-            &input_script_pubkey,
-            input_value,
-            &cets,
-        )?;
-        own_signatures.extend(sigs);
-    }
+    Ok(AcceptedContract {
+        offered_contract: offered_contract.clone(),
+        accept_params: accept_params.clone(),
+        funding_inputs: funding_inputs_info.to_vec(),
+        adaptor_infos,
+        adaptor_signatures: Some(cet_adaptor_signatures.to_vec()),
+        accept_refund_signature: *refund_signature,
+        dlc_transactions,
+    })
+}
 
+/// Signs the funding inputs of the offering party in the given fund
+/// transaction and returns their witnesses.
+fn sign_offer_funding_inputs<S: Deref>(
+    secp: &Secp256k1<All>,
+    offered_contract: &OfferedContract,
+    accept_params: &PartyParams,
+    fund: &mut Transaction,
+    signer: &S,
+    funding_sighash_type: EcdsaSighashType,
+) -> Result<Vec<Witness>, Error>
+where
+    S::Target: ContractSigner,
+{
     let mut input_serial_ids: Vec<_> = offered_contract
         .funding_inputs_info
         .iter()
@@ -400,8 +1290,7 @@ where
         .collect();
     input_serial_ids.sort_unstable();
 
-    // Vec<Witness>
-    let witnesses: Vec<Witness> = offered_contract
+    offered_contract
         .funding_inputs_info
         .iter()
         .map(|x| {
@@ -425,14 +1314,72 @@ where
                 Error::InvalidParameters(format!("Previous tx output not found at index {}", vout))
             })?;
 
+            // The shared output is completed by the accepting party which adds
+            // its own signature, so only ours is provided.
+            if let Some(shared_funding_input) = offered_contract
+                .shared_funding_input
+                .as_ref()
+                .filter(|x| tx_out.script_pubkey == x.funding_script.to_v0_p2wsh())
+            {
+                let sk = signer.get_secret_key_for_pubkey(&shared_funding_input.own_pubkey)?;
+                let sig = dlc::util::get_sig_for_tx_input(
+                    secp,
+                    fund,
+                    input_index,
+                    &shared_funding_input.funding_script,
+                    tx_out.value,
+                    funding_sighash_type,
+                    &sk,
+                )?;
+                return Ok(Witness::from_vec(vec![sig]));
+            }
+
             // pass wallet instead of privkeys
-            signer.sign_tx_input(&mut fund, input_index, tx_out, None)?;
+            signer.sign_tx_input_with_sighash_type(
+                fund,
+                input_index,
+                tx_out,
+                None,
+                funding_sighash_type,
+            )?;
 
             Ok(fund.input[input_index].witness.clone())
         })
-        .collect::<Result<Vec<_>, Error>>()?;
+        .collect()
+}
+
+/// Generates the offering party CET adaptor signatures and refund signature
+/// for the given [`AcceptedContract`] and creates the resulting
+/// [`SignedContract`] including the provided funding input witnesses.
+fn sign_accepted_contract_internal(
+    secp: &Secp256k1<All>,
+    accepted_contract: AcceptedContract,
+    funding_witnesses: Vec<Witness>,
+    adaptor_secret: &SecretKey,
+    input_script_pubkey: &Script,
+    input_value: u64,
+    channel_id: Option<ChannelId>,
+) -> Result<(SignedContract, Vec<EcdsaAdaptorSignature>), Error> {
+    let mut own_signatures: Vec<EcdsaAdaptorSignature> = Vec::new();
+
+    for (contract_info, adaptor_info) in accepted_contract
+        .offered_contract
+        .contract_info
+        .iter()
+        .zip(accepted_contract.adaptor_infos.iter())
+    {
+        let sigs = contract_info.get_adaptor_signatures(
+            secp,
+            adaptor_info,
+            adaptor_secret,
+            input_script_pubkey,
+            input_value,
+            &accepted_contract.dlc_transactions.cets,
+        )?;
+        own_signatures.extend(sigs);
+    }
 
-    let funding_signatures: Vec<FundingSignature> = witnesses
+    let funding_signatures: Vec<FundingSignature> = funding_witnesses
         .into_iter()
         .map(|witness| {
             let witness_elements = witness
@@ -441,38 +1388,19 @@ where
                     witness: z.to_vec(),
                 })
                 .collect();
-            Ok(FundingSignature { witness_elements })
+            FundingSignature { witness_elements }
         })
-        .collect::<Result<Vec<_>, Error>>()?;
-
-    input_serial_ids.sort_unstable();
+        .collect();
 
     let offer_refund_signature = dlc::util::get_raw_sig_for_tx_input(
         secp,
-        refund,
+        &accepted_contract.dlc_transactions.refund,
         0,
-        &input_script_pubkey,
+        input_script_pubkey,
         input_value,
         adaptor_secret,
     )?;
 
-    let dlc_transactions = DlcTransactions {
-        fund,
-        cets,
-        refund: refund.clone(),
-        funding_script_pubkey: funding_script_pubkey.clone(),
-    };
-
-    let accepted_contract = AcceptedContract {
-        offered_contract: offered_contract.clone(),
-        accept_params: accept_params.clone(),
-        funding_inputs: funding_inputs_info.to_vec(),
-        adaptor_infos,
-        adaptor_signatures: Some(cet_adaptor_signatures.to_vec()),
-        accept_refund_signature: *refund_signature,
-        dlc_transactions,
-    };
-
     let signed_contract = SignedContract {
         accepted_contract,
         adaptor_signatures: None,
@@ -486,15 +1414,17 @@ where
 
 /// Verifies the information from the offer party [`Sign` message](dlc_messages::SignDlc),
 /// creates the accepting party's [`SignedContract`] and returns it along with the
-/// signed fund transaction.
+/// signed fund transaction. The accepting party funding inputs are signed using
+/// the provided `funding_sighash_type`.
 pub fn verify_signed_contract<S: Deref>(
     secp: &Secp256k1<All>,
     accepted_contract: &AcceptedContract,
     sign_msg: &SignDlc,
     signer: &S,
+    funding_sighash_type: EcdsaSighashType,
 ) -> Result<(SignedContract, Transaction), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let cet_adaptor_signatures: Vec<_> = (&sign_msg.cet_adaptor_signatures).into();
     verify_signed_contract_internal(
@@ -508,9 +1438,56 @@ where
         None,
         signer,
         None,
+        funding_sighash_type,
     )
 }
 
+/// Verifies the information from the offer party [`Sign` message](dlc_messages::SignDlc)
+/// and creates the accepting party's [`SignedContract`], without signing the
+/// accepting party funding inputs. The fund transaction of the returned
+/// contract includes the offering party signatures, and can be completed using
+/// the PSBT returned by [`get_funding_psbt`] with [`finalize_fund_tx_with_psbt`].
+pub fn verify_signed_contract_for_psbt<S: Deref>(
+    secp: &Secp256k1<All>,
+    accepted_contract: &AcceptedContract,
+    sign_msg: &SignDlc,
+    signer: &S,
+    funding_sighash_type: EcdsaSighashType,
+) -> Result<SignedContract, Error>
+where
+    S::Target: ContractSigner,
+{
+    let cet_adaptor_signatures: Vec<_> = (&sign_msg.cet_adaptor_signatures).into();
+    let (mut signed_contract, fund_tx) = verify_signed_contract_signatures(
+        secp,
+        accepted_contract,
+        &sign_msg.refund_signature,
+        &cet_adaptor_signatures,
+        &sign_msg.funding_signatures,
+        accepted_contract.dlc_transactions.get_fund_output().value,
+        None,
+        None,
+        signer,
+        None,
+        funding_sighash_type,
+    )?;
+    signed_contract.accepted_contract.dlc_transactions.fund = fund_tx;
+    Ok(signed_contract)
+}
+
+/// Returns the fund transaction of a [`SignedContract`] obtained from
+/// [`verify_signed_contract_for_psbt`], completed with the accepting party
+/// funding input signatures found in the given PSBT.
+pub fn finalize_fund_tx_with_psbt(
+    signed_contract: &SignedContract,
+    psbt: &PartiallySignedTransaction,
+) -> Result<Transaction, Error> {
+    let accepted_contract = &signed_contract.accepted_contract;
+    let mut fund_tx = accepted_contract.dlc_transactions.fund.clone();
+    crate::psbt::apply_funding_psbt(&mut fund_tx, psbt, &accepted_contract.funding_inputs)?;
+    Ok(fund_tx)
+}
+
 pub(crate) fn verify_signed_contract_internal<S: Deref>(
     secp: &Secp256k1<All>,
     accepted_contract: &AcceptedContract,
@@ -522,10 +1499,89 @@ pub(crate) fn verify_signed_contract_internal<S: Deref>(
     counter_adaptor_pk: Option<PublicKey>,
     signer: &S,
     channel_id: Option<ChannelId>,
+    funding_sighash_type: EcdsaSighashType,
+) -> Result<(SignedContract, Transaction), Error>
+where
+    S::Target: ContractSigner,
+{
+    let (signed_contract, mut fund_tx) = verify_signed_contract_signatures(
+        secp,
+        accepted_contract,
+        refund_signature,
+        cet_adaptor_signatures,
+        funding_signatures,
+        input_value,
+        input_script_pubkey,
+        counter_adaptor_pk,
+        signer,
+        channel_id,
+        funding_sighash_type,
+    )?;
+
+    let mut input_serials: Vec<_> = accepted_contract
+        .offered_contract
+        .funding_inputs_info
+        .iter()
+        .chain(accepted_contract.funding_inputs.iter())
+        .map(|x| x.funding_input.input_serial_id)
+        .collect();
+    input_serials.sort_unstable();
+
+    for funding_input_info in &accepted_contract.funding_inputs {
+        let input_index = input_serials
+            .iter()
+            .position(|x| x == &funding_input_info.funding_input.input_serial_id)
+            .ok_or_else(|| {
+                Error::InvalidState(format!(
+                    "Could not find input for serial id {}",
+                    funding_input_info.funding_input.input_serial_id,
+                ))
+            })?;
+        let tx =
+            Transaction::consensus_decode(&mut funding_input_info.funding_input.prev_tx.as_slice())
+                .map_err(|_| {
+                    Error::InvalidParameters(
+                        "Could not decode funding input previous tx parameter".to_string(),
+                    )
+                })?;
+        let vout = funding_input_info.funding_input.prev_tx_vout;
+        let tx_out = tx.output.get(vout as usize).ok_or_else(|| {
+            Error::InvalidParameters(format!("Previous tx output not found at index {}", vout))
+        })?;
+
+        signer.sign_tx_input_with_sighash_type(
+            &mut fund_tx,
+            input_index,
+            tx_out,
+            None,
+            funding_sighash_type,
+        )?;
+    }
+
+    Ok((signed_contract, fund_tx))
+}
+
+/// Verifies the signatures of the offering party and returns the accepting
+/// party [`SignedContract`] along with the fund transaction including the
+/// offering party funding input signatures.
+fn verify_signed_contract_signatures<S: Deref>(
+    secp: &Secp256k1<All>,
+    accepted_contract: &AcceptedContract,
+    refund_signature: &Signature,
+    cet_adaptor_signatures: &[EcdsaAdaptorSignature],
+    funding_signatures: &FundingSignatures,
+    input_value: u64,
+    input_script_pubkey: Option<Script>,
+    counter_adaptor_pk: Option<PublicKey>,
+    signer: &S,
+    channel_id: Option<ChannelId>,
+    funding_sighash_type: EcdsaSighashType,
 ) -> Result<(SignedContract, Transaction), Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
+    dlc::util::validate_funding_sighash_type(funding_sighash_type)?;
+
     let offered_contract = &accepted_contract.offered_contract;
     let input_script_pubkey = input_script_pubkey.unwrap_or_else(|| {
         accepted_contract
@@ -590,38 +1646,40 @@ where
                 ))
             })?;
 
-        fund_tx.input[input_index].witness = Witness::from_vec(
+        let witness = Witness::from_vec(
             funding_signatures
                 .witness_elements
                 .iter()
                 .map(|x| x.witness.clone())
                 .collect(),
         );
-    }
 
-    for funding_input_info in &accepted_contract.funding_inputs {
-        let input_index = input_serials
-            .iter()
-            .position(|x| x == &funding_input_info.funding_input.input_serial_id)
-            .ok_or_else(|| {
-                Error::InvalidState(format!(
-                    "Could not find input for serial id {}",
-                    funding_input_info.funding_input.input_serial_id,
-                ))
-            })?;
-        let tx =
-            Transaction::consensus_decode(&mut funding_input_info.funding_input.prev_tx.as_slice())
-                .map_err(|_| {
-                    Error::InvalidParameters(
-                        "Could not decode funding input previous tx parameter".to_string(),
-                    )
-                })?;
-        let vout = funding_input_info.funding_input.prev_tx_vout;
-        let tx_out = tx.output.get(vout as usize).ok_or_else(|| {
-            Error::InvalidParameters(format!("Previous tx output not found at index {}", vout))
+        dlc::util::validate_funding_witness_sighash_types(&witness).map_err(|_| {
+            Error::InvalidParameters(format!(
+                "Invalid sighash type for funding input with serial id {}",
+                funding_input.funding_input.input_serial_id
+            ))
         })?;
 
-        signer.sign_tx_input(&mut fund_tx, input_index, tx_out, None)?;
+        let witness = match &offered_contract.shared_funding_input {
+            Some(shared_funding_input)
+                if is_shared_funding_input(&funding_input.funding_input, shared_funding_input)? =>
+            {
+                get_shared_funding_witness(
+                    secp,
+                    &fund_tx,
+                    input_index,
+                    &funding_input.funding_input,
+                    shared_funding_input,
+                    &witness,
+                    signer,
+                    funding_sighash_type,
+                )?
+            }
+            _ => witness,
+        };
+
+        fund_tx.input[input_index].witness = witness;
     }
 
     let signed_contract = SignedContract {
@@ -635,6 +1693,80 @@ where
     Ok((signed_contract, fund_tx))
 }
 
+/// Verifies the counter party signature for the shared funding input and
+/// returns the complete witness including our own signature.
+fn get_shared_funding_witness<S: Deref>(
+    secp: &Secp256k1<All>,
+    fund_tx: &Transaction,
+    input_index: usize,
+    funding_input: &FundingInput,
+    shared_funding_input: &SharedFundingInput,
+    counter_witness: &Witness,
+    signer: &S,
+    funding_sighash_type: EcdsaSighashType,
+) -> Result<Witness, Error>
+where
+    S::Target: ContractSigner,
+{
+    let invalid_signature = || {
+        Error::InvalidParameters(format!(
+            "Invalid signature for shared funding input with serial id {}",
+            funding_input.input_serial_id
+        ))
+    };
+
+    let counter_sig = match counter_witness.to_vec().as_slice() {
+        [sig] => EcdsaSig::from_slice(sig).map_err(|_| invalid_signature())?,
+        _ => return Err(invalid_signature()),
+    };
+    let counter_pubkey = crate::utils::get_shared_funding_counter_pubkey(
+        &shared_funding_input.funding_script,
+        &shared_funding_input.own_pubkey,
+    )?;
+    let prev_output = get_prev_output(funding_input)?;
+
+    let sig_hash = SighashCache::new(fund_tx)
+        .segwit_signature_hash(
+            input_index,
+            &shared_funding_input.funding_script,
+            prev_output.value,
+            counter_sig.hash_ty,
+        )
+        .map_err(dlc::Error::from)?;
+    secp.verify_ecdsa(
+        &Message::from_slice(&sig_hash[..])?,
+        &counter_sig.sig,
+        &counter_pubkey,
+    )
+    .map_err(|_| invalid_signature())?;
+
+    let sk = signer.get_secret_key_for_pubkey(&shared_funding_input.own_pubkey)?;
+    let own_sig = dlc::util::get_sig_for_tx_input(
+        secp,
+        fund_tx,
+        input_index,
+        &shared_funding_input.funding_script,
+        prev_output.value,
+        funding_sighash_type,
+        &sk,
+    )?;
+
+    let sigs = if shared_funding_input.own_pubkey < counter_pubkey {
+        vec![own_sig, counter_sig.to_vec()]
+    } else {
+        vec![counter_sig.to_vec(), own_sig]
+    };
+
+    Ok(Witness::from_vec(
+        std::iter::once(Vec::new())
+            .chain(sigs)
+            .chain(std::iter::once(
+                shared_funding_input.funding_script.to_bytes(),
+            ))
+            .collect(),
+    ))
+}
+
 /// Signs and return the CET that can be used to close the given contract.
 pub fn get_signed_cet<C: Signing, S: Deref>(
     secp: &Secp256k1<C>,
@@ -645,7 +1777,7 @@ pub fn get_signed_cet<C: Signing, S: Deref>(
     signer: &S,
 ) -> Result<Transaction, Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let (range_info, sigs) =
         crate::utils::get_range_info_and_oracle_sigs(contract_info, adaptor_info, attestations)?;
@@ -700,7 +1832,7 @@ pub fn get_signed_refund<C: Signing, S: Deref>(
     signer: &S,
 ) -> Result<Transaction, Error>
 where
-    S::Target: Signer,
+    S::Target: ContractSigner,
 {
     let accepted_contract = &contract.accepted_contract;
     let offered_contract = &accepted_contract.offered_contract;
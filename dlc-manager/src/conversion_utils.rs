@@ -1,4 +1,6 @@
 use crate::contract::{
+    boolean_descriptor::{BooleanEnumDescriptor, EnumCondition},
+    composite_descriptor::{CompositeDescriptor, CompositeOperation},
     contract_info::ContractInfo,
     enum_descriptor::EnumDescriptor,
     numerical_descriptor::{DifferenceParams, NumericalDescriptor},
@@ -6,8 +8,9 @@ use crate::contract::{
     ContractDescriptor, FundingInputInfo,
 };
 use crate::payout_curve::{
-    HyperbolaPayoutCurvePiece, PayoutFunction, PayoutFunctionPiece, PayoutPoint,
-    PolynomialPayoutCurvePiece, RoundingInterval, RoundingIntervals,
+    HyperbolaPayoutCurvePiece, MonotoneCubicPayoutCurvePiece, PayoutFunction, PayoutFunctionPiece,
+    PayoutPoint, PolynomialPayoutCurvePiece, RoundingInterval, RoundingIntervals,
+    StepPayoutCurvePiece,
 };
 use bitcoin::{consensus::encode::Decodable, OutPoint, Transaction};
 use dlc::{EnumerationPayout, Payout, TxInputInfo};
@@ -17,14 +20,17 @@ use dlc_messages::oracle_msgs::{
 use dlc_messages::FundingInput;
 use dlc_messages::{
     contract_msgs::{
-        ContractDescriptor as SerContractDescriptor, ContractInfo as SerContractInfo,
-        ContractInfoInner, ContractOutcome, DisjointContractInfo, EnumeratedContractDescriptor,
+        BooleanEnumContractDescriptor, CompositeOperation as SerCompositeOperation,
+        CompositeOutcomeContractDescriptor, ContractDescriptor as SerContractDescriptor,
+        ContractInfo as SerContractInfo, ContractInfoInner, ContractOutcome, DisjointContractInfo,
+        EnumCondition as SerEnumCondition, EnumeratedContractDescriptor,
         HyperbolaPayoutCurvePiece as SerHyperbolaPayoutCurvePiece,
+        MonotoneCubicPayoutCurvePiece as SerMonotoneCubicPayoutCurvePiece,
         NumericOutcomeContractDescriptor, PayoutCurvePiece as SerPayoutCurvePiece,
         PayoutFunction as SerPayoutFunction, PayoutFunctionPiece as SerPayoutFunctionPiece,
         PayoutPoint as SerPayoutPoint, PolynomialPayoutCurvePiece as SerPolynomialPayoutCurvePiece,
         RoundingInterval as SerRoundingInterval, RoundingIntervals as SerRoundingIntervals,
-        SingleContractInfo,
+        SingleContractInfo, StepPayoutCurvePiece as SerStepPayoutCurvePiece,
     },
     oracle_msgs::EventDescriptor,
 };
@@ -177,26 +183,114 @@ pub(crate) fn get_contract_info_and_announcements(
                 } else {
                     return Err(Error::InvalidParameters);
                 };
-                let nb_digits = announcements
+                let (nb_digits, precisions): (Vec<_>, Vec<_>) = announcements
                     .iter()
                     .map(|x| match &x.oracle_event.event_descriptor {
                         EventDescriptor::DigitDecompositionEvent(d) => {
                             if d.base == expected_base {
-                                Ok(d.nb_digits as usize)
+                                Ok((d.nb_digits as usize, d.precision))
                             } else {
                                 Err(Error::InvalidParameters)
                             }
                         }
                         _ => Err(Error::InvalidParameters),
                     })
-                    .collect::<Result<Vec<_>, _>>()?;
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .unzip();
+                // Oracles with a finer precision are truncated to the coarsest
+                // one, which both parties can derive from the announcements.
+                let max_precision = *precisions.iter().max().expect("to have announcements");
+                let truncated_digits: Vec<usize> = if precisions.iter().all(|x| *x == max_precision)
+                {
+                    Vec::new()
+                } else {
+                    precisions
+                        .iter()
+                        .map(|x| (max_precision - x) as usize)
+                        .collect()
+                };
+                let oracle_numeric_infos = OracleNumericInfo {
+                    base: expected_base as usize,
+                    nb_digits,
+                };
+                // The advertised number of digits must match the announcements,
+                // otherwise the parties would disagree on the outcome domain.
+                let normalized = oracle_numeric_infos
+                    .truncate(&truncated_digits)
+                    .map_err(|_| Error::InvalidParameters)?;
+                if normalized.get_min_nb_digits() != numeric.num_digits as usize {
+                    return Err(Error::InvalidParameters);
+                }
                 let descriptor = ContractDescriptor::Numerical(NumericalDescriptor {
                     payout_function: (&numeric.payout_function).into(),
                     rounding_intervals: (&numeric.rounding_intervals).into(),
                     difference_params,
+                    oracle_numeric_infos,
+                    truncated_digits,
+                });
+                (descriptor, announcements, threshold)
+            }
+            SerContractDescriptor::CompositeOutcomeContractDescriptor(composite) => {
+                // Each event is attested by its own oracle, and both
+                // attestations are required to know the combined outcome.
+                let announcements = match contract_info.oracle_info {
+                    SerOracleInfo::Multi(multi)
+                        if multi.threshold == 2 && multi.oracle_announcements.len() == 2 =>
+                    {
+                        multi.oracle_announcements
+                    }
+                    _ => return Err(Error::InvalidParameters),
+                };
+                let digit_events = announcements
+                    .iter()
+                    .map(|x| match &x.oracle_event.event_descriptor {
+                        EventDescriptor::DigitDecompositionEvent(d) => Ok(d),
+                        _ => Err(Error::InvalidParameters),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if digit_events[0].base != digit_events[1].base
+                    || digit_events[0].nb_digits != composite.first_num_digits
+                    || digit_events[1].nb_digits != composite.second_num_digits
+                {
+                    return Err(Error::InvalidParameters);
+                }
+                let descriptor = ContractDescriptor::Composite(CompositeDescriptor {
+                    operation: composite.operation.into(),
+                    ratio_precision: composite.ratio_precision,
+                    payout_function: (&composite.payout_function).into(),
+                    rounding_intervals: (&composite.rounding_intervals).into(),
                     oracle_numeric_infos: OracleNumericInfo {
-                        base: expected_base as usize,
-                        nb_digits,
+                        base: digit_events[0].base as usize,
+                        nb_digits: vec![
+                            composite.first_num_digits as usize,
+                            composite.second_num_digits as usize,
+                        ],
+                    },
+                });
+                (descriptor, announcements, 2)
+            }
+            SerContractDescriptor::BooleanEnumContractDescriptor(boolean) => {
+                let (announcements, threshold) = match contract_info.oracle_info {
+                    SerOracleInfo::Single(single) => (vec![single.oracle_announcement], 1),
+                    SerOracleInfo::Multi(multi) => (multi.oracle_announcements, multi.threshold),
+                };
+                // The condition can only be evaluated once every oracle attested.
+                if threshold as usize != announcements.len()
+                    || boolean.true_offer_payout > total_collateral
+                    || boolean.false_offer_payout > total_collateral
+                {
+                    return Err(Error::InvalidParameters);
+                }
+                let descriptor = ContractDescriptor::BooleanEnum(BooleanEnumDescriptor {
+                    condition: (&boolean.condition).into(),
+                    true_payout: Payout {
+                        offer: boolean.true_offer_payout,
+                        accept: total_collateral - boolean.true_offer_payout,
+                    },
+                    false_payout: Payout {
+                        offer: boolean.false_offer_payout,
+                        accept: total_collateral - boolean.false_offer_payout,
                     },
                 });
                 (descriptor, announcements, threshold)
@@ -291,18 +385,90 @@ impl From<&EnumDescriptor> for EnumeratedContractDescriptor {
 impl From<&NumericalDescriptor> for NumericOutcomeContractDescriptor {
     fn from(num_descriptor: &NumericalDescriptor) -> NumericOutcomeContractDescriptor {
         NumericOutcomeContractDescriptor {
-            num_digits: *num_descriptor
-                .oracle_numeric_infos
-                .nb_digits
-                .iter()
-                .min()
-                .expect("to have at least a value") as u16,
+            num_digits: num_descriptor
+                .get_normalized_numeric_infos()
+                .expect("to have valid truncated digits")
+                .get_min_nb_digits() as u16,
             payout_function: (&num_descriptor.payout_function).into(),
             rounding_intervals: (&num_descriptor.rounding_intervals).into(),
         }
     }
 }
 
+impl From<SerCompositeOperation> for CompositeOperation {
+    fn from(operation: SerCompositeOperation) -> CompositeOperation {
+        match operation {
+            SerCompositeOperation::Difference => CompositeOperation::Difference,
+            SerCompositeOperation::Ratio => CompositeOperation::Ratio,
+        }
+    }
+}
+
+impl From<CompositeOperation> for SerCompositeOperation {
+    fn from(operation: CompositeOperation) -> SerCompositeOperation {
+        match operation {
+            CompositeOperation::Difference => SerCompositeOperation::Difference,
+            CompositeOperation::Ratio => SerCompositeOperation::Ratio,
+        }
+    }
+}
+
+impl From<&CompositeDescriptor> for CompositeOutcomeContractDescriptor {
+    fn from(composite_descriptor: &CompositeDescriptor) -> CompositeOutcomeContractDescriptor {
+        let nb_digits = &composite_descriptor.oracle_numeric_infos.nb_digits;
+        CompositeOutcomeContractDescriptor {
+            first_num_digits: nb_digits[0] as u16,
+            second_num_digits: nb_digits[1] as u16,
+            operation: composite_descriptor.operation.into(),
+            ratio_precision: composite_descriptor.ratio_precision,
+            payout_function: (&composite_descriptor.payout_function).into(),
+            rounding_intervals: (&composite_descriptor.rounding_intervals).into(),
+        }
+    }
+}
+
+impl From<&SerEnumCondition> for EnumCondition {
+    fn from(condition: &SerEnumCondition) -> EnumCondition {
+        match condition {
+            SerEnumCondition::Outcome {
+                oracle_index,
+                outcomes,
+            } => EnumCondition::Outcome {
+                oracle_index: *oracle_index as usize,
+                outcomes: outcomes.clone(),
+            },
+            SerEnumCondition::And(c) => EnumCondition::And(c.iter().map(|x| x.into()).collect()),
+            SerEnumCondition::Or(c) => EnumCondition::Or(c.iter().map(|x| x.into()).collect()),
+        }
+    }
+}
+
+impl From<&EnumCondition> for SerEnumCondition {
+    fn from(condition: &EnumCondition) -> SerEnumCondition {
+        match condition {
+            EnumCondition::Outcome {
+                oracle_index,
+                outcomes,
+            } => SerEnumCondition::Outcome {
+                oracle_index: *oracle_index as u16,
+                outcomes: outcomes.clone(),
+            },
+            EnumCondition::And(c) => SerEnumCondition::And(c.iter().map(|x| x.into()).collect()),
+            EnumCondition::Or(c) => SerEnumCondition::Or(c.iter().map(|x| x.into()).collect()),
+        }
+    }
+}
+
+impl From<&BooleanEnumDescriptor> for BooleanEnumContractDescriptor {
+    fn from(boolean_descriptor: &BooleanEnumDescriptor) -> BooleanEnumContractDescriptor {
+        BooleanEnumContractDescriptor {
+            condition: (&boolean_descriptor.condition).into(),
+            true_offer_payout: boolean_descriptor.true_payout.offer,
+            false_offer_payout: boolean_descriptor.false_payout.offer,
+        }
+    }
+}
+
 impl From<&ContractDescriptor> for SerContractDescriptor {
     fn from(descriptor: &ContractDescriptor) -> SerContractDescriptor {
         match descriptor {
@@ -312,6 +478,12 @@ impl From<&ContractDescriptor> for SerContractDescriptor {
             ContractDescriptor::Numerical(n) => {
                 SerContractDescriptor::NumericOutcomeContractDescriptor(n.into())
             }
+            ContractDescriptor::Composite(c) => {
+                SerContractDescriptor::CompositeOutcomeContractDescriptor(c.into())
+            }
+            ContractDescriptor::BooleanEnum(b) => {
+                SerContractDescriptor::BooleanEnumContractDescriptor(b.into())
+            }
         }
     }
 }
@@ -342,6 +514,14 @@ impl From<&PayoutFunction> for SerPayoutFunction {
                             (&h.left_end_point).into(),
                             SerPayoutCurvePiece::HyperbolaPayoutCurvePiece(h.into()),
                         ),
+                        PayoutFunctionPiece::StepPayoutCurvePiece(s) => (
+                            (&s.payout_points[0]).into(),
+                            SerPayoutCurvePiece::StepPayoutCurvePiece(s.into()),
+                        ),
+                        PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(m) => (
+                            (&m.payout_points[0]).into(),
+                            SerPayoutCurvePiece::MonotoneCubicPayoutCurvePiece(m.into()),
+                        ),
                     };
                     SerPayoutFunctionPiece {
                         end_point: left,
@@ -358,6 +538,12 @@ impl From<&PayoutFunction> for SerPayoutFunction {
                     PayoutFunctionPiece::HyperbolaPayoutCurvePiece(h) => {
                         (&h.right_end_point).into()
                     }
+                    PayoutFunctionPiece::StepPayoutCurvePiece(s) => {
+                        s.payout_points.last().unwrap().into()
+                    }
+                    PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(m) => {
+                        m.payout_points.last().unwrap().into()
+                    }
                 }
             },
         }
@@ -411,6 +597,24 @@ fn from_ser_payout_function_piece(
                 d: h.d,
             })
         }
+        SerPayoutCurvePiece::StepPayoutCurvePiece(s) => {
+            PayoutFunctionPiece::StepPayoutCurvePiece(StepPayoutCurvePiece {
+                payout_points: vec![(&piece.end_point).into()]
+                    .into_iter()
+                    .chain(s.payout_points.iter().map(|x| x.into()))
+                    .chain(vec![(right_end_point).into()])
+                    .collect(),
+            })
+        }
+        SerPayoutCurvePiece::MonotoneCubicPayoutCurvePiece(m) => {
+            PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(MonotoneCubicPayoutCurvePiece {
+                payout_points: vec![(&piece.end_point).into()]
+                    .into_iter()
+                    .chain(m.payout_points.iter().map(|x| x.into()))
+                    .chain(vec![(right_end_point).into()])
+                    .collect(),
+            })
+        }
     }
 }
 
@@ -510,6 +714,34 @@ impl From<&SerPolynomialPayoutCurvePiece> for PolynomialPayoutCurvePiece {
     }
 }
 
+impl From<&StepPayoutCurvePiece> for SerStepPayoutCurvePiece {
+    fn from(piece: &StepPayoutCurvePiece) -> SerStepPayoutCurvePiece {
+        SerStepPayoutCurvePiece {
+            payout_points: piece
+                .payout_points
+                .iter()
+                .skip(1)
+                .take(piece.payout_points.len() - 2)
+                .map(|x| x.into())
+                .collect(),
+        }
+    }
+}
+
+impl From<&MonotoneCubicPayoutCurvePiece> for SerMonotoneCubicPayoutCurvePiece {
+    fn from(piece: &MonotoneCubicPayoutCurvePiece) -> SerMonotoneCubicPayoutCurvePiece {
+        SerMonotoneCubicPayoutCurvePiece {
+            payout_points: piece
+                .payout_points
+                .iter()
+                .skip(1)
+                .take(piece.payout_points.len() - 2)
+                .map(|x| x.into())
+                .collect(),
+        }
+    }
+}
+
 impl From<&FundingInputInfo> for FundingInput {
     fn from(info: &FundingInputInfo) -> FundingInput {
         info.funding_input.clone()
@@ -601,4 +833,29 @@ mod tests {
         let res: PayoutFunction = (&ser_payout_function).into();
         assert_eq!(payout_function, res);
     }
+
+    #[test]
+    fn received_non_ascending_step_points_are_invalid() {
+        let point = |event_outcome, outcome_payout| SerPayoutPoint {
+            event_outcome,
+            outcome_payout,
+            extra_precision: 0,
+        };
+        let ser_payout_function = SerPayoutFunction {
+            payout_function_pieces: vec![SerPayoutFunctionPiece {
+                end_point: point(0, 0),
+                payout_curve_piece: SerPayoutCurvePiece::StepPayoutCurvePiece(
+                    SerStepPayoutCurvePiece {
+                        payout_points: vec![point(20, 50), point(10, 60)],
+                    },
+                ),
+            }],
+            last_endpoint: point(40, 100),
+        };
+
+        let payout_function: PayoutFunction = (&ser_payout_function).into();
+        payout_function
+            .validate(40)
+            .expect_err("Step points should have ascending outcomes");
+    }
 }
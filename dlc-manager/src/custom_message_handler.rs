@@ -0,0 +1,275 @@
+//! #CustomMessageHandler
+//! Adapter plugging DLC message handling into the custom message mechanism of
+//! the LDK, so that DLC messages are exchanged over the connections of a
+//! [`lightning::ln::peer_handler::PeerManager`] and processed by a
+//! [`Manager`] as they are received.
+//!
+//! The adapter should be given to the `PeerManager` as its custom message
+//! handler. As LDK does not notify custom message handlers of connection
+//! events, [`DlcCustomMessageHandler::peer_connected`] and
+//! [`DlcCustomMessageHandler::peer_disconnected`] should be called by the
+//! networking code, and `PeerManager::process_events` called when
+//! [`DlcCustomMessageHandler::has_pending_messages`] returns `true`.
+
+use std::io::Read;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use dlc_messages::message_handler::MessageHandler;
+use dlc_messages::{Message, WireMessage};
+use lightning::chain::chaininterface::FeeEstimator;
+use lightning::ln::msgs::{DecodeError, ErrorAction, LightningError};
+use lightning::ln::peer_handler::CustomMessageHandler;
+use lightning::ln::wire::CustomMessageReader;
+use lightning::util::logger::Level;
+use log::error;
+use secp256k1_zkp::PublicKey;
+
+use crate::error::Error;
+use crate::manager::Manager;
+use crate::{Blockchain, ContractSigner, Oracle, Storage, Time, Wallet};
+
+/// Processes DLC messages received from peers, returning the message to send
+/// back if any.
+pub trait DlcMessageProcessor {
+    /// Processes a message received from the peer with the given id.
+    fn process_dlc_message(
+        &self,
+        msg: &Message,
+        counter_party: PublicKey,
+    ) -> Result<Option<Message>, Error>;
+
+    /// Processes several messages received from the peer with the given id in
+    /// order, returning the result of processing each of them.
+    fn process_dlc_messages(
+        &self,
+        msgs: &[Message],
+        counter_party: PublicKey,
+    ) -> Vec<Result<Option<Message>, Error>> {
+        msgs.iter()
+            .map(|msg| self.process_dlc_message(msg, counter_party))
+            .collect()
+    }
+}
+
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, F: Deref, K: Deref> DlcMessageProcessor
+    for Mutex<Manager<W, B, S, O, T, F, K>>
+where
+    W::Target: Wallet,
+    B::Target: Blockchain,
+    S::Target: Storage,
+    O::Target: Oracle,
+    T::Target: Time,
+    F::Target: FeeEstimator,
+    K::Target: ContractSigner,
+{
+    fn process_dlc_message(
+        &self,
+        msg: &Message,
+        counter_party: PublicKey,
+    ) -> Result<Option<Message>, Error> {
+        self.lock().unwrap().on_dlc_message(msg, counter_party)
+    }
+
+    fn process_dlc_messages(
+        &self,
+        msgs: &[Message],
+        counter_party: PublicKey,
+    ) -> Vec<Result<Option<Message>, Error>> {
+        self.lock().unwrap().on_dlc_messages(msgs, counter_party)
+    }
+}
+
+/// Implements the LDK custom message traits on top of a [`MessageHandler`],
+/// which takes care of message segmentation, passing the reassembled messages
+/// to a [`DlcMessageProcessor`] and sending back its replies.
+pub struct DlcCustomMessageHandler<P: Deref, H: Deref<Target = MessageHandler>>
+where
+    P::Target: DlcMessageProcessor,
+{
+    processor: P,
+    message_handler: H,
+}
+
+impl<P: Deref, H: Deref<Target = MessageHandler>> DlcCustomMessageHandler<P, H>
+where
+    P::Target: DlcMessageProcessor,
+{
+    /// Creates a new instance passing the messages received through
+    /// `message_handler` to `processor`.
+    pub fn new(processor: P, message_handler: H) -> Self {
+        DlcCustomMessageHandler {
+            processor,
+            message_handler,
+        }
+    }
+
+    /// Returns the underlying message handler, which can be used to send
+    /// messages to peers.
+    pub fn message_handler(&self) -> &MessageHandler {
+        &self.message_handler
+    }
+
+    /// Should be called when a connection with the given peer is established,
+    /// to send again the messages that it did not acknowledge.
+    pub fn peer_connected(&self, node_id: &PublicKey) {
+        self.message_handler.peer_connected(node_id);
+    }
+
+    /// Should be called when the connection with the given peer is lost.
+    pub fn peer_disconnected(&self, node_id: &PublicKey) {
+        self.message_handler.peer_disconnected(node_id);
+    }
+
+    /// Returns whether there are messages waiting to be sent to peers.
+    pub fn has_pending_messages(&self) -> bool {
+        self.message_handler.has_pending_messages()
+    }
+
+    /// Processes the messages received from peers, queuing the replies to be
+    /// sent. Returns the first error that occurred while processing a message
+    /// from the peer with id `org`, errors for other peers being only logged.
+    fn process_received_messages(&self, org: &PublicKey) -> Result<(), LightningError> {
+        let mut by_peer: Vec<(PublicKey, Vec<Message>)> = Vec::new();
+        for (node_id, msg) in self.message_handler.get_and_clear_received_messages() {
+            match by_peer.iter_mut().find(|(pk, _)| *pk == node_id) {
+                Some((_, msgs)) => msgs.push(msg),
+                None => by_peer.push((node_id, vec![msg])),
+            }
+        }
+
+        let mut res = Ok(());
+        for (node_id, msgs) in by_peer {
+            let mut replies = Vec::new();
+            for result in self.processor.process_dlc_messages(&msgs, node_id) {
+                match result {
+                    Ok(Some(reply)) => replies.push(reply),
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!("Error processing message from {}: {}", node_id, e);
+                        if node_id == *org && res.is_ok() {
+                            res = Err(LightningError {
+                                err: format!("Error processing DLC message: {}", e),
+                                action: ErrorAction::IgnoreAndLog(Level::Warn),
+                            });
+                        }
+                    }
+                }
+            }
+            if !replies.is_empty() {
+                self.message_handler.send_messages(node_id, &replies);
+            }
+        }
+        res
+    }
+}
+
+impl<P: Deref, H: Deref<Target = MessageHandler>> CustomMessageReader
+    for DlcCustomMessageHandler<P, H>
+where
+    P::Target: DlcMessageProcessor,
+{
+    type CustomMessage = WireMessage;
+    fn read<R: Read>(
+        &self,
+        msg_type: u16,
+        buffer: &mut R,
+    ) -> Result<Option<WireMessage>, DecodeError> {
+        self.message_handler.read(msg_type, buffer)
+    }
+}
+
+impl<P: Deref, H: Deref<Target = MessageHandler>> CustomMessageHandler
+    for DlcCustomMessageHandler<P, H>
+where
+    P::Target: DlcMessageProcessor,
+{
+    fn handle_custom_message(
+        &self,
+        msg: WireMessage,
+        org: &PublicKey,
+    ) -> Result<(), LightningError> {
+        self.message_handler.handle_custom_message(msg, org)?;
+        self.process_received_messages(org)
+    }
+
+    fn get_and_clear_pending_msg(&self) -> Vec<(PublicKey, WireMessage)> {
+        self.message_handler.get_and_clear_pending_msg()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dlc_messages::channel::Reject;
+    use secp256k1_zkp::{Secp256k1, SecretKey};
+
+    struct EchoProcessor {
+        received: Mutex<Vec<(PublicKey, Message)>>,
+    }
+
+    impl DlcMessageProcessor for EchoProcessor {
+        fn process_dlc_message(
+            &self,
+            msg: &Message,
+            counter_party: PublicKey,
+        ) -> Result<Option<Message>, Error> {
+            self.received
+                .lock()
+                .unwrap()
+                .push((counter_party, msg.clone()));
+            match msg {
+                Message::Reject(r) if r.channel_id == [0; 32] => {
+                    Err(Error::InvalidParameters("Unknown channel".to_string()))
+                }
+                _ => Ok(Some(msg.clone())),
+            }
+        }
+    }
+
+    fn get_peer() -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[1; 32]).unwrap())
+    }
+
+    fn get_reject(channel_id: [u8; 32]) -> Message {
+        Message::Reject(Reject { channel_id })
+    }
+
+    #[test]
+    fn received_messages_are_processed_and_replied_to() {
+        let processor = EchoProcessor {
+            received: Mutex::new(Vec::new()),
+        };
+        let message_handler = MessageHandler::new();
+        let handler = DlcCustomMessageHandler::new(&processor, &message_handler);
+
+        handler
+            .handle_custom_message(WireMessage::Message(get_reject([1; 32])), &get_peer())
+            .expect("to be able to handle the message");
+
+        assert_eq!(1, processor.received.lock().unwrap().len());
+        assert!(handler.has_pending_messages());
+        let pending = handler.get_and_clear_pending_msg();
+        assert_eq!(1, pending.len());
+        assert_eq!(get_peer(), pending[0].0);
+        match &pending[0].1 {
+            WireMessage::Message(Message::Reject(r)) => assert_eq!([1; 32], r.channel_id),
+            _ => panic!("Expected a reject message"),
+        }
+    }
+
+    #[test]
+    fn processing_errors_are_returned() {
+        let processor = EchoProcessor {
+            received: Mutex::new(Vec::new()),
+        };
+        let message_handler = MessageHandler::new();
+        let handler = DlcCustomMessageHandler::new(&processor, &message_handler);
+
+        let err = handler
+            .handle_custom_message(WireMessage::Message(get_reject([0; 32])), &get_peer())
+            .expect_err("the processing error to be returned");
+        assert!(matches!(err.action, ErrorAction::IgnoreAndLog(_)));
+        assert!(!handler.has_pending_messages());
+    }
+}
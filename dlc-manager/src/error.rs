@@ -25,6 +25,14 @@ pub enum Error {
     DlcError(dlc::Error),
     /// An error occurred in the Secp library.
     SecpError(secp256k1_zkp::Error),
+    /// An offered contract relies on oracles that are not trusted.
+    UntrustedOracles(String),
+    /// An error occurred while sending or receiving messages.
+    TransportError(String),
+    /// A peer exceeded the limits on the messages it can send.
+    RateLimited(String),
+    /// A remote service failed repeatedly and requests to it are suspended.
+    ServiceUnavailable(String),
 }
 
 impl fmt::Display for Error {
@@ -40,6 +48,10 @@ impl fmt::Display for Error {
             Error::DlcError(_) => write!(f, "Dlc error"),
             Error::OracleError(ref s) => write!(f, "Oracle error {}", s),
             Error::SecpError(_) => write!(f, "Secp error"),
+            Error::UntrustedOracles(ref s) => write!(f, "Untrusted oracles: {}", s),
+            Error::TransportError(ref s) => write!(f, "Transport error {}", s),
+            Error::RateLimited(ref s) => write!(f, "Rate limited: {}", s),
+            Error::ServiceUnavailable(ref s) => write!(f, "Service unavailable: {}", s),
         }
     }
 }
@@ -87,6 +99,10 @@ impl std::error::Error for Error {
             Error::OracleError(_) => None,
             Error::DlcError(e) => Some(e),
             Error::SecpError(e) => Some(e),
+            Error::UntrustedOracles(_) => None,
+            Error::TransportError(_) => None,
+            Error::RateLimited(_) => None,
+            Error::ServiceUnavailable(_) => None,
         }
     }
 }
@@ -0,0 +1,150 @@
+//! #FallbackOracle
+//! [`Oracle`] implementation querying several endpoints serving the data of
+//! the same oracle.
+
+use std::ops::Deref;
+
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
+use log::warn;
+use secp256k1_zkp::XOnlyPublicKey;
+
+use crate::error::Error;
+use crate::Oracle;
+
+/// Oracle client forwarding requests to a list of mirrors of the same oracle.
+/// Mirrors are queried in order, moving to the next one when a request fails.
+pub struct FallbackOracle<O: Deref>
+where
+    O::Target: Oracle,
+{
+    mirrors: Vec<O>,
+    public_key: XOnlyPublicKey,
+    cross_check: bool,
+}
+
+impl<O: Deref> FallbackOracle<O>
+where
+    O::Target: Oracle,
+{
+    /// Creates a new instance using the given mirrors, which must all serve
+    /// data for the same oracle public key. If `cross_check` is set, all the
+    /// mirrors are queried and their responses are required to be identical.
+    pub fn new(mirrors: Vec<O>, cross_check: bool) -> Result<Self, Error> {
+        let public_key = mirrors
+            .first()
+            .ok_or_else(|| Error::InvalidParameters("At least one mirror is required".to_string()))?
+            .get_public_key();
+
+        if mirrors.iter().any(|x| x.get_public_key() != public_key) {
+            return Err(Error::InvalidParameters(
+                "All mirrors must serve the same oracle".to_string(),
+            ));
+        }
+
+        Ok(FallbackOracle {
+            mirrors,
+            public_key,
+            cross_check,
+        })
+    }
+
+    fn query<T, F>(&self, event_id: &str, f: F) -> Result<T, Error>
+    where
+        T: PartialEq,
+        F: Fn(&O::Target) -> Result<T, Error>,
+    {
+        let mut res: Option<T> = None;
+        let mut last_error = None;
+        for (i, mirror) in self.mirrors.iter().enumerate() {
+            match f(&**mirror) {
+                Ok(value) => {
+                    if !self.cross_check {
+                        return Ok(value);
+                    }
+                    if res.is_none() {
+                        res = Some(value);
+                    } else if res.as_ref() != Some(&value) {
+                        return Err(Error::OracleError(format!(
+                            "Mirror {} returned inconsistent data for event {}",
+                            i, event_id
+                        )));
+                    }
+                }
+                Err(e) => {
+                    warn!("Mirror {} failed for event {}: {}", i, event_id, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        res.ok_or_else(|| last_error.expect("to have at least one mirror"))
+    }
+}
+
+impl<O: Deref> Oracle for FallbackOracle<O>
+where
+    O::Target: Oracle,
+{
+    fn get_public_key(&self) -> XOnlyPublicKey {
+        self.public_key
+    }
+
+    fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement, Error> {
+        self.query(event_id, |o| o.get_announcement(event_id))
+    }
+
+    fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation, Error> {
+        self.query(event_id, |o| o.get_attestation(event_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dlc_messages::oracle_msgs::{EnumEventDescriptor, EventDescriptor};
+    use mocks::dlc_manager::{fallback_oracle::FallbackOracle, Oracle};
+    use mocks::mock_oracle_provider::MockOracle;
+    use secp256k1_zkp::SecretKey;
+
+    fn get_mirror(with_event: bool) -> MockOracle {
+        let mut oracle = MockOracle::from_secret_key(&SecretKey::from_slice(&[1; 32]).unwrap());
+        if with_event {
+            oracle.add_event(
+                "event",
+                &EventDescriptor::EnumEvent(EnumEventDescriptor {
+                    outcomes: vec!["a".to_string(), "b".to_string()],
+                }),
+                1,
+            );
+        }
+        oracle
+    }
+
+    #[test]
+    fn fails_over_to_next_mirror() {
+        let mirrors = vec![get_mirror(false), get_mirror(true)];
+        let oracle = FallbackOracle::new(mirrors.iter().collect(), false).unwrap();
+
+        oracle
+            .get_announcement("event")
+            .expect("the second mirror to provide the announcement");
+        oracle
+            .get_attestation("event")
+            .expect_err("no mirror to provide the attestation");
+    }
+
+    #[test]
+    fn cross_check_detects_inconsistent_mirrors() {
+        let mirrors = vec![get_mirror(true), get_mirror(true)];
+        let oracle = FallbackOracle::new(mirrors.iter().collect(), true).unwrap();
+
+        oracle
+            .get_announcement("event")
+            .expect_err("announcements with different nonces to be rejected");
+    }
+
+    #[test]
+    fn mirrors_of_different_oracles_are_rejected() {
+        let mirrors = vec![get_mirror(true), MockOracle::new()];
+        assert!(FallbackOracle::new(mirrors.iter().collect::<Vec<_>>(), false).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! #FeeRatePolicy
+//! Restrictions on the fee rates of contracts and channels offered by peers.
+
+use std::ops::Deref;
+
+use crate::error::Error;
+use crate::Blockchain;
+
+/// Describes the fee rates accepted when receiving contract and channel
+/// offers. The fees of the funding and closing transactions are partly paid
+/// out of the funds of the accepting party, so offers with excessive fee rates
+/// would silently take away from its change and payouts.
+#[derive(Clone, Debug)]
+pub struct FeeRatePolicy {
+    /// The maximum fee rate, in satoshis per virtual byte, of offered
+    /// contracts. Any fee rate is accepted if `None`.
+    pub max_fee_rate: Option<u64>,
+    /// The maximum ratio between the fee rate of offered contracts and the
+    /// fee rate estimated by the blockchain provider for
+    /// `confirmation_target`. Not checked if `None`.
+    pub max_estimate_multiplier: Option<u64>,
+    /// The confirmation target of the fee rate estimate offered fee rates are
+    /// compared to.
+    pub confirmation_target: u32,
+}
+
+impl Default for FeeRatePolicy {
+    fn default() -> Self {
+        FeeRatePolicy {
+            max_fee_rate: None,
+            max_estimate_multiplier: None,
+            confirmation_target: 1,
+        }
+    }
+}
+
+impl FeeRatePolicy {
+    /// Returns an error if the given fee rate of an offer exceeds the maximum
+    /// allowed by this policy. The blockchain provider is only queried when
+    /// a maximum ratio to its estimate is set.
+    pub fn check_fee_rate<B: Deref>(
+        &self,
+        fee_rate_per_vb: u64,
+        blockchain: &B,
+    ) -> Result<(), Error>
+    where
+        B::Target: Blockchain,
+    {
+        if let Some(max_fee_rate) = self.max_fee_rate {
+            if fee_rate_per_vb > max_fee_rate {
+                return Err(Error::InvalidParameters(format!(
+                    "Fee rate {} exceeds maximum fee rate {}",
+                    fee_rate_per_vb, max_fee_rate
+                )));
+            }
+        }
+
+        if let Some(max_estimate_multiplier) = self.max_estimate_multiplier {
+            let estimate = blockchain.get_fee_rate(self.confirmation_target)?;
+            if fee_rate_per_vb > estimate.saturating_mul(max_estimate_multiplier) {
+                return Err(Error::InvalidParameters(format!(
+                    "Fee rate {} exceeds {} times the estimated fee rate {}",
+                    fee_rate_per_vb, max_estimate_multiplier, estimate
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mocks::dlc_manager::fee_rate_policy::FeeRatePolicy;
+    use mocks::fault_injection::FaultKind;
+    use mocks::mock_blockchain::MockBlockchain;
+
+    #[test]
+    fn any_fee_rate_is_accepted_by_default() {
+        let blockchain = MockBlockchain::new();
+        blockchain
+            .faults()
+            .fail_method("get_fee_rate", FaultKind::Blockchain);
+
+        FeeRatePolicy::default()
+            .check_fee_rate(u64::MAX, &&blockchain)
+            .expect("any fee rate to be accepted");
+    }
+
+    #[test]
+    fn fee_rate_above_maximum_is_rejected() {
+        let blockchain = MockBlockchain::new();
+        let policy = FeeRatePolicy {
+            max_fee_rate: Some(100),
+            ..Default::default()
+        };
+
+        policy
+            .check_fee_rate(100, &&blockchain)
+            .expect("fee rate at maximum to be accepted");
+        policy
+            .check_fee_rate(101, &&blockchain)
+            .expect_err("fee rate above maximum to be rejected");
+    }
+
+    #[test]
+    fn fee_rate_far_above_estimate_is_rejected() {
+        // The mock blockchain estimates a fee rate of 2 sats/vbyte.
+        let blockchain = MockBlockchain::new();
+        let policy = FeeRatePolicy {
+            max_estimate_multiplier: Some(10),
+            ..Default::default()
+        };
+
+        policy
+            .check_fee_rate(20, &&blockchain)
+            .expect("fee rate within bounds to be accepted");
+        policy
+            .check_fee_rate(21, &&blockchain)
+            .expect_err("fee rate far above estimate to be rejected");
+
+        blockchain
+            .faults()
+            .fail_method("get_fee_rate", FaultKind::Blockchain);
+        policy
+            .check_fee_rate(2, &&blockchain)
+            .expect_err("offers to be rejected without estimate");
+    }
+}
@@ -0,0 +1,408 @@
+//! #HttpTransport
+//! Request/response exchange of DLC messages over HTTP, for integrators that
+//! do not run Lightning nodes.
+//!
+//! Messages are posted as JSON to [`MESSAGES_PATH`] together with an
+//! idempotency key chosen by the sender and a signature of the sender's node
+//! key. Requests are processed asynchronously, the reply being retrieved by
+//! polling `{MESSAGES_PATH}/{sender}/{idempotency_key}`. Posting a request
+//! with an already used key returns the state of the original request
+//! instead of processing the message again.
+//!
+//! [`HttpTransportHandler`] implements the server side independently of any
+//! HTTP framework, while [`HttpTransportClient`] implements the client side.
+
+use std::collections::{HashMap, VecDeque};
+use std::ops::Deref;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use dlc_messages::json::{from_json, to_json};
+use dlc_messages::Message;
+use secp256k1_zkp::rand::{thread_rng, Rng};
+use secp256k1_zkp::{
+    ecdsa::Signature, All, Message as SecpMessage, PublicKey, Secp256k1, SecretKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::custom_message_handler::DlcMessageProcessor;
+use crate::error::Error;
+
+/// The path to which messages are posted.
+pub const MESSAGES_PATH: &str = "/dlc/messages";
+
+/// The maximum number of requests whose state is kept by a
+/// [`HttpTransportHandler`], the oldest ones being forgotten first.
+pub const MAX_STORED_REQUESTS: usize = 1000;
+
+const REQUEST_TAG: &[u8] = b"DLC/http_transport/request";
+
+/// A message sent to a peer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRequest {
+    /// The key identifying the request for the sender.
+    pub idempotency_key: String,
+    /// The node id of the sender.
+    pub sender: PublicKey,
+    /// The message in canonical JSON.
+    pub message: serde_json::Value,
+    /// The signature of the request by the sender.
+    pub signature: Signature,
+}
+
+/// The state of a request.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum MessageResponse {
+    /// The message has not been processed yet.
+    Pending,
+    /// The message was processed, possibly yielding a reply.
+    Completed {
+        /// The reply to the message in canonical JSON, if any.
+        reply: Option<serde_json::Value>,
+    },
+    /// The message could not be processed.
+    Failed {
+        /// A description of the error.
+        error: String,
+    },
+}
+
+fn get_request_hash(idempotency_key: &str, msg: &Message) -> SecpMessage {
+    let tag = sha256::Hash::hash(REQUEST_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag[..]);
+    engine.input(&tag[..]);
+    engine.input(&(idempotency_key.len() as u64).to_be_bytes());
+    engine.input(idempotency_key.as_bytes());
+    engine.input(&msg.encode_with_type());
+    SecpMessage::from_slice(&sha256::Hash::from_engine(engine)[..])
+        .expect("a hash to be a valid message")
+}
+
+fn message_to_value(msg: &Message) -> Result<serde_json::Value, Error> {
+    let json = to_json(msg).map_err(|e| Error::TransportError(e.to_string()))?;
+    serde_json::from_str(&json).map_err(|e| Error::TransportError(e.to_string()))
+}
+
+fn message_from_value(value: &serde_json::Value) -> Result<Message, Error> {
+    from_json(&value.to_string()).map_err(|e| Error::TransportError(e.to_string()))
+}
+
+enum RequestState {
+    Pending(Message),
+    Processing,
+    Done(MessageResponse),
+}
+
+impl RequestState {
+    fn to_response(&self) -> MessageResponse {
+        match self {
+            RequestState::Pending(_) | RequestState::Processing => MessageResponse::Pending,
+            RequestState::Done(response) => response.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct RequestStore {
+    states: HashMap<(PublicKey, String), RequestState>,
+    order: VecDeque<(PublicKey, String)>,
+}
+
+/// Server side of the transport, passing the received messages to a
+/// [`DlcMessageProcessor`] and storing its replies until retrieved.
+pub struct HttpTransportHandler<P: Deref>
+where
+    P::Target: DlcMessageProcessor,
+{
+    processor: P,
+    secp: Secp256k1<All>,
+    requests: Mutex<RequestStore>,
+}
+
+impl<P: Deref> HttpTransportHandler<P>
+where
+    P::Target: DlcMessageProcessor,
+{
+    /// Creates a new handler passing messages to the given processor.
+    pub fn new(processor: P) -> Self {
+        HttpTransportHandler {
+            processor,
+            secp: Secp256k1::new(),
+            requests: Mutex::new(RequestStore::default()),
+        }
+    }
+
+    /// Handles a `POST` request to [`MESSAGES_PATH`] with the given body,
+    /// returning the JSON body of the response. Errors should be returned to
+    /// the client as bad requests.
+    pub fn handle_post(&self, body: &str) -> Result<String, Error> {
+        let request: MessageRequest = serde_json::from_str(body)
+            .map_err(|e| Error::TransportError(format!("Invalid request: {}", e)))?;
+        let msg = message_from_value(&request.message)?;
+        self.secp.verify_ecdsa(
+            &get_request_hash(&request.idempotency_key, &msg),
+            &request.signature,
+            &request.sender,
+        )?;
+
+        let mut requests = self.requests.lock().unwrap();
+        let key = (request.sender, request.idempotency_key);
+        let response = match requests.states.get(&key) {
+            Some(state) => state.to_response(),
+            None => {
+                requests
+                    .states
+                    .insert(key.clone(), RequestState::Pending(msg));
+                requests.order.push_back(key);
+                if requests.order.len() > MAX_STORED_REQUESTS {
+                    if let Some(oldest) = requests.order.pop_front() {
+                        requests.states.remove(&oldest);
+                    }
+                }
+                MessageResponse::Pending
+            }
+        };
+        serde_json::to_string(&response).map_err(|e| Error::TransportError(e.to_string()))
+    }
+
+    /// Handles a `GET` request for the state of the request with the given
+    /// idempotency key from the given sender, returning the JSON body of the
+    /// response.
+    pub fn handle_get(&self, sender: &PublicKey, idempotency_key: &str) -> Result<String, Error> {
+        let requests = self.requests.lock().unwrap();
+        let response = requests
+            .states
+            .get(&(*sender, idempotency_key.to_string()))
+            .ok_or_else(|| Error::InvalidParameters("Unknown request.".to_string()))?
+            .to_response();
+        serde_json::to_string(&response).map_err(|e| Error::TransportError(e.to_string()))
+    }
+
+    /// Processes the pending requests, storing the replies to be retrieved by
+    /// the senders. Should be called periodically.
+    pub fn process_pending_requests(&self) {
+        let pending = {
+            let mut requests = self.requests.lock().unwrap();
+            let mut pending = Vec::new();
+            for (key, state) in requests.states.iter_mut() {
+                if let RequestState::Pending(_) = state {
+                    if let RequestState::Pending(msg) =
+                        std::mem::replace(state, RequestState::Processing)
+                    {
+                        pending.push((key.clone(), msg));
+                    }
+                }
+            }
+            pending
+        };
+
+        for (key, msg) in pending {
+            let response = match self
+                .processor
+                .process_dlc_message(&msg, key.0)
+                .and_then(|reply| reply.as_ref().map(message_to_value).transpose())
+            {
+                Ok(reply) => MessageResponse::Completed { reply },
+                Err(e) => MessageResponse::Failed {
+                    error: e.to_string(),
+                },
+            };
+            if let Some(state) = self.requests.lock().unwrap().states.get_mut(&key) {
+                *state = RequestState::Done(response);
+            }
+        }
+    }
+}
+
+/// Client side of the transport.
+pub struct HttpTransportClient {
+    client: reqwest::blocking::Client,
+    secp: Secp256k1<All>,
+    secret_key: SecretKey,
+    poll_interval: Duration,
+    max_polls: usize,
+}
+
+impl HttpTransportClient {
+    /// Creates a client signing requests with the given node secret key and
+    /// polling for replies every `poll_interval`, at most `max_polls` times.
+    pub fn new(secret_key: SecretKey, poll_interval: Duration, max_polls: usize) -> Self {
+        HttpTransportClient {
+            client: reqwest::blocking::Client::new(),
+            secp: Secp256k1::new(),
+            secret_key,
+            poll_interval,
+            max_polls,
+        }
+    }
+
+    /// Returns the node id of the client.
+    pub fn node_id(&self) -> PublicKey {
+        PublicKey::from_secret_key(&self.secp, &self.secret_key)
+    }
+
+    /// Sends the given message to the peer serving the transport at
+    /// `base_url`, waiting for it to be processed and returning the reply.
+    pub fn send_message(&self, base_url: &str, msg: &Message) -> Result<Option<Message>, Error> {
+        let idempotency_key = thread_rng().gen::<[u8; 16]>().to_hex();
+        self.send_message_with_key(base_url, &idempotency_key, msg)
+    }
+
+    /// Same as [`HttpTransportClient::send_message`] using the given
+    /// idempotency key, so that the message is not processed again if it was
+    /// already received by the peer.
+    pub fn send_message_with_key(
+        &self,
+        base_url: &str,
+        idempotency_key: &str,
+        msg: &Message,
+    ) -> Result<Option<Message>, Error> {
+        let base_url = base_url.trim_end_matches('/');
+        let request = MessageRequest {
+            idempotency_key: idempotency_key.to_string(),
+            sender: self.node_id(),
+            message: message_to_value(msg)?,
+            signature: self
+                .secp
+                .sign_ecdsa(&get_request_hash(idempotency_key, msg), &self.secret_key),
+        };
+
+        let mut response: MessageResponse = self
+            .client
+            .post(format!("{}{}", base_url, MESSAGES_PATH))
+            .json(&request)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.json())
+            .map_err(to_transport_error)?;
+
+        let poll_url = format!(
+            "{}{}/{}/{}",
+            base_url, MESSAGES_PATH, request.sender, idempotency_key
+        );
+        for _ in 0..self.max_polls {
+            if response != MessageResponse::Pending {
+                break;
+            }
+            std::thread::sleep(self.poll_interval);
+            response = self
+                .client
+                .get(&poll_url)
+                .send()
+                .and_then(|r| r.error_for_status())
+                .and_then(|r| r.json())
+                .map_err(to_transport_error)?;
+        }
+
+        match response {
+            MessageResponse::Pending => Err(Error::TransportError(
+                "Timed out waiting for the message to be processed.".to_string(),
+            )),
+            MessageResponse::Completed { reply } => {
+                reply.as_ref().map(message_from_value).transpose()
+            }
+            MessageResponse::Failed { error } => Err(Error::TransportError(format!(
+                "Peer failed to process message: {}",
+                error
+            ))),
+        }
+    }
+}
+
+fn to_transport_error(e: reqwest::Error) -> Error {
+    Error::TransportError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dlc_messages::channel::Reject;
+
+    struct EchoProcessor {
+        nb_processed: Mutex<usize>,
+    }
+
+    impl DlcMessageProcessor for EchoProcessor {
+        fn process_dlc_message(
+            &self,
+            msg: &Message,
+            _: PublicKey,
+        ) -> Result<Option<Message>, Error> {
+            *self.nb_processed.lock().unwrap() += 1;
+            Ok(Some(msg.clone()))
+        }
+    }
+
+    fn get_request(key: &str, secret_key: &SecretKey) -> MessageRequest {
+        let secp = Secp256k1::new();
+        let msg = Message::Reject(Reject {
+            channel_id: [1; 32],
+        });
+        MessageRequest {
+            idempotency_key: key.to_string(),
+            sender: PublicKey::from_secret_key(&secp, secret_key),
+            message: message_to_value(&msg).unwrap(),
+            signature: secp.sign_ecdsa(&get_request_hash(key, &msg), secret_key),
+        }
+    }
+
+    fn parse_response(body: &str) -> MessageResponse {
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn request_is_processed_once_and_reply_polled() {
+        let processor = EchoProcessor {
+            nb_processed: Mutex::new(0),
+        };
+        let handler = HttpTransportHandler::new(&processor);
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let request = get_request("key", &secret_key);
+        let body = serde_json::to_string(&request).unwrap();
+
+        assert_eq!(
+            MessageResponse::Pending,
+            parse_response(&handler.handle_post(&body).unwrap())
+        );
+        handler.process_pending_requests();
+        assert_eq!(1, *processor.nb_processed.lock().unwrap());
+
+        let expected = MessageResponse::Completed {
+            reply: Some(request.message.clone()),
+        };
+        assert_eq!(
+            expected,
+            parse_response(&handler.handle_get(&request.sender, "key").unwrap())
+        );
+
+        assert_eq!(
+            expected,
+            parse_response(&handler.handle_post(&body).unwrap())
+        );
+        handler.process_pending_requests();
+        assert_eq!(1, *processor.nb_processed.lock().unwrap());
+    }
+
+    #[test]
+    fn invalid_signature_is_rejected() {
+        let processor = EchoProcessor {
+            nb_processed: Mutex::new(0),
+        };
+        let handler = HttpTransportHandler::new(&processor);
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let mut request = get_request("key", &secret_key);
+        request.idempotency_key = "other".to_string();
+
+        handler
+            .handle_post(&serde_json::to_string(&request).unwrap())
+            .expect_err("an invalid signature");
+        handler
+            .handle_get(&request.sender, "other")
+            .expect_err("an unknown request");
+    }
+}
@@ -0,0 +1,296 @@
+//! #Key derivation
+//! Deterministic derivation of the keys used in contracts and channels from an
+//! extended private key, so that they can all be recovered from a seed without
+//! requiring a backup of the keys themselves.
+//!
+//! The key of type `t` for the contract or channel with temporary id `id`
+//! established with the counter party `p` is derived using the path
+//! `m/4475971'/h0'/h1'/h2'/h3'/t'`, where `4475971` is the ASCII encoding of
+//! "DLC" and `h0` to `h3` are the first four big endian 32 bits integers of
+//! `SHA256(p || id)` with their most significant bit cleared, `p` being the
+//! 33 bytes compressed encoding of the counter party public key. The index `t`
+//! of each key type is given by [`ContractKeyType::get_index`].
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey};
+use bitcoin::{Script, Transaction, TxOut};
+use secp256k1_zkp::{All, PublicKey, Secp256k1, SecretKey, Signing};
+
+use crate::error::Error;
+use crate::zeroizing::ZeroizingSecretKey;
+use crate::{ContractSigner, Storage};
+
+/// The purpose index of the derivation paths of contract keys.
+pub const CONTRACT_KEY_PURPOSE: u32 = 4475971;
+
+/// The types of keys used by a party in a contract or channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContractKeyType {
+    /// The key locking the funding output.
+    Funding,
+    /// The base point from which the channel own keys are derived.
+    OwnBasepoint,
+    /// The base point from which the channel publish keys are derived.
+    PublishBasepoint,
+    /// The base point from which the channel revocation keys are derived.
+    RevocationBasepoint,
+    /// The seed from which the channel per update secrets are derived.
+    PerUpdateSeed,
+}
+
+impl ContractKeyType {
+    /// All the key types, in the order of their index.
+    pub const ALL: [ContractKeyType; 5] = [
+        ContractKeyType::Funding,
+        ContractKeyType::OwnBasepoint,
+        ContractKeyType::PublishBasepoint,
+        ContractKeyType::RevocationBasepoint,
+        ContractKeyType::PerUpdateSeed,
+    ];
+
+    /// Returns the index of the key type in derivation paths.
+    pub fn get_index(&self) -> u32 {
+        match self {
+            ContractKeyType::Funding => 0,
+            ContractKeyType::OwnBasepoint => 1,
+            ContractKeyType::PublishBasepoint => 2,
+            ContractKeyType::RevocationBasepoint => 3,
+            ContractKeyType::PerUpdateSeed => 4,
+        }
+    }
+}
+
+/// Identifies a key used in a contract or channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ContractKeyId {
+    /// The public key of the counter party.
+    pub counter_party: PublicKey,
+    /// The temporary id of the contract, or of the channel for keys used in a
+    /// channel.
+    pub temporary_id: [u8; 32],
+    /// The type of the key.
+    pub key_type: ContractKeyType,
+}
+
+impl ContractKeyId {
+    /// Returns the path from which the identified key is derived.
+    pub fn get_derivation_path(&self) -> DerivationPath {
+        let hash = sha256::Hash::hash(
+            &[&self.counter_party.serialize()[..], &self.temporary_id[..]].concat(),
+        )
+        .into_inner();
+
+        let mut indexes = vec![CONTRACT_KEY_PURPOSE];
+        indexes.extend(hash.chunks(4).take(4).map(|chunk| {
+            u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) & 0x7fffffff
+        }));
+        indexes.push(self.key_type.get_index());
+
+        indexes
+            .into_iter()
+            .map(|index| {
+                ChildNumber::from_hardened_idx(index).expect("index to be lower than 2^31")
+            })
+            .collect()
+    }
+
+    /// Derives the identified key from the given extended private key.
+    pub fn derive_secret_key<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        xpriv: &ExtendedPrivKey,
+    ) -> Result<SecretKey, Error> {
+        let derived = xpriv
+            .derive_priv(secp, &self.get_derivation_path())
+            .map_err(|e| Error::InvalidParameters(format!("Could not derive key: {}", e)))?;
+        Ok(derived.private_key)
+    }
+}
+
+/// A [`ContractSigner`] deriving the keys of contracts and channels from an
+/// extended private key. As it does not hold the keys of the wallet, funding
+/// inputs are signed externally using PSBTs.
+pub struct DerivedKeysSigner {
+    xpriv: ExtendedPrivKey,
+    secp: Secp256k1<All>,
+    keys: Mutex<HashMap<PublicKey, SecretKey>>,
+}
+
+impl DerivedKeysSigner {
+    /// Creates a signer deriving keys from the given extended private key.
+    pub fn new(xpriv: ExtendedPrivKey) -> Self {
+        DerivedKeysSigner {
+            xpriv,
+            secp: Secp256k1::new(),
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Derives all the keys of the contract or channel with the given
+    /// temporary id, so that they can be retrieved from their public key. Must
+    /// be called for each existing contract and channel after restarting, e.g.
+    /// using [`DerivedKeysSigner::recover_keys_from_storage`].
+    pub fn recover_contract_keys(
+        &self,
+        counter_party: &PublicKey,
+        temporary_id: &[u8; 32],
+    ) -> Result<(), Error> {
+        derive_all_contract_keys(self, counter_party, temporary_id)
+    }
+
+    /// Derives the keys of all the contracts and channels found in the given
+    /// storage, see [`DerivedKeysSigner::recover_contract_keys`].
+    pub fn recover_keys_from_storage<S: Deref>(&self, store: &S) -> Result<(), Error>
+    where
+        S::Target: Storage,
+    {
+        for_each_stored_contract(store, |counter_party, temporary_id| {
+            self.recover_contract_keys(counter_party, temporary_id)
+        })
+    }
+}
+
+/// Derives all the key types of the contract or channel with the given
+/// temporary id using the given signer.
+pub(crate) fn derive_all_contract_keys<C: ContractSigner + ?Sized>(
+    signer: &C,
+    counter_party: &PublicKey,
+    temporary_id: &[u8; 32],
+) -> Result<(), Error> {
+    for key_type in ContractKeyType::ALL {
+        signer.derive_contract_secret_key(&ContractKeyId {
+            counter_party: *counter_party,
+            temporary_id: *temporary_id,
+            key_type,
+        })?;
+    }
+    Ok(())
+}
+
+/// Calls `f` with the counter party and temporary id of every contract and
+/// channel found in the given storage.
+pub(crate) fn for_each_stored_contract<S: Deref, F>(store: &S, mut f: F) -> Result<(), Error>
+where
+    S::Target: Storage,
+    F: FnMut(&PublicKey, &[u8; 32]) -> Result<(), Error>,
+{
+    for contract in store.get_contracts()? {
+        f(
+            &contract.get_counter_party_id(),
+            &contract.get_temporary_id(),
+        )?;
+    }
+    for channel in store.get_offered_channels()? {
+        f(&channel.counter_party, &channel.temporary_channel_id)?;
+    }
+    for channel in store.get_signed_channels(None)? {
+        f(&channel.counter_party, &channel.temporary_channel_id)?;
+    }
+    Ok(())
+}
+
+impl ContractSigner for DerivedKeysSigner {
+    fn get_new_secret_key(&self) -> Result<SecretKey, Error> {
+        Err(Error::InvalidState(
+            "Keys can only be derived for a contract or channel.".to_string(),
+        ))
+    }
+
+    fn derive_contract_secret_key(&self, key_id: &ContractKeyId) -> Result<SecretKey, Error> {
+        let secret_key = key_id.derive_secret_key(&self.secp, &self.xpriv)?;
+        self.keys.lock().unwrap().insert(
+            PublicKey::from_secret_key(&self.secp, &secret_key),
+            secret_key,
+        );
+        Ok(secret_key)
+    }
+
+    fn sign_tx_input(
+        &self,
+        _tx: &mut Transaction,
+        _input_index: usize,
+        _tx_out: &TxOut,
+        _redeem_script: Option<Script>,
+    ) -> Result<(), Error> {
+        Err(Error::InvalidState(
+            "Signer does not hold the keys of the wallet.".to_string(),
+        ))
+    }
+
+    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<ZeroizingSecretKey, Error> {
+        self.keys
+            .lock()
+            .unwrap()
+            .get(pubkey)
+            .map(|x| ZeroizingSecretKey::new(*x))
+            .ok_or_else(|| Error::InvalidParameters("Unknown public key.".to_string()))
+    }
+
+    fn can_sign_funding_inputs(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::Network;
+
+    fn get_signer() -> DerivedKeysSigner {
+        DerivedKeysSigner::new(ExtendedPrivKey::new_master(Network::Regtest, &[1; 32]).unwrap())
+    }
+
+    fn get_key_id(temporary_id: [u8; 32], key_type: ContractKeyType) -> ContractKeyId {
+        ContractKeyId {
+            counter_party: "0218845781f631c48f1c9709e23092067d06837f30aa0cd0544ac887fe91ddd166"
+                .parse()
+                .unwrap(),
+            temporary_id,
+            key_type,
+        }
+    }
+
+    #[test]
+    fn derivation_path_is_keyed_by_contract_and_type() {
+        let path = get_key_id([2; 32], ContractKeyType::PublishBasepoint).get_derivation_path();
+        let indexes = path.as_ref();
+
+        assert_eq!(6, indexes.len());
+        assert_eq!(
+            ChildNumber::from_hardened_idx(CONTRACT_KEY_PURPOSE).unwrap(),
+            indexes[0]
+        );
+        assert_eq!(ChildNumber::from_hardened_idx(2).unwrap(), indexes[5]);
+        assert_ne!(
+            get_key_id([2; 32], ContractKeyType::Funding).get_derivation_path(),
+            get_key_id([3; 32], ContractKeyType::Funding).get_derivation_path()
+        );
+    }
+
+    #[test]
+    fn keys_can_be_recovered_from_seed() {
+        let signer = get_signer();
+        let key_id = get_key_id([2; 32], ContractKeyType::Funding);
+        let secret_key = signer.derive_contract_secret_key(&key_id).unwrap();
+        let pubkey = PublicKey::from_secret_key(&signer.secp, &secret_key);
+        assert_eq!(
+            secret_key,
+            *signer.get_secret_key_for_pubkey(&pubkey).unwrap()
+        );
+
+        let recovered_signer = get_signer();
+        assert!(recovered_signer.get_secret_key_for_pubkey(&pubkey).is_err());
+        recovered_signer
+            .recover_contract_keys(&key_id.counter_party, &key_id.temporary_id)
+            .unwrap();
+
+        assert_eq!(
+            secret_key,
+            *recovered_signer.get_secret_key_for_pubkey(&pubkey).unwrap()
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! #LDK signer
+//! A [`ContractSigner`] deriving the keys of contracts and channels from an
+//! LDK [`KeysManager`], so that nodes running LDK can recover their DLC keys
+//! from the seed they already back up for their lightning channels.
+//!
+//! The keys of a contract or channel are taken from the channel keys that the
+//! [`KeysManager`] derives for the parameters `SHA256("DLC" || p || id)`, `p`
+//! being the compressed public key of the counter party and `id` the temporary
+//! id of the contract or channel. The DLC tag ensures that these parameters
+//! don't overlap with the ones LDK uses for its own channels.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{Script, Transaction, TxOut};
+use lightning::chain::keysinterface::{EntropySource, KeysManager};
+use secp256k1_zkp::{All, PublicKey, Secp256k1, SecretKey};
+
+use crate::error::Error;
+use crate::key_derivation::{
+    derive_all_contract_keys, for_each_stored_contract, ContractKeyId, ContractKeyType,
+};
+use crate::zeroizing::ZeroizingSecretKey;
+use crate::{ContractSigner, Storage};
+
+const DLC_KEYS_TAG: &[u8] = b"DLC";
+
+/// A [`ContractSigner`] using the channel keys of an LDK [`KeysManager`]. As
+/// the on-chain funds are not held by the [`KeysManager`], funding inputs are
+/// signed externally using PSBTs.
+pub struct LdkKeysSigner<K: Deref<Target = KeysManager>> {
+    keys_manager: K,
+    secp: Secp256k1<All>,
+    keys: Mutex<HashMap<PublicKey, SecretKey>>,
+}
+
+impl<K: Deref<Target = KeysManager>> LdkKeysSigner<K> {
+    /// Creates a signer deriving keys from the given [`KeysManager`].
+    pub fn new(keys_manager: K) -> Self {
+        LdkKeysSigner {
+            keys_manager,
+            secp: Secp256k1::new(),
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Derives all the keys of the contract or channel with the given
+    /// temporary id. Must be called for each existing contract and channel
+    /// after restarting, e.g. using
+    /// [`LdkKeysSigner::recover_keys_from_storage`].
+    pub fn recover_contract_keys(
+        &self,
+        counter_party: &PublicKey,
+        temporary_id: &[u8; 32],
+    ) -> Result<(), Error> {
+        derive_all_contract_keys(self, counter_party, temporary_id)
+    }
+
+    /// Derives the keys of all the contracts and channels found in the given
+    /// storage.
+    pub fn recover_keys_from_storage<S: Deref>(&self, store: &S) -> Result<(), Error>
+    where
+        S::Target: Storage,
+    {
+        for_each_stored_contract(store, |counter_party, temporary_id| {
+            self.recover_contract_keys(counter_party, temporary_id)
+        })
+    }
+
+    fn get_channel_keys_params(key_id: &ContractKeyId) -> [u8; 32] {
+        sha256::Hash::hash(
+            &[
+                DLC_KEYS_TAG,
+                &key_id.counter_party.serialize()[..],
+                &key_id.temporary_id[..],
+            ]
+            .concat(),
+        )
+        .into_inner()
+    }
+}
+
+impl<K: Deref<Target = KeysManager>> ContractSigner for LdkKeysSigner<K> {
+    fn get_new_secret_key(&self) -> Result<SecretKey, Error> {
+        SecretKey::from_slice(&self.keys_manager.get_secure_random_bytes())
+            .map_err(|e| Error::InvalidState(format!("Could not generate secret key: {}", e)))
+    }
+
+    fn derive_contract_secret_key(&self, key_id: &ContractKeyId) -> Result<SecretKey, Error> {
+        let channel_keys = self
+            .keys_manager
+            .derive_channel_keys(0, &Self::get_channel_keys_params(key_id));
+        let secret_key = match key_id.key_type {
+            ContractKeyType::Funding => channel_keys.funding_key,
+            ContractKeyType::OwnBasepoint => channel_keys.payment_key,
+            ContractKeyType::PublishBasepoint => channel_keys.delayed_payment_base_key,
+            ContractKeyType::RevocationBasepoint => channel_keys.revocation_base_key,
+            ContractKeyType::PerUpdateSeed => SecretKey::from_slice(&channel_keys.commitment_seed)
+                .map_err(|e| Error::InvalidState(format!("Invalid per update seed: {}", e)))?,
+        };
+        self.keys.lock().unwrap().insert(
+            PublicKey::from_secret_key(&self.secp, &secret_key),
+            secret_key,
+        );
+        Ok(secret_key)
+    }
+
+    fn sign_tx_input(
+        &self,
+        _tx: &mut Transaction,
+        _input_index: usize,
+        _tx_out: &TxOut,
+        _redeem_script: Option<Script>,
+    ) -> Result<(), Error> {
+        Err(Error::InvalidState(
+            "The LDK keys manager does not hold the on-chain funds.".to_string(),
+        ))
+    }
+
+    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<ZeroizingSecretKey, Error> {
+        self.keys
+            .lock()
+            .unwrap()
+            .get(pubkey)
+            .map(|x| ZeroizingSecretKey::new(*x))
+            .ok_or_else(|| Error::InvalidParameters("Unknown public key.".to_string()))
+    }
+
+    fn can_sign_funding_inputs(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn get_signer() -> LdkKeysSigner<Arc<KeysManager>> {
+        LdkKeysSigner::new(Arc::new(KeysManager::new(&[1; 32], 1, 2)))
+    }
+
+    fn get_key_id(key_type: ContractKeyType) -> ContractKeyId {
+        ContractKeyId {
+            counter_party: "0218845781f631c48f1c9709e23092067d06837f30aa0cd0544ac887fe91ddd166"
+                .parse()
+                .unwrap(),
+            temporary_id: [2; 32],
+            key_type,
+        }
+    }
+
+    #[test]
+    fn keys_are_recovered_from_ldk_seed() {
+        let signer = get_signer();
+        let funding_key = signer
+            .derive_contract_secret_key(&get_key_id(ContractKeyType::Funding))
+            .unwrap();
+        let own_key = signer
+            .derive_contract_secret_key(&get_key_id(ContractKeyType::OwnBasepoint))
+            .unwrap();
+        assert_ne!(funding_key, own_key);
+
+        let pubkey = PublicKey::from_secret_key(&signer.secp, &funding_key);
+        let recovered_signer = get_signer();
+        assert!(recovered_signer.get_secret_key_for_pubkey(&pubkey).is_err());
+        let key_id = get_key_id(ContractKeyType::Funding);
+        recovered_signer
+            .recover_contract_keys(&key_id.counter_party, &key_id.temporary_id)
+            .unwrap();
+
+        assert_eq!(
+            funding_key,
+            *recovered_signer.get_secret_key_for_pubkey(&pubkey).unwrap()
+        );
+    }
+}
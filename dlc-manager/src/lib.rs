@@ -3,7 +3,7 @@
 
 #![crate_name = "dlc_manager"]
 // Coding conventions
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 #![deny(non_upper_case_globals)]
 #![deny(non_camel_case_types)]
 #![deny(non_snake_case)]
@@ -21,35 +21,77 @@ extern crate core;
 extern crate dlc_trie;
 extern crate lightning;
 extern crate log;
+#[cfg(feature = "nostr-transport")]
+extern crate nostr;
+#[cfg(feature = "proptest")]
+extern crate proptest;
 #[cfg(feature = "fuzztarget")]
 extern crate rand_chacha;
+#[cfg(any(feature = "rest-oracle", feature = "http-transport"))]
+extern crate reqwest;
 extern crate secp256k1_zkp;
 
 pub mod chain_monitor;
 pub mod channel;
 pub mod channel_updater;
 pub mod contract;
+mod contract_lock;
 pub mod contract_updater;
 mod conversion_utils;
+pub mod custom_message_handler;
 pub mod error;
+pub mod fallback_oracle;
+pub mod fee_rate_policy;
+#[cfg(feature = "http-transport")]
+pub mod http_transport;
+pub mod key_derivation;
+pub mod ldk_signer;
 pub mod manager;
+pub mod multi_party_updater;
+#[cfg(feature = "nostr-transport")]
+pub mod nostr_transport;
+pub mod novation_updater;
+pub mod onion_message_transport;
+pub mod oracle_trust;
 pub mod payout_curve;
+#[cfg(feature = "proptest")]
+pub mod proptest_strategies;
+pub(crate) mod psbt;
+pub mod rate_limiter;
+#[cfg(feature = "rest-oracle")]
+pub mod rest_oracle_client;
+pub mod retry;
 mod utils;
+pub mod utxo_reservation;
+pub mod zeroizing;
 
-use bitcoin::{Address, Block, OutPoint, Script, Transaction, TxOut, Txid};
+use bitcoin::{
+    Address, Block, BlockHash, BlockHeader, EcdsaSighashType, OutPoint, Script, Transaction, TxOut,
+    Txid,
+};
 use chain_monitor::ChainMonitor;
 use channel::offered_channel::OfferedChannel;
 use channel::signed_channel::{SignedChannel, SignedChannelStateType};
 use channel::Channel;
+use contract::multi_party_contract::MultiPartyContract;
+use contract::novation_contract::NovationContract;
 use contract::PreClosedContract;
-use contract::{offered_contract::OfferedContract, signed_contract::SignedContract, Contract};
+use contract::{
+    offered_contract::OfferedContract, signed_contract::SignedContract, ArchivedContract, Contract,
+    ContractState,
+};
+use dlc_messages::message_handler::OutboundMessageStore;
 use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
 use dlc_messages::ser_impls::{read_address, write_address};
+use dlc_messages::Message;
 use error::Error;
 use lightning::ln::msgs::DecodeError;
 use lightning::util::ser::{Readable, Writeable, Writer};
 use secp256k1_zkp::XOnlyPublicKey;
 use secp256k1_zkp::{PublicKey, SecretKey};
+use std::ops::Deref;
+use std::time::Duration;
+use zeroizing::ZeroizingSecretKey;
 
 /// Type alias for a contract id.
 pub type ContractId = [u8; 32];
@@ -63,9 +105,12 @@ pub trait Time {
     fn unix_time_now(&self) -> u64;
 }
 
-/// Provide current time through `SystemTime`.
+/// Provide current time through `SystemTime`. Not available on
+/// wasm32-unknown-unknown where the time must be provided by the host.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 pub struct SystemTimeProvider {}
 
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 impl Time for SystemTimeProvider {
     fn unix_time_now(&self) -> u64 {
         let now = std::time::SystemTime::now();
@@ -75,8 +120,24 @@ impl Time for SystemTimeProvider {
     }
 }
 
-/// Provides signing related functionalities.
-pub trait Signer {
+/// Holds the keys used in contracts and channels and signs their transactions,
+/// including the funding inputs, so that keys can be kept apart from the
+/// [`Wallet`] managing UTXOs and addresses, e.g. in another process or on a
+/// hardware device.
+pub trait ContractSigner {
+    /// Generate a new secret key and store it so that it can later be
+    /// retrieved.
+    fn get_new_secret_key(&self) -> Result<SecretKey, Error>;
+    /// Returns the secret key identified by `key_id`, storing it so that it
+    /// can later be retrieved. Signers can derive it deterministically, see
+    /// [`key_derivation`], for the keys of every contract to be recoverable
+    /// from a seed. The default implementation generates a new secret key.
+    fn derive_contract_secret_key(
+        &self,
+        _key_id: &key_derivation::ContractKeyId,
+    ) -> Result<SecretKey, Error> {
+        self.get_new_secret_key()
+    }
     /// Signs a transaction input
     fn sign_tx_input(
         &self,
@@ -85,19 +146,43 @@ pub trait Signer {
         tx_out: &TxOut,
         redeem_script: Option<Script>,
     ) -> Result<(), Error>;
-    /// Get the secret key associated with the provided public key.
-    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<SecretKey, Error>;
+    /// Signs a transaction input using the given sighash type. The default
+    /// implementation only supports `SIGHASH_ALL`.
+    fn sign_tx_input_with_sighash_type(
+        &self,
+        tx: &mut Transaction,
+        input_index: usize,
+        tx_out: &TxOut,
+        redeem_script: Option<Script>,
+        sighash_type: EcdsaSighashType,
+    ) -> Result<(), Error> {
+        if sighash_type != EcdsaSighashType::All {
+            return Err(Error::InvalidParameters(format!(
+                "Sighash type {} is not supported by this signer.",
+                sighash_type
+            )));
+        }
+        self.sign_tx_input(tx, input_index, tx_out, redeem_script)
+    }
+    /// Get the secret key associated with the provided public key, wrapped so
+    /// that it is wiped from memory once used.
+    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<ZeroizingSecretKey, Error>;
+    /// Whether the signer can sign the funding inputs provided by the wallet.
+    /// When it cannot, contracts are funded by having the funding inputs
+    /// signed externally using PSBTs, see
+    /// [`manager::Manager::get_funding_psbt`].
+    fn can_sign_funding_inputs(&self) -> bool {
+        true
+    }
 }
 
 /// Wallet trait to provide functionalities related to generating, storing and
-/// managing bitcoin addresses and UTXOs.
-pub trait Wallet: Signer {
+/// managing bitcoin addresses and UTXOs. Does not require access to any key.
+pub trait Wallet {
     /// Returns a new (unused) address.
     fn get_new_address(&self) -> Result<Address, Error>;
-    /// Generate a new secret key and store it in the wallet so that it can later
-    /// be retrieved.
-    fn get_new_secret_key(&self) -> Result<SecretKey, Error>;
-    /// Get a set of UTXOs to fund the given amount.
+    /// Get a set of UTXOs to fund the given amount. UTXOs reserved using
+    /// [`Wallet::reserve_utxos`] must not be returned.
     fn get_utxos_for_amount(
         &self,
         amount: u64,
@@ -106,6 +191,35 @@ pub trait Wallet: Signer {
     ) -> Result<Vec<Utxo>, Error>;
     /// Import the provided address.
     fn import_address(&self, address: &Address) -> Result<(), Error>;
+    /// Returns the script pubkey receiving the payout of the contract or
+    /// channel with the given temporary id, unless one was set in its
+    /// [`contract::contract_input::ContractInput`]. Defaults to a new address
+    /// of the wallet, and can be overridden to e.g. send the payouts of some
+    /// contracts to cold storage.
+    fn get_payout_script_pubkey(&self, _temporary_id: &ContractId) -> Result<Script, Error> {
+        Ok(self.get_new_address()?.script_pubkey())
+    }
+    /// Returns the script pubkey receiving the change of the funding inputs
+    /// of the contract or channel with the given temporary id, unless one was
+    /// set in its [`contract::contract_input::ContractInput`]. Defaults to a
+    /// new address of the wallet.
+    fn get_change_script_pubkey(&self, _temporary_id: &ContractId) -> Result<Script, Error> {
+        Ok(self.get_new_address()?.script_pubkey())
+    }
+    /// Reserves the given UTXOs for the contract or channel with the given
+    /// temporary id, replacing any previous reservation for it. The UTXOs
+    /// must be released once `ttl` has elapsed, even if
+    /// [`Wallet::release_utxos`] was never called, so that a handshake that
+    /// never completes cannot lock them indefinitely.
+    fn reserve_utxos(
+        &self,
+        temporary_id: &ContractId,
+        utxos: &[OutPoint],
+        ttl: Duration,
+    ) -> Result<(), Error>;
+    /// Releases the UTXOs reserved for the contract or channel with the given
+    /// temporary id.
+    fn release_utxos(&self, temporary_id: &ContractId) -> Result<(), Error>;
 }
 
 /// Blockchain trait provides access to the bitcoin blockchain.
@@ -122,6 +236,202 @@ pub trait Blockchain {
     fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, Error>;
     /// Get the number of confirmation for the transaction with given id.
     fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32, Error>;
+    /// Returns the estimated fee rate, in satoshis per virtual byte, for a
+    /// transaction to be confirmed within `confirmation_target` blocks.
+    fn get_fee_rate(&self, confirmation_target: u32) -> Result<u64, Error>;
+    /// Returns the unconfirmed transaction spending the given outpoint if one
+    /// is in the mempool.
+    fn get_mempool_spend(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, Error>;
+    /// Returns the last block of the best chain. The default implementation
+    /// retrieves the whole block.
+    fn get_blockchain_tip(&self) -> Result<ChainTip, Error> {
+        let height = self.get_blockchain_height()?;
+        Ok(ChainTip {
+            height,
+            header: self.get_block_at_height(height)?.header,
+        })
+    }
+    /// Returns the headers of the blocks of the best chain above the given
+    /// height, in increasing height order. The default implementation
+    /// retrieves the whole blocks.
+    fn get_headers_since(&self, height: u64) -> Result<Vec<BlockHeader>, Error> {
+        let tip_height = self.get_blockchain_height()?;
+        (height + 1..=tip_height)
+            .map(|h| Ok(self.get_block_at_height(h)?.header))
+            .collect()
+    }
+    /// Returns the state of the circuit breaker guarding the requests to the
+    /// underlying service. Implementations that do not use one are always
+    /// closed.
+    fn get_circuit_state(&self) -> retry::CircuitState {
+        retry::CircuitState::Closed
+    }
+}
+
+/// The last block of the best chain known to a [`Blockchain`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainTip {
+    /// The height of the block.
+    pub height: u64,
+    /// The header of the block.
+    pub header: BlockHeader,
+}
+
+/// Change of the state of the blockchain pushed by a blockchain provider, that
+/// can be processed by the [`manager::Manager`] instead of polling for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainNotification {
+    /// A new block was connected to the tip of the chain.
+    BlockConnected {
+        /// The height of the block.
+        height: u64,
+        /// The hash of the block.
+        block_hash: BlockHash,
+    },
+    /// A transaction was included in a block.
+    TransactionConfirmed {
+        /// The id of the transaction.
+        txid: Txid,
+        /// The height of the block including the transaction.
+        height: u64,
+    },
+    /// An outpoint was spent by a transaction, either in the mempool or in a
+    /// block.
+    OutpointSpent {
+        /// The outpoint that was spent.
+        outpoint: OutPoint,
+        /// The id of the spending transaction.
+        spending_txid: Txid,
+    },
+}
+
+/// Bound of the [`AsyncBlockchain`] implementations, requiring them to be
+/// `Send + Sync` except on wasm32 which has no threads.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSendSync: Send + Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + Sync + ?Sized> MaybeSendSync for T {}
+/// Bound of the [`AsyncBlockchain`] implementations, requiring them to be
+/// `Send + Sync` except on wasm32 which has no threads.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSendSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSendSync for T {}
+
+/// Asynchronous counterpart of the [`Blockchain`] trait, enabling
+/// implementations to batch the requests made for several transactions. On
+/// wasm32 its futures are not required to be `Send`, so that it can be
+/// implemented on top of the (single threaded) browser APIs.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait AsyncBlockchain: MaybeSendSync {
+    /// Broadcast the given transaction to the bitcoin network.
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<(), Error>;
+    /// Returns the network currently used (mainnet, testnet or regtest).
+    fn get_network(&self) -> Result<bitcoin::network::constants::Network, Error>;
+    /// Returns the height of the blockchain
+    async fn get_blockchain_height(&self) -> Result<u64, Error>;
+    /// Returns the block at given height
+    async fn get_block_at_height(&self, height: u64) -> Result<Block, Error>;
+    /// Get the transaction with given id.
+    async fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, Error>;
+    /// Get the number of confirmation for the transaction with given id.
+    async fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32, Error>;
+    /// Returns the estimated fee rate, in satoshis per virtual byte, for a
+    /// transaction to be confirmed within `confirmation_target` blocks.
+    async fn get_fee_rate(&self, confirmation_target: u32) -> Result<u64, Error>;
+    /// Returns the unconfirmed transaction spending the given outpoint if one
+    /// is in the mempool.
+    async fn get_mempool_spend(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, Error>;
+    /// Returns the last block of the best chain.
+    async fn get_blockchain_tip(&self) -> Result<ChainTip, Error>;
+    /// Returns the headers of the blocks of the best chain above the given
+    /// height, in increasing height order.
+    async fn get_headers_since(&self, height: u64) -> Result<Vec<BlockHeader>, Error>;
+    /// Returns the transactions with the given ids, in the same order. The
+    /// default implementation fetches them one at a time and should be
+    /// overridden by implementations able to batch requests.
+    async fn get_transactions(&self, tx_ids: &[Txid]) -> Result<Vec<Transaction>, Error> {
+        let mut transactions = Vec::with_capacity(tx_ids.len());
+        for tx_id in tx_ids {
+            transactions.push(self.get_transaction(tx_id).await?);
+        }
+        Ok(transactions)
+    }
+    /// Returns the number of confirmations of the transactions with the given
+    /// ids, in the same order. The default implementation fetches them one at
+    /// a time and should be overridden by implementations able to batch
+    /// requests.
+    async fn get_transactions_confirmations(&self, tx_ids: &[Txid]) -> Result<Vec<u32>, Error> {
+        let mut confirmations = Vec::with_capacity(tx_ids.len());
+        for tx_id in tx_ids {
+            confirmations.push(self.get_transaction_confirmations(tx_id).await?);
+        }
+        Ok(confirmations)
+    }
+}
+
+/// A write applied as part of a [`Storage::write_batch`] call.
+#[derive(Clone, Copy, Debug)]
+pub enum StorageWrite<'a> {
+    /// Inserts or updates the given contract, removing any record under its
+    /// temporary id once accepted.
+    Contract(&'a Contract),
+    /// Inserts or updates the given channel, removing any record under its
+    /// temporary id once accepted.
+    Channel(&'a Channel),
+    /// Replaces the persisted [`ChainMonitor`].
+    ChainMonitor(&'a ChainMonitor),
+}
+
+/// A page of contracts retrieved using [`Storage::get_contracts_page`].
+#[derive(Clone, Debug)]
+pub struct ContractPage {
+    /// The contracts of the page, ordered by id.
+    pub contracts: Vec<Contract>,
+    /// The cursor to use to retrieve the next page, `None` if there are no
+    /// more contracts.
+    pub next_cursor: Option<ContractId>,
+}
+
+/// Iterator over the contracts of a [`Storage`], retrieving them one page at
+/// a time. Iteration stops after the first error.
+pub struct ContractIterator<'a, S: ?Sized> {
+    store: &'a S,
+    state: Option<ContractState>,
+    page_size: usize,
+    cursor: Option<ContractId>,
+    contracts: std::vec::IntoIter<Contract>,
+    is_done: bool,
+}
+
+impl<'a, S: Storage + ?Sized> Iterator for ContractIterator<'a, S> {
+    type Item = Result<Contract, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(contract) = self.contracts.next() {
+                return Some(Ok(contract));
+            }
+            if self.is_done {
+                return None;
+            }
+            match self
+                .store
+                .get_contracts_page(self.state, self.cursor, self.page_size)
+            {
+                Ok(page) => {
+                    self.is_done = page.next_cursor.is_none();
+                    self.cursor = page.next_cursor;
+                    self.contracts = page.contracts.into_iter();
+                }
+                Err(e) => {
+                    self.is_done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
 }
 
 /// Storage trait provides functionalities to store and retrieve DLCs.
@@ -145,6 +455,43 @@ pub trait Storage {
     /// Returns the set of contracts whos broadcasted cet has not been verified to be confirmed on
     /// blockchain
     fn get_preclosed_contracts(&self) -> Result<Vec<PreClosedContract>, Error>;
+    /// Returns at most `limit` contracts with an id greater than `cursor`,
+    /// ordered by id. Only returns the ones in the given `state` if set.
+    fn get_contracts_page(
+        &self,
+        state: Option<ContractState>,
+        cursor: Option<ContractId>,
+        limit: usize,
+    ) -> Result<ContractPage, Error>;
+    /// Returns an iterator over the contracts, retrieving `page_size` of them
+    /// at a time. Only yields the ones in the given `state` if set.
+    fn iter_contracts(
+        &self,
+        state: Option<ContractState>,
+        page_size: usize,
+    ) -> ContractIterator<'_, Self> {
+        assert!(page_size > 0, "Page size must be greater than zero");
+        ContractIterator {
+            store: self,
+            state,
+            page_size,
+            cursor: None,
+            contracts: Vec::new().into_iter(),
+            is_done: false,
+        }
+    }
+    /// Returns the contracts with the given counter party.
+    fn get_contracts_by_counter_party(
+        &self,
+        counter_party: &PublicKey,
+    ) -> Result<Vec<Contract>, Error>;
+    /// Returns the contracts depending on the oracle event with the given id.
+    fn get_contracts_by_event_id(&self, event_id: &str) -> Result<Vec<Contract>, Error>;
+    /// Persists the given archival records and deletes the contracts they
+    /// were created from atomically.
+    fn archive_contracts(&self, archives: &[ArchivedContract]) -> Result<(), Error>;
+    /// Returns the archival records of the contracts pruned from the store.
+    fn get_archived_contracts(&self) -> Result<Vec<ArchivedContract>, Error>;
     /// Update the state of the channel and optionally its associated contract
     /// atomically.
     fn upsert_channel(&self, channel: Channel, contract: Option<Contract>) -> Result<(), Error>;
@@ -160,10 +507,423 @@ pub trait Storage {
     ) -> Result<Vec<SignedChannel>, Error>;
     /// Returns the set of channels in offer state.
     fn get_offered_channels(&self) -> Result<Vec<OfferedChannel>, Error>;
+    /// Applies all the given writes atomically, so that either all or none
+    /// of them are persisted.
+    fn write_batch(&self, writes: &[StorageWrite]) -> Result<(), Error>;
+    /// Rewrites the contracts and channels persisted using a previous version
+    /// of the serialization format with the current one.
+    fn migrate(&self) -> Result<(), Error>;
     /// Writes the [`ChainMonitor`] data to the store.
     fn persist_chain_monitor(&self, monitor: &ChainMonitor) -> Result<(), Error>;
     /// Returns the latest [`ChainMonitor`] in the store if any.
     fn get_chain_monitor(&self) -> Result<Option<ChainMonitor>, Error>;
+    /// Persists an announcement retrieved from an oracle.
+    fn persist_oracle_announcement(&self, announcement: &OracleAnnouncement) -> Result<(), Error>;
+    /// Returns the announcement of the oracle with the given public key for
+    /// the event with the given id if any.
+    fn get_oracle_announcement(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAnnouncement>, Error>;
+    /// Persists an attestation retrieved from an oracle for the event with
+    /// the given id.
+    fn persist_oracle_attestation(
+        &self,
+        event_id: &str,
+        attestation: &OracleAttestation,
+    ) -> Result<(), Error>;
+    /// Returns the attestation of the oracle with the given public key for
+    /// the event with the given id if any.
+    fn get_oracle_attestation(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAttestation>, Error>;
+    /// Replaces the outbound messages to the peer with the given id that were
+    /// not yet acknowledged.
+    fn persist_outbound_messages(
+        &self,
+        node_id: &PublicKey,
+        messages: &[Message],
+    ) -> Result<(), Error>;
+    /// Returns the outbound messages of every peer that were not yet
+    /// acknowledged.
+    fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, Error>;
+    /// Creates or updates a contract between more than two parties. Once the
+    /// id of the contract differs from its temporary id, the record stored
+    /// under the temporary id is replaced.
+    fn upsert_multi_party_contract(&self, contract: &MultiPartyContract) -> Result<(), Error>;
+    /// Returns the multi party contract with the given id if any.
+    fn get_multi_party_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<MultiPartyContract>, Error>;
+    /// Returns all the multi party contracts in the store.
+    fn get_multi_party_contracts(&self) -> Result<Vec<MultiPartyContract>, Error>;
+    /// Creates or updates the transfer of a contract, stored under the id of
+    /// the transferred contract.
+    fn upsert_novation_contract(&self, contract: &NovationContract) -> Result<(), Error>;
+    /// Returns the transfer of the contract with the given id if any.
+    fn get_novation_contract(&self, id: &ContractId) -> Result<Option<NovationContract>, Error>;
+    /// Returns all the contract transfers in the store.
+    fn get_novation_contracts(&self) -> Result<Vec<NovationContract>, Error>;
+}
+
+/// Persists the outbound messages of a
+/// [`MessageHandler`](dlc_messages::message_handler::MessageHandler) using a
+/// [`Storage`].
+pub struct StorageMessageStore<S: Deref>(pub S)
+where
+    S::Target: Storage;
+
+impl<S: Deref> OutboundMessageStore for StorageMessageStore<S>
+where
+    S::Target: Storage,
+{
+    fn persist_outbound_messages(
+        &self,
+        node_id: &PublicKey,
+        messages: &[Message],
+    ) -> Result<(), std::io::Error> {
+        self.0
+            .persist_outbound_messages(node_id, messages)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+    }
+
+    fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, std::io::Error> {
+        self.0
+            .get_outbound_messages()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+    }
+}
+
+/// Asynchronous counterpart of the [`Storage`] trait, enabling the use of
+/// network backed stores without blocking the executor.
+#[async_trait::async_trait]
+pub trait AsyncStorage: Send + Sync {
+    /// Returns the contract with given id if found.
+    async fn get_contract(&self, id: &ContractId) -> Result<Option<Contract>, Error>;
+    /// Return all contracts
+    async fn get_contracts(&self) -> Result<Vec<Contract>, Error>;
+    /// Create a record for the given contract.
+    async fn create_contract(&self, contract: &OfferedContract) -> Result<(), Error>;
+    /// Delete the record for the contract with the given id.
+    async fn delete_contract(&self, id: &ContractId) -> Result<(), Error>;
+    /// Update the given contract.
+    async fn update_contract(&self, contract: &Contract) -> Result<(), Error>;
+    /// Returns the set of contracts in offered state.
+    async fn get_contract_offers(&self) -> Result<Vec<OfferedContract>, Error>;
+    /// Returns the set of contracts in signed state.
+    async fn get_signed_contracts(&self) -> Result<Vec<SignedContract>, Error>;
+    /// Returns the set of confirmed contracts.
+    async fn get_confirmed_contracts(&self) -> Result<Vec<SignedContract>, Error>;
+    /// Returns the set of contracts whos broadcasted cet has not been verified to be confirmed on
+    /// blockchain
+    async fn get_preclosed_contracts(&self) -> Result<Vec<PreClosedContract>, Error>;
+    /// Returns at most `limit` contracts with an id greater than `cursor`,
+    /// ordered by id. Only returns the ones in the given `state` if set.
+    async fn get_contracts_page(
+        &self,
+        state: Option<ContractState>,
+        cursor: Option<ContractId>,
+        limit: usize,
+    ) -> Result<ContractPage, Error>;
+    /// Returns the contracts with the given counter party.
+    async fn get_contracts_by_counter_party(
+        &self,
+        counter_party: &PublicKey,
+    ) -> Result<Vec<Contract>, Error>;
+    /// Returns the contracts depending on the oracle event with the given id.
+    async fn get_contracts_by_event_id(&self, event_id: &str) -> Result<Vec<Contract>, Error>;
+    /// Persists the given archival records and deletes the contracts they
+    /// were created from atomically.
+    async fn archive_contracts(&self, archives: &[ArchivedContract]) -> Result<(), Error>;
+    /// Returns the archival records of the contracts pruned from the store.
+    async fn get_archived_contracts(&self) -> Result<Vec<ArchivedContract>, Error>;
+    /// Update the state of the channel and optionally its associated contract
+    /// atomically.
+    async fn upsert_channel(
+        &self,
+        channel: Channel,
+        contract: Option<Contract>,
+    ) -> Result<(), Error>;
+    /// Delete the channel with given [`ChannelId`] if any.
+    async fn delete_channel(&self, channel_id: &ChannelId) -> Result<(), Error>;
+    /// Returns the channel with given [`ChannelId`] if any.
+    async fn get_channel(&self, channel_id: &ChannelId) -> Result<Option<Channel>, Error>;
+    /// Returns the set of [`SignedChannel`] in the store. Returns only the one
+    /// with matching `channel_state` if set.
+    async fn get_signed_channels(
+        &self,
+        channel_state: Option<SignedChannelStateType>,
+    ) -> Result<Vec<SignedChannel>, Error>;
+    /// Returns the set of channels in offer state.
+    async fn get_offered_channels(&self) -> Result<Vec<OfferedChannel>, Error>;
+    /// Applies all the given writes atomically, so that either all or none
+    /// of them are persisted.
+    async fn write_batch(&self, writes: &[StorageWrite<'_>]) -> Result<(), Error>;
+    /// Rewrites the contracts and channels persisted using a previous version
+    /// of the serialization format with the current one.
+    async fn migrate(&self) -> Result<(), Error>;
+    /// Writes the [`ChainMonitor`] data to the store.
+    async fn persist_chain_monitor(&self, monitor: &ChainMonitor) -> Result<(), Error>;
+    /// Returns the latest [`ChainMonitor`] in the store if any.
+    async fn get_chain_monitor(&self) -> Result<Option<ChainMonitor>, Error>;
+    /// Persists an announcement retrieved from an oracle.
+    async fn persist_oracle_announcement(
+        &self,
+        announcement: &OracleAnnouncement,
+    ) -> Result<(), Error>;
+    /// Returns the announcement of the oracle with the given public key for
+    /// the event with the given id if any.
+    async fn get_oracle_announcement(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAnnouncement>, Error>;
+    /// Persists an attestation retrieved from an oracle for the event with
+    /// the given id.
+    async fn persist_oracle_attestation(
+        &self,
+        event_id: &str,
+        attestation: &OracleAttestation,
+    ) -> Result<(), Error>;
+    /// Returns the attestation of the oracle with the given public key for
+    /// the event with the given id if any.
+    async fn get_oracle_attestation(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAttestation>, Error>;
+    /// Replaces the outbound messages to the peer with the given id that were
+    /// not yet acknowledged.
+    async fn persist_outbound_messages(
+        &self,
+        node_id: &PublicKey,
+        messages: &[Message],
+    ) -> Result<(), Error>;
+    /// Returns the outbound messages of every peer that were not yet
+    /// acknowledged.
+    async fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, Error>;
+    /// Creates or updates a contract between more than two parties. Once the
+    /// id of the contract differs from its temporary id, the record stored
+    /// under the temporary id is replaced.
+    async fn upsert_multi_party_contract(&self, contract: &MultiPartyContract)
+        -> Result<(), Error>;
+    /// Returns the multi party contract with the given id if any.
+    async fn get_multi_party_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<MultiPartyContract>, Error>;
+    /// Returns all the multi party contracts in the store.
+    async fn get_multi_party_contracts(&self) -> Result<Vec<MultiPartyContract>, Error>;
+    /// Creates or updates the transfer of a contract, stored under the id of
+    /// the transferred contract.
+    async fn upsert_novation_contract(&self, contract: &NovationContract) -> Result<(), Error>;
+    /// Returns the transfer of the contract with the given id if any.
+    async fn get_novation_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<NovationContract>, Error>;
+    /// Returns all the contract transfers in the store.
+    async fn get_novation_contracts(&self) -> Result<Vec<NovationContract>, Error>;
+}
+
+/// Exposes a [`Storage`] through the [`AsyncStorage`] interface. Calls are
+/// forwarded directly to the wrapped store, so it should only be used with
+/// stores that do not block for long, such as local data bases.
+pub struct AsyncStorageAdapter<S: Deref>(pub S)
+where
+    S::Target: Storage;
+
+#[async_trait::async_trait]
+impl<S: Deref + Send + Sync> AsyncStorage for AsyncStorageAdapter<S>
+where
+    S::Target: Storage + Sync,
+{
+    async fn get_contract(&self, id: &ContractId) -> Result<Option<Contract>, Error> {
+        self.0.get_contract(id)
+    }
+
+    async fn get_contracts(&self) -> Result<Vec<Contract>, Error> {
+        self.0.get_contracts()
+    }
+
+    async fn create_contract(&self, contract: &OfferedContract) -> Result<(), Error> {
+        self.0.create_contract(contract)
+    }
+
+    async fn delete_contract(&self, id: &ContractId) -> Result<(), Error> {
+        self.0.delete_contract(id)
+    }
+
+    async fn update_contract(&self, contract: &Contract) -> Result<(), Error> {
+        self.0.update_contract(contract)
+    }
+
+    async fn get_contract_offers(&self) -> Result<Vec<OfferedContract>, Error> {
+        self.0.get_contract_offers()
+    }
+
+    async fn get_signed_contracts(&self) -> Result<Vec<SignedContract>, Error> {
+        self.0.get_signed_contracts()
+    }
+
+    async fn get_confirmed_contracts(&self) -> Result<Vec<SignedContract>, Error> {
+        self.0.get_confirmed_contracts()
+    }
+
+    async fn get_preclosed_contracts(&self) -> Result<Vec<PreClosedContract>, Error> {
+        self.0.get_preclosed_contracts()
+    }
+
+    async fn get_contracts_page(
+        &self,
+        state: Option<ContractState>,
+        cursor: Option<ContractId>,
+        limit: usize,
+    ) -> Result<ContractPage, Error> {
+        self.0.get_contracts_page(state, cursor, limit)
+    }
+
+    async fn get_contracts_by_counter_party(
+        &self,
+        counter_party: &PublicKey,
+    ) -> Result<Vec<Contract>, Error> {
+        self.0.get_contracts_by_counter_party(counter_party)
+    }
+
+    async fn get_contracts_by_event_id(&self, event_id: &str) -> Result<Vec<Contract>, Error> {
+        self.0.get_contracts_by_event_id(event_id)
+    }
+
+    async fn archive_contracts(&self, archives: &[ArchivedContract]) -> Result<(), Error> {
+        self.0.archive_contracts(archives)
+    }
+
+    async fn get_archived_contracts(&self) -> Result<Vec<ArchivedContract>, Error> {
+        self.0.get_archived_contracts()
+    }
+
+    async fn upsert_channel(
+        &self,
+        channel: Channel,
+        contract: Option<Contract>,
+    ) -> Result<(), Error> {
+        self.0.upsert_channel(channel, contract)
+    }
+
+    async fn delete_channel(&self, channel_id: &ChannelId) -> Result<(), Error> {
+        self.0.delete_channel(channel_id)
+    }
+
+    async fn get_channel(&self, channel_id: &ChannelId) -> Result<Option<Channel>, Error> {
+        self.0.get_channel(channel_id)
+    }
+
+    async fn get_signed_channels(
+        &self,
+        channel_state: Option<SignedChannelStateType>,
+    ) -> Result<Vec<SignedChannel>, Error> {
+        self.0.get_signed_channels(channel_state)
+    }
+
+    async fn get_offered_channels(&self) -> Result<Vec<OfferedChannel>, Error> {
+        self.0.get_offered_channels()
+    }
+
+    async fn write_batch(&self, writes: &[StorageWrite<'_>]) -> Result<(), Error> {
+        self.0.write_batch(writes)
+    }
+
+    async fn migrate(&self) -> Result<(), Error> {
+        self.0.migrate()
+    }
+
+    async fn persist_chain_monitor(&self, monitor: &ChainMonitor) -> Result<(), Error> {
+        self.0.persist_chain_monitor(monitor)
+    }
+
+    async fn get_chain_monitor(&self) -> Result<Option<ChainMonitor>, Error> {
+        self.0.get_chain_monitor()
+    }
+
+    async fn persist_oracle_announcement(
+        &self,
+        announcement: &OracleAnnouncement,
+    ) -> Result<(), Error> {
+        self.0.persist_oracle_announcement(announcement)
+    }
+
+    async fn get_oracle_announcement(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAnnouncement>, Error> {
+        self.0.get_oracle_announcement(oracle_public_key, event_id)
+    }
+
+    async fn persist_oracle_attestation(
+        &self,
+        event_id: &str,
+        attestation: &OracleAttestation,
+    ) -> Result<(), Error> {
+        self.0.persist_oracle_attestation(event_id, attestation)
+    }
+
+    async fn get_oracle_attestation(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAttestation>, Error> {
+        self.0.get_oracle_attestation(oracle_public_key, event_id)
+    }
+
+    async fn persist_outbound_messages(
+        &self,
+        node_id: &PublicKey,
+        messages: &[Message],
+    ) -> Result<(), Error> {
+        self.0.persist_outbound_messages(node_id, messages)
+    }
+
+    async fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, Error> {
+        self.0.get_outbound_messages()
+    }
+
+    async fn upsert_multi_party_contract(
+        &self,
+        contract: &MultiPartyContract,
+    ) -> Result<(), Error> {
+        self.0.upsert_multi_party_contract(contract)
+    }
+
+    async fn get_multi_party_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<MultiPartyContract>, Error> {
+        self.0.get_multi_party_contract(id)
+    }
+
+    async fn get_multi_party_contracts(&self) -> Result<Vec<MultiPartyContract>, Error> {
+        self.0.get_multi_party_contracts()
+    }
+
+    async fn upsert_novation_contract(&self, contract: &NovationContract) -> Result<(), Error> {
+        self.0.upsert_novation_contract(contract)
+    }
+
+    async fn get_novation_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<NovationContract>, Error> {
+        self.0.get_novation_contract(id)
+    }
+
+    async fn get_novation_contracts(&self) -> Result<Vec<NovationContract>, Error> {
+        self.0.get_novation_contracts()
+    }
 }
 
 /// Oracle trait provides access to oracle information.
@@ -176,6 +936,44 @@ pub trait Oracle {
     fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation, Error>;
 }
 
+/// Asynchronous counterpart of the [`Oracle`] trait, enabling implementations
+/// to retrieve information for several events concurrently.
+#[async_trait::async_trait]
+pub trait AsyncOracle: Send + Sync {
+    /// Returns the public key of the oracle.
+    fn get_public_key(&self) -> XOnlyPublicKey;
+    /// Returns the announcement for the event with the given id if found.
+    async fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement, Error>;
+    /// Returns the attestation for the event with the given id if found.
+    async fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation, Error>;
+    /// Returns the announcements for the events with the given ids, in the
+    /// same order. The default implementation fetches them one at a time and
+    /// should be overridden by implementations able to batch requests.
+    async fn get_announcements(
+        &self,
+        event_ids: &[String],
+    ) -> Result<Vec<OracleAnnouncement>, Error> {
+        let mut announcements = Vec::with_capacity(event_ids.len());
+        for event_id in event_ids {
+            announcements.push(self.get_announcement(event_id).await?);
+        }
+        Ok(announcements)
+    }
+    /// Returns the attestations for the events with the given ids, in the
+    /// same order. The default implementation fetches them one at a time and
+    /// should be overridden by implementations able to batch requests.
+    async fn get_attestations(
+        &self,
+        event_ids: &[String],
+    ) -> Result<Vec<OracleAttestation>, Error> {
+        let mut attestations = Vec::with_capacity(event_ids.len());
+        for event_id in event_ids {
+            attestations.push(self.get_attestation(event_id).await?);
+        }
+        Ok(attestations)
+    }
+}
+
 /// Represents a UTXO.
 #[derive(Clone, Debug)]
 pub struct Utxo {
@@ -1,41 +1,69 @@
 //! #Manager a component to create and update DLCs.
 
-use super::{Blockchain, Oracle, Storage, Time, Wallet};
+use super::{Blockchain, ChainNotification, ChainTip, Oracle, Storage, StorageWrite, Time, Wallet};
 use crate::chain_monitor::{ChainMonitor, ChannelInfo, RevokedTxType, TxType};
 use crate::channel::offered_channel::OfferedChannel;
 use crate::channel::signed_channel::{SignedChannel, SignedChannelState, SignedChannelStateType};
 use crate::channel::Channel;
 use crate::channel_updater::get_signed_channel_state;
 use crate::channel_updater::verify_signed_channel;
+use crate::contract::multi_party_contract::{
+    MultiPartyContract, MultiPartyContractInput, MultiPartyContractState,
+};
+use crate::contract::novation_contract::{NovationContract, NovationRole, NovationState};
 use crate::contract::{
     accepted_contract::AcceptedContract, contract_info::ContractInfo,
     contract_input::ContractInput, contract_input::OracleInput, offered_contract::OfferedContract,
-    signed_contract::SignedContract, AdaptorInfo, ClosedContract, Contract, FailedAcceptContract,
-    FailedSignContract, PreClosedContract,
+    signed_contract::SignedContract, AdaptorInfo, ArchivedContract, ClosedContract, Contract,
+    ContractState, FailedAcceptContract, FailedSignContract, FundingInputInfo, PreClosedContract,
+    SharedFundingInput,
+};
+use crate::contract_lock::ContractLocks;
+use crate::contract_updater::{
+    accept_contract, accept_contract_with_shared_funding, verify_accepted_and_sign_contract,
+    verify_accepted_contract,
 };
-use crate::contract_updater::{accept_contract, verify_accepted_and_sign_contract};
 use crate::error::Error;
-use crate::Signer;
+use crate::fee_rate_policy::FeeRatePolicy;
+use crate::oracle_trust::OracleTrustConfig;
+use crate::rate_limiter::{RateLimitViolation, RateLimiter, RateLimits};
+use crate::retry::CircuitState;
+use crate::ContractSigner;
 use crate::{ChannelId, ContractId};
+use bitcoin::blockdata::locktime::LOCK_TIME_THRESHOLD;
+use bitcoin::util::psbt::PartiallySignedTransaction;
 use bitcoin::Address;
+use bitcoin::BlockHeader;
+use bitcoin::EcdsaSighashType;
+use bitcoin::OutPoint;
 use bitcoin::Transaction;
+use dlc::PartyParams;
 use dlc_messages::channel::{
     AcceptChannel, CollaborativeCloseOffer, OfferChannel, Reject, RenewAccept, RenewConfirm,
     RenewFinalize, RenewOffer, SettleAccept, SettleConfirm, SettleFinalize, SettleOffer,
     SignChannel,
 };
+use dlc_messages::features::Features;
+use dlc_messages::multi_party::{
+    MultiPartyAccept, MultiPartyFinalize, MultiPartyFunding, MultiPartyOffer, MultiPartySetup,
+    MultiPartySign,
+};
+use dlc_messages::novation::{
+    NovationAccept, NovationConfirm, NovationFinalize, NovationOffer, NovationRequest,
+};
 use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
 use dlc_messages::{AcceptDlc, Message as DlcMessage, OfferDlc, SignDlc};
 use lightning::chain::chaininterface::FeeEstimator;
-use lightning::ln::chan_utils::{
-    build_commitment_secret, derive_private_key, derive_private_revocation_key,
-};
+use lightning::ln::chan_utils::{build_commitment_secret, derive_private_revocation_key};
 use log::{error, warn};
 use secp256k1_zkp::XOnlyPublicKey;
 use secp256k1_zkp::{ecdsa::Signature, All, PublicKey, Secp256k1, SecretKey};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::string::ToString;
+use std::sync::Mutex;
+use std::time::Duration;
 
 /// The number of confirmations required before moving the the confirmed state.
 pub const NB_CONFIRMATIONS: u32 = 6;
@@ -46,6 +74,44 @@ pub const CET_NSEQUENCE: u32 = 288;
 /// Timeout in seconds when waiting for a peer's reply, after which a DLC channel
 /// is forced closed.
 pub const PEER_TIMEOUT: u64 = 3600;
+/// The number of contracts retrieved at once from the storage when checking
+/// the state of the contracts.
+pub const CONTRACT_PAGE_SIZE: usize = 100;
+/// The number of blocks within which the transactions of contracts offered
+/// without a fee rate are expected to confirm.
+pub const OFFER_CONFIRMATION_TARGET: u32 = 6;
+/// The number of blocks within which punishment transactions are expected to
+/// confirm, as they compete with the counter party claiming its output.
+pub const PUNISH_CONFIRMATION_TARGET: u32 = 2;
+/// The age in seconds of the last block after which the blockchain provider
+/// is considered to have fallen behind. Three hours without a block are very
+/// unlikely on mainnet.
+pub const MAX_TIP_AGE: u64 = 3 * 3600;
+/// How long the UTXOs funding a contract or channel remain reserved by the
+/// wallet while waiting for the counter party, unless configured otherwise
+/// using [`Manager::set_utxo_reservation_ttl`].
+pub const UTXO_RESERVATION_TTL: Duration = Duration::from_secs(86400);
+/// The number of blocks whose median timestamp has to be past a time based
+/// lock time for a transaction to be valid in the next block.
+const MEDIAN_TIME_SPAN: usize = 11;
+
+/// The states of the contracts that can be pruned from the storage.
+const FINAL_CONTRACT_STATES: [ContractState; 5] = [
+    ContractState::Closed,
+    ContractState::Refunded,
+    ContractState::FailedAccept,
+    ContractState::FailedSign,
+    ContractState::Rejected,
+];
+
+/// Determines which contracts are pruned from the storage by
+/// [`Manager::prune_contracts`].
+#[derive(Clone, Copy, Debug)]
+pub struct RetentionPolicy {
+    /// The number of days during which contracts in a final state are kept,
+    /// measured from [`ArchivedContract::final_time`].
+    pub max_age_days: u64,
+}
 
 type ClosableContractInfo<'a> = Option<(
     &'a ContractInfo,
@@ -53,8 +119,9 @@ type ClosableContractInfo<'a> = Option<(
     Vec<(usize, OracleAttestation)>,
 )>;
 
-/// Used to create and update DLCs.
-pub struct Manager<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, F: Deref>
+/// Used to create and update DLCs. The `signer` holding the keys defaults to
+/// the wallet but can be provided separately using [`Manager::new_with_signer`].
+pub struct Manager<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, F: Deref, K: Deref = W>
 where
     W::Target: Wallet,
     B::Target: Blockchain,
@@ -62,15 +129,26 @@ where
     O::Target: Oracle,
     T::Target: Time,
     F::Target: FeeEstimator,
+    K::Target: ContractSigner,
 {
     oracles: HashMap<XOnlyPublicKey, O>,
     wallet: W,
+    signer: K,
     blockchain: B,
     store: S,
     secp: Secp256k1<All>,
     chain_monitor: ChainMonitor,
     time: T,
     fee_estimator: F,
+    funding_sighash_type: EcdsaSighashType,
+    verify_announcements: bool,
+    oracle_trust_config: OracleTrustConfig,
+    fee_rate_policy: FeeRatePolicy,
+    rate_limiter: Mutex<RateLimiter>,
+    contract_locks: ContractLocks,
+    utxo_reservation_ttl: Duration,
+    pending_multi_party_messages: Vec<(PublicKey, DlcMessage)>,
+    pending_novation_messages: Vec<(PublicKey, DlcMessage)>,
 }
 
 macro_rules! get_object_in_state {
@@ -158,16 +236,17 @@ macro_rules! check_for_timed_out_channels {
     };
 }
 
-impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, F: Deref> Manager<W, B, S, O, T, F>
+impl<W: Deref + Clone, B: Deref, S: Deref, O: Deref, T: Deref, F: Deref>
+    Manager<W, B, S, O, T, F, W>
 where
-    W::Target: Wallet,
+    W::Target: Wallet + ContractSigner,
     B::Target: Blockchain,
     S::Target: Storage,
     O::Target: Oracle,
     T::Target: Time,
     F::Target: FeeEstimator,
 {
-    /// Create a new Manager struct.
+    /// Create a new Manager struct using the wallet as signer.
     pub fn new(
         wallet: W,
         blockchain: B,
@@ -175,20 +254,124 @@ where
         oracles: HashMap<XOnlyPublicKey, O>,
         time: T,
         fee_estimator: F,
+    ) -> Result<Self, Error> {
+        Self::new_with_signer(
+            wallet.clone(),
+            wallet,
+            blockchain,
+            store,
+            oracles,
+            time,
+            fee_estimator,
+        )
+    }
+}
+
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, F: Deref, K: Deref>
+    Manager<W, B, S, O, T, F, K>
+where
+    W::Target: Wallet,
+    B::Target: Blockchain,
+    S::Target: Storage,
+    O::Target: Oracle,
+    T::Target: Time,
+    F::Target: FeeEstimator,
+    K::Target: ContractSigner,
+{
+    /// Create a new Manager struct using `signer` to hold the keys and sign
+    /// transactions, while `wallet` only provides addresses and UTXOs.
+    pub fn new_with_signer(
+        wallet: W,
+        signer: K,
+        blockchain: B,
+        store: S,
+        oracles: HashMap<XOnlyPublicKey, O>,
+        time: T,
+        fee_estimator: F,
     ) -> Result<Self, Error> {
         let init_height = blockchain.get_blockchain_height()?;
         Ok(Manager {
             secp: secp256k1_zkp::Secp256k1::new(),
             wallet,
+            signer,
             blockchain,
             store,
             oracles,
             time,
             fee_estimator,
             chain_monitor: ChainMonitor::new(init_height),
+            funding_sighash_type: EcdsaSighashType::All,
+            verify_announcements: true,
+            oracle_trust_config: OracleTrustConfig::default(),
+            fee_rate_policy: FeeRatePolicy::default(),
+            rate_limiter: Mutex::new(RateLimiter::default()),
+            contract_locks: ContractLocks::default(),
+            utxo_reservation_ttl: UTXO_RESERVATION_TTL,
+            pending_multi_party_messages: Vec::new(),
+            pending_novation_messages: Vec::new(),
         })
     }
 
+    /// Set the configuration restricting the oracles that contracts offered
+    /// by peers can rely on. Offers not satisfying it are rejected.
+    pub fn set_oracle_trust_config(&mut self, oracle_trust_config: OracleTrustConfig) {
+        self.oracle_trust_config = oracle_trust_config;
+    }
+
+    /// Set the policy restricting the fee rates of contracts and channels
+    /// offered by peers, which are not restricted by default. Offers not
+    /// satisfying it are rejected.
+    pub fn set_fee_rate_policy(&mut self, fee_rate_policy: FeeRatePolicy) {
+        self.fee_rate_policy = fee_rate_policy;
+    }
+
+    /// Set the limits on the messages accepted from each peer, which are not
+    /// limited by default.
+    pub fn set_rate_limits(&mut self, rate_limits: RateLimits) {
+        self.rate_limiter.lock().unwrap().set_limits(rate_limits);
+    }
+
+    /// Returns the messages rejected since the last call because their
+    /// senders exceeded the rate limits.
+    pub fn get_and_clear_rate_limit_violations(&mut self) -> Vec<RateLimitViolation> {
+        self.rate_limiter.lock().unwrap().get_and_clear_violations()
+    }
+
+    /// Set how long the UTXOs funding a contract or channel remain reserved
+    /// while waiting for the counter party, [`UTXO_RESERVATION_TTL`] by
+    /// default. Each step of the establishment renews the reservation.
+    pub fn set_utxo_reservation_ttl(&mut self, utxo_reservation_ttl: Duration) {
+        self.utxo_reservation_ttl = utxo_reservation_ttl;
+    }
+
+    /// Set whether the signatures and nonce counts of oracle announcements
+    /// are verified when offering or receiving contracts and channels (enabled
+    /// by default). Should only be disabled in test environments.
+    pub fn set_verify_announcements(&mut self, verify_announcements: bool) {
+        self.verify_announcements = verify_announcements;
+    }
+
+    /// Set the sighash type used to sign own funding inputs of DLCs (channels
+    /// always use `SIGHASH_ALL`). Only `SIGHASH_ALL` (the default) and
+    /// `SIGHASH_ALL|SIGHASH_ANYONECANPAY` are supported. Note that inputs must
+    /// not be attached to the funding transaction once signed, as changing its
+    /// txid invalidates the CETs and refund transaction spending it: the fees
+    /// of a pending funding transaction can only be bumped using CPFP on a
+    /// change output.
+    pub fn set_funding_sighash_type(
+        &mut self,
+        funding_sighash_type: EcdsaSighashType,
+    ) -> Result<(), Error> {
+        dlc::util::validate_funding_sighash_type(funding_sighash_type).map_err(|_| {
+            Error::InvalidParameters(format!(
+                "Unsupported funding sighash type {}",
+                funding_sighash_type
+            ))
+        })?;
+        self.funding_sighash_type = funding_sighash_type;
+        Ok(())
+    }
+
     /// Get the store from the Manager to access contracts.
     pub fn get_store(&self) -> &S {
         &self.store
@@ -199,18 +382,44 @@ where
         &mut self.store
     }
 
+    /// Returns the state of the circuit breaker of the blockchain provider,
+    /// while it is open contracts are not checked.
+    pub fn get_blockchain_circuit_state(&self) -> CircuitState {
+        self.blockchain.get_circuit_state()
+    }
+
+    /// Returns the tip of the chain of the blockchain provider, or an error if
+    /// its last block is older than [`MAX_TIP_AGE`], indicating that the
+    /// provider has fallen behind.
+    pub fn check_blockchain_sync(&self) -> Result<ChainTip, Error> {
+        let tip = self.blockchain.get_blockchain_tip()?;
+        let tip_age = self
+            .time
+            .unix_time_now()
+            .saturating_sub(tip.header.time as u64);
+        if tip_age > MAX_TIP_AGE {
+            return Err(Error::BlockchainError(format!(
+                "Blockchain provider is stalled at height {}, its last block is {} seconds old",
+                tip.height, tip_age
+            )));
+        }
+        Ok(tip)
+    }
+
     /// Function called to pass a DlcMessage to the Manager.
     pub fn on_dlc_message(
         &mut self,
         msg: &DlcMessage,
         counter_party: PublicKey,
     ) -> Result<Option<DlcMessage>, Error> {
+        self.check_rate_limits(msg, &counter_party)?;
+
         match msg {
             DlcMessage::Offer(o) => {
                 self.on_offer_message(o, counter_party)?;
                 Ok(None)
             }
-            DlcMessage::Accept(a) => Ok(Some(self.on_accept_message(a, &counter_party)?)),
+            DlcMessage::Accept(a) => self.on_accept_message(a, &counter_party),
             DlcMessage::Sign(s) => {
                 self.on_sign_message(s, &counter_party)?;
                 Ok(None)
@@ -262,9 +471,89 @@ where
                 self.on_reject(r, &counter_party)?;
                 Ok(None)
             }
+            DlcMessage::MultiPartyOffer(o) => {
+                self.on_multi_party_offer(o, counter_party)?;
+                Ok(None)
+            }
+            DlcMessage::MultiPartyAccept(a) => {
+                self.on_multi_party_accept(a, &counter_party)?;
+                Ok(None)
+            }
+            DlcMessage::MultiPartySetup(s) => Ok(Some(DlcMessage::MultiPartySign(
+                self.on_multi_party_setup(s, &counter_party)?,
+            ))),
+            DlcMessage::MultiPartySign(s) => {
+                self.on_multi_party_sign(s, &counter_party)?;
+                Ok(None)
+            }
+            DlcMessage::MultiPartyFinalize(f) => Ok(Some(DlcMessage::MultiPartyFunding(
+                self.on_multi_party_finalize(f, &counter_party)?,
+            ))),
+            DlcMessage::MultiPartyFunding(f) => {
+                self.on_multi_party_funding(f, &counter_party)?;
+                Ok(None)
+            }
+            DlcMessage::NovationOffer(o) => {
+                self.on_novation_offer(o, counter_party)?;
+                Ok(None)
+            }
+            DlcMessage::NovationAccept(a) => {
+                self.on_novation_accept(a, &counter_party)?;
+                Ok(None)
+            }
+            DlcMessage::NovationRequest(r) => Ok(Some(DlcMessage::NovationConfirm(
+                self.on_novation_request(r, &counter_party)?,
+            ))),
+            DlcMessage::NovationConfirm(c) => {
+                self.on_novation_confirm(c, &counter_party)?;
+                Ok(None)
+            }
+            DlcMessage::NovationFinalize(f) => {
+                self.on_novation_finalize(f, &counter_party)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Processes an accept or sign message received from the given peer. As
+    /// opposed to [`Manager::on_dlc_message`], a shared reference is enough so
+    /// that messages relating to different contracts can be verified and
+    /// signed concurrently from several threads. Messages relating to the
+    /// same contract are processed one at a time.
+    pub fn on_contract_message(
+        &self,
+        msg: &DlcMessage,
+        counter_party: PublicKey,
+    ) -> Result<Option<DlcMessage>, Error> {
+        match msg {
+            DlcMessage::Accept(a) => {
+                self.check_rate_limits(msg, &counter_party)?;
+                self.on_accept_message(a, &counter_party)
+            }
+            DlcMessage::Sign(s) => {
+                self.check_rate_limits(msg, &counter_party)?;
+                self.on_sign_message(s, &counter_party)?;
+                Ok(None)
+            }
+            _ => Err(Error::InvalidParameters(
+                "Only accept and sign messages can be processed concurrently.".to_string(),
+            )),
         }
     }
 
+    /// Processes several messages received from the same peer in order,
+    /// returning the result of processing each of them. A failure to process
+    /// a message does not prevent the following ones from being processed.
+    pub fn on_dlc_messages(
+        &mut self,
+        msgs: &[DlcMessage],
+        counter_party: PublicKey,
+    ) -> Vec<Result<Option<DlcMessage>, Error>> {
+        msgs.iter()
+            .map(|msg| self.on_dlc_message(msg, counter_party))
+            .collect()
+    }
+
     /// Function called to create a new DLC. The offered contract will be stored
     /// and an OfferDlc message returned.
     pub fn send_offer(
@@ -272,6 +561,7 @@ where
         contract_input: &ContractInput,
         counter_party: PublicKey,
     ) -> Result<OfferDlc, Error> {
+        let contract_input = self.with_fee_rate(contract_input)?;
         contract_input.validate()?;
 
         let oracle_announcements = contract_input
@@ -282,144 +572,1593 @@ where
 
         let (offered_contract, offer_msg) = crate::contract_updater::offer_contract(
             &self.secp,
-            contract_input,
+            &contract_input,
             oracle_announcements,
             REFUND_DELAY,
             &counter_party,
             &self.wallet,
+            &self.signer,
             &self.blockchain,
             &self.time,
         )?;
 
-        offered_contract.validate()?;
-
-        self.store.create_contract(&offered_contract)?;
+        self.store_offered_contract(&offered_contract)?;
 
         Ok(offer_msg)
     }
 
-    /// Function to call to accept a DLC for which an offer was received.
-    pub fn accept_contract_offer(
+    /// Creates several DLCs at once, returning the result of offering each of
+    /// them in order. The UTXOs funding all the offers are selected using a
+    /// single wallet request, offers that cannot be funded from this selection
+    /// falling back to selecting their own UTXOs. A failure to create an offer
+    /// does not prevent the following ones from being created.
+    pub fn send_offers(
         &mut self,
-        contract_id: &ContractId,
-    ) -> Result<(ContractId, PublicKey, AcceptDlc), Error> {
-        let offered_contract =
-            get_contract_in_state!(self, contract_id, Offered, None as Option<PublicKey>)?;
-
-        let counter_party = offered_contract.counter_party;
-
-        let (accepted_contract, accept_msg) = accept_contract(
-            &self.secp,
-            &offered_contract,
-            &self.wallet,
-            &self.blockchain,
-        )?;
+        offers: &[(ContractInput, PublicKey)],
+    ) -> Vec<Result<OfferDlc, Error>> {
+        let estimated_fee_rate = if offers.iter().any(|(x, _)| x.fee_rate == 0) {
+            self.blockchain.get_fee_rate(OFFER_CONFIRMATION_TARGET).ok()
+        } else {
+            None
+        };
 
-        self.wallet.import_address(&Address::p2wsh(
-            &accepted_contract.dlc_transactions.funding_script_pubkey,
-            self.blockchain.get_network()?,
-        ))?;
+        let prepared = offers
+            .iter()
+            .map(|(contract_input, _)| {
+                let mut contract_input = contract_input.clone();
+                if let (0, Some(fee_rate)) = (contract_input.fee_rate, estimated_fee_rate) {
+                    contract_input.fee_rate = fee_rate;
+                }
+                let contract_input = self.with_fee_rate(&contract_input)?.into_owned();
+                contract_input.validate()?;
+                let oracle_announcements = contract_input
+                    .contract_infos
+                    .iter()
+                    .map(|x| self.get_oracle_announcements(&x.oracles))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok((contract_input, oracle_announcements))
+            })
+            .collect::<Vec<Result<_, Error>>>();
 
-        let contract_id = accepted_contract.get_contract_id();
+        let funded = prepared
+            .iter()
+            .enumerate()
+            .filter_map(|(i, x)| x.as_ref().ok().map(|(input, _)| (i, input)))
+            .collect::<Vec<_>>();
+        let amounts = funded
+            .iter()
+            .map(|(_, input)| {
+                crate::utils::get_approximate_required_amount(
+                    input.offer_collateral,
+                    input.fee_rate,
+                )
+            })
+            .collect::<Vec<_>>();
+        let max_fee_rate = funded.iter().map(|(_, input)| input.fee_rate).max();
+        let mut utxos = vec![None; offers.len()];
+        if let Some(max_fee_rate) = max_fee_rate {
+            if let Ok(selected) =
+                self.wallet
+                    .get_utxos_for_amount(amounts.iter().sum(), Some(max_fee_rate), false)
+            {
+                for ((i, _), split) in funded
+                    .iter()
+                    .zip(crate::utils::split_utxos(selected, &amounts))
+                {
+                    utxos[*i] = split;
+                }
+            }
+        }
 
-        self.store
-            .update_contract(&Contract::Accepted(accepted_contract))?;
+        let mut results = prepared.into_iter().map(Some).collect::<Vec<_>>();
+        let mut offer_msgs = (0..offers.len()).map(|_| None).collect::<Vec<_>>();
+        // Offers funded by the shared selection are created and their UTXOs
+        // reserved first, so that the other ones cannot select them.
+        let order = (0..offers.len())
+            .filter(|i| utxos[*i].is_some())
+            .chain((0..offers.len()).filter(|i| utxos[*i].is_none()))
+            .collect::<Vec<_>>();
+        for i in order {
+            let res = results[i].take().expect("to process each offer once");
+            offer_msgs[i] = Some(res.and_then(|(contract_input, oracle_announcements)| {
+                let counter_party = &offers[i].1;
+                let (offered_contract, offer_msg) = match utxos[i].take() {
+                    Some(utxos) => crate::contract_updater::offer_contract_with_utxos(
+                        &self.secp,
+                        &contract_input,
+                        oracle_announcements,
+                        REFUND_DELAY,
+                        counter_party,
+                        utxos,
+                        &self.wallet,
+                        &self.signer,
+                        &self.blockchain,
+                        &self.time,
+                    )?,
+                    None => crate::contract_updater::offer_contract(
+                        &self.secp,
+                        &contract_input,
+                        oracle_announcements,
+                        REFUND_DELAY,
+                        counter_party,
+                        &self.wallet,
+                        &self.signer,
+                        &self.blockchain,
+                        &self.time,
+                    )?,
+                };
+                self.store_offered_contract(&offered_contract)?;
+                Ok(offer_msg)
+            }));
+        }
 
-        Ok((contract_id, counter_party, accept_msg))
+        offer_msgs
+            .into_iter()
+            .map(|x| x.expect("to have processed each offer"))
+            .collect()
     }
 
-    /// Function to call to check the state of the currently executing DLCs and
-    /// update them if possible.
-    pub fn periodic_check(&mut self) -> Result<(), Error> {
-        self.check_signed_contracts()?;
-        self.check_confirmed_contracts()?;
-        self.check_preclosed_contracts()?;
-        self.channel_checks()?;
+    /// Validates and stores a contract offered by us, reserving the UTXOs
+    /// funding it.
+    fn store_offered_contract(&self, offered_contract: &OfferedContract) -> Result<(), Error> {
+        offered_contract.validate()?;
 
-        Ok(())
+        self.reserve_utxos(&offered_contract.id, &offered_contract.offer_params)?;
+        self.release_utxos_on_error(
+            &offered_contract.id,
+            self.store.create_contract(offered_contract),
+        )
     }
 
-    fn on_offer_message(
+    /// Function called to create a new DLC funded solely by the output at
+    /// `prev_tx_vout` of `prev_tx`, jointly owned with the counter party. Out of
+    /// the output, `accept_share` is attributed to the counter party and the
+    /// rest to us. The offered contract will be stored and an OfferDlc message
+    /// returned.
+    pub fn send_offer_with_shared_funding(
         &mut self,
-        offered_message: &OfferDlc,
+        contract_input: &ContractInput,
         counter_party: PublicKey,
-    ) -> Result<(), Error> {
-        offered_message.validate(&self.secp, REFUND_DELAY, REFUND_DELAY * 2)?;
-        let contract: OfferedContract =
-            OfferedContract::try_from_offer_dlc(offered_message, counter_party)?;
-        contract.validate()?;
+        prev_tx: &Transaction,
+        prev_tx_vout: u32,
+        shared_funding_input: &SharedFundingInput,
+        accept_share: u64,
+    ) -> Result<OfferDlc, Error> {
+        let contract_input = self.with_fee_rate(contract_input)?;
+        contract_input.validate()?;
 
-        if self.store.get_contract(&contract.id)?.is_some() {
-            return Err(Error::InvalidParameters(
-                "Contract with identical id already exists".to_string(),
-            ));
-        }
+        let oracle_announcements = contract_input
+            .contract_infos
+            .iter()
+            .map(|x| self.get_oracle_announcements(&x.oracles))
+            .collect::<Result<Vec<_>, Error>>()?;
 
-        self.store.create_contract(&contract)?;
+        let (offered_contract, offer_msg) =
+            crate::contract_updater::offer_contract_with_shared_funding(
+                &self.secp,
+                &contract_input,
+                oracle_announcements,
+                REFUND_DELAY,
+                &counter_party,
+                prev_tx,
+                prev_tx_vout,
+                shared_funding_input,
+                accept_share,
+                &self.wallet,
+                &self.signer,
+                &self.time,
+            )?;
 
-        Ok(())
+        offered_contract.validate()?;
+
+        self.store.create_contract(&offered_contract)?;
+
+        Ok(offer_msg)
     }
 
-    fn on_accept_message(
+    /// Function to call to offer a DLC rolling over the given confirmed
+    /// contract, funded solely by its fund output. Out of the fund output,
+    /// `counter_payout` is attributed to the counter party and the rest to us,
+    /// the rolled over contract being closed once the funding transaction of
+    /// the new one is confirmed.
+    pub fn send_rollover_offer(
         &mut self,
-        accept_msg: &AcceptDlc,
-        counter_party: &PublicKey,
-    ) -> Result<DlcMessage, Error> {
-        let offered_contract = get_contract_in_state!(
-            self,
-            &accept_msg.temporary_contract_id,
-            Offered,
-            Some(*counter_party)
-        )?;
+        contract_id: &ContractId,
+        contract_input: &ContractInput,
+        counter_payout: u64,
+    ) -> Result<OfferDlc, Error> {
+        let contract =
+            get_contract_in_state!(self, contract_id, Confirmed, None as Option<PublicKey>)?;
 
-        let (signed_contract, signed_msg) = match verify_accepted_and_sign_contract(
+        let contract_input = self.with_fee_rate(contract_input)?;
+        contract_input.validate()?;
+
+        let oracle_announcements = contract_input
+            .contract_infos
+            .iter()
+            .map(|x| self.get_oracle_announcements(&x.oracles))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let (offered_contract, offer_msg) = crate::contract_updater::offer_contract_rollover(
             &self.secp,
-            &offered_contract,
-            accept_msg,
+            &contract,
+            &contract_input,
+            oracle_announcements,
+            REFUND_DELAY,
+            counter_payout,
             &self.wallet,
-        ) {
-            Ok(contract) => contract,
-            Err(e) => return self.accept_fail_on_error(offered_contract, accept_msg.clone(), e),
-        };
+            &self.signer,
+            &self.time,
+        )?;
 
-        self.wallet.import_address(&Address::p2wsh(
-            &signed_contract
-                .accepted_contract
-                .dlc_transactions
-                .funding_script_pubkey,
-            self.blockchain.get_network()?,
-        ))?;
+        offered_contract.validate()?;
 
-        self.store
-            .update_contract(&Contract::Signed(signed_contract))?;
+        self.store.create_contract(&offered_contract)?;
 
-        Ok(DlcMessage::Sign(signed_msg))
+        Ok(offer_msg)
     }
 
-    fn on_sign_message(
+    /// Function to call to offer a DLC topping up our collateral in the given
+    /// confirmed contract, which it rolls over. The offered contract is funded
+    /// by the fund output of the given contract and by new inputs from our
+    /// wallet, the collateral of the counter party being unchanged. As with
+    /// [`Self::send_rollover_offer`], the given contract is settled by
+    /// attributing `counter_payout` out of its fund output to the counter
+    /// party and the rest to us, and is closed once the funding transaction of
+    /// the new one is confirmed.
+    pub fn send_top_up_offer(
         &mut self,
-        sign_message: &SignDlc,
-        peer_id: &PublicKey,
-    ) -> Result<(), Error> {
-        let accepted_contract =
-            get_contract_in_state!(self, &sign_message.contract_id, Accepted, Some(*peer_id))?;
+        contract_id: &ContractId,
+        contract_input: &ContractInput,
+        counter_payout: u64,
+    ) -> Result<OfferDlc, Error> {
+        let contract =
+            get_contract_in_state!(self, contract_id, Confirmed, None as Option<PublicKey>)?;
 
-        let (signed_contract, fund_tx) = match crate::contract_updater::verify_signed_contract(
-            &self.secp,
-            &accepted_contract,
-            sign_message,
-            &self.wallet,
-        ) {
-            Ok(contract) => contract,
-            Err(e) => return self.sign_fail_on_error(accepted_contract, sign_message.clone(), e),
-        };
+        // Contracts funded by a shared output are signed without a PSBT.
+        if !self.signer.can_sign_funding_inputs() {
+            return Err(Error::InvalidState(
+                "Top-ups require a signer able to sign funding inputs.".to_string(),
+            ));
+        }
 
-        self.store
-            .update_contract(&Contract::Signed(signed_contract))?;
+        let contract_input = self.with_fee_rate(contract_input)?;
+        contract_input.validate()?;
 
-        self.blockchain.send_transaction(&fund_tx)?;
+        let oracle_announcements = contract_input
+            .contract_infos
+            .iter()
+            .map(|x| self.get_oracle_announcements(&x.oracles))
+            .collect::<Result<Vec<_>, Error>>()?;
 
-        Ok(())
+        let (offered_contract, offer_msg) = crate::contract_updater::offer_contract_top_up(
+            &self.secp,
+            &contract,
+            &contract_input,
+            oracle_announcements,
+            REFUND_DELAY,
+            counter_payout,
+            &self.wallet,
+            &self.signer,
+            &self.blockchain,
+            &self.time,
+        )?;
+
+        self.store_offered_contract(&offered_contract)?;
+
+        Ok(offer_msg)
+    }
+
+    /// Function to call to accept a DLC for which an offer was received.
+    pub fn accept_contract_offer(
+        &mut self,
+        contract_id: &ContractId,
+    ) -> Result<(ContractId, PublicKey, AcceptDlc), Error> {
+        let offered_contract =
+            get_contract_in_state!(self, contract_id, Offered, None as Option<PublicKey>)?;
+
+        // The split of the shared output must be explicitly approved.
+        if offered_contract.rollover.is_some() {
+            return Err(Error::InvalidParameters(
+                "Offer rolls over an existing contract, use accept_rollover_offer.".to_string(),
+            ));
+        }
+        if offered_contract.shared_funding_input.is_some()
+            || offered_contract.shared_funding_accept_share.is_some()
+        {
+            return Err(Error::InvalidParameters(
+                "Offer is funded by a shared output, use accept_contract_offer_with_shared_funding."
+                    .to_string(),
+            ));
+        }
+
+        let (accepted_contract, accept_msg) = accept_contract(
+            &self.secp,
+            &offered_contract,
+            &self.wallet,
+            &self.signer,
+            &self.blockchain,
+        )?;
+
+        self.reserve_utxos(&offered_contract.id, &accepted_contract.accept_params)?;
+        let res = self.store_accepted_contract(accepted_contract, accept_msg);
+        self.release_utxos_on_error(&offered_contract.id, res)
+    }
+
+    /// Accepts several DLC offers, returning the result of accepting each of
+    /// them in order. A failure to accept an offer does not prevent the
+    /// following ones from being accepted.
+    pub fn accept_contract_offers(
+        &mut self,
+        contract_ids: &[ContractId],
+    ) -> Vec<Result<(ContractId, PublicKey, AcceptDlc), Error>> {
+        contract_ids
+            .iter()
+            .map(|id| self.accept_contract_offer(id))
+            .collect()
+    }
+
+    /// Function to call to accept a DLC offer funded by an output jointly owned
+    /// with the offering party, out of which we expect to be attributed
+    /// `accept_share`. Offers attributing us a different share are rejected.
+    pub fn accept_contract_offer_with_shared_funding(
+        &mut self,
+        contract_id: &ContractId,
+        shared_funding_input: &SharedFundingInput,
+        accept_share: u64,
+    ) -> Result<(ContractId, PublicKey, AcceptDlc), Error> {
+        let offered_contract =
+            get_contract_in_state!(self, contract_id, Offered, None as Option<PublicKey>)?;
+
+        let (accepted_contract, accept_msg) = accept_contract_with_shared_funding(
+            &self.secp,
+            &offered_contract,
+            shared_funding_input,
+            accept_share,
+            &self.wallet,
+            &self.signer,
+        )?;
+
+        self.store_accepted_contract(accepted_contract, accept_msg)
+    }
+
+    /// Function to call to accept a DLC offer rolling over one of our confirmed
+    /// contracts, out of which we expect to be attributed `counter_payout`.
+    /// Offers attributing us a different amount are rejected.
+    pub fn accept_rollover_offer(
+        &mut self,
+        contract_id: &ContractId,
+        counter_payout: u64,
+    ) -> Result<(ContractId, PublicKey, AcceptDlc), Error> {
+        let offered_contract =
+            get_contract_in_state!(self, contract_id, Offered, None as Option<PublicKey>)?;
+
+        let shared_funding_input = match (
+            &offered_contract.rollover,
+            &offered_contract.shared_funding_input,
+        ) {
+            (Some(_), Some(shared_funding_input)) => shared_funding_input.clone(),
+            _ => {
+                return Err(Error::InvalidParameters(
+                    "Offer does not roll over a contract.".to_string(),
+                ))
+            }
+        };
+
+        self.accept_contract_offer_with_shared_funding(
+            contract_id,
+            &shared_funding_input,
+            counter_payout,
+        )
+    }
+
+    fn store_accepted_contract(
+        &mut self,
+        accepted_contract: AcceptedContract,
+        accept_msg: AcceptDlc,
+    ) -> Result<(ContractId, PublicKey, AcceptDlc), Error> {
+        let counter_party = accepted_contract.offered_contract.counter_party;
+
+        self.wallet.import_address(&Address::p2wsh(
+            &accepted_contract.dlc_transactions.funding_script_pubkey,
+            self.blockchain.get_network()?,
+        ))?;
+
+        let contract_id = accepted_contract.get_contract_id();
+
+        self.store
+            .update_contract(&Contract::Accepted(accepted_contract))?;
+
+        Ok((contract_id, counter_party, accept_msg))
+    }
+
+    /// Returns a PSBT of the fund transaction of the given contract, in which
+    /// the local funding inputs are to be signed when the signer cannot sign
+    /// them (see [`ContractSigner::can_sign_funding_inputs`]). This is the
+    /// case of contracts offered by us once they were accepted, and of
+    /// contracts accepted by us once they were signed by the offering party.
+    pub fn get_funding_psbt(
+        &self,
+        contract_id: &ContractId,
+    ) -> Result<PartiallySignedTransaction, Error> {
+        let accepted_contract = match self.store.get_contract(contract_id)? {
+            Some(Contract::Accepted(c)) if c.offered_contract.is_offer_party => c,
+            Some(Contract::Signed(c)) if !c.accepted_contract.offered_contract.is_offer_party => {
+                c.accepted_contract
+            }
+            Some(c) => {
+                return Err(Error::InvalidState(format!(
+                    "Invalid state {:?} to sign funding inputs.",
+                    c
+                )))
+            }
+            None => return Err(Error::InvalidParameters("Unknown contract id.".to_string())),
+        };
+
+        crate::contract_updater::get_funding_psbt(&accepted_contract, self.funding_sighash_type)
+    }
+
+    /// Resumes the establishment of a contract using the PSBT obtained from
+    /// [`Manager::get_funding_psbt`] once its funding inputs were signed. For
+    /// contracts offered by us, returns the [`SignDlc`] message to be sent to
+    /// the counter party. For contracts accepted by us, the fund transaction
+    /// is broadcast.
+    pub fn on_signed_funding_psbt(
+        &mut self,
+        contract_id: &ContractId,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<Option<(SignDlc, PublicKey)>, Error> {
+        match self.store.get_contract(contract_id)? {
+            Some(Contract::Accepted(accepted_contract))
+                if accepted_contract.offered_contract.is_offer_party =>
+            {
+                let (signed_contract, sign_msg) =
+                    crate::contract_updater::sign_accepted_contract_with_psbt(
+                        &self.secp,
+                        &accepted_contract,
+                        psbt,
+                        &self.signer,
+                    )?;
+                let counter_party = accepted_contract.offered_contract.counter_party;
+
+                self.store
+                    .update_contract(&Contract::Signed(signed_contract))?;
+
+                Ok(Some((sign_msg, counter_party)))
+            }
+            Some(Contract::Signed(signed_contract))
+                if !signed_contract
+                    .accepted_contract
+                    .offered_contract
+                    .is_offer_party =>
+            {
+                let fund_tx =
+                    crate::contract_updater::finalize_fund_tx_with_psbt(&signed_contract, psbt)?;
+
+                self.blockchain.send_transaction(&fund_tx)?;
+
+                Ok(None)
+            }
+            Some(c) => Err(Error::InvalidState(format!(
+                "Invalid state {:?} to sign funding inputs.",
+                c
+            ))),
+            None => Err(Error::InvalidParameters("Unknown contract id.".to_string())),
+        }
+    }
+
+    /// Whether the given funding inputs of ours need to be signed externally
+    /// using a PSBT.
+    fn requires_funding_psbt(&self, own_funding_inputs: &[FundingInputInfo]) -> bool {
+        !self.signer.can_sign_funding_inputs() && !own_funding_inputs.is_empty()
+    }
+
+    /// Reserves the funding inputs of the given party parameters for the
+    /// contract or channel with the given temporary id.
+    fn reserve_utxos(
+        &self,
+        temporary_id: &ContractId,
+        party_params: &PartyParams,
+    ) -> Result<(), Error> {
+        let outpoints = party_params
+            .inputs
+            .iter()
+            .map(|x| x.outpoint)
+            .collect::<Vec<_>>();
+        self.wallet
+            .reserve_utxos(temporary_id, &outpoints, self.utxo_reservation_ttl)
+    }
+
+    /// Releases the UTXOs reserved for the given temporary id. Errors are
+    /// only logged as they happen while handling another failure, and the
+    /// reservation expires in any case.
+    fn release_utxos(&self, temporary_id: &ContractId) {
+        if let Err(e) = self.wallet.release_utxos(temporary_id) {
+            error!("Error releasing utxos: {}", e);
+        }
+    }
+
+    /// Releases the UTXOs reserved for the given temporary id if `res` is an
+    /// error, so that a failed establishment does not keep them locked.
+    fn release_utxos_on_error<R>(
+        &self,
+        temporary_id: &ContractId,
+        res: Result<R, Error>,
+    ) -> Result<R, Error> {
+        if res.is_err() {
+            self.release_utxos(temporary_id);
+        }
+        res
+    }
+
+    /// Creates a contract between us, acting as coordinator, and the given
+    /// participants. The contract is stored and the offer to send to each
+    /// participant returned.
+    pub fn send_multi_party_offer(
+        &mut self,
+        contract_input: &MultiPartyContractInput,
+        participants: &[PublicKey],
+    ) -> Result<Vec<(PublicKey, MultiPartyOffer)>, Error> {
+        let mut contract_input = contract_input.clone();
+        if contract_input.fee_rate == 0 {
+            contract_input.fee_rate = self.blockchain.get_fee_rate(OFFER_CONFIRMATION_TARGET)?;
+        }
+
+        let oracle_announcement = self
+            .get_oracle_announcements(&OracleInput {
+                public_keys: vec![contract_input.oracle_public_key],
+                event_id: contract_input.event_id.clone(),
+                event_ids: Vec::new(),
+                threshold: 1,
+            })?
+            .pop()
+            .expect("to have one announcement per oracle");
+
+        let (contract, offers) = crate::multi_party_updater::offer_contract(
+            &self.secp,
+            &contract_input,
+            oracle_announcement,
+            participants,
+            REFUND_DELAY,
+            &self.wallet,
+            &self.signer,
+            &self.blockchain,
+            &self.time,
+        )?;
+
+        self.reserve_multi_party_utxos(&contract)?;
+        self.release_utxos_on_error(
+            &contract.temporary_id,
+            self.store.upsert_multi_party_contract(&contract),
+        )?;
+
+        Ok(participants.iter().copied().zip(offers).collect())
+    }
+
+    /// Accepts a contract offered by a coordinator, returning the node id of
+    /// the coordinator and the message to send to it.
+    pub fn accept_multi_party_offer(
+        &mut self,
+        contract_id: &ContractId,
+    ) -> Result<(PublicKey, MultiPartyAccept), Error> {
+        let contract = self.get_multi_party_contract(contract_id)?;
+        let (accepted_contract, accept_msg) = crate::multi_party_updater::accept_contract(
+            &self.secp,
+            &contract,
+            &self.wallet,
+            &self.signer,
+            &self.blockchain,
+        )?;
+
+        self.reserve_multi_party_utxos(&accepted_contract)?;
+        self.release_utxos_on_error(
+            &accepted_contract.temporary_id,
+            self.store.upsert_multi_party_contract(&accepted_contract),
+        )?;
+
+        let coordinator = accepted_contract
+            .coordinator
+            .expect("to have a coordinator as participant");
+        Ok((coordinator, accept_msg))
+    }
+
+    /// Returns the messages that need to be sent to the participants of multi
+    /// party contracts coordinated by us, produced since the last call.
+    pub fn get_and_clear_pending_multi_party_messages(&mut self) -> Vec<(PublicKey, DlcMessage)> {
+        std::mem::take(&mut self.pending_multi_party_messages)
+    }
+
+    /// Offers to transfer our side of the confirmed contract with the given
+    /// id to the given transferee, in exchange for `price` paid to us by the
+    /// transferee. The fees of the novation transaction are also paid by the
+    /// transferee at the given fee rate, estimated if zero. Returns the
+    /// message to send to the transferee.
+    pub fn send_novation_offer(
+        &mut self,
+        contract_id: &ContractId,
+        transferee: &PublicKey,
+        price: u64,
+        fee_rate_per_vb: u64,
+    ) -> Result<NovationOffer, Error> {
+        let contract =
+            get_contract_in_state!(self, contract_id, Confirmed, None as Option<PublicKey>)?;
+        self.check_no_active_novation(contract_id)?;
+        let fee_rate_per_vb = if fee_rate_per_vb == 0 {
+            self.blockchain.get_fee_rate(OFFER_CONFIRMATION_TARGET)?
+        } else {
+            fee_rate_per_vb
+        };
+
+        let (novation, offer) = crate::novation_updater::offer_novation(
+            &contract,
+            transferee,
+            price,
+            fee_rate_per_vb,
+            &self.wallet,
+        )?;
+
+        self.store.upsert_novation_contract(&novation)?;
+
+        Ok(offer)
+    }
+
+    /// Accepts the offer to take over a side of the contract with the given
+    /// id, returning the node id of the transferor and the message to send to
+    /// it.
+    pub fn accept_novation_offer(
+        &mut self,
+        contract_id: &ContractId,
+    ) -> Result<(PublicKey, NovationAccept), Error> {
+        let novation = self.get_novation_contract(contract_id)?;
+        let (novation, accept_msg) = crate::novation_updater::accept_novation(
+            &self.secp,
+            &novation,
+            &self.wallet,
+            &self.signer,
+            &self.blockchain,
+        )?;
+
+        let outpoints = crate::conversion_utils::get_tx_input_infos(&accept_msg.funding_inputs)?
+            .0
+            .iter()
+            .map(|x| x.outpoint)
+            .collect::<Vec<_>>();
+        self.wallet
+            .reserve_utxos(contract_id, &outpoints, self.utxo_reservation_ttl)?;
+        self.release_utxos_on_error(contract_id, self.store.upsert_novation_contract(&novation))?;
+
+        let transferor = novation
+            .transferor
+            .expect("to have a transferor as transferee");
+        Ok((transferor, accept_msg))
+    }
+
+    /// Returns the messages that need to be sent to the parties of contract
+    /// transfers we are involved in, produced since the last call.
+    pub fn get_and_clear_pending_novation_messages(&mut self) -> Vec<(PublicKey, DlcMessage)> {
+        std::mem::take(&mut self.pending_novation_messages)
+    }
+
+    /// Function to call to check the state of the currently executing DLCs and
+    /// update them if possible.
+    pub fn periodic_check(&mut self) -> Result<(), Error> {
+        // Checking contracts against an unavailable blockchain would only
+        // produce errors for each of them.
+        if self.blockchain.get_circuit_state() == CircuitState::Open {
+            return Err(Error::ServiceUnavailable(
+                "The blockchain provider is unavailable".to_string(),
+            ));
+        }
+        if let Err(e) = self.check_blockchain_sync() {
+            warn!("{}", e);
+        }
+        self.check_signed_contracts()?;
+        self.check_confirmed_contracts()?;
+        self.check_preclosed_contracts()?;
+        self.check_novation_contracts()?;
+        self.check_multi_party_contracts()?;
+        self.channel_checks()?;
+
+        Ok(())
+    }
+
+    /// Updates the contracts and channels affected by the given notification
+    /// pushed by the blockchain provider. Confirmations only change when
+    /// blocks are connected, so processing notifications can replace the
+    /// chain related part of [`Manager::periodic_check`], which is still
+    /// required to close contracts once their oracle events have matured.
+    pub fn process_chain_notification(
+        &mut self,
+        notification: &ChainNotification,
+    ) -> Result<(), Error> {
+        match notification {
+            ChainNotification::BlockConnected { .. } => {
+                self.check_signed_contracts()?;
+                self.check_preclosed_contracts()?;
+                self.channel_checks()
+            }
+            ChainNotification::TransactionConfirmed { txid, .. } => {
+                self.for_each_contract(ContractState::Signed, |manager, contract| {
+                    if let Contract::Signed(c) = contract {
+                        if c.accepted_contract.dlc_transactions.fund.txid() != *txid {
+                            return;
+                        }
+                        if let Err(e) = manager.check_signed_contract(&c) {
+                            error!(
+                                "Error checking signed contract {}: {}",
+                                c.accepted_contract.get_contract_id_string(),
+                                e
+                            )
+                        }
+                    }
+                })?;
+                self.for_each_contract(ContractState::PreClosed, |manager, contract| {
+                    if let Contract::PreClosed(c) = contract {
+                        if c.signed_cet.txid() != *txid {
+                            return;
+                        }
+                        if let Err(e) = manager.check_preclosed_contract(&c) {
+                            error!(
+                                "Error checking pre-closed contract {}: {}",
+                                c.signed_contract.accepted_contract.get_contract_id_string(),
+                                e
+                            )
+                        }
+                    }
+                })
+            }
+            ChainNotification::OutpointSpent {
+                outpoint,
+                spending_txid,
+            } => {
+                self.check_for_mempool_tx(Some(outpoint))?;
+                self.for_each_contract(ContractState::Confirmed, |manager, contract| {
+                    if let Contract::Confirmed(c) = contract {
+                        let dlc_transactions = &c.accepted_contract.dlc_transactions;
+                        let fund_outpoint = OutPoint::new(
+                            dlc_transactions.fund.txid(),
+                            dlc_transactions.get_fund_output_index() as u32,
+                        );
+                        if c.channel_id.is_some() || fund_outpoint != *outpoint {
+                            return;
+                        }
+                        warn!(
+                            "Fund output of contract {} spent by {}",
+                            c.accepted_contract.get_contract_id_string(),
+                            spending_txid
+                        );
+                        if let Err(e) = manager.check_confirmed_contract(&c) {
+                            error!(
+                                "Error checking confirmed contract {}: {}",
+                                c.accepted_contract.get_contract_id_string(),
+                                e
+                            )
+                        }
+                    }
+                })
+            }
+        }
+    }
+
+    /// Replaces the contracts in a final state that are older than allowed by
+    /// the given policy with an archival record in the storage, returning the
+    /// created records.
+    pub fn prune_contracts(
+        &self,
+        policy: &RetentionPolicy,
+    ) -> Result<Vec<ArchivedContract>, Error> {
+        let max_age = policy.max_age_days.saturating_mul(86400);
+        let now = self.time.unix_time_now();
+        let mut archived = Vec::new();
+        for state in FINAL_CONTRACT_STATES {
+            let mut expired = Vec::new();
+            for contract in self.store.iter_contracts(Some(state), CONTRACT_PAGE_SIZE) {
+                if let Some(archive) = ArchivedContract::from_contract(&contract?) {
+                    if archive.final_time.saturating_add(max_age) <= now {
+                        expired.push(archive);
+                    }
+                }
+            }
+            for chunk in expired.chunks(CONTRACT_PAGE_SIZE) {
+                self.store.archive_contracts(chunk)?;
+            }
+            archived.append(&mut expired);
+        }
+        Ok(archived)
+    }
+
+    fn on_offer_message(
+        &mut self,
+        offered_message: &OfferDlc,
+        counter_party: PublicKey,
+    ) -> Result<(), Error> {
+        if self.verify_announcements {
+            offered_message.validate(&self.secp, REFUND_DELAY, REFUND_DELAY * 2)?;
+        } else {
+            offered_message.validate_without_announcements(REFUND_DELAY, REFUND_DELAY * 2)?;
+        }
+        check_peer_features(&offered_message.features)?;
+        if let Some(offer_signature) = &offered_message.signature {
+            offered_message.verify_signature(&self.secp, &offer_signature.node_id)?;
+        }
+        crate::contract_updater::verify_ownership_proofs(
+            &self.secp,
+            &offered_message.temporary_contract_id,
+            &offered_message.funding_inputs,
+            &offered_message.ownership_proofs,
+        )?;
+        let mut contract: OfferedContract =
+            OfferedContract::try_from_offer_dlc(offered_message, counter_party)?;
+        contract.validate()?;
+        contract.check_hyperbola_feature(&offered_message.features)?;
+        self.check_oracle_trust(&contract)?;
+        self.fee_rate_policy
+            .check_fee_rate(contract.fee_rate_per_vb, &self.blockchain)?;
+
+        if let Some(rollover) = &offered_message.rollover {
+            let rolled_over = get_contract_in_state!(
+                self,
+                &rollover.contract_id,
+                Confirmed,
+                Some(counter_party)
+            )?;
+            crate::contract_updater::apply_rollover(&mut contract, &rolled_over, rollover)?;
+        }
+
+        if self.store.get_contract(&contract.id)?.is_some() {
+            return Err(Error::InvalidParameters(
+                "Contract with identical id already exists".to_string(),
+            ));
+        }
+
+        self.store.create_contract(&contract)?;
+
+        Ok(())
+    }
+
+    fn check_rate_limits(&self, msg: &DlcMessage, counter_party: &PublicKey) -> Result<(), Error> {
+        let store = &self.store;
+        self.rate_limiter.lock().unwrap().check_message(
+            counter_party,
+            msg,
+            self.time.unix_time_now(),
+            || get_nb_pending_handshakes(store, counter_party),
+        )
+    }
+
+    fn on_accept_message(
+        &self,
+        accept_msg: &AcceptDlc,
+        counter_party: &PublicKey,
+    ) -> Result<Option<DlcMessage>, Error> {
+        let _lock = self.contract_locks.lock(&accept_msg.temporary_contract_id);
+        let offered_contract = get_contract_in_state!(
+            self,
+            &accept_msg.temporary_contract_id,
+            Offered,
+            Some(*counter_party)
+        )?;
+
+        if self.requires_funding_psbt(&offered_contract.funding_inputs_info)
+            && offered_contract.shared_funding_input.is_none()
+        {
+            let accepted_contract =
+                match verify_accepted_contract(&self.secp, &offered_contract, accept_msg) {
+                    Ok(contract) => contract,
+                    Err(e) => {
+                        return self.accept_fail_on_error(offered_contract, accept_msg.clone(), e)
+                    }
+                };
+
+            self.wallet.import_address(&Address::p2wsh(
+                &accepted_contract.dlc_transactions.funding_script_pubkey,
+                self.blockchain.get_network()?,
+            ))?;
+
+            self.reserve_utxos(&offered_contract.id, &offered_contract.offer_params)?;
+
+            self.store
+                .update_contract(&Contract::Accepted(accepted_contract))?;
+
+            return Ok(None);
+        }
+
+        let (signed_contract, signed_msg) = match verify_accepted_and_sign_contract(
+            &self.secp,
+            &offered_contract,
+            accept_msg,
+            &self.signer,
+            self.funding_sighash_type,
+        ) {
+            Ok(contract) => contract,
+            Err(e) => return self.accept_fail_on_error(offered_contract, accept_msg.clone(), e),
+        };
+
+        self.wallet.import_address(&Address::p2wsh(
+            &signed_contract
+                .accepted_contract
+                .dlc_transactions
+                .funding_script_pubkey,
+            self.blockchain.get_network()?,
+        ))?;
+
+        self.store
+            .update_contract(&Contract::Signed(signed_contract))?;
+
+        Ok(Some(DlcMessage::Sign(signed_msg)))
+    }
+
+    fn on_sign_message(&self, sign_message: &SignDlc, peer_id: &PublicKey) -> Result<(), Error> {
+        let _lock = self.contract_locks.lock(&sign_message.contract_id);
+        let accepted_contract =
+            get_contract_in_state!(self, &sign_message.contract_id, Accepted, Some(*peer_id))?;
+
+        if accepted_contract.offered_contract.is_offer_party {
+            return Err(Error::InvalidState(
+                "Received sign message for a contract offered by us.".to_string(),
+            ));
+        }
+
+        if self.requires_funding_psbt(&accepted_contract.funding_inputs) {
+            let signed_contract = match crate::contract_updater::verify_signed_contract_for_psbt(
+                &self.secp,
+                &accepted_contract,
+                sign_message,
+                &self.signer,
+                self.funding_sighash_type,
+            ) {
+                Ok(contract) => contract,
+                Err(e) => {
+                    return self.sign_fail_on_error(accepted_contract, sign_message.clone(), e)
+                }
+            };
+
+            self.reserve_utxos(
+                &signed_contract.accepted_contract.offered_contract.id,
+                &signed_contract.accepted_contract.accept_params,
+            )?;
+
+            self.store
+                .update_contract(&Contract::Signed(signed_contract))?;
+
+            return Ok(());
+        }
+
+        let (signed_contract, fund_tx) = match crate::contract_updater::verify_signed_contract(
+            &self.secp,
+            &accepted_contract,
+            sign_message,
+            &self.signer,
+            self.funding_sighash_type,
+        ) {
+            Ok(contract) => contract,
+            Err(e) => return self.sign_fail_on_error(accepted_contract, sign_message.clone(), e),
+        };
+
+        self.store
+            .update_contract(&Contract::Signed(signed_contract))?;
+
+        self.blockchain.send_transaction(&fund_tx)?;
+
+        Ok(())
+    }
+
+    fn get_multi_party_contract(
+        &self,
+        contract_id: &ContractId,
+    ) -> Result<MultiPartyContract, Error> {
+        self.store
+            .get_multi_party_contract(contract_id)?
+            .ok_or_else(|| Error::InvalidParameters("Unknown multi party contract id.".to_string()))
+    }
+
+    /// Returns the contract with the given id along with the index of the
+    /// given peer among its parties.
+    fn get_multi_party_contract_for_peer(
+        &self,
+        contract_id: &ContractId,
+        peer_id: &PublicKey,
+    ) -> Result<(MultiPartyContract, usize), Error> {
+        let contract = self.get_multi_party_contract(contract_id)?;
+        let party_index = contract.get_party_index(peer_id).ok_or_else(|| {
+            Error::InvalidParameters(format!(
+                "Peer {:02x?} is not involved with contract {:02x?}.",
+                peer_id, contract_id
+            ))
+        })?;
+        Ok((contract, party_index))
+    }
+
+    /// Returns the contract with the given id if the given peer is its
+    /// coordinator.
+    fn get_multi_party_contract_from_coordinator(
+        &self,
+        contract_id: &ContractId,
+        peer_id: &PublicKey,
+    ) -> Result<MultiPartyContract, Error> {
+        match self.get_multi_party_contract_for_peer(contract_id, peer_id)? {
+            (contract, 0) => Ok(contract),
+            _ => Err(Error::InvalidParameters(format!(
+                "Peer {:02x?} is not the coordinator of contract {:02x?}.",
+                peer_id, contract_id
+            ))),
+        }
+    }
+
+    fn reserve_multi_party_utxos(&self, contract: &MultiPartyContract) -> Result<(), Error> {
+        let own_params = contract.party_params[contract.own_index()]
+            .as_ref()
+            .expect("to have our own parameters");
+        let outpoints = crate::conversion_utils::get_tx_input_infos(&own_params.funding_inputs)?
+            .0
+            .iter()
+            .map(|x| x.outpoint)
+            .collect::<Vec<_>>();
+        self.wallet.reserve_utxos(
+            &contract.temporary_id,
+            &outpoints,
+            self.utxo_reservation_ttl,
+        )
+    }
+
+    /// Queues the given message to be sent to every participant of the given
+    /// contract.
+    fn queue_multi_party_message(&mut self, contract: &MultiPartyContract, msg: DlcMessage) {
+        for participant in &contract.offer.participants {
+            self.pending_multi_party_messages
+                .push((*participant, msg.clone()));
+        }
+    }
+
+    fn on_multi_party_offer(
+        &mut self,
+        offer: &MultiPartyOffer,
+        counter_party: PublicKey,
+    ) -> Result<(), Error> {
+        offer.validate(REFUND_DELAY, REFUND_DELAY * 2)?;
+        if offer.party_index == 0 {
+            return Err(Error::InvalidParameters(
+                "Offer cannot be addressed to the coordinator.".to_string(),
+            ));
+        }
+        if self.verify_announcements {
+            offer.oracle_announcement.validate(&self.secp)?;
+        }
+        self.oracle_trust_config
+            .check_single_oracle(&offer.oracle_announcement.oracle_public_key)?;
+        self.fee_rate_policy
+            .check_fee_rate(offer.fee_rate_per_vb, &self.blockchain)?;
+
+        if self
+            .store
+            .get_multi_party_contract(&offer.temporary_contract_id)?
+            .is_some()
+        {
+            return Err(Error::InvalidParameters(
+                "Contract with identical id already exists".to_string(),
+            ));
+        }
+
+        let contract = MultiPartyContract::new(offer.clone(), Some(counter_party));
+        self.store.upsert_multi_party_contract(&contract)
+    }
+
+    fn on_multi_party_accept(
+        &mut self,
+        accept_msg: &MultiPartyAccept,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let (contract, party_index) = self
+            .get_multi_party_contract_for_peer(&accept_msg.temporary_contract_id, counter_party)?;
+
+        let (contract, setup_msg) = crate::multi_party_updater::on_accept(
+            &self.secp,
+            &contract,
+            accept_msg,
+            party_index,
+            &self.signer,
+        )?;
+
+        self.store.upsert_multi_party_contract(&contract)?;
+
+        if let Some(setup_msg) = setup_msg {
+            self.queue_multi_party_message(&contract, DlcMessage::MultiPartySetup(setup_msg));
+        }
+
+        Ok(())
+    }
+
+    fn on_multi_party_setup(
+        &mut self,
+        setup_msg: &MultiPartySetup,
+        counter_party: &PublicKey,
+    ) -> Result<MultiPartySign, Error> {
+        let contract = self.get_multi_party_contract_from_coordinator(
+            &setup_msg.temporary_contract_id,
+            counter_party,
+        )?;
+
+        let (contract, sign_msg) =
+            crate::multi_party_updater::on_setup(&self.secp, &contract, setup_msg, &self.signer)?;
+
+        self.store.upsert_multi_party_contract(&contract)?;
+
+        Ok(sign_msg)
+    }
+
+    fn on_multi_party_sign(
+        &mut self,
+        sign_msg: &MultiPartySign,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let (contract, party_index) =
+            self.get_multi_party_contract_for_peer(&sign_msg.contract_id, counter_party)?;
+
+        let (contract, finalize_msg) =
+            crate::multi_party_updater::on_sign(&self.secp, &contract, sign_msg, party_index)?;
+
+        self.store.upsert_multi_party_contract(&contract)?;
+
+        if let Some(finalize_msg) = finalize_msg {
+            self.queue_multi_party_message(&contract, DlcMessage::MultiPartyFinalize(finalize_msg));
+        }
+
+        Ok(())
+    }
+
+    fn on_multi_party_finalize(
+        &mut self,
+        finalize_msg: &MultiPartyFinalize,
+        counter_party: &PublicKey,
+    ) -> Result<MultiPartyFunding, Error> {
+        let contract = self
+            .get_multi_party_contract_from_coordinator(&finalize_msg.contract_id, counter_party)?;
+
+        let (contract, funding_msg) = crate::multi_party_updater::on_finalize(
+            &self.secp,
+            &contract,
+            finalize_msg,
+            &self.signer,
+        )?;
+
+        self.wallet.import_address(&Address::p2wsh(
+            &contract.get_dlc_transactions()?.funding_script_pubkey,
+            self.blockchain.get_network()?,
+        ))?;
+
+        self.store.upsert_multi_party_contract(&contract)?;
+
+        Ok(funding_msg)
+    }
+
+    fn on_multi_party_funding(
+        &mut self,
+        funding_msg: &MultiPartyFunding,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let (contract, party_index) =
+            self.get_multi_party_contract_for_peer(&funding_msg.contract_id, counter_party)?;
+
+        let (contract, fund_tx) = crate::multi_party_updater::on_funding(
+            &contract,
+            funding_msg,
+            party_index,
+            &self.signer,
+        )?;
+
+        if fund_tx.is_some() {
+            self.wallet.import_address(&Address::p2wsh(
+                &contract.get_dlc_transactions()?.funding_script_pubkey,
+                self.blockchain.get_network()?,
+            ))?;
+        }
+
+        self.store.upsert_multi_party_contract(&contract)?;
+
+        if let Some(fund_tx) = fund_tx {
+            self.blockchain.send_transaction(&fund_tx)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_multi_party_contracts(&mut self) -> Result<(), Error> {
+        for contract in self.store.get_multi_party_contracts()? {
+            let res = match contract.state {
+                // Participants are not notified of the broadcast of the fund
+                // transaction by the coordinator.
+                MultiPartyContractState::Signed | MultiPartyContractState::Broadcast => {
+                    self.check_broadcast_multi_party_contract(&contract)
+                }
+                MultiPartyContractState::Confirmed => {
+                    self.check_confirmed_multi_party_contract(&contract)
+                }
+                _ => continue,
+            };
+            if let Err(e) = res {
+                error!(
+                    "Error checking multi party contract {:02x?}: {}",
+                    contract.id, e
+                )
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_broadcast_multi_party_contract(
+        &mut self,
+        contract: &MultiPartyContract,
+    ) -> Result<(), Error> {
+        let fund_txid = contract.get_dlc_transactions()?.fund.txid();
+        if self.blockchain.get_transaction_confirmations(&fund_txid)? >= NB_CONFIRMATIONS {
+            let mut contract = contract.clone();
+            contract.state = MultiPartyContractState::Confirmed;
+            self.store.upsert_multi_party_contract(&contract)?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes the contract with the CET of the attested outcome once the
+    /// oracle attestation is available, or refunds it once the refund
+    /// locktime has passed.
+    fn check_confirmed_multi_party_contract(
+        &mut self,
+        contract: &MultiPartyContract,
+    ) -> Result<(), Error> {
+        let announcement = &contract.offer.oracle_announcement;
+        let attestation = if (announcement.oracle_event.event_maturity_epoch as u64)
+            <= self.time.unix_time_now()
+        {
+            self.get_oracle_attestation(announcement)
+        } else {
+            None
+        };
+
+        let (contract, closing_tx) = match attestation {
+            Some(attestation) => crate::multi_party_updater::close_contract(
+                &self.secp,
+                contract,
+                &attestation,
+                &self.signer,
+            )?,
+            None => {
+                let lock_time = contract.offer.refund_locktime;
+                if (lock_time as u64) > self.time.unix_time_now()
+                    || !self.is_lock_time_reached(lock_time)?
+                {
+                    return Ok(());
+                }
+                crate::multi_party_updater::refund_contract(&self.secp, contract, &self.signer)?
+            }
+        };
+
+        // Another party may already have broadcast the same transaction.
+        if self
+            .blockchain
+            .get_transaction_confirmations(&closing_tx.txid())?
+            == 0
+        {
+            self.blockchain.send_transaction(&closing_tx)?;
+        }
+
+        self.store.upsert_multi_party_contract(&contract)
+    }
+
+    fn get_novation_contract(&self, contract_id: &ContractId) -> Result<NovationContract, Error> {
+        self.store
+            .get_novation_contract(contract_id)?
+            .ok_or_else(|| Error::InvalidParameters("Unknown novation contract id.".to_string()))
+    }
+
+    /// Returns the transfer of the contract with the given id if the given
+    /// peer has the given role in it.
+    fn get_novation_contract_for_peer(
+        &self,
+        contract_id: &ContractId,
+        peer_id: &PublicKey,
+        role: NovationRole,
+    ) -> Result<NovationContract, Error> {
+        let novation = self.get_novation_contract(contract_id)?;
+        let expected_peer = match role {
+            NovationRole::Transferor => novation.transferor,
+            NovationRole::RemainingParty => novation.remaining_party,
+            NovationRole::Transferee => novation.transferee,
+        };
+        if expected_peer != Some(*peer_id) {
+            return Err(Error::InvalidParameters(format!(
+                "Peer {:02x?} is not the {:?} of contract {:02x?}.",
+                peer_id, role, contract_id
+            )));
+        }
+        Ok(novation)
+    }
+
+    /// Returns an error if the contract with the given id is being
+    /// transferred.
+    fn check_no_active_novation(&self, contract_id: &ContractId) -> Result<(), Error> {
+        match self.store.get_novation_contract(contract_id)? {
+            Some(n) if n.state != NovationState::Completed => Err(Error::InvalidState(
+                "The contract is already being transferred.".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn on_novation_offer(
+        &mut self,
+        offer: &NovationOffer,
+        counter_party: PublicKey,
+    ) -> Result<(), Error> {
+        if self
+            .store
+            .get_novation_contract(&offer.contract_id)?
+            .is_some()
+        {
+            return Err(Error::InvalidParameters(
+                "Novation with identical contract id already exists".to_string(),
+            ));
+        }
+        if self.verify_announcements {
+            offer.contract_info.validate_announcements(&self.secp)?;
+        }
+        for contract_info in
+            crate::conversion_utils::get_contract_info_and_announcements(&offer.contract_info)?
+        {
+            self.oracle_trust_config
+                .check_contract_info(&contract_info)?;
+        }
+        self.fee_rate_policy
+            .check_fee_rate(offer.terms.fee_rate_per_vb, &self.blockchain)?;
+
+        let novation =
+            crate::novation_updater::on_novation_offer(offer, counter_party, &self.blockchain)?;
+        self.store.upsert_novation_contract(&novation)
+    }
+
+    fn on_novation_accept(
+        &mut self,
+        accept_msg: &NovationAccept,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let novation = self.get_novation_contract_for_peer(
+            &accept_msg.contract_id,
+            counter_party,
+            NovationRole::Transferee,
+        )?;
+
+        let (novation, request) =
+            crate::novation_updater::on_novation_accept(&novation, accept_msg)?;
+
+        self.store.upsert_novation_contract(&novation)?;
+
+        let remaining_party = novation
+            .remaining_party
+            .expect("to have a remaining party as transferor");
+        self.pending_novation_messages
+            .push((remaining_party, DlcMessage::NovationRequest(request)));
+
+        Ok(())
+    }
+
+    fn on_novation_request(
+        &mut self,
+        request: &NovationRequest,
+        counter_party: &PublicKey,
+    ) -> Result<NovationConfirm, Error> {
+        let contract =
+            get_contract_in_state!(self, &request.contract_id, Confirmed, Some(*counter_party))?;
+        self.check_no_active_novation(&request.contract_id)?;
+
+        let (novation, confirm_msg) = crate::novation_updater::on_novation_request(
+            &self.secp,
+            &contract,
+            request,
+            &self.signer,
+        )?;
+
+        self.wallet.import_address(&Address::p2wsh(
+            &novation
+                .novated_contract
+                .as_ref()
+                .expect("to have a novated contract")
+                .accepted_contract
+                .dlc_transactions
+                .funding_script_pubkey,
+            self.blockchain.get_network()?,
+        ))?;
+
+        self.store.upsert_novation_contract(&novation)?;
+
+        Ok(confirm_msg)
+    }
+
+    fn on_novation_confirm(
+        &mut self,
+        confirm_msg: &NovationConfirm,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let novation = self.get_novation_contract_for_peer(
+            &confirm_msg.contract_id,
+            counter_party,
+            NovationRole::RemainingParty,
+        )?;
+
+        let (novation, finalize_msg) = crate::novation_updater::on_novation_confirm(
+            &self.secp,
+            &novation,
+            confirm_msg,
+            &self.signer,
+        )?;
+
+        self.store.upsert_novation_contract(&novation)?;
+
+        let transferee = novation
+            .transferee
+            .expect("to have a transferee as transferor");
+        self.pending_novation_messages
+            .push((transferee, DlcMessage::NovationFinalize(finalize_msg)));
+
+        Ok(())
+    }
+
+    fn on_novation_finalize(
+        &mut self,
+        finalize_msg: &NovationFinalize,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let novation = self.get_novation_contract_for_peer(
+            &finalize_msg.contract_id,
+            counter_party,
+            NovationRole::Transferor,
+        )?;
+
+        let (novation, novation_tx) = crate::novation_updater::on_novation_finalize(
+            &self.secp,
+            &novation,
+            finalize_msg,
+            &self.signer,
+        )?;
+        let novated_contract = novation
+            .novated_contract
+            .clone()
+            .expect("to have a novated contract");
+
+        self.wallet.import_address(&Address::p2wsh(
+            &novated_contract
+                .accepted_contract
+                .dlc_transactions
+                .funding_script_pubkey,
+            self.blockchain.get_network()?,
+        ))?;
+
+        self.store.upsert_novation_contract(&novation)?;
+        self.store
+            .update_contract(&Contract::Signed(novated_contract))?;
+
+        self.blockchain.send_transaction(&novation_tx)?;
+
+        Ok(())
+    }
+
+    fn check_novation_contracts(&mut self) -> Result<(), Error> {
+        for novation in self.store.get_novation_contracts()? {
+            let is_pending = match novation.state {
+                NovationState::Confirmed => novation.role() != NovationRole::Transferee,
+                NovationState::Broadcast => true,
+                _ => false,
+            };
+            if !is_pending {
+                continue;
+            }
+            if let Err(e) = self.check_novation_contract(&novation) {
+                error!(
+                    "Error checking novation of contract {:02x?}: {}",
+                    novation.contract_id, e
+                )
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the transferred contract once the novation transaction is
+    /// confirmed.
+    fn check_novation_contract(&mut self, novation: &NovationContract) -> Result<(), Error> {
+        let novation_txid = novation
+            .novation_transaction
+            .as_ref()
+            .ok_or_else(|| Error::InvalidState("Missing novation transaction.".to_string()))?
+            .txid();
+        let confirmations = self
+            .blockchain
+            .get_transaction_confirmations(&novation_txid)?;
+        if confirmations < NB_CONFIRMATIONS {
+            return Ok(());
+        }
+
+        match novation.role() {
+            NovationRole::Transferor => {
+                let contract = get_contract_in_state!(
+                    self,
+                    &novation.contract_id,
+                    Confirmed,
+                    None as Option<PublicKey>
+                )?;
+                let own_collateral = novation.offer.transferor_collateral;
+                self.close_spent_contract(
+                    &contract,
+                    novation.offer.terms.price as i64 - own_collateral as i64,
+                )?;
+            }
+            NovationRole::RemainingParty => {
+                let contract = get_contract_in_state!(
+                    self,
+                    &novation.contract_id,
+                    Confirmed,
+                    None as Option<PublicKey>
+                )?;
+                let novated_contract = novation
+                    .novated_contract
+                    .clone()
+                    .ok_or_else(|| Error::InvalidState("Missing novated contract.".to_string()))?;
+                self.store
+                    .update_contract(&Contract::Confirmed(novated_contract))?;
+                self.close_spent_contract(&contract, 0)?;
+            }
+            // The novated contract of the transferee is confirmed by
+            // `check_signed_contracts`.
+            NovationRole::Transferee => {}
+        }
+
+        let mut novation = novation.clone();
+        novation.state = NovationState::Completed;
+        self.store.upsert_novation_contract(&novation)
+    }
+
+    /// Closes the given contract, whose fund output was spent cooperatively
+    /// by a transaction other than a CET, refund or close transaction.
+    fn close_spent_contract(&mut self, contract: &SignedContract, pnl: i64) -> Result<(), Error> {
+        let closed_contract = self.get_spent_contract_closure(contract, pnl);
+        self.store.update_contract(&closed_contract)
+    }
+
+    /// Returns the closed contract for the given contract, whose fund output
+    /// was spent cooperatively, without persisting it.
+    fn get_spent_contract_closure(&self, contract: &SignedContract, pnl: i64) -> Contract {
+        Contract::Closed(ClosedContract {
+            attestations: None,
+            signed_cet: None,
+            contract_id: contract.accepted_contract.get_contract_id(),
+            temporary_contract_id: contract.accepted_contract.offered_contract.id,
+            counter_party_id: contract.accepted_contract.offered_contract.counter_party,
+            pnl,
+            closed_at: self.time.unix_time_now(),
+        })
+    }
+
+    fn check_oracle_trust(&self, contract: &OfferedContract) -> Result<(), Error> {
+        for contract_info in &contract.contract_info {
+            self.oracle_trust_config
+                .check_contract_info(contract_info)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the given contract input, with the fee rate estimated by the
+    /// blockchain provider if none was set.
+    fn with_fee_rate<'a>(
+        &self,
+        contract_input: &'a ContractInput,
+    ) -> Result<Cow<'a, ContractInput>, Error> {
+        if contract_input.fee_rate != 0 {
+            return Ok(Cow::Borrowed(contract_input));
+        }
+        let mut contract_input = contract_input.clone();
+        contract_input.fee_rate = self.blockchain.get_fee_rate(OFFER_CONFIRMATION_TARGET)?;
+        Ok(Cow::Owned(contract_input))
     }
 
     fn get_oracle_announcements(
@@ -427,24 +2166,40 @@ where
         oracle_inputs: &OracleInput,
     ) -> Result<Vec<OracleAnnouncement>, Error> {
         let mut announcements = Vec::new();
-        for pubkey in &oracle_inputs.public_keys {
+        for (i, pubkey) in oracle_inputs.public_keys.iter().enumerate() {
             let oracle = self
                 .oracles
                 .get(pubkey)
                 .ok_or_else(|| Error::InvalidParameters("Unknown oracle public key".to_string()))?;
-            announcements.push(oracle.get_announcement(&oracle_inputs.event_id)?.clone());
+            let event_id = oracle_inputs.get_event_id(i);
+            if let Some(announcement) = self.store.get_oracle_announcement(pubkey, event_id)? {
+                announcements.push(announcement);
+                continue;
+            }
+            let announcement = oracle.get_announcement(event_id)?;
+            if self.verify_announcements {
+                announcement.validate(&self.secp).map_err(|e| {
+                    Error::OracleError(format!(
+                        "Invalid announcement for event {}: {}",
+                        event_id, e
+                    ))
+                })?;
+            }
+            self.store.persist_oracle_announcement(&announcement)?;
+            announcements.push(announcement);
         }
 
         Ok(announcements)
     }
 
     fn sign_fail_on_error<R>(
-        &mut self,
+        &self,
         accepted_contract: AcceptedContract,
         sign_message: SignDlc,
         e: Error,
     ) -> Result<R, Error> {
         error!("Error in on_sign {}", e);
+        self.release_utxos(&accepted_contract.offered_contract.id);
         self.store
             .update_contract(&Contract::FailedSign(FailedSignContract {
                 accepted_contract,
@@ -455,12 +2210,13 @@ where
     }
 
     fn accept_fail_on_error<R>(
-        &mut self,
+        &self,
         offered_contract: OfferedContract,
         accept_message: AcceptDlc,
         e: Error,
     ) -> Result<R, Error> {
         error!("Error in on_accept {}", e);
+        self.release_utxos(&offered_contract.id);
         self.store
             .update_contract(&Contract::FailedAccept(FailedAcceptContract {
                 offered_contract,
@@ -475,42 +2231,134 @@ where
             &contract.accepted_contract.dlc_transactions.fund.txid(),
         )?;
         if confirmations >= NB_CONFIRMATIONS {
-            self.store
-                .update_contract(&Contract::Confirmed(contract.clone()))?;
+            // The rolled over contracts are closed in the same batch as the
+            // new contract is confirmed, so that they cannot remain confirmed
+            // if the process stops in between.
+            let closed_contracts = if contract
+                .accepted_contract
+                .offered_contract
+                .shared_funding_input
+                .is_some()
+            {
+                self.get_rolled_over_contract_closures(contract)?
+            } else {
+                Vec::new()
+            };
+            let confirmed_contract = Contract::Confirmed(contract.clone());
+            let mut writes = vec![StorageWrite::Contract(&confirmed_contract)];
+            writes.extend(closed_contracts.iter().map(StorageWrite::Contract));
+            self.store.write_batch(&writes)?;
         }
         Ok(())
     }
 
-    fn check_signed_contracts(&mut self) -> Result<(), Error> {
-        for c in self.store.get_signed_contracts()? {
-            if let Err(e) = self.check_signed_contract(&c) {
-                error!(
-                    "Error checking confirmed contract {}: {}",
-                    c.accepted_contract.get_contract_id_string(),
-                    e
-                )
+    /// Returns the closed contracts for the confirmed contracts whose fund
+    /// output is spent by the fund transaction of the given contract, which
+    /// rolled them over.
+    fn get_rolled_over_contract_closures(
+        &mut self,
+        contract: &SignedContract,
+    ) -> Result<Vec<Contract>, Error> {
+        let accepted_contract = &contract.accepted_contract;
+        let offered_contract = &accepted_contract.offered_contract;
+        let fund = &accepted_contract.dlc_transactions.fund;
+        let mut rolled_over = Vec::new();
+        self.for_each_contract(ContractState::Confirmed, |_, c| {
+            if let Contract::Confirmed(c) = c {
+                let dlc_transactions = &c.accepted_contract.dlc_transactions;
+                let fund_outpoint = OutPoint::new(
+                    dlc_transactions.fund.txid(),
+                    dlc_transactions.get_fund_output_index() as u32,
+                );
+                if fund
+                    .input
+                    .iter()
+                    .any(|x| x.previous_output == fund_outpoint)
+                {
+                    rolled_over.push(c);
+                }
             }
+        })?;
+
+        // The accepting party does not provide inputs, so its part of the
+        // funding inputs comes from the rolled over fund output, the offer
+        // party getting the rest of it.
+        let (_, accept_amount) =
+            crate::contract_updater::get_shared_funding_amounts(offered_contract)?;
+
+        let mut closed_contracts = Vec::with_capacity(rolled_over.len());
+        for c in rolled_over {
+            let fund_output_value = c.accepted_contract.dlc_transactions.get_fund_output().value;
+            let own_amount = if offered_contract.is_offer_party {
+                fund_output_value - accept_amount
+            } else {
+                accept_amount
+            };
+            let old_offered_contract = &c.accepted_contract.offered_contract;
+            let own_collateral = if old_offered_contract.is_offer_party {
+                old_offered_contract.offer_params.collateral
+            } else {
+                c.accepted_contract.accept_params.collateral
+            };
+            closed_contracts.push(
+                self.get_spent_contract_closure(&c, own_amount as i64 - own_collateral as i64),
+            );
         }
 
-        Ok(())
+        Ok(closed_contracts)
     }
 
-    fn check_confirmed_contracts(&mut self) -> Result<(), Error> {
-        for c in self.store.get_confirmed_contracts()? {
-            // Confirmed contracts from channel are processed in channel specific methods.
-            if c.channel_id.is_some() {
-                continue;
+    /// Calls `f` on each contract in the given state, retrieving them from
+    /// the storage one page at a time so that they are not all held in memory.
+    fn for_each_contract<F>(&mut self, state: ContractState, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut Self, Contract),
+    {
+        let mut cursor = None;
+        loop {
+            let page = self
+                .store
+                .get_contracts_page(Some(state), cursor, CONTRACT_PAGE_SIZE)?;
+            for contract in page.contracts {
+                f(self, contract);
             }
-            if let Err(e) = self.check_confirmed_contract(&c) {
-                error!(
-                    "Error checking confirmed contract {}: {}",
-                    c.accepted_contract.get_contract_id_string(),
-                    e
-                )
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => return Ok(()),
             }
         }
+    }
 
-        Ok(())
+    fn check_signed_contracts(&mut self) -> Result<(), Error> {
+        self.for_each_contract(ContractState::Signed, |manager, contract| {
+            if let Contract::Signed(c) = contract {
+                if let Err(e) = manager.check_signed_contract(&c) {
+                    error!(
+                        "Error checking confirmed contract {}: {}",
+                        c.accepted_contract.get_contract_id_string(),
+                        e
+                    )
+                }
+            }
+        })
+    }
+
+    fn check_confirmed_contracts(&mut self) -> Result<(), Error> {
+        self.for_each_contract(ContractState::Confirmed, |manager, contract| {
+            if let Contract::Confirmed(c) = contract {
+                // Confirmed contracts from channel are processed in channel specific methods.
+                if c.channel_id.is_some() {
+                    return;
+                }
+                if let Err(e) = manager.check_confirmed_contract(&c) {
+                    error!(
+                        "Error checking confirmed contract {}: {}",
+                        c.accepted_contract.get_contract_id_string(),
+                        e
+                    )
+                }
+            }
+        })
     }
 
     fn get_closable_contract_info<'a>(
@@ -520,25 +2368,21 @@ where
         let contract_infos = &contract.accepted_contract.offered_contract.contract_info;
         let adaptor_infos = &contract.accepted_contract.adaptor_infos;
         for (contract_info, adaptor_info) in contract_infos.iter().zip(adaptor_infos.iter()) {
+            // Oracles may attest to different events maturing at different
+            // times, so indexes are taken before filtering.
             let matured: Vec<_> = contract_info
                 .oracle_announcements
                 .iter()
-                .filter(|x| {
+                .enumerate()
+                .filter(|(_, x)| {
                     (x.oracle_event.event_maturity_epoch as u64) <= self.time.unix_time_now()
                 })
-                .enumerate()
                 .collect();
             if matured.len() >= contract_info.threshold {
                 let attestations: Vec<_> = matured
                     .iter()
                     .filter_map(|(i, announcement)| {
-                        let oracle = self.oracles.get(&announcement.oracle_public_key)?;
-                        Some((
-                            *i,
-                            oracle
-                                .get_attestation(&announcement.oracle_event.event_id)
-                                .ok()?,
-                        ))
+                        Some((*i, self.get_oracle_attestation(announcement)?))
                     })
                     .collect();
                 if attestations.len() >= contract_info.threshold {
@@ -549,6 +2393,38 @@ where
         None
     }
 
+    /// Returns the attestation for the given announcement, from the store if
+    /// it was previously retrieved, or from the oracle in which case it gets
+    /// persisted.
+    fn get_oracle_attestation(
+        &self,
+        announcement: &OracleAnnouncement,
+    ) -> Option<OracleAttestation> {
+        let public_key = &announcement.oracle_public_key;
+        let event_id = &announcement.oracle_event.event_id;
+        match self.store.get_oracle_attestation(public_key, event_id) {
+            Ok(Some(attestation)) => return Some(attestation),
+            Ok(None) => {}
+            Err(e) => warn!("Error reading attestation for event {}: {}", event_id, e),
+        }
+        let attestation = self
+            .oracles
+            .get(public_key)?
+            .get_attestation(event_id)
+            .ok()?;
+        if let Err(e) = attestation.validate(&self.secp, announcement) {
+            warn!("Invalid attestation for event {}: {}", event_id, e);
+            return None;
+        }
+        if let Err(e) = self
+            .store
+            .persist_oracle_attestation(event_id, &attestation)
+        {
+            warn!("Error persisting attestation for event {}: {}", event_id, e);
+        }
+        Some(attestation)
+    }
+
     fn check_confirmed_contract(&mut self, contract: &SignedContract) -> Result<(), Error> {
         let closable_contract_info = self.get_closable_contract_info(contract);
         if let Some((contract_info, adaptor_info, attestations)) = closable_contract_info {
@@ -558,7 +2434,7 @@ where
                 contract_info,
                 adaptor_info,
                 &attestations,
-                &self.wallet,
+                &self.signer,
             )?;
             match self.close_contract(
                 contract,
@@ -586,17 +2462,17 @@ where
     }
 
     fn check_preclosed_contracts(&mut self) -> Result<(), Error> {
-        for c in self.store.get_preclosed_contracts()? {
-            if let Err(e) = self.check_preclosed_contract(&c) {
-                error!(
-                    "Error checking pre-closed contract {}: {}",
-                    c.signed_contract.accepted_contract.get_contract_id_string(),
-                    e
-                )
+        self.for_each_contract(ContractState::PreClosed, |manager, contract| {
+            if let Contract::PreClosed(c) = contract {
+                if let Err(e) = manager.check_preclosed_contract(&c) {
+                    error!(
+                        "Error checking pre-closed contract {}: {}",
+                        c.signed_contract.accepted_contract.get_contract_id_string(),
+                        e
+                    )
+                }
             }
-        }
-
-        Ok(())
+        })
     }
 
     fn check_preclosed_contract(&mut self, contract: &PreClosedContract) -> Result<(), Error> {
@@ -623,6 +2499,7 @@ where
                     .signed_contract
                     .accepted_contract
                     .compute_pnl(&contract.signed_cet),
+                closed_at: self.time.unix_time_now(),
             };
             self.store
                 .update_contract(&Contract::Closed(closed_contract))?;
@@ -672,21 +2549,33 @@ where
             contract_id: contract.accepted_contract.get_contract_id(),
             temporary_contract_id: contract.accepted_contract.offered_contract.id,
             counter_party_id: contract.accepted_contract.offered_contract.counter_party,
+            closed_at: self.time.unix_time_now(),
         };
 
         Ok(Contract::Closed(closed_contract))
     }
 
+    /// Returns whether a transaction with the given lock time can be included
+    /// in the next block, according to the chain rather than the local clock.
+    fn is_lock_time_reached(&self, lock_time: u32) -> Result<bool, Error> {
+        let tip = self.blockchain.get_blockchain_tip()?;
+        if lock_time < LOCK_TIME_THRESHOLD {
+            return Ok(lock_time as u64 <= tip.height);
+        }
+        let start_height = tip.height.saturating_sub(MEDIAN_TIME_SPAN as u64);
+        let headers = self.blockchain.get_headers_since(start_height)?;
+        Ok(lock_time < get_median_time_past(&headers))
+    }
+
     fn check_refund(&mut self, contract: &SignedContract) -> Result<(), Error> {
         // TODO(tibo): should check for confirmation of refund before updating state
-        if contract
+        let lock_time = contract
             .accepted_contract
             .dlc_transactions
             .refund
             .lock_time
-            .0 as u64
-            <= self.time.unix_time_now()
-        {
+            .0;
+        if lock_time as u64 <= self.time.unix_time_now() && self.is_lock_time_reached(lock_time)? {
             let accepted_contract = &contract.accepted_contract;
             let refund = accepted_contract.dlc_transactions.refund.clone();
             let confirmations = self
@@ -694,7 +2583,7 @@ where
                 .get_transaction_confirmations(&refund.txid())?;
             if confirmations == 0 {
                 let refund =
-                    crate::contract_updater::get_signed_refund(&self.secp, contract, &self.wallet)?;
+                    crate::contract_updater::get_signed_refund(&self.secp, contract, &self.signer)?;
                 self.blockchain.send_transaction(&refund)?;
             }
 
@@ -706,7 +2595,8 @@ where
     }
 }
 
-impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, F: Deref> Manager<W, B, S, O, T, F>
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, F: Deref, K: Deref>
+    Manager<W, B, S, O, T, F, K>
 where
     W::Target: Wallet,
     B::Target: Blockchain,
@@ -714,6 +2604,7 @@ where
     O::Target: Oracle,
     T::Target: Time,
     F::Target: FeeEstimator,
+    K::Target: ContractSigner,
 {
     /// Create a new channel offer and return the [`dlc_messages::channel::OfferChannel`]
     /// message to be sent to the `counter_party`.
@@ -722,6 +2613,8 @@ where
         contract_input: &ContractInput,
         counter_party: PublicKey,
     ) -> Result<OfferChannel, Error> {
+        self.check_can_fund_channel()?;
+        let contract_input = self.with_fee_rate(contract_input)?;
         let oracle_announcements = contract_input
             .contract_infos
             .iter()
@@ -730,26 +2623,42 @@ where
 
         let (offered_channel, offered_contract) = crate::channel_updater::offer_channel(
             &self.secp,
-            contract_input,
+            &contract_input,
             &counter_party,
             &oracle_announcements,
             CET_NSEQUENCE,
             REFUND_DELAY,
             &self.wallet,
+            &self.signer,
             &self.blockchain,
             &self.time,
         )?;
 
         let msg = offered_channel.get_offer_channel_msg(&offered_contract);
+        let temporary_channel_id = offered_channel.temporary_channel_id;
 
-        self.store.upsert_channel(
+        self.reserve_utxos(&temporary_channel_id, &offered_contract.offer_params)?;
+        let res = self.store.upsert_channel(
             Channel::Offered(offered_channel),
             Some(Contract::Offered(offered_contract)),
-        )?;
+        );
+        self.release_utxos_on_error(&temporary_channel_id, res)?;
 
         Ok(msg)
     }
 
+    /// Channel funding inputs are always signed by the signer, as they cannot
+    /// be provided using PSBTs.
+    fn check_can_fund_channel(&self) -> Result<(), Error> {
+        if !self.signer.can_sign_funding_inputs() {
+            return Err(Error::InvalidParameters(
+                "Channels cannot be funded with a signer unable to sign funding inputs."
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     /// Accept a channel that was offered. Returns the [`dlc_messages::channel::AcceptChannel`]
     /// message to be sent, the updated [`crate::ChannelId`] and [`crate::ContractId`],
     /// as well as the public key of the offering node.
@@ -766,6 +2675,8 @@ where
             ));
         }
 
+        self.check_can_fund_channel()?;
+
         let offered_contract = get_contract_in_state!(
             self,
             &offered_channel.offered_contract_id,
@@ -779,22 +2690,30 @@ where
                 &offered_channel,
                 &offered_contract,
                 &self.wallet,
+                &self.signer,
                 &self.blockchain,
             )?;
 
-        self.wallet.import_address(&Address::p2wsh(
-            &accepted_contract.dlc_transactions.funding_script_pubkey,
-            self.blockchain.get_network()?,
-        ))?;
+        let temporary_channel_id = offered_channel.temporary_channel_id;
+        self.reserve_utxos(&temporary_channel_id, &accepted_contract.accept_params)?;
 
         let channel_id = accepted_channel.channel_id;
         let contract_id = accepted_contract.get_contract_id();
         let counter_party = accepted_contract.offered_contract.counter_party;
 
-        self.store.upsert_channel(
-            Channel::Accepted(accepted_channel),
-            Some(Contract::Accepted(accepted_contract)),
-        )?;
+        let res = self
+            .wallet
+            .import_address(&Address::p2wsh(
+                &accepted_contract.dlc_transactions.funding_script_pubkey,
+                self.blockchain.get_network()?,
+            ))
+            .and_then(|_| {
+                self.store.upsert_channel(
+                    Channel::Accepted(accepted_channel),
+                    Some(Contract::Accepted(accepted_contract)),
+                )
+            });
+        self.release_utxos_on_error(&temporary_channel_id, res)?;
 
         Ok((accept_channel, channel_id, contract_id, counter_party))
     }
@@ -822,7 +2741,7 @@ where
             &mut signed_channel,
             counter_payout,
             PEER_TIMEOUT,
-            &self.wallet,
+            &self.signer,
             &self.time,
         )?;
 
@@ -849,7 +2768,7 @@ where
             CET_NSEQUENCE,
             0,
             PEER_TIMEOUT,
-            &self.wallet,
+            &self.signer,
             &self.time,
         )?;
 
@@ -870,6 +2789,7 @@ where
         counter_payout: u64,
         contract_input: &ContractInput,
     ) -> Result<(RenewOffer, PublicKey), Error> {
+        let contract_input = self.with_fee_rate(contract_input)?;
         let mut signed_channel =
             get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
 
@@ -882,13 +2802,13 @@ where
         let (msg, offered_contract) = crate::channel_updater::renew_offer(
             &self.secp,
             &mut signed_channel,
-            contract_input,
+            &contract_input,
             oracle_announcements,
             counter_payout,
             REFUND_DELAY,
             PEER_TIMEOUT,
             CET_NSEQUENCE,
-            &self.wallet,
+            &self.signer,
             &self.time,
         )?;
 
@@ -928,7 +2848,7 @@ where
             &offered_contract,
             CET_NSEQUENCE,
             PEER_TIMEOUT,
-            &self.wallet,
+            &self.signer,
             &self.time,
         )?;
 
@@ -1012,7 +2932,7 @@ where
             &self.secp,
             &mut signed_channel,
             counter_payout,
-            &self.wallet,
+            &self.signer,
             &self.time,
         )?;
 
@@ -1024,9 +2944,10 @@ where
             },
         );
 
-        self.store
-            .upsert_channel(Channel::Signed(signed_channel), None)?;
-        self.store.persist_chain_monitor(&self.chain_monitor)?;
+        self.store.write_batch(&[
+            StorageWrite::Channel(&Channel::Signed(signed_channel)),
+            StorageWrite::ChainMonitor(&self.chain_monitor),
+        ])?;
 
         Ok(msg)
     }
@@ -1067,6 +2988,7 @@ where
                 temporary_contract_id: contract.accepted_contract.offered_contract.id,
                 counter_party_id: signed_channel.counter_party,
                 pnl,
+                closed_at: self.time.unix_time_now(),
             })
         } else {
             None
@@ -1075,18 +2997,15 @@ where
         let close_tx = crate::channel_updater::accept_collaborative_close_offer(
             &self.secp,
             &mut signed_channel,
-            &self.wallet,
+            &self.signer,
         )?;
 
         self.blockchain.send_transaction(&close_tx)?;
 
-        self.store
-            .upsert_channel(Channel::Signed(signed_channel), None)?;
-
-        if let Some(closed_contract) = closed_contract {
-            self.store
-                .update_contract(&Contract::Closed(closed_contract))?;
-        }
+        self.store.upsert_channel(
+            Channel::Signed(signed_channel),
+            closed_contract.map(Contract::Closed),
+        )?;
 
         Ok(())
     }
@@ -1129,17 +3048,31 @@ where
         offer_channel: &OfferChannel,
         counter_party: PublicKey,
     ) -> Result<(), Error> {
-        offer_channel.validate(
-            &self.secp,
-            REFUND_DELAY,
-            REFUND_DELAY * 2,
-            CET_NSEQUENCE,
-            CET_NSEQUENCE * 2,
-        )?;
+        if self.verify_announcements {
+            offer_channel.validate(
+                &self.secp,
+                REFUND_DELAY,
+                REFUND_DELAY * 2,
+                CET_NSEQUENCE,
+                CET_NSEQUENCE * 2,
+            )?;
+        } else {
+            offer_channel.validate_without_announcements(
+                REFUND_DELAY,
+                REFUND_DELAY * 2,
+                CET_NSEQUENCE,
+                CET_NSEQUENCE * 2,
+            )?;
+        }
+        check_peer_features(&offer_channel.features)?;
 
         let (channel, contract) = OfferedChannel::from_offer_channel(offer_channel, counter_party)?;
 
         contract.validate()?;
+        contract.check_hyperbola_feature(&offer_channel.features)?;
+        self.check_oracle_trust(&contract)?;
+        self.fee_rate_policy
+            .check_fee_rate(contract.fee_rate_per_vb, &self.blockchain)?;
 
         if self
             .store
@@ -1183,12 +3116,13 @@ where
                 accept_channel,
                 //TODO(tibo): this should be parameterizable.
                 CET_NSEQUENCE,
-                &self.wallet,
+                &self.signer,
             );
 
             match res {
                 Ok(res) => res,
                 Err(e) => {
+                    self.release_utxos(&offered_channel.temporary_channel_id);
                     let channel = crate::channel::FailedAccept {
                         temporary_channel_id: accept_channel.temporary_channel_id,
                         error_message: format!("Error validating accept channel: {}", e),
@@ -1225,12 +3159,11 @@ where
             unreachable!();
         }
 
-        self.store.upsert_channel(
-            Channel::Signed(signed_channel),
-            Some(Contract::Signed(signed_contract)),
-        )?;
-
-        self.store.persist_chain_monitor(&self.chain_monitor)?;
+        self.store.write_batch(&[
+            StorageWrite::Channel(&Channel::Signed(signed_channel)),
+            StorageWrite::Contract(&Contract::Signed(signed_contract)),
+            StorageWrite::ChainMonitor(&self.chain_monitor),
+        ])?;
 
         Ok(sign_channel)
     }
@@ -1255,12 +3188,13 @@ where
                 &accepted_channel,
                 &accepted_contract,
                 sign_channel,
-                &self.wallet,
+                &self.signer,
             );
 
             match res {
                 Ok(res) => res,
                 Err(e) => {
+                    self.release_utxos(&accepted_channel.temporary_channel_id);
                     let channel = crate::channel::FailedSign {
                         channel_id: sign_channel.channel_id,
                         error_message: format!("Error validating accept channel: {}", e),
@@ -1291,11 +3225,11 @@ where
 
         self.blockchain.send_transaction(&signed_channel.fund_tx)?;
 
-        self.store.upsert_channel(
-            Channel::Signed(signed_channel),
-            Some(Contract::Signed(signed_contract)),
-        )?;
-        self.store.persist_chain_monitor(&self.chain_monitor)?;
+        self.store.write_batch(&[
+            StorageWrite::Channel(&Channel::Signed(signed_channel)),
+            StorageWrite::Contract(&Contract::Signed(signed_contract)),
+            StorageWrite::ChainMonitor(&self.chain_monitor),
+        ])?;
 
         Ok(())
     }
@@ -1337,7 +3271,7 @@ where
             CET_NSEQUENCE,
             0,
             PEER_TIMEOUT,
-            &self.wallet,
+            &self.signer,
             &self.time,
         )?;
 
@@ -1373,7 +3307,7 @@ where
             &self.secp,
             &mut signed_channel,
             settle_confirm,
-            &self.wallet,
+            &self.signer,
         )?;
 
         self.chain_monitor.add_tx(
@@ -1409,11 +3343,14 @@ where
             temporary_contract_id: contract.accepted_contract.offered_contract.id,
             counter_party_id: signed_channel.counter_party,
             pnl: (own_collateral as i64) - (own_payout as i64),
+            closed_at: self.time.unix_time_now(),
         });
 
-        self.store
-            .upsert_channel(Channel::Signed(signed_channel), Some(closed_contract))?;
-        self.store.persist_chain_monitor(&self.chain_monitor)?;
+        self.store.write_batch(&[
+            StorageWrite::Channel(&Channel::Signed(signed_channel)),
+            StorageWrite::Contract(&closed_contract),
+            StorageWrite::ChainMonitor(&self.chain_monitor),
+        ])?;
 
         Ok(msg)
     }
@@ -1479,11 +3416,14 @@ where
             temporary_contract_id: contract.accepted_contract.offered_contract.id,
             counter_party_id: signed_channel.counter_party,
             pnl: (own_collateral as i64) - (own_payout as i64),
+            closed_at: self.time.unix_time_now(),
         });
 
-        self.store
-            .upsert_channel(Channel::Signed(signed_channel), Some(closed_contract))?;
-        self.store.persist_chain_monitor(&self.chain_monitor)?;
+        self.store.write_batch(&[
+            StorageWrite::Channel(&Channel::Signed(signed_channel)),
+            StorageWrite::Contract(&closed_contract),
+            StorageWrite::ChainMonitor(&self.chain_monitor),
+        ])?;
 
         Ok(())
     }
@@ -1508,9 +3448,10 @@ where
         let offered_contract =
             crate::channel_updater::on_renew_offer(&mut signed_channel, renew_offer)?;
 
-        self.store.create_contract(&offered_contract)?;
-        self.store
-            .upsert_channel(Channel::Signed(signed_channel), None)?;
+        self.store.write_batch(&[
+            StorageWrite::Contract(&Contract::Offered(offered_contract)),
+            StorageWrite::Channel(&Channel::Signed(signed_channel)),
+        ])?;
 
         Ok(None)
     }
@@ -1538,7 +3479,7 @@ where
             &offered_contract,
             CET_NSEQUENCE,
             PEER_TIMEOUT,
-            &self.wallet,
+            &self.signer,
             &self.time,
         )?;
 
@@ -1595,6 +3536,7 @@ where
                     temporary_contract_id: contract.accepted_contract.offered_contract.id,
                     counter_party_id: signed_channel.counter_party,
                     pnl,
+                    closed_at: self.time.unix_time_now(),
                 });
                 (
                     TxType::Revoked {
@@ -1637,7 +3579,7 @@ where
             &mut signed_channel,
             &accepted_contract,
             renew_confirm,
-            &self.wallet,
+            &self.signer,
         )?;
 
         self.chain_monitor.add_tx(
@@ -1659,17 +3601,18 @@ where
             },
         );
 
+        let channel = Channel::Signed(signed_channel);
         // Directly confirmed as we're in a channel the fund tx is already confirmed.
-        self.store.upsert_channel(
-            Channel::Signed(signed_channel),
-            Some(Contract::Confirmed(signed_contract)),
-        )?;
-
-        self.store.persist_chain_monitor(&self.chain_monitor)?;
-
-        if let Some(closed_contract) = closed_contract {
-            self.store.update_contract(&closed_contract)?;
+        let contract = Contract::Confirmed(signed_contract);
+        let mut writes = vec![
+            StorageWrite::Channel(&channel),
+            StorageWrite::Contract(&contract),
+            StorageWrite::ChainMonitor(&self.chain_monitor),
+        ];
+        if let Some(closed_contract) = closed_contract.as_ref() {
+            writes.push(StorageWrite::Contract(closed_contract));
         }
+        self.store.write_batch(&writes)?;
 
         Ok(msg)
     }
@@ -1713,6 +3656,7 @@ where
                     temporary_contract_id: contract.accepted_contract.offered_contract.id,
                     counter_party_id: signed_channel.counter_party,
                     pnl,
+                    closed_at: self.time.unix_time_now(),
                 });
                 (
                     TxType::Revoked {
@@ -1768,13 +3712,15 @@ where
             },
         );
 
-        self.store
-            .upsert_channel(Channel::Signed(signed_channel), None)?;
-        self.store.persist_chain_monitor(&self.chain_monitor)?;
-
-        if let Some(closed_contract) = closed_contract {
-            self.store.update_contract(&closed_contract)?;
+        let channel = Channel::Signed(signed_channel);
+        let mut writes = vec![
+            StorageWrite::Channel(&channel),
+            StorageWrite::ChainMonitor(&self.chain_monitor),
+        ];
+        if let Some(closed_contract) = closed_contract.as_ref() {
+            writes.push(StorageWrite::Contract(closed_contract));
         }
+        self.store.write_batch(&writes)?;
 
         Ok(())
     }
@@ -1825,6 +3771,9 @@ where
         if let Err(e) = self.check_for_timed_out_channels() {
             error!("Error checking timed out channels {}", e);
         }
+        if let Err(e) = self.check_for_mempool_tx(None) {
+            error!("Error checking the mempool for channel transactions {}", e);
+        }
         self.check_for_watched_tx()
     }
 
@@ -1857,235 +3806,283 @@ where
             let watch_res = self.chain_monitor.process_block(&block, height);
 
             for (tx, channel_info) in watch_res {
-                let mut signed_channel = match get_channel_in_state!(
-                    self,
-                    &channel_info.channel_id,
-                    Signed,
-                    None as Option<PublicKey>
-                ) {
-                    Ok(c) => c,
-                    Err(e) => {
-                        error!(
-                            "Could not retrieve channel {:?}: {}",
-                            channel_info.channel_id, e
-                        );
-                        continue;
-                    }
-                };
+                self.process_watched_tx(tx, channel_info)?;
+            }
 
-                if let TxType::Current = channel_info.tx_type {
-                    // TODO(tibo): should only considered closed after some confirmations.
-                    // Ideally should save previous state, and maybe restore in
-                    // case of reorg, though if the counter party has sent the
-                    // tx to close the channel it is unlikely that the tx will
-                    // not be part of a future block.
-                    let contract = if let Some(contract_id) = signed_channel.get_contract_id() {
-                        let contract_opt = self.store.get_contract(&contract_id)?;
-                        if let Some(contract) = contract_opt {
-                            match contract {
-                                Contract::Confirmed(c) => {
-                                    Some(Contract::PreClosed(PreClosedContract {
-                                        signed_contract: c,
-                                        attestations: None,
-                                        signed_cet: tx.clone(),
-                                    }))
-                                }
-                                _ => None,
-                            }
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    };
+            self.chain_monitor.increment_height(&block.block_hash());
+        }
+
+        Ok(())
+    }
+
+    /// Looks in the mempool for transactions spending the fund output of the
+    /// signed channels, so that revoked transactions are punished and closes
+    /// by the counter party are noticed before being confirmed.
+    fn check_for_mempool_tx(&mut self, outpoint: Option<&OutPoint>) -> Result<(), Error> {
+        for channel in self.store.get_signed_channels(None)? {
+            if is_closed_channel_state(&channel.state) {
+                continue;
+            }
+            let fund_outpoint =
+                OutPoint::new(channel.fund_tx.txid(), channel.fund_output_index as u32);
+            if outpoint.map_or(false, |o| *o != fund_outpoint) {
+                continue;
+            }
+            let res =
+                self.blockchain
+                    .get_mempool_spend(&fund_outpoint)
+                    .and_then(|spend| match spend {
+                        Some(tx) => match self.chain_monitor.get_watched_tx(&tx.txid()) {
+                            Some(channel_info) => self.process_watched_tx(tx, channel_info),
+                            None => Ok(()),
+                        },
+                        None => Ok(()),
+                    });
+            if let Err(e) = res {
+                error!(
+                    "Error checking the mempool for channel {:?}: {}",
+                    channel.channel_id, e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process_watched_tx(
+        &mut self,
+        tx: Transaction,
+        channel_info: ChannelInfo,
+    ) -> Result<(), Error> {
+        let mut signed_channel = match get_channel_in_state!(
+            self,
+            &channel_info.channel_id,
+            Signed,
+            None as Option<PublicKey>
+        ) {
+            Ok(c) => c,
+            Err(e) => {
+                error!(
+                    "Could not retrieve channel {:?}: {}",
+                    channel_info.channel_id, e
+                );
+                return Ok(());
+            }
+        };
 
-                    signed_channel.state = SignedChannelState::CounterClosed;
-                    self.store
-                        .upsert_channel(Channel::Signed(signed_channel), contract)?;
-                    continue;
-                } else if let TxType::Revoked {
-                    update_idx,
-                    own_adaptor_signature,
-                    is_offer,
-                    revoked_tx_type,
-                } = channel_info.tx_type
-                {
-                    let secret = signed_channel
-                        .counter_party_commitment_secrets
-                        .get_secret(update_idx)
-                        .expect("to be able to retrieve the per update secret");
-                    let counter_per_update_secret = SecretKey::from_slice(&secret)
-                        .expect("to be able to parse the counter per update secret.");
+        // Transactions seen in the mempool are processed again once confirmed.
+        if is_closed_channel_state(&signed_channel.state) {
+            return Ok(());
+        }
 
-                    let per_update_seed_pk = signed_channel.own_per_update_seed;
+        if let TxType::Current = channel_info.tx_type {
+            // TODO(tibo): should only considered closed after some confirmations.
+            // Ideally should save previous state, and maybe restore in
+            // case of reorg, though if the counter party has sent the
+            // tx to close the channel it is unlikely that the tx will
+            // not be part of a future block.
+            let contract = if let Some(contract_id) = signed_channel.get_contract_id() {
+                let contract_opt = self.store.get_contract(&contract_id)?;
+                if let Some(contract) = contract_opt {
+                    match contract {
+                        Contract::Confirmed(c) => Some(Contract::PreClosed(PreClosedContract {
+                            signed_contract: c,
+                            attestations: None,
+                            signed_cet: tx.clone(),
+                        })),
+                        _ => None,
+                    }
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
 
-                    let per_update_seed_sk =
-                        self.wallet.get_secret_key_for_pubkey(&per_update_seed_pk)?;
+            signed_channel.state = SignedChannelState::CounterClosed;
+            self.store
+                .upsert_channel(Channel::Signed(signed_channel), contract)?;
+            return Ok(());
+        } else if let TxType::Revoked {
+            update_idx,
+            own_adaptor_signature,
+            is_offer,
+            revoked_tx_type,
+        } = channel_info.tx_type
+        {
+            let secret = signed_channel
+                .counter_party_commitment_secrets
+                .get_secret(update_idx)
+                .expect("to be able to retrieve the per update secret");
+            let counter_per_update_secret = SecretKey::from_slice(&secret)
+                .expect("to be able to parse the counter per update secret.");
 
-                    let per_update_secret = SecretKey::from_slice(&build_commitment_secret(
-                        per_update_seed_sk.as_ref(),
-                        update_idx,
-                    ))
-                    .expect("a valid secret key.");
+            let per_update_seed_pk = signed_channel.own_per_update_seed;
 
-                    let per_update_point =
-                        PublicKey::from_secret_key(&self.secp, &per_update_secret);
+            let per_update_seed_sk = self.signer.get_secret_key_for_pubkey(&per_update_seed_pk)?;
 
-                    let own_revocation_params = signed_channel.own_points.get_revokable_params(
-                        &self.secp,
-                        &signed_channel.counter_points.revocation_basepoint,
-                        &per_update_point,
-                    );
+            let per_update_secret = SecretKey::from_slice(&build_commitment_secret(
+                per_update_seed_sk.as_ref(),
+                update_idx,
+            ))
+            .expect("a valid secret key.");
 
-                    let counter_per_update_point =
-                        PublicKey::from_secret_key(&self.secp, &counter_per_update_secret);
+            let per_update_point = PublicKey::from_secret_key(&self.secp, &per_update_secret);
 
-                    let base_own_sk = self
-                        .wallet
-                        .get_secret_key_for_pubkey(&signed_channel.own_points.own_basepoint)?;
+            let own_revocation_params = signed_channel.own_points.get_revokable_params(
+                &self.secp,
+                &signed_channel.counter_points.revocation_basepoint,
+                &per_update_point,
+            );
 
-                    let own_sk = derive_private_key(&self.secp, &per_update_point, &base_own_sk);
+            let counter_per_update_point =
+                PublicKey::from_secret_key(&self.secp, &counter_per_update_secret);
 
-                    let counter_revocation_params =
-                        signed_channel.counter_points.get_revokable_params(
-                            &self.secp,
-                            &signed_channel.own_points.revocation_basepoint,
-                            &counter_per_update_point,
-                        );
+            let own_sk = crate::utils::derive_own_secret_key(
+                &self.secp,
+                &self.signer,
+                &signed_channel.own_points.own_basepoint,
+                &per_update_point,
+            )?;
 
-                    let witness = if signed_channel.own_params.fund_pubkey
-                        < signed_channel.counter_params.fund_pubkey
-                    {
-                        tx.input[0].witness.to_vec().remove(1)
-                    } else {
-                        tx.input[0].witness.to_vec().remove(2)
-                    };
+            let counter_revocation_params = signed_channel.counter_points.get_revokable_params(
+                &self.secp,
+                &signed_channel.own_points.revocation_basepoint,
+                &counter_per_update_point,
+            );
 
-                    let sig_data = witness
-                        .iter()
-                        .take(witness.len() - 1)
-                        .cloned()
-                        .collect::<Vec<_>>();
-                    let own_sig = Signature::from_der(&sig_data)?;
+            let witness = if signed_channel.own_params.fund_pubkey
+                < signed_channel.counter_params.fund_pubkey
+            {
+                tx.input[0].witness.to_vec().remove(1)
+            } else {
+                tx.input[0].witness.to_vec().remove(2)
+            };
 
-                    let counter_sk = own_adaptor_signature.recover(
-                        &self.secp,
-                        &own_sig,
-                        &counter_revocation_params.publish_pk.inner,
-                    )?;
+            let sig_data = witness
+                .iter()
+                .take(witness.len() - 1)
+                .cloned()
+                .collect::<Vec<_>>();
+            let own_sig = Signature::from_der(&sig_data)?;
 
-                    let own_revocation_base_secret = &self.wallet.get_secret_key_for_pubkey(
-                        &signed_channel.own_points.revocation_basepoint,
-                    )?;
+            let counter_sk = own_adaptor_signature.recover(
+                &self.secp,
+                &own_sig,
+                &counter_revocation_params.publish_pk.inner,
+            )?;
 
-                    let counter_revocation_sk = derive_private_revocation_key(
-                        &self.secp,
-                        &counter_per_update_secret,
-                        own_revocation_base_secret,
-                    );
+            let own_revocation_base_secret = &self
+                .wallet
+                .get_secret_key_for_pubkey(&signed_channel.own_points.revocation_basepoint)?;
 
-                    let (offer_params, accept_params) = if is_offer {
-                        (&own_revocation_params, &counter_revocation_params)
-                    } else {
-                        (&counter_revocation_params, &own_revocation_params)
-                    };
+            let counter_revocation_sk = derive_private_revocation_key(
+                &self.secp,
+                &counter_per_update_secret,
+                own_revocation_base_secret,
+            );
+
+            let (offer_params, accept_params) = if is_offer {
+                (&own_revocation_params, &counter_revocation_params)
+            } else {
+                (&counter_revocation_params, &own_revocation_params)
+            };
 
-                    let fee_rate_per_vb: u64 = (self.fee_estimator.get_est_sat_per_1000_weight(
+            let fee_rate_per_vb = self
+                .blockchain
+                .get_fee_rate(PUNISH_CONFIRMATION_TARGET)
+                .unwrap_or_else(|e| {
+                    warn!("Could not estimate the punishment fee rate: {}", e);
+                    (self.fee_estimator.get_est_sat_per_1000_weight(
                         lightning::chain::chaininterface::ConfirmationTarget::HighPriority,
                     ) / 250)
-                        .into();
-
-                    let signed_tx = match revoked_tx_type {
-                        RevokedTxType::Buffer => {
-                            dlc::channel::create_and_sign_punish_buffer_transaction(
-                                &self.secp,
-                                offer_params,
-                                accept_params,
-                                &own_sk,
-                                &counter_sk,
-                                &counter_revocation_sk,
-                                &tx,
-                                &self.wallet.get_new_address()?,
-                                0,
-                                fee_rate_per_vb,
-                            )?
-                        }
-                        RevokedTxType::Settle => {
-                            dlc::channel::create_and_sign_punish_settle_transaction(
-                                &self.secp,
-                                offer_params,
-                                accept_params,
-                                &own_sk,
-                                &counter_sk,
-                                &counter_revocation_sk,
-                                &tx,
-                                &self.wallet.get_new_address()?,
-                                CET_NSEQUENCE,
-                                0,
-                                fee_rate_per_vb,
-                                is_offer,
-                            )?
-                        }
-                    };
+                        .into()
+                });
 
-                    self.blockchain.send_transaction(&signed_tx)?;
+            let signed_tx = match revoked_tx_type {
+                RevokedTxType::Buffer => dlc::channel::create_and_sign_punish_buffer_transaction(
+                    &self.secp,
+                    offer_params,
+                    accept_params,
+                    &own_sk,
+                    &counter_sk,
+                    &counter_revocation_sk,
+                    &tx,
+                    &self.wallet.get_new_address()?,
+                    0,
+                    fee_rate_per_vb,
+                )?,
+                RevokedTxType::Settle => dlc::channel::create_and_sign_punish_settle_transaction(
+                    &self.secp,
+                    offer_params,
+                    accept_params,
+                    &own_sk,
+                    &counter_sk,
+                    &counter_revocation_sk,
+                    &tx,
+                    &self.wallet.get_new_address()?,
+                    CET_NSEQUENCE,
+                    0,
+                    fee_rate_per_vb,
+                    is_offer,
+                )?,
+            };
 
-                    signed_channel.state = SignedChannelState::ClosedPunished {
-                        punishment_txid: signed_tx.txid(),
-                    };
+            self.blockchain.send_transaction(&signed_tx)?;
 
-                    self.store
-                        .upsert_channel(Channel::Signed(signed_channel), None)?;
-                } else if let TxType::CollaborativeClose = channel_info.tx_type {
-                    if let Some(SignedChannelState::Established {
-                        signed_contract_id,
-                        is_offer,
-                        ..
-                    }) = signed_channel.roll_back_state
-                    {
-                        let counter_payout = get_signed_channel_state!(
-                            signed_channel,
-                            CollaborativeCloseOffered,
-                            counter_payout
-                        )?;
-                        let contract = get_contract_in_state!(
-                            self,
-                            &signed_contract_id,
-                            Confirmed,
-                            None::<PublicKey>
-                        )?;
-                        let own_payout =
-                            contract.accepted_contract.offered_contract.total_collateral
-                                - counter_payout;
-                        let own_collateral = if is_offer {
-                            contract
-                                .accepted_contract
-                                .offered_contract
-                                .offer_params
-                                .collateral
-                        } else {
-                            contract.accepted_contract.accept_params.collateral
-                        };
-                        let pnl = (own_collateral as i64) - (own_payout as i64);
-
-                        let closed_contract = ClosedContract {
-                            attestations: None,
-                            signed_cet: None,
-                            contract_id: signed_contract_id,
-                            temporary_contract_id: contract.accepted_contract.offered_contract.id,
-                            counter_party_id: signed_channel.counter_party,
-                            pnl,
-                        };
-                        self.store
-                            .update_contract(&Contract::Closed(closed_contract))?;
-                    }
-                    signed_channel.state = SignedChannelState::CollaborativelyClosed;
-                    self.store
-                        .upsert_channel(Channel::Signed(signed_channel), None)?;
-                }
-            }
+            signed_channel.state = SignedChannelState::ClosedPunished {
+                punishment_txid: signed_tx.txid(),
+            };
 
-            self.chain_monitor.increment_height(&block.block_hash());
+            self.store
+                .upsert_channel(Channel::Signed(signed_channel), None)?;
+        } else if let TxType::CollaborativeClose = channel_info.tx_type {
+            let closed_contract = if let Some(SignedChannelState::Established {
+                signed_contract_id,
+                is_offer,
+                ..
+            }) = signed_channel.roll_back_state
+            {
+                let counter_payout = get_signed_channel_state!(
+                    signed_channel,
+                    CollaborativeCloseOffered,
+                    counter_payout
+                )?;
+                let contract = get_contract_in_state!(
+                    self,
+                    &signed_contract_id,
+                    Confirmed,
+                    None::<PublicKey>
+                )?;
+                let own_payout =
+                    contract.accepted_contract.offered_contract.total_collateral - counter_payout;
+                let own_collateral = if is_offer {
+                    contract
+                        .accepted_contract
+                        .offered_contract
+                        .offer_params
+                        .collateral
+                } else {
+                    contract.accepted_contract.accept_params.collateral
+                };
+                let pnl = (own_collateral as i64) - (own_payout as i64);
+
+                let closed_contract = ClosedContract {
+                    attestations: None,
+                    signed_cet: None,
+                    contract_id: signed_contract_id,
+                    temporary_contract_id: contract.accepted_contract.offered_contract.id,
+                    counter_party_id: signed_channel.counter_party,
+                    pnl,
+                    closed_at: self.time.unix_time_now(),
+                };
+                Some(Contract::Closed(closed_contract))
+            } else {
+                None
+            };
+            signed_channel.state = SignedChannelState::CollaborativelyClosed;
+            self.store
+                .upsert_channel(Channel::Signed(signed_channel), closed_contract)?;
         }
 
         Ok(())
@@ -2150,7 +4147,7 @@ where
             contract_info,
             &attestations,
             adaptor_info,
-            &self.wallet,
+            &self.signer,
         )?;
 
         let buffer_transaction =
@@ -2160,10 +4157,10 @@ where
 
         self.chain_monitor.remove_tx(&buffer_transaction.txid());
 
-        self.store
-            .upsert_channel(Channel::Signed(signed_channel), None)?;
-
-        self.store.persist_chain_monitor(&self.chain_monitor)?;
+        self.store.write_batch(&[
+            StorageWrite::Channel(&Channel::Signed(signed_channel)),
+            StorageWrite::ChainMonitor(&self.chain_monitor),
+        ])?;
 
         Ok(())
     }
@@ -2173,7 +4170,7 @@ where
         let settle_tx = crate::channel_updater::close_settled_channel(
             &self.secp,
             &mut signed_channel,
-            &self.wallet,
+            &self.signer,
         )?;
 
         self.blockchain.send_transaction(&settle_tx)?;
@@ -2185,11 +4182,72 @@ where
     }
 }
 
+/// Returns the number of contracts and channels offered by the given peer
+/// that were not accepted or rejected yet.
+fn get_nb_pending_handshakes<S: Deref>(store: &S, counter_party: &PublicKey) -> Result<usize, Error>
+where
+    S::Target: Storage,
+{
+    let nb_contracts = store
+        .get_contract_offers()?
+        .iter()
+        .filter(|c| !c.is_offer_party && c.counter_party == *counter_party)
+        .count();
+    let nb_channels = store
+        .get_offered_channels()?
+        .iter()
+        .filter(|c| !c.is_offer_party && c.counter_party == *counter_party)
+        .count();
+    Ok(nb_contracts + nb_channels)
+}
+
+/// Returns the median of the timestamps of the last [`MEDIAN_TIME_SPAN`] of
+/// the given headers.
+fn get_median_time_past(headers: &[BlockHeader]) -> u32 {
+    let mut times: Vec<_> = headers
+        .iter()
+        .rev()
+        .take(MEDIAN_TIME_SPAN)
+        .map(|h| h.time)
+        .collect();
+    times.sort_unstable();
+    times.get(times.len() / 2).copied().unwrap_or(0)
+}
+
+fn is_closed_channel_state(state: &SignedChannelState) -> bool {
+    matches!(
+        state,
+        SignedChannelState::Closed
+            | SignedChannelState::CounterClosed
+            | SignedChannelState::ClosedPunished { .. }
+            | SignedChannelState::CollaborativelyClosed
+    )
+}
+
+/// Returns an error if the features advertised by a peer in an offer require
+/// capabilities that are not supported.
+fn check_peer_features(features: &Option<Features>) -> Result<(), Error> {
+    if let Some(features) = features {
+        Features::supported().negotiate(features).map_err(|bits| {
+            Error::InvalidParameters(format!(
+                "Offer requires unsupported features (bits {:#x})",
+                bits
+            ))
+        })?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use dlc_messages::Message;
     use mocks::{
-        dlc_manager::{manager::Manager, Oracle},
+        dlc_manager::{
+            contract::{Contract, ContractState, SharedFundingInput},
+            manager::{Manager, RetentionPolicy},
+            Oracle, Storage,
+        },
         memory_storage_provider::MemoryStorage,
         mock_blockchain::MockBlockchain,
         mock_oracle_provider::MockOracle,
@@ -2209,7 +4267,7 @@ mod test {
     >;
 
     fn get_manager() -> TestManager {
-        let blockchain = Rc::new(MockBlockchain {});
+        let blockchain = Rc::new(MockBlockchain::new());
         let store = Rc::new(MemoryStorage::new());
         let wallet = Rc::new(MockWallet::new(&blockchain, 100));
 
@@ -2248,6 +4306,493 @@ mod test {
             .expect_err("To reject the second offer message");
     }
 
+    #[test]
+    fn reject_offer_with_forged_announcement() {
+        let mut offer: dlc_messages::OfferDlc =
+            serde_json::from_str(include_str!("../test_inputs/offer_contract.json")).unwrap();
+        match &mut offer.contract_info {
+            dlc_messages::contract_msgs::ContractInfo::SingleContractInfo(s) => {
+                match &mut s.contract_info.oracle_info {
+                    dlc_messages::oracle_msgs::OracleInfo::Single(o) => {
+                        o.oracle_announcement.oracle_event.event_id.push('0')
+                    }
+                    _ => panic!("Expected single oracle info"),
+                }
+            }
+            _ => panic!("Expected single contract info"),
+        }
+        let offer_message = Message::Offer(offer);
+
+        let mut manager = get_manager();
+
+        manager
+            .on_dlc_message(&offer_message, pubkey())
+            .expect_err("To reject the offer with a forged announcement");
+
+        manager.set_verify_announcements(false);
+
+        manager
+            .on_dlc_message(&offer_message, pubkey())
+            .expect("To accept the offer when not verifying announcements");
+    }
+
+    #[test]
+    fn reject_offer_requiring_unsupported_features() {
+        let mut offer: dlc_messages::OfferDlc =
+            serde_json::from_str(include_str!("../test_inputs/offer_contract.json")).unwrap();
+        let mut features = dlc_messages::features::Features::supported();
+        features.set_required(dlc_messages::features::Feature::TaprootFunding);
+        offer.features = Some(features);
+
+        let mut manager = get_manager();
+
+        manager
+            .on_dlc_message(&Message::Offer(offer.clone()), pubkey())
+            .expect_err("To reject the offer requiring taproot funding");
+
+        features.set_optional(dlc_messages::features::Feature::TaprootFunding);
+        offer.features = Some(features);
+
+        manager
+            .on_dlc_message(&Message::Offer(offer), pubkey())
+            .expect("To accept the offer optionally supporting taproot funding");
+    }
+
+    #[test]
+    fn reject_offer_with_invalid_monotone_cubic_piece() {
+        use dlc_messages::contract_msgs::{
+            ContractDescriptor, ContractInfo, MonotoneCubicPayoutCurvePiece, PayoutCurvePiece,
+            PayoutPoint,
+        };
+
+        let get_offer = |payout_points| {
+            let mut offer: dlc_messages::OfferDlc =
+                serde_json::from_str(include_str!("../test_inputs/offer_contract.json")).unwrap();
+            match &mut offer.contract_info {
+                ContractInfo::SingleContractInfo(s) => {
+                    match &mut s.contract_info.contract_descriptor {
+                        ContractDescriptor::NumericOutcomeContractDescriptor(n) => {
+                            n.payout_function.payout_function_pieces[0].payout_curve_piece =
+                                PayoutCurvePiece::MonotoneCubicPayoutCurvePiece(
+                                    MonotoneCubicPayoutCurvePiece { payout_points },
+                                )
+                        }
+                        _ => panic!("Expected numeric outcome contract descriptor"),
+                    }
+                }
+                _ => panic!("Expected single contract info"),
+            }
+            Message::Offer(offer)
+        };
+        let point = |event_outcome, outcome_payout| PayoutPoint {
+            event_outcome,
+            outcome_payout,
+            extra_precision: 0,
+        };
+
+        let mut manager = get_manager();
+
+        manager
+            .on_dlc_message(&get_offer(vec![point(0, 50000000)]), pubkey())
+            .expect_err("To reject the offer with duplicate monotone cubic outcomes");
+
+        manager
+            .on_dlc_message(&get_offer(vec![point(3, 90000000)]), pubkey())
+            .expect("To accept the offer with ascending monotone cubic outcomes");
+    }
+
+    #[test]
+    fn prune_contracts_archives_expired_contracts() {
+        let offer: dlc_messages::OfferDlc =
+            serde_json::from_str(include_str!("../test_inputs/offer_contract.json")).unwrap();
+        let refund_locktime = offer.refund_locktime as u64;
+        let mut manager = get_manager();
+        manager
+            .on_dlc_message(&Message::Offer(offer), pubkey())
+            .expect("To accept the offer message");
+        let offered = manager.get_store().get_contract_offers().unwrap().remove(0);
+        manager
+            .get_store()
+            .update_contract(&Contract::Rejected(offered))
+            .unwrap();
+        let policy = RetentionPolicy { max_age_days: 1 };
+
+        mocks::mock_time::set_time(refund_locktime + 86399);
+        assert!(manager.prune_contracts(&policy).unwrap().is_empty());
+
+        mocks::mock_time::set_time(refund_locktime + 86400);
+        let archived = manager.prune_contracts(&policy).unwrap();
+        assert_eq!(1, archived.len());
+        assert_eq!(ContractState::Rejected, archived[0].state);
+        assert!(manager.get_store().get_contracts().unwrap().is_empty());
+        assert_eq!(
+            1,
+            manager.get_store().get_archived_contracts().unwrap().len()
+        );
+    }
+
+    #[test]
+    fn offer_is_not_stored_when_storage_fails() {
+        let offer_message = Message::Offer(
+            serde_json::from_str(include_str!("../test_inputs/offer_contract.json")).unwrap(),
+        );
+
+        let mut manager = get_manager();
+        manager.get_store().faults().fail_nth_call(
+            "create_contract",
+            1,
+            mocks::fault_injection::FaultKind::Storage,
+        );
+
+        manager
+            .on_dlc_message(&offer_message, pubkey())
+            .expect_err("To fail storing the offer");
+        assert!(manager
+            .get_store()
+            .get_contract_offers()
+            .unwrap()
+            .is_empty());
+
+        manager
+            .on_dlc_message(&offer_message, pubkey())
+            .expect("To accept the offer once the storage recovered");
+        assert_eq!(
+            2,
+            manager
+                .get_store()
+                .faults()
+                .get_call_count("create_contract")
+        );
+    }
+
+    #[test]
+    fn reject_shared_funding_offer_with_unexpected_share() {
+        let mut offer: dlc_messages::OfferDlc =
+            serde_json::from_str(include_str!("../test_inputs/offer_contract.json")).unwrap();
+        offer.shared_funding_accept_share = Some(100000);
+        let contract_id = offer.temporary_contract_id;
+        let shared_funding_input = SharedFundingInput {
+            funding_script: bitcoin::Script::new(),
+            own_pubkey: pubkey(),
+        };
+
+        let mut manager = get_manager();
+
+        manager
+            .on_dlc_message(&Message::Offer(offer), pubkey())
+            .expect("To accept the offer message");
+
+        let err = manager
+            .accept_contract_offer_with_shared_funding(&contract_id, &shared_funding_input, 90000)
+            .expect_err("To reject the offer attributing an unexpected share");
+        assert!(err.to_string().contains("expected share"));
+    }
+
+    #[test]
+    fn shared_funding_split_must_be_explicitly_accepted() {
+        let mut offer: dlc_messages::OfferDlc =
+            serde_json::from_str(include_str!("../test_inputs/offer_contract.json")).unwrap();
+        offer.shared_funding_accept_share = Some(100000);
+        let contract_id = offer.temporary_contract_id;
+
+        let mut manager = get_manager();
+
+        manager
+            .on_dlc_message(&Message::Offer(offer), pubkey())
+            .expect("To accept the offer message");
+
+        manager
+            .accept_contract_offer(&contract_id)
+            .expect_err("To require approving the split of the shared output");
+        manager
+            .accept_rollover_offer(&contract_id, 100000)
+            .expect_err("To reject accepting an offer not rolling over a contract");
+    }
+
+    #[test]
+    fn reject_multi_party_offer_exceeding_fee_rate_policy() {
+        use bitcoin::util::bip32::ExtendedPrivKey;
+        use dlc_messages::multi_party::MultiPartyOutcome;
+        use dlc_messages::oracle_msgs::{EnumEventDescriptor, EventDescriptor};
+        use mocks::dlc_manager::contract::multi_party_contract::MultiPartyContractInput;
+        use mocks::dlc_manager::fee_rate_policy::FeeRatePolicy;
+        use mocks::dlc_manager::key_derivation::DerivedKeysSigner;
+        use mocks::dlc_manager::manager::REFUND_DELAY;
+
+        let mut manager = get_manager();
+
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let mut oracle = MockOracle::new();
+        oracle.add_event(
+            "event",
+            &EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: vec!["a".to_string(), "b".to_string()],
+            }),
+            1700000000,
+        );
+        let blockchain = Rc::new(MockBlockchain::new());
+        let wallet = MockWallet::new(&blockchain, 10);
+        let signer = DerivedKeysSigner::new(
+            ExtendedPrivKey::new_master(bitcoin::Network::Regtest, &[1; 32]).unwrap(),
+        );
+        let contract_input = MultiPartyContractInput {
+            collaterals: vec![1000000, 1000000],
+            fee_rate: 10,
+            outcomes: vec![
+                MultiPartyOutcome {
+                    outcome: "a".to_string(),
+                    payouts: vec![2000000, 0],
+                },
+                MultiPartyOutcome {
+                    outcome: "b".to_string(),
+                    payouts: vec![0, 2000000],
+                },
+            ],
+            oracle_public_key: oracle.get_public_key(),
+            event_id: "event".to_string(),
+        };
+        let (_, mut offers) = mocks::dlc_manager::multi_party_updater::offer_contract(
+            &secp,
+            &contract_input,
+            oracle.get_announcement("event").unwrap(),
+            &[pubkey()],
+            REFUND_DELAY,
+            &&wallet,
+            &&signer,
+            &blockchain,
+            &&MockTime {},
+        )
+        .unwrap();
+        let offer_message = Message::MultiPartyOffer(offers.pop().unwrap());
+
+        manager.set_fee_rate_policy(FeeRatePolicy {
+            max_fee_rate: Some(5),
+            ..Default::default()
+        });
+        let err = manager
+            .on_dlc_message(&offer_message, pubkey())
+            .expect_err("To reject the offer exceeding the maximum fee rate");
+        assert!(err.to_string().contains("Fee rate"));
+
+        manager.set_fee_rate_policy(FeeRatePolicy::default());
+        manager
+            .on_dlc_message(&offer_message, pubkey())
+            .expect("To accept the offer without maximum fee rate");
+    }
+
+    #[test]
+    fn reject_novation_offer_exceeding_fee_rate_policy() {
+        use bitcoin::hashes::Hash;
+        use dlc_messages::novation::{NovationOffer, NovationTerms};
+        use mocks::dlc_manager::fee_rate_policy::FeeRatePolicy;
+
+        let offer: dlc_messages::OfferDlc =
+            serde_json::from_str(include_str!("../test_inputs/offer_contract.json")).unwrap();
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let other_pubkey = PublicKey::from_secret_key(
+            &secp,
+            &secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap(),
+        );
+        let novation_offer = NovationOffer {
+            protocol_version: 1,
+            chain_hash: offer.chain_hash,
+            contract_id: [1; 32],
+            temporary_contract_id: offer.temporary_contract_id,
+            remaining_party: other_pubkey,
+            transferor_is_offer_party: true,
+            contract_info: offer.contract_info.clone(),
+            transferor_collateral: offer.offer_collateral,
+            transferor_funding_pubkey: offer.funding_pubkey,
+            transferor_payout_serial_id: offer.payout_serial_id,
+            remaining_funding_pubkey: other_pubkey,
+            remaining_payout_spk: bitcoin::Script::new(),
+            remaining_payout_serial_id: 1,
+            fund_txid: bitcoin::Txid::all_zeros(),
+            fund_output_index: 0,
+            fund_output_value: offer.contract_info.get_total_collateral(),
+            cet_locktime: offer.cet_locktime,
+            refund_locktime: offer.refund_locktime,
+            terms: NovationTerms {
+                price: 1000,
+                price_spk: bitcoin::Script::new(),
+                price_serial_id: 2,
+                fund_output_serial_id: 3,
+                fee_rate_per_vb: 10,
+            },
+        };
+
+        let mut manager = get_manager();
+        manager.set_fee_rate_policy(FeeRatePolicy {
+            max_fee_rate: Some(5),
+            ..Default::default()
+        });
+
+        let err = manager
+            .on_dlc_message(&Message::NovationOffer(novation_offer), pubkey())
+            .expect_err("To reject the offer exceeding the maximum fee rate");
+        assert!(err.to_string().contains("Fee rate"));
+    }
+
+    #[test]
+    fn contract_messages_are_processed_concurrently() {
+        use dlc::{EnumerationPayout, Payout};
+        use dlc_messages::oracle_msgs::{EnumEventDescriptor, EventDescriptor};
+        use mocks::dlc_manager::contract::contract_input::{
+            ContractInput, ContractInputInfo, OracleInput,
+        };
+        use mocks::dlc_manager::contract::enum_descriptor::EnumDescriptor;
+        use mocks::dlc_manager::contract::ContractDescriptor;
+        use std::sync::Arc;
+        use std::thread;
+
+        type SyncTestManager = Manager<
+            Arc<MockWallet>,
+            Arc<MockBlockchain>,
+            Arc<MemoryStorage>,
+            Arc<MockOracle>,
+            Arc<MockTime>,
+            Arc<MockBlockchain>,
+        >;
+
+        let blockchain = Arc::new(MockBlockchain::new());
+        let mut oracle = MockOracle::new();
+        oracle.add_event(
+            "event",
+            &EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: vec!["a".to_string(), "b".to_string()],
+            }),
+            1700000000,
+        );
+        let oracle = Arc::new(oracle);
+        let get_sync_manager = || -> SyncTestManager {
+            let mut oracles = HashMap::new();
+            oracles.insert(oracle.get_public_key(), oracle.clone());
+            Manager::new(
+                Arc::new(MockWallet::new(&blockchain, 100)),
+                blockchain.clone(),
+                Arc::new(MemoryStorage::new()),
+                oracles,
+                Arc::new(MockTime {}),
+                blockchain.clone(),
+            )
+            .unwrap()
+        };
+        let mut offer_manager = get_sync_manager();
+        let mut accept_manager = get_sync_manager();
+        let offer_party = pubkey();
+        let accept_party = PublicKey::from_secret_key(
+            &secp256k1_zkp::Secp256k1::new(),
+            &secp256k1_zkp::SecretKey::from_slice(&[1; 32]).unwrap(),
+        );
+
+        let contract_input = ContractInput {
+            offer_collateral: 1000000,
+            accept_collateral: 1000000,
+            fee_rate: 2,
+            contract_infos: vec![ContractInputInfo {
+                contract_descriptor: ContractDescriptor::Enum(EnumDescriptor {
+                    outcome_payouts: vec![
+                        EnumerationPayout {
+                            outcome: "a".to_string(),
+                            payout: Payout {
+                                offer: 2000000,
+                                accept: 0,
+                            },
+                        },
+                        EnumerationPayout {
+                            outcome: "b".to_string(),
+                            payout: Payout {
+                                offer: 0,
+                                accept: 2000000,
+                            },
+                        },
+                    ],
+                }),
+                oracles: OracleInput {
+                    public_keys: vec![oracle.get_public_key()],
+                    event_id: "event".to_string(),
+                    event_ids: Vec::new(),
+                    threshold: 1,
+                },
+            }],
+            metadata: None,
+            payout_script_pubkey: None,
+            change_script_pubkey: None,
+            additional_payout_outputs: Vec::new(),
+        };
+
+        let mut contract_ids = Vec::new();
+        let mut accept_msgs = Vec::new();
+        for _ in 0..2 {
+            let offer = offer_manager
+                .send_offer(&contract_input, accept_party)
+                .expect("To create the offer");
+            accept_manager
+                .on_dlc_message(&Message::Offer(offer.clone()), offer_party)
+                .expect("To accept the offer message");
+            let (contract_id, _, accept_msg) = accept_manager
+                .accept_contract_offer(&offer.temporary_contract_id)
+                .expect("To accept the offer");
+            contract_ids.push(contract_id);
+            accept_msgs.push(accept_msg);
+        }
+        // The first contract is accepted twice.
+        accept_msgs.push(accept_msgs[0].clone());
+
+        let offer_manager = Arc::new(offer_manager);
+        let accept_results: Vec<_> = accept_msgs
+            .into_iter()
+            .map(|accept_msg| {
+                let offer_manager = offer_manager.clone();
+                thread::spawn(move || {
+                    offer_manager.on_contract_message(&Message::Accept(accept_msg), accept_party)
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert!(accept_results[1].is_ok());
+        assert_eq!(
+            1,
+            [&accept_results[0], &accept_results[2]]
+                .iter()
+                .filter(|x| x.is_ok())
+                .count(),
+            "Only one of the duplicate accept messages should be processed"
+        );
+        let sign_msgs: Vec<_> = accept_results
+            .into_iter()
+            .filter_map(|x| match x {
+                Ok(Some(Message::Sign(sign_msg))) => Some(sign_msg),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(2, sign_msgs.len());
+
+        let accept_manager = Arc::new(accept_manager);
+        let sign_handles: Vec<_> = sign_msgs
+            .into_iter()
+            .map(|sign_msg| {
+                let accept_manager = accept_manager.clone();
+                thread::spawn(move || {
+                    accept_manager.on_contract_message(&Message::Sign(sign_msg), offer_party)
+                })
+            })
+            .collect();
+        for handle in sign_handles {
+            handle.join().unwrap().expect("To process the sign message");
+        }
+
+        for contract_id in &contract_ids {
+            for manager in [&offer_manager, &accept_manager].iter() {
+                let contract = manager.get_store().get_contract(contract_id).unwrap();
+                assert_eq!(Some(ContractState::Signed), contract.map(|x| x.get_state()));
+            }
+        }
+    }
+
     #[test]
     fn reject_channel_offer_with_existing_channel_id() {
         let offer_message = Message::OfferChannel(
@@ -2264,4 +4809,24 @@ mod test {
             .on_dlc_message(&offer_message, pubkey())
             .expect_err("To reject the second offer message");
     }
+
+    #[test]
+    fn median_time_past_uses_last_eleven_blocks() {
+        use bitcoin::hashes::Hash;
+        let headers: Vec<_> = [100, 5, 1, 9, 2, 8, 3, 7, 4, 6, 10, 11]
+            .iter()
+            .map(|time| bitcoin::BlockHeader {
+                version: 1,
+                prev_blockhash: bitcoin::BlockHash::all_zeros(),
+                merkle_root: bitcoin::TxMerkleNode::all_zeros(),
+                time: *time,
+                bits: 0,
+                nonce: 0,
+            })
+            .collect();
+
+        assert_eq!(6, super::get_median_time_past(&headers));
+        assert_eq!(5, super::get_median_time_past(&headers[..3]));
+        assert_eq!(0, super::get_median_time_past(&[]));
+    }
 }
@@ -0,0 +1,1026 @@
+//! # This module contains static functions to update the state of a DLC
+//! between more than two parties, see [`dlc_messages::multi_party`] for a
+//! description of the protocol.
+
+use std::ops::Deref;
+
+use bitcoin::{consensus::Decodable, Transaction, Witness};
+use dlc::DlcTransactions;
+use dlc_messages::multi_party::{
+    MultiPartyAccept, MultiPartyFinalize, MultiPartyFunding, MultiPartyOffer, MultiPartyParams,
+    MultiPartySetup, MultiPartySign,
+};
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
+use dlc_messages::{
+    CetAdaptorSignatures, FundingInput, FundingSignature, FundingSignatures, WitnessElement,
+};
+use secp256k1_zkp::{ecdsa::Signature, All, EcdsaAdaptorSignature, PublicKey, Secp256k1};
+
+use crate::{
+    contract::multi_party_contract::{
+        MultiPartyContract, MultiPartyContractInput, MultiPartyContractState,
+    },
+    conversion_utils::{BITCOIN_CHAINHASH, PROTOCOL_VERSION},
+    error::Error,
+    Blockchain, ContractSigner, Time, Wallet,
+};
+
+macro_rules! check_state {
+    ($contract: expr, $state: ident) => {
+        if $contract.state != MultiPartyContractState::$state {
+            return Err(Error::InvalidState(format!(
+                "Invalid state {:?} expected {}.",
+                $contract.state,
+                stringify!($state)
+            )));
+        }
+    };
+    ($contract: expr, $state: ident, $is_coordinator: expr) => {
+        if $contract.state != MultiPartyContractState::$state
+            || $contract.is_coordinator() != $is_coordinator
+        {
+            return Err(Error::InvalidState(format!(
+                "Invalid state {:?} expected {}.",
+                $contract.state,
+                stringify!($state)
+            )));
+        }
+    };
+}
+
+/// Creates a [`MultiPartyContract`] as coordinator, and the
+/// [`MultiPartyOffer`] message to send to each participant, in the order of
+/// `participants`.
+pub fn offer_contract<W: Deref, S: Deref, B: Deref, T: Deref>(
+    secp: &Secp256k1<All>,
+    contract_input: &MultiPartyContractInput,
+    oracle_announcement: OracleAnnouncement,
+    participants: &[PublicKey],
+    refund_delay: u32,
+    wallet: &W,
+    signer: &S,
+    blockchain: &B,
+    time: &T,
+) -> Result<(MultiPartyContract, Vec<MultiPartyOffer>), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+    B::Target: Blockchain,
+    T::Target: Time,
+{
+    contract_input.validate()?;
+    if participants.len() + 1 != contract_input.collaterals.len() {
+        return Err(Error::InvalidParameters(
+            "A collateral is required for the coordinator and each participant.".to_string(),
+        ));
+    }
+
+    let temporary_contract_id = crate::utils::get_new_temporary_id();
+    // Participants use the coordinator id to derive their keys, the
+    // coordinator uses the one of the first participant.
+    let (party_params, _, funding_inputs_info) = crate::utils::get_party_params(
+        secp,
+        &participants[0],
+        &temporary_contract_id,
+        contract_input.collaterals[0],
+        contract_input.fee_rate,
+        None,
+        None,
+        wallet,
+        signer,
+        blockchain,
+    )?;
+
+    let refund_locktime = oracle_announcement.oracle_event.event_maturity_epoch + refund_delay;
+    let offer = MultiPartyOffer {
+        protocol_version: PROTOCOL_VERSION,
+        chain_hash: BITCOIN_CHAINHASH,
+        temporary_contract_id,
+        participants: participants.to_vec(),
+        party_index: 0,
+        collaterals: contract_input.collaterals.clone(),
+        outcomes: contract_input.outcomes.clone(),
+        oracle_announcement,
+        offer_params: MultiPartyParams {
+            funding_pubkey: party_params.fund_pubkey,
+            payout_spk: party_params.payout_script_pubkey,
+            payout_serial_id: party_params.payout_serial_id,
+            funding_inputs: funding_inputs_info.iter().map(|x| x.into()).collect(),
+            change_spk: party_params.change_script_pubkey,
+            change_serial_id: party_params.change_serial_id,
+        },
+        fund_output_serial_id: crate::utils::get_new_serial_id(),
+        fee_rate_per_vb: contract_input.fee_rate,
+        cet_locktime: time.unix_time_now() as u32,
+        refund_locktime,
+    };
+    offer.validate(refund_delay, refund_delay * 2)?;
+
+    let offers = (1..offer.nb_parties())
+        .map(|i| MultiPartyOffer {
+            party_index: i as u16,
+            ..offer.clone()
+        })
+        .collect();
+
+    Ok((MultiPartyContract::new(offer, None), offers))
+}
+
+/// Accepts a [`MultiPartyContract`] offered by the coordinator, returning the
+/// updated contract and the [`MultiPartyAccept`] message to send to the
+/// coordinator.
+pub fn accept_contract<W: Deref, S: Deref, B: Deref>(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    wallet: &W,
+    signer: &S,
+    blockchain: &B,
+) -> Result<(MultiPartyContract, MultiPartyAccept), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+    B::Target: Blockchain,
+{
+    check_state!(contract, Offered, false);
+    let coordinator = contract
+        .coordinator
+        .ok_or_else(|| Error::InvalidState("Missing coordinator.".to_string()))?;
+
+    let (party_params, _, funding_inputs_info) = crate::utils::get_party_params(
+        secp,
+        &coordinator,
+        &contract.temporary_id,
+        contract.offer.collaterals[contract.own_index()],
+        contract.offer.fee_rate_per_vb,
+        None,
+        None,
+        wallet,
+        signer,
+        blockchain,
+    )?;
+
+    let accept_params = MultiPartyParams {
+        funding_pubkey: party_params.fund_pubkey,
+        payout_spk: party_params.payout_script_pubkey,
+        payout_serial_id: party_params.payout_serial_id,
+        funding_inputs: funding_inputs_info.iter().map(|x| x.into()).collect(),
+        change_spk: party_params.change_script_pubkey,
+        change_serial_id: party_params.change_serial_id,
+    };
+
+    let mut accepted_contract = contract.clone();
+    accepted_contract.party_params[contract.own_index()] = Some(accept_params.clone());
+    accepted_contract.state = MultiPartyContractState::Accepted;
+
+    let accept_msg = MultiPartyAccept {
+        protocol_version: PROTOCOL_VERSION,
+        temporary_contract_id: contract.temporary_id,
+        accept_params,
+    };
+
+    Ok((accepted_contract, accept_msg))
+}
+
+/// Records the parameters of the participant at `party_index` as coordinator.
+/// Once the parameters of all participants are known, the contract
+/// transactions are created and signed, and the [`MultiPartySetup`] message
+/// to send to every participant is returned.
+pub fn on_accept<S: Deref>(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    accept_msg: &MultiPartyAccept,
+    party_index: usize,
+    signer: &S,
+) -> Result<(MultiPartyContract, Option<MultiPartySetup>), Error>
+where
+    S::Target: ContractSigner,
+{
+    check_state!(contract, Offered, true);
+    if contract.party_params[party_index].is_some() {
+        return Err(Error::InvalidParameters(
+            "Participant already accepted the contract.".to_string(),
+        ));
+    }
+
+    let mut contract = contract.clone();
+    contract.party_params[party_index] = Some(accept_msg.accept_params.clone());
+
+    if contract.party_params.iter().any(|x| x.is_none()) {
+        return Ok((contract, None));
+    }
+
+    let dlc_transactions = set_up_contract(&mut contract)?;
+    let (adaptor_sigs, refund_sig) =
+        get_own_signatures(secp, &contract, &dlc_transactions, signer)?;
+    contract.cet_adaptor_signatures[0] = Some(adaptor_sigs);
+    contract.refund_signatures[0] = Some(refund_sig);
+
+    let setup_msg = MultiPartySetup {
+        protocol_version: PROTOCOL_VERSION,
+        temporary_contract_id: contract.temporary_id,
+        party_params: contract.party_params.iter().flatten().cloned().collect(),
+    };
+
+    Ok((contract, Some(setup_msg)))
+}
+
+/// Creates the contract transactions from the parameters of all parties
+/// received from the coordinator, returning the updated contract and the
+/// [`MultiPartySign`] message to send to the coordinator.
+pub fn on_setup<S: Deref>(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    setup_msg: &MultiPartySetup,
+    signer: &S,
+) -> Result<(MultiPartyContract, MultiPartySign), Error>
+where
+    S::Target: ContractSigner,
+{
+    check_state!(contract, Accepted, false);
+    let own_index = contract.own_index();
+    if setup_msg.party_params.len() != contract.offer.nb_parties()
+        || Some(&setup_msg.party_params[0]) != contract.party_params[0].as_ref()
+        || Some(&setup_msg.party_params[own_index]) != contract.party_params[own_index].as_ref()
+    {
+        return Err(Error::InvalidParameters(
+            "Party parameters don't match the contract.".to_string(),
+        ));
+    }
+
+    let mut contract = contract.clone();
+    contract.party_params = setup_msg.party_params.iter().cloned().map(Some).collect();
+    let dlc_transactions = set_up_contract(&mut contract)?;
+    let (cet_adaptor_signatures, refund_signature) =
+        get_own_signatures(secp, &contract, &dlc_transactions, signer)?;
+    contract.cet_adaptor_signatures[own_index] = Some(cet_adaptor_signatures.clone());
+    contract.refund_signatures[own_index] = Some(refund_signature);
+
+    let sign_msg = MultiPartySign {
+        protocol_version: PROTOCOL_VERSION,
+        contract_id: contract.id,
+        cet_adaptor_signatures,
+        refund_signature,
+    };
+
+    Ok((contract, sign_msg))
+}
+
+/// Verifies and records the signatures of the participant at `party_index` as
+/// coordinator. Once the signatures of all participants are known, the
+/// [`MultiPartyFinalize`] message to send to every participant is returned.
+pub fn on_sign(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    sign_msg: &MultiPartySign,
+    party_index: usize,
+) -> Result<(MultiPartyContract, Option<MultiPartyFinalize>), Error> {
+    check_state!(contract, SetUp, true);
+    if contract.refund_signatures[party_index].is_some() {
+        return Err(Error::InvalidParameters(
+            "Participant already signed the contract.".to_string(),
+        ));
+    }
+
+    let dlc_transactions = contract.get_dlc_transactions()?;
+    verify_party_signatures(
+        secp,
+        contract,
+        &dlc_transactions,
+        party_index,
+        &sign_msg.cet_adaptor_signatures,
+        &sign_msg.refund_signature,
+    )?;
+
+    let mut contract = contract.clone();
+    contract.cet_adaptor_signatures[party_index] = Some(sign_msg.cet_adaptor_signatures.clone());
+    contract.refund_signatures[party_index] = Some(sign_msg.refund_signature);
+
+    if contract.refund_signatures.iter().any(|x| x.is_none()) {
+        return Ok((contract, None));
+    }
+
+    contract.state = MultiPartyContractState::Signed;
+    let finalize_msg = MultiPartyFinalize {
+        protocol_version: PROTOCOL_VERSION,
+        contract_id: contract.id,
+        cet_adaptor_signatures: contract
+            .cet_adaptor_signatures
+            .iter()
+            .flatten()
+            .cloned()
+            .collect(),
+        refund_signatures: contract
+            .refund_signatures
+            .iter()
+            .flatten()
+            .cloned()
+            .collect(),
+    };
+
+    Ok((contract, Some(finalize_msg)))
+}
+
+/// Verifies the signatures of all parties received from the coordinator and
+/// signs the funding inputs of the participant, returning the updated
+/// contract and the [`MultiPartyFunding`] message to send to the coordinator.
+pub fn on_finalize<S: Deref>(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    finalize_msg: &MultiPartyFinalize,
+    signer: &S,
+) -> Result<(MultiPartyContract, MultiPartyFunding), Error>
+where
+    S::Target: ContractSigner,
+{
+    check_state!(contract, SetUp, false);
+    let own_index = contract.own_index();
+    let nb_parties = contract.offer.nb_parties();
+    if finalize_msg.cet_adaptor_signatures.len() != nb_parties
+        || finalize_msg.refund_signatures.len() != nb_parties
+        || Some(&finalize_msg.refund_signatures[own_index])
+            != contract.refund_signatures[own_index].as_ref()
+    {
+        return Err(Error::InvalidParameters(
+            "Signatures don't match the contract.".to_string(),
+        ));
+    }
+
+    let mut dlc_transactions = contract.get_dlc_transactions()?;
+    for (i, (adaptor_sigs, refund_sig)) in finalize_msg
+        .cet_adaptor_signatures
+        .iter()
+        .zip(finalize_msg.refund_signatures.iter())
+        .enumerate()
+        .filter(|(i, _)| *i != own_index)
+    {
+        verify_party_signatures(
+            secp,
+            contract,
+            &dlc_transactions,
+            i,
+            adaptor_sigs,
+            refund_sig,
+        )?;
+    }
+
+    let mut contract = contract.clone();
+    contract.cet_adaptor_signatures = finalize_msg
+        .cet_adaptor_signatures
+        .iter()
+        .cloned()
+        .map(Some)
+        .collect();
+    contract.refund_signatures = finalize_msg
+        .refund_signatures
+        .iter()
+        .cloned()
+        .map(Some)
+        .collect();
+
+    let funding_signatures = sign_funding_inputs(&contract, &mut dlc_transactions.fund, signer)?;
+    contract.funding_signatures[own_index] = Some(funding_signatures.clone());
+    contract.state = MultiPartyContractState::Signed;
+
+    let funding_msg = MultiPartyFunding {
+        protocol_version: PROTOCOL_VERSION,
+        contract_id: contract.id,
+        funding_signatures,
+    };
+
+    Ok((contract, funding_msg))
+}
+
+/// Records the funding input signatures of the participant at `party_index`
+/// as coordinator. Once the signatures of all participants are known, the
+/// funding inputs of the coordinator are signed and the fully signed fund
+/// transaction is returned.
+pub fn on_funding<S: Deref>(
+    contract: &MultiPartyContract,
+    funding_msg: &MultiPartyFunding,
+    party_index: usize,
+    signer: &S,
+) -> Result<(MultiPartyContract, Option<Transaction>), Error>
+where
+    S::Target: ContractSigner,
+{
+    check_state!(contract, Signed, true);
+    let nb_inputs = get_funding_inputs(contract, party_index)?.len();
+    if contract.funding_signatures[party_index].is_some()
+        || funding_msg.funding_signatures.funding_signatures.len() != nb_inputs
+    {
+        return Err(Error::InvalidParameters(
+            "Invalid funding signatures.".to_string(),
+        ));
+    }
+
+    let mut contract = contract.clone();
+    contract.funding_signatures[party_index] = Some(funding_msg.funding_signatures.clone());
+
+    if contract
+        .funding_signatures
+        .iter()
+        .skip(1)
+        .any(|x| x.is_none())
+    {
+        return Ok((contract, None));
+    }
+
+    let mut fund = contract.get_dlc_transactions()?.fund;
+    let own_funding_signatures = sign_funding_inputs(&contract, &mut fund, signer)?;
+    contract.funding_signatures[0] = Some(own_funding_signatures);
+
+    let input_serial_ids = get_input_serial_ids(&contract)?;
+    for i in 1..contract.offer.nb_parties() {
+        let funding_inputs = get_funding_inputs(&contract, i)?;
+        let funding_signatures = contract.funding_signatures[i]
+            .as_ref()
+            .expect("to have the funding signatures of all parties");
+        for (funding_input, funding_signature) in funding_inputs
+            .iter()
+            .zip(funding_signatures.funding_signatures.iter())
+        {
+            let input_index = get_input_index(&input_serial_ids, funding_input)?;
+            fund.input[input_index].witness = Witness::from_vec(
+                funding_signature
+                    .witness_elements
+                    .iter()
+                    .map(|x| x.witness.clone())
+                    .collect(),
+            );
+        }
+    }
+
+    contract.state = MultiPartyContractState::Broadcast;
+
+    Ok((contract, Some(fund)))
+}
+
+/// Signs the CET of the outcome attested by the oracle of a confirmed
+/// contract using the adaptor signatures of the other parties, returning the
+/// closed contract and the CET to broadcast.
+pub fn close_contract<S: Deref>(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    attestation: &OracleAttestation,
+    signer: &S,
+) -> Result<(MultiPartyContract, Transaction), Error>
+where
+    S::Target: ContractSigner,
+{
+    check_state!(contract, Confirmed);
+    let cet_index = match attestation.outcomes.as_slice() {
+        [outcome]
+            if attestation.oracle_public_key
+                == contract.offer.oracle_announcement.oracle_public_key =>
+        {
+            contract
+                .offer
+                .outcomes
+                .iter()
+                .position(|x| &x.outcome == outcome)
+        }
+        _ => None,
+    }
+    .ok_or_else(|| {
+        Error::InvalidParameters("Attestation does not match any outcome.".to_string())
+    })?;
+
+    let dlc_transactions = contract.get_dlc_transactions()?;
+    let adaptor_signatures = get_other_parties_signatures(contract, |i| {
+        contract.cet_adaptor_signatures[i]
+            .as_ref()
+            .and_then(|x| x.ecdsa_adaptor_signatures.get(cet_index))
+            .map(|x| x.signature)
+    })?;
+    let fund_pubkey = get_party_fund_pubkey(contract, contract.own_index())?;
+    let fund_privkey = signer.get_secret_key_for_pubkey(&fund_pubkey)?;
+
+    let mut cet = dlc_transactions.cets[cet_index].clone();
+    dlc::multi_party::sign_cet(
+        secp,
+        &mut cet,
+        &adaptor_signatures,
+        &[attestation.signatures.clone()],
+        &fund_privkey,
+        &dlc_transactions.funding_script_pubkey,
+        dlc_transactions.get_fund_output().value,
+    )?;
+
+    let mut contract = contract.clone();
+    contract.state = MultiPartyContractState::Closed;
+    contract.closing_tx = Some(cet.clone());
+
+    Ok((contract, cet))
+}
+
+/// Signs the refund transaction of a confirmed contract using the refund
+/// signatures of the other parties, returning the refunded contract and the
+/// refund transaction to broadcast. The caller is responsible for checking
+/// that the refund locktime has passed.
+pub fn refund_contract<S: Deref>(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    signer: &S,
+) -> Result<(MultiPartyContract, Transaction), Error>
+where
+    S::Target: ContractSigner,
+{
+    check_state!(contract, Confirmed);
+    let dlc_transactions = contract.get_dlc_transactions()?;
+    let refund_signatures =
+        get_other_parties_signatures(contract, |i| contract.refund_signatures[i])?;
+    let fund_pubkey = get_party_fund_pubkey(contract, contract.own_index())?;
+    let fund_privkey = signer.get_secret_key_for_pubkey(&fund_pubkey)?;
+
+    let mut refund = dlc_transactions.refund.clone();
+    dlc::multi_party::sign_multi_sig_input(
+        secp,
+        &mut refund,
+        &refund_signatures,
+        &fund_privkey,
+        &dlc_transactions.funding_script_pubkey,
+        dlc_transactions.get_fund_output().value,
+        0,
+    )?;
+
+    let mut contract = contract.clone();
+    contract.state = MultiPartyContractState::Refunded;
+    contract.closing_tx = Some(refund.clone());
+
+    Ok((contract, refund))
+}
+
+/// Returns the signature of each other party obtained with `get_signature`,
+/// along with the funding public key of the party.
+fn get_other_parties_signatures<T, F>(
+    contract: &MultiPartyContract,
+    get_signature: F,
+) -> Result<Vec<(PublicKey, T)>, Error>
+where
+    F: Fn(usize) -> Option<T>,
+{
+    (0..contract.offer.nb_parties())
+        .filter(|i| *i != contract.own_index())
+        .map(|i| {
+            let signature = get_signature(i)
+                .ok_or_else(|| Error::InvalidState("Missing signatures of a party.".to_string()))?;
+            Ok((get_party_fund_pubkey(contract, i)?, signature))
+        })
+        .collect()
+}
+
+/// Builds the transactions of a contract for which the parameters of all
+/// parties are known, and sets its id.
+fn set_up_contract(contract: &mut MultiPartyContract) -> Result<DlcTransactions, Error> {
+    let dlc_transactions = contract.get_dlc_transactions()?;
+    contract.id = crate::utils::compute_id(
+        dlc_transactions.fund.txid(),
+        dlc_transactions.get_fund_output_index() as u16,
+        &contract.temporary_id,
+    );
+    contract.state = MultiPartyContractState::SetUp;
+    Ok(dlc_transactions)
+}
+
+fn get_own_signatures<S: Deref>(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    dlc_transactions: &DlcTransactions,
+    signer: &S,
+) -> Result<(CetAdaptorSignatures, Signature), Error>
+where
+    S::Target: ContractSigner,
+{
+    let fund_pubkey = get_party_fund_pubkey(contract, contract.own_index())?;
+    let fund_privkey = signer.get_secret_key_for_pubkey(&fund_pubkey)?;
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+
+    let adaptor_sigs = dlc::create_cet_adaptor_sigs_from_oracle_info(
+        secp,
+        &dlc_transactions.cets,
+        &contract.get_oracle_infos(),
+        &fund_privkey,
+        &dlc_transactions.funding_script_pubkey,
+        fund_output_value,
+        &contract.get_cet_messages(),
+    )?;
+
+    let refund_sig = dlc::util::get_raw_sig_for_tx_input(
+        secp,
+        &dlc_transactions.refund,
+        0,
+        &dlc_transactions.funding_script_pubkey,
+        fund_output_value,
+        &fund_privkey,
+    )?;
+
+    Ok((adaptor_sigs.as_slice().into(), refund_sig))
+}
+
+fn verify_party_signatures(
+    secp: &Secp256k1<All>,
+    contract: &MultiPartyContract,
+    dlc_transactions: &DlcTransactions,
+    party_index: usize,
+    cet_adaptor_signatures: &CetAdaptorSignatures,
+    refund_signature: &Signature,
+) -> Result<(), Error> {
+    let fund_pubkey = get_party_fund_pubkey(contract, party_index)?;
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+
+    dlc::verify_tx_input_sig(
+        secp,
+        refund_signature,
+        &dlc_transactions.refund,
+        0,
+        &dlc_transactions.funding_script_pubkey,
+        fund_output_value,
+        &fund_pubkey,
+    )?;
+
+    let adaptor_sigs: Vec<EcdsaAdaptorSignature> = cet_adaptor_signatures
+        .ecdsa_adaptor_signatures
+        .iter()
+        .map(|x| x.signature)
+        .collect();
+    dlc::multi_party::verify_cet_adaptor_sigs_from_oracle_info(
+        secp,
+        &adaptor_sigs,
+        &dlc_transactions.cets,
+        &contract.get_oracle_infos(),
+        &fund_pubkey,
+        &dlc_transactions.funding_script_pubkey,
+        fund_output_value,
+        &contract.get_cet_messages(),
+    )?;
+
+    Ok(())
+}
+
+/// Signs our own funding inputs in the given fund transaction and returns
+/// their witnesses.
+fn sign_funding_inputs<S: Deref>(
+    contract: &MultiPartyContract,
+    fund: &mut Transaction,
+    signer: &S,
+) -> Result<FundingSignatures, Error>
+where
+    S::Target: ContractSigner,
+{
+    if !signer.can_sign_funding_inputs() {
+        return Err(Error::InvalidState(
+            "Multi party contracts require a signer able to sign funding inputs.".to_string(),
+        ));
+    }
+
+    let input_serial_ids = get_input_serial_ids(contract)?;
+    let funding_signatures = get_funding_inputs(contract, contract.own_index())?
+        .iter()
+        .map(|funding_input| {
+            let input_index = get_input_index(&input_serial_ids, funding_input)?;
+            let tx = Transaction::consensus_decode(&mut funding_input.prev_tx.as_slice()).map_err(
+                |_| {
+                    Error::InvalidParameters(
+                        "Could not decode funding input previous tx parameter".to_string(),
+                    )
+                },
+            )?;
+            let vout = funding_input.prev_tx_vout;
+            let tx_out = tx.output.get(vout as usize).ok_or_else(|| {
+                Error::InvalidParameters(format!("Previous tx output not found at index {}", vout))
+            })?;
+            signer.sign_tx_input(fund, input_index, tx_out, None)?;
+            let witness_elements = fund.input[input_index]
+                .witness
+                .iter()
+                .map(|z| WitnessElement {
+                    witness: z.to_vec(),
+                })
+                .collect();
+            Ok(FundingSignature { witness_elements })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(FundingSignatures { funding_signatures })
+}
+
+fn get_party_fund_pubkey(
+    contract: &MultiPartyContract,
+    party_index: usize,
+) -> Result<PublicKey, Error> {
+    contract.party_params[party_index]
+        .as_ref()
+        .map(|x| x.funding_pubkey)
+        .ok_or_else(|| Error::InvalidState("Missing parameters of a party.".to_string()))
+}
+
+fn get_funding_inputs(
+    contract: &MultiPartyContract,
+    party_index: usize,
+) -> Result<&[FundingInput], Error> {
+    contract.party_params[party_index]
+        .as_ref()
+        .map(|x| x.funding_inputs.as_slice())
+        .ok_or_else(|| Error::InvalidState("Missing parameters of a party.".to_string()))
+}
+
+/// Returns the serial ids of all the funding inputs, in the order of the
+/// inputs of the fund transaction.
+fn get_input_serial_ids(contract: &MultiPartyContract) -> Result<Vec<u64>, Error> {
+    let mut input_serial_ids = Vec::new();
+    for i in 0..contract.offer.nb_parties() {
+        input_serial_ids.extend(
+            get_funding_inputs(contract, i)?
+                .iter()
+                .map(|x| x.input_serial_id),
+        );
+    }
+    input_serial_ids.sort_unstable();
+    Ok(input_serial_ids)
+}
+
+fn get_input_index(input_serial_ids: &[u64], funding_input: &FundingInput) -> Result<usize, Error> {
+    input_serial_ids
+        .iter()
+        .position(|x| x == &funding_input.input_serial_id)
+        .ok_or_else(|| {
+            Error::InvalidState(format!(
+                "Could not find input for serial id {}",
+                funding_input.input_serial_id
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use bitcoin::util::bip32::ExtendedPrivKey;
+    use bitcoin::{Network, Script, Transaction, TxOut};
+    use dlc_messages::multi_party::MultiPartyOutcome;
+    use dlc_messages::oracle_msgs::{EnumEventDescriptor, EventDescriptor};
+    use mocks::dlc_manager::contract::multi_party_contract::{
+        MultiPartyContract, MultiPartyContractInput, MultiPartyContractState,
+    };
+    use mocks::dlc_manager::error::Error;
+    use mocks::dlc_manager::key_derivation::{ContractKeyId, DerivedKeysSigner};
+    use mocks::dlc_manager::multi_party_updater::{
+        accept_contract, close_contract, offer_contract, on_accept, on_finalize, on_funding,
+        on_setup, on_sign, refund_contract,
+    };
+    use mocks::dlc_manager::zeroizing::ZeroizingSecretKey;
+    use mocks::dlc_manager::{ContractSigner, Oracle};
+    use mocks::mock_blockchain::MockBlockchain;
+    use mocks::mock_oracle_provider::MockOracle;
+    use mocks::mock_time::MockTime;
+    use mocks::mock_wallet::MockWallet;
+    use secp256k1_zkp::{ecdsa::Signature, PublicKey, Secp256k1, SecretKey};
+
+    const EVENT_ID: &str = "event";
+    const EVENT_MATURITY: u32 = 1700000000;
+    const REFUND_DELAY: u32 = 86400 * 7;
+    const OUTCOMES: [&str; 3] = ["a", "b", "c"];
+
+    /// Signer deriving distinct keys for each party, pretending to sign the
+    /// funding inputs of the mock wallet.
+    struct TestSigner(DerivedKeysSigner);
+
+    impl ContractSigner for TestSigner {
+        fn get_new_secret_key(&self) -> Result<SecretKey, Error> {
+            self.0.get_new_secret_key()
+        }
+
+        fn derive_contract_secret_key(&self, key_id: &ContractKeyId) -> Result<SecretKey, Error> {
+            self.0.derive_contract_secret_key(key_id)
+        }
+
+        fn sign_tx_input(
+            &self,
+            _tx: &mut Transaction,
+            _input_index: usize,
+            _tx_out: &TxOut,
+            _redeem_script: Option<Script>,
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_secret_key_for_pubkey(
+            &self,
+            pubkey: &PublicKey,
+        ) -> Result<ZeroizingSecretKey, Error> {
+            self.0.get_secret_key_for_pubkey(pubkey)
+        }
+    }
+
+    fn get_oracle() -> MockOracle {
+        let mut oracle = MockOracle::new();
+        oracle.add_event(
+            EVENT_ID,
+            &EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: OUTCOMES.iter().map(|x| x.to_string()).collect(),
+            }),
+            EVENT_MATURITY,
+        );
+        oracle
+    }
+
+    /// Establishes a contract between three parties, each outcome paying out
+    /// the total collateral to one of them. Returns the contract of each
+    /// party, once confirmed, along with its signer.
+    fn establish_contract(oracle: &MockOracle) -> (Vec<MultiPartyContract>, Vec<TestSigner>) {
+        let secp = Secp256k1::new();
+        let blockchain = Rc::new(MockBlockchain::new());
+        let wallets: Vec<_> = (0..3).map(|_| MockWallet::new(&blockchain, 10)).collect();
+        let signers: Vec<_> = (1..=3u8)
+            .map(|i| {
+                TestSigner(DerivedKeysSigner::new(
+                    ExtendedPrivKey::new_master(Network::Regtest, &[i; 32]).unwrap(),
+                ))
+            })
+            .collect();
+        let node_ids: Vec<_> = (1..=3u8)
+            .map(|i| PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[i; 32]).unwrap()))
+            .collect();
+
+        let contract_input = MultiPartyContractInput {
+            collaterals: vec![3000000, 2000000, 1000000],
+            fee_rate: 2,
+            outcomes: OUTCOMES
+                .iter()
+                .enumerate()
+                .map(|(i, outcome)| {
+                    let mut payouts = vec![0; 3];
+                    payouts[i] = 6000000;
+                    MultiPartyOutcome {
+                        outcome: outcome.to_string(),
+                        payouts,
+                    }
+                })
+                .collect(),
+            oracle_public_key: oracle.get_public_key(),
+            event_id: EVENT_ID.to_string(),
+        };
+
+        let (coordinator_contract, offers) = offer_contract(
+            &secp,
+            &contract_input,
+            oracle.get_announcement(EVENT_ID).unwrap(),
+            &node_ids[1..],
+            REFUND_DELAY,
+            &&wallets[0],
+            &&signers[0],
+            &blockchain,
+            &&MockTime {},
+        )
+        .unwrap();
+
+        let mut contracts = vec![coordinator_contract];
+        let mut accept_msgs = Vec::new();
+        for (i, offer) in offers.into_iter().enumerate() {
+            let contract = MultiPartyContract::new(offer, Some(node_ids[0]));
+            let (contract, accept_msg) = accept_contract(
+                &secp,
+                &contract,
+                &&wallets[i + 1],
+                &&signers[i + 1],
+                &blockchain,
+            )
+            .unwrap();
+            contracts.push(contract);
+            accept_msgs.push(accept_msg);
+        }
+
+        let mut setup_msg = None;
+        for (i, accept_msg) in accept_msgs.iter().enumerate() {
+            let (contract, msg) =
+                on_accept(&secp, &contracts[0], accept_msg, i + 1, &&signers[0]).unwrap();
+            contracts[0] = contract;
+            setup_msg = msg;
+        }
+        let setup_msg = setup_msg.expect("all participants to have accepted");
+
+        let mut sign_msgs = Vec::new();
+        for i in 1..3 {
+            let (contract, sign_msg) =
+                on_setup(&secp, &contracts[i], &setup_msg, &&signers[i]).unwrap();
+            contracts[i] = contract;
+            sign_msgs.push(sign_msg);
+        }
+
+        let mut finalize_msg = None;
+        for (i, sign_msg) in sign_msgs.iter().enumerate() {
+            let (contract, msg) = on_sign(&secp, &contracts[0], sign_msg, i + 1).unwrap();
+            contracts[0] = contract;
+            finalize_msg = msg;
+        }
+        let finalize_msg = finalize_msg.expect("all participants to have signed");
+
+        let mut funding_msgs = Vec::new();
+        for i in 1..3 {
+            let (contract, funding_msg) =
+                on_finalize(&secp, &contracts[i], &finalize_msg, &&signers[i]).unwrap();
+            contracts[i] = contract;
+            funding_msgs.push(funding_msg);
+        }
+
+        let mut fund_tx = None;
+        for (i, funding_msg) in funding_msgs.iter().enumerate() {
+            let (contract, tx) =
+                on_funding(&contracts[0], funding_msg, i + 1, &&signers[0]).unwrap();
+            contracts[0] = contract;
+            fund_tx = tx;
+        }
+        assert!(fund_tx.is_some());
+        assert_eq!(MultiPartyContractState::Broadcast, contracts[0].state);
+
+        for contract in &mut contracts {
+            contract.state = MultiPartyContractState::Confirmed;
+        }
+
+        (contracts, signers)
+    }
+
+    /// Checks that the given transaction spending the fund output of the
+    /// contract carries a valid signature of each party.
+    fn assert_signed_by_all_parties(contract: &MultiPartyContract, tx: &Transaction) {
+        let secp = Secp256k1::new();
+        let dlc_transactions = contract.get_dlc_transactions().unwrap();
+        let mut pubkeys: Vec<_> = contract
+            .party_params
+            .iter()
+            .map(|x| x.as_ref().unwrap().funding_pubkey)
+            .collect();
+        pubkeys.sort();
+
+        let witness = tx.input[0].witness.to_vec();
+        assert_eq!(pubkeys.len() + 2, witness.len());
+        for (pubkey, sig) in pubkeys.iter().zip(witness[1..].iter()) {
+            let sig = Signature::from_der(&sig[..sig.len() - 1]).unwrap();
+            dlc::verify_tx_input_sig(
+                &secp,
+                &sig,
+                tx,
+                0,
+                &dlc_transactions.funding_script_pubkey,
+                dlc_transactions.get_fund_output().value,
+                pubkey,
+            )
+            .expect("the signature of each party to be valid");
+        }
+    }
+
+    #[test]
+    fn contract_is_closed_with_cet_of_attested_outcome() {
+        let secp = Secp256k1::new();
+        let mut oracle = get_oracle();
+        let (contracts, signers) = establish_contract(&oracle);
+        oracle.add_attestation(EVENT_ID, &[OUTCOMES[1].to_string()]);
+        let attestation = oracle.get_attestation(EVENT_ID).unwrap();
+        let cet_txid = contracts[0].get_dlc_transactions().unwrap().cets[1].txid();
+
+        for (contract, signer) in contracts.iter().zip(signers.iter()) {
+            let (closed_contract, cet) =
+                close_contract(&secp, contract, &attestation, &signer).unwrap();
+            assert_eq!(MultiPartyContractState::Closed, closed_contract.state);
+            assert_eq!(Some(&cet), closed_contract.closing_tx.as_ref());
+            assert_eq!(cet_txid, cet.txid());
+            assert_signed_by_all_parties(contract, &cet);
+        }
+    }
+
+    #[test]
+    fn contract_is_only_closed_once_confirmed_with_attested_outcome() {
+        let secp = Secp256k1::new();
+        let mut oracle = get_oracle();
+        let (mut contracts, signers) = establish_contract(&oracle);
+
+        oracle.add_attestation(EVENT_ID, &["d".to_string()]);
+        let attestation = oracle.get_attestation(EVENT_ID).unwrap();
+        close_contract(&secp, &contracts[0], &attestation, &&signers[0])
+            .expect_err("attestation of unknown outcome to be rejected");
+
+        oracle.add_attestation(EVENT_ID, &[OUTCOMES[0].to_string()]);
+        let attestation = oracle.get_attestation(EVENT_ID).unwrap();
+        contracts[0].state = MultiPartyContractState::Broadcast;
+        close_contract(&secp, &contracts[0], &attestation, &&signers[0])
+            .expect_err("unconfirmed contract not to be closed");
+        refund_contract(&secp, &contracts[0], &&signers[0])
+            .expect_err("unconfirmed contract not to be refunded");
+    }
+
+    #[test]
+    fn contract_is_refunded_with_signatures_of_all_parties() {
+        let secp = Secp256k1::new();
+        let oracle = get_oracle();
+        let (contracts, signers) = establish_contract(&oracle);
+        let refund_txid = contracts[0].get_dlc_transactions().unwrap().refund.txid();
+
+        for (contract, signer) in contracts.iter().zip(signers.iter()) {
+            let (refunded_contract, refund) = refund_contract(&secp, contract, &signer).unwrap();
+            assert_eq!(MultiPartyContractState::Refunded, refunded_contract.state);
+            assert_eq!(Some(&refund), refunded_contract.closing_tx.as_ref());
+            assert_eq!(refund_txid, refund.txid());
+            assert_signed_by_all_parties(contract, &refund);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! #NostrTransport
+//! Exchange of DLC messages as NIP-04 encrypted direct messages published on
+//! Nostr relays, enabling peers to negotiate contracts without being able to
+//! connect to each other directly.
+//!
+//! Messages are serialized with their type prefix and hex encoded. As relays
+//! limit the size of events, messages larger than [`MAX_CHUNK_SIZE`] bytes
+//! are split over several direct messages which are reassembled on
+//! reception. Peers are identified by the public key with even parity
+//! corresponding to their Nostr public key.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, Hash};
+use dlc_messages::Message;
+use log::{error, warn};
+use nostr::nips::nip04;
+use nostr::{Event, EventBuilder, EventId, Filter, Keys, Kind, Timestamp};
+use secp256k1_zkp::{PublicKey, SecretKey};
+
+use crate::custom_message_handler::DlcMessageProcessor;
+use crate::error::Error;
+
+/// The maximum number of bytes of a serialized message sent in a single
+/// direct message.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024;
+
+/// The maximum number of direct messages a message can be split into.
+pub const MAX_NB_CHUNKS: usize = 64;
+
+const CONTENT_PREFIX: &str = "dlc1";
+
+/// Provides access to Nostr relays.
+pub trait NostrRelay {
+    /// Publishes the given event.
+    fn publish_event(&self, event: &Event) -> Result<(), Error>;
+    /// Returns the stored events matching the given filter.
+    fn get_events(&self, filter: &Filter) -> Result<Vec<Event>, Error>;
+}
+
+/// Returns the contents of the direct messages used to send the given
+/// message.
+pub fn get_message_contents(msg: &Message) -> Vec<String> {
+    let payload = msg.encode_with_type();
+    let id = sha256::Hash::hash(&payload)[..8].to_hex();
+    let nb_chunks = (payload.len() + MAX_CHUNK_SIZE - 1) / MAX_CHUNK_SIZE;
+    payload
+        .chunks(MAX_CHUNK_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            format!(
+                "{}:{}:{}:{}:{}",
+                CONTENT_PREFIX,
+                id,
+                i,
+                nb_chunks,
+                chunk.to_hex()
+            )
+        })
+        .collect()
+}
+
+struct PartialMessage {
+    id: String,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+/// Reconstructs messages from the contents of direct messages, keeping at
+/// most one partially received message per peer.
+#[derive(Default)]
+pub struct MessageReassembler {
+    partial_messages: HashMap<PublicKey, PartialMessage>,
+}
+
+impl MessageReassembler {
+    /// Creates a new empty reassembler.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Processes the content of a direct message received from the given
+    /// peer, returning the message if it is complete. Receiving a chunk of a
+    /// new message from a peer discards its previous partial message.
+    pub fn process_content(
+        &mut self,
+        peer: PublicKey,
+        content: &str,
+    ) -> Result<Option<Message>, Error> {
+        let parts = content.split(':').collect::<Vec<_>>();
+        if parts.len() != 5 || parts[0] != CONTENT_PREFIX {
+            return Err(invalid_content("unexpected format"));
+        }
+        let index: usize = parts[2]
+            .parse()
+            .map_err(|_| invalid_content("invalid chunk index"))?;
+        let nb_chunks: usize = parts[3]
+            .parse()
+            .map_err(|_| invalid_content("invalid number of chunks"))?;
+        if nb_chunks == 0 || nb_chunks > MAX_NB_CHUNKS || index >= nb_chunks {
+            return Err(invalid_content("invalid chunk index"));
+        }
+        let chunk = Vec::<u8>::from_hex(parts[4]).map_err(|_| invalid_content("invalid hex"))?;
+        if chunk.len() > MAX_CHUNK_SIZE {
+            return Err(invalid_content("chunk too large"));
+        }
+
+        let id = parts[1];
+        let partial = self
+            .partial_messages
+            .entry(peer)
+            .or_insert_with(|| PartialMessage {
+                id: id.to_string(),
+                chunks: vec![None; nb_chunks],
+            });
+        if partial.id != id || partial.chunks.len() != nb_chunks {
+            *partial = PartialMessage {
+                id: id.to_string(),
+                chunks: vec![None; nb_chunks],
+            };
+        }
+        partial.chunks[index] = Some(chunk);
+
+        if partial.chunks.iter().any(|c| c.is_none()) {
+            return Ok(None);
+        }
+
+        let partial = self
+            .partial_messages
+            .remove(&peer)
+            .expect("to have a partial message");
+        let payload = partial
+            .chunks
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect::<Vec<_>>();
+        if sha256::Hash::hash(&payload)[..8].to_hex() != partial.id {
+            return Err(invalid_content("message id mismatch"));
+        }
+        let msg = Message::read_with_type(&mut Cursor::new(payload))
+            .map_err(|e| invalid_content(&format!("could not decode message {}", e)))?;
+        Ok(Some(msg))
+    }
+}
+
+/// Sends and receives DLC messages through Nostr relays, passing the received
+/// messages to a [`DlcMessageProcessor`] and sending back its replies.
+pub struct NostrTransport<R: Deref, P: Deref>
+where
+    R::Target: NostrRelay,
+    P::Target: DlcMessageProcessor,
+{
+    relay: R,
+    processor: P,
+    keys: Keys,
+    reassembler: Mutex<MessageReassembler>,
+    // Timestamp from which events are requested, and ids of the events
+    // already processed with that timestamp.
+    last_seen: Mutex<(Timestamp, Vec<EventId>)>,
+}
+
+impl<R: Deref, P: Deref> NostrTransport<R, P>
+where
+    R::Target: NostrRelay,
+    P::Target: DlcMessageProcessor,
+{
+    /// Creates a new instance using the given secret key as Nostr identity
+    /// and processing the direct messages published from now on.
+    pub fn new(relay: R, processor: P, secret_key: &SecretKey) -> Result<Self, Error> {
+        let secret_key = nostr::secp256k1::SecretKey::from_slice(secret_key.as_ref())
+            .map_err(|e| Error::TransportError(e.to_string()))?;
+        Ok(NostrTransport {
+            relay,
+            processor,
+            keys: Keys::new(secret_key),
+            reassembler: Mutex::new(MessageReassembler::new()),
+            last_seen: Mutex::new((Timestamp::now(), Vec::new())),
+        })
+    }
+
+    /// Returns the id under which peers know this node.
+    pub fn node_id(&self) -> PublicKey {
+        to_node_id(&self.keys.public_key())
+    }
+
+    /// Returns the filter matching the direct messages sent to this node from
+    /// the given timestamp.
+    pub fn subscription_filter(&self, since: Timestamp) -> Filter {
+        Filter::new()
+            .kind(Kind::EncryptedDirectMessage)
+            .pubkey(self.keys.public_key())
+            .since(since)
+    }
+
+    /// Sends the given message to the peer with the given id.
+    pub fn send_message(&self, peer: &PublicKey, msg: &Message) -> Result<(), Error> {
+        let receiver = nostr::secp256k1::XOnlyPublicKey::from_slice(&peer.serialize()[1..])
+            .map_err(|e| Error::TransportError(e.to_string()))?;
+        for content in get_message_contents(msg) {
+            let event = EventBuilder::new_encrypted_direct_msg(&self.keys, receiver, content, None)
+                .and_then(|b| b.to_event(&self.keys))
+                .map_err(|e| Error::TransportError(e.to_string()))?;
+            self.relay.publish_event(&event)?;
+        }
+        Ok(())
+    }
+
+    /// Fetches the direct messages received since the last call, processing
+    /// the complete DLC messages and sending the resulting replies. Invalid
+    /// direct messages and processing errors are logged and skipped. Should
+    /// be called periodically.
+    pub fn process_events(&self) -> Result<(), Error> {
+        let mut last_seen = self.last_seen.lock().unwrap();
+        let mut events = self
+            .relay
+            .get_events(&self.subscription_filter(last_seen.0))?;
+        events.retain(|e| !last_seen.1.contains(&e.id));
+        events.sort_by_key(|e| e.created_at);
+
+        for event in events {
+            if event.created_at > last_seen.0 {
+                *last_seen = (event.created_at, Vec::new());
+            }
+            last_seen.1.push(event.id);
+
+            if let Err(e) = self.process_event(&event) {
+                warn!("Could not process event {}: {}", event.id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process_event(&self, event: &Event) -> Result<(), Error> {
+        if event.kind != Kind::EncryptedDirectMessage {
+            return Err(Error::TransportError("Unexpected event kind.".to_string()));
+        }
+        event
+            .verify()
+            .map_err(|e| Error::TransportError(e.to_string()))?;
+        let secret_key = self
+            .keys
+            .secret_key()
+            .map_err(|e| Error::TransportError(e.to_string()))?;
+        let content = nip04::decrypt(&secret_key, &event.pubkey, &event.content)
+            .map_err(|e| Error::TransportError(e.to_string()))?;
+
+        let peer = to_node_id(&event.pubkey);
+        let msg = match self
+            .reassembler
+            .lock()
+            .unwrap()
+            .process_content(peer, &content)?
+        {
+            Some(msg) => msg,
+            None => return Ok(()),
+        };
+
+        match self.processor.process_dlc_message(&msg, peer) {
+            Ok(Some(reply)) => self.send_message(&peer, &reply),
+            Ok(None) => Ok(()),
+            Err(e) => {
+                error!("Error processing message from {}: {}", peer, e);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn to_node_id(public_key: &nostr::secp256k1::XOnlyPublicKey) -> PublicKey {
+    let mut buf = [2u8; 33];
+    buf[1..].copy_from_slice(&public_key.serialize());
+    PublicKey::from_slice(&buf).expect("a valid x only public key")
+}
+
+fn invalid_content(reason: &str) -> Error {
+    Error::TransportError(format!("Invalid direct message content: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dlc_messages::channel::Reject;
+    use dlc_messages::AcceptDlc;
+    use secp256k1_zkp::Secp256k1;
+
+    fn get_peer() -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[1; 32]).unwrap())
+    }
+
+    #[test]
+    fn small_message_uses_single_chunk() {
+        let msg = Message::Reject(Reject {
+            channel_id: [1; 32],
+        });
+        let contents = get_message_contents(&msg);
+        assert_eq!(1, contents.len());
+
+        let mut reassembler = MessageReassembler::new();
+        let received = reassembler
+            .process_content(get_peer(), &contents[0])
+            .unwrap()
+            .expect("a complete message");
+        assert_eq!(msg.encode_with_type(), received.encode_with_type());
+    }
+
+    #[test]
+    fn large_message_is_reassembled() {
+        let accept: AcceptDlc = serde_json::from_str(include_str!(
+            "../../dlc-messages/src/test_inputs/accept_msg.json"
+        ))
+        .unwrap();
+        let msg = Message::Accept(accept);
+        let mut contents = get_message_contents(&msg);
+        assert!(contents.len() > 1);
+        contents.reverse();
+
+        let mut reassembler = MessageReassembler::new();
+        let last = contents.pop().unwrap();
+        for content in &contents {
+            assert!(reassembler
+                .process_content(get_peer(), content)
+                .unwrap()
+                .is_none());
+        }
+        let received = reassembler
+            .process_content(get_peer(), &last)
+            .unwrap()
+            .expect("a complete message");
+        assert_eq!(msg.encode_with_type(), received.encode_with_type());
+    }
+
+    #[test]
+    fn invalid_content_is_rejected() {
+        let mut reassembler = MessageReassembler::new();
+        for content in [
+            "hello",
+            "dlc1:00:0:0:00",
+            "dlc1:00:1:1:00",
+            "dlc1:00:0:1:zz",
+        ] {
+            reassembler
+                .process_content(get_peer(), content)
+                .expect_err("invalid content");
+        }
+        reassembler
+            .process_content(get_peer(), "dlc1:0000000000000000:0:1:0000")
+            .expect_err("message id mismatch");
+    }
+}
@@ -0,0 +1,992 @@
+//! # This module contains static functions to update the state of the transfer
+//! of the side of a party in a contract to a new party, see
+//! [`dlc_messages::novation`] for a description of the protocol.
+
+use std::ops::Deref;
+
+use bitcoin::{consensus::Decodable, OutPoint, Script, Transaction, TxOut};
+use dlc::{novation::FUND_INPUT_INDEX, DlcTransactions, PartyParams};
+use dlc_messages::novation::{
+    NovationAccept, NovationConfirm, NovationFinalize, NovationOffer, NovationRequest,
+    NovationTerms,
+};
+use dlc_messages::{CetAdaptorSignatures, FundingSignatures};
+use secp256k1_zkp::{
+    ecdsa::Signature, All, EcdsaAdaptorSignature, PublicKey, Secp256k1, SecretKey,
+};
+
+use crate::{
+    contract::{
+        accepted_contract::AcceptedContract,
+        novation_contract::{NovationContract, NovationRole, NovationState},
+        offered_contract::OfferedContract,
+        signed_contract::SignedContract,
+        AdaptorInfo, FundingInputInfo,
+    },
+    conversion_utils::{
+        get_contract_info_and_announcements, get_tx_input_infos, BITCOIN_CHAINHASH,
+        PROTOCOL_VERSION,
+    },
+    error::Error,
+    manager::NB_CONFIRMATIONS,
+    Blockchain, ContractSigner, Wallet,
+};
+
+macro_rules! check_state {
+    ($contract: expr, $state: ident, $role: ident) => {
+        if $contract.state != NovationState::$state || $contract.role() != NovationRole::$role {
+            return Err(Error::InvalidState(format!(
+                "Invalid state {:?} expected {}.",
+                $contract.state,
+                stringify!($state)
+            )));
+        }
+    };
+}
+
+/// The contract obtained by replacing the transferor with the transferee,
+/// before the signatures of the parties are known.
+struct NovatedContract {
+    offered_contract: OfferedContract,
+    accept_params: PartyParams,
+    funding_inputs: Vec<FundingInputInfo>,
+    dlc_transactions: DlcTransactions,
+}
+
+/// Creates a [`NovationContract`] as transferor of the given confirmed
+/// contract, and the [`NovationOffer`] message to send to the transferee.
+/// The transferee pays `price` to an address of the wallet, as well as the
+/// fees of the novation transaction at the given fee rate.
+pub fn offer_novation<W: Deref>(
+    contract: &SignedContract,
+    transferee: &PublicKey,
+    price: u64,
+    fee_rate_per_vb: u64,
+    wallet: &W,
+) -> Result<(NovationContract, NovationOffer), Error>
+where
+    W::Target: Wallet,
+{
+    if contract.channel_id.is_some() {
+        return Err(Error::InvalidParameters(
+            "Contracts within channels cannot be transferred.".to_string(),
+        ));
+    }
+    let offered_contract = &contract.accepted_contract.offered_contract;
+    if !offered_contract
+        .offer_params
+        .additional_payout_outputs
+        .is_empty()
+        || !contract
+            .accepted_contract
+            .accept_params
+            .additional_payout_outputs
+            .is_empty()
+    {
+        return Err(Error::InvalidParameters(
+            "Contracts with additional payout outputs cannot be transferred.".to_string(),
+        ));
+    }
+    if transferee == &offered_contract.counter_party {
+        return Err(Error::InvalidParameters(
+            "The contract cannot be transferred to the counter party.".to_string(),
+        ));
+    }
+    dlc::util::validate_fee_rate(fee_rate_per_vb)
+        .map_err(|_| Error::InvalidParameters("Fee rate too high.".to_string()))?;
+
+    let terms = NovationTerms {
+        price,
+        price_spk: wallet.get_new_address()?.script_pubkey(),
+        price_serial_id: crate::utils::get_new_serial_id(),
+        fund_output_serial_id: crate::utils::get_new_serial_id(),
+        fee_rate_per_vb,
+    };
+    let offer = get_novation_offer(contract, true, offered_contract.counter_party, terms);
+
+    let novation = NovationContract {
+        contract_id: offer.contract_id,
+        state: NovationState::Offered,
+        transferor: None,
+        remaining_party: Some(offered_contract.counter_party),
+        transferee: Some(*transferee),
+        offer: offer.clone(),
+        accept: None,
+        novation_transaction: None,
+        novated_contract: None,
+    };
+
+    Ok((novation, offer))
+}
+
+/// Creates a [`NovationContract`] as transferee from the given offer, after
+/// checking that the fund output of the contract is confirmed and locked to
+/// the keys of the transferor and of the remaining party.
+pub fn on_novation_offer<B: Deref>(
+    offer: &NovationOffer,
+    transferor: PublicKey,
+    blockchain: &B,
+) -> Result<NovationContract, Error>
+where
+    B::Target: Blockchain,
+{
+    offer.validate()?;
+    let contract_id = crate::utils::compute_id(
+        offer.fund_txid,
+        offer.fund_output_index,
+        &offer.temporary_contract_id,
+    );
+    if contract_id != offer.contract_id {
+        return Err(Error::InvalidParameters(
+            "Contract id does not match the fund output.".to_string(),
+        ));
+    }
+
+    if blockchain.get_transaction_confirmations(&offer.fund_txid)? < NB_CONFIRMATIONS {
+        return Err(Error::InvalidParameters(
+            "Fund transaction of the contract is not confirmed.".to_string(),
+        ));
+    }
+    let fund_tx = blockchain.get_transaction(&offer.fund_txid)?;
+    let funding_script_pubkey = get_funding_script_pubkey(offer);
+    match fund_tx.output.get(offer.fund_output_index as usize) {
+        Some(o)
+            if o.value == offer.fund_output_value
+                && o.script_pubkey == funding_script_pubkey.to_v0_p2wsh() => {}
+        _ => {
+            return Err(Error::InvalidParameters(
+                "Fund output does not match the offer.".to_string(),
+            ))
+        }
+    }
+
+    Ok(NovationContract {
+        contract_id: offer.contract_id,
+        state: NovationState::Offered,
+        transferor: Some(transferor),
+        remaining_party: Some(offer.remaining_party),
+        transferee: None,
+        offer: offer.clone(),
+        accept: None,
+        novation_transaction: None,
+        novated_contract: None,
+    })
+}
+
+/// Accepts a transfer offered to us, returning the updated [`NovationContract`]
+/// and the [`NovationAccept`] message to send to the transferor.
+pub fn accept_novation<W: Deref, S: Deref, B: Deref>(
+    secp: &Secp256k1<All>,
+    novation: &NovationContract,
+    wallet: &W,
+    signer: &S,
+    blockchain: &B,
+) -> Result<(NovationContract, NovationAccept), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+    B::Target: Blockchain,
+{
+    check_state!(novation, Offered, Transferee);
+    let offer = &novation.offer;
+    let transferor = novation
+        .transferor
+        .ok_or_else(|| Error::InvalidState("Missing transferor.".to_string()))?;
+    let remaining_party = novation
+        .remaining_party
+        .ok_or_else(|| Error::InvalidState("Missing remaining party.".to_string()))?;
+
+    let (party_params, fund_privkey, funding_inputs_info) = crate::utils::get_party_params(
+        secp,
+        &transferor,
+        &offer.contract_id,
+        offer.terms.price,
+        offer.terms.fee_rate_per_vb,
+        None,
+        None,
+        wallet,
+        signer,
+        blockchain,
+    )?;
+
+    let mut accept_msg = NovationAccept {
+        protocol_version: PROTOCOL_VERSION,
+        contract_id: offer.contract_id,
+        funding_pubkey: party_params.fund_pubkey,
+        payout_spk: party_params.payout_script_pubkey,
+        funding_inputs: funding_inputs_info.iter().map(|x| x.into()).collect(),
+        change_spk: party_params.change_script_pubkey,
+        change_serial_id: party_params.change_serial_id,
+        cet_adaptor_signatures: CetAdaptorSignatures {
+            ecdsa_adaptor_signatures: Vec::new(),
+        },
+        refund_signature: Signature::from_compact(&[1; 64]).expect("to be a valid signature"),
+    };
+
+    let novated_contract = get_novated_contract(
+        offer,
+        &accept_msg,
+        remaining_party,
+        offer.transferor_is_offer_party,
+    )?;
+    let dlc_transactions = &novated_contract.dlc_transactions;
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+    let (_, adaptor_sigs) = get_adaptor_infos_and_signatures(
+        secp,
+        &novated_contract.offered_contract,
+        dlc_transactions,
+        &fund_privkey,
+    )?;
+    accept_msg.cet_adaptor_signatures = adaptor_sigs.as_slice().into();
+    accept_msg.refund_signature = dlc::util::get_raw_sig_for_tx_input(
+        secp,
+        &dlc_transactions.refund,
+        0,
+        &dlc_transactions.funding_script_pubkey,
+        fund_output_value,
+        &fund_privkey,
+    )?;
+
+    let mut novation = novation.clone();
+    novation.state = NovationState::Accepted;
+    novation.accept = Some(accept_msg.clone());
+    novation.novation_transaction = Some(novated_contract.dlc_transactions.fund);
+
+    Ok((novation, accept_msg))
+}
+
+/// Records the parameters of the transferee as transferor, returning the
+/// updated [`NovationContract`] and the [`NovationRequest`] to send to the
+/// remaining party.
+pub fn on_novation_accept(
+    novation: &NovationContract,
+    accept_msg: &NovationAccept,
+) -> Result<(NovationContract, NovationRequest), Error> {
+    check_state!(novation, Offered, Transferor);
+    let transferee = novation
+        .transferee
+        .ok_or_else(|| Error::InvalidState("Missing transferee.".to_string()))?;
+
+    let (novation_tx, _) = create_novation_transaction(&novation.offer, accept_msg)?;
+
+    let mut novation = novation.clone();
+    novation.state = NovationState::Accepted;
+    novation.accept = Some(accept_msg.clone());
+    novation.novation_transaction = Some(novation_tx);
+
+    let request = NovationRequest {
+        protocol_version: PROTOCOL_VERSION,
+        contract_id: novation.contract_id,
+        transferee,
+        offer: novation.offer.clone(),
+        accept: accept_msg.clone(),
+    };
+
+    Ok((novation, request))
+}
+
+/// Verifies the signatures of the transferee as remaining party of the given
+/// contract and signs the novated contract and the fund input of the novation
+/// transaction, returning the [`NovationContract`] and the
+/// [`NovationConfirm`] message to send to the transferor.
+pub fn on_novation_request<S: Deref>(
+    secp: &Secp256k1<All>,
+    contract: &SignedContract,
+    request: &NovationRequest,
+    signer: &S,
+) -> Result<(NovationContract, NovationConfirm), Error>
+where
+    S::Target: ContractSigner,
+{
+    let accepted_contract = &contract.accepted_contract;
+    let offered_contract = &accepted_contract.offered_contract;
+    let transferor = offered_contract.counter_party;
+    let offer = &request.offer;
+    if request.transferee == transferor || request.contract_id != offer.contract_id {
+        return Err(Error::InvalidParameters(
+            "Invalid novation request.".to_string(),
+        ));
+    }
+
+    // The contract info is not compared as the signatures of the transferee
+    // are verified against our own transactions.
+    let mut expected_offer =
+        get_novation_offer(contract, false, offer.remaining_party, offer.terms.clone());
+    expected_offer.contract_info = offer.contract_info.clone();
+    if &expected_offer != offer
+        || contract.channel_id.is_some()
+        || !offered_contract
+            .offer_params
+            .additional_payout_outputs
+            .is_empty()
+        || !accepted_contract
+            .accept_params
+            .additional_payout_outputs
+            .is_empty()
+    {
+        return Err(Error::InvalidParameters(
+            "Novation offer does not match the contract.".to_string(),
+        ));
+    }
+    dlc::util::validate_fee_rate(offer.terms.fee_rate_per_vb)?;
+
+    let novated_contract = get_novated_contract(
+        offer,
+        &request.accept,
+        request.transferee,
+        !offer.transferor_is_offer_party,
+    )?;
+    verify_signatures(
+        secp,
+        &novated_contract,
+        &accepted_contract.adaptor_infos,
+        &request.accept.funding_pubkey,
+        &request.accept.cet_adaptor_signatures,
+        &request.accept.refund_signature,
+    )?;
+
+    let fund_privkey = signer.get_secret_key_for_pubkey(&offer.remaining_funding_pubkey)?;
+    let dlc_transactions = &novated_contract.dlc_transactions;
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+    let mut adaptor_sigs = Vec::new();
+    for (contract_info, adaptor_info) in novated_contract
+        .offered_contract
+        .contract_info
+        .iter()
+        .zip(accepted_contract.adaptor_infos.iter())
+    {
+        adaptor_sigs.extend(contract_info.get_adaptor_signatures(
+            secp,
+            adaptor_info,
+            &fund_privkey,
+            &dlc_transactions.funding_script_pubkey,
+            fund_output_value,
+            &dlc_transactions.cets,
+        )?);
+    }
+    let refund_signature = dlc::util::get_raw_sig_for_tx_input(
+        secp,
+        &dlc_transactions.refund,
+        0,
+        &dlc_transactions.funding_script_pubkey,
+        fund_output_value,
+        &fund_privkey,
+    )?;
+    let fund_input_signature = dlc::util::get_raw_sig_for_tx_input(
+        secp,
+        &dlc_transactions.fund,
+        FUND_INPUT_INDEX,
+        &accepted_contract.dlc_transactions.funding_script_pubkey,
+        offer.fund_output_value,
+        &fund_privkey,
+    )?;
+
+    let novation_tx = dlc_transactions.fund.clone();
+    let signed_contract = get_signed_contract(
+        novated_contract,
+        accepted_contract.adaptor_infos.clone(),
+        &request.accept.cet_adaptor_signatures,
+        refund_signature,
+        request.accept.refund_signature,
+    );
+
+    let novation = NovationContract {
+        contract_id: offer.contract_id,
+        state: NovationState::Confirmed,
+        transferor: Some(transferor),
+        remaining_party: None,
+        transferee: Some(request.transferee),
+        offer: offer.clone(),
+        accept: Some(request.accept.clone()),
+        novation_transaction: Some(novation_tx),
+        novated_contract: Some(signed_contract),
+    };
+
+    let confirm_msg = NovationConfirm {
+        protocol_version: PROTOCOL_VERSION,
+        contract_id: offer.contract_id,
+        cet_adaptor_signatures: adaptor_sigs.as_slice().into(),
+        refund_signature,
+        fund_input_signature,
+    };
+
+    Ok((novation, confirm_msg))
+}
+
+/// Verifies the signature of the remaining party for the fund input of the
+/// novation transaction as transferor and adds our own, returning the updated
+/// [`NovationContract`] and the [`NovationFinalize`] message to send to the
+/// transferee.
+pub fn on_novation_confirm<S: Deref>(
+    secp: &Secp256k1<All>,
+    novation: &NovationContract,
+    confirm_msg: &NovationConfirm,
+    signer: &S,
+) -> Result<(NovationContract, NovationFinalize), Error>
+where
+    S::Target: ContractSigner,
+{
+    check_state!(novation, Accepted, Transferor);
+    let offer = &novation.offer;
+    let novation_tx = novation
+        .novation_transaction
+        .as_ref()
+        .ok_or_else(|| Error::InvalidState("Missing novation transaction.".to_string()))?;
+    let funding_script_pubkey = get_funding_script_pubkey(offer);
+
+    dlc::verify_tx_input_sig(
+        secp,
+        &confirm_msg.fund_input_signature,
+        novation_tx,
+        FUND_INPUT_INDEX,
+        &funding_script_pubkey,
+        offer.fund_output_value,
+        &offer.remaining_funding_pubkey,
+    )?;
+
+    let fund_privkey = signer.get_secret_key_for_pubkey(&offer.transferor_funding_pubkey)?;
+    let transferor_fund_input_signature = dlc::util::get_raw_sig_for_tx_input(
+        secp,
+        novation_tx,
+        FUND_INPUT_INDEX,
+        &funding_script_pubkey,
+        offer.fund_output_value,
+        &fund_privkey,
+    )?;
+
+    let mut novation = novation.clone();
+    novation.state = NovationState::Confirmed;
+
+    let finalize_msg = NovationFinalize {
+        protocol_version: PROTOCOL_VERSION,
+        contract_id: novation.contract_id,
+        cet_adaptor_signatures: confirm_msg.cet_adaptor_signatures.clone(),
+        refund_signature: confirm_msg.refund_signature,
+        transferor_fund_input_signature,
+        remaining_fund_input_signature: confirm_msg.fund_input_signature,
+    };
+
+    Ok((novation, finalize_msg))
+}
+
+/// Verifies the signatures of the remaining party and of the transferor as
+/// transferee and signs our funding inputs, returning the updated
+/// [`NovationContract`] holding the novated contract and the fully signed
+/// novation transaction to broadcast.
+pub fn on_novation_finalize<S: Deref>(
+    secp: &Secp256k1<All>,
+    novation: &NovationContract,
+    finalize_msg: &NovationFinalize,
+    signer: &S,
+) -> Result<(NovationContract, Transaction), Error>
+where
+    S::Target: ContractSigner,
+{
+    check_state!(novation, Accepted, Transferee);
+    if !signer.can_sign_funding_inputs() {
+        return Err(Error::InvalidState(
+            "Contract transfers require a signer able to sign funding inputs.".to_string(),
+        ));
+    }
+    let offer = &novation.offer;
+    let accept_msg = novation
+        .accept
+        .as_ref()
+        .ok_or_else(|| Error::InvalidState("Missing novation accept.".to_string()))?;
+    let remaining_party = novation
+        .remaining_party
+        .ok_or_else(|| Error::InvalidState("Missing remaining party.".to_string()))?;
+
+    let novated_contract = get_novated_contract(
+        offer,
+        accept_msg,
+        remaining_party,
+        offer.transferor_is_offer_party,
+    )?;
+    let adaptor_infos = verify_signatures_and_get_adaptor_infos(
+        secp,
+        &novated_contract,
+        &offer.remaining_funding_pubkey,
+        &finalize_msg.cet_adaptor_signatures,
+        &finalize_msg.refund_signature,
+    )?;
+
+    let mut novation_tx = novated_contract.dlc_transactions.fund.clone();
+    let funding_script_pubkey = get_funding_script_pubkey(offer);
+    for (signature, pubkey) in &[
+        (
+            &finalize_msg.transferor_fund_input_signature,
+            &offer.transferor_funding_pubkey,
+        ),
+        (
+            &finalize_msg.remaining_fund_input_signature,
+            &offer.remaining_funding_pubkey,
+        ),
+    ] {
+        dlc::verify_tx_input_sig(
+            secp,
+            signature,
+            &novation_tx,
+            FUND_INPUT_INDEX,
+            &funding_script_pubkey,
+            offer.fund_output_value,
+            pubkey,
+        )?;
+    }
+    dlc::novation::finalize_novation_fund_input(
+        &mut novation_tx,
+        &funding_script_pubkey,
+        [
+            (
+                &offer.transferor_funding_pubkey,
+                &finalize_msg.transferor_fund_input_signature,
+            ),
+            (
+                &offer.remaining_funding_pubkey,
+                &finalize_msg.remaining_fund_input_signature,
+            ),
+        ],
+    );
+    sign_funding_inputs(&mut novation_tx, &accept_msg.funding_inputs, signer)?;
+
+    let fund_privkey = signer.get_secret_key_for_pubkey(&accept_msg.funding_pubkey)?;
+    let dlc_transactions = &novated_contract.dlc_transactions;
+    let refund_signature = dlc::util::get_raw_sig_for_tx_input(
+        secp,
+        &dlc_transactions.refund,
+        0,
+        &dlc_transactions.funding_script_pubkey,
+        dlc_transactions.get_fund_output().value,
+        &fund_privkey,
+    )?;
+    let signed_contract = get_signed_contract(
+        novated_contract,
+        adaptor_infos,
+        &finalize_msg.cet_adaptor_signatures,
+        refund_signature,
+        finalize_msg.refund_signature,
+    );
+
+    let mut novation = novation.clone();
+    novation.state = NovationState::Broadcast;
+    novation.novation_transaction = Some(novation_tx.clone());
+    novation.novated_contract = Some(signed_contract);
+
+    Ok((novation, novation_tx))
+}
+
+/// Returns the [`NovationOffer`] describing the given contract. The side of
+/// the contract transferred is ours if `own_side` is true, the one of the
+/// counter party otherwise.
+pub fn get_novation_offer(
+    contract: &SignedContract,
+    own_side: bool,
+    remaining_party: PublicKey,
+    terms: NovationTerms,
+) -> NovationOffer {
+    let accepted_contract = &contract.accepted_contract;
+    let offered_contract = &accepted_contract.offered_contract;
+    let transferor_is_offer_party = offered_contract.is_offer_party == own_side;
+    let (transferor_params, remaining_params) = if transferor_is_offer_party {
+        (
+            &offered_contract.offer_params,
+            &accepted_contract.accept_params,
+        )
+    } else {
+        (
+            &accepted_contract.accept_params,
+            &offered_contract.offer_params,
+        )
+    };
+    let dlc_transactions = &accepted_contract.dlc_transactions;
+
+    NovationOffer {
+        protocol_version: PROTOCOL_VERSION,
+        chain_hash: BITCOIN_CHAINHASH,
+        contract_id: accepted_contract.get_contract_id(),
+        temporary_contract_id: offered_contract.id,
+        remaining_party,
+        transferor_is_offer_party,
+        contract_info: offered_contract.into(),
+        transferor_collateral: transferor_params.collateral,
+        transferor_funding_pubkey: transferor_params.fund_pubkey,
+        transferor_payout_serial_id: transferor_params.payout_serial_id,
+        remaining_funding_pubkey: remaining_params.fund_pubkey,
+        remaining_payout_spk: remaining_params.payout_script_pubkey.clone(),
+        remaining_payout_serial_id: remaining_params.payout_serial_id,
+        fund_txid: dlc_transactions.fund.txid(),
+        fund_output_index: dlc_transactions.get_fund_output_index() as u16,
+        fund_output_value: dlc_transactions.get_fund_output().value,
+        cet_locktime: offered_contract.cet_locktime,
+        refund_locktime: offered_contract.refund_locktime,
+        terms,
+    }
+}
+
+/// Returns the redeem script of the fund output of the transferred contract.
+fn get_funding_script_pubkey(offer: &NovationOffer) -> Script {
+    dlc::make_funding_redeemscript(
+        &offer.transferor_funding_pubkey,
+        &offer.remaining_funding_pubkey,
+    )
+}
+
+/// Returns the parameters of the transferee, who takes over the collateral
+/// and payout serial id of the transferor.
+fn get_transferee_params(
+    offer: &NovationOffer,
+    accept_msg: &NovationAccept,
+) -> Result<PartyParams, Error> {
+    let (inputs, input_amount) = get_tx_input_infos(&accept_msg.funding_inputs)?;
+    Ok(PartyParams {
+        fund_pubkey: accept_msg.funding_pubkey,
+        change_script_pubkey: accept_msg.change_spk.clone(),
+        change_serial_id: accept_msg.change_serial_id,
+        payout_script_pubkey: accept_msg.payout_spk.clone(),
+        payout_serial_id: offer.transferor_payout_serial_id,
+        inputs,
+        input_amount,
+        collateral: offer.transferor_collateral,
+        additional_payout_outputs: Vec::new(),
+    })
+}
+
+/// Returns the parameters of the remaining party, which doesn't provide any
+/// input to the novation transaction.
+fn get_remaining_params(offer: &NovationOffer) -> PartyParams {
+    PartyParams {
+        fund_pubkey: offer.remaining_funding_pubkey,
+        change_script_pubkey: Script::new(),
+        change_serial_id: 0,
+        payout_script_pubkey: offer.remaining_payout_spk.clone(),
+        payout_serial_id: offer.remaining_payout_serial_id,
+        inputs: Vec::new(),
+        input_amount: 0,
+        collateral: offer.contract_info.get_total_collateral() - offer.transferor_collateral,
+        additional_payout_outputs: Vec::new(),
+    }
+}
+
+fn create_novation_transaction(
+    offer: &NovationOffer,
+    accept_msg: &NovationAccept,
+) -> Result<(Transaction, Script), Error> {
+    let transferee_params = get_transferee_params(offer, accept_msg)?;
+    Ok(dlc::novation::create_novation_transaction(
+        OutPoint {
+            txid: offer.fund_txid,
+            vout: offer.fund_output_index as u32,
+        },
+        offer.fund_output_value,
+        &offer.remaining_funding_pubkey,
+        &transferee_params,
+        TxOut {
+            value: offer.terms.price,
+            script_pubkey: offer.terms.price_spk.clone(),
+        },
+        offer.terms.price_serial_id,
+        offer.terms.fee_rate_per_vb,
+        offer.terms.fund_output_serial_id,
+    )?)
+}
+
+/// Builds the novated contract, seen by the party whose counter party has the
+/// given node id.
+fn get_novated_contract(
+    offer: &NovationOffer,
+    accept_msg: &NovationAccept,
+    counter_party: PublicKey,
+    is_offer_party: bool,
+) -> Result<NovatedContract, Error> {
+    let contract_info = get_contract_info_and_announcements(&offer.contract_info)?;
+    let total_collateral = offer.contract_info.get_total_collateral();
+    let transferee_params = get_transferee_params(offer, accept_msg)?;
+    let remaining_params = get_remaining_params(offer);
+    let transferee_inputs: Vec<FundingInputInfo> =
+        accept_msg.funding_inputs.iter().map(|x| x.into()).collect();
+    let (offer_params, accept_params, offer_inputs, accept_inputs) =
+        if offer.transferor_is_offer_party {
+            (
+                transferee_params,
+                remaining_params,
+                transferee_inputs,
+                Vec::new(),
+            )
+        } else {
+            (
+                remaining_params,
+                transferee_params,
+                Vec::new(),
+                transferee_inputs,
+            )
+        };
+
+    let (novation_tx, funding_script_pubkey) = create_novation_transaction(offer, accept_msg)?;
+    let mut payouts = Vec::new();
+    for info in &contract_info {
+        payouts.extend(info.get_payouts(total_collateral)?);
+    }
+    let (cets, refund) = dlc::novation::create_novated_cets_and_refund_tx(
+        &offer_params,
+        &accept_params,
+        &novation_tx,
+        &funding_script_pubkey,
+        &payouts,
+        offer.refund_locktime,
+        offer.cet_locktime,
+    )?;
+
+    Ok(NovatedContract {
+        offered_contract: OfferedContract {
+            id: offer.temporary_contract_id,
+            is_offer_party,
+            contract_info,
+            counter_party,
+            offer_params,
+            total_collateral,
+            funding_inputs_info: offer_inputs,
+            fund_output_serial_id: offer.terms.fund_output_serial_id,
+            fee_rate_per_vb: offer.terms.fee_rate_per_vb,
+            cet_locktime: offer.cet_locktime,
+            refund_locktime: offer.refund_locktime,
+            shared_funding_input: None,
+            shared_funding_accept_share: None,
+            metadata: None,
+            rollover: None,
+        },
+        accept_params,
+        funding_inputs: accept_inputs,
+        dlc_transactions: DlcTransactions {
+            fund: novation_tx,
+            cets,
+            refund,
+            funding_script_pubkey,
+        },
+    })
+}
+
+/// Returns the CETs of each contract info of the given contract, which are
+/// laid out one after the other.
+fn get_cets_per_contract_info<'a>(
+    contract: &'a NovatedContract,
+) -> Result<Vec<&'a [Transaction]>, Error> {
+    let offered_contract = &contract.offered_contract;
+    let mut start = 0;
+    let mut res = Vec::new();
+    for info in &offered_contract.contract_info {
+        let end = start + info.get_payouts(offered_contract.total_collateral)?.len();
+        res.push(&contract.dlc_transactions.cets[start..end]);
+        start = end;
+    }
+    Ok(res)
+}
+
+fn get_adaptor_infos_and_signatures(
+    secp: &Secp256k1<All>,
+    offered_contract: &OfferedContract,
+    dlc_transactions: &DlcTransactions,
+    fund_privkey: &SecretKey,
+) -> Result<(Vec<AdaptorInfo>, Vec<EcdsaAdaptorSignature>), Error> {
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+    let mut adaptor_infos = Vec::new();
+    let mut adaptor_sigs = Vec::new();
+    let mut start = 0;
+    for contract_info in &offered_contract.contract_info {
+        let end = start
+            + contract_info
+                .get_payouts(offered_contract.total_collateral)?
+                .len();
+        let (adaptor_info, sigs) = contract_info.get_adaptor_info(
+            secp,
+            offered_contract.total_collateral,
+            fund_privkey,
+            &dlc_transactions.funding_script_pubkey,
+            fund_output_value,
+            &dlc_transactions.cets[start..end],
+            adaptor_sigs.len(),
+        )?;
+        adaptor_infos.push(adaptor_info);
+        adaptor_sigs.extend(sigs);
+        start = end;
+    }
+    Ok((adaptor_infos, adaptor_sigs))
+}
+
+/// Verifies the refund signature and CET adaptor signatures of the counter
+/// party using the given adaptor information.
+fn verify_signatures(
+    secp: &Secp256k1<All>,
+    contract: &NovatedContract,
+    adaptor_infos: &[AdaptorInfo],
+    fund_pubkey: &PublicKey,
+    cet_adaptor_signatures: &CetAdaptorSignatures,
+    refund_signature: &Signature,
+) -> Result<(), Error> {
+    let dlc_transactions = &contract.dlc_transactions;
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+    verify_refund_signature(secp, dlc_transactions, fund_pubkey, refund_signature)?;
+
+    let adaptor_sigs = get_adaptor_signatures(cet_adaptor_signatures);
+    let mut adaptor_sig_start = 0;
+    for (contract_info, adaptor_info) in contract
+        .offered_contract
+        .contract_info
+        .iter()
+        .zip(adaptor_infos.iter())
+    {
+        adaptor_sig_start = contract_info.verify_adaptor_info(
+            secp,
+            fund_pubkey,
+            &dlc_transactions.funding_script_pubkey,
+            fund_output_value,
+            &dlc_transactions.cets,
+            &adaptor_sigs,
+            adaptor_sig_start,
+            adaptor_info,
+        )?;
+    }
+    Ok(())
+}
+
+/// Verifies the refund signature and CET adaptor signatures of the counter
+/// party, returning the adaptor information of the contract.
+fn verify_signatures_and_get_adaptor_infos(
+    secp: &Secp256k1<All>,
+    contract: &NovatedContract,
+    fund_pubkey: &PublicKey,
+    cet_adaptor_signatures: &CetAdaptorSignatures,
+    refund_signature: &Signature,
+) -> Result<Vec<AdaptorInfo>, Error> {
+    let dlc_transactions = &contract.dlc_transactions;
+    let fund_output_value = dlc_transactions.get_fund_output().value;
+    verify_refund_signature(secp, dlc_transactions, fund_pubkey, refund_signature)?;
+
+    let adaptor_sigs = get_adaptor_signatures(cet_adaptor_signatures);
+    let mut adaptor_infos = Vec::new();
+    let mut adaptor_index = 0;
+    for (contract_info, cets) in contract
+        .offered_contract
+        .contract_info
+        .iter()
+        .zip(get_cets_per_contract_info(contract)?)
+    {
+        let (adaptor_info, next_index) = contract_info.verify_and_get_adaptor_info(
+            secp,
+            contract.offered_contract.total_collateral,
+            fund_pubkey,
+            &dlc_transactions.funding_script_pubkey,
+            fund_output_value,
+            cets,
+            &adaptor_sigs,
+            adaptor_index,
+        )?;
+        adaptor_infos.push(adaptor_info);
+        adaptor_index = next_index;
+    }
+    Ok(adaptor_infos)
+}
+
+fn verify_refund_signature(
+    secp: &Secp256k1<All>,
+    dlc_transactions: &DlcTransactions,
+    fund_pubkey: &PublicKey,
+    refund_signature: &Signature,
+) -> Result<(), Error> {
+    dlc::verify_tx_input_sig(
+        secp,
+        refund_signature,
+        &dlc_transactions.refund,
+        0,
+        &dlc_transactions.funding_script_pubkey,
+        dlc_transactions.get_fund_output().value,
+        fund_pubkey,
+    )?;
+    Ok(())
+}
+
+fn get_adaptor_signatures(
+    cet_adaptor_signatures: &CetAdaptorSignatures,
+) -> Vec<EcdsaAdaptorSignature> {
+    cet_adaptor_signatures
+        .ecdsa_adaptor_signatures
+        .iter()
+        .map(|x| x.signature)
+        .collect()
+}
+
+/// Creates the [`SignedContract`] of the novated contract from the signatures
+/// of both parties, placing them according to our side of the contract.
+fn get_signed_contract(
+    contract: NovatedContract,
+    adaptor_infos: Vec<AdaptorInfo>,
+    counter_adaptor_signatures: &CetAdaptorSignatures,
+    own_refund_signature: Signature,
+    counter_refund_signature: Signature,
+) -> SignedContract {
+    let is_offer_party = contract.offered_contract.is_offer_party;
+    let counter_adaptor_signatures = get_adaptor_signatures(counter_adaptor_signatures);
+    let (accept_adaptor_signatures, offer_adaptor_signatures) = if is_offer_party {
+        (Some(counter_adaptor_signatures), None)
+    } else {
+        (None, Some(counter_adaptor_signatures))
+    };
+    let (offer_refund_signature, accept_refund_signature) = if is_offer_party {
+        (own_refund_signature, counter_refund_signature)
+    } else {
+        (counter_refund_signature, own_refund_signature)
+    };
+
+    SignedContract {
+        accepted_contract: AcceptedContract {
+            offered_contract: contract.offered_contract,
+            accept_params: contract.accept_params,
+            funding_inputs: contract.funding_inputs,
+            adaptor_infos,
+            adaptor_signatures: accept_adaptor_signatures,
+            accept_refund_signature,
+            dlc_transactions: contract.dlc_transactions,
+        },
+        adaptor_signatures: offer_adaptor_signatures,
+        offer_refund_signature,
+        funding_signatures: FundingSignatures {
+            funding_signatures: Vec::new(),
+        },
+        channel_id: None,
+    }
+}
+
+/// Signs the given funding inputs of the transferee, which follow the fund
+/// input in the novation transaction ordered by serial id.
+fn sign_funding_inputs<S: Deref>(
+    novation_tx: &mut Transaction,
+    funding_inputs: &[dlc_messages::FundingInput],
+    signer: &S,
+) -> Result<(), Error>
+where
+    S::Target: ContractSigner,
+{
+    let mut input_serial_ids: Vec<_> = funding_inputs.iter().map(|x| x.input_serial_id).collect();
+    input_serial_ids.sort_unstable();
+
+    for funding_input in funding_inputs {
+        let input_index = FUND_INPUT_INDEX
+            + 1
+            + input_serial_ids
+                .iter()
+                .position(|x| x == &funding_input.input_serial_id)
+                .expect("to find the serial id of the input");
+        let tx =
+            Transaction::consensus_decode(&mut funding_input.prev_tx.as_slice()).map_err(|_| {
+                Error::InvalidParameters(
+                    "Could not decode funding input previous tx parameter".to_string(),
+                )
+            })?;
+        let vout = funding_input.prev_tx_vout;
+        let tx_out = tx.output.get(vout as usize).ok_or_else(|| {
+            Error::InvalidParameters(format!("Previous tx output not found at index {}", vout))
+        })?;
+        signer.sign_tx_input(novation_tx, input_index, tx_out, None)?;
+    }
+
+    Ok(())
+}
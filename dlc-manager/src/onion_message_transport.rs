@@ -0,0 +1,355 @@
+//! #OnionMessageTransport
+//! Exchange of DLC messages as custom onion messages routed through the
+//! Lightning Network, so that peers can negotiate contracts without being
+//! directly connected and without intermediate nodes learning who is
+//! communicating with whom.
+//!
+//! As onion messages are limited in size, DLC messages are split in
+//! fragments of at most [`MAX_FRAGMENT_SIZE`] bytes. Onion messages do not
+//! authenticate their sender, so every fragment carries the node id of the
+//! sender together with its signature of the complete message, which is
+//! verified once the message is reassembled.
+//!
+//! [`DlcOnionMessageHandler`] should be given to the LDK `OnionMessenger` as
+//! its custom message handler, and the fragments returned by
+//! [`DlcOnionMessageHandler::get_and_clear_pending_messages`] sent using
+//! `OnionMessenger::send_onion_message` with `OnionMessageContents::Custom`.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use bitcoin::hashes::{sha256, Hash};
+use dlc_messages::Message;
+use lightning::ln::msgs::DecodeError;
+use lightning::onion_message::{CustomOnionMessageContents, CustomOnionMessageHandler};
+use lightning::util::ser::{Readable, Writeable, Writer};
+use log::{error, warn};
+use secp256k1_zkp::{
+    ecdsa::Signature, All, Message as SecpMessage, PublicKey, Secp256k1, SecretKey,
+};
+
+use crate::custom_message_handler::DlcMessageProcessor;
+use crate::error::Error;
+
+/// The TLV type of DLC onion messages.
+pub const DLC_ONION_MESSAGE_TYPE: u64 = 55_001;
+
+/// The maximum number of bytes of a serialized message carried by a single
+/// onion message.
+pub const MAX_FRAGMENT_SIZE: usize = 16 * 1024;
+
+/// The maximum number of fragments a message can be split into.
+pub const MAX_NB_FRAGMENTS: u16 = 256;
+
+/// The maximum number of messages being reassembled at the same time.
+pub const MAX_PENDING_REASSEMBLIES: usize = 16;
+
+/// A fragment of a DLC message carried by an onion message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DlcOnionMessage {
+    /// The node id of the sender of the message.
+    pub sender: PublicKey,
+    /// The hash of the serialized message.
+    pub message_hash: [u8; 32],
+    /// The signature of the message hash by the sender.
+    pub signature: Signature,
+    /// The index of the fragment.
+    pub index: u16,
+    /// The number of fragments of the message.
+    pub nb_fragments: u16,
+    /// The fragment data.
+    pub data: Vec<u8>,
+}
+
+impl_dlc_writeable!(DlcOnionMessage, {
+    (sender, writeable),
+    (message_hash, writeable),
+    (signature, writeable),
+    (index, writeable),
+    (nb_fragments, writeable),
+    (data, vec)
+});
+
+impl CustomOnionMessageContents for DlcOnionMessage {
+    fn tlv_type(&self) -> u64 {
+        DLC_ONION_MESSAGE_TYPE
+    }
+}
+
+fn get_signed_hash(message_hash: &[u8; 32]) -> SecpMessage {
+    SecpMessage::from_slice(message_hash).expect("a hash to be a valid message")
+}
+
+/// Splits the given message in signed fragments.
+pub fn get_fragments(
+    secp: &Secp256k1<All>,
+    secret_key: &SecretKey,
+    msg: &Message,
+) -> Result<Vec<DlcOnionMessage>, Error> {
+    let payload = msg.encode_with_type();
+    let chunks = payload.chunks(MAX_FRAGMENT_SIZE).collect::<Vec<_>>();
+    if chunks.len() > MAX_NB_FRAGMENTS as usize {
+        return Err(Error::InvalidParameters(
+            "Message is too large to be sent as onion messages.".to_string(),
+        ));
+    }
+    let message_hash = sha256::Hash::hash(&payload).into_inner();
+    let signature = secp.sign_ecdsa(&get_signed_hash(&message_hash), secret_key);
+    let sender = PublicKey::from_secret_key(secp, secret_key);
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| DlcOnionMessage {
+            sender,
+            message_hash,
+            signature,
+            index: i as u16,
+            nb_fragments: chunks.len() as u16,
+            data: chunk.to_vec(),
+        })
+        .collect())
+}
+
+/// Handles DLC onion messages, passing the reassembled messages to a
+/// [`DlcMessageProcessor`] and queuing its replies to be sent.
+pub struct DlcOnionMessageHandler<P: Deref>
+where
+    P::Target: DlcMessageProcessor,
+{
+    processor: P,
+    secp: Secp256k1<All>,
+    secret_key: SecretKey,
+    partial_messages: Mutex<HashMap<(PublicKey, [u8; 32]), Vec<Option<DlcOnionMessage>>>>,
+    pending_messages: Mutex<Vec<(PublicKey, DlcOnionMessage)>>,
+}
+
+impl<P: Deref> DlcOnionMessageHandler<P>
+where
+    P::Target: DlcMessageProcessor,
+{
+    /// Creates a new handler signing messages with the given node secret key.
+    pub fn new(processor: P, secret_key: SecretKey) -> Self {
+        DlcOnionMessageHandler {
+            processor,
+            secp: Secp256k1::new(),
+            secret_key,
+            partial_messages: Mutex::new(HashMap::new()),
+            pending_messages: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queues the fragments of the given message to be sent to the node with
+    /// the given id.
+    pub fn send_message(&self, node_id: PublicKey, msg: &Message) -> Result<(), Error> {
+        let fragments = get_fragments(&self.secp, &self.secret_key, msg)?;
+        self.pending_messages
+            .lock()
+            .unwrap()
+            .extend(fragments.into_iter().map(|f| (node_id, f)));
+        Ok(())
+    }
+
+    /// Returns the fragments to be sent with the id of their destination
+    /// node, and empties the sending queue.
+    pub fn get_and_clear_pending_messages(&self) -> Vec<(PublicKey, DlcOnionMessage)> {
+        std::mem::take(&mut *self.pending_messages.lock().unwrap())
+    }
+
+    /// Adds the given fragment to the message being reassembled, returning
+    /// the message and its sender once all its fragments were received.
+    fn process_fragment(
+        &self,
+        fragment: DlcOnionMessage,
+    ) -> Result<Option<(PublicKey, Message)>, Error> {
+        if fragment.nb_fragments == 0
+            || fragment.nb_fragments > MAX_NB_FRAGMENTS
+            || fragment.index >= fragment.nb_fragments
+            || fragment.data.len() > MAX_FRAGMENT_SIZE
+        {
+            return Err(Error::InvalidParameters("Invalid fragment.".to_string()));
+        }
+
+        let key = (fragment.sender, fragment.message_hash);
+        let mut partial_messages = self.partial_messages.lock().unwrap();
+        if !partial_messages.contains_key(&key)
+            && partial_messages.len() >= MAX_PENDING_REASSEMBLIES
+        {
+            return Err(Error::InvalidState(
+                "Too many messages being reassembled.".to_string(),
+            ));
+        }
+        let fragments = partial_messages
+            .entry(key)
+            .or_insert_with(|| vec![None; fragment.nb_fragments as usize]);
+        if fragments.len() != fragment.nb_fragments as usize {
+            return Err(Error::InvalidParameters(
+                "Inconsistent number of fragments.".to_string(),
+            ));
+        }
+        let index = fragment.index as usize;
+        fragments[index] = Some(fragment);
+        if fragments.iter().any(|f| f.is_none()) {
+            return Ok(None);
+        }
+
+        let fragments = partial_messages
+            .remove(&key)
+            .expect("to have the message fragments")
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        let payload = fragments
+            .iter()
+            .flat_map(|f| f.data.iter().cloned())
+            .collect::<Vec<_>>();
+        if sha256::Hash::hash(&payload).into_inner() != key.1 {
+            return Err(Error::InvalidParameters(
+                "Message does not match its hash.".to_string(),
+            ));
+        }
+        self.secp
+            .verify_ecdsa(&get_signed_hash(&key.1), &fragments[0].signature, &key.0)?;
+        let msg = Message::read_with_type(&mut Cursor::new(payload))
+            .map_err(|e| Error::InvalidParameters(format!("Could not decode message: {}", e)))?;
+        Ok(Some((key.0, msg)))
+    }
+}
+
+impl<P: Deref> CustomOnionMessageHandler for DlcOnionMessageHandler<P>
+where
+    P::Target: DlcMessageProcessor,
+{
+    type CustomMessage = DlcOnionMessage;
+
+    fn handle_custom_message(&self, msg: DlcOnionMessage) {
+        let (sender, msg) = match self.process_fragment(msg) {
+            Ok(Some(m)) => m,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Discarding DLC onion message: {}", e);
+                return;
+            }
+        };
+
+        match self.processor.process_dlc_message(&msg, sender) {
+            Ok(Some(reply)) => {
+                if let Err(e) = self.send_message(sender, &reply) {
+                    error!("Could not send reply to {}: {}", sender, e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("Error processing message from {}: {}", sender, e),
+        }
+    }
+
+    fn read_custom_message<R: std::io::Read>(
+        &self,
+        message_type: u64,
+        buffer: &mut R,
+    ) -> Result<Option<DlcOnionMessage>, DecodeError> {
+        if message_type == DLC_ONION_MESSAGE_TYPE {
+            Ok(Some(Readable::read(buffer)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dlc_messages::AcceptDlc;
+
+    struct EchoProcessor {
+        received: Mutex<Vec<(PublicKey, Message)>>,
+    }
+
+    impl DlcMessageProcessor for EchoProcessor {
+        fn process_dlc_message(
+            &self,
+            msg: &Message,
+            counter_party: PublicKey,
+        ) -> Result<Option<Message>, Error> {
+            self.received
+                .lock()
+                .unwrap()
+                .push((counter_party, msg.clone()));
+            Ok(Some(msg.clone()))
+        }
+    }
+
+    fn get_accept() -> Message {
+        let accept: AcceptDlc = serde_json::from_str(include_str!(
+            "../../dlc-messages/src/test_inputs/accept_msg.json"
+        ))
+        .unwrap();
+        Message::Accept(accept)
+    }
+
+    #[test]
+    fn fragmented_message_is_reassembled_and_replied_to() {
+        let processor = EchoProcessor {
+            received: Mutex::new(Vec::new()),
+        };
+        let sender =
+            DlcOnionMessageHandler::new(&processor, SecretKey::from_slice(&[1; 32]).unwrap());
+        let receiver =
+            DlcOnionMessageHandler::new(&processor, SecretKey::from_slice(&[2; 32]).unwrap());
+        let receiver_id = PublicKey::from_secret_key(&receiver.secp, &receiver.secret_key);
+        let msg = get_accept();
+
+        sender.send_message(receiver_id, &msg).unwrap();
+        let fragments = sender.get_and_clear_pending_messages();
+        assert!(fragments.len() > 1);
+        for (node_id, fragment) in fragments.into_iter().rev() {
+            assert_eq!(receiver_id, node_id);
+            let mut buf = Vec::new();
+            fragment.write(&mut buf).unwrap();
+            let read = receiver
+                .read_custom_message(fragment.tlv_type(), &mut Cursor::new(buf))
+                .unwrap()
+                .expect("a DLC onion message");
+            receiver.handle_custom_message(read);
+        }
+
+        let received = processor.received.lock().unwrap();
+        assert_eq!(1, received.len());
+        assert_eq!(
+            PublicKey::from_secret_key(&sender.secp, &sender.secret_key),
+            received[0].0
+        );
+        assert_eq!(msg.encode_with_type(), received[0].1.encode_with_type());
+        assert!(!receiver.get_and_clear_pending_messages().is_empty());
+    }
+
+    #[test]
+    fn forged_sender_is_rejected() {
+        let processor = EchoProcessor {
+            received: Mutex::new(Vec::new()),
+        };
+        let handler =
+            DlcOnionMessageHandler::new(&processor, SecretKey::from_slice(&[1; 32]).unwrap());
+        let secp = Secp256k1::new();
+        let mut fragments = get_fragments(
+            &secp,
+            &SecretKey::from_slice(&[2; 32]).unwrap(),
+            &get_accept(),
+        )
+        .unwrap();
+        let forged_sender =
+            PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[3; 32]).unwrap());
+        for fragment in fragments.iter_mut() {
+            fragment.sender = forged_sender;
+        }
+
+        let last = fragments.pop().unwrap();
+        for fragment in fragments {
+            assert!(handler.process_fragment(fragment).unwrap().is_none());
+        }
+        handler
+            .process_fragment(last)
+            .expect_err("an invalid signature");
+    }
+}
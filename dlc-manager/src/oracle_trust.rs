@@ -0,0 +1,203 @@
+//! #OracleTrustConfig
+//! Restrictions on the oracles that contracts offered by peers can rely on.
+
+use std::collections::HashSet;
+
+use secp256k1_zkp::XOnlyPublicKey;
+
+use crate::contract::contract_info::ContractInfo;
+use crate::contract::ContractDescriptor;
+use crate::error::Error;
+
+/// Describes which oracle sets are trusted when receiving contract offers.
+#[derive(Clone, Debug)]
+pub struct OracleTrustConfig {
+    /// The public keys of the oracles that can be used in offered contracts.
+    /// Any oracle is accepted if `None`.
+    pub allowed_oracles: Option<HashSet<XOnlyPublicKey>>,
+    /// The minimum number of distinct oracles whose attestations are required
+    /// to close a contract.
+    pub min_distinct_oracles: usize,
+    /// The maximum value of the `max_error_exp` difference parameter of
+    /// numerical contracts. Any value is accepted if `None`.
+    pub max_error_exp: Option<usize>,
+}
+
+impl Default for OracleTrustConfig {
+    fn default() -> Self {
+        OracleTrustConfig {
+            allowed_oracles: None,
+            min_distinct_oracles: 1,
+            max_error_exp: None,
+        }
+    }
+}
+
+impl OracleTrustConfig {
+    /// Returns an error if the given contract information relies on oracles
+    /// or parameters that are not trusted by this configuration.
+    pub fn check_contract_info(&self, contract_info: &ContractInfo) -> Result<(), Error> {
+        let oracles = contract_info
+            .oracle_announcements
+            .iter()
+            .map(|x| x.oracle_public_key)
+            .collect::<HashSet<_>>();
+
+        if let Some(allowed_oracles) = &self.allowed_oracles {
+            if let Some(unknown) = oracles.iter().find(|x| !allowed_oracles.contains(x)) {
+                return Err(Error::UntrustedOracles(format!(
+                    "Oracle {} is not allowed",
+                    unknown
+                )));
+            }
+        }
+
+        if oracles.len() < self.min_distinct_oracles
+            || contract_info.threshold < self.min_distinct_oracles
+        {
+            return Err(Error::UntrustedOracles(format!(
+                "Contract requires {} attestations from {} distinct oracles but at least {} are required",
+                contract_info.threshold,
+                oracles.len(),
+                self.min_distinct_oracles
+            )));
+        }
+
+        if let (Some(max_error_exp), ContractDescriptor::Numerical(n)) =
+            (self.max_error_exp, &contract_info.contract_descriptor)
+        {
+            if let Some(params) = &n.difference_params {
+                if params.max_error_exp > max_error_exp {
+                    return Err(Error::UntrustedOracles(format!(
+                        "Maximum error exponent {} exceeds allowed value {}",
+                        params.max_error_exp, max_error_exp
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns an error if a contract relying on the single given oracle is
+    /// not trusted by this configuration.
+    pub fn check_single_oracle(&self, oracle_public_key: &XOnlyPublicKey) -> Result<(), Error> {
+        if let Some(allowed_oracles) = &self.allowed_oracles {
+            if !allowed_oracles.contains(oracle_public_key) {
+                return Err(Error::UntrustedOracles(format!(
+                    "Oracle {} is not allowed",
+                    oracle_public_key
+                )));
+            }
+        }
+
+        if self.min_distinct_oracles > 1 {
+            return Err(Error::UntrustedOracles(format!(
+                "Contract relies on a single oracle but at least {} are required",
+                self.min_distinct_oracles
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::enum_descriptor::EnumDescriptor;
+    use dlc_messages::oracle_msgs::{
+        EnumEventDescriptor, EventDescriptor, OracleAnnouncement, OracleEvent,
+    };
+    use secp256k1_zkp::{schnorr::Signature, KeyPair, SecretKey, SECP256K1};
+
+    fn get_public_key(i: u8) -> XOnlyPublicKey {
+        let sk = SecretKey::from_slice(&[i; 32]).unwrap();
+        XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(SECP256K1, &sk)).0
+    }
+
+    fn get_contract_info(oracles: &[u8], threshold: usize) -> ContractInfo {
+        let oracle_announcements = oracles
+            .iter()
+            .map(|i| OracleAnnouncement {
+                announcement_signature: Signature::from_slice(&[1; 64]).unwrap(),
+                oracle_public_key: get_public_key(*i),
+                oracle_event: OracleEvent {
+                    oracle_nonces: vec![get_public_key(*i)],
+                    event_maturity_epoch: 1,
+                    event_descriptor: EventDescriptor::EnumEvent(EnumEventDescriptor {
+                        outcomes: vec!["a".to_string()],
+                    }),
+                    event_id: "event".to_string(),
+                },
+            })
+            .collect();
+        ContractInfo {
+            contract_descriptor: ContractDescriptor::Enum(EnumDescriptor {
+                outcome_payouts: Vec::new(),
+            }),
+            oracle_announcements,
+            threshold,
+        }
+    }
+
+    #[test]
+    fn unknown_oracle_is_rejected() {
+        let config = OracleTrustConfig {
+            allowed_oracles: Some(vec![get_public_key(1)].into_iter().collect()),
+            ..Default::default()
+        };
+
+        config
+            .check_contract_info(&get_contract_info(&[1], 1))
+            .expect("allowed oracle to be trusted");
+        assert!(matches!(
+            config.check_contract_info(&get_contract_info(&[1, 2], 1)),
+            Err(Error::UntrustedOracles(_))
+        ));
+    }
+
+    #[test]
+    fn insufficient_oracle_set_is_rejected() {
+        let config = OracleTrustConfig {
+            min_distinct_oracles: 2,
+            ..Default::default()
+        };
+
+        config
+            .check_contract_info(&get_contract_info(&[1, 2, 3], 2))
+            .expect("oracle set to be sufficient");
+        assert!(matches!(
+            config.check_contract_info(&get_contract_info(&[1, 2, 3], 1)),
+            Err(Error::UntrustedOracles(_))
+        ));
+        assert!(matches!(
+            config.check_contract_info(&get_contract_info(&[1, 1], 2)),
+            Err(Error::UntrustedOracles(_))
+        ));
+    }
+
+    #[test]
+    fn single_oracle_is_checked() {
+        let config = OracleTrustConfig {
+            allowed_oracles: Some(vec![get_public_key(1)].into_iter().collect()),
+            ..Default::default()
+        };
+
+        config
+            .check_single_oracle(&get_public_key(1))
+            .expect("allowed oracle to be trusted");
+        assert!(matches!(
+            config.check_single_oracle(&get_public_key(2)),
+            Err(Error::UntrustedOracles(_))
+        ));
+        assert!(matches!(
+            OracleTrustConfig {
+                min_distinct_oracles: 2,
+                ..Default::default()
+            }
+            .check_single_oracle(&get_public_key(1)),
+            Err(Error::UntrustedOracles(_))
+        ));
+    }
+}
@@ -1,7 +1,39 @@
 //! #PayoutFunction
+//!
+//! With the `serde` feature enabled, payout functions and rounding intervals
+//! are represented in JSON as follows (field names are camel cased and each
+//! piece is tagged with its type):
+//!
+//! ```json
+//! {
+//!   "payoutFunction": {
+//!     "payoutFunctionPieces": [
+//!       { "polynomialPayoutCurvePiece": { "payoutPoints": [
+//!         { "eventOutcome": 0, "outcomePayout": 0, "extraPrecision": 0 },
+//!         { "eventOutcome": 10, "outcomePayout": 100, "extraPrecision": 0 }
+//!       ] } },
+//!       { "hyperbolaPayoutCurvePiece": {
+//!         "leftEndPoint": { "eventOutcome": 10, "outcomePayout": 100, "extraPrecision": 0 },
+//!         "rightEndPoint": { "eventOutcome": 20, "outcomePayout": 50, "extraPrecision": 0 },
+//!         "usePositivePiece": true, "translateOutcome": 0.0, "translatePayout": 0.0,
+//!         "a": 1.0, "b": 0.0, "c": 0.0, "d": 1000.0
+//!       } },
+//!       { "stepPayoutCurvePiece": { "payoutPoints": [ ... ] } },
+//!       { "monotoneCubicPayoutCurvePiece": { "payoutPoints": [ ... ] } }
+//!     ]
+//!   },
+//!   "roundingIntervals": { "intervals": [ { "beginInterval": 0, "roundingMod": 1 } ] }
+//! }
+//! ```
+//!
+//! Consecutive pieces must share their end points. Deserialized values are
+//! checked when the contract using them is validated.
 
 use std::ops::Deref;
 
+pub mod builder;
+pub mod fit;
+
 use crate::error::Error;
 use dlc::{Payout, RangePayout};
 #[cfg(feature = "serde")]
@@ -40,8 +72,24 @@ impl PayoutFunction {
         }
     }
 
+    /// Whether the function contains hyperbola pieces.
+    pub(crate) fn has_hyperbola_pieces(&self) -> bool {
+        self.payout_function_pieces
+            .iter()
+            .any(|x| matches!(x, PayoutFunctionPiece::HyperbolaPayoutCurvePiece(_)))
+    }
+
     /// Validate that the payout function is continuous and covers the interval [0, max_value]
     pub fn validate(&self, max_value: u64) -> Result<(), Error> {
+        for piece in &self.payout_function_pieces {
+            match piece {
+                PayoutFunctionPiece::StepPayoutCurvePiece(s) => s.validate()?,
+                PayoutFunctionPiece::HyperbolaPayoutCurvePiece(h) => h.validate()?,
+                PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(m) => m.validate()?,
+                _ => {}
+            }
+        }
+
         if !is_continuous(&self.payout_function_pieces) {
             return Err(Error::InvalidParameters(
                 "Payout function is not continuous.".to_string(),
@@ -64,6 +112,20 @@ impl PayoutFunction {
                 PayoutFunctionPiece::HyperbolaPayoutCurvePiece(h) => {
                     h.left_end_point.event_outcome == 0
                 }
+                PayoutFunctionPiece::StepPayoutCurvePiece(s) => {
+                    s.payout_points
+                        .first()
+                        .expect("to have at least a point")
+                        .event_outcome
+                        == 0
+                }
+                PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(m) => {
+                    m.payout_points
+                        .first()
+                        .expect("to have at least a point")
+                        .event_outcome
+                        == 0
+                }
             };
 
             let last = self
@@ -81,6 +143,20 @@ impl PayoutFunction {
                 PayoutFunctionPiece::HyperbolaPayoutCurvePiece(h) => {
                     h.right_end_point.event_outcome == max_value
                 }
+                PayoutFunctionPiece::StepPayoutCurvePiece(s) => {
+                    s.payout_points
+                        .last()
+                        .expect("to have at least a point")
+                        .event_outcome
+                        == max_value
+                }
+                PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(m) => {
+                    m.payout_points
+                        .last()
+                        .expect("to have at least a point")
+                        .event_outcome
+                        == max_value
+                }
             };
 
             starts_at_zero && finishes_at_max
@@ -121,6 +197,10 @@ pub enum PayoutFunctionPiece {
     PolynomialPayoutCurvePiece(PolynomialPayoutCurvePiece),
     /// A function piece represented by an hyperbola.
     HyperbolaPayoutCurvePiece(HyperbolaPayoutCurvePiece),
+    /// A function piece represented by a step function.
+    StepPayoutCurvePiece(StepPayoutCurvePiece),
+    /// A function piece represented by a monotone cubic spline.
+    MonotoneCubicPayoutCurvePiece(MonotoneCubicPayoutCurvePiece),
 }
 
 impl PayoutFunctionPiece {
@@ -138,6 +218,12 @@ impl PayoutFunctionPiece {
             PayoutFunctionPiece::HyperbolaPayoutCurvePiece(h) => {
                 h.to_range_payouts(rounding_intervals, total_collateral, range_payouts)
             }
+            PayoutFunctionPiece::StepPayoutCurvePiece(s) => {
+                s.to_range_payouts(rounding_intervals, total_collateral, range_payouts)
+            }
+            PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(m) => {
+                m.to_range_payouts(rounding_intervals, total_collateral, range_payouts)
+            }
         }
     }
 
@@ -145,6 +231,8 @@ impl PayoutFunctionPiece {
         match self {
             PayoutFunctionPiece::PolynomialPayoutCurvePiece(p) => &p.payout_points[0],
             PayoutFunctionPiece::HyperbolaPayoutCurvePiece(h) => &h.left_end_point,
+            PayoutFunctionPiece::StepPayoutCurvePiece(s) => &s.payout_points[0],
+            PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(m) => &m.payout_points[0],
         }
     }
 
@@ -152,12 +240,18 @@ impl PayoutFunctionPiece {
         match self {
             PayoutFunctionPiece::PolynomialPayoutCurvePiece(p) => p.payout_points.last().unwrap(),
             PayoutFunctionPiece::HyperbolaPayoutCurvePiece(h) => &h.right_end_point,
+            PayoutFunctionPiece::StepPayoutCurvePiece(s) => s.payout_points.last().unwrap(),
+            PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(m) => {
+                m.payout_points.last().unwrap()
+            }
         }
     }
 }
 
 trait Evaluable {
-    fn evaluate(&self, outcome: u64) -> f64;
+    /// Evaluates the function for the given outcome, returning an error if the
+    /// evaluation fails.
+    fn try_evaluate(&self, outcome: u64) -> Result<f64, Error>;
 
     fn get_rounded_payout(
         &self,
@@ -165,7 +259,7 @@ trait Evaluable {
         rounding_intervals: &RoundingIntervals,
         total_collateral: u64,
     ) -> Result<u64, Error> {
-        let payout_double = self.evaluate(outcome);
+        let payout_double = self.try_evaluate(outcome)?;
         if payout_double.is_sign_negative() || (payout_double != 0.0 && !payout_double.is_normal())
         {
             return Err(Error::InvalidParameters(format!(
@@ -299,9 +393,7 @@ impl PolynomialPayoutCurvePiece {
             ))
         }
     }
-}
 
-impl Evaluable for PolynomialPayoutCurvePiece {
     fn evaluate(&self, outcome: u64) -> f64 {
         let nb_points = self.payout_points.len();
 
@@ -311,7 +403,7 @@ impl Evaluable for PolynomialPayoutCurvePiece {
             return if left_point.outcome_payout == right_point.outcome_payout {
                 right_point.outcome_payout as f64
             } else {
-                let slope = (right_point.outcome_payout - left_point.outcome_payout) as f64
+                let slope = (right_point.outcome_payout as f64 - left_point.outcome_payout as f64)
                     / (right_point.event_outcome - left_point.event_outcome) as f64;
                 (outcome - left_point.event_outcome) as f64 * slope
                     + left_point.outcome_payout as f64
@@ -340,6 +432,12 @@ impl Evaluable for PolynomialPayoutCurvePiece {
 
         result
     }
+}
+
+impl Evaluable for PolynomialPayoutCurvePiece {
+    fn try_evaluate(&self, outcome: u64) -> Result<f64, Error> {
+        Ok(self.evaluate(outcome))
+    }
 
     fn get_first_outcome(&self) -> u64 {
         self.payout_points[0].event_outcome
@@ -433,45 +531,190 @@ impl HyperbolaPayoutCurvePiece {
         c: f64,
         d: f64,
     ) -> Result<Self, Error> {
-        if a * d == b * c {
-            Err(Error::InvalidParameters(
+        let piece = HyperbolaPayoutCurvePiece {
+            left_end_point,
+            right_end_point,
+            use_positive_piece,
+            translate_outcome,
+            translate_payout,
+            a,
+            b,
+            c,
+            d,
+        };
+        piece.validate()?;
+        Ok(piece)
+    }
+
+    /// Validate that the parameters of the piece define a hyperbola and can be
+    /// represented exactly as fixed point numbers.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.a * self.d == self.b * self.c {
+            return Err(Error::InvalidParameters(
                 "a * c cannot equal d * c".to_string(),
-            ))
-        } else if left_end_point.event_outcome >= right_end_point.event_outcome {
-            Err(Error::InvalidParameters(
+            ));
+        }
+        if self.left_end_point.event_outcome >= self.right_end_point.event_outcome {
+            return Err(Error::InvalidParameters(
                 "Left end point outcome must be strictly less than right end point outcome"
                     .to_string(),
-            ))
-        } else {
-            Ok(HyperbolaPayoutCurvePiece {
-                left_end_point,
-                right_end_point,
-                use_positive_piece,
-                translate_outcome,
-                translate_payout,
-                a,
-                b,
-                c,
-                d,
-            })
+            ));
+        }
+        for value in &[
+            self.translate_outcome,
+            self.translate_payout,
+            self.a,
+            self.b,
+            self.c,
+            self.d,
+        ] {
+            to_fixed_point(*value)?;
+        }
+        if self.a == 0.0 {
+            return Err(Error::InvalidParameters(
+                "Hyperbola parameter a cannot be zero.".to_string(),
+            ));
         }
+        Ok(())
     }
 }
 
-impl Evaluable for HyperbolaPayoutCurvePiece {
-    fn evaluate(&self, outcome: u64) -> f64 {
-        let outcome = outcome as f64;
-        let translated_outcome = outcome - self.translate_outcome;
-        let sqrt_term_abs_val = (translated_outcome.powi(2) - 4.0 * self.a * self.b).sqrt();
+/// Number of fractional bits of the fixed point numbers used to evaluate
+/// hyperbola pieces.
+const HYPERBOLA_FRACTIONAL_BITS: u32 = 32;
+
+fn hyperbola_overflow_error() -> Error {
+    Error::InvalidParameters("Overflow while evaluating hyperbola.".to_string())
+}
+
+/// Converts the given value to a fixed point number, returning an error if it
+/// cannot be represented exactly.
+fn to_fixed_point(value: f64) -> Result<i128, Error> {
+    // Multiplying by a power of two is exact.
+    let scaled = value * ((1u64 << HYPERBOLA_FRACTIONAL_BITS) as f64);
+    if !scaled.is_finite() || scaled.abs() >= 2f64.powi(126) {
+        return Err(hyperbola_overflow_error());
+    }
+    if scaled.fract() != 0.0 {
+        return Err(Error::InvalidParameters(format!(
+            "Hyperbola parameter {} cannot be represented with {} fractional bits.",
+            value, HYPERBOLA_FRACTIONAL_BITS
+        )));
+    }
+    Ok(scaled as i128)
+}
+
+/// Rounds the given value to the nearest number that can be used as a
+/// hyperbola parameter.
+pub(crate) fn round_to_fixed_point(value: f64) -> f64 {
+    let one = (1u64 << HYPERBOLA_FRACTIONAL_BITS) as f64;
+    (value * one).round() / one
+}
+
+/// Computes `x * y / z`, splitting `y` into quotient and remainder of the
+/// division by `z` to avoid overflowing on the intermediate product.
+fn mul_div(x: i128, y: i128, z: i128) -> Result<i128, Error> {
+    let quotient = y.checked_div(z).ok_or_else(hyperbola_overflow_error)?;
+    let remainder = y % z;
+    x.checked_mul(quotient)
+        .and_then(|q| {
+            x.checked_mul(remainder)
+                .and_then(|r| r.checked_div(z))
+                .and_then(|r| q.checked_add(r))
+        })
+        .ok_or_else(hyperbola_overflow_error)
+}
+
+/// Computes the floor of the square root of the given value.
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let bits = 128 - value.leading_zeros();
+    let mut cur = 1u128 << ((bits + 1) / 2);
+    loop {
+        let next = (cur + value / cur) >> 1;
+        if next >= cur {
+            return cur;
+        }
+        cur = next;
+    }
+}
+
+impl HyperbolaPayoutCurvePiece {
+    /// Evaluates the hyperbola using checked fixed point arithmetic, so that
+    /// all implementations compute exactly the same value. All values are
+    /// signed 128 bits integers with `HYPERBOLA_FRACTIONAL_BITS` fractional
+    /// bits (`ONE = 2^32`), divisions truncate towards zero and square roots
+    /// are floored. With `A`, `B`, `C`, `D`, `TO` and `TP` the parameters
+    /// converted exactly to fixed point:
+    ///
+    /// 1. `x = outcome * ONE - TO`
+    /// 2. `disc = mul_div(x, x, ONE) - 4 * mul_div(A, B, ONE)`, the hyperbola
+    ///    is not defined for the outcome if `disc` is negative
+    /// 3. `s = isqrt(disc * ONE)`, or `isqrt(disc) * 2^16` if `disc * ONE`
+    ///    overflows, negated if `use_positive_piece` is false
+    /// 4. `result = mul_div(C, x + s, 2 * A) + mul_div(2 * A, D, x + s) + TP`
+    ///
+    /// where `mul_div(x, y, z) = x * (y / z) + (x * (y % z)) / z`. Any overflow
+    /// or division by zero is an error. The vectors of
+    /// `hyperbola_fixed_point_test_vectors` can be used to check other
+    /// implementations.
+    fn evaluate_fixed_point(&self, outcome: u64) -> Result<i128, Error> {
+        let one = 1i128 << HYPERBOLA_FRACTIONAL_BITS;
+        let a = to_fixed_point(self.a)?;
+        let b = to_fixed_point(self.b)?;
+        let c = to_fixed_point(self.c)?;
+        let d = to_fixed_point(self.d)?;
+        let translate_payout = to_fixed_point(self.translate_payout)?;
+
+        let translated_outcome = (outcome as i128 * one)
+            .checked_sub(to_fixed_point(self.translate_outcome)?)
+            .ok_or_else(hyperbola_overflow_error)?;
+        let discriminant = mul_div(translated_outcome, translated_outcome, one)?
+            .checked_sub(
+                mul_div(a, b, one)?
+                    .checked_mul(4)
+                    .ok_or_else(hyperbola_overflow_error)?,
+            )
+            .ok_or_else(hyperbola_overflow_error)?;
+        if discriminant < 0 {
+            return Err(Error::InvalidParameters(
+                "Hyperbola is not defined for the given outcome.".to_string(),
+            ));
+        }
+
+        // Scale before taking the root when possible to keep all fractional
+        // bits, otherwise only half of them are kept.
+        let sqrt_term_abs_val = if discriminant <= i128::MAX >> HYPERBOLA_FRACTIONAL_BITS {
+            isqrt((discriminant << HYPERBOLA_FRACTIONAL_BITS) as u128) as i128
+        } else {
+            (isqrt(discriminant as u128) as i128) << (HYPERBOLA_FRACTIONAL_BITS / 2)
+        };
         let sqrt_term = if self.use_positive_piece {
             sqrt_term_abs_val
         } else {
             -sqrt_term_abs_val
         };
 
-        let first_term = self.c * (translated_outcome + sqrt_term) / (2.0 * self.a);
-        let second_term = 2.0 * self.a * self.d / (translated_outcome + sqrt_term);
-        first_term + second_term + self.translate_payout
+        let denominator = translated_outcome
+            .checked_add(sqrt_term)
+            .ok_or_else(hyperbola_overflow_error)?;
+        let two_a = a.checked_mul(2).ok_or_else(hyperbola_overflow_error)?;
+        let first_term = mul_div(c, denominator, two_a)?;
+        let second_term = mul_div(two_a, d, denominator)?;
+
+        first_term
+            .checked_add(second_term)
+            .and_then(|x| x.checked_add(translate_payout))
+            .ok_or_else(hyperbola_overflow_error)
+    }
+}
+
+impl Evaluable for HyperbolaPayoutCurvePiece {
+    fn try_evaluate(&self, outcome: u64) -> Result<f64, Error> {
+        let res = self.evaluate_fixed_point(outcome)?;
+        Ok(res as f64 / ((1u64 << HYPERBOLA_FRACTIONAL_BITS) as f64))
     }
 
     fn get_first_outcome(&self) -> u64 {
@@ -482,116 +725,496 @@ impl Evaluable for HyperbolaPayoutCurvePiece {
     }
 }
 
-/// Provides information on if and how to round the payouts of a payout function
-/// to reduce the number of adaptor signatures required. A `rounding_mod` value
-/// of 1 indicates that no rounding is performed.
-#[derive(Clone, Debug)]
+/// A function piece represented by a step function. Each point gives the payout
+/// from its outcome (included) up to the outcome of the next point (excluded),
+/// the last point giving the payout for the right end outcome.
+#[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
     serde(rename_all = "camelCase")
 )]
-pub struct RoundingInterval {
-    /// The start of the rounding interval.
-    pub begin_interval: u64,
-    /// The rounding modulus value.
-    pub rounding_mod: u64,
+pub struct StepPayoutCurvePiece {
+    /// The points at which the payout changes.
+    pub(crate) payout_points: Vec<PayoutPoint>,
 }
 
-/// A set of rounding intervals.
-#[derive(Clone, Debug)]
-#[cfg_attr(
-    feature = "serde",
-    derive(Serialize, Deserialize),
-    serde(rename_all = "camelCase")
-)]
-pub struct RoundingIntervals {
-    /// Contains the rounding intervals.
-    pub intervals: Vec<RoundingInterval>,
+impl StepPayoutCurvePiece {
+    /// Create a new StepPayoutCurvePiece
+    pub fn new(payout_points: Vec<PayoutPoint>) -> Result<Self, Error> {
+        let piece = StepPayoutCurvePiece { payout_points };
+        piece.validate()?;
+        Ok(piece)
+    }
+
+    /// Validate that the piece has at least two points with ascending event
+    /// outcomes.
+    pub fn validate(&self) -> Result<(), Error> {
+        let is_ascending = self.payout_points.len() > 1
+            && self
+                .payout_points
+                .iter()
+                .zip(self.payout_points.iter().skip(1))
+                .all(|(cur, next)| cur.event_outcome < next.event_outcome);
+        if is_ascending {
+            Ok(())
+        } else {
+            Err(Error::InvalidParameters(
+                "Payout points must have ascending event outcome value.".to_string(),
+            ))
+        }
+    }
 }
 
-impl RoundingIntervals {
-    /// Round the given payout based on the rounding modulus matching the given
-    /// outcome.
-    pub fn round(&self, outcome: u64, payout: f64) -> u64 {
-        let rounding_mod = match self
+/// Extends the range payouts with the outcomes in `[start, end]` that all
+/// have the same `payout` before rounding.
+fn push_constant_range(
+    start: u64,
+    end: u64,
+    payout: f64,
+    rounding_intervals: &RoundingIntervals,
+    total_collateral: u64,
+    cur_range: &mut RangePayout,
+    range_payouts: &mut Vec<RangePayout>,
+) -> Result<(), Error> {
+    if payout > total_collateral as f64 {
+        return Err(Error::InvalidParameters(
+            "Computed payout is greater than total collateral.".to_string(),
+        ));
+    }
+
+    // The rounding modulus can change within the range so it is split
+    // at the rounding interval boundaries.
+    let mut cur = start;
+    loop {
+        let sub_end = match rounding_intervals
             .intervals
-            .binary_search_by(|x| x.begin_interval.cmp(&outcome))
+            .iter()
+            .find(|x| x.begin_interval > cur)
         {
-            Ok(index) => self.intervals[index].rounding_mod,
-            Err(index) if index != 0 => self.intervals[index - 1].rounding_mod,
-            _ => unreachable!(),
-        } as f64;
-
-        let m = if payout >= 0.0 {
-            payout % rounding_mod
+            Some(next) if next.begin_interval - 1 < end => next.begin_interval - 1,
+            _ => end,
+        };
+        let rounded = u64::min(rounding_intervals.round(cur, payout), total_collateral);
+        let count = (sub_end - cur + 1) as usize;
+        if cur_range.payout.offer == rounded {
+            cur_range.count += count;
         } else {
-            payout % rounding_mod + rounding_mod
+            let prev = std::mem::replace(
+                cur_range,
+                RangePayout {
+                    start: cur as usize,
+                    count,
+                    payout: Payout {
+                        offer: rounded,
+                        accept: total_collateral - rounded,
+                    },
+                },
+            );
+            range_payouts.push(prev);
+        }
+
+        if sub_end == end {
+            return Ok(());
+        }
+        cur = sub_end + 1;
+    }
+}
+
+impl StepPayoutCurvePiece {
+    fn evaluate(&self, outcome: u64) -> f64 {
+        let index = match self
+            .payout_points
+            .binary_search_by(|x| x.event_outcome.cmp(&outcome))
+        {
+            Ok(index) => index,
+            Err(index) => index.saturating_sub(1),
         };
+        self.payout_points[index].get_outcome_payout()
+    }
+}
 
-        if m >= rounding_mod / 2.0 {
-            (payout + rounding_mod - m).round() as u64
-        } else {
-            (payout - m).round() as u64
+impl Evaluable for StepPayoutCurvePiece {
+    fn try_evaluate(&self, outcome: u64) -> Result<f64, Error> {
+        Ok(self.evaluate(outcome))
+    }
+
+    fn get_first_outcome(&self) -> u64 {
+        self.payout_points[0].event_outcome
+    }
+
+    fn get_last_outcome(&self) -> u64 {
+        self.payout_points.last().unwrap().event_outcome
+    }
+
+    fn to_range_payouts(
+        &self,
+        rounding_intervals: &RoundingIntervals,
+        total_collateral: u64,
+        range_payouts: &mut Vec<RangePayout>,
+    ) -> Result<(), Error> {
+        // The current range already contains the first outcome.
+        let mut cur_range =
+            self.get_cur_range(range_payouts, total_collateral, rounding_intervals)?;
+
+        for (i, (cur, next)) in self
+            .payout_points
+            .iter()
+            .zip(self.payout_points.iter().skip(1))
+            .enumerate()
+        {
+            let start = if i == 0 {
+                cur.event_outcome + 1
+            } else {
+                cur.event_outcome
+            };
+            if start < next.event_outcome {
+                push_constant_range(
+                    start,
+                    next.event_outcome - 1,
+                    cur.get_outcome_payout(),
+                    rounding_intervals,
+                    total_collateral,
+                    &mut cur_range,
+                    range_payouts,
+                )?;
+            }
         }
+
+        let last = self.payout_points.last().unwrap();
+        push_constant_range(
+            last.event_outcome,
+            last.event_outcome,
+            last.get_outcome_payout(),
+            rounding_intervals,
+            total_collateral,
+            &mut cur_range,
+            range_payouts,
+        )?;
+
+        range_payouts.push(cur_range);
+
+        Ok(())
+    }
+}
+
+/// A function piece represented by a monotone cubic spline going through the
+/// payout points, using the Fritsch-Carlson method to compute the tangents.
+/// Contrary to polynomial interpolation, the curve never overshoots the payouts
+/// of the points surrounding an outcome.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MonotoneCubicPayoutCurvePiece {
+    /// The set of points to be used to interpolate the spline.
+    pub(crate) payout_points: Vec<PayoutPoint>,
+}
+
+impl MonotoneCubicPayoutCurvePiece {
+    /// Create a new MonotoneCubicPayoutCurvePiece
+    pub fn new(payout_points: Vec<PayoutPoint>) -> Result<Self, Error> {
+        let piece = MonotoneCubicPayoutCurvePiece { payout_points };
+        piece.validate()?;
+        Ok(piece)
     }
 
-    /// Validate that the instance is well formed, meaning non empty and with the
-    /// first interval starting at zero.
+    /// Validate that the piece has at least two points with ascending event
+    /// outcomes, which the computation of the tangents relies on.
     pub fn validate(&self) -> Result<(), Error> {
-        if self.intervals.is_empty() {
-            return Err(Error::InvalidParameters(
-                "Empty rounding intervals.".to_string(),
-            ));
+        let is_ascending = self.payout_points.len() > 1
+            && self
+                .payout_points
+                .iter()
+                .zip(self.payout_points.iter().skip(1))
+                .all(|(cur, next)| cur.event_outcome < next.event_outcome);
+        if is_ascending {
+            Ok(())
+        } else {
+            Err(Error::InvalidParameters(
+                "Payout points must have ascending event outcome value.".to_string(),
+            ))
         }
+    }
 
-        if self.intervals[0].begin_interval != 0 {
-            return Err(Error::InvalidParameters(
-                "Rounding interval doesn't start at 0.".to_string(),
-            ));
-        }
+    /// Computes the tangent at each point. Only basic floating point operations
+    /// are used so that all parties compute identical values.
+    fn get_tangents(&self) -> Vec<f64> {
+        let points = &self.payout_points;
+        let nb_points = points.len();
+        let secants: Vec<f64> = points
+            .iter()
+            .zip(points.iter().skip(1))
+            .map(|(cur, next)| {
+                (next.get_outcome_payout() - cur.get_outcome_payout())
+                    / (next.event_outcome - cur.event_outcome) as f64
+            })
+            .collect();
 
-        let mut cur = self.intervals[0].begin_interval;
+        let mut tangents = Vec::with_capacity(nb_points);
+        tangents.push(secants[0]);
+        for i in 1..nb_points - 1 {
+            if secants[i - 1] * secants[i] <= 0.0 {
+                tangents.push(0.0);
+            } else {
+                tangents.push((secants[i - 1] + secants[i]) / 2.0);
+            }
+        }
+        tangents.push(secants[nb_points - 2]);
 
-        if self.intervals.iter().skip(1).any(|x| {
-            let res = x.begin_interval <= cur;
-            cur = x.begin_interval;
-            res
-        }) {
-            return Err(Error::InvalidParameters(
-                "Rounding intervals being value are not strictly increasing".to_string(),
-            ));
+        for (i, secant) in secants.iter().enumerate() {
+            if *secant == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+            let alpha = tangents[i] / secant;
+            let beta = tangents[i + 1] / secant;
+            let norm = alpha * alpha + beta * beta;
+            if norm > 9.0 {
+                let tau = 3.0 / norm.sqrt();
+                tangents[i] = tau * alpha * secant;
+                tangents[i + 1] = tau * beta * secant;
+            }
         }
 
-        Ok(())
+        tangents
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use secp256k1_zkp::rand::{thread_rng, RngCore};
+/// Evaluates a monotone cubic spline with precomputed tangents.
+struct MonotoneCubicEvaluator<'a> {
+    piece: &'a MonotoneCubicPayoutCurvePiece,
+    tangents: Vec<f64>,
+}
 
-    #[test]
-    fn lagrange_interpolate_test() {
-        let polynomial = PolynomialPayoutCurvePiece {
-            payout_points: vec![
-                PayoutPoint {
-                    event_outcome: 0,
-                    outcome_payout: 1,
-                    extra_precision: 0,
-                },
-                PayoutPoint {
-                    event_outcome: 2,
-                    outcome_payout: 5,
-                    extra_precision: 0,
-                },
-                PayoutPoint {
-                    event_outcome: 4,
-                    outcome_payout: 17,
-                    extra_precision: 0,
-                },
-            ],
+impl<'a> MonotoneCubicEvaluator<'a> {
+    fn evaluate(&self, outcome: u64) -> f64 {
+        let points = &self.piece.payout_points;
+        let index = match points.binary_search_by(|x| x.event_outcome.cmp(&outcome)) {
+            Ok(index) => return points[index].get_outcome_payout(),
+            Err(index) => index.max(1).min(points.len() - 1) - 1,
+        };
+
+        let (left, right) = (&points[index], &points[index + 1]);
+        let (left_payout, right_payout) = (left.get_outcome_payout(), right.get_outcome_payout());
+        let h = (right.event_outcome - left.event_outcome) as f64;
+        let t = (outcome as f64 - left.event_outcome as f64) / h;
+        let t2 = t * t;
+        let t3 = t2 * t;
+
+        let res = (2.0 * t3 - 3.0 * t2 + 1.0) * left_payout
+            + (t3 - 2.0 * t2 + t) * h * self.tangents[index]
+            + (-2.0 * t3 + 3.0 * t2) * right_payout
+            + (t3 - t2) * h * self.tangents[index + 1];
+
+        // The spline is monotone between two points, clamping only removes
+        // floating point errors.
+        res.max(left_payout.min(right_payout))
+            .min(left_payout.max(right_payout))
+    }
+}
+
+impl<'a> Evaluable for MonotoneCubicEvaluator<'a> {
+    fn try_evaluate(&self, outcome: u64) -> Result<f64, Error> {
+        Ok(self.evaluate(outcome))
+    }
+
+    fn get_first_outcome(&self) -> u64 {
+        self.piece.get_first_outcome()
+    }
+
+    fn get_last_outcome(&self) -> u64 {
+        self.piece.get_last_outcome()
+    }
+}
+
+impl MonotoneCubicPayoutCurvePiece {
+    fn evaluate(&self, outcome: u64) -> f64 {
+        MonotoneCubicEvaluator {
+            piece: self,
+            tangents: self.get_tangents(),
+        }
+        .evaluate(outcome)
+    }
+}
+
+impl Evaluable for MonotoneCubicPayoutCurvePiece {
+    fn try_evaluate(&self, outcome: u64) -> Result<f64, Error> {
+        Ok(self.evaluate(outcome))
+    }
+
+    fn get_first_outcome(&self) -> u64 {
+        self.payout_points[0].event_outcome
+    }
+
+    fn get_last_outcome(&self) -> u64 {
+        self.payout_points.last().unwrap().event_outcome
+    }
+
+    fn to_range_payouts(
+        &self,
+        rounding_intervals: &RoundingIntervals,
+        total_collateral: u64,
+        range_payouts: &mut Vec<RangePayout>,
+    ) -> Result<(), Error> {
+        let evaluator = MonotoneCubicEvaluator {
+            piece: self,
+            tangents: self.get_tangents(),
+        };
+        compute_range_payouts(
+            &evaluator,
+            rounding_intervals,
+            total_collateral,
+            range_payouts,
+        )
+    }
+}
+
+/// Provides information on if and how to round the payouts of a payout function
+/// to reduce the number of adaptor signatures required. A `rounding_mod` value
+/// of 1 indicates that no rounding is performed.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct RoundingInterval {
+    /// The start of the rounding interval.
+    pub begin_interval: u64,
+    /// The rounding modulus value.
+    pub rounding_mod: u64,
+}
+
+/// A set of rounding intervals.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct RoundingIntervals {
+    /// Contains the rounding intervals.
+    pub intervals: Vec<RoundingInterval>,
+}
+
+impl RoundingIntervals {
+    /// Round the given payout based on the rounding modulus matching the given
+    /// outcome.
+    pub fn round(&self, outcome: u64, payout: f64) -> u64 {
+        let rounding_mod = match self
+            .intervals
+            .binary_search_by(|x| x.begin_interval.cmp(&outcome))
+        {
+            Ok(index) => self.intervals[index].rounding_mod,
+            Err(index) if index != 0 => self.intervals[index - 1].rounding_mod,
+            _ => unreachable!(),
+        } as f64;
+
+        let m = if payout >= 0.0 {
+            payout % rounding_mod
+        } else {
+            payout % rounding_mod + rounding_mod
+        };
+
+        if m >= rounding_mod / 2.0 {
+            (payout + rounding_mod - m).round() as u64
+        } else {
+            (payout - m).round() as u64
+        }
+    }
+
+    /// Validate that the instance is well formed, meaning non empty, with the
+    /// first interval starting at zero, strictly increasing interval starts
+    /// and non zero rounding moduli.
+    pub fn validate(&self) -> Result<(), Error> {
+        let first = self
+            .intervals
+            .first()
+            .ok_or_else(|| Error::InvalidParameters("Empty rounding intervals.".to_string()))?;
+
+        if first.begin_interval != 0 {
+            return Err(Error::InvalidParameters(format!(
+                "Rounding interval doesn't start at 0 but at {}.",
+                first.begin_interval
+            )));
+        }
+
+        for (i, (prev, cur)) in self
+            .intervals
+            .iter()
+            .zip(self.intervals.iter().skip(1))
+            .enumerate()
+        {
+            if cur.begin_interval <= prev.begin_interval {
+                return Err(Error::InvalidParameters(format!(
+                    "Rounding interval {} starting at {} overlaps with previous one starting at {}.",
+                    i + 1,
+                    cur.begin_interval,
+                    prev.begin_interval
+                )));
+            }
+        }
+
+        if let Some(i) = self.intervals.iter().position(|x| x.rounding_mod == 0) {
+            return Err(Error::InvalidParameters(format!(
+                "Rounding interval {} has a zero rounding modulus.",
+                i
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Validate that the instance is well formed and that each interval covers
+    /// at least one outcome of `[0, max_value]`.
+    pub fn validate_for_outcomes(&self, max_value: u64) -> Result<(), Error> {
+        self.validate()?;
+
+        let last = self
+            .intervals
+            .last()
+            .expect("to have at least one interval");
+        if last.begin_interval > max_value {
+            return Err(Error::InvalidParameters(format!(
+                "Rounding interval {} starts at {} which is beyond the maximum outcome {}.",
+                self.intervals.len() - 1,
+                last.begin_interval,
+                max_value
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lagrange_interpolate_test() {
+        let polynomial = PolynomialPayoutCurvePiece {
+            payout_points: vec![
+                PayoutPoint {
+                    event_outcome: 0,
+                    outcome_payout: 1,
+                    extra_precision: 0,
+                },
+                PayoutPoint {
+                    event_outcome: 2,
+                    outcome_payout: 5,
+                    extra_precision: 0,
+                },
+                PayoutPoint {
+                    event_outcome: 4,
+                    outcome_payout: 17,
+                    extra_precision: 0,
+                },
+            ],
         };
 
         assert_eq!(101_f64, polynomial.evaluate(10));
@@ -704,35 +1327,103 @@ mod test {
     }
 
     #[test]
-    fn hyperbola_evaluate_test() {
-        let d = (thread_rng().next_u64() as f64) + (thread_rng().next_u64() as f64 / 100.0);
-        let translate_payout =
-            (thread_rng().next_u64() as f64) + (thread_rng().next_u64() as f64 / 100.0);
-        let outcomes: Vec<_> = (0..100).map(|_| thread_rng().next_u64()).collect();
-        let expected_payout = |outcome: u64| -> f64 { d / outcome as f64 + translate_payout };
+    fn hyperbola_fixed_point_test_vectors() {
+        // (outcome, use_positive_piece, translate_outcome, translate_payout,
+        // a, b, c, d, expected fixed point result)
+        let test_vectors: Vec<(u64, bool, f64, f64, f64, f64, f64, f64, i128)> = vec![
+            (
+                30_000,
+                true,
+                0.0,
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+                63_000_000_000_000_000_000.0,
+                9_019_431_321_600_000_000_000_000,
+            ),
+            (10, true, 2.5, 0.0, 1.0, -1.375, 0.0, 10.0, 5_593_091_061),
+            (500, true, 2.5, 0.0, 1.0, -1.375, 0.0, 10.0, 86_330_521),
+            (0, true, 50.0, 50.0, 5.0, -1.0, 0.0, 1.0, 429_925_370_770),
+            (1023, true, 50.0, 50.0, 5.0, -1.0, 0.0, 1.0, 214_770_435_430),
+            (7, false, 0.0, 0.0, 1.0, -2.0, 0.5, 3.0, -47_458_677_539),
+            (
+                3,
+                true,
+                0.75,
+                100.0,
+                0.5,
+                0.125,
+                -0.25,
+                2.0,
+                426_658_340_123,
+            ),
+        ];
 
-        let hyperbola = HyperbolaPayoutCurvePiece {
-            left_end_point: PayoutPoint {
-                event_outcome: 1,
-                outcome_payout: 0,
-                extra_precision: 0,
-            },
-            right_end_point: PayoutPoint {
-                event_outcome: u64::MAX,
-                outcome_payout: 0,
-                extra_precision: 0,
-            },
-            use_positive_piece: true,
-            translate_outcome: 0.0,
+        for (
+            outcome,
+            use_positive_piece,
+            translate_outcome,
             translate_payout,
-            a: 1.0,
-            b: 0.0,
-            c: 0.0,
+            a,
+            b,
+            c,
             d,
-        };
+            expected,
+        ) in test_vectors
+        {
+            let hyperbola = HyperbolaPayoutCurvePiece::new(
+                PayoutPoint {
+                    event_outcome: 0,
+                    outcome_payout: 0,
+                    extra_precision: 0,
+                },
+                PayoutPoint {
+                    event_outcome: 1 << 20,
+                    outcome_payout: 0,
+                    extra_precision: 0,
+                },
+                use_positive_piece,
+                translate_outcome,
+                translate_payout,
+                a,
+                b,
+                c,
+                d,
+            )
+            .expect("valid parameters");
+            assert_eq!(expected, hyperbola.evaluate_fixed_point(outcome).unwrap());
+        }
+    }
 
-        for outcome in outcomes {
-            assert_eq!(expected_payout(outcome), hyperbola.evaluate(outcome));
+    #[test]
+    fn hyperbola_parameters_losing_precision_are_rejected() {
+        for (a, d) in &[(1e-12, 1.0), (1.0, 0.1), (0.0, 1.0)] {
+            let hyperbola = HyperbolaPayoutCurvePiece {
+                left_end_point: PayoutPoint {
+                    event_outcome: 1,
+                    outcome_payout: 0,
+                    extra_precision: 0,
+                },
+                right_end_point: PayoutPoint {
+                    event_outcome: 1000,
+                    outcome_payout: 0,
+                    extra_precision: 0,
+                },
+                use_positive_piece: true,
+                translate_outcome: 0.0,
+                translate_payout: 0.0,
+                a: *a,
+                b: 1.0,
+                c: 1.0,
+                d: *d,
+            };
+            hyperbola
+                .validate()
+                .expect_err("parameters should be exactly representable");
+            hyperbola
+                .try_evaluate(10)
+                .expect_err("parameters should not be truncated");
         }
     }
 
@@ -753,8 +1444,8 @@ mod test {
             translate_outcome: 2.5,
             translate_payout: 0.0,
             a: 1.0,
-            b: -1.4,
-            c: -0.1,
+            b: -1.375,
+            c: -0.125,
             d: 10.0,
         };
 
@@ -789,7 +1480,7 @@ mod test {
             translate_outcome: 2.5,
             translate_payout: 0.0,
             a: 1.0,
-            b: -1.4,
+            b: -1.375,
             c: 0.0,
             d: 10.0,
         };
@@ -808,6 +1499,328 @@ mod test {
             .expect("to be able to compute the range payouts");
     }
 
+    #[test]
+    fn hyperbola_fixed_point_evaluate_test() {
+        let hyperbola = HyperbolaPayoutCurvePiece {
+            left_end_point: PayoutPoint {
+                event_outcome: 1,
+                outcome_payout: 0,
+                extra_precision: 0,
+            },
+            right_end_point: PayoutPoint {
+                event_outcome: 1 << 20,
+                outcome_payout: 0,
+                extra_precision: 0,
+            },
+            use_positive_piece: true,
+            translate_outcome: 0.0,
+            translate_payout: 0.0,
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 2_100_000_000_000_000.0 * 30_000.0,
+        };
+
+        assert_eq!(
+            2_100_000_000_000_000.0,
+            hyperbola.try_evaluate(30_000).unwrap()
+        );
+        assert_eq!(
+            63_000_000_000_000.0,
+            hyperbola.try_evaluate(1_000_000).unwrap()
+        );
+    }
+
+    #[test]
+    fn hyperbola_fixed_point_overflow_test() {
+        let hyperbola = HyperbolaPayoutCurvePiece {
+            left_end_point: PayoutPoint {
+                event_outcome: 1,
+                outcome_payout: 0,
+                extra_precision: 0,
+            },
+            right_end_point: PayoutPoint {
+                event_outcome: 1000,
+                outcome_payout: 0,
+                extra_precision: 0,
+            },
+            use_positive_piece: true,
+            translate_outcome: 0.0,
+            translate_payout: 0.0,
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 1e30,
+        };
+
+        hyperbola
+            .try_evaluate(10)
+            .expect_err("Should not silently overflow");
+    }
+
+    #[test]
+    fn isqrt_test() {
+        for value in &[0u128, 1, 2, 3, 4, 15, 16, 17, u64::MAX as u128, u128::MAX] {
+            let root = isqrt(*value);
+            assert!(root * root <= *value);
+            assert!((root + 1)
+                .checked_mul(root + 1)
+                .map_or(true, |x| x > *value));
+        }
+    }
+
+    #[test]
+    fn step_to_range_payouts_test() {
+        let payout_function = PayoutFunction::new(vec![
+            PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+                PolynomialPayoutCurvePiece::new(vec![
+                    PayoutPoint {
+                        event_outcome: 0,
+                        outcome_payout: 0,
+                        extra_precision: 0,
+                    },
+                    PayoutPoint {
+                        event_outcome: 10,
+                        outcome_payout: 0,
+                        extra_precision: 0,
+                    },
+                ])
+                .unwrap(),
+            ),
+            PayoutFunctionPiece::StepPayoutCurvePiece(
+                StepPayoutCurvePiece::new(vec![
+                    PayoutPoint {
+                        event_outcome: 10,
+                        outcome_payout: 0,
+                        extra_precision: 0,
+                    },
+                    PayoutPoint {
+                        event_outcome: 20,
+                        outcome_payout: 52,
+                        extra_precision: 0,
+                    },
+                    PayoutPoint {
+                        event_outcome: 30,
+                        outcome_payout: 100,
+                        extra_precision: 0,
+                    },
+                    PayoutPoint {
+                        event_outcome: 40,
+                        outcome_payout: 100,
+                        extra_precision: 0,
+                    },
+                ])
+                .unwrap(),
+            ),
+        ])
+        .unwrap();
+        let rounding_intervals = RoundingIntervals {
+            intervals: vec![
+                RoundingInterval {
+                    begin_interval: 0,
+                    rounding_mod: 1,
+                },
+                RoundingInterval {
+                    begin_interval: 25,
+                    rounding_mod: 10,
+                },
+            ],
+        };
+        let expected_ranges = vec![
+            RangePayout {
+                start: 0,
+                count: 20,
+                payout: Payout {
+                    offer: 0,
+                    accept: 100,
+                },
+            },
+            RangePayout {
+                start: 20,
+                count: 5,
+                payout: Payout {
+                    offer: 52,
+                    accept: 48,
+                },
+            },
+            RangePayout {
+                start: 25,
+                count: 5,
+                payout: Payout {
+                    offer: 50,
+                    accept: 50,
+                },
+            },
+            RangePayout {
+                start: 30,
+                count: 11,
+                payout: Payout {
+                    offer: 100,
+                    accept: 0,
+                },
+            },
+        ];
+
+        assert_eq!(
+            expected_ranges,
+            payout_function
+                .to_range_payouts(100, &rounding_intervals)
+                .expect("to be able to compute the range payouts.")
+        );
+    }
+
+    #[test]
+    fn monotone_cubic_does_not_overshoot_test() {
+        let payout_points = vec![
+            PayoutPoint {
+                event_outcome: 0,
+                outcome_payout: 0,
+                extra_precision: 0,
+            },
+            PayoutPoint {
+                event_outcome: 10,
+                outcome_payout: 10,
+                extra_precision: 0,
+            },
+            PayoutPoint {
+                event_outcome: 20,
+                outcome_payout: 100,
+                extra_precision: 0,
+            },
+            PayoutPoint {
+                event_outcome: 30,
+                outcome_payout: 100,
+                extra_precision: 0,
+            },
+        ];
+        let rounding_intervals = RoundingIntervals {
+            intervals: vec![RoundingInterval {
+                begin_interval: 0,
+                rounding_mod: 1,
+            }],
+        };
+
+        PolynomialPayoutCurvePiece::new(payout_points.clone())
+            .unwrap()
+            .to_range_payouts(&rounding_intervals, 100, &mut Vec::new())
+            .expect_err("Polynomial interpolation should go out of range");
+
+        let spline = MonotoneCubicPayoutCurvePiece::new(payout_points).unwrap();
+        let mut range_payouts = Vec::new();
+        spline
+            .to_range_payouts(&rounding_intervals, 100, &mut range_payouts)
+            .expect("to be able to compute the range payouts");
+
+        assert_eq!(0, range_payouts[0].payout.offer);
+        assert!(range_payouts
+            .iter()
+            .zip(range_payouts.iter().skip(1))
+            .all(|(cur, next)| cur.payout.offer < next.payout.offer
+                && cur.start + cur.count == next.start));
+        let last = range_payouts.last().unwrap();
+        assert_eq!(100, last.payout.offer);
+        assert_eq!(31, last.start + last.count);
+        for (outcome, payout) in &[(10, 10), (20, 100)] {
+            assert_eq!(*payout as f64, spline.evaluate(*outcome));
+        }
+    }
+
+    #[test]
+    fn payout_function_json_test() {
+        let input = r#"{"payoutFunctionPieces":[{"polynomialPayoutCurvePiece":{"payoutPoints":[{"eventOutcome":0,"outcomePayout":0,"extraPrecision":0},{"eventOutcome":10,"outcomePayout":100,"extraPrecision":0}]}},{"hyperbolaPayoutCurvePiece":{"leftEndPoint":{"eventOutcome":10,"outcomePayout":100,"extraPrecision":0},"rightEndPoint":{"eventOutcome":20,"outcomePayout":50,"extraPrecision":0},"usePositivePiece":true,"translateOutcome":0.0,"translatePayout":0.0,"a":1.0,"b":0.0,"c":0.0,"d":1000.0}},{"stepPayoutCurvePiece":{"payoutPoints":[{"eventOutcome":20,"outcomePayout":50,"extraPrecision":0},{"eventOutcome":30,"outcomePayout":60,"extraPrecision":0}]}},{"monotoneCubicPayoutCurvePiece":{"payoutPoints":[{"eventOutcome":30,"outcomePayout":60,"extraPrecision":0},{"eventOutcome":35,"outcomePayout":80,"extraPrecision":0},{"eventOutcome":40,"outcomePayout":100,"extraPrecision":0}]}}]}"#;
+        let rounding_input = r#"{"intervals":[{"beginInterval":0,"roundingMod":1},{"beginInterval":20,"roundingMod":10}]}"#;
+
+        let payout_function: PayoutFunction = serde_json::from_str(input).unwrap();
+        let rounding_intervals: RoundingIntervals = serde_json::from_str(rounding_input).unwrap();
+
+        payout_function.validate(40).unwrap();
+        rounding_intervals.validate().unwrap();
+        assert_eq!(input, serde_json::to_string(&payout_function).unwrap());
+        assert_eq!(
+            rounding_input,
+            serde_json::to_string(&rounding_intervals).unwrap()
+        );
+    }
+
+    #[test]
+    fn step_payout_curve_validity_test() {
+        StepPayoutCurvePiece::new(vec![
+            PayoutPoint {
+                event_outcome: 10,
+                outcome_payout: 0,
+                extra_precision: 0,
+            },
+            PayoutPoint {
+                event_outcome: 10,
+                outcome_payout: 10,
+                extra_precision: 0,
+            },
+        ])
+        .expect_err("Step points should have ascending outcomes");
+    }
+
+    #[test]
+    fn payout_function_with_non_ascending_step_points_is_invalid() {
+        let payout_function = PayoutFunction {
+            payout_function_pieces: vec![PayoutFunctionPiece::StepPayoutCurvePiece(
+                StepPayoutCurvePiece {
+                    payout_points: vec![
+                        PayoutPoint {
+                            event_outcome: 0,
+                            outcome_payout: 0,
+                            extra_precision: 0,
+                        },
+                        PayoutPoint {
+                            event_outcome: 20,
+                            outcome_payout: 50,
+                            extra_precision: 0,
+                        },
+                        PayoutPoint {
+                            event_outcome: 10,
+                            outcome_payout: 60,
+                            extra_precision: 0,
+                        },
+                        PayoutPoint {
+                            event_outcome: 40,
+                            outcome_payout: 100,
+                            extra_precision: 0,
+                        },
+                    ],
+                },
+            )],
+        };
+
+        payout_function
+            .validate(40)
+            .expect_err("Step points should have ascending outcomes");
+    }
+
+    #[test]
+    fn payout_function_with_duplicate_monotone_cubic_points_is_invalid() {
+        let point = |event_outcome, outcome_payout| PayoutPoint {
+            event_outcome,
+            outcome_payout,
+            extra_precision: 0,
+        };
+        let get_payout_function = |payout_points| PayoutFunction {
+            payout_function_pieces: vec![PayoutFunctionPiece::MonotoneCubicPayoutCurvePiece(
+                MonotoneCubicPayoutCurvePiece { payout_points },
+            )],
+        };
+
+        get_payout_function(vec![
+            point(0, 0),
+            point(20, 50),
+            point(20, 60),
+            point(40, 100),
+        ])
+        .validate(40)
+        .expect_err("Monotone cubic points should have ascending outcomes");
+        get_payout_function(vec![point(0, 0)])
+            .validate(0)
+            .expect_err("Monotone cubic pieces should have at least two points");
+    }
+
     #[test]
     fn hyperbola_invalid_parameters_tests() {
         HyperbolaPayoutCurvePiece::new(
@@ -1098,6 +2111,49 @@ mod test {
         }
     }
 
+    #[test]
+    fn rounding_intervals_zero_modulus_is_invalid() {
+        let rounding_intervals = RoundingIntervals {
+            intervals: vec![
+                RoundingInterval {
+                    begin_interval: 0,
+                    rounding_mod: 1,
+                },
+                RoundingInterval {
+                    begin_interval: 10,
+                    rounding_mod: 0,
+                },
+            ],
+        };
+
+        rounding_intervals
+            .validate()
+            .expect_err("should not accept zero rounding modulus");
+    }
+
+    #[test]
+    fn rounding_intervals_beyond_max_outcome_is_invalid() {
+        let rounding_intervals = RoundingIntervals {
+            intervals: vec![
+                RoundingInterval {
+                    begin_interval: 0,
+                    rounding_mod: 1,
+                },
+                RoundingInterval {
+                    begin_interval: 1024,
+                    rounding_mod: 10,
+                },
+            ],
+        };
+
+        rounding_intervals
+            .validate_for_outcomes(1024)
+            .expect("interval starting at max outcome to be valid");
+        rounding_intervals
+            .validate_for_outcomes(1023)
+            .expect_err("should not accept interval starting beyond max outcome");
+    }
+
     #[test]
     fn rounding_intervals_valid_is_valid() {
         let rounding_intervals = RoundingIntervals {
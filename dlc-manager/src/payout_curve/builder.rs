@@ -0,0 +1,530 @@
+//! # Payout curve builder
+//! Constructors producing validated payout functions and rounding intervals for
+//! common financial products from strike and notional parameters. Payouts are
+//! always expressed from the point of view of the offer party.
+
+use super::{
+    HyperbolaPayoutCurvePiece, PayoutFunction, PayoutFunctionPiece, PayoutPoint,
+    PolynomialPayoutCurvePiece, RoundingInterval, RoundingIntervals, StepPayoutCurvePiece,
+};
+use crate::error::Error;
+
+/// A payout function together with the rounding intervals to use with it.
+#[derive(Clone, Debug)]
+pub struct PayoutCurve {
+    /// The payout function of the product.
+    pub payout_function: PayoutFunction,
+    /// The rounding intervals to apply to the payout function.
+    pub rounding_intervals: RoundingIntervals,
+}
+
+/// The direction of an option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionType {
+    /// Pays out when the outcome is above the strike.
+    Call,
+    /// Pays out when the outcome is below the strike.
+    Put,
+}
+
+/// Builds payout curves for contracts whose outcomes range over
+/// `[0, max_outcome]` and locking `total_collateral` in the funding output.
+#[derive(Clone, Debug)]
+pub struct PayoutCurveBuilder {
+    max_outcome: u64,
+    total_collateral: u64,
+    rounding_mod: u64,
+}
+
+impl PayoutCurveBuilder {
+    /// Creates a new builder. `max_outcome` is the largest outcome that can be
+    /// attested, e.g. `2^nb_digits - 1` for a base 2 decomposition.
+    pub fn new(max_outcome: u64, total_collateral: u64) -> Self {
+        PayoutCurveBuilder {
+            max_outcome,
+            total_collateral,
+            rounding_mod: 1,
+        }
+    }
+
+    /// Sets the rounding modulus applied over the whole outcome range, which
+    /// defaults to 1 (no rounding).
+    pub fn rounding_mod(mut self, rounding_mod: u64) -> Self {
+        self.rounding_mod = rounding_mod;
+        self
+    }
+
+    /// Covered call where the offer party sells a call with the given strike
+    /// and locks the whole collateral as underlying. Below the strike the offer
+    /// party receives the total collateral, above it the value of the strike
+    /// expressed in collateral, `total_collateral * strike / outcome`.
+    pub fn covered_call(&self, strike: u64) -> Result<PayoutCurve, Error> {
+        if strike == 0 || strike >= self.max_outcome {
+            return Err(Error::InvalidParameters(
+                "Strike must be within the outcome range.".to_string(),
+            ));
+        }
+
+        let d = self.total_collateral as f64 * strike as f64;
+        let max_payout = d / self.max_outcome as f64;
+        let right_end_point = PayoutPoint {
+            event_outcome: self.max_outcome,
+            outcome_payout: max_payout.trunc() as u64,
+            extra_precision: (max_payout.fract() * ((1 << 16) as f64)) as u16,
+        };
+        let hyperbola = HyperbolaPayoutCurvePiece::new(
+            self.point(strike, self.total_collateral),
+            right_end_point,
+            true,
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+            d,
+        )?;
+
+        let pieces = vec![
+            PayoutFunctionPiece::PolynomialPayoutCurvePiece(PolynomialPayoutCurvePiece::new(
+                vec![
+                    self.point(0, self.total_collateral),
+                    self.point(strike, self.total_collateral),
+                ],
+            )?),
+            PayoutFunctionPiece::HyperbolaPayoutCurvePiece(hyperbola),
+        ];
+
+        self.build(PayoutFunction::new(pieces)?)
+    }
+
+    /// Put bought by the offer party, paying `notional` per unit of outcome
+    /// below the strike, capped at the total collateral.
+    pub fn put(&self, strike: u64, notional: u64) -> Result<PayoutCurve, Error> {
+        if strike > self.max_outcome {
+            return Err(Error::InvalidParameters(
+                "Strike must be within the outcome range.".to_string(),
+            ));
+        }
+
+        let points = self.clamped_line(strike, 0, notional, false)?;
+        self.linear(&points)
+    }
+
+    /// Vertical spread bought by the offer party. For a call spread the payout
+    /// goes from zero at `lower_strike` to the total collateral at
+    /// `upper_strike`, and the other way around for a put spread.
+    pub fn vertical_spread(
+        &self,
+        option_type: OptionType,
+        lower_strike: u64,
+        upper_strike: u64,
+    ) -> Result<PayoutCurve, Error> {
+        if lower_strike >= upper_strike || upper_strike > self.max_outcome {
+            return Err(Error::InvalidParameters(
+                "Strikes must be ascending and within the outcome range.".to_string(),
+            ));
+        }
+
+        let (low_payout, high_payout) = match option_type {
+            OptionType::Call => (0, self.total_collateral),
+            OptionType::Put => (self.total_collateral, 0),
+        };
+
+        self.linear(&[
+            (0, low_payout),
+            (lower_strike, low_payout),
+            (upper_strike, high_payout),
+            (self.max_outcome, high_payout),
+        ])
+    }
+
+    /// Linear contract for difference where the offer party is long, putting
+    /// up `offer_collateral` and gaining `notional` per unit of outcome above
+    /// `entry_outcome`. Payouts are capped by the collateral of each party.
+    pub fn capped_linear_cfd(
+        &self,
+        entry_outcome: u64,
+        offer_collateral: u64,
+        notional: u64,
+    ) -> Result<PayoutCurve, Error> {
+        if entry_outcome > self.max_outcome || offer_collateral > self.total_collateral {
+            return Err(Error::InvalidParameters(
+                "Entry outcome and offer collateral must be within the contract bounds."
+                    .to_string(),
+            ));
+        }
+
+        let points = self.clamped_line(entry_outcome, offer_collateral, notional, true)?;
+        self.linear(&points)
+    }
+
+    /// Binary option bought by the offer party, paying the total collateral
+    /// if the outcome is at or above the strike for a call, or below it for a
+    /// put.
+    pub fn binary_option(
+        &self,
+        option_type: OptionType,
+        strike: u64,
+    ) -> Result<PayoutCurve, Error> {
+        if strike == 0 || strike > self.max_outcome {
+            return Err(Error::InvalidParameters(
+                "Strike must be within the outcome range.".to_string(),
+            ));
+        }
+
+        let (below, above) = match option_type {
+            OptionType::Call => (0, self.total_collateral),
+            OptionType::Put => (self.total_collateral, 0),
+        };
+
+        let mut points = vec![self.point(0, below), self.point(strike, above)];
+        if strike < self.max_outcome {
+            points.push(self.point(self.max_outcome, above));
+        }
+        let piece = StepPayoutCurvePiece::new(points)?;
+
+        self.build(PayoutFunction::new(vec![
+            PayoutFunctionPiece::StepPayoutCurvePiece(piece),
+        ])?)
+    }
+
+    /// Inverse (coin margined) future where the offer party is long a
+    /// position worth `notional` sats at `entry_price`, putting up
+    /// `notional / leverage` as collateral. The offer payout is
+    /// `offer_collateral + notional - notional * entry_price / outcome`, which
+    /// is kept constant below the price at which the offer party is
+    /// liquidated and above the one at which the accept party is. Tighter
+    /// `liquidation_bounds` can be given to close the position earlier.
+    pub fn inverse_future(
+        &self,
+        entry_price: u64,
+        notional: u64,
+        leverage: u64,
+        liquidation_bounds: Option<(u64, u64)>,
+    ) -> Result<PayoutCurve, Error> {
+        if entry_price == 0 || entry_price > self.max_outcome || notional == 0 || leverage == 0 {
+            return Err(Error::InvalidParameters(
+                "Entry price, notional and leverage must be non zero and within bounds."
+                    .to_string(),
+            ));
+        }
+        let offer_collateral = notional / leverage;
+        if offer_collateral > self.total_collateral {
+            return Err(Error::InvalidParameters(
+                "Offer collateral cannot be greater than total collateral.".to_string(),
+            ));
+        }
+
+        // Use integers for the liquidation prices so that payouts stay within
+        // the collateral bounds.
+        let translate_payout = (offer_collateral + notional) as u128;
+        let d = notional as u128 * entry_price as u128;
+        let mut lower = ((d + translate_payout - 1) / translate_payout) as u64;
+        let mut upper = match translate_payout.checked_sub(self.total_collateral as u128) {
+            Some(x) if x > 0 => u64::min((d / x) as u64, self.max_outcome),
+            _ => self.max_outcome,
+        };
+        if let Some((low_bound, high_bound)) = liquidation_bounds {
+            if low_bound > entry_price || high_bound < entry_price {
+                return Err(Error::InvalidParameters(
+                    "Liquidation bounds must contain the entry price.".to_string(),
+                ));
+            }
+            lower = u64::max(lower, low_bound);
+            upper = u64::min(upper, high_bound);
+        }
+        if lower >= upper {
+            return Err(Error::InvalidParameters(
+                "Leverage is too high for the outcome range.".to_string(),
+            ));
+        }
+
+        // Constant pieces ignore the extra precision of their points, so
+        // breakpoints are rounded to the nearest sat to match the rounded
+        // hyperbola payouts next to them.
+        let payout_at = |outcome: u64| {
+            let numerator = translate_payout * outcome as u128 - d;
+            let payout = (2 * numerator + outcome as u128) / (2 * outcome as u128);
+            self.point(outcome, payout as u64)
+        };
+        let left_point = payout_at(lower);
+        let right_point = payout_at(upper);
+
+        let mut pieces = vec![PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+            PolynomialPayoutCurvePiece::new(vec![
+                self.point(0, left_point.outcome_payout),
+                left_point.clone(),
+            ])?,
+        )];
+        pieces.push(PayoutFunctionPiece::HyperbolaPayoutCurvePiece(
+            HyperbolaPayoutCurvePiece::new(
+                left_point,
+                right_point.clone(),
+                true,
+                0.0,
+                translate_payout as f64,
+                1.0,
+                0.0,
+                0.0,
+                -(d as f64),
+            )?,
+        ));
+        if upper < self.max_outcome {
+            pieces.push(PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+                PolynomialPayoutCurvePiece::new(vec![
+                    right_point.clone(),
+                    self.point(self.max_outcome, right_point.outcome_payout),
+                ])?,
+            ));
+        }
+
+        self.build(PayoutFunction::new(pieces)?)
+    }
+
+    /// Builds a piecewise linear payout curve going through the given
+    /// `(outcome, payout)` points, which must start at zero and end at the
+    /// maximum outcome.
+    pub fn linear(&self, points: &[(u64, u64)]) -> Result<PayoutCurve, Error> {
+        if points.iter().any(|x| x.1 > self.total_collateral) {
+            return Err(Error::InvalidParameters(
+                "Payout cannot be greater than total collateral.".to_string(),
+            ));
+        }
+
+        let mut pieces = Vec::new();
+        for (cur, next) in points.iter().zip(points.iter().skip(1)) {
+            if cur.0 == next.0 && cur.1 == next.1 {
+                continue;
+            }
+            pieces.push(PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+                PolynomialPayoutCurvePiece::new(vec![
+                    self.point(cur.0, cur.1),
+                    self.point(next.0, next.1),
+                ])?,
+            ));
+        }
+
+        self.build(PayoutFunction::new(pieces)?)
+    }
+
+    /// Returns the points of the line going through `(anchor_outcome,
+    /// anchor_payout)` with a slope of `notional`, clamped to the outcome range
+    /// and to payouts between zero and the total collateral. Breakpoints are
+    /// rounded towards the anchor so that they stay on the line.
+    fn clamped_line(
+        &self,
+        anchor_outcome: u64,
+        anchor_payout: u64,
+        notional: u64,
+        increasing: bool,
+    ) -> Result<Vec<(u64, u64)>, Error> {
+        if notional == 0 {
+            return Err(Error::InvalidParameters(
+                "Notional must be greater than zero.".to_string(),
+            ));
+        }
+
+        let (left_room, right_room) = if increasing {
+            (anchor_payout, self.total_collateral - anchor_payout)
+        } else {
+            (self.total_collateral - anchor_payout, anchor_payout)
+        };
+        let apply = |steps: u64, up: bool| {
+            if up {
+                anchor_payout + notional * steps
+            } else {
+                anchor_payout - notional * steps
+            }
+        };
+
+        let left_steps = u64::min(left_room / notional, anchor_outcome);
+        let right_steps = u64::min(right_room / notional, self.max_outcome - anchor_outcome);
+        let left = (anchor_outcome - left_steps, apply(left_steps, !increasing));
+        let right = (anchor_outcome + right_steps, apply(right_steps, increasing));
+
+        Ok(vec![(0, left.1), left, right, (self.max_outcome, right.1)])
+    }
+
+    fn point(&self, event_outcome: u64, outcome_payout: u64) -> PayoutPoint {
+        PayoutPoint {
+            event_outcome,
+            outcome_payout,
+            extra_precision: 0,
+        }
+    }
+
+    fn build(&self, payout_function: PayoutFunction) -> Result<PayoutCurve, Error> {
+        if self.rounding_mod == 0 {
+            return Err(Error::InvalidParameters(
+                "Rounding modulus must be greater than zero.".to_string(),
+            ));
+        }
+        payout_function.validate(self.max_outcome)?;
+        let rounding_intervals = RoundingIntervals {
+            intervals: vec![RoundingInterval {
+                begin_interval: 0,
+                rounding_mod: self.rounding_mod,
+            }],
+        };
+        rounding_intervals.validate()?;
+
+        Ok(PayoutCurve {
+            payout_function,
+            rounding_intervals,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAX_OUTCOME: u64 = 1023;
+    const TOTAL_COLLATERAL: u64 = 100000;
+
+    fn get_payout(curve: &PayoutCurve, outcome: u64) -> u64 {
+        let range_payouts = curve
+            .payout_function
+            .to_range_payouts(TOTAL_COLLATERAL, &curve.rounding_intervals)
+            .expect("to be able to compute the range payouts");
+        range_payouts
+            .iter()
+            .find(|x| x.start as u64 <= outcome && outcome < (x.start + x.count) as u64)
+            .expect("to have a range for each outcome")
+            .payout
+            .offer
+    }
+
+    fn builder() -> PayoutCurveBuilder {
+        PayoutCurveBuilder::new(MAX_OUTCOME, TOTAL_COLLATERAL)
+    }
+
+    #[test]
+    fn covered_call_test() {
+        let curve = builder().covered_call(500).unwrap();
+
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&curve, 0));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&curve, 500));
+        assert_eq!(50000, get_payout(&curve, 1000));
+    }
+
+    #[test]
+    fn put_test() {
+        let curve = builder().put(600, 1000).unwrap();
+
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&curve, 0));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&curve, 500));
+        assert_eq!(50000, get_payout(&curve, 550));
+        assert_eq!(0, get_payout(&curve, 600));
+        assert_eq!(0, get_payout(&curve, MAX_OUTCOME));
+    }
+
+    #[test]
+    fn vertical_spread_test() {
+        let call = builder()
+            .vertical_spread(OptionType::Call, 200, 400)
+            .unwrap();
+        let put = builder()
+            .vertical_spread(OptionType::Put, 200, 400)
+            .unwrap();
+
+        assert_eq!(0, get_payout(&call, 100));
+        assert_eq!(50000, get_payout(&call, 300));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&call, 500));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&put, 100));
+        assert_eq!(50000, get_payout(&put, 300));
+        assert_eq!(0, get_payout(&put, 500));
+    }
+
+    #[test]
+    fn capped_linear_cfd_test() {
+        let curve = builder().capped_linear_cfd(500, 40000, 1000).unwrap();
+
+        assert_eq!(0, get_payout(&curve, 0));
+        assert_eq!(0, get_payout(&curve, 460));
+        assert_eq!(40000, get_payout(&curve, 500));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&curve, 560));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&curve, MAX_OUTCOME));
+    }
+
+    #[test]
+    fn capped_linear_cfd_not_reaching_bounds_test() {
+        let curve = builder().capped_linear_cfd(500, 50000, 10).unwrap();
+
+        assert_eq!(45000, get_payout(&curve, 0));
+        assert_eq!(55230, get_payout(&curve, MAX_OUTCOME));
+    }
+
+    #[test]
+    fn binary_option_test() {
+        let call = builder().binary_option(OptionType::Call, 512).unwrap();
+        let put = builder().binary_option(OptionType::Put, 512).unwrap();
+
+        assert_eq!(0, get_payout(&call, 511));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&call, 512));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&put, 511));
+        assert_eq!(0, get_payout(&put, 512));
+    }
+
+    #[test]
+    fn inverse_future_test() {
+        let curve = builder().inverse_future(500, 50000, 1, None).unwrap();
+
+        assert_eq!(0, get_payout(&curve, 100));
+        assert_eq!(0, get_payout(&curve, 250));
+        assert_eq!(50000, get_payout(&curve, 500));
+        assert_eq!(75000, get_payout(&curve, 1000));
+    }
+
+    #[test]
+    fn inverse_future_liquidation_test() {
+        let curve = builder().inverse_future(200, 60000, 1, None).unwrap();
+
+        assert_eq!(0, get_payout(&curve, 50));
+        assert_eq!(60000, get_payout(&curve, 200));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&curve, 600));
+        assert_eq!(TOTAL_COLLATERAL, get_payout(&curve, MAX_OUTCOME));
+    }
+
+    #[test]
+    fn inverse_future_liquidation_bounds_test() {
+        let curve = builder()
+            .inverse_future(500, 50000, 2, Some((400, 800)))
+            .unwrap();
+
+        assert_eq!(12500, get_payout(&curve, 0));
+        assert_eq!(12500, get_payout(&curve, 400));
+        assert_eq!(25000, get_payout(&curve, 500));
+        assert_eq!(43750, get_payout(&curve, 800));
+        assert_eq!(43750, get_payout(&curve, MAX_OUTCOME));
+    }
+
+    #[test]
+    fn invalid_parameters_test() {
+        builder()
+            .covered_call(MAX_OUTCOME)
+            .expect_err("strike should be lower than max outcome");
+        builder()
+            .vertical_spread(OptionType::Call, 400, 200)
+            .expect_err("strikes should be ascending");
+        builder()
+            .capped_linear_cfd(500, TOTAL_COLLATERAL + 1, 10)
+            .expect_err("offer collateral should not exceed total collateral");
+        builder()
+            .put(500, 0)
+            .expect_err("notional should not be zero");
+        builder()
+            .binary_option(OptionType::Call, 0)
+            .expect_err("strike should be greater than zero");
+        builder()
+            .inverse_future(500, 50000, 0, None)
+            .expect_err("leverage should not be zero");
+        builder()
+            .inverse_future(500, 50000, 1, Some((600, 800)))
+            .expect_err("liquidation bounds should contain the entry price");
+        builder()
+            .rounding_mod(0)
+            .binary_option(OptionType::Call, 512)
+            .expect_err("rounding modulus should be greater than zero");
+    }
+}
@@ -0,0 +1,251 @@
+//! # Payout function fitting
+//! Approximates arbitrary payout curves with payout function pieces, so that
+//! callers only need to provide the payout for each outcome.
+
+use super::{
+    round_to_fixed_point, Evaluable, HyperbolaPayoutCurvePiece, PayoutFunction,
+    PayoutFunctionPiece, PayoutPoint, PolynomialPayoutCurvePiece,
+};
+use crate::error::Error;
+
+/// Builds a payout function approximating `payout` over `[0, max_outcome]`
+/// within `max_error` sats (before rounding). The closure is evaluated for
+/// every outcome in the range.
+pub fn fit_payout_function<F: Fn(u64) -> u64>(
+    payout: F,
+    max_outcome: u64,
+    max_error: u64,
+) -> Result<PayoutFunction, Error> {
+    let samples: Vec<(u64, u64)> = (0..=max_outcome).map(|x| (x, payout(x))).collect();
+    fit_samples(&samples, max_error)
+}
+
+/// Builds a payout function going through the first and last of the given
+/// `(outcome, payout)` samples and deviating from the others by at most
+/// `max_error` sats (before rounding). Samples must have strictly increasing
+/// outcomes. Pieces are extended as far as possible, using linear pieces or
+/// hyperbolas whichever covers more samples.
+pub fn fit_samples(samples: &[(u64, u64)], max_error: u64) -> Result<PayoutFunction, Error> {
+    let is_ascending = samples.len() > 1
+        && samples
+            .iter()
+            .zip(samples.iter().skip(1))
+            .all(|(cur, next)| cur.0 < next.0);
+    if !is_ascending {
+        return Err(Error::InvalidParameters(
+            "Samples must contain at least two points with ascending outcomes.".to_string(),
+        ));
+    }
+
+    let max_error = max_error as f64;
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < samples.len() - 1 {
+        let linear_end = get_linear_end(samples, start, max_error);
+        let (end, piece) = match get_hyperbola_end(samples, start, linear_end, max_error) {
+            Some((end, hyperbola)) => (
+                end,
+                PayoutFunctionPiece::HyperbolaPayoutCurvePiece(hyperbola),
+            ),
+            None => (
+                linear_end,
+                PayoutFunctionPiece::PolynomialPayoutCurvePiece(PolynomialPayoutCurvePiece::new(
+                    vec![to_point(samples[start]), to_point(samples[linear_end])],
+                )?),
+            ),
+        };
+        pieces.push(piece);
+        start = end;
+    }
+
+    PayoutFunction::new(pieces)
+}
+
+fn to_point(sample: (u64, u64)) -> PayoutPoint {
+    PayoutPoint {
+        event_outcome: sample.0,
+        outcome_payout: sample.1,
+        extra_precision: 0,
+    }
+}
+
+/// Returns the index of the furthest sample that can be reached from `start`
+/// with a line staying within `max_error` of the samples in between. The set
+/// of slopes satisfying all the samples seen so far is maintained so that each
+/// sample is only visited once.
+fn get_linear_end(samples: &[(u64, u64)], start: usize, max_error: f64) -> usize {
+    let (start_outcome, start_payout) = (samples[start].0, samples[start].1 as f64);
+    let mut min_slope = f64::NEG_INFINITY;
+    let mut max_slope = f64::INFINITY;
+    let mut end = start + 1;
+
+    for (i, (outcome, payout)) in samples.iter().enumerate().skip(start + 1) {
+        let dx = (outcome - start_outcome) as f64;
+        let dy = *payout as f64 - start_payout;
+        let slope = dy / dx;
+        if slope < min_slope || slope > max_slope {
+            break;
+        }
+        end = i;
+        min_slope = f64::max(min_slope, (dy - max_error) / dx);
+        max_slope = f64::min(max_slope, (dy + max_error) / dx);
+    }
+
+    end
+}
+
+/// Returns the hyperbola `d / outcome + translate_payout` going through the
+/// samples at `start` and at the returned index, if one reaching further than
+/// `min_end` within `max_error` exists.
+fn get_hyperbola_end(
+    samples: &[(u64, u64)],
+    start: usize,
+    min_end: usize,
+    max_error: f64,
+) -> Option<(usize, HyperbolaPayoutCurvePiece)> {
+    if samples[start].0 == 0 || min_end + 1 >= samples.len() {
+        return None;
+    }
+
+    // Look for the furthest valid end by doubling the distance and then
+    // narrowing it down with a binary search.
+    let mut best = None;
+    let mut step = 1;
+    let mut low = min_end;
+    let mut high = samples.len();
+    while low + 1 < high {
+        let candidate = usize::min(low + step, high - 1);
+        match try_fit_hyperbola(samples, start, candidate, max_error) {
+            Some(hyperbola) => {
+                best = Some((candidate, hyperbola));
+                low = candidate;
+                step *= 2;
+            }
+            None => {
+                high = candidate;
+                step = usize::max(1, (candidate - low) / 2);
+            }
+        }
+    }
+
+    best
+}
+
+fn try_fit_hyperbola(
+    samples: &[(u64, u64)],
+    start: usize,
+    end: usize,
+    max_error: f64,
+) -> Option<HyperbolaPayoutCurvePiece> {
+    let (left, right) = (samples[start], samples[end]);
+    if left.1 == right.1 {
+        return None;
+    }
+    let (x0, y0, x1, y1) = (left.0 as f64, left.1 as f64, right.0 as f64, right.1 as f64);
+    // Hyperbola parameters must be representable as fixed point numbers.
+    let d = round_to_fixed_point((y0 - y1) * x0 * x1 / (x1 - x0));
+    let translate_payout = round_to_fixed_point(y0 - d / x0);
+    let hyperbola = HyperbolaPayoutCurvePiece::new(
+        to_point(left),
+        to_point(right),
+        true,
+        0.0,
+        translate_payout,
+        1.0,
+        0.0,
+        0.0,
+        d,
+    )
+    .ok()?;
+
+    let is_within_error = samples[start..=end].iter().all(|(outcome, payout)| {
+        hyperbola
+            .try_evaluate(*outcome)
+            .map(|x| x >= 0.0 && (x - *payout as f64).abs() <= max_error)
+            .unwrap_or(false)
+    });
+
+    if is_within_error {
+        Some(hyperbola)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::payout_curve::{RoundingInterval, RoundingIntervals};
+
+    fn get_max_error<F: Fn(u64) -> u64>(
+        payout_function: &PayoutFunction,
+        payout: F,
+        total_collateral: u64,
+    ) -> u64 {
+        let range_payouts = payout_function
+            .to_range_payouts(
+                total_collateral,
+                &RoundingIntervals {
+                    intervals: vec![RoundingInterval {
+                        begin_interval: 0,
+                        rounding_mod: 1,
+                    }],
+                },
+            )
+            .expect("to be able to compute the range payouts");
+        range_payouts
+            .iter()
+            .flat_map(|r| (r.start..r.start + r.count).map(move |x| (x as u64, r.payout.offer)))
+            .map(|(outcome, fitted)| {
+                let expected = payout(outcome);
+                if fitted > expected {
+                    fitted - expected
+                } else {
+                    expected - fitted
+                }
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn fit_piecewise_linear_test() {
+        let payout = |x: u64| -> u64 {
+            if x < 100 {
+                0
+            } else if x < 200 {
+                (x - 100) * 1000
+            } else {
+                100000
+            }
+        };
+
+        let payout_function = fit_payout_function(payout, 1023, 0).unwrap();
+
+        assert_eq!(3, payout_function.payout_function_pieces.len());
+        payout_function.validate(1023).unwrap();
+        assert_eq!(0, get_max_error(&payout_function, payout, 100000));
+    }
+
+    #[test]
+    fn fit_inverse_payout_test() {
+        let payout = |x: u64| -> u64 {
+            if x < 100 {
+                100000
+            } else {
+                ((100000 * 100) as f64 / x as f64).round() as u64
+            }
+        };
+
+        let payout_function = fit_payout_function(payout, 1023, 1).unwrap();
+
+        assert_eq!(2, payout_function.payout_function_pieces.len());
+        assert!(get_max_error(&payout_function, payout, 100000) <= 2);
+    }
+
+    #[test]
+    fn fit_samples_invalid_test() {
+        fit_samples(&[(0, 0)], 0).expect_err("should require at least two samples");
+        fit_samples(&[(0, 0), (10, 1), (5, 2)], 0).expect_err("should require ascending outcomes");
+    }
+}
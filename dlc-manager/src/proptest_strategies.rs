@@ -0,0 +1,364 @@
+//! #Proptest strategies
+//! [`proptest`] strategies generating valid contract inputs, payout functions,
+//! rounding intervals and oracle announcements, so that downstream crates can
+//! test their own policy and serialization layers against realistic DLC
+//! structures. Only available with the `proptest` feature.
+//!
+//! Numerical events generated here always use base 2 and unsigned outcomes,
+//! and the announcements used by a contract all share the same event.
+
+use std::collections::BTreeSet;
+
+use dlc::{EnumerationPayout, Payout};
+use dlc_messages::oracle_msgs::{
+    DigitDecompositionEventDescriptor, EnumEventDescriptor, EventDescriptor, OracleAnnouncement,
+    OracleEvent,
+};
+use dlc_trie::OracleNumericInfo;
+use lightning::util::ser::Writeable;
+use proptest::collection;
+use proptest::prelude::*;
+use secp256k1_zkp::hashes::sha256;
+use secp256k1_zkp::{KeyPair, Message, Secp256k1, SecretKey, XOnlyPublicKey};
+
+use crate::contract::contract_input::{ContractInput, ContractInputInfo, OracleInput};
+use crate::contract::enum_descriptor::EnumDescriptor;
+use crate::contract::numerical_descriptor::NumericalDescriptor;
+use crate::contract::ContractDescriptor;
+use crate::payout_curve::{
+    PayoutFunction, PayoutFunctionPiece, PayoutPoint, PolynomialPayoutCurvePiece, RoundingInterval,
+    RoundingIntervals, StepPayoutCurvePiece,
+};
+
+/// The maximum collateral of each party, in satoshis.
+pub const MAX_COLLATERAL: u64 = 100 * 100_000_000;
+/// The maximum fee rate accepted by [`ContractInput::validate`].
+pub const MAX_FEE_RATE: u64 = 25 * 250;
+/// The maximum number of digits of numerical events.
+pub const MAX_NB_DIGITS: usize = 16;
+/// The maximum number of outcomes of enumerated events.
+pub const MAX_NB_OUTCOMES: usize = 8;
+/// The maximum number of oracles of a contract.
+pub const MAX_NB_ORACLES: usize = 5;
+/// The maximum number of contract infos of a contract input.
+pub const MAX_NB_CONTRACT_INFOS: usize = 3;
+/// The maximum number of pieces of a payout function.
+pub const MAX_NB_PIECES: usize = 5;
+/// The maximum number of rounding intervals.
+pub const MAX_NB_ROUNDING_INTERVALS: usize = 4;
+/// The maximum rounding modulus of a rounding interval.
+pub const MAX_ROUNDING_MOD: u64 = 100_000;
+
+/// Returns a strategy generating valid secret keys.
+pub fn secret_key() -> impl Strategy<Value = SecretKey> {
+    any::<[u8; 32]>().prop_filter_map("Invalid secret key", |x| SecretKey::from_slice(&x).ok())
+}
+
+/// Returns a strategy generating distinct outcomes for an enumerated event.
+pub fn enum_outcomes() -> impl Strategy<Value = Vec<String>> {
+    collection::btree_set("[a-z0-9_]{1,16}", 2..=MAX_NB_OUTCOMES)
+        .prop_map(|x| x.into_iter().collect())
+}
+
+/// Returns a strategy generating event descriptors, either enumerated or
+/// numerical.
+pub fn event_descriptor() -> impl Strategy<Value = EventDescriptor> {
+    prop_oneof![
+        enum_outcomes()
+            .prop_map(|outcomes| EventDescriptor::EnumEvent(EnumEventDescriptor { outcomes })),
+        (2..=MAX_NB_DIGITS, "[a-z]{1,8}", -8i32..=8).prop_map(|(nb_digits, unit, precision)| {
+            EventDescriptor::DigitDecompositionEvent(DigitDecompositionEventDescriptor {
+                base: 2,
+                is_signed: false,
+                unit,
+                precision,
+                nb_digits: nb_digits as u16,
+            })
+        }),
+    ]
+}
+
+/// Returns a strategy generating announcements of the given event, signed by
+/// a random oracle and using random nonces.
+pub fn oracle_announcement(
+    event_id: String,
+    event_descriptor: EventDescriptor,
+) -> impl Strategy<Value = OracleAnnouncement> {
+    let nb_nonces = match &event_descriptor {
+        EventDescriptor::EnumEvent(_) => 1,
+        EventDescriptor::DigitDecompositionEvent(d) => d.nb_digits as usize,
+    };
+    let secp = Secp256k1::new();
+    (
+        secret_key(),
+        collection::vec(secret_key(), nb_nonces),
+        any::<u32>(),
+    )
+        .prop_map(move |(oracle_key, nonce_keys, event_maturity_epoch)| {
+            let x_only = |sk: &SecretKey| {
+                XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(&secp, sk)).0
+            };
+            let oracle_event = OracleEvent {
+                oracle_nonces: nonce_keys.iter().map(x_only).collect(),
+                event_maturity_epoch,
+                event_descriptor: event_descriptor.clone(),
+                event_id: event_id.clone(),
+            };
+            let msg = Message::from_hashed_data::<sha256::Hash>(&oracle_event.encode());
+            let key_pair = KeyPair::from_secret_key(&secp, &oracle_key);
+            OracleAnnouncement {
+                announcement_signature: secp.sign_schnorr_no_aux_rand(&msg, &key_pair),
+                oracle_public_key: x_only(&oracle_key),
+                oracle_event,
+            }
+        })
+}
+
+/// Returns a strategy generating descriptors paying each of the given
+/// outcomes.
+pub fn enum_descriptor(
+    outcomes: Vec<String>,
+    total_collateral: u64,
+) -> impl Strategy<Value = EnumDescriptor> {
+    collection::vec(0..=total_collateral, outcomes.len()).prop_map(move |offer_payouts| {
+        EnumDescriptor {
+            outcome_payouts: outcomes
+                .iter()
+                .zip(offer_payouts)
+                .map(|(outcome, offer)| EnumerationPayout {
+                    outcome: outcome.clone(),
+                    payout: Payout {
+                        offer,
+                        accept: total_collateral - offer,
+                    },
+                })
+                .collect(),
+        }
+    })
+}
+
+/// Returns a strategy generating continuous payout functions covering
+/// `[0, max_value]`, made of linear and step pieces whose payouts are within
+/// `[0, total_collateral]`. `max_value` must be at least 2.
+pub fn payout_function(
+    max_value: u64,
+    total_collateral: u64,
+) -> impl Strategy<Value = PayoutFunction> {
+    let max_nb_inner_points = std::cmp::min(MAX_NB_PIECES as u64 - 1, max_value - 1) as usize;
+    collection::btree_set(1..max_value, 0..=max_nb_inner_points)
+        .prop_flat_map(move |inner_points: BTreeSet<u64>| {
+            let mut outcomes = vec![0];
+            outcomes.extend(inner_points);
+            outcomes.push(max_value);
+            let nb_pieces = outcomes.len() - 1;
+            (
+                collection::vec(0..=total_collateral, outcomes.len()),
+                collection::vec(any::<bool>(), nb_pieces),
+                Just(outcomes),
+            )
+        })
+        .prop_map(|(payouts, is_step, outcomes)| {
+            let points = outcomes
+                .into_iter()
+                .zip(payouts)
+                .map(|(event_outcome, outcome_payout)| PayoutPoint {
+                    event_outcome,
+                    outcome_payout,
+                    extra_precision: 0,
+                })
+                .collect::<Vec<_>>();
+            let pieces = points
+                .windows(2)
+                .zip(is_step)
+                .map(|(points, is_step)| {
+                    if is_step {
+                        StepPayoutCurvePiece::new(points.to_vec())
+                            .map(PayoutFunctionPiece::StepPayoutCurvePiece)
+                    } else {
+                        PolynomialPayoutCurvePiece::new(points.to_vec())
+                            .map(PayoutFunctionPiece::PolynomialPayoutCurvePiece)
+                    }
+                    .expect("points to be ascending")
+                })
+                .collect();
+            PayoutFunction::new(pieces).expect("pieces to be continuous")
+        })
+}
+
+/// Returns a strategy generating rounding intervals valid for the outcomes
+/// in `[0, max_value]`.
+pub fn rounding_intervals(max_value: u64) -> impl Strategy<Value = RoundingIntervals> {
+    let max_nb_inner_intervals =
+        std::cmp::min(MAX_NB_ROUNDING_INTERVALS as u64 - 1, max_value) as usize;
+    (
+        collection::btree_set(1..=max_value, 0..=max_nb_inner_intervals),
+        collection::vec(1..=MAX_ROUNDING_MOD, MAX_NB_ROUNDING_INTERVALS),
+    )
+        .prop_map(|(inner_begins, rounding_mods)| RoundingIntervals {
+            intervals: std::iter::once(0)
+                .chain(inner_begins)
+                .zip(rounding_mods)
+                .map(|(begin_interval, rounding_mod)| RoundingInterval {
+                    begin_interval,
+                    rounding_mod,
+                })
+                .collect(),
+        })
+}
+
+/// Returns a strategy generating descriptors for a numerical event in base 2
+/// with the given number of digits, attested by the given number of oracles.
+pub fn numerical_descriptor(
+    nb_digits: usize,
+    nb_oracles: usize,
+    total_collateral: u64,
+) -> impl Strategy<Value = NumericalDescriptor> {
+    let max_value = (1u64 << nb_digits) - 1;
+    (
+        payout_function(max_value, total_collateral),
+        rounding_intervals(max_value),
+    )
+        .prop_map(
+            move |(payout_function, rounding_intervals)| NumericalDescriptor {
+                payout_function,
+                rounding_intervals,
+                difference_params: None,
+                oracle_numeric_infos: OracleNumericInfo {
+                    base: 2,
+                    nb_digits: vec![nb_digits; nb_oracles],
+                },
+                truncated_digits: Vec::new(),
+            },
+        )
+}
+
+/// Returns a strategy generating descriptors matching the given event.
+pub fn contract_descriptor(
+    event_descriptor: &EventDescriptor,
+    nb_oracles: usize,
+    total_collateral: u64,
+) -> BoxedStrategy<ContractDescriptor> {
+    match event_descriptor {
+        EventDescriptor::EnumEvent(e) => enum_descriptor(e.outcomes.clone(), total_collateral)
+            .prop_map(ContractDescriptor::Enum)
+            .boxed(),
+        EventDescriptor::DigitDecompositionEvent(d) => {
+            numerical_descriptor(d.nb_digits as usize, nb_oracles, total_collateral)
+                .prop_map(ContractDescriptor::Numerical)
+                .boxed()
+        }
+    }
+}
+
+/// Returns a strategy generating contract input infos along with the
+/// announcements of the oracles they refer to.
+pub fn contract_input_info_with_announcements(
+    total_collateral: u64,
+) -> impl Strategy<Value = (ContractInputInfo, Vec<OracleAnnouncement>)> {
+    (event_descriptor(), "[a-zA-Z0-9]{1,32}", 1..=MAX_NB_ORACLES)
+        .prop_flat_map(move |(event_descriptor, event_id, nb_oracles)| {
+            (
+                contract_descriptor(&event_descriptor, nb_oracles, total_collateral),
+                collection::vec(
+                    oracle_announcement(event_id.clone(), event_descriptor),
+                    nb_oracles,
+                ),
+                1..=nb_oracles as u16,
+                Just(event_id),
+            )
+        })
+        .prop_map(
+            |(contract_descriptor, announcements, threshold, event_id)| {
+                let oracles = OracleInput {
+                    public_keys: announcements.iter().map(|x| x.oracle_public_key).collect(),
+                    event_id,
+                    event_ids: Vec::new(),
+                    threshold,
+                };
+                (
+                    ContractInputInfo {
+                        contract_descriptor,
+                        oracles,
+                    },
+                    announcements,
+                )
+            },
+        )
+}
+
+/// Returns a strategy generating valid contract inputs along with, for each
+/// of their contract infos, the announcements of the oracles they refer to.
+pub fn contract_input_with_announcements(
+) -> impl Strategy<Value = (ContractInput, Vec<Vec<OracleAnnouncement>>)> {
+    (1..=MAX_COLLATERAL, 1..=MAX_COLLATERAL)
+        .prop_flat_map(|(offer_collateral, accept_collateral)| {
+            (
+                Just(offer_collateral),
+                Just(accept_collateral),
+                1..=MAX_FEE_RATE,
+                collection::vec(
+                    contract_input_info_with_announcements(offer_collateral + accept_collateral),
+                    1..=MAX_NB_CONTRACT_INFOS,
+                ),
+            )
+        })
+        .prop_map(|(offer_collateral, accept_collateral, fee_rate, infos)| {
+            let (contract_infos, announcements) = infos.into_iter().unzip();
+            (
+                ContractInput {
+                    offer_collateral,
+                    accept_collateral,
+                    fee_rate,
+                    contract_infos,
+                    metadata: None,
+                    payout_script_pubkey: None,
+                    change_script_pubkey: None,
+                    additional_payout_outputs: Vec::new(),
+                },
+                announcements,
+            )
+        })
+}
+
+/// Returns a strategy generating valid contract inputs.
+pub fn contract_input() -> impl Strategy<Value = ContractInput> {
+    contract_input_with_announcements().prop_map(|(contract_input, _)| contract_input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::contract_info::ContractInfo;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn generated_contract_inputs_are_valid(
+            (contract_input, announcements) in contract_input_with_announcements()
+        ) {
+            let secp = Secp256k1::verification_only();
+            contract_input.validate().unwrap();
+            let total_collateral = contract_input.offer_collateral + contract_input.accept_collateral;
+            for (info, announcements) in contract_input.contract_infos.into_iter().zip(announcements) {
+                for announcement in &announcements {
+                    announcement.validate(&secp).unwrap();
+                }
+                let contract_info = ContractInfo {
+                    contract_descriptor: info.contract_descriptor,
+                    oracle_announcements: announcements,
+                    threshold: info.oracles.threshold as usize,
+                };
+                contract_info.validate().unwrap();
+                contract_info.get_payouts(total_collateral).unwrap();
+            }
+        }
+
+        #[test]
+        fn generated_rounding_intervals_are_valid(
+            (max_value, rounding_intervals) in (2..1000u64).prop_flat_map(|x| (Just(x), rounding_intervals(x)))
+        ) {
+            rounding_intervals.validate_for_outcomes(max_value).unwrap();
+        }
+    }
+}
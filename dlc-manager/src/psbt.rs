@@ -0,0 +1,268 @@
+//! Conversion of fund transactions to and from PSBTs, used to have the funding
+//! inputs signed by an external signer such as a hardware wallet.
+
+use bitcoin::consensus::Decodable;
+use bitcoin::util::psbt::PartiallySignedTransaction;
+use bitcoin::{EcdsaSighashType, Transaction, TxOut, Txid, Witness};
+
+use crate::contract::FundingInputInfo;
+use crate::error::Error;
+
+/// Creates a PSBT for the given fund transaction, with the previous output
+/// data of all its funding inputs populated. Inputs that were already signed
+/// are finalized, and the `own_inputs` are set to be signed with the given
+/// sighash type.
+pub(crate) fn create_funding_psbt(
+    fund_tx: &Transaction,
+    counter_inputs: &[FundingInputInfo],
+    own_inputs: &[FundingInputInfo],
+    sighash_type: EcdsaSighashType,
+) -> Result<PartiallySignedTransaction, Error> {
+    let mut unsigned_tx = fund_tx.clone();
+    for input in unsigned_tx.input.iter_mut() {
+        input.script_sig = bitcoin::Script::new();
+        input.witness = Witness::new();
+    }
+    let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned_tx)
+        .map_err(|e| Error::InvalidState(format!("Could not create funding PSBT: {}", e)))?;
+
+    let funding_inputs = counter_inputs
+        .iter()
+        .map(|x| (x, false))
+        .chain(own_inputs.iter().map(|x| (x, true)));
+    for (funding_input_info, is_own) in funding_inputs {
+        let funding_input = &funding_input_info.funding_input;
+        let (prev_tx, prev_output) = decode_prev_tx(funding_input_info)?;
+        let input_index = get_input_index(fund_tx, &prev_tx.txid(), funding_input.prev_tx_vout)?;
+        let input = &mut psbt.inputs[input_index];
+        input.witness_utxo = Some(prev_output);
+        input.non_witness_utxo = Some(prev_tx);
+        if !funding_input.redeem_script.is_empty() {
+            input.redeem_script = Some(funding_input.redeem_script.clone());
+        }
+        if is_own {
+            input.sighash_type = Some(sighash_type.into());
+        }
+    }
+
+    for (input, tx_in) in psbt.inputs.iter_mut().zip(fund_tx.input.iter()) {
+        if !tx_in.script_sig.is_empty() {
+            input.final_script_sig = Some(tx_in.script_sig.clone());
+        }
+        if !tx_in.witness.is_empty() {
+            input.final_script_witness = Some(tx_in.witness.clone());
+        }
+    }
+
+    Ok(psbt)
+}
+
+/// Sets the signatures of the `own_inputs` found in the given PSBT on the
+/// fund transaction, and returns their witnesses in the same order.
+pub(crate) fn apply_funding_psbt(
+    fund_tx: &mut Transaction,
+    psbt: &PartiallySignedTransaction,
+    own_inputs: &[FundingInputInfo],
+) -> Result<Vec<Witness>, Error> {
+    if psbt.unsigned_tx.txid() != fund_tx.txid() {
+        return Err(Error::InvalidParameters(
+            "PSBT does not correspond to the fund transaction".to_string(),
+        ));
+    }
+
+    own_inputs
+        .iter()
+        .map(|funding_input_info| {
+            let funding_input = &funding_input_info.funding_input;
+            let (prev_tx, _) = decode_prev_tx(funding_input_info)?;
+            let input_index =
+                get_input_index(fund_tx, &prev_tx.txid(), funding_input.prev_tx_vout)?;
+            let not_signed = || {
+                Error::InvalidParameters(format!(
+                    "Funding input with serial id {} is not signed in the PSBT",
+                    funding_input.input_serial_id
+                ))
+            };
+            let input = psbt.inputs.get(input_index).ok_or_else(not_signed)?;
+
+            let witness = match &input.final_script_witness {
+                Some(witness) => witness.clone(),
+                // A single signature for a native P2WPKH input is enough to
+                // finalize it, so signers are not required to do it.
+                None if input.partial_sigs.len() == 1 && funding_input.redeem_script.is_empty() => {
+                    let (pubkey, sig) = input.partial_sigs.iter().next().expect("to have one");
+                    Witness::from_vec(vec![sig.to_vec(), pubkey.to_bytes()])
+                }
+                None => return Err(not_signed()),
+            };
+
+            dlc::util::validate_funding_witness_sighash_types(&witness).map_err(|_| {
+                Error::InvalidParameters(format!(
+                    "Invalid sighash type for funding input with serial id {}",
+                    funding_input.input_serial_id
+                ))
+            })?;
+
+            if let Some(script_sig) = &input.final_script_sig {
+                fund_tx.input[input_index].script_sig = script_sig.clone();
+            }
+            fund_tx.input[input_index].witness = witness.clone();
+
+            Ok(witness)
+        })
+        .collect()
+}
+
+fn decode_prev_tx(funding_input_info: &FundingInputInfo) -> Result<(Transaction, TxOut), Error> {
+    let funding_input = &funding_input_info.funding_input;
+    let prev_tx =
+        Transaction::consensus_decode(&mut funding_input.prev_tx.as_slice()).map_err(|_| {
+            Error::InvalidParameters(
+                "Could not decode funding input previous tx parameter".to_string(),
+            )
+        })?;
+    let vout = funding_input.prev_tx_vout;
+    let prev_output = prev_tx
+        .output
+        .get(vout as usize)
+        .ok_or_else(|| {
+            Error::InvalidParameters(format!("Previous tx output not found at index {}", vout))
+        })?
+        .clone();
+    Ok((prev_tx, prev_output))
+}
+
+fn get_input_index(fund_tx: &Transaction, txid: &Txid, vout: u32) -> Result<usize, Error> {
+    fund_tx
+        .input
+        .iter()
+        .position(|x| x.previous_output.txid == *txid && x.previous_output.vout == vout)
+        .ok_or_else(|| {
+            Error::InvalidState(format!(
+                "Could not find funding input {}:{} in the fund transaction",
+                txid, vout
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::Encodable;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{EcdsaSig, OutPoint, PackedLockTime, Script, Sequence, TxIn};
+    use dlc_messages::FundingInput;
+    use secp256k1_zkp::{Message, PublicKey, SecretKey, SECP256K1};
+
+    fn get_funding_input_info(prev_tx: &Transaction, serial_id: u64) -> FundingInputInfo {
+        let mut writer = Vec::new();
+        prev_tx.consensus_encode(&mut writer).unwrap();
+        FundingInputInfo {
+            funding_input: FundingInput {
+                input_serial_id: serial_id,
+                prev_tx: writer,
+                prev_tx_vout: 0,
+                sequence: 0xffffffff,
+                max_witness_len: 107,
+                redeem_script: Script::new(),
+            },
+            address: None,
+        }
+    }
+
+    fn get_prev_tx(value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new_v0_p2wpkh(&bitcoin::WPubkeyHash::hash(&[value as u8])),
+            }],
+        }
+    }
+
+    fn get_fund_tx(prev_txs: &[&Transaction]) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: prev_txs
+                .iter()
+                .map(|x| TxIn {
+                    previous_output: OutPoint {
+                        txid: x.txid(),
+                        vout: 0,
+                    },
+                    script_sig: Script::new(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: vec![],
+        }
+    }
+
+    #[test]
+    fn funding_psbt_round_trip() {
+        let own_prev_tx = get_prev_tx(1000);
+        let counter_prev_tx = get_prev_tx(2000);
+        let own_inputs = vec![get_funding_input_info(&own_prev_tx, 1)];
+        let counter_inputs = vec![get_funding_input_info(&counter_prev_tx, 0)];
+        let mut fund_tx = get_fund_tx(&[&counter_prev_tx, &own_prev_tx]);
+        let counter_witness = Witness::from_vec(vec![vec![1], vec![2]]);
+        fund_tx.input[0].witness = counter_witness.clone();
+
+        let mut psbt = create_funding_psbt(
+            &fund_tx,
+            &counter_inputs,
+            &own_inputs,
+            EcdsaSighashType::All,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(counter_witness.clone()),
+            psbt.inputs[0].final_script_witness
+        );
+        assert_eq!(
+            Some(own_prev_tx.output[0].clone()),
+            psbt.inputs[1].witness_utxo
+        );
+        assert_eq!(
+            Some(EcdsaSighashType::All.into()),
+            psbt.inputs[1].sighash_type
+        );
+        assert!(psbt.inputs[0].sighash_type.is_none());
+
+        assert!(apply_funding_psbt(&mut fund_tx.clone(), &psbt, &own_inputs).is_err());
+
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let sig = EcdsaSig::sighash_all(
+            SECP256K1.sign_ecdsa(&Message::from_slice(&[2; 32]).unwrap(), &sk),
+        );
+        let pubkey = bitcoin::PublicKey::new(PublicKey::from_secret_key(SECP256K1, &sk));
+        psbt.inputs[1].partial_sigs.insert(pubkey, sig);
+
+        let witnesses = apply_funding_psbt(&mut fund_tx, &psbt, &own_inputs).unwrap();
+
+        let expected = Witness::from_vec(vec![sig.to_vec(), pubkey.to_bytes()]);
+        assert_eq!(vec![expected.clone()], witnesses);
+        assert_eq!(expected, fund_tx.input[1].witness);
+        assert_eq!(counter_witness, fund_tx.input[0].witness);
+    }
+
+    #[test]
+    fn funding_psbt_for_other_transaction_is_rejected() {
+        let own_prev_tx = get_prev_tx(1000);
+        let own_inputs = vec![get_funding_input_info(&own_prev_tx, 0)];
+        let fund_tx = get_fund_tx(&[&own_prev_tx]);
+        let psbt = create_funding_psbt(&fund_tx, &[], &own_inputs, EcdsaSighashType::All).unwrap();
+
+        let mut other_tx = get_fund_tx(&[&own_prev_tx, &get_prev_tx(5)]);
+
+        assert!(matches!(
+            apply_funding_psbt(&mut other_tx, &psbt, &own_inputs),
+            Err(Error::InvalidParameters(_))
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+//! #RateLimiter
+//! Per-peer limits on the messages processed by the
+//! [`crate::manager::Manager`], enforced before any cryptographic
+//! verification so that peers cannot make a node spend significant resources
+//! by flooding it with messages. Violations are recorded so that operators
+//! can ban abusive peers.
+
+use std::collections::HashMap;
+
+use dlc_messages::Message;
+use lightning::util::ser::Writeable;
+use secp256k1_zkp::PublicKey;
+
+use crate::error::Error;
+
+const WINDOW_DURATION: u64 = 60;
+
+/// Limits on the messages received from each peer. Limits set to `None` are
+/// not enforced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    /// The maximum number of contract and channel offers received per minute.
+    pub max_offers_per_minute: Option<u32>,
+    /// The maximum number of message bytes received per minute.
+    pub max_bytes_per_minute: Option<u64>,
+    /// The maximum number of received contract and channel offers that were
+    /// not accepted or rejected yet.
+    pub max_pending_handshakes: Option<usize>,
+}
+
+/// The limit that was exceeded by a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateLimitKind {
+    /// Too many offers were received within a minute.
+    Offers,
+    /// Too many bytes were received within a minute.
+    Bytes,
+    /// Too many offers are pending.
+    PendingHandshakes,
+}
+
+/// A message that was rejected because its sender exceeded a limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateLimitViolation {
+    /// The id of the peer that sent the message.
+    pub counter_party: PublicKey,
+    /// The limit that was exceeded.
+    pub kind: RateLimitKind,
+    /// The unix time at which the message was rejected.
+    pub timestamp: u64,
+}
+
+#[derive(Default)]
+struct PeerUsage {
+    window_start: u64,
+    nb_offers: u32,
+    nb_bytes: u64,
+}
+
+/// Tracks the messages received from each peer against a set of
+/// [`RateLimits`].
+#[derive(Default)]
+pub(crate) struct RateLimiter {
+    limits: RateLimits,
+    usages: HashMap<PublicKey, PeerUsage>,
+    violations: Vec<RateLimitViolation>,
+}
+
+impl RateLimiter {
+    pub(crate) fn set_limits(&mut self, limits: RateLimits) {
+        self.limits = limits;
+    }
+
+    /// Records the given message received from `counter_party` at `now`,
+    /// returning an error if it exceeds the limits. `get_nb_pending` is only
+    /// called for offers when the number of pending handshakes is limited.
+    pub(crate) fn check_message<F>(
+        &mut self,
+        counter_party: &PublicKey,
+        msg: &Message,
+        now: u64,
+        get_nb_pending: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce() -> Result<usize, Error>,
+    {
+        if self.limits == RateLimits::default() {
+            return Ok(());
+        }
+
+        let is_offer = matches!(
+            msg,
+            Message::Offer(_)
+                | Message::OfferChannel(_)
+                | Message::MultiPartyOffer(_)
+                | Message::NovationOffer(_)
+        );
+        let usage = self.usages.entry(*counter_party).or_default();
+        if usage.window_start + WINDOW_DURATION <= now {
+            *usage = PeerUsage {
+                window_start: now,
+                ..Default::default()
+            };
+        }
+        usage.nb_bytes += msg.serialized_length() as u64;
+        if is_offer {
+            usage.nb_offers += 1;
+        }
+
+        let mut violation = None;
+        if self
+            .limits
+            .max_bytes_per_minute
+            .map_or(false, |max| usage.nb_bytes > max)
+        {
+            violation = Some(RateLimitKind::Bytes);
+        } else if is_offer {
+            if self
+                .limits
+                .max_offers_per_minute
+                .map_or(false, |max| usage.nb_offers > max)
+            {
+                violation = Some(RateLimitKind::Offers);
+            } else if let Some(max) = self.limits.max_pending_handshakes {
+                if get_nb_pending()? >= max {
+                    violation = Some(RateLimitKind::PendingHandshakes);
+                }
+            }
+        }
+
+        match violation {
+            Some(kind) => {
+                self.violations.push(RateLimitViolation {
+                    counter_party: *counter_party,
+                    kind,
+                    timestamp: now,
+                });
+                Err(Error::RateLimited(format!(
+                    "Peer {} exceeded the {:?} limit.",
+                    counter_party, kind
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn get_and_clear_violations(&mut self) -> Vec<RateLimitViolation> {
+        std::mem::take(&mut self.violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dlc_messages::channel::Reject;
+    use dlc_messages::OfferDlc;
+    use secp256k1_zkp::{Secp256k1, SecretKey};
+
+    fn get_peer(i: u8) -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[i; 32]).unwrap())
+    }
+
+    fn get_offer() -> Message {
+        let offer: OfferDlc = serde_json::from_str(include_str!(
+            "../../dlc-messages/src/test_inputs/offer_msg.json"
+        ))
+        .unwrap();
+        Message::Offer(offer)
+    }
+
+    fn get_reject() -> Message {
+        Message::Reject(Reject {
+            channel_id: [0; 32],
+        })
+    }
+
+    #[test]
+    fn offers_per_minute_are_limited() {
+        let mut limiter = RateLimiter::default();
+        limiter.set_limits(RateLimits {
+            max_offers_per_minute: Some(2),
+            ..Default::default()
+        });
+
+        for _ in 0..2 {
+            limiter
+                .check_message(&get_peer(1), &get_offer(), 10, || Ok(0))
+                .expect("offer within the limit");
+        }
+        limiter
+            .check_message(&get_peer(1), &get_reject(), 10, || Ok(0))
+            .expect("offers only to be limited");
+        limiter
+            .check_message(&get_peer(1), &get_offer(), 20, || Ok(0))
+            .expect_err("too many offers");
+        limiter
+            .check_message(&get_peer(2), &get_offer(), 20, || Ok(0))
+            .expect("limits to apply per peer");
+        limiter
+            .check_message(&get_peer(1), &get_offer(), 70, || Ok(0))
+            .expect("limit to be reset after a minute");
+
+        assert_eq!(
+            vec![RateLimitViolation {
+                counter_party: get_peer(1),
+                kind: RateLimitKind::Offers,
+                timestamp: 20
+            }],
+            limiter.get_and_clear_violations()
+        );
+        assert!(limiter.get_and_clear_violations().is_empty());
+    }
+
+    #[test]
+    fn bytes_and_pending_handshakes_are_limited() {
+        let mut limiter = RateLimiter::default();
+        limiter.set_limits(RateLimits {
+            max_bytes_per_minute: Some(get_offer().serialized_length() as u64),
+            max_pending_handshakes: Some(1),
+            ..Default::default()
+        });
+
+        limiter
+            .check_message(&get_peer(1), &get_offer(), 0, || Ok(1))
+            .expect_err("too many pending handshakes");
+        limiter
+            .check_message(&get_peer(1), &get_reject(), 0, || Ok(0))
+            .expect_err("too many bytes");
+
+        let kinds = limiter
+            .get_and_clear_violations()
+            .into_iter()
+            .map(|v| v.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![RateLimitKind::PendingHandshakes, RateLimitKind::Bytes],
+            kinds
+        );
+    }
+}
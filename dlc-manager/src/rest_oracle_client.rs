@@ -0,0 +1,262 @@
+//! # RestOracleClient
+//! Implementation of the [`Oracle`] trait for oracles exposing the common
+//! HTTP API (`/pubkey`, `/announcement/{event_id}`, `/attestation/{event_id}`
+//! and `/events`).
+
+use std::time::Duration;
+
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
+use secp256k1_zkp::{schnorr::Signature, Secp256k1, VerifyOnly, XOnlyPublicKey};
+use serde::Deserialize;
+
+use crate::error::Error;
+use crate::Oracle;
+
+/// Configuration of a [`RestOracleClient`].
+#[derive(Clone, Debug)]
+pub struct RestOracleConfig {
+    /// The base url of the oracle API.
+    pub base_url: String,
+    /// The timeout applied to each request made to the oracle.
+    pub timeout: Duration,
+    /// Whether the content of responses should be checked for consistency
+    /// with the request and the oracle public key.
+    pub validate_responses: bool,
+    /// The url of a SOCKS5 proxy to send the requests through, required for
+    /// `.onion` oracles in which case it must use the `socks5h` scheme.
+    pub proxy: Option<String>,
+}
+
+impl RestOracleConfig {
+    /// Creates a configuration for the oracle at the given url with default
+    /// timeout and response validation enabled.
+    pub fn new(base_url: &str) -> Self {
+        RestOracleConfig {
+            base_url: base_url.to_string(),
+            timeout: Duration::from_secs(30),
+            validate_responses: true,
+            proxy: None,
+        }
+    }
+}
+
+/// Client for an oracle exposing the common HTTP API.
+pub struct RestOracleClient {
+    base_url: String,
+    client: reqwest::blocking::Client,
+    public_key: XOnlyPublicKey,
+    validate_responses: bool,
+    secp: Secp256k1<VerifyOnly>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PublicKeyResponse {
+    public_key: XOnlyPublicKey,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AttestationResponse {
+    event_id: String,
+    signatures: Vec<Signature>,
+    values: Vec<String>,
+}
+
+fn normalize_base_url(base_url: &str) -> Result<String, Error> {
+    if base_url.is_empty() {
+        return Err(Error::InvalidParameters("Invalid base url".to_string()));
+    }
+
+    Ok(base_url.trim_end_matches('/').to_string())
+}
+
+fn get_proxy(proxy: Option<&str>, base_url: &str) -> Result<Option<reqwest::Proxy>, Error> {
+    let is_onion = reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.ends_with(".onion")))
+        .unwrap_or(false);
+    if is_onion && !proxy.map_or(false, |p| p.starts_with("socks5h://")) {
+        return Err(Error::InvalidParameters(
+            "Onion oracles require a socks5h proxy".to_string(),
+        ));
+    }
+    proxy
+        .map(|p| reqwest::Proxy::all(p).map_err(|e| Error::InvalidParameters(e.to_string())))
+        .transpose()
+}
+
+fn to_io_error(e: reqwest::Error) -> Error {
+    Error::IOError(std::io::Error::new(std::io::ErrorKind::Other, e))
+}
+
+fn validate_announcement(
+    announcement: &OracleAnnouncement,
+    public_key: &XOnlyPublicKey,
+    event_id: Option<&str>,
+    secp: &Secp256k1<VerifyOnly>,
+) -> Result<(), Error> {
+    if &announcement.oracle_public_key != public_key {
+        return Err(Error::OracleError(
+            "Announcement was signed by an unexpected oracle".to_string(),
+        ));
+    }
+
+    if let Some(event_id) = event_id {
+        if announcement.oracle_event.event_id != event_id {
+            return Err(Error::OracleError(format!(
+                "Expected announcement for event {} but got {}",
+                event_id, announcement.oracle_event.event_id
+            )));
+        }
+    }
+
+    announcement
+        .validate(secp)
+        .map_err(|e| Error::OracleError(format!("Invalid announcement: {}", e)))
+}
+
+fn validate_attestation(attestation: &AttestationResponse, event_id: &str) -> Result<(), Error> {
+    if attestation.event_id != event_id {
+        return Err(Error::OracleError(format!(
+            "Expected attestation for event {} but got {}",
+            event_id, attestation.event_id
+        )));
+    }
+
+    if attestation.signatures.is_empty() || attestation.signatures.len() != attestation.values.len()
+    {
+        return Err(Error::OracleError(
+            "Attestation must have one signature per outcome value".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+impl RestOracleClient {
+    /// Creates a client for the oracle described by the given configuration,
+    /// retrieving its public key. Returns an error if the oracle could not be
+    /// reached.
+    pub fn new(config: RestOracleConfig) -> Result<RestOracleClient, Error> {
+        let base_url = normalize_base_url(&config.base_url)?;
+        let mut builder = reqwest::blocking::Client::builder().timeout(config.timeout);
+        if let Some(proxy) = get_proxy(config.proxy.as_deref(), &base_url)? {
+            builder = builder.proxy(proxy);
+        }
+        let client = builder.build().map_err(to_io_error)?;
+        let public_key =
+            get::<PublicKeyResponse>(&client, &format!("{}/pubkey", base_url))?.public_key;
+        Ok(RestOracleClient {
+            base_url,
+            client,
+            public_key,
+            validate_responses: config.validate_responses,
+            secp: Secp256k1::verification_only(),
+        })
+    }
+
+    /// Returns the announcements of all the events known to the oracle.
+    pub fn list_events(&self) -> Result<Vec<OracleAnnouncement>, Error> {
+        let announcements: Vec<OracleAnnouncement> =
+            get(&self.client, &format!("{}/events", self.base_url))?;
+        if self.validate_responses {
+            for announcement in &announcements {
+                validate_announcement(announcement, &self.public_key, None, &self.secp)?;
+            }
+        }
+        Ok(announcements)
+    }
+}
+
+fn get<T>(client: &reqwest::blocking::Client, url: &str) -> Result<T, Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    client
+        .get(url)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .map_err(to_io_error)?
+        .json::<T>()
+        .map_err(|e| Error::OracleError(e.to_string()))
+}
+
+impl Oracle for RestOracleClient {
+    fn get_public_key(&self) -> XOnlyPublicKey {
+        self.public_key
+    }
+
+    fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement, Error> {
+        let url = format!("{}/announcement/{}", self.base_url, event_id);
+        let announcement: OracleAnnouncement = get(&self.client, &url)?;
+        if self.validate_responses {
+            validate_announcement(&announcement, &self.public_key, Some(event_id), &self.secp)?;
+        }
+        Ok(announcement)
+    }
+
+    fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation, Error> {
+        let url = format!("{}/attestation/{}", self.base_url, event_id);
+        let attestation: AttestationResponse = get(&self.client, &url)?;
+        if self.validate_responses {
+            validate_attestation(&attestation, event_id)?;
+        }
+        Ok(OracleAttestation {
+            oracle_public_key: self.public_key,
+            signatures: attestation.signatures,
+            outcomes: attestation.values,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_base_url_test() {
+        assert_eq!(
+            "http://localhost:8080",
+            normalize_base_url("http://localhost:8080/").unwrap()
+        );
+        assert_eq!(
+            "http://localhost:8080",
+            normalize_base_url("http://localhost:8080").unwrap()
+        );
+        normalize_base_url("").expect_err("an empty url to be invalid");
+    }
+
+    #[test]
+    fn onion_oracle_requires_socks5h_proxy() {
+        let onion_url = "http://oracleexample.onion";
+        get_proxy(None, onion_url).expect_err("a missing proxy to be invalid");
+        get_proxy(Some("socks5://127.0.0.1:9050"), onion_url)
+            .expect_err("a proxy resolving names locally to be invalid");
+        assert!(get_proxy(Some("socks5h://127.0.0.1:9050"), onion_url)
+            .unwrap()
+            .is_some());
+        assert!(get_proxy(None, "http://localhost:8080").unwrap().is_none());
+    }
+
+    #[test]
+    fn attestation_mismatch_is_invalid() {
+        let sig: Signature = "ee05b1211d5f974732b10107dd302da062be47cd18f061c5080a50743412f9fd590cad90cfea762472e6fe865c4223bd388c877b7881a27892e15843ff1ac360".parse().unwrap();
+        let attestation = AttestationResponse {
+            event_id: "btcusd1624943400".to_string(),
+            signatures: vec![sig, sig],
+            values: vec!["0".to_string(), "1".to_string()],
+        };
+
+        validate_attestation(&attestation, "btcusd1624943400").expect("a valid attestation");
+        validate_attestation(&attestation, "btcusd1624943401")
+            .expect_err("an attestation for another event to be invalid");
+
+        let attestation = AttestationResponse {
+            values: vec!["0".to_string()],
+            ..attestation
+        };
+        validate_attestation(&attestation, "btcusd1624943400")
+            .expect_err("an attestation with missing values to be invalid");
+    }
+}
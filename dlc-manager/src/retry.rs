@@ -0,0 +1,257 @@
+//! #Retry
+//! Bounded retries with exponential backoff for the requests made by network
+//! providers, together with a circuit breaker suspending requests to a
+//! service that keeps failing so that callers fail fast instead of waiting
+//! on timeouts.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+/// Configuration of the retries and of the circuit breaker of a provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// The maximum number of times a failed request is retried.
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled for each subsequent one.
+    pub initial_backoff: Duration,
+    /// The maximum delay between two attempts.
+    pub max_backoff: Duration,
+    /// The number of consecutive failed requests after which the circuit is
+    /// opened.
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a request is let through to
+    /// check whether the service recovered.
+    pub reset_timeout: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Returns the delay to wait for before the given retry, starting at zero.
+    pub fn get_backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+/// The state of a [`CircuitBreaker`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent normally.
+    Closed,
+    /// The service failed repeatedly, requests fail without being sent.
+    Open,
+    /// The reset timeout expired, the next request will decide whether the
+    /// circuit gets closed or opened again.
+    HalfOpen,
+}
+
+/// The failure of an attempt at a request.
+#[derive(Debug)]
+pub enum RequestError {
+    /// The request may succeed if retried, e.g. on a timeout or a connection
+    /// error.
+    Transient(Error),
+    /// The request will fail again if retried, e.g. when the service rejected
+    /// it.
+    Permanent(Error),
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Retries failed requests to a service and tracks its availability.
+pub struct CircuitBreaker {
+    config: RetryConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    /// Creates a closed circuit breaker with the given configuration.
+    pub fn new(config: RetryConfig) -> Self {
+        CircuitBreaker {
+            config,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Returns the current state of the circuit.
+    pub fn get_state(&self) -> CircuitState {
+        match self.state.lock().unwrap().opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() >= self.config.reset_timeout => {
+                CircuitState::HalfOpen
+            }
+            Some(_) => CircuitState::Open,
+        }
+    }
+
+    /// Calls `f` until it succeeds or returns a permanent error, waiting with
+    /// exponential backoff between attempts, for at most the configured
+    /// number of retries. Fails without calling `f` while the circuit is open.
+    pub fn call<T, F>(&self, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Result<T, RequestError>,
+    {
+        if self.get_state() == CircuitState::Open {
+            return Err(Error::ServiceUnavailable(format!(
+                "{} consecutive requests failed",
+                self.state.lock().unwrap().consecutive_failures
+            )));
+        }
+
+        let mut retry = 0;
+        loop {
+            match f() {
+                Ok(res) => {
+                    self.record_success();
+                    return Ok(res);
+                }
+                // The service responded, so it is available.
+                Err(RequestError::Permanent(e)) => {
+                    self.record_success();
+                    return Err(e);
+                }
+                Err(RequestError::Transient(e)) => {
+                    if retry >= self.config.max_retries {
+                        self.record_failure();
+                        return Err(e);
+                    }
+                    std::thread::sleep(self.config.get_backoff(retry));
+                    retry += 1;
+                }
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.config.failure_threshold {
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_config() -> RetryConfig {
+        RetryConfig {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(3),
+            failure_threshold: 2,
+            reset_timeout: Duration::from_secs(3600),
+        }
+    }
+
+    fn transient() -> RequestError {
+        RequestError::Transient(Error::BlockchainError("timeout".to_string()))
+    }
+
+    #[test]
+    fn backoff_is_exponential_and_bounded() {
+        let config = get_config();
+        assert_eq!(Duration::from_millis(1), config.get_backoff(0));
+        assert_eq!(Duration::from_millis(2), config.get_backoff(1));
+        assert_eq!(Duration::from_millis(3), config.get_backoff(2));
+        assert_eq!(Duration::from_millis(3), config.get_backoff(64));
+    }
+
+    #[test]
+    fn transient_errors_are_retried() {
+        let breaker = CircuitBreaker::new(get_config());
+        let mut attempts = 0;
+        let res = breaker.call(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(transient())
+            } else {
+                Ok(attempts)
+            }
+        });
+
+        assert_eq!(3, res.unwrap());
+        assert_eq!(CircuitState::Closed, breaker.get_state());
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let breaker = CircuitBreaker::new(get_config());
+        let mut attempts = 0;
+        breaker
+            .call::<(), _>(|| {
+                attempts += 1;
+                Err(RequestError::Permanent(Error::BlockchainError(
+                    "not found".to_string(),
+                )))
+            })
+            .expect_err("the request to fail");
+
+        assert_eq!(1, attempts);
+        assert_eq!(CircuitState::Closed, breaker.get_state());
+    }
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(get_config());
+        let mut attempts = 0;
+        for _ in 0..2 {
+            breaker
+                .call::<(), _>(|| {
+                    attempts += 1;
+                    Err(transient())
+                })
+                .expect_err("the request to fail");
+        }
+        assert_eq!(6, attempts);
+        assert_eq!(CircuitState::Open, breaker.get_state());
+
+        let res = breaker.call(|| {
+            attempts += 1;
+            Ok(())
+        });
+        assert!(matches!(res, Err(Error::ServiceUnavailable(_))));
+        assert_eq!(6, attempts);
+    }
+
+    #[test]
+    fn circuit_closes_once_service_recovers() {
+        let breaker = CircuitBreaker::new(RetryConfig {
+            reset_timeout: Duration::from_secs(0),
+            ..get_config()
+        });
+        for _ in 0..2 {
+            breaker
+                .call::<(), _>(|| Err(transient()))
+                .expect_err("the request to fail");
+        }
+        assert_eq!(CircuitState::HalfOpen, breaker.get_state());
+
+        breaker.call(|| Ok(())).unwrap();
+        assert_eq!(CircuitState::Closed, breaker.get_state());
+    }
+}
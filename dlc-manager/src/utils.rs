@@ -1,25 +1,35 @@
 use std::ops::Deref;
 
-use bitcoin::{consensus::Encodable, Txid};
-use dlc::{PartyParams, TxInputInfo};
+use bitcoin::{blockdata::script::Instruction, consensus::Encodable, Script, Transaction, Txid};
+use dlc::{PartyParams, PayoutOutput, TxInputInfo};
 use dlc_messages::{
     oracle_msgs::{OracleAnnouncement, OracleAttestation},
     FundingInput,
 };
 use dlc_trie::RangeInfo;
+use lightning::ln::chan_utils::derive_private_key;
 #[cfg(not(feature = "fuzztarget"))]
 use secp256k1_zkp::rand::{thread_rng, Rng, RngCore};
-use secp256k1_zkp::{PublicKey, Secp256k1, SecretKey, Signing};
+use secp256k1_zkp::{PublicKey, Secp256k1, Signing};
 
 use crate::{
     channel::party_points::PartyBasePoints,
-    contract::{contract_info::ContractInfo, AdaptorInfo, FundingInputInfo},
+    contract::{
+        contract_info::ContractInfo, contract_input::ContractInput, AdaptorInfo, FundingInputInfo,
+        SharedFundingInput,
+    },
     error::Error,
-    Blockchain, Wallet,
+    key_derivation::{ContractKeyId, ContractKeyType},
+    zeroizing::ZeroizingSecretKey,
+    Blockchain, ContractSigner, Utxo, Wallet,
 };
 
 const APPROXIMATE_CET_VBYTES: u64 = 190;
 const APPROXIMATE_CLOSING_VBYTES: u64 = 168;
+/// Witness size of a 2-of-2 multisig input (same as the CET funding input).
+const SHARED_FUNDING_MAX_WITNESS_LEN: u16 = 220;
+/// Witness size of a taproot key path spend with a non default sighash type.
+const P2TR_MAX_WITNESS_LEN: u16 = 67;
 
 pub fn get_common_fee(fee_rate: u64) -> u64 {
     (APPROXIMATE_CET_VBYTES + APPROXIMATE_CLOSING_VBYTES) * fee_rate
@@ -65,30 +75,130 @@ pub(crate) fn compute_id(
     res
 }
 
-pub(crate) fn get_party_params<C: Signing, W: Deref, B: Deref>(
+/// Returns the payout and change script pubkeys of a party, using the given
+/// ones when set and the ones provided by the wallet otherwise.
+fn get_output_script_pubkeys<W: Deref>(
+    wallet: &W,
+    temporary_id: &[u8; 32],
+    payout_script_pubkey: Option<&Script>,
+    change_script_pubkey: Option<&Script>,
+) -> Result<(Script, Script), Error>
+where
+    W::Target: Wallet,
+{
+    let payout_spk = match payout_script_pubkey {
+        Some(spk) => spk.clone(),
+        None => wallet.get_payout_script_pubkey(temporary_id)?,
+    };
+    let change_spk = match change_script_pubkey {
+        Some(spk) => spk.clone(),
+        None => wallet.get_change_script_pubkey(temporary_id)?,
+    };
+    Ok((payout_spk, change_spk))
+}
+
+pub(crate) fn get_party_params<C: Signing, W: Deref, S: Deref, B: Deref>(
     secp: &Secp256k1<C>,
+    counter_party: &PublicKey,
+    temporary_id: &[u8; 32],
     own_collateral: u64,
     fee_rate: u64,
+    payout_script_pubkey: Option<&Script>,
+    change_script_pubkey: Option<&Script>,
     wallet: &W,
+    signer: &S,
     blockchain: &B,
-) -> Result<(PartyParams, SecretKey, Vec<FundingInputInfo>), Error>
+) -> Result<(PartyParams, ZeroizingSecretKey, Vec<FundingInputInfo>), Error>
 where
     W::Target: Wallet,
+    S::Target: ContractSigner,
     B::Target: Blockchain,
 {
-    let funding_privkey = wallet.get_new_secret_key()?;
+    // The selected UTXOs are reserved by the manager for the contract or
+    // channel being established, see `Wallet::reserve_utxos`.
+    let utxos = wallet.get_utxos_for_amount(
+        get_approximate_required_amount(own_collateral, fee_rate),
+        Some(fee_rate),
+        false,
+    )?;
+
+    get_party_params_from_utxos(
+        secp,
+        counter_party,
+        temporary_id,
+        own_collateral,
+        utxos,
+        payout_script_pubkey,
+        change_script_pubkey,
+        wallet,
+        signer,
+        blockchain,
+    )
+}
+
+/// Creates the party parameters of a contract funded by the given UTXOs.
+pub(crate) fn get_party_params_from_utxos<C: Signing, W: Deref, S: Deref, B: Deref>(
+    secp: &Secp256k1<C>,
+    counter_party: &PublicKey,
+    temporary_id: &[u8; 32],
+    own_collateral: u64,
+    utxos: Vec<Utxo>,
+    payout_script_pubkey: Option<&Script>,
+    change_script_pubkey: Option<&Script>,
+    wallet: &W,
+    signer: &S,
+    blockchain: &B,
+) -> Result<(PartyParams, ZeroizingSecretKey, Vec<FundingInputInfo>), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+    B::Target: Blockchain,
+{
+    let funding_privkey: ZeroizingSecretKey = signer
+        .derive_contract_secret_key(&ContractKeyId {
+            counter_party: *counter_party,
+            temporary_id: *temporary_id,
+            key_type: ContractKeyType::Funding,
+        })?
+        .into();
     let funding_pubkey = PublicKey::from_secret_key(secp, &funding_privkey);
 
-    let payout_addr = wallet.get_new_address()?;
-    let payout_spk = payout_addr.script_pubkey();
+    let (payout_spk, change_spk) = get_output_script_pubkeys(
+        wallet,
+        temporary_id,
+        payout_script_pubkey,
+        change_script_pubkey,
+    )?;
     let payout_serial_id = get_new_serial_id();
-    let change_addr = wallet.get_new_address()?;
-    let change_spk = change_addr.script_pubkey();
     let change_serial_id = get_new_serial_id();
 
-    let appr_required_amount = own_collateral + get_half_common_fee(fee_rate);
-    let utxos = wallet.get_utxos_for_amount(appr_required_amount, Some(fee_rate), true)?;
+    let (funding_inputs_info, funding_tx_info, total_input) =
+        get_funding_inputs_from_utxos(utxos, blockchain)?;
+
+    let party_params = PartyParams {
+        fund_pubkey: funding_pubkey,
+        change_script_pubkey: change_spk,
+        change_serial_id,
+        payout_script_pubkey: payout_spk,
+        payout_serial_id,
+        inputs: funding_tx_info,
+        collateral: own_collateral,
+        input_amount: total_input,
+        additional_payout_outputs: Vec::new(),
+    };
 
+    Ok((party_params, funding_privkey, funding_inputs_info))
+}
+
+/// Creates the funding inputs spending the given UTXOs, returning them
+/// together with their total value.
+pub(crate) fn get_funding_inputs_from_utxos<B: Deref>(
+    utxos: Vec<Utxo>,
+    blockchain: &B,
+) -> Result<(Vec<FundingInputInfo>, Vec<TxInputInfo>, u64), Error>
+where
+    B::Target: Blockchain,
+{
     let mut funding_inputs_info: Vec<FundingInputInfo> = Vec::new();
     let mut funding_tx_info: Vec<TxInputInfo> = Vec::new();
     let mut total_input = 0;
@@ -98,8 +208,12 @@ where
         prev_tx.consensus_encode(&mut writer)?;
         let prev_tx_vout = utxo.outpoint.vout;
         let sequence = 0xffffffff;
-        // TODO(tibo): this assumes P2WPKH with low R
-        let max_witness_len = 107;
+        // TODO(tibo): this assumes P2WPKH with low R for non taproot inputs.
+        let max_witness_len = if utxo.tx_out.script_pubkey.is_v1_p2tr() {
+            P2TR_MAX_WITNESS_LEN
+        } else {
+            107
+        };
         let funding_input = FundingInput {
             input_serial_id: get_new_serial_id(),
             prev_tx: writer,
@@ -117,34 +231,258 @@ where
         funding_inputs_info.push(funding_input_info);
     }
 
+    Ok((funding_inputs_info, funding_tx_info, total_input))
+}
+
+/// Creates the party parameters for the offering party of a contract funded by
+/// a shared output. The shared output is used as the sole funding input and its
+/// full value is set as input amount.
+pub(crate) fn get_party_params_for_shared_funding<C: Signing, W: Deref, S: Deref>(
+    secp: &Secp256k1<C>,
+    counter_party: &PublicKey,
+    temporary_id: &[u8; 32],
+    own_collateral: u64,
+    prev_tx: &Transaction,
+    prev_tx_vout: u32,
+    shared_funding_input: &SharedFundingInput,
+    payout_script_pubkey: Option<&Script>,
+    change_script_pubkey: Option<&Script>,
+    wallet: &W,
+    signer: &S,
+) -> Result<(PartyParams, ZeroizingSecretKey, Vec<FundingInputInfo>), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+{
+    let prev_output = prev_tx.output.get(prev_tx_vout as usize).ok_or_else(|| {
+        Error::InvalidParameters(format!(
+            "Previous tx output not found at index {}",
+            prev_tx_vout
+        ))
+    })?;
+
+    if prev_output.script_pubkey != shared_funding_input.funding_script.to_v0_p2wsh() {
+        return Err(Error::InvalidParameters(
+            "Shared output does not match the provided funding script".to_string(),
+        ));
+    }
+
+    get_shared_funding_counter_pubkey(
+        &shared_funding_input.funding_script,
+        &shared_funding_input.own_pubkey,
+    )?;
+
+    let mut writer = Vec::new();
+    prev_tx.consensus_encode(&mut writer)?;
+    let funding_input = FundingInput {
+        input_serial_id: get_new_serial_id(),
+        prev_tx: writer,
+        prev_tx_vout,
+        sequence: 0xffffffff,
+        max_witness_len: SHARED_FUNDING_MAX_WITNESS_LEN,
+        redeem_script: Script::new(),
+    };
+
+    let (mut party_params, fund_secret_key) = get_party_params_without_inputs(
+        secp,
+        counter_party,
+        temporary_id,
+        own_collateral,
+        prev_output.value,
+        payout_script_pubkey,
+        change_script_pubkey,
+        wallet,
+        signer,
+    )?;
+    party_params.inputs = vec![(&funding_input).into()];
+
+    Ok((
+        party_params,
+        fund_secret_key,
+        vec![FundingInputInfo {
+            funding_input,
+            address: None,
+        }],
+    ))
+}
+
+/// Creates party parameters that do not include any funding input.
+pub(crate) fn get_party_params_without_inputs<C: Signing, W: Deref, S: Deref>(
+    secp: &Secp256k1<C>,
+    counter_party: &PublicKey,
+    temporary_id: &[u8; 32],
+    own_collateral: u64,
+    input_amount: u64,
+    payout_script_pubkey: Option<&Script>,
+    change_script_pubkey: Option<&Script>,
+    wallet: &W,
+    signer: &S,
+) -> Result<(PartyParams, ZeroizingSecretKey), Error>
+where
+    W::Target: Wallet,
+    S::Target: ContractSigner,
+{
+    let funding_privkey: ZeroizingSecretKey = signer
+        .derive_contract_secret_key(&ContractKeyId {
+            counter_party: *counter_party,
+            temporary_id: *temporary_id,
+            key_type: ContractKeyType::Funding,
+        })?
+        .into();
+    let (payout_spk, change_spk) = get_output_script_pubkeys(
+        wallet,
+        temporary_id,
+        payout_script_pubkey,
+        change_script_pubkey,
+    )?;
     let party_params = PartyParams {
-        fund_pubkey: funding_pubkey,
+        fund_pubkey: PublicKey::from_secret_key(secp, &funding_privkey),
         change_script_pubkey: change_spk,
-        change_serial_id,
+        change_serial_id: get_new_serial_id(),
         payout_script_pubkey: payout_spk,
-        payout_serial_id,
-        inputs: funding_tx_info,
+        payout_serial_id: get_new_serial_id(),
+        inputs: Vec::new(),
         collateral: own_collateral,
-        input_amount: total_input,
+        input_amount,
+        additional_payout_outputs: Vec::new(),
     };
 
-    Ok((party_params, funding_privkey, funding_inputs_info))
+    Ok((party_params, funding_privkey))
+}
+
+/// Returns the additional payout outputs of the offering party described by
+/// the given contract input, with new serial ids.
+pub(crate) fn get_additional_payout_outputs(contract_input: &ContractInput) -> Vec<PayoutOutput> {
+    contract_input
+        .additional_payout_outputs
+        .iter()
+        .map(|x| PayoutOutput {
+            script_pubkey: x.script_pubkey.clone(),
+            serial_id: get_new_serial_id(),
+            share_bps: x.share_bps,
+        })
+        .collect()
+}
+
+/// Returns the part of a shared funding output attributed to the accepting
+/// party, which covers its collateral and its share of the fees.
+pub(crate) fn get_shared_funding_accept_amount(accept_collateral: u64, fee_rate: u64) -> u64 {
+    get_approximate_required_amount(accept_collateral, fee_rate)
+}
+
+/// Returns the approximate amount a party needs to fund to cover its
+/// collateral and its share of the fees.
+pub(crate) fn get_approximate_required_amount(collateral: u64, fee_rate: u64) -> u64 {
+    collateral + get_half_common_fee(fee_rate)
 }
 
-pub(crate) fn get_party_base_points<C: Signing, W: Deref>(
+/// Splits the given UTXOs into sets funding each of the given amounts, the
+/// largest amounts being funded first. An amount is funded by the smallest
+/// UTXO covering it if any, and by the largest remaining UTXOs otherwise.
+/// Amounts that cannot be funded from the remaining UTXOs are set to `None`.
+pub(crate) fn split_utxos(mut utxos: Vec<Utxo>, amounts: &[u64]) -> Vec<Option<Vec<Utxo>>> {
+    utxos.sort_by_key(|x| x.tx_out.value);
+    let mut order = (0..amounts.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| std::cmp::Reverse(amounts[*i]));
+
+    let mut res = vec![None; amounts.len()];
+    for i in order {
+        if let Some(pos) = utxos.iter().position(|x| x.tx_out.value >= amounts[i]) {
+            res[i] = Some(vec![utxos.remove(pos)]);
+            continue;
+        }
+        let available: u64 = utxos.iter().map(|x| x.tx_out.value).sum();
+        if available < amounts[i] {
+            continue;
+        }
+        let mut selected = Vec::new();
+        let mut total = 0;
+        while total < amounts[i] {
+            let utxo = utxos.pop().expect("to have enough remaining UTXOs");
+            total += utxo.tx_out.value;
+            selected.push(utxo);
+        }
+        res[i] = Some(selected);
+    }
+
+    res
+}
+
+/// Returns the public key of the counter party within the given shared funding
+/// script, checking that the script is a 2-of-2 multisig including `own_pubkey`.
+pub(crate) fn get_shared_funding_counter_pubkey(
+    funding_script: &Script,
+    own_pubkey: &PublicKey,
+) -> Result<PublicKey, Error> {
+    let pubkeys = funding_script
+        .instructions()
+        .filter_map(|x| match x {
+            Ok(Instruction::PushBytes(b)) => PublicKey::from_slice(b).ok(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let counter_pubkey = match pubkeys.as_slice() {
+        [a, b] if a == own_pubkey => *b,
+        [a, b] if b == own_pubkey => *a,
+        _ => {
+            return Err(Error::InvalidParameters(
+                "Shared funding script must be a 2-of-2 multisig including own public key"
+                    .to_string(),
+            ))
+        }
+    };
+
+    if &dlc::make_funding_redeemscript(own_pubkey, &counter_pubkey) != funding_script {
+        return Err(Error::InvalidParameters(
+            "Shared funding script must be a 2-of-2 multisig including own public key".to_string(),
+        ));
+    }
+
+    Ok(counter_pubkey)
+}
+
+pub(crate) fn get_party_base_points<C: Signing, S: Deref>(
     secp: &Secp256k1<C>,
-    wallet: &W,
+    counter_party: &PublicKey,
+    temporary_channel_id: &[u8; 32],
+    signer: &S,
 ) -> Result<PartyBasePoints, Error>
 where
-    W::Target: Wallet,
+    S::Target: ContractSigner,
 {
+    let get_basepoint = |key_type| -> Result<PublicKey, Error> {
+        let secret_key: ZeroizingSecretKey = signer
+            .derive_contract_secret_key(&ContractKeyId {
+                counter_party: *counter_party,
+                temporary_id: *temporary_channel_id,
+                key_type,
+            })?
+            .into();
+        Ok(PublicKey::from_secret_key(secp, &secret_key))
+    };
     Ok(PartyBasePoints {
-        own_basepoint: PublicKey::from_secret_key(secp, &wallet.get_new_secret_key()?),
-        publish_basepoint: PublicKey::from_secret_key(secp, &wallet.get_new_secret_key()?),
-        revocation_basepoint: PublicKey::from_secret_key(secp, &wallet.get_new_secret_key()?),
+        own_basepoint: get_basepoint(ContractKeyType::OwnBasepoint)?,
+        publish_basepoint: get_basepoint(ContractKeyType::PublishBasepoint)?,
+        revocation_basepoint: get_basepoint(ContractKeyType::RevocationBasepoint)?,
     })
 }
 
+/// Derives the secret key of the given base point of the signer, tweaked with
+/// the given per update point.
+pub(crate) fn derive_own_secret_key<C: Signing, S: Deref>(
+    secp: &Secp256k1<C>,
+    signer: &S,
+    basepoint: &PublicKey,
+    per_update_point: &PublicKey,
+) -> Result<ZeroizingSecretKey, Error>
+where
+    S::Target: ContractSigner,
+{
+    let base_secret = signer.get_secret_key_for_pubkey(basepoint)?;
+    Ok(derive_private_key(secp, per_update_point, &base_secret).into())
+}
+
 fn get_half_common_fee(fee_rate: u64) -> u64 {
     let common_fee = get_common_fee(fee_rate);
     (common_fee as f64 / 2_f64).ceil() as u64
@@ -193,11 +531,12 @@ pub(crate) fn get_latest_maturity_date(
 mod tests {
     use std::str::FromStr;
 
+    use bitcoin::hashes::Hash;
     use dlc_messages::oracle_msgs::{EnumEventDescriptor, EventDescriptor, OracleEvent};
     use secp256k1_zkp::{
         rand::{thread_rng, RngCore},
         schnorr::Signature,
-        XOnlyPublicKey,
+        SecretKey, XOnlyPublicKey,
     };
 
     use super::*;
@@ -233,6 +572,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn get_shared_funding_counter_pubkey_test() {
+        let secp = Secp256k1::new();
+        let own_pubkey =
+            PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[1u8; 32]).unwrap());
+        let counter_pubkey =
+            PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[2u8; 32]).unwrap());
+        let other_pubkey =
+            PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[3u8; 32]).unwrap());
+        let funding_script = dlc::make_funding_redeemscript(&own_pubkey, &counter_pubkey);
+
+        assert_eq!(
+            counter_pubkey,
+            get_shared_funding_counter_pubkey(&funding_script, &own_pubkey).unwrap()
+        );
+        assert!(get_shared_funding_counter_pubkey(&funding_script, &other_pubkey).is_err());
+        assert!(
+            get_shared_funding_counter_pubkey(&funding_script.to_v0_p2wsh(), &own_pubkey).is_err()
+        );
+    }
+
+    fn get_utxo(value: u64) -> Utxo {
+        let secp = Secp256k1::new();
+        let pubkey = bitcoin::PublicKey::new(PublicKey::from_secret_key(
+            &secp,
+            &SecretKey::from_slice(&[1u8; 32]).unwrap(),
+        ));
+        let address = bitcoin::Address::p2wpkh(&pubkey, bitcoin::Network::Regtest).unwrap();
+        Utxo {
+            tx_out: bitcoin::TxOut {
+                value,
+                script_pubkey: address.script_pubkey(),
+            },
+            outpoint: bitcoin::OutPoint {
+                txid: Txid::all_zeros(),
+                vout: value as u32,
+            },
+            address,
+            redeem_script: Script::new(),
+            reserved: false,
+        }
+    }
+
+    #[test]
+    fn split_utxos_test() {
+        let utxos = [10, 20, 50, 100].iter().map(|x| get_utxo(*x)).collect();
+        let values = |x: &Option<Vec<Utxo>>| {
+            x.as_ref()
+                .map(|x| x.iter().map(|y| y.tx_out.value).collect::<Vec<_>>())
+        };
+
+        let res = split_utxos(utxos, &[15, 60, 30, 200]);
+
+        // The largest amount cannot be funded, the next ones are funded by the
+        // smallest covering UTXO or by the largest remaining ones.
+        assert_eq!(None, values(&res[3]));
+        assert_eq!(Some(vec![100]), values(&res[1]));
+        assert_eq!(Some(vec![50]), values(&res[2]));
+        assert_eq!(Some(vec![20]), values(&res[0]));
+    }
+
+    #[test]
+    fn split_utxos_combines_utxos() {
+        let utxos = [10, 20, 50].iter().map(|x| get_utxo(*x)).collect();
+
+        let res = split_utxos(utxos, &[65, 10]);
+
+        let total: u64 = res[0]
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|x| x.tx_out.value)
+            .sum();
+        assert_eq!(70, total);
+        assert_eq!(10, res[1].as_ref().unwrap()[0].tx_out.value);
+    }
+
     fn create_announcement(maturity: u32) -> OracleAnnouncement {
         let xonly_pk = XOnlyPublicKey::from_str(
             "e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443",
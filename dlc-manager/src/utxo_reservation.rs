@@ -0,0 +1,123 @@
+//! #UtxoReservations
+//! Bookkeeping of the UTXOs reserved by a [`crate::Wallet`] for the contracts
+//! and channels being established, so that wallet implementations can honor
+//! [`crate::Wallet::reserve_utxos`] and [`crate::Wallet::release_utxos`]
+//! without each tracking expiries themselves.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bitcoin::OutPoint;
+
+use crate::ContractId;
+
+struct Reservation {
+    outpoints: Vec<OutPoint>,
+    expiry: Instant,
+}
+
+/// The UTXOs reserved for each contract or channel, identified by their
+/// temporary id. Reservations are lost on restart.
+#[derive(Default)]
+pub struct UtxoReservations {
+    reservations: Mutex<HashMap<ContractId, Reservation>>,
+}
+
+impl UtxoReservations {
+    /// Creates an empty set of reservations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves the given outpoints for the given id until `ttl` has elapsed,
+    /// replacing any previous reservation for the id.
+    pub fn reserve(&self, temporary_id: &ContractId, outpoints: &[OutPoint], ttl: Duration) {
+        self.reservations.lock().unwrap().insert(
+            *temporary_id,
+            Reservation {
+                outpoints: outpoints.to_vec(),
+                expiry: Instant::now() + ttl,
+            },
+        );
+    }
+
+    /// Releases the reservation of the given id, returning the outpoints that
+    /// were reserved.
+    pub fn release(&self, temporary_id: &ContractId) -> Vec<OutPoint> {
+        self.reservations
+            .lock()
+            .unwrap()
+            .remove(temporary_id)
+            .map(|x| x.outpoints)
+            .unwrap_or_default()
+    }
+
+    /// Removes the reservations that have expired, returning their outpoints.
+    pub fn remove_expired(&self) -> Vec<OutPoint> {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        self.reservations.lock().unwrap().retain(|_, reservation| {
+            if reservation.expiry > now {
+                return true;
+            }
+            expired.append(&mut reservation.outpoints);
+            false
+        });
+        expired
+    }
+
+    /// Whether the given outpoint is part of a reservation that has not
+    /// expired.
+    pub fn is_reserved(&self, outpoint: &OutPoint) -> bool {
+        let now = Instant::now();
+        self.reservations
+            .lock()
+            .unwrap()
+            .values()
+            .any(|x| x.expiry > now && x.outpoints.contains(outpoint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::Txid;
+
+    fn outpoint(vout: u32) -> OutPoint {
+        OutPoint::new(Txid::from_inner([1; 32]), vout)
+    }
+
+    #[test]
+    fn released_utxos_are_not_reserved() {
+        let reservations = UtxoReservations::new();
+        reservations.reserve(
+            &[1; 32],
+            &[outpoint(0), outpoint(1)],
+            Duration::from_secs(60),
+        );
+        reservations.reserve(&[2; 32], &[outpoint(2)], Duration::from_secs(60));
+
+        assert!(reservations.is_reserved(&outpoint(1)));
+        assert_eq!(
+            vec![outpoint(0), outpoint(1)],
+            reservations.release(&[1; 32])
+        );
+        assert!(!reservations.is_reserved(&outpoint(1)));
+        assert!(reservations.is_reserved(&outpoint(2)));
+        assert!(reservations.release(&[1; 32]).is_empty());
+    }
+
+    #[test]
+    fn expired_reservations_are_removed() {
+        let reservations = UtxoReservations::new();
+        reservations.reserve(&[1; 32], &[outpoint(0)], Duration::ZERO);
+        reservations.reserve(&[2; 32], &[outpoint(1)], Duration::from_secs(60));
+
+        assert!(!reservations.is_reserved(&outpoint(0)));
+        assert_eq!(vec![outpoint(0)], reservations.remove_expired());
+        assert!(reservations.remove_expired().is_empty());
+        assert!(reservations.is_reserved(&outpoint(1)));
+    }
+}
@@ -0,0 +1,92 @@
+//! #ZeroizingSecretKey
+//! Wrapper around the secret keys handled by the manager, the updaters and the
+//! wallets, wiping them from memory once they are no longer needed.
+
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+use secp256k1_zkp::{constants::SECRET_KEY_SIZE, SecretKey, ONE_KEY};
+
+/// A [`SecretKey`] that is overwritten when dropped. Dereferences to the
+/// wrapped key so that it can be passed to functions expecting a
+/// `&SecretKey` without being copied.
+///
+/// As [`SecretKey`] does not expose its memory, it is wiped by overwriting it
+/// with a constant key, zero not being a valid secret key. Copies made of the
+/// wrapped key, e.g. by dereferencing it by value, are not wiped.
+pub struct ZeroizingSecretKey(SecretKey);
+
+impl ZeroizingSecretKey {
+    /// Wraps the given secret key.
+    pub fn new(secret_key: SecretKey) -> Self {
+        ZeroizingSecretKey(secret_key)
+    }
+}
+
+impl From<SecretKey> for ZeroizingSecretKey {
+    fn from(secret_key: SecretKey) -> Self {
+        ZeroizingSecretKey::new(secret_key)
+    }
+}
+
+impl Clone for ZeroizingSecretKey {
+    fn clone(&self) -> Self {
+        ZeroizingSecretKey(self.0)
+    }
+}
+
+impl Deref for ZeroizingSecretKey {
+    type Target = SecretKey;
+
+    fn deref(&self) -> &SecretKey {
+        &self.0
+    }
+}
+
+impl AsRef<[u8; SECRET_KEY_SIZE]> for ZeroizingSecretKey {
+    fn as_ref(&self) -> &[u8; SECRET_KEY_SIZE] {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for ZeroizingSecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for ZeroizingSecretKey {}
+
+impl fmt::Debug for ZeroizingSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ZeroizingSecretKey(<redacted>)")
+    }
+}
+
+impl Drop for ZeroizingSecretKey {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        // A volatile write cannot be optimized away as a dead store, and the
+        // fence prevents it from being reordered with subsequent operations.
+        // Safety: the pointer is derived from a mutable reference to a plain
+        // byte array, so it is valid and aligned.
+        unsafe { std::ptr::write_volatile(&mut self.0, ONE_KEY) };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapped_key_is_usable_and_redacted() {
+        let secret_key = SecretKey::from_slice(&[2; 32]).unwrap();
+        let wrapped = ZeroizingSecretKey::new(secret_key);
+
+        assert_eq!(secret_key, *wrapped);
+        assert_eq!(&[2; 32], wrapped.as_ref());
+        assert_eq!("ZeroizingSecretKey(<redacted>)", format!("{:?}", wrapped));
+    }
+}
@@ -16,9 +16,13 @@ use test_utils::*;
 
 use bitcoin_test_utils::rpc_helpers::init_clients;
 use bitcoincore_rpc::RpcApi;
+use dlc_manager::contract::multi_party_contract::{
+    MultiPartyContractInput, MultiPartyContractState,
+};
 use dlc_manager::contract::{numerical_descriptor::DifferenceParams, Contract};
 use dlc_manager::manager::Manager;
 use dlc_manager::{Blockchain, Oracle, Storage, Wallet};
+use dlc_messages::multi_party::MultiPartyOutcome;
 use dlc_messages::{AcceptDlc, OfferDlc, SignDlc};
 use dlc_messages::{CetAdaptorSignatures, Message};
 use lightning::ln::wire::Type;
@@ -712,3 +716,162 @@ fn manager_execution_test(test_params: TestParams, path: TestPath) {
 
     create_test_vector();
 }
+
+#[test]
+#[ignore]
+fn multi_party_enum_test() {
+    let (_, _, sink_rpc) = init_clients();
+    let oracle = Arc::new(get_enum_oracle());
+    let mock_time = Arc::new(mocks::mock_time::MockTime {});
+    mocks::mock_time::set_time((EVENT_MATURITY as u64) - 1);
+
+    let electrs = Arc::new(ElectrsBlockchainProvider::new(
+        "http://localhost:3004/".to_string(),
+        bitcoin::Network::Regtest,
+    ));
+
+    let node_ids: Vec<secp256k1_zkp::PublicKey> = (1..=3u8)
+        .map(|i| {
+            let sk = secp256k1_zkp::SecretKey::from_slice(&[i; 32]).unwrap();
+            secp256k1_zkp::PublicKey::from_secret_key(secp256k1_zkp::SECP256K1, &sk)
+        })
+        .collect();
+
+    let mut managers = Vec::new();
+    let mut wallets = Vec::new();
+    for _ in 0..node_ids.len() {
+        let store = Arc::new(mocks::memory_storage_provider::MemoryStorage::new());
+        let wallet = Arc::new(SimpleWallet::new(
+            electrs.clone(),
+            store.clone(),
+            bitcoin::Network::Regtest,
+        ));
+        sink_rpc
+            .send_to_address(
+                &wallet.get_new_address().unwrap(),
+                Amount::from_btc(2.0).unwrap(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let mut oracles = HashMap::with_capacity(1);
+        oracles.insert(oracle.get_public_key(), Arc::clone(&oracle));
+        managers.push(
+            Manager::new(
+                Arc::clone(&wallet),
+                Arc::clone(&electrs),
+                store,
+                oracles,
+                Arc::clone(&mock_time),
+                Arc::clone(&electrs),
+            )
+            .unwrap(),
+        );
+        wallets.push(wallet);
+    }
+
+    let sink_address = sink_rpc.get_new_address(None, None).expect("RPC Error");
+    let prev_height = electrs.get_blockchain_height().unwrap();
+    sink_rpc
+        .generate_to_address(6, &sink_address)
+        .expect("RPC Error");
+    while electrs.get_blockchain_height().unwrap() < prev_height + 6 {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    for wallet in &wallets {
+        refresh_wallet(wallet, 200000000);
+    }
+
+    let contract_input = MultiPartyContractInput {
+        collaterals: vec![50000000, 30000000, 20000000],
+        fee_rate: 2,
+        outcomes: enum_outcomes()
+            .into_iter()
+            .enumerate()
+            .map(|(i, outcome)| MultiPartyOutcome {
+                outcome,
+                payouts: match i {
+                    0 => vec![100000000, 0, 0],
+                    1 => vec![0, 100000000, 0],
+                    2 => vec![0, 0, 100000000],
+                    _ => vec![50000000, 30000000, 20000000],
+                },
+            })
+            .collect(),
+        oracle_public_key: oracle.get_public_key(),
+        event_id: EVENT_ID.to_string(),
+    };
+
+    let coordinator_id = node_ids[0];
+    let offers = managers[0]
+        .send_multi_party_offer(&contract_input, &node_ids[1..])
+        .expect("to be able to offer the contract");
+    let temporary_id = offers[0].1.temporary_contract_id;
+
+    let mut accepts = Vec::new();
+    for (i, (_, offer)) in offers.into_iter().enumerate() {
+        let participant = &mut managers[i + 1];
+        let res = participant
+            .on_dlc_message(&Message::MultiPartyOffer(offer), coordinator_id)
+            .expect("to be able to process the offer");
+        assert!(res.is_none());
+        let (node_id, accept) = participant
+            .accept_multi_party_offer(&temporary_id)
+            .expect("to be able to accept the offer");
+        assert_eq!(coordinator_id, node_id);
+        accepts.push((node_ids[i + 1], Message::MultiPartyAccept(accept)));
+    }
+
+    // Delivers the given messages from the participants to the coordinator
+    // and the messages broadcast in return to the participants, returning the
+    // replies of the participants.
+    let mut exchange = |msgs: Vec<(secp256k1_zkp::PublicKey, Message)>| {
+        for (node_id, msg) in msgs {
+            let res = managers[0]
+                .on_dlc_message(&msg, node_id)
+                .expect("coordinator to process the message");
+            assert!(res.is_none());
+        }
+        managers[0]
+            .get_and_clear_pending_multi_party_messages()
+            .into_iter()
+            .map(|(node_id, msg)| {
+                let i = node_ids.iter().position(|x| x == &node_id).unwrap();
+                let reply = managers[i]
+                    .on_dlc_message(&msg, coordinator_id)
+                    .expect("participant to process the message")
+                    .expect("participant to reply");
+                (node_id, reply)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let signs = exchange(accepts);
+    assert_eq!(2, signs.len());
+    let fundings = exchange(signs);
+    assert_eq!(2, fundings.len());
+    assert!(exchange(fundings).is_empty());
+
+    let contracts = managers[0].get_store().get_multi_party_contracts().unwrap();
+    assert_eq!(1, contracts.len());
+    assert_eq!(MultiPartyContractState::Broadcast, contracts[0].state);
+
+    let fund_txid = contracts[0].get_dlc_transactions().unwrap().fund.txid();
+    sink_rpc
+        .generate_to_address(1, &sink_address)
+        .expect("RPC Error");
+    let mut retry = 0;
+    while electrs
+        .get_transaction_confirmations(&fund_txid)
+        .unwrap_or(0)
+        == 0
+    {
+        assert!(retry < 30, "Fund transaction not confirmed");
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        retry += 1;
+    }
+}
@@ -282,6 +282,7 @@ pub fn get_enum_test_params(
         oracles: OracleInput {
             public_keys: oracles.iter().map(|x| x.get_public_key()).collect(),
             event_id: EVENT_ID.to_owned(),
+            event_ids: Vec::new(),
             threshold: threshold as u16,
         },
     };
@@ -291,6 +292,10 @@ pub fn get_enum_test_params(
         accept_collateral: ACCEPT_COLLATERAL,
         fee_rate: 2,
         contract_infos: vec![contract_info],
+        metadata: None,
+        payout_script_pubkey: None,
+        change_script_pubkey: None,
+        additional_payout_outputs: Vec::new(),
     };
 
     TestParams {
@@ -379,6 +384,7 @@ pub fn get_numerical_contract_descriptor(
         },
         oracle_numeric_infos,
         difference_params,
+        truncated_digits: Vec::new(),
     })
 }
 
@@ -479,6 +485,7 @@ pub fn get_numerical_test_params(
         oracles: OracleInput {
             public_keys: oracles.iter().map(|x| x.get_public_key()).collect(),
             event_id: EVENT_ID.to_owned(),
+            event_ids: Vec::new(),
             threshold: threshold as u16,
         },
         contract_descriptor,
@@ -489,6 +496,10 @@ pub fn get_numerical_test_params(
         accept_collateral: ACCEPT_COLLATERAL,
         fee_rate: 2,
         contract_infos: vec![contract_info],
+        metadata: None,
+        payout_script_pubkey: None,
+        change_script_pubkey: None,
+        additional_payout_outputs: Vec::new(),
     };
 
     TestParams {
@@ -510,6 +521,7 @@ pub fn get_enum_and_numerical_test_params(
         oracles: OracleInput {
             public_keys: enum_oracles.iter().map(|x| x.get_public_key()).collect(),
             event_id: EVENT_ID.to_owned(),
+            event_ids: Vec::new(),
             threshold: threshold as u16,
         },
         contract_descriptor: enum_contract_descriptor,
@@ -528,6 +540,7 @@ pub fn get_enum_and_numerical_test_params(
                 .map(|x| x.get_public_key())
                 .collect(),
             event_id: EVENT_ID.to_owned(),
+            event_ids: Vec::new(),
             threshold: threshold as u16,
         },
         contract_descriptor: numerical_contract_descriptor,
@@ -544,6 +557,10 @@ pub fn get_enum_and_numerical_test_params(
         accept_collateral: ACCEPT_COLLATERAL,
         fee_rate: 2,
         contract_infos,
+        metadata: None,
+        payout_script_pubkey: None,
+        change_script_pubkey: None,
+        additional_payout_outputs: Vec::new(),
     };
 
     TestParams {
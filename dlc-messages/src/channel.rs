@@ -11,8 +11,9 @@ use secp256k1_zkp::{
 use crate::FundingSignatures;
 use crate::{
     contract_msgs::ContractInfo,
+    features::{Features, FEATURES_TLV_TYPE},
     ser_impls::{read_ecdsa_adaptor_signature, write_ecdsa_adaptor_signature},
-    CetAdaptorSignatures, FundingInput, NegotiationFields,
+    CetAdaptorSignatures, FundingInput, NegotiationFields, UnknownTlv,
 };
 
 /// Contains information about a party wishing to enter into a DLC with
@@ -80,6 +81,18 @@ pub struct OfferChannel {
     pub refund_locktime: u32,
     /// The nSequence value to use for the CETs.
     pub cet_nsequence: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// The protocol features supported or required by the offer party.
+    pub features: Option<Features>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// TLV records with an unknown odd type following the message fields.
+    pub unknown_tlvs: Vec<UnknownTlv>,
 }
 
 impl_dlc_writeable!(OfferChannel, {
@@ -105,7 +118,7 @@ impl_dlc_writeable!(OfferChannel, {
         (cet_locktime, writeable),
         (refund_locktime, writeable),
         (cet_nsequence, writeable)
-});
+}, tlv_stream: unknown_tlvs, { (FEATURES_TLV_TYPE, features) });
 
 impl OfferChannel {
     /// Returns whether the message satisfies validity requirements.
@@ -116,6 +129,24 @@ impl OfferChannel {
         max_timeout_interval: u32,
         min_cet_nsequence: u32,
         max_cet_nsequence: u32,
+    ) -> Result<(), Error> {
+        self.validate_without_announcements(
+            min_timeout_interval,
+            max_timeout_interval,
+            min_cet_nsequence,
+            max_cet_nsequence,
+        )?;
+        self.contract_info.validate_announcements(secp)
+    }
+
+    /// Returns whether the message satisfies validity requirements, without
+    /// verifying the oracle announcements it contains.
+    pub fn validate_without_announcements(
+        &self,
+        min_timeout_interval: u32,
+        max_timeout_interval: u32,
+        min_cet_nsequence: u32,
+        max_cet_nsequence: u32,
     ) -> Result<(), Error> {
         let closest_maturity_date = self.contract_info.get_closest_maturity_date();
         let valid_dates = self.cet_locktime <= closest_maturity_date
@@ -127,15 +158,6 @@ impl OfferChannel {
             return Err(Error::InvalidArgument);
         }
 
-        match &self.contract_info {
-            ContractInfo::SingleContractInfo(s) => s.contract_info.oracle_info.validate(secp)?,
-            ContractInfo::DisjointContractInfo(d) => {
-                for c in &d.contract_infos {
-                    c.oracle_info.validate(secp)?;
-                }
-            }
-        }
-
         Ok(())
     }
 }
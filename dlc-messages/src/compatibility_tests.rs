@@ -221,6 +221,7 @@ fn get_party_params<C: Signing>(
             inputs,
             collateral: params.collateral,
             input_amount: total_value,
+            additional_payout_outputs: Vec::new(),
         },
         fund_inputs,
         sks,
@@ -680,6 +681,7 @@ fn test_dlc_fees() {
             payout_serial_id: 0,
             inputs: get_inputs(inputs),
             input_amount: 110000,
+            additional_payout_outputs: Vec::new(),
         }
     };
 
@@ -3,6 +3,8 @@
 use lightning::ln::msgs::DecodeError;
 use lightning::util::ser::{Readable, Writeable, Writer};
 use oracle_msgs::OracleInfo;
+use secp256k1_zkp::{Secp256k1, Verification};
+use ser_impls::{read_strings, read_vec, write_strings, write_vec};
 
 #[derive(Clone, PartialEq, Debug, Eq)]
 #[cfg_attr(
@@ -63,6 +65,23 @@ impl ContractInfo {
                 .expect("to have at least one element"),
         }
     }
+
+    /// Checks the signature and number of nonces of all the oracle
+    /// announcements used in the contract.
+    pub fn validate_announcements<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+    ) -> Result<(), dlc::Error> {
+        match self {
+            ContractInfo::SingleContractInfo(s) => s.contract_info.oracle_info.validate(secp),
+            ContractInfo::DisjointContractInfo(d) => {
+                for c in &d.contract_infos {
+                    c.oracle_info.validate(secp)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -126,10 +145,20 @@ pub enum ContractDescriptor {
     EnumeratedContractDescriptor(EnumeratedContractDescriptor),
     /// Used for contract based on numerical outcomes.
     NumericOutcomeContractDescriptor(NumericOutcomeContractDescriptor),
+    /// Used for contract based on the combination of the numerical outcomes of
+    /// two events.
+    CompositeOutcomeContractDescriptor(CompositeOutcomeContractDescriptor),
+    /// Used for contract based on a boolean combination of the enumerated
+    /// outcomes of several events.
+    BooleanEnumContractDescriptor(BooleanEnumContractDescriptor),
 }
 
 impl_dlc_writeable_enum!(
-    ContractDescriptor, (0, EnumeratedContractDescriptor), (1, NumericOutcomeContractDescriptor);;;
+    ContractDescriptor,
+    (0, EnumeratedContractDescriptor),
+    (1, NumericOutcomeContractDescriptor),
+    (2, CompositeOutcomeContractDescriptor),
+    (3, BooleanEnumContractDescriptor);;;
 );
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -166,6 +195,106 @@ pub struct NumericOutcomeContractDescriptor {
 
 impl_dlc_writeable!(NumericOutcomeContractDescriptor, { (num_digits, writeable), (payout_function, writeable), (rounding_intervals, writeable) });
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// The operation used to combine the outcomes of the two events of a composite
+/// contract.
+pub enum CompositeOperation {
+    /// The outcome of the first event minus the outcome of the second one.
+    Difference,
+    /// The outcome of the first event divided by the outcome of the second one.
+    Ratio,
+}
+
+impl_dlc_writeable_enum!(CompositeOperation,;;; (0, Difference), (1, Ratio));
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// Information about outcomes and payouts for a contract based on the
+/// combination of the outcomes of two numerical events, each attested by its
+/// own oracle.
+pub struct CompositeOutcomeContractDescriptor {
+    /// The number of digits used by the oracle of the first event.
+    pub first_num_digits: u16,
+    /// The number of digits used by the oracle of the second event.
+    pub second_num_digits: u16,
+    /// How the outcomes of the two events are combined.
+    pub operation: CompositeOperation,
+    /// The factor by which ratios are multiplied before being truncated.
+    pub ratio_precision: u64,
+    /// The function representing the payout depending on the combined outcome.
+    pub payout_function: PayoutFunction,
+    /// The rounding intervals to be applied to the payouts.
+    pub rounding_intervals: RoundingIntervals,
+}
+
+impl_dlc_writeable!(CompositeOutcomeContractDescriptor, {
+    (first_num_digits, writeable),
+    (second_num_digits, writeable),
+    (operation, writeable),
+    (ratio_precision, writeable),
+    (payout_function, writeable),
+    (rounding_intervals, writeable)
+});
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// A condition on the outcomes attested by the oracles of a contract.
+pub enum EnumCondition {
+    /// Satisfied if the oracle at the given index attests to one of the given
+    /// outcomes.
+    Outcome {
+        /// The index of the oracle within the contract oracle information.
+        oracle_index: u16,
+        /// The outcomes satisfying the condition.
+        outcomes: Vec<String>,
+    },
+    /// Satisfied if all of the conditions are.
+    And(Vec<EnumCondition>),
+    /// Satisfied if any of the conditions is.
+    Or(Vec<EnumCondition>),
+}
+
+impl_dlc_writeable_enum!(EnumCondition,;
+    (0, Outcome, {(oracle_index, writeable), (outcomes, {cb_writeable, write_strings, read_strings})});
+    (1, And, write_vec, read_vec), (2, Or, write_vec, read_vec);
+);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// Information about the payouts of a contract based on whether a boolean
+/// combination of enumerated outcomes attested by several oracles holds.
+pub struct BooleanEnumContractDescriptor {
+    /// The condition on the attested outcomes.
+    pub condition: EnumCondition,
+    /// The payout of the offer party if the condition is satisfied.
+    pub true_offer_payout: u64,
+    /// The payout of the offer party if the condition is not satisfied.
+    pub false_offer_payout: u64,
+}
+
+impl_dlc_writeable!(BooleanEnumContractDescriptor, {
+    (condition, writeable),
+    (true_offer_payout, writeable),
+    (false_offer_payout, writeable)
+});
+
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(
     feature = "serde",
@@ -210,11 +339,17 @@ pub enum PayoutCurvePiece {
     PolynomialPayoutCurvePiece(PolynomialPayoutCurvePiece),
     /// Used for curves represented as hyperbola functions.
     HyperbolaPayoutCurvePiece(HyperbolaPayoutCurvePiece),
+    /// Used for curves represented as step functions.
+    StepPayoutCurvePiece(StepPayoutCurvePiece),
+    /// Used for curves represented as monotone cubic splines.
+    MonotoneCubicPayoutCurvePiece(MonotoneCubicPayoutCurvePiece),
 }
 
 impl_dlc_writeable_enum!(PayoutCurvePiece,
   (0, PolynomialPayoutCurvePiece),
-  (1, HyperbolaPayoutCurvePiece);;;
+  (1, HyperbolaPayoutCurvePiece),
+  (2, StepPayoutCurvePiece),
+  (3, MonotoneCubicPayoutCurvePiece);;;
 );
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -231,6 +366,36 @@ pub struct PolynomialPayoutCurvePiece {
 
 impl_dlc_writeable!(PolynomialPayoutCurvePiece, { (payout_points, vec) });
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// A payout curve represented by a step function.
+pub struct StepPayoutCurvePiece {
+    /// The points at which the payout changes, excluding the end points of the
+    /// piece.
+    pub payout_points: Vec<PayoutPoint>,
+}
+
+impl_dlc_writeable!(StepPayoutCurvePiece, { (payout_points, vec) });
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// A payout curve represented by a monotone cubic spline.
+pub struct MonotoneCubicPayoutCurvePiece {
+    /// The points to be used to interpolate the spline, excluding the end
+    /// points of the piece.
+    pub payout_points: Vec<PayoutPoint>,
+}
+
+impl_dlc_writeable!(MonotoneCubicPayoutCurvePiece, { (payout_points, vec) });
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
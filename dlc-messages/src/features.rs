@@ -0,0 +1,159 @@
+//! Feature bits used to negotiate optional protocol capabilities with peers.
+//!
+//! Following the convention of the Lightning Network, each feature is
+//! assigned a pair of bits: the even bit indicates that the feature is required
+//! by the sender, while the odd bit indicates that it is optionally supported.
+//! A peer must reject messages requiring a feature that it does not know about.
+
+use lightning::ln::msgs::DecodeError;
+use lightning::util::ser::{Readable, Writeable, Writer};
+
+/// The type of the TLV record carrying the features of the sender in offer
+/// messages.
+pub const FEATURES_TLV_TYPE: u64 = 1;
+
+/// Capabilities that can be negotiated between peers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    /// Large messages can be split using segment start and chunk messages.
+    Segmentation,
+    /// The funding output can be a taproot output.
+    TaprootFunding,
+    /// Adaptor signatures can be sent in several chunks.
+    ChunkedSignatures,
+    /// Hyperbola payout curve pieces are evaluated with checked fixed point
+    /// arithmetic instead of floating point numbers. Required by offers using
+    /// hyperbola pieces, so that both parties compute the same payouts.
+    FixedPointHyperbola,
+}
+
+impl Feature {
+    fn required_bit(&self) -> u64 {
+        match self {
+            Feature::Segmentation => 0,
+            Feature::TaprootFunding => 2,
+            Feature::ChunkedSignatures => 4,
+            Feature::FixedPointHyperbola => 6,
+        }
+    }
+
+    fn mask(&self) -> u64 {
+        0b11 << self.required_bit()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// A set of feature bits.
+pub struct Features {
+    /// The raw feature bits.
+    pub bits: u64,
+}
+
+impl_dlc_writeable!(Features, { (bits, writeable) });
+
+impl Features {
+    /// Returns an empty feature set.
+    pub fn empty() -> Self {
+        Features { bits: 0 }
+    }
+
+    /// Returns the set of features supported by this implementation.
+    pub fn supported() -> Self {
+        let mut features = Features::empty();
+        features.set_optional(Feature::Segmentation);
+        features.set_optional(Feature::FixedPointHyperbola);
+        features
+    }
+
+    /// Marks the given feature as required.
+    pub fn set_required(&mut self, feature: Feature) {
+        self.bits = (self.bits & !feature.mask()) | (1 << feature.required_bit());
+    }
+
+    /// Marks the given feature as optionally supported.
+    pub fn set_optional(&mut self, feature: Feature) {
+        self.bits = (self.bits & !feature.mask()) | (1 << (feature.required_bit() + 1));
+    }
+
+    /// Whether the given feature is either required or optionally supported.
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.bits & feature.mask() != 0
+    }
+
+    /// Whether the given feature is required.
+    pub fn requires(&self, feature: Feature) -> bool {
+        self.bits & (1 << feature.required_bit()) != 0
+    }
+
+    /// Returns the features that can be used with a peer advertising
+    /// `remote`, or an error containing the bits required by the peer that
+    /// are not supported by `self`.
+    pub fn negotiate(&self, remote: &Features) -> Result<Features, u64> {
+        let mut common = Features::empty();
+        let mut unsupported = 0;
+        for bit in (0..64).step_by(2) {
+            let mask = 0b11u64 << bit;
+            if self.bits & mask == 0 {
+                unsupported |= remote.bits & (1 << bit);
+            } else if remote.bits & mask != 0 {
+                if (self.bits | remote.bits) & (1 << bit) != 0 {
+                    common.bits |= 1 << bit;
+                } else {
+                    common.bits |= 1 << (bit + 1);
+                }
+            }
+        }
+
+        if unsupported != 0 {
+            return Err(unsupported);
+        }
+
+        Ok(common)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_query_features_test() {
+        let mut features = Features::empty();
+        assert!(!features.supports(Feature::TaprootFunding));
+
+        features.set_optional(Feature::TaprootFunding);
+        assert!(features.supports(Feature::TaprootFunding));
+        assert!(!features.requires(Feature::TaprootFunding));
+
+        features.set_required(Feature::TaprootFunding);
+        assert!(features.requires(Feature::TaprootFunding));
+        assert!(!features.supports(Feature::Segmentation));
+    }
+
+    #[test]
+    fn negotiate_features_test() {
+        let local = Features::supported();
+        let mut remote = Features::empty();
+        remote.set_required(Feature::Segmentation);
+        remote.set_optional(Feature::ChunkedSignatures);
+
+        let common = local.negotiate(&remote).expect("to be able to negotiate");
+        assert!(common.requires(Feature::Segmentation));
+        assert!(!common.supports(Feature::ChunkedSignatures));
+
+        remote.set_required(Feature::FixedPointHyperbola);
+        let common = local.negotiate(&remote).expect("to be able to negotiate");
+        assert!(common.requires(Feature::FixedPointHyperbola));
+
+        remote.set_required(Feature::TaprootFunding);
+        assert_eq!(
+            Err(1 << Feature::TaprootFunding.required_bit()),
+            local.negotiate(&remote)
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! # Canonical JSON
+//! Serialization of messages to and from the JSON layout used by other DLC
+//! implementations (such as node-dlc), where each message is an object
+//! starting with its `type` followed by its fields in wire order, using camel
+//! case field names and lower case hexadecimal strings.
+
+use std::fmt;
+
+use lightning::ln::wire::Type;
+use serde::{Deserialize, Serialize};
+
+use Message;
+
+/// An error that can occur when converting messages to or from JSON.
+#[derive(Debug)]
+pub enum Error {
+    /// The JSON was invalid or did not match the expected message layout.
+    Json(serde_json::Error),
+    /// The type of the message is not known.
+    UnknownMessageType(u16),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "Invalid JSON message: {}", e),
+            Error::UnknownMessageType(t) => write!(f, "Unknown message type {}", t),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
+
+#[derive(Serialize)]
+struct TypedMessage<'a, T> {
+    #[serde(rename = "type")]
+    msg_type: u16,
+    #[serde(flatten)]
+    message: &'a T,
+}
+
+#[derive(Deserialize)]
+struct MessageType {
+    #[serde(rename = "type")]
+    msg_type: u16,
+}
+
+macro_rules! impl_canonical_json {
+    ($(($type_id: ident, $variant: ident)),*) => {
+        /// Serializes the message to canonical JSON.
+        pub fn to_json(message: &Message) -> Result<String, Error> {
+            let json = match message {
+                $(
+                    Message::$variant(m) => serde_json::to_string(&TypedMessage {
+                        msg_type: message.type_id(),
+                        message: m,
+                    })?,
+                )*
+            };
+            Ok(json)
+        }
+
+        /// Parses a message from canonical JSON, using its `type` field to
+        /// determine the kind of message.
+        pub fn from_json(json: &str) -> Result<Message, Error> {
+            let MessageType { msg_type } = serde_json::from_str(json)?;
+            let message = match msg_type {
+                $(
+                    $crate::$type_id => Message::$variant(serde_json::from_str(json)?),
+                )*
+                _ => return Err(Error::UnknownMessageType(msg_type)),
+            };
+            Ok(message)
+        }
+    };
+}
+
+impl_canonical_json!(
+    (OFFER_TYPE, Offer),
+    (ACCEPT_TYPE, Accept),
+    (SIGN_TYPE, Sign),
+    (OFFER_CHANNEL_TYPE, OfferChannel),
+    (ACCEPT_CHANNEL_TYPE, AcceptChannel),
+    (SIGN_CHANNEL_TYPE, SignChannel),
+    (SETTLE_CHANNEL_OFFER_TYPE, SettleOffer),
+    (SETTLE_CHANNEL_ACCEPT_TYPE, SettleAccept),
+    (SETTLE_CHANNEL_CONFIRM_TYPE, SettleConfirm),
+    (SETTLE_CHANNEL_FINALIZE_TYPE, SettleFinalize),
+    (RENEW_CHANNEL_OFFER_TYPE, RenewOffer),
+    (RENEW_CHANNEL_ACCEPT_TYPE, RenewAccept),
+    (RENEW_CHANNEL_CONFIRM_TYPE, RenewConfirm),
+    (RENEW_CHANNEL_FINALIZE_TYPE, RenewFinalize),
+    (COLLABORATIVE_CLOSE_OFFER_TYPE, CollaborativeCloseOffer),
+    (REJECT, Reject),
+    (MULTI_PARTY_OFFER_TYPE, MultiPartyOffer),
+    (MULTI_PARTY_ACCEPT_TYPE, MultiPartyAccept),
+    (MULTI_PARTY_SETUP_TYPE, MultiPartySetup),
+    (MULTI_PARTY_SIGN_TYPE, MultiPartySign),
+    (MULTI_PARTY_FINALIZE_TYPE, MultiPartyFinalize),
+    (MULTI_PARTY_FUNDING_TYPE, MultiPartyFunding),
+    (NOVATION_OFFER_TYPE, NovationOffer),
+    (NOVATION_ACCEPT_TYPE, NovationAccept),
+    (NOVATION_REQUEST_TYPE, NovationRequest),
+    (NOVATION_CONFIRM_TYPE, NovationConfirm),
+    (NOVATION_FINALIZE_TYPE, NovationFinalize)
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lightning::util::ser::Writeable;
+    use {AcceptDlc, OfferDlc, SignDlc};
+
+    #[test]
+    fn canonical_json_roundtrip() {
+        let offer: OfferDlc =
+            serde_json::from_str(include_str!("./test_inputs/offer_msg.json")).unwrap();
+        let accept: AcceptDlc =
+            serde_json::from_str(include_str!("./test_inputs/accept_msg.json")).unwrap();
+        let sign: SignDlc =
+            serde_json::from_str(include_str!("./test_inputs/sign_msg.json")).unwrap();
+
+        for message in vec![
+            Message::Offer(offer),
+            Message::Accept(accept),
+            Message::Sign(sign),
+        ] {
+            let json = to_json(&message).unwrap();
+            assert!(json.starts_with(&format!("{{\"type\":{},", message.type_id())));
+            let parsed = from_json(&json).unwrap();
+            assert_eq!(message.encode(), parsed.encode());
+        }
+    }
+
+    #[test]
+    fn upper_case_hex_is_accepted() {
+        let offer: OfferDlc =
+            serde_json::from_str(include_str!("./test_inputs/offer_msg.json")).unwrap();
+        let id = offer
+            .temporary_contract_id
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let json = to_json(&Message::Offer(offer.clone())).unwrap();
+        assert!(json.contains(&id));
+
+        let parsed = from_json(&json.replace(&id, &id.to_uppercase())).unwrap();
+        match parsed {
+            Message::Offer(parsed) => assert_eq!(offer, parsed),
+            _ => panic!("Expected an offer message"),
+        }
+    }
+
+    #[test]
+    fn unknown_message_type_fails() {
+        assert!(matches!(
+            from_json("{\"type\":1}"),
+            Err(Error::UnknownMessageType(1))
+        ));
+    }
+}
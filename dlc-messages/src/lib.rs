@@ -11,6 +11,8 @@
 #![deny(missing_docs)]
 
 extern crate bitcoin;
+#[cfg(feature = "noise")]
+extern crate chacha20poly1305;
 extern crate dlc;
 extern crate lightning;
 extern crate secp256k1_zkp;
@@ -23,21 +25,32 @@ extern crate bitcoin_test_utils;
 #[cfg(any(test, feature = "serde"))]
 extern crate serde;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "use-serde"))]
 extern crate serde_json;
 
 pub mod channel;
 pub mod contract_msgs;
+pub mod features;
+#[cfg(feature = "use-serde")]
+pub mod json;
 pub mod message_handler;
+pub mod multi_party;
+#[cfg(feature = "noise")]
+pub mod noise;
+pub mod novation;
 pub mod oracle_msgs;
 pub mod segmentation;
+pub mod session;
 
 #[cfg(any(test, feature = "serde"))]
 pub mod serde_utils;
+#[cfg(feature = "serde")]
+pub mod test_vectors;
 
 use std::fmt::Display;
 
 use crate::ser_impls::{read_ecdsa_adaptor_signature, write_ecdsa_adaptor_signature};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
 use bitcoin::{consensus::Decodable, OutPoint, Script, Transaction};
 use channel::{
     AcceptChannel, CollaborativeCloseOffer, OfferChannel, Reject, RenewAccept, RenewConfirm,
@@ -45,12 +58,18 @@ use channel::{
     SignChannel,
 };
 use contract_msgs::ContractInfo;
-use dlc::{Error, TxInputInfo};
+use dlc::{Error, PayoutOutput, TxInputInfo};
+use features::{Features, FEATURES_TLV_TYPE};
 use lightning::ln::msgs::DecodeError;
 use lightning::ln::wire::Type;
-use lightning::util::ser::{Readable, Writeable, Writer};
-use secp256k1_zkp::Verification;
+use lightning::util::ser::{Readable, Writeable, Writer, MAX_BUF_SIZE};
+use multi_party::{
+    MultiPartyAccept, MultiPartyFinalize, MultiPartyFunding, MultiPartyOffer, MultiPartySetup,
+    MultiPartySign,
+};
+use novation::{NovationAccept, NovationConfirm, NovationFinalize, NovationOffer, NovationRequest};
 use secp256k1_zkp::{ecdsa::Signature, EcdsaAdaptorSignature, PublicKey, Secp256k1};
+use secp256k1_zkp::{Message, SecretKey, Signing, Verification};
 use segmentation::{SegmentChunk, SegmentStart};
 
 macro_rules! impl_type {
@@ -86,6 +105,17 @@ impl_type!(
     43022
 );
 impl_type!(REJECT, Reject, 43024);
+impl_type!(MULTI_PARTY_OFFER_TYPE, MultiPartyOffer, 43026);
+impl_type!(MULTI_PARTY_ACCEPT_TYPE, MultiPartyAccept, 43028);
+impl_type!(MULTI_PARTY_SETUP_TYPE, MultiPartySetup, 43030);
+impl_type!(MULTI_PARTY_SIGN_TYPE, MultiPartySign, 43032);
+impl_type!(MULTI_PARTY_FINALIZE_TYPE, MultiPartyFinalize, 43034);
+impl_type!(MULTI_PARTY_FUNDING_TYPE, MultiPartyFunding, 43036);
+impl_type!(NOVATION_OFFER_TYPE, NovationOffer, 43038);
+impl_type!(NOVATION_ACCEPT_TYPE, NovationAccept, 43040);
+impl_type!(NOVATION_REQUEST_TYPE, NovationRequest, 43042);
+impl_type!(NOVATION_CONFIRM_TYPE, NovationConfirm, 43044);
+impl_type!(NOVATION_FINALIZE_TYPE, NovationFinalize, 43046);
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(
@@ -243,6 +273,41 @@ pub struct WitnessElement {
 
 impl_dlc_writeable!(WitnessElement, { (witness, vec) });
 
+/// The type of the TLV record carrying the [`OwnershipProofs`] of the funding
+/// inputs of an offer or accept message.
+pub const FUNDING_INPUT_OWNERSHIP_PROOFS_TLV_TYPE: u64 = 5;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// A proof that the sender of a message controls one of its funding inputs,
+/// as produced by [`dlc::ownership_proof`].
+pub struct OwnershipProof {
+    /// The serial id of the funding input the proof refers to.
+    pub input_serial_id: u64,
+    /// The witness elements of the proof.
+    pub witness_elements: Vec<WitnessElement>,
+}
+
+impl_dlc_writeable!(OwnershipProof, { (input_serial_id, writeable), (witness_elements, vec) });
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// Contains the ownership proofs for the funding inputs of a message.
+pub struct OwnershipProofs {
+    /// The set of ownership proofs.
+    pub proofs: Vec<OwnershipProof>,
+}
+
+impl_dlc_writeable!(OwnershipProofs, { (proofs, vec) });
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
@@ -287,6 +352,213 @@ pub struct DisjointNegotiationFields {
 
 impl_dlc_writeable!(DisjointNegotiationFields, { (negotiation_fields, vec) });
 
+/// The type of the TLV record carrying the [`OfferMetadata`] of an offer.
+pub const OFFER_METADATA_TLV_TYPE: u64 = 3;
+
+/// The maximum length in bytes of each field of an [`OfferMetadata`].
+pub const MAX_OFFER_METADATA_FIELD_LENGTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// Human readable information attached to an offer, which is not used in the
+/// construction of the contract.
+pub struct OfferMetadata {
+    /// A short label describing the contract.
+    pub label: String,
+    /// An identifier chosen by the offer party to refer to the contract.
+    pub reference_id: String,
+}
+
+impl_dlc_writeable!(OfferMetadata, { (label, string), (reference_id, string) });
+
+impl OfferMetadata {
+    /// Returns an error if any of the fields is too long.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.label.len() > MAX_OFFER_METADATA_FIELD_LENGTH
+            || self.reference_id.len() > MAX_OFFER_METADATA_FIELD_LENGTH
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(())
+    }
+}
+
+/// The type of the TLV record carrying the [`RolloverInfo`] of an offer. The
+/// type is even so that peers unaware of rollovers reject the offer instead of
+/// treating it as a regular one.
+pub const ROLLOVER_TLV_TYPE: u64 = 6;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// Information attached to an offer funded by the fund output of an existing
+/// contract between the parties, which is settled by the funding transaction
+/// of the offered contract. The offer party can provide additional funding
+/// inputs to top up its collateral.
+pub struct RolloverInfo {
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract being rolled over.
+    pub contract_id: [u8; 32],
+    /// The part of the value of the fund output of the rolled over contract
+    /// attributed to the accepting party, the rest going to the offer party.
+    pub counter_payout: u64,
+}
+
+impl_dlc_writeable!(RolloverInfo, { (contract_id, writeable), (counter_payout, writeable) });
+
+/// The type of the TLV record carrying the part of the output jointly owned by
+/// the parties funding an offer that is attributed to the accepting party. The
+/// type is even so that peers unaware of it reject the offer instead of
+/// funding their collateral from their own inputs.
+pub const SHARED_FUNDING_TLV_TYPE: u64 = 8;
+
+/// The type of the TLV record carrying the [`AdditionalPayoutOutputs`] of an
+/// offer or accept message. The type is even so that peers unaware of it reject
+/// the message instead of building transactions paying the whole payout of the
+/// sender to a single output.
+pub const ADDITIONAL_PAYOUT_OUTPUTS_TLV_TYPE: u64 = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// An additional output receiving a share of the payout of the sender of a
+/// message.
+pub struct AdditionalPayoutOutput {
+    /// The script pubkey receiving the share of the payout.
+    pub script_pubkey: Script,
+    /// Serial id to order CET and refund transaction outputs.
+    pub serial_id: u64,
+    /// The share of the payout sent to the output, in basis points.
+    pub share_bps: u16,
+}
+
+impl_dlc_writeable!(AdditionalPayoutOutput, {
+    (script_pubkey, writeable),
+    (serial_id, writeable),
+    (share_bps, writeable)
+});
+
+impl From<&AdditionalPayoutOutput> for PayoutOutput {
+    fn from(output: &AdditionalPayoutOutput) -> PayoutOutput {
+        PayoutOutput {
+            script_pubkey: output.script_pubkey.clone(),
+            serial_id: output.serial_id,
+            share_bps: output.share_bps,
+        }
+    }
+}
+
+impl From<&PayoutOutput> for AdditionalPayoutOutput {
+    fn from(output: &PayoutOutput) -> AdditionalPayoutOutput {
+        AdditionalPayoutOutput {
+            script_pubkey: output.script_pubkey.clone(),
+            serial_id: output.serial_id,
+            share_bps: output.share_bps,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// Contains the additional outputs receiving a share of the payout of the
+/// sender of a message, the remainder being sent to its payout script pubkey.
+pub struct AdditionalPayoutOutputs {
+    /// The set of additional payout outputs.
+    pub outputs: Vec<AdditionalPayoutOutput>,
+}
+
+impl_dlc_writeable!(AdditionalPayoutOutputs, { (outputs, vec) });
+
+impl AdditionalPayoutOutputs {
+    /// Returns the given payout outputs as a TLV record value, or `None` if
+    /// there are none.
+    pub fn from_payout_outputs(outputs: &[PayoutOutput]) -> Option<AdditionalPayoutOutputs> {
+        if outputs.is_empty() {
+            return None;
+        }
+
+        Some(AdditionalPayoutOutputs {
+            outputs: outputs.iter().map(|x| x.into()).collect(),
+        })
+    }
+
+    /// Returns the payout outputs contained in the given TLV record value.
+    pub fn to_payout_outputs(outputs: &Option<AdditionalPayoutOutputs>) -> Vec<PayoutOutput> {
+        outputs
+            .as_ref()
+            .map(|x| x.outputs.iter().map(|x| x.into()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The type of the TLV record carrying the [`OfferSignature`] of an offer. It
+/// is greater than the type of other known records so that the signature is
+/// serialized after the fields it commits to.
+pub const OFFER_SIGNATURE_TLV_TYPE: u64 = 241;
+
+const OFFER_SIGNATURE_TAG: &[u8] = b"DLC/offer/signature";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// A signature over an offer by the node identity key of the offer party,
+/// enabling offers relayed by third parties to be authenticated.
+pub struct OfferSignature {
+    /// The node identity key of the offer party.
+    pub node_id: PublicKey,
+    /// The signature over the message returned by
+    /// [`OfferDlc::signature_message`].
+    pub signature: Signature,
+}
+
+impl_dlc_writeable!(OfferSignature, { (node_id, writeable), (signature, writeable) });
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+/// A TLV record of a type unknown to this implementation. Records with an odd
+/// type are optional, and are kept as is to be ignored by the application.
+pub struct UnknownTlv {
+    /// The type of the record.
+    pub tlv_type: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_string"
+        )
+    )]
+    /// The value of the record.
+    pub value: Vec<u8>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(
     feature = "serde",
@@ -343,6 +615,57 @@ pub struct OfferDlc {
     pub cet_locktime: u32,
     /// The lock time for the refund transactions.
     pub refund_locktime: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// The protocol features supported or required by the offer party.
+    pub features: Option<Features>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Human readable information about the offered contract.
+    pub metadata: Option<OfferMetadata>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Proofs that the offer party controls its funding inputs.
+    pub ownership_proofs: Option<OwnershipProofs>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// The contract settled by the funding transaction of the offered
+    /// contract, if any.
+    pub rollover: Option<RolloverInfo>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// The part of the output jointly owned by the parties funding the offered
+    /// contract that is attributed to the accepting party, if the contract is
+    /// funded by such an output outside of a rollover.
+    pub shared_funding_accept_share: Option<u64>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Additional outputs receiving a share of the payout of the offer party.
+    pub additional_payout_outputs: Option<AdditionalPayoutOutputs>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// The signature of the offer party authenticating the offer.
+    pub signature: Option<OfferSignature>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// TLV records with an unknown odd type following the message fields.
+    pub unknown_tlvs: Vec<UnknownTlv>,
 }
 
 impl OfferDlc {
@@ -354,6 +677,49 @@ impl OfferDlc {
         }
     }
 
+    /// Returns the message to sign to authenticate the offer, which commits
+    /// to every field of the offer except its signature.
+    pub fn signature_message(&self) -> Message {
+        let mut unsigned = self.clone();
+        unsigned.signature = None;
+        let tag = sha256::Hash::hash(OFFER_SIGNATURE_TAG);
+        let mut engine = sha256::Hash::engine();
+        engine.input(&tag[..]);
+        engine.input(&tag[..]);
+        engine.input(&unsigned.encode());
+        Message::from_slice(&sha256::Hash::from_engine(engine)[..]).expect("a 32 bytes hash")
+    }
+
+    /// Signs the offer using the given node identity secret key, replacing any
+    /// existing signature.
+    pub fn sign<C: Signing>(&mut self, secp: &Secp256k1<C>, node_secret_key: &SecretKey) {
+        let signature = secp.sign_ecdsa(&self.signature_message(), node_secret_key);
+        self.signature = Some(OfferSignature {
+            node_id: PublicKey::from_secret_key(secp, node_secret_key),
+            signature,
+        });
+    }
+
+    /// Returns an error if the offer is not signed by `node_id` or if its
+    /// signature is invalid.
+    pub fn verify_signature<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        node_id: &PublicKey,
+    ) -> Result<(), Error> {
+        match &self.signature {
+            Some(offer_signature) if offer_signature.node_id == *node_id => {
+                secp.verify_ecdsa(
+                    &self.signature_message(),
+                    &offer_signature.signature,
+                    node_id,
+                )?;
+                Ok(())
+            }
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+
     /// Returns whether the message satisfies validity requirements.
     pub fn validate<C: Verification>(
         &self,
@@ -361,19 +727,38 @@ impl OfferDlc {
         min_timeout_interval: u32,
         max_timeout_interval: u32,
     ) -> Result<(), Error> {
-        match &self.contract_info {
-            ContractInfo::SingleContractInfo(s) => s.contract_info.oracle_info.validate(secp)?,
-            ContractInfo::DisjointContractInfo(d) => {
-                if d.contract_infos.len() < 2 {
-                    return Err(Error::InvalidArgument);
-                }
+        self.validate_without_announcements(min_timeout_interval, max_timeout_interval)?;
+        self.contract_info.validate_announcements(secp)
+    }
 
-                for c in &d.contract_infos {
-                    c.oracle_info.validate(secp)?;
-                }
+    /// Returns whether the message satisfies validity requirements, without
+    /// verifying the oracle announcements it contains.
+    pub fn validate_without_announcements(
+        &self,
+        min_timeout_interval: u32,
+        max_timeout_interval: u32,
+    ) -> Result<(), Error> {
+        if let ContractInfo::DisjointContractInfo(d) = &self.contract_info {
+            if d.contract_infos.len() < 2 {
+                return Err(Error::InvalidArgument);
             }
         }
 
+        if let Some(metadata) = &self.metadata {
+            metadata.validate()?;
+        }
+
+        // The fund output of the rolled over contract or the shared output is
+        // one of the inputs, and the accepting party's share of it is only
+        // given once.
+        let is_shared_funding =
+            self.rollover.is_some() || self.shared_funding_accept_share.is_some();
+        if (is_shared_funding && self.funding_inputs.is_empty())
+            || (self.rollover.is_some() && self.shared_funding_accept_share.is_some())
+        {
+            return Err(Error::InvalidArgument);
+        }
+
         let closest_maturity_date = self.contract_info.get_closest_maturity_date();
         let valid_dates = self.cet_locktime <= closest_maturity_date
             && closest_maturity_date + min_timeout_interval <= self.refund_locktime
@@ -403,6 +788,14 @@ impl_dlc_writeable!(OfferDlc, {
         (fee_rate_per_vb, writeable),
         (cet_locktime, writeable),
         (refund_locktime, writeable)
+}, tlv_stream: unknown_tlvs, {
+    (FEATURES_TLV_TYPE, features),
+    (OFFER_METADATA_TLV_TYPE, metadata),
+    (FUNDING_INPUT_OWNERSHIP_PROOFS_TLV_TYPE, ownership_proofs),
+    (ROLLOVER_TLV_TYPE, rollover),
+    (SHARED_FUNDING_TLV_TYPE, shared_funding_accept_share),
+    (ADDITIONAL_PAYOUT_OUTPUTS_TLV_TYPE, additional_payout_outputs),
+    (OFFER_SIGNATURE_TLV_TYPE, signature)
 });
 
 /// Contains information about a party wishing to accept a DLC offer. The contained
@@ -447,6 +840,24 @@ pub struct AcceptDlc {
     pub refund_signature: Signature,
     /// The negotiation fields from the accept party.
     pub negotiation_fields: Option<NegotiationFields>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Proofs that the accept party controls its funding inputs.
+    pub ownership_proofs: Option<OwnershipProofs>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    /// Additional outputs receiving a share of the payout of the accept party.
+    pub additional_payout_outputs: Option<AdditionalPayoutOutputs>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// TLV records with an unknown odd type following the message fields.
+    pub unknown_tlvs: Vec<UnknownTlv>,
 }
 
 impl_dlc_writeable!(AcceptDlc, {
@@ -462,6 +873,9 @@ impl_dlc_writeable!(AcceptDlc, {
     (cet_adaptor_signatures, writeable),
     (refund_signature, writeable),
     (negotiation_fields, option)
+}, tlv_stream: unknown_tlvs, {
+    (FUNDING_INPUT_OWNERSHIP_PROOFS_TLV_TYPE, ownership_proofs),
+    (ADDITIONAL_PAYOUT_OUTPUTS_TLV_TYPE, additional_payout_outputs)
 });
 
 /// Contains all the required signatures for the DLC transactions from the offering
@@ -490,6 +904,12 @@ pub struct SignDlc {
     pub refund_signature: Signature,
     /// The set of funding signatures from the offer party.
     pub funding_signatures: FundingSignatures,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    /// TLV records with an unknown odd type following the message fields.
+    pub unknown_tlvs: Vec<UnknownTlv>,
 }
 
 impl_dlc_writeable!(SignDlc, {
@@ -498,10 +918,15 @@ impl_dlc_writeable!(SignDlc, {
     (cet_adaptor_signatures, writeable),
     (refund_signature, writeable),
     (funding_signatures, writeable)
-});
+}, tlv_stream: unknown_tlvs);
 
 #[allow(missing_docs)]
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub enum Message {
     Offer(OfferDlc),
     Accept(AcceptDlc),
@@ -519,6 +944,17 @@ pub enum Message {
     RenewFinalize(RenewFinalize),
     CollaborativeCloseOffer(CollaborativeCloseOffer),
     Reject(Reject),
+    MultiPartyOffer(MultiPartyOffer),
+    MultiPartyAccept(MultiPartyAccept),
+    MultiPartySetup(MultiPartySetup),
+    MultiPartySign(MultiPartySign),
+    MultiPartyFinalize(MultiPartyFinalize),
+    MultiPartyFunding(MultiPartyFunding),
+    NovationOffer(NovationOffer),
+    NovationAccept(NovationAccept),
+    NovationRequest(NovationRequest),
+    NovationConfirm(NovationConfirm),
+    NovationFinalize(NovationFinalize),
 }
 
 macro_rules! impl_type_writeable_for_enum {
@@ -537,6 +973,12 @@ macro_rules! impl_type_writeable_for_enum {
                    $($type_name::$variant_name(v) => v.write(writer),)*
                 }
             }
+
+            fn serialized_length(&self) -> usize {
+                match self {
+                   $($type_name::$variant_name(v) => v.serialized_length(),)*
+                }
+            }
        }
     };
 }
@@ -558,10 +1000,67 @@ impl_type_writeable_for_enum!(Message,
     RenewConfirm,
     RenewFinalize,
     CollaborativeCloseOffer,
-    Reject
+    Reject,
+    MultiPartyOffer,
+    MultiPartyAccept,
+    MultiPartySetup,
+    MultiPartySign,
+    MultiPartyFinalize,
+    MultiPartyFunding,
+    NovationOffer,
+    NovationAccept,
+    NovationRequest,
+    NovationConfirm,
+    NovationFinalize
 });
 
+impl Message {
+    /// Returns the serialization of the message prefixed by its type, as sent
+    /// in a single wire message.
+    pub fn encode_with_type(&self) -> Vec<u8> {
+        let mut buf = self.type_id().encode();
+        buf.extend(self.encode());
+        buf
+    }
+
+    /// Reads a message serialized using [`Message::encode_with_type`]. Messages
+    /// ending with a TLV stream are read until the end of `reader`.
+    pub fn read_with_type<R: std::io::Read>(reader: &mut R) -> Result<Message, DecodeError> {
+        let msg_type: u16 = Readable::read(reader)?;
+        match message_handler::read_dlc_message(msg_type, reader)? {
+            Some(WireMessage::Message(m)) => Ok(m),
+            _ => Err(DecodeError::UnknownRequiredFeature),
+        }
+    }
+
+    /// Returns the number of bytes taken by the message when sent in a single
+    /// wire message, including its type prefix.
+    pub fn wire_length(&self) -> usize {
+        2 + self.serialized_length()
+    }
+
+    /// Returns whether the message is too large to be sent in a single wire
+    /// message and needs to be split into segments.
+    pub fn requires_segmentation(&self) -> bool {
+        self.serialized_length() > MAX_BUF_SIZE
+    }
+
+    /// Returns the number of wire messages required to send the message.
+    pub fn nb_segments(&self) -> usize {
+        if self.requires_segmentation() {
+            segmentation::get_nb_segments(self.serialized_length())
+        } else {
+            1
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 /// Wrapper for DLC related message and segmentation related messages.
 pub enum WireMessage {
     /// Message related to establishment of a DLC contract.
@@ -624,6 +1123,374 @@ mod tests {
         roundtrip_test!(SignDlc, input);
     }
 
+    fn get_multi_party_offer() -> MultiPartyOffer {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let offer: OfferDlc = serde_json::from_str(input).unwrap();
+        let oracle_announcement = match &offer.contract_info {
+            ContractInfo::SingleContractInfo(s) => match &s.contract_info.oracle_info {
+                oracle_msgs::OracleInfo::Multi(m) => m.oracle_announcements[0].clone(),
+                oracle_msgs::OracleInfo::Single(s) => s.oracle_announcement.clone(),
+            },
+            ContractInfo::DisjointContractInfo(_) => unreachable!(),
+        };
+        MultiPartyOffer {
+            protocol_version: offer.protocol_version,
+            chain_hash: offer.chain_hash,
+            temporary_contract_id: offer.temporary_contract_id,
+            participants: vec![offer.funding_pubkey, offer.funding_pubkey],
+            party_index: 1,
+            collaterals: vec![100000, 50000, 50000],
+            outcomes: vec![multi_party::MultiPartyOutcome {
+                outcome: "a".to_string(),
+                payouts: vec![0, 100000, 100000],
+            }],
+            oracle_announcement,
+            offer_params: multi_party::MultiPartyParams {
+                funding_pubkey: offer.funding_pubkey,
+                payout_spk: offer.payout_spk.clone(),
+                payout_serial_id: offer.payout_serial_id,
+                funding_inputs: offer.funding_inputs.clone(),
+                change_spk: offer.change_spk.clone(),
+                change_serial_id: offer.change_serial_id,
+            },
+            fund_output_serial_id: offer.fund_output_serial_id,
+            fee_rate_per_vb: offer.fee_rate_per_vb,
+            cet_locktime: offer.cet_locktime,
+            refund_locktime: offer.refund_locktime,
+        }
+    }
+
+    #[test]
+    fn multi_party_offer_roundtrip_and_validation() {
+        let offer = get_multi_party_offer();
+        test_roundtrip(offer.clone());
+        let msg = Message::MultiPartyOffer(offer.clone());
+        let encoded = msg.encode_with_type();
+        let decoded = Message::read_with_type(&mut std::io::Cursor::new(&encoded)).unwrap();
+        assert_eq!(encoded, decoded.encode_with_type());
+
+        let week = 7 * 86400;
+        offer.validate(week, 2 * week).expect("offer to be valid");
+
+        let mut invalid = offer.clone();
+        invalid.outcomes[0].payouts[0] = 1;
+        invalid
+            .validate(week, 2 * week)
+            .expect_err("payouts not matching the collaterals");
+
+        let mut invalid = offer.clone();
+        invalid.party_index = 3;
+        invalid
+            .validate(week, 2 * week)
+            .expect_err("party index out of bounds");
+    }
+
+    #[test]
+    fn novation_messages_roundtrip() {
+        let offer: OfferDlc =
+            serde_json::from_str(include_str!("./test_inputs/offer_msg.json")).unwrap();
+        let accept: AcceptDlc =
+            serde_json::from_str(include_str!("./test_inputs/accept_msg.json")).unwrap();
+        let terms = novation::NovationTerms {
+            price: 10000,
+            price_spk: offer.payout_spk.clone(),
+            price_serial_id: 3,
+            fund_output_serial_id: offer.fund_output_serial_id,
+            fee_rate_per_vb: offer.fee_rate_per_vb,
+        };
+        let novation_offer = NovationOffer {
+            protocol_version: offer.protocol_version,
+            chain_hash: offer.chain_hash,
+            contract_id: [1; 32],
+            temporary_contract_id: offer.temporary_contract_id,
+            remaining_party: accept.funding_pubkey,
+            transferor_is_offer_party: true,
+            contract_info: offer.contract_info.clone(),
+            transferor_collateral: offer.offer_collateral,
+            transferor_funding_pubkey: offer.funding_pubkey,
+            transferor_payout_serial_id: offer.payout_serial_id,
+            remaining_funding_pubkey: accept.funding_pubkey,
+            remaining_payout_spk: accept.payout_spk.clone(),
+            remaining_payout_serial_id: accept.payout_serial_id,
+            fund_txid: bitcoin::Txid::all_zeros(),
+            fund_output_index: 0,
+            fund_output_value: offer.contract_info.get_total_collateral(),
+            cet_locktime: offer.cet_locktime,
+            refund_locktime: offer.refund_locktime,
+            terms,
+        };
+        novation_offer.validate().expect("offer to be valid");
+        let mut invalid = novation_offer.clone();
+        invalid.remaining_funding_pubkey = invalid.transferor_funding_pubkey;
+        invalid.validate().expect_err("identical funding pubkeys");
+
+        let novation_accept = NovationAccept {
+            protocol_version: accept.protocol_version,
+            contract_id: [1; 32],
+            funding_pubkey: accept.funding_pubkey,
+            payout_spk: accept.payout_spk.clone(),
+            funding_inputs: accept.funding_inputs.clone(),
+            change_spk: accept.change_spk.clone(),
+            change_serial_id: accept.change_serial_id,
+            cet_adaptor_signatures: accept.cet_adaptor_signatures.clone(),
+            refund_signature: accept.refund_signature,
+        };
+        let messages = vec![
+            Message::NovationOffer(novation_offer.clone()),
+            Message::NovationAccept(novation_accept.clone()),
+            Message::NovationRequest(NovationRequest {
+                protocol_version: accept.protocol_version,
+                contract_id: [1; 32],
+                transferee: offer.funding_pubkey,
+                offer: novation_offer,
+                accept: novation_accept,
+            }),
+            Message::NovationConfirm(NovationConfirm {
+                protocol_version: accept.protocol_version,
+                contract_id: [1; 32],
+                cet_adaptor_signatures: accept.cet_adaptor_signatures.clone(),
+                refund_signature: accept.refund_signature,
+                fund_input_signature: accept.refund_signature,
+            }),
+            Message::NovationFinalize(NovationFinalize {
+                protocol_version: accept.protocol_version,
+                contract_id: [1; 32],
+                cet_adaptor_signatures: accept.cet_adaptor_signatures,
+                refund_signature: accept.refund_signature,
+                transferor_fund_input_signature: accept.refund_signature,
+                remaining_fund_input_signature: accept.refund_signature,
+            }),
+        ];
+        for msg in messages {
+            let encoded = msg.encode_with_type();
+            let decoded = Message::read_with_type(&mut std::io::Cursor::new(&encoded)).unwrap();
+            assert_eq!(encoded, decoded.encode_with_type());
+        }
+    }
+
+    #[test]
+    fn message_json_roundtrip() {
+        let input = include_str!("./test_inputs/accept_msg.json");
+        let accept: AcceptDlc = serde_json::from_str(input).unwrap();
+        let msg = WireMessage::Message(Message::Accept(accept));
+
+        let json = serde_json::to_string(&msg).unwrap();
+        let deser: WireMessage = serde_json::from_str(&json).unwrap();
+
+        assert!(json.starts_with("{\"message\":{\"accept\":"));
+        assert_eq!(msg.encode(), deser.encode());
+    }
+
+    #[test]
+    fn unknown_odd_tlvs_roundtrip() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let mut offer: OfferDlc = serde_json::from_str(input).unwrap();
+        offer.unknown_tlvs = vec![
+            UnknownTlv {
+                tlv_type: 1001,
+                value: vec![1, 2, 3],
+            },
+            UnknownTlv {
+                tlv_type: 0xFFFF_FFFF_FF,
+                value: vec![4; 300],
+            },
+        ];
+
+        test_roundtrip(offer.clone());
+
+        let mut buf = offer.encode();
+        buf.truncate(buf.len() - 1);
+        OfferDlc::read(&mut std::io::Cursor::new(&buf))
+            .expect_err("Should not read truncated TLV record.");
+    }
+
+    #[test]
+    fn offer_features_roundtrip() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let mut offer: OfferDlc = serde_json::from_str(input).unwrap();
+        offer.features = Some(features::Features::supported());
+        offer.unknown_tlvs = vec![UnknownTlv {
+            tlv_type: 1001,
+            value: vec![1],
+        }];
+
+        test_roundtrip(offer.clone());
+
+        let mut without_features = offer.clone();
+        without_features.features = None;
+        assert_eq!(
+            offer.serialized_length(),
+            without_features.serialized_length() + 10
+        );
+    }
+
+    #[test]
+    fn offer_metadata_roundtrip_and_validation() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let mut offer: OfferDlc = serde_json::from_str(input).unwrap();
+        offer.features = Some(features::Features::supported());
+        offer.metadata = Some(OfferMetadata {
+            label: "BTC/USD weekly call".to_string(),
+            reference_id: "order-42".to_string(),
+        });
+
+        test_roundtrip(offer.clone());
+        offer
+            .validate(SECP256K1, 86400 * 7, 86400 * 14)
+            .expect("to validate offer with metadata");
+
+        offer.metadata = Some(OfferMetadata {
+            label: "a".repeat(MAX_OFFER_METADATA_FIELD_LENGTH + 1),
+            reference_id: String::new(),
+        });
+        offer
+            .validate(SECP256K1, 86400 * 7, 86400 * 14)
+            .expect_err("Should not validate offer with too long label.");
+    }
+
+    #[test]
+    fn offer_shared_funding_roundtrip_and_validation() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let mut offer: OfferDlc = serde_json::from_str(input).unwrap();
+        offer.funding_inputs.truncate(1);
+        offer.shared_funding_accept_share = Some(100000);
+
+        test_roundtrip(offer.clone());
+        offer
+            .validate(SECP256K1, 86400 * 7, 86400 * 14)
+            .expect("to validate offer funded by a shared output");
+
+        offer.rollover = Some(RolloverInfo {
+            contract_id: [1; 32],
+            counter_payout: 100000,
+        });
+        offer
+            .validate(SECP256K1, 86400 * 7, 86400 * 14)
+            .expect_err("Should not validate share given twice.");
+    }
+
+    #[test]
+    fn additional_payout_outputs_roundtrip() {
+        let mut accept: AcceptDlc =
+            serde_json::from_str(include_str!("./test_inputs/accept_msg.json")).unwrap();
+        let outputs = vec![PayoutOutput {
+            script_pubkey: accept.payout_spk.clone(),
+            serial_id: accept.payout_serial_id + 1,
+            share_bps: 2500,
+        }];
+        accept.additional_payout_outputs = AdditionalPayoutOutputs::from_payout_outputs(&outputs);
+
+        test_roundtrip(accept.clone());
+        assert_eq!(
+            outputs,
+            AdditionalPayoutOutputs::to_payout_outputs(&accept.additional_payout_outputs)
+        );
+        assert!(AdditionalPayoutOutputs::from_payout_outputs(&[]).is_none());
+    }
+
+    #[test]
+    fn offer_rollover_roundtrip_and_validation() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let mut offer: OfferDlc = serde_json::from_str(input).unwrap();
+        offer.funding_inputs.truncate(1);
+        offer.rollover = Some(RolloverInfo {
+            contract_id: [1; 32],
+            counter_payout: 100000,
+        });
+
+        test_roundtrip(offer.clone());
+        offer
+            .validate(SECP256K1, 86400 * 7, 86400 * 14)
+            .expect("to validate offer rolling over a contract");
+
+        let mut input = offer.funding_inputs[0].clone();
+        input.input_serial_id += 1;
+        offer.funding_inputs.push(input);
+        offer
+            .validate(SECP256K1, 86400 * 7, 86400 * 14)
+            .expect("to validate offer topping up a contract");
+
+        offer.funding_inputs.clear();
+        offer
+            .validate(SECP256K1, 86400 * 7, 86400 * 14)
+            .expect_err("Should not validate rollover without funding inputs.");
+    }
+
+    #[test]
+    fn signed_offer_roundtrip_and_verification() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let mut offer: OfferDlc = serde_json::from_str(input).unwrap();
+        let node_secret_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let node_id = PublicKey::from_secret_key(SECP256K1, &node_secret_key);
+        offer.sign(SECP256K1, &node_secret_key);
+
+        test_roundtrip(offer.clone());
+        offer
+            .verify_signature(SECP256K1, &node_id)
+            .expect("a valid signature");
+
+        let other_node_id =
+            PublicKey::from_secret_key(SECP256K1, &SecretKey::from_slice(&[2u8; 32]).unwrap());
+        offer
+            .verify_signature(SECP256K1, &other_node_id)
+            .expect_err("a signature from another node");
+
+        offer.offer_collateral += 1;
+        offer
+            .verify_signature(SECP256K1, &node_id)
+            .expect_err("a signature over a modified offer");
+
+        offer.signature = None;
+        offer
+            .verify_signature(SECP256K1, &node_id)
+            .expect_err("an unsigned offer");
+    }
+
+    #[test]
+    fn serialized_length_matches_encoding() {
+        let mut offer: OfferDlc =
+            serde_json::from_str(include_str!("./test_inputs/offer_msg.json")).unwrap();
+        offer.features = Some(features::Features::supported());
+        offer.unknown_tlvs = vec![UnknownTlv {
+            tlv_type: 1001,
+            value: vec![1; 300],
+        }];
+        let accept: AcceptDlc =
+            serde_json::from_str(include_str!("./test_inputs/accept_msg.json")).unwrap();
+        let sign: SignDlc =
+            serde_json::from_str(include_str!("./test_inputs/sign_msg.json")).unwrap();
+
+        for msg in vec![
+            Message::Offer(offer),
+            Message::Accept(accept),
+            Message::Sign(sign),
+        ] {
+            let encoded = msg.encode();
+            assert_eq!(encoded.len(), msg.serialized_length());
+            assert_eq!(encoded.len() + 2, msg.wire_length());
+            assert_eq!(encoded.len() > MAX_BUF_SIZE, msg.requires_segmentation());
+        }
+    }
+
+    #[test]
+    fn unknown_even_or_unordered_tlvs_are_rejected() {
+        let input = include_str!("./test_inputs/sign_msg.json");
+        let sign: SignDlc = serde_json::from_str(input).unwrap();
+
+        for tlvs in &[
+            vec![(2u64, 1u8)],
+            vec![(3, 1), (1, 1)],
+            vec![(1, 1), (1, 1)],
+        ] {
+            let mut buf = sign.encode();
+            for (tlv_type, value) in tlvs {
+                buf.extend_from_slice(&[*tlv_type as u8, 1, *value]);
+            }
+            SignDlc::read(&mut std::io::Cursor::new(&buf))
+                .expect_err("Should reject invalid TLV stream.");
+        }
+    }
+
     #[test]
     fn valid_offer_message_passes_validation() {
         let input = include_str!("./test_inputs/offer_msg.json");
@@ -5,31 +5,91 @@ use std::{
     fmt::Display,
     io::Cursor,
     sync::Mutex,
+    time::Instant,
 };
 
+use bitcoin::hashes::{sha256, Hash};
 use lightning::{
     ln::{
         msgs::{DecodeError, LightningError},
         peer_handler::CustomMessageHandler,
         wire::{CustomMessageReader, Type},
     },
-    util::ser::{Readable, Writeable, MAX_BUF_SIZE},
+    util::ser::{Readable, Writeable},
 };
 use secp256k1_zkp::PublicKey;
 
 use crate::{
-    segmentation::{get_segments, segment_reader::SegmentReader},
+    segmentation::{get_segments, segment_reader::SegmentReader, SegmentationLimits},
+    session::{unix_time_now, MessageDirection, PeerSession},
     Message, WireMessage,
 };
 
+// Number of messages that can be queued for each peer by default.
+const DEFAULT_MAX_QUEUED_MESSAGES_PER_PEER: usize = 100;
+
+// Number of recently received messages of each peer used to detect
+// retransmissions by default.
+const DEFAULT_DEDUPLICATION_WINDOW: usize = 32;
+
+/// Limits on the number of messages kept for each peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageQueueLimits {
+    /// The maximum number of received messages of a peer waiting to be
+    /// processed. Peers sending more messages are disconnected.
+    pub max_inbound_per_peer: usize,
+    /// The maximum number of messages sent to a peer that are kept until
+    /// acknowledged, the oldest ones being dropped first.
+    pub max_outbound_per_peer: usize,
+    /// The number of recently received messages of each peer remembered to
+    /// discard identical retransmissions.
+    pub deduplication_window: usize,
+}
+
+impl Default for MessageQueueLimits {
+    fn default() -> Self {
+        MessageQueueLimits {
+            max_inbound_per_peer: DEFAULT_MAX_QUEUED_MESSAGES_PER_PEER,
+            max_outbound_per_peer: DEFAULT_MAX_QUEUED_MESSAGES_PER_PEER,
+            deduplication_window: DEFAULT_DEDUPLICATION_WINDOW,
+        }
+    }
+}
+
+/// Persistent storage for the outbound messages of a [`MessageHandler`],
+/// enabling messages not yet acknowledged by a peer to survive restarts.
+pub trait OutboundMessageStore {
+    /// Replaces the stored outbound messages for the peer with the given id.
+    fn persist_outbound_messages(
+        &self,
+        node_id: &PublicKey,
+        messages: &[Message],
+    ) -> Result<(), std::io::Error>;
+    /// Returns the stored outbound messages of every peer.
+    fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, std::io::Error>;
+}
+
 /// MessageHandler is used to send and receive messages through the custom
 /// message handling mechanism of the LDK. It also handles message segmentation
 /// by splitting large messages when sending and re-constructing them when
 /// receiving.
+///
+/// Sent messages are kept until acknowledged by the peer, which happens
+/// implicitly when a message is received from it, so that they can be sent
+/// again using [`MessageHandler::peer_connected`] after a disconnection. They
+/// are persisted if an [`OutboundMessageStore`] is provided.
+///
+/// The messages exchanged with each peer are tracked in a [`PeerSession`].
 pub struct MessageHandler {
     msg_events: Mutex<VecDeque<(PublicKey, WireMessage)>>,
     msg_received: Mutex<Vec<(PublicKey, Message)>>,
     segment_readers: Mutex<HashMap<PublicKey, SegmentReader>>,
+    segmentation_limits: SegmentationLimits,
+    unacked_messages: Mutex<HashMap<PublicKey, Vec<Message>>>,
+    outbound_store: Option<Box<dyn OutboundMessageStore + Send + Sync>>,
+    queue_limits: MessageQueueLimits,
+    recently_received: Mutex<HashMap<PublicKey, VecDeque<sha256::Hash>>>,
+    sessions: Mutex<HashMap<PublicKey, PeerSession>>,
 }
 
 impl Default for MessageHandler {
@@ -41,13 +101,175 @@ impl Default for MessageHandler {
 impl MessageHandler {
     /// Creates a new instance of a [`MessageHandler`]
     pub fn new() -> Self {
+        Self::with_segmentation_limits(SegmentationLimits::default())
+    }
+
+    /// Creates a new instance of a [`MessageHandler`] applying the given
+    /// limits when reassembling segmented messages.
+    pub fn with_segmentation_limits(segmentation_limits: SegmentationLimits) -> Self {
         MessageHandler {
             msg_events: Mutex::new(VecDeque::new()),
             msg_received: Mutex::new(Vec::new()),
             segment_readers: Mutex::new(HashMap::new()),
+            segmentation_limits,
+            unacked_messages: Mutex::new(HashMap::new()),
+            outbound_store: None,
+            queue_limits: MessageQueueLimits::default(),
+            recently_received: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Applies the given limits to the messages kept for each peer.
+    pub fn with_queue_limits(mut self, queue_limits: MessageQueueLimits) -> Self {
+        self.queue_limits = queue_limits;
+        self
+    }
+
+    /// Persists outbound messages using the given store, loading the messages
+    /// that were not acknowledged before the handler was last stopped. These
+    /// are sent again when [`MessageHandler::peer_connected`] is called.
+    pub fn with_outbound_message_store(
+        mut self,
+        store: Box<dyn OutboundMessageStore + Send + Sync>,
+    ) -> Result<Self, std::io::Error> {
+        {
+            let mut unacked_messages = self.unacked_messages.lock().unwrap();
+            for (node_id, messages) in store.get_outbound_messages()? {
+                unacked_messages
+                    .entry(node_id)
+                    .or_default()
+                    .extend(messages);
+            }
+        }
+        self.outbound_store = Some(store);
+        Ok(self)
+    }
+
+    /// Queues again the messages sent to the given peer that it did not
+    /// acknowledge. Should be called when a connection with the peer is
+    /// established.
+    pub fn peer_connected(&self, node_id: &PublicKey) {
+        self.update_session(node_id, |s| s.record_connection_change(true));
+        let messages = self
+            .unacked_messages
+            .lock()
+            .unwrap()
+            .get(node_id)
+            .cloned()
+            .unwrap_or_default();
+        for msg in messages {
+            self.queue_message(*node_id, msg);
+        }
+    }
+
+    /// Discards the partially received segmented message of the given peer,
+    /// as its remaining chunks will not be received. Should be called when
+    /// the connection with the peer is lost.
+    pub fn peer_disconnected(&self, node_id: &PublicKey) {
+        self.segment_readers.lock().unwrap().remove(node_id);
+        self.update_session(node_id, |s| s.record_connection_change(false));
+    }
+
+    /// Returns the state of the session with the given peer, if any message
+    /// or connection event was recorded for it.
+    pub fn get_peer_session(&self, node_id: &PublicKey) -> Option<PeerSession> {
+        self.sessions.lock().unwrap().get(node_id).cloned()
+    }
+
+    /// Returns the state of the sessions with all known peers.
+    pub fn get_peer_sessions(&self) -> Vec<(PublicKey, PeerSession)> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(pk, s)| (*pk, s.clone()))
+            .collect()
+    }
+
+    /// Returns the ids of the peers that did not reply within `timeout`
+    /// seconds to a message expecting a reply.
+    pub fn get_stalled_peers(&self, timeout: u64) -> Vec<PublicKey> {
+        let now = unix_time_now();
+        self.sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, s)| s.is_stalled(now, timeout))
+            .map(|(pk, _)| *pk)
+            .collect()
+    }
+
+    fn update_session<F: FnOnce(&mut PeerSession)>(&self, node_id: &PublicKey, f: F) {
+        f(self.sessions.lock().unwrap().entry(*node_id).or_default());
+    }
+
+    /// Marks all the messages sent to the given peer as acknowledged, so that
+    /// they are not sent again.
+    pub fn acknowledge_messages(&self, node_id: &PublicKey) {
+        let mut unacked_messages = self.unacked_messages.lock().unwrap();
+        if unacked_messages.remove(node_id).is_some() {
+            self.persist_unacked_messages(node_id, &[]);
+            self.update_session(node_id, |s| s.record_acknowledgement());
+        }
+    }
+
+    /// Queues a message received from a peer for processing, discarding it if
+    /// it is identical to a recently received one.
+    fn push_received_message(&self, org: &PublicKey, msg: Message) -> Result<(), LightningError> {
+        self.acknowledge_messages(org);
+
+        let mut msg_received = self.msg_received.lock().unwrap();
+        if msg_received.iter().filter(|(pk, _)| pk == org).count()
+            >= self.queue_limits.max_inbound_per_peer
+        {
+            return Err(LightningError {
+                err: "Too many messages queued from peer.".to_string(),
+                action: lightning::ln::msgs::ErrorAction::DisconnectPeer { msg: None },
+            });
+        }
+
+        let hash = sha256::Hash::hash(&msg.encode_with_type());
+        let mut recently_received = self.recently_received.lock().unwrap();
+        let recent = recently_received.entry(*org).or_default();
+        if recent.contains(&hash) {
+            return Ok(());
+        }
+        recent.push_back(hash);
+        if recent.len() > self.queue_limits.deduplication_window {
+            recent.pop_front();
+        }
+
+        self.update_session(org, |s| s.record_message(&msg, MessageDirection::Received));
+        msg_received.push((*org, msg));
+        Ok(())
+    }
+
+    fn persist_unacked_messages(&self, node_id: &PublicKey, messages: &[Message]) {
+        if let Some(store) = &self.outbound_store {
+            // On failure the messages are still kept in memory, and all of
+            // them are persisted again on the next update for this peer.
+            let _ = store.persist_outbound_messages(node_id, messages);
         }
     }
 
+    /// Discards the partially received segmented messages whose chunks were
+    /// not all received within the reassembly timeout, returning the ids of
+    /// the peers that sent them. Should be called periodically.
+    pub fn remove_timed_out_segments(&self) -> Vec<PublicKey> {
+        let now = Instant::now();
+        let mut segment_readers = self.segment_readers.lock().unwrap();
+        let timed_out = segment_readers
+            .iter()
+            .filter(|(_, reader)| reader.has_timed_out(now))
+            .map(|(pk, _)| *pk)
+            .collect::<Vec<_>>();
+        for pk in &timed_out {
+            segment_readers.remove(pk);
+        }
+        timed_out
+    }
+
     /// Returns the messages received by the message handler and empty the
     /// receiving buffer.
     pub fn get_and_clear_received_messages(&self) -> Vec<(PublicKey, Message)> {
@@ -60,7 +282,40 @@ impl MessageHandler {
     /// sent right away, but only when the LDK
     /// [`lightning::ln::peer_handler::PeerManager::process_events`] is next called.
     pub fn send_message(&self, node_id: PublicKey, msg: Message) {
-        if msg.serialized_length() > MAX_BUF_SIZE {
+        self.send_messages(node_id, &[msg]);
+    }
+
+    /// Send several messages to the peer with given node id, in order. The
+    /// unacknowledged messages of the peer are persisted only once for the
+    /// whole batch.
+    pub fn send_messages(&self, node_id: PublicKey, msgs: &[Message]) {
+        {
+            let mut unacked_messages = self.unacked_messages.lock().unwrap();
+            let messages = unacked_messages.entry(node_id).or_default();
+            let mut updated = false;
+            for msg in msgs {
+                let encoded = msg.encode_with_type();
+                if messages.iter().any(|m| m.encode_with_type() == encoded) {
+                    continue;
+                }
+                messages.push(msg.clone());
+                if messages.len() > self.queue_limits.max_outbound_per_peer {
+                    messages.remove(0);
+                }
+                self.update_session(&node_id, |s| s.record_message(msg, MessageDirection::Sent));
+                updated = true;
+            }
+            if updated {
+                self.persist_unacked_messages(&node_id, messages);
+            }
+        }
+        for msg in msgs {
+            self.queue_message(node_id, msg.clone());
+        }
+    }
+
+    fn queue_message(&self, node_id: PublicKey, msg: Message) {
+        if msg.requires_segmentation() {
             let (seg_start, seg_chunks) = get_segments(msg.encode(), msg.type_id());
             let mut msg_events = self.msg_events.lock().unwrap();
             msg_events.push_back((node_id, WireMessage::SegmentStart(seg_start)));
@@ -93,7 +348,7 @@ macro_rules! handle_read_dlc_messages {
     }};
 }
 
-fn read_dlc_message<R: ::std::io::Read>(
+pub(crate) fn read_dlc_message<R: ::std::io::Read>(
     msg_type: u16,
     mut buffer: &mut R,
 ) -> Result<Option<WireMessage>, DecodeError> {
@@ -114,7 +369,18 @@ fn read_dlc_message<R: ::std::io::Read>(
         (RENEW_CHANNEL_ACCEPT_TYPE, RenewAccept),
         (RENEW_CHANNEL_CONFIRM_TYPE, RenewConfirm),
         (RENEW_CHANNEL_FINALIZE_TYPE, RenewFinalize),
-        (COLLABORATIVE_CLOSE_OFFER_TYPE, CollaborativeCloseOffer)
+        (COLLABORATIVE_CLOSE_OFFER_TYPE, CollaborativeCloseOffer),
+        (MULTI_PARTY_OFFER_TYPE, MultiPartyOffer),
+        (MULTI_PARTY_ACCEPT_TYPE, MultiPartyAccept),
+        (MULTI_PARTY_SETUP_TYPE, MultiPartySetup),
+        (MULTI_PARTY_SIGN_TYPE, MultiPartySign),
+        (MULTI_PARTY_FINALIZE_TYPE, MultiPartyFinalize),
+        (MULTI_PARTY_FUNDING_TYPE, MultiPartyFunding),
+        (NOVATION_OFFER_TYPE, NovationOffer),
+        (NOVATION_ACCEPT_TYPE, NovationAccept),
+        (NOVATION_REQUEST_TYPE, NovationRequest),
+        (NOVATION_CONFIRM_TYPE, NovationConfirm),
+        (NOVATION_FINALIZE_TYPE, NovationFinalize)
     )
 }
 
@@ -150,9 +416,24 @@ impl CustomMessageHandler for MessageHandler {
         org: &PublicKey,
     ) -> Result<(), LightningError> {
         let mut segment_readers = self.segment_readers.lock().unwrap();
+
+        if let WireMessage::SegmentStart(_) = msg {
+            let nb_pending = segment_readers
+                .iter()
+                .filter(|(pk, reader)| *pk != org && reader.expecting_chunk())
+                .count();
+            if nb_pending >= self.segmentation_limits.max_pending_reassemblies {
+                return Err(LightningError {
+                    err: "Too many segmented messages being reassembled.".to_string(),
+                    action: lightning::ln::msgs::ErrorAction::DisconnectPeer { msg: None },
+                });
+            }
+        }
+
+        let segmentation_limits = self.segmentation_limits;
         let segment_reader = segment_readers
             .entry(*org)
-            .or_insert_with(SegmentReader::new);
+            .or_insert_with(|| SegmentReader::with_limits(segmentation_limits));
 
         if segment_reader.expecting_chunk() {
             match msg {
@@ -172,7 +453,7 @@ impl CustomMessageHandler for MessageHandler {
                             })?
                             .expect("to have a message")
                         {
-                            self.msg_received.lock().unwrap().push((*org, m));
+                            self.push_received_message(org, m)?;
                         } else {
                             return Err(to_ln_error(
                                 "Unexpected message type",
@@ -191,7 +472,7 @@ impl CustomMessageHandler for MessageHandler {
         }
 
         match msg {
-            WireMessage::Message(m) => self.msg_received.lock().unwrap().push((*org, m)),
+            WireMessage::Message(m) => self.push_received_message(org, m)?,
             WireMessage::SegmentStart(s) => segment_reader
                 .process_segment_start(s)
                 .map_err(|e| to_ln_error(e, "Error processing segment start"))?,
@@ -317,6 +598,37 @@ mod tests {
         assert!(handler.msg_events.lock().unwrap().len() > 1);
     }
 
+    #[test]
+    fn send_messages_in_batch_test() {
+        let offer: OfferDlc =
+            serde_json::from_str(include_str!("./test_inputs/offer_msg.json")).unwrap();
+        let mut other_offer = offer.clone();
+        other_offer.temporary_contract_id = [1; 32];
+        let store = TestStore::default();
+        let handler = MessageHandler::new()
+            .with_outbound_message_store(Box::new(store.clone()))
+            .unwrap();
+
+        handler.send_messages(
+            some_pk(),
+            &[Message::Offer(offer), Message::Offer(other_offer)],
+        );
+
+        let pending = handler.get_and_clear_pending_msg();
+        assert_eq!(2, pending.len());
+        match &pending[1].1 {
+            WireMessage::Message(Message::Offer(o)) => {
+                assert_eq!([1; 32], o.temporary_contract_id)
+            }
+            _ => panic!("Expected an offer message"),
+        }
+        assert_eq!(2, store.messages.lock().unwrap()[&some_pk()].len());
+        assert_eq!(
+            2,
+            handler.get_peer_session(&some_pk()).unwrap().history.len()
+        );
+    }
+
     #[test]
     fn is_empty_after_clearing_msg_events_test() {
         let input = include_str!("./test_inputs/accept_msg.json");
@@ -348,4 +660,211 @@ mod tests {
             panic!("Expected an accept message");
         }
     }
+
+    #[test]
+    fn peer_disconnection_discards_segments_test() {
+        let input1 = include_str!("./test_inputs/segment_start_msg.json");
+        let input2 = include_str!("./test_inputs/segment_chunk_msg.json");
+        let segment_start: SegmentStart = serde_json::from_str(input1).unwrap();
+        let segment_chunk: SegmentChunk = serde_json::from_str(input2).unwrap();
+
+        let handler = MessageHandler::new();
+        handler
+            .handle_custom_message(WireMessage::SegmentStart(segment_start), &some_pk())
+            .expect("to be able to process segment start");
+        handler.peer_disconnected(&some_pk());
+        handler
+            .handle_custom_message(WireMessage::SegmentChunk(segment_chunk), &some_pk())
+            .expect_err("the segment start to have been discarded");
+        assert!(handler.get_and_clear_received_messages().is_empty());
+    }
+
+    #[test]
+    fn too_many_pending_reassemblies_fails_test() {
+        let input = include_str!("./test_inputs/segment_start_msg.json");
+        let segment_start: SegmentStart = serde_json::from_str(input).unwrap();
+        let other_pk = PublicKey::from_secret_key(
+            SECP256K1,
+            &secp256k1_zkp::SecretKey::from_slice(&[2; 32]).unwrap(),
+        );
+
+        let handler = MessageHandler::with_segmentation_limits(SegmentationLimits {
+            max_pending_reassemblies: 1,
+            ..Default::default()
+        });
+        handler
+            .handle_custom_message(WireMessage::SegmentStart(segment_start.clone()), &some_pk())
+            .expect("to be able to process segment start");
+        handler
+            .handle_custom_message(WireMessage::SegmentStart(segment_start), &other_pk)
+            .expect_err("should not start reassembling a second message");
+    }
+
+    #[test]
+    fn remove_timed_out_segments_test() {
+        let input = include_str!("./test_inputs/segment_start_msg.json");
+        let segment_start: SegmentStart = serde_json::from_str(input).unwrap();
+
+        let handler = MessageHandler::with_segmentation_limits(SegmentationLimits {
+            reassembly_timeout: std::time::Duration::from_secs(0),
+            ..Default::default()
+        });
+        handler
+            .handle_custom_message(WireMessage::SegmentStart(segment_start), &some_pk())
+            .expect("to be able to process segment start");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        assert_eq!(vec![some_pk()], handler.remove_timed_out_segments());
+        assert!(handler.segment_readers.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn duplicate_messages_are_discarded_test() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let offer: OfferDlc = serde_json::from_str(input).unwrap();
+        let handler = MessageHandler::new();
+
+        for _ in 0..2 {
+            handler
+                .handle_custom_message(
+                    WireMessage::Message(Message::Offer(offer.clone())),
+                    &some_pk(),
+                )
+                .expect("to be able to handle the message");
+        }
+
+        assert_eq!(1, handler.get_and_clear_received_messages().len());
+    }
+
+    #[test]
+    fn too_many_inbound_messages_fails_test() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let mut offer: OfferDlc = serde_json::from_str(input).unwrap();
+        let handler = MessageHandler::new().with_queue_limits(MessageQueueLimits {
+            max_inbound_per_peer: 1,
+            ..Default::default()
+        });
+
+        handler
+            .handle_custom_message(
+                WireMessage::Message(Message::Offer(offer.clone())),
+                &some_pk(),
+            )
+            .expect("to be able to handle the message");
+        offer.temporary_contract_id[0] ^= 1;
+        handler
+            .handle_custom_message(WireMessage::Message(Message::Offer(offer)), &some_pk())
+            .expect_err("should not queue more messages than the limit");
+    }
+
+    #[test]
+    fn outbound_messages_are_capped_test() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let mut offer: OfferDlc = serde_json::from_str(input).unwrap();
+        let handler = MessageHandler::new().with_queue_limits(MessageQueueLimits {
+            max_outbound_per_peer: 2,
+            ..Default::default()
+        });
+
+        for i in 0..3 {
+            offer.temporary_contract_id[0] = i;
+            handler.send_message(some_pk(), Message::Offer(offer.clone()));
+        }
+        handler.send_message(some_pk(), Message::Offer(offer));
+
+        assert_eq!(
+            2,
+            handler.unacked_messages.lock().unwrap()[&some_pk()].len()
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct TestStore {
+        messages: std::sync::Arc<Mutex<HashMap<PublicKey, Vec<Message>>>>,
+    }
+
+    impl OutboundMessageStore for TestStore {
+        fn persist_outbound_messages(
+            &self,
+            node_id: &PublicKey,
+            messages: &[Message],
+        ) -> Result<(), std::io::Error> {
+            self.messages
+                .lock()
+                .unwrap()
+                .insert(*node_id, messages.to_vec());
+            Ok(())
+        }
+
+        fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, std::io::Error> {
+            Ok(self
+                .messages
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(pk, msgs)| (*pk, msgs.clone()))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn unacked_messages_are_resent_after_restart_test() {
+        let input = include_str!("./test_inputs/offer_msg.json");
+        let offer: OfferDlc = serde_json::from_str(input).unwrap();
+        let store = TestStore::default();
+
+        let handler = MessageHandler::new()
+            .with_outbound_message_store(Box::new(store.clone()))
+            .unwrap();
+        handler.send_message(some_pk(), Message::Offer(offer.clone()));
+        handler.get_and_clear_pending_msg();
+
+        let handler = MessageHandler::new()
+            .with_outbound_message_store(Box::new(store.clone()))
+            .unwrap();
+        assert!(!handler.has_pending_messages());
+        handler.peer_connected(&some_pk());
+        assert_eq!(1, handler.get_and_clear_pending_msg().len());
+
+        handler
+            .handle_custom_message(WireMessage::Message(Message::Offer(offer)), &some_pk())
+            .expect("to be able to handle the message");
+        handler.peer_connected(&some_pk());
+        assert!(!handler.has_pending_messages());
+        assert!(store.messages.lock().unwrap()[&some_pk()].is_empty());
+    }
+
+    #[test]
+    fn peer_session_is_tracked_test() {
+        let offer: OfferDlc =
+            serde_json::from_str(include_str!("./test_inputs/offer_msg.json")).unwrap();
+        let accept: AcceptDlc =
+            serde_json::from_str(include_str!("./test_inputs/accept_msg.json")).unwrap();
+        let handler = MessageHandler::new();
+        assert!(handler.get_peer_session(&some_pk()).is_none());
+
+        handler.peer_connected(&some_pk());
+        handler.send_message(some_pk(), Message::Offer(offer));
+        let session = handler.get_peer_session(&some_pk()).unwrap();
+        assert!(session.connected);
+        let awaiting = session.awaiting_reply().expect("to await an accept");
+        assert_eq!(crate::OFFER_TYPE, awaiting.msg_type);
+        assert!(!awaiting.acknowledged);
+        assert!(handler.get_stalled_peers(3600).is_empty());
+
+        handler
+            .handle_custom_message(WireMessage::Message(Message::Accept(accept)), &some_pk())
+            .unwrap();
+        let session = handler.get_peer_session(&some_pk()).unwrap();
+        assert!(session.last_sent().unwrap().acknowledged);
+        assert!(session.last_acknowledged.is_some());
+        assert!(session.awaiting_reply().is_none());
+        assert_eq!(
+            crate::ACCEPT_TYPE,
+            session.awaiting_local_reply().unwrap().msg_type
+        );
+
+        handler.peer_disconnected(&some_pk());
+        assert!(!handler.get_peer_session(&some_pk()).unwrap().connected);
+    }
 }
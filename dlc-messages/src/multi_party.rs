@@ -0,0 +1,359 @@
+//! Contains messages used for the establishment of DLCs between more than two
+//! parties. A coordinator offers the contract to each participant, collects
+//! their parameters and signatures and distributes them to all participants.
+//! The establishment goes as follows:
+//! * the coordinator sends a [`MultiPartyOffer`] to each participant,
+//! * each participant replies with a [`MultiPartyAccept`],
+//! * the coordinator sends a [`MultiPartySetup`] with the parameters of all
+//!   parties, from which every party builds the contract transactions,
+//! * each participant replies with a [`MultiPartySign`] containing its CET
+//!   adaptor signatures and refund signature,
+//! * the coordinator sends a [`MultiPartyFinalize`] with the signatures of all
+//!   parties,
+//! * each participant replies with a [`MultiPartyFunding`] containing the
+//!   signatures of its funding inputs, which the coordinator combines to
+//!   broadcast the fund transaction.
+//!
+//! Once the fund transaction is confirmed, every party holds the signatures
+//! required to close the contract on its own, either with the CET of the
+//! outcome attested by the oracle or with the refund transaction after the
+//! refund locktime.
+
+use bitcoin::Script;
+use dlc::Error;
+use lightning::ln::msgs::DecodeError;
+use lightning::util::ser::{Readable, Writeable, Writer};
+use secp256k1_zkp::{ecdsa::Signature, PublicKey};
+
+use crate::{
+    oracle_msgs::OracleAnnouncement,
+    ser_impls::{read_as_tlv, write_as_tlv},
+    CetAdaptorSignatures, FundingInput, FundingSignatures,
+};
+
+/// The parameters of a party to a multi party contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartyParams {
+    /// The public key of the party in the n-of-n funding output.
+    pub funding_pubkey: PublicKey,
+    /// The SPK where the party will receive their payout.
+    pub payout_spk: Script,
+    /// Serial id to order CET outputs.
+    pub payout_serial_id: u64,
+    /// Inputs used by the party to fund the contract.
+    pub funding_inputs: Vec<FundingInput>,
+    /// The SPK where the party will receive their change.
+    pub change_spk: Script,
+    /// Serial id to order funding transaction outputs.
+    pub change_serial_id: u64,
+}
+
+impl_dlc_writeable!(MultiPartyParams, {
+    (funding_pubkey, writeable),
+    (payout_spk, writeable),
+    (payout_serial_id, writeable),
+    (funding_inputs, vec),
+    (change_spk, writeable),
+    (change_serial_id, writeable)
+});
+
+/// The payouts of every party for an outcome of a multi party contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartyOutcome {
+    /// The outcome as attested by the oracle.
+    pub outcome: String,
+    /// The payout of each party, the coordinator first.
+    pub payouts: Vec<u64>,
+}
+
+impl_dlc_writeable!(MultiPartyOutcome, { (outcome, string), (payouts, vec) });
+
+/// Offer of a contract between the coordinator and several participants, sent
+/// by the coordinator to each of them.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartyOffer {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The identifier of the chain on which the contract will be settled.
+    pub chain_hash: [u8; 32],
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// Temporary contract id to identify the contract.
+    pub temporary_contract_id: [u8; 32],
+    /// The node ids of the participants, excluding the coordinator.
+    pub participants: Vec<PublicKey>,
+    /// The index of the recipient of the offer among the parties, the
+    /// coordinator being at index zero.
+    pub party_index: u16,
+    /// The collateral of each party, the coordinator first.
+    pub collaterals: Vec<u64>,
+    /// The possible outcomes of the contract and their payouts.
+    pub outcomes: Vec<MultiPartyOutcome>,
+    /// The announcement of the oracle attesting to the outcome.
+    pub oracle_announcement: OracleAnnouncement,
+    /// The parameters of the coordinator.
+    pub offer_params: MultiPartyParams,
+    /// Serial id to order funding transaction outputs.
+    pub fund_output_serial_id: u64,
+    /// The fee rate to use to compute transaction fees for this contract.
+    pub fee_rate_per_vb: u64,
+    /// The lock time for the CETs.
+    pub cet_locktime: u32,
+    /// The lock time for the refund transactions.
+    pub refund_locktime: u32,
+}
+
+impl_dlc_writeable!(MultiPartyOffer, {
+    (protocol_version, writeable),
+    (chain_hash, writeable),
+    (temporary_contract_id, writeable),
+    (participants, vec),
+    (party_index, writeable),
+    (collaterals, vec),
+    (outcomes, vec),
+    (oracle_announcement, {cb_writeable, write_as_tlv, read_as_tlv}),
+    (offer_params, writeable),
+    (fund_output_serial_id, writeable),
+    (fee_rate_per_vb, writeable),
+    (cet_locktime, writeable),
+    (refund_locktime, writeable)
+});
+
+impl MultiPartyOffer {
+    /// Returns the number of parties to the contract, including the
+    /// coordinator.
+    pub fn nb_parties(&self) -> usize {
+        self.participants.len() + 1
+    }
+
+    /// Returns the total collateral locked in the contract.
+    pub fn get_total_collateral(&self) -> u64 {
+        self.collaterals.iter().sum()
+    }
+
+    /// Returns whether the message satisfies validity requirements, without
+    /// verifying the oracle announcement it contains.
+    pub fn validate(
+        &self,
+        min_timeout_interval: u32,
+        max_timeout_interval: u32,
+    ) -> Result<(), Error> {
+        let nb_parties = self.nb_parties();
+        if nb_parties < 2
+            || nb_parties > dlc::multi_party::MAX_PARTIES
+            || self.party_index as usize >= nb_parties
+            || self.collaterals.len() != nb_parties
+            || self.outcomes.is_empty()
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        let total_collateral = self
+            .collaterals
+            .iter()
+            .try_fold(0u64, |acc, x| acc.checked_add(*x))
+            .ok_or(Error::InvalidArgument)?;
+        for outcome in &self.outcomes {
+            let total_payout = outcome
+                .payouts
+                .iter()
+                .try_fold(0u64, |acc, x| acc.checked_add(*x));
+            if outcome.payouts.len() != nb_parties || total_payout != Some(total_collateral) {
+                return Err(Error::InvalidArgument);
+            }
+        }
+
+        let maturity_date = self.oracle_announcement.oracle_event.event_maturity_epoch;
+        let valid_dates = self.cet_locktime <= maturity_date
+            && maturity_date + min_timeout_interval <= self.refund_locktime
+            && self.refund_locktime <= maturity_date + max_timeout_interval;
+        if !valid_dates {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(())
+    }
+}
+
+/// Parameters of a participant accepting a [`MultiPartyOffer`], sent to the
+/// coordinator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartyAccept {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The temporary contract id for the contract.
+    pub temporary_contract_id: [u8; 32],
+    /// The parameters of the accepting participant.
+    pub accept_params: MultiPartyParams,
+}
+
+impl_dlc_writeable!(MultiPartyAccept, {
+    (protocol_version, writeable),
+    (temporary_contract_id, writeable),
+    (accept_params, writeable)
+});
+
+/// The parameters of all parties, sent by the coordinator to each participant
+/// once every participant accepted the offer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartySetup {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The temporary contract id for the contract.
+    pub temporary_contract_id: [u8; 32],
+    /// The parameters of each party, the coordinator first.
+    pub party_params: Vec<MultiPartyParams>,
+}
+
+impl_dlc_writeable!(MultiPartySetup, {
+    (protocol_version, writeable),
+    (temporary_contract_id, writeable),
+    (party_params, vec)
+});
+
+/// The signatures of a participant for the contract transactions, sent to the
+/// coordinator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartySign {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract referred to by this message.
+    pub contract_id: [u8; 32],
+    /// The CET adaptor signatures of the participant.
+    pub cet_adaptor_signatures: CetAdaptorSignatures,
+    /// The refund signature of the participant.
+    pub refund_signature: Signature,
+}
+
+impl_dlc_writeable!(MultiPartySign, {
+    (protocol_version, writeable),
+    (contract_id, writeable),
+    (cet_adaptor_signatures, writeable),
+    (refund_signature, writeable)
+});
+
+/// The signatures of all parties for the contract transactions, sent by the
+/// coordinator to each participant once they were all collected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartyFinalize {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract referred to by this message.
+    pub contract_id: [u8; 32],
+    /// The CET adaptor signatures of each party, the coordinator first.
+    pub cet_adaptor_signatures: Vec<CetAdaptorSignatures>,
+    /// The refund signature of each party, the coordinator first.
+    pub refund_signatures: Vec<Signature>,
+}
+
+impl_dlc_writeable!(MultiPartyFinalize, {
+    (protocol_version, writeable),
+    (contract_id, writeable),
+    (cet_adaptor_signatures, vec),
+    (refund_signatures, vec)
+});
+
+/// The signatures of the funding inputs of a participant, sent to the
+/// coordinator once the participant verified the signatures of all parties.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct MultiPartyFunding {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract referred to by this message.
+    pub contract_id: [u8; 32],
+    /// The signatures of the funding inputs of the participant.
+    pub funding_signatures: FundingSignatures,
+}
+
+impl_dlc_writeable!(MultiPartyFunding, {
+    (protocol_version, writeable),
+    (contract_id, writeable),
+    (funding_signatures, writeable)
+});
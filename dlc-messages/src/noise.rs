@@ -0,0 +1,534 @@
+//! Authenticated encryption of messages exchanged over transports other than
+//! the Lightning Network peer connections (raw TCP, HTTP, ...).
+//!
+//! The handshake follows the `Noise_XK` construction used by BOLT 8, keyed by
+//! the node ids of the peers, with a distinct prologue so that handshakes
+//! cannot be confused with Lightning ones. As DLC messages can be large,
+//! frames use a four bytes length prefix instead of the two bytes of BOLT 8.
+
+use std::fmt;
+use std::io::Read;
+
+use bitcoin::hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use secp256k1_zkp::ecdh::SharedSecret;
+use secp256k1_zkp::rand::thread_rng;
+use secp256k1_zkp::{PublicKey, Secp256k1, SecretKey};
+
+const PROTOCOL_NAME: &[u8] = b"Noise_XK_secp256k1_ChaChaPoly_SHA256";
+const PROLOGUE: &[u8] = b"dlc";
+const HANDSHAKE_VERSION: u8 = 0;
+const TAG_SIZE: usize = 16;
+const KEY_ROTATION_INTERVAL: u64 = 1000;
+
+/// The size of the first and second handshake acts.
+pub const ACT_ONE_TWO_SIZE: usize = 50;
+
+/// The size of the third handshake act.
+pub const ACT_THREE_SIZE: usize = 66;
+
+/// The size of the encrypted length prefix of each frame.
+pub const LENGTH_HEADER_SIZE: usize = 4 + TAG_SIZE;
+
+/// The maximum size of a message sent in a single frame.
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// An error that can occur during the handshake or when decrypting frames.
+#[derive(Debug)]
+pub enum NoiseError {
+    /// The handshake act has an unknown version or an invalid size.
+    InvalidAct,
+    /// A public key received from the peer is invalid.
+    InvalidKey,
+    /// Authenticated decryption failed.
+    DecryptionFailed,
+    /// The message exceeds [`MAX_MESSAGE_SIZE`].
+    MessageTooLarge,
+    /// An error occurred while reading from the underlying transport.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for NoiseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NoiseError::InvalidAct => write!(f, "Invalid handshake act"),
+            NoiseError::InvalidKey => write!(f, "Invalid public key"),
+            NoiseError::DecryptionFailed => write!(f, "Decryption failed"),
+            NoiseError::MessageTooLarge => write!(f, "Message too large"),
+            NoiseError::Io(e) => write!(f, "IO error {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NoiseError {}
+
+impl From<std::io::Error> for NoiseError {
+    fn from(e: std::io::Error) -> NoiseError {
+        NoiseError::Io(e)
+    }
+}
+
+fn hkdf(salt: &[u8; 32], ikm: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let mut engine = HmacEngine::<sha256::Hash>::new(salt);
+    engine.input(ikm);
+    let prk = Hmac::from_engine(engine);
+
+    let mut engine = HmacEngine::<sha256::Hash>::new(&prk[..]);
+    engine.input(&[1]);
+    let t1 = Hmac::from_engine(engine);
+
+    let mut engine = HmacEngine::<sha256::Hash>::new(&prk[..]);
+    engine.input(&t1[..]);
+    engine.input(&[2]);
+    let t2 = Hmac::from_engine(engine);
+
+    (t1.into_inner(), t2.into_inner())
+}
+
+fn get_nonce(n: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&n.to_le_bytes());
+    nonce
+}
+
+fn encrypt_with_ad(key: &[u8; 32], n: u64, ad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(
+            Nonce::from_slice(&get_nonce(n)),
+            Payload {
+                msg: plaintext,
+                aad: ad,
+            },
+        )
+        .expect("encryption to succeed")
+}
+
+fn decrypt_with_ad(
+    key: &[u8; 32],
+    n: u64,
+    ad: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, NoiseError> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(
+            Nonce::from_slice(&get_nonce(n)),
+            Payload {
+                msg: ciphertext,
+                aad: ad,
+            },
+        )
+        .map_err(|_| NoiseError::DecryptionFailed)
+}
+
+fn ecdh(public_key: &PublicKey, secret_key: &SecretKey) -> [u8; 32] {
+    SharedSecret::new(public_key, secret_key).secret_bytes()
+}
+
+struct SymmetricState {
+    ck: [u8; 32],
+    h: sha256::Hash,
+    temp_k: [u8; 32],
+}
+
+impl SymmetricState {
+    fn new(responder_static_key: &PublicKey) -> Self {
+        let h = sha256::Hash::hash(PROTOCOL_NAME);
+        let ck = h.into_inner();
+        let mut state = SymmetricState {
+            ck,
+            h,
+            temp_k: [0; 32],
+        };
+        state.mix_hash(PROLOGUE);
+        state.mix_hash(&responder_static_key.serialize());
+        state
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&self.h[..]);
+        engine.input(data);
+        self.h = sha256::Hash::from_engine(engine);
+    }
+
+    fn mix_key(&mut self, shared_secret: &[u8; 32]) {
+        let (ck, temp_k) = hkdf(&self.ck, shared_secret);
+        self.ck = ck;
+        self.temp_k = temp_k;
+    }
+
+    fn encrypt_and_hash(&mut self, n: u64, plaintext: &[u8]) -> Vec<u8> {
+        let c = encrypt_with_ad(&self.temp_k, n, &self.h[..], plaintext);
+        self.mix_hash(&c);
+        c
+    }
+
+    fn decrypt_and_hash(&mut self, n: u64, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let p = decrypt_with_ad(&self.temp_k, n, &self.h[..], ciphertext)?;
+        self.mix_hash(ciphertext);
+        Ok(p)
+    }
+}
+
+fn parse_act(act: &[u8], expected_size: usize) -> Result<&[u8], NoiseError> {
+    if act.len() != expected_size || act[0] != HANDSHAKE_VERSION {
+        return Err(NoiseError::InvalidAct);
+    }
+    Ok(&act[1..])
+}
+
+/// State of the handshake of the initiator of a connection.
+pub struct OutboundHandshake {
+    state: SymmetricState,
+    local_secret_key: SecretKey,
+    ephemeral_key: SecretKey,
+    remote_node_id: PublicKey,
+}
+
+impl OutboundHandshake {
+    /// Starts a handshake with the node with the given id, returning the
+    /// first act to send to it.
+    pub fn new(
+        local_secret_key: &SecretKey,
+        remote_node_id: &PublicKey,
+    ) -> (Self, [u8; ACT_ONE_TWO_SIZE]) {
+        Self::with_ephemeral_key(
+            local_secret_key,
+            remote_node_id,
+            SecretKey::new(&mut thread_rng()),
+        )
+    }
+
+    /// Same as [`OutboundHandshake::new`] using the given ephemeral key,
+    /// which must never be reused.
+    pub fn with_ephemeral_key(
+        local_secret_key: &SecretKey,
+        remote_node_id: &PublicKey,
+        ephemeral_key: SecretKey,
+    ) -> (Self, [u8; ACT_ONE_TWO_SIZE]) {
+        let secp = Secp256k1::signing_only();
+        let mut state = SymmetricState::new(remote_node_id);
+        let ephemeral_pubkey = PublicKey::from_secret_key(&secp, &ephemeral_key);
+        state.mix_hash(&ephemeral_pubkey.serialize());
+        state.mix_key(&ecdh(remote_node_id, &ephemeral_key));
+        let c = state.encrypt_and_hash(0, &[]);
+
+        let mut act_one = [0u8; ACT_ONE_TWO_SIZE];
+        act_one[0] = HANDSHAKE_VERSION;
+        act_one[1..34].copy_from_slice(&ephemeral_pubkey.serialize());
+        act_one[34..].copy_from_slice(&c);
+
+        (
+            OutboundHandshake {
+                state,
+                local_secret_key: *local_secret_key,
+                ephemeral_key,
+                remote_node_id: *remote_node_id,
+            },
+            act_one,
+        )
+    }
+
+    /// Processes the second act received from the responder, returning the
+    /// third act to send to it and the established session.
+    pub fn process_act_two(
+        mut self,
+        act_two: &[u8],
+    ) -> Result<([u8; ACT_THREE_SIZE], NoiseSession), NoiseError> {
+        let secp = Secp256k1::signing_only();
+        let act = parse_act(act_two, ACT_ONE_TWO_SIZE)?;
+        let remote_ephemeral =
+            PublicKey::from_slice(&act[..33]).map_err(|_| NoiseError::InvalidKey)?;
+        self.state.mix_hash(&act[..33]);
+        self.state
+            .mix_key(&ecdh(&remote_ephemeral, &self.ephemeral_key));
+        self.state.decrypt_and_hash(0, &act[33..])?;
+
+        let local_pubkey = PublicKey::from_secret_key(&secp, &self.local_secret_key);
+        let c = self.state.encrypt_and_hash(1, &local_pubkey.serialize());
+        self.state
+            .mix_key(&ecdh(&remote_ephemeral, &self.local_secret_key));
+        let t = encrypt_with_ad(&self.state.temp_k, 0, &self.state.h[..], &[]);
+        let (sending_key, receiving_key) = hkdf(&self.state.ck, &[]);
+
+        let mut act_three = [0u8; ACT_THREE_SIZE];
+        act_three[0] = HANDSHAKE_VERSION;
+        act_three[1..50].copy_from_slice(&c);
+        act_three[50..].copy_from_slice(&t);
+
+        Ok((
+            act_three,
+            NoiseSession::new(
+                self.remote_node_id,
+                self.state.ck,
+                sending_key,
+                receiving_key,
+            ),
+        ))
+    }
+}
+
+/// State of the handshake of the responder of a connection.
+pub struct InboundHandshake {
+    state: SymmetricState,
+    local_secret_key: SecretKey,
+    ephemeral_key: SecretKey,
+    remote_ephemeral: Option<PublicKey>,
+}
+
+impl InboundHandshake {
+    /// Creates the state used to respond to a handshake.
+    pub fn new(local_secret_key: &SecretKey) -> Self {
+        Self::with_ephemeral_key(local_secret_key, SecretKey::new(&mut thread_rng()))
+    }
+
+    /// Same as [`InboundHandshake::new`] using the given ephemeral key,
+    /// which must never be reused.
+    pub fn with_ephemeral_key(local_secret_key: &SecretKey, ephemeral_key: SecretKey) -> Self {
+        let secp = Secp256k1::signing_only();
+        InboundHandshake {
+            state: SymmetricState::new(&PublicKey::from_secret_key(&secp, local_secret_key)),
+            local_secret_key: *local_secret_key,
+            ephemeral_key,
+            remote_ephemeral: None,
+        }
+    }
+
+    /// Processes the first act received from the initiator, returning the
+    /// second act to send to it.
+    pub fn process_act_one(
+        &mut self,
+        act_one: &[u8],
+    ) -> Result<[u8; ACT_ONE_TWO_SIZE], NoiseError> {
+        if self.remote_ephemeral.is_some() {
+            return Err(NoiseError::InvalidAct);
+        }
+        let secp = Secp256k1::signing_only();
+        let act = parse_act(act_one, ACT_ONE_TWO_SIZE)?;
+        let remote_ephemeral =
+            PublicKey::from_slice(&act[..33]).map_err(|_| NoiseError::InvalidKey)?;
+        self.state.mix_hash(&act[..33]);
+        self.state
+            .mix_key(&ecdh(&remote_ephemeral, &self.local_secret_key));
+        self.state.decrypt_and_hash(0, &act[33..])?;
+
+        let ephemeral_pubkey = PublicKey::from_secret_key(&secp, &self.ephemeral_key);
+        self.state.mix_hash(&ephemeral_pubkey.serialize());
+        self.state
+            .mix_key(&ecdh(&remote_ephemeral, &self.ephemeral_key));
+        let c = self.state.encrypt_and_hash(0, &[]);
+        self.remote_ephemeral = Some(remote_ephemeral);
+
+        let mut act_two = [0u8; ACT_ONE_TWO_SIZE];
+        act_two[0] = HANDSHAKE_VERSION;
+        act_two[1..34].copy_from_slice(&ephemeral_pubkey.serialize());
+        act_two[34..].copy_from_slice(&c);
+        Ok(act_two)
+    }
+
+    /// Processes the third act received from the initiator, returning the
+    /// established session, from which the authenticated node id of the
+    /// initiator can be obtained.
+    pub fn process_act_three(mut self, act_three: &[u8]) -> Result<NoiseSession, NoiseError> {
+        if self.remote_ephemeral.is_none() {
+            return Err(NoiseError::InvalidAct);
+        }
+        let act = parse_act(act_three, ACT_THREE_SIZE)?;
+        let remote_static = self.state.decrypt_and_hash(1, &act[..49])?;
+        let remote_node_id =
+            PublicKey::from_slice(&remote_static).map_err(|_| NoiseError::InvalidKey)?;
+        self.state
+            .mix_key(&ecdh(&remote_node_id, &self.ephemeral_key));
+        decrypt_with_ad(&self.state.temp_k, 0, &self.state.h[..], &act[49..])?;
+        let (receiving_key, sending_key) = hkdf(&self.state.ck, &[]);
+
+        Ok(NoiseSession::new(
+            remote_node_id,
+            self.state.ck,
+            sending_key,
+            receiving_key,
+        ))
+    }
+}
+
+struct CipherState {
+    key: [u8; 32],
+    nonce: u64,
+    chaining_key: [u8; 32],
+}
+
+impl CipherState {
+    fn encrypt(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let c = encrypt_with_ad(&self.key, self.nonce, &[], plaintext);
+        self.increment_nonce();
+        c
+    }
+
+    fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let p = decrypt_with_ad(&self.key, self.nonce, &[], ciphertext)?;
+        self.increment_nonce();
+        Ok(p)
+    }
+
+    fn increment_nonce(&mut self) {
+        self.nonce += 1;
+        if self.nonce == KEY_ROTATION_INTERVAL {
+            let (chaining_key, key) = hkdf(&self.chaining_key, &self.key);
+            self.chaining_key = chaining_key;
+            self.key = key;
+            self.nonce = 0;
+        }
+    }
+}
+
+/// An established encrypted session with a peer.
+pub struct NoiseSession {
+    remote_node_id: PublicKey,
+    sending: CipherState,
+    receiving: CipherState,
+}
+
+impl NoiseSession {
+    fn new(
+        remote_node_id: PublicKey,
+        chaining_key: [u8; 32],
+        sending_key: [u8; 32],
+        receiving_key: [u8; 32],
+    ) -> Self {
+        NoiseSession {
+            remote_node_id,
+            sending: CipherState {
+                key: sending_key,
+                nonce: 0,
+                chaining_key,
+            },
+            receiving: CipherState {
+                key: receiving_key,
+                nonce: 0,
+                chaining_key,
+            },
+        }
+    }
+
+    /// Returns the authenticated node id of the peer.
+    pub fn remote_node_id(&self) -> PublicKey {
+        self.remote_node_id
+    }
+
+    /// Returns the frame carrying the given message.
+    pub fn encrypt_message(&mut self, msg: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if msg.len() > MAX_MESSAGE_SIZE {
+            return Err(NoiseError::MessageTooLarge);
+        }
+        let mut frame = self.sending.encrypt(&(msg.len() as u32).to_be_bytes());
+        frame.extend(self.sending.encrypt(msg));
+        Ok(frame)
+    }
+
+    /// Decrypts the length prefix of a frame, returning the number of bytes
+    /// of the frame body that follows it.
+    pub fn decrypt_length_header(
+        &mut self,
+        header: &[u8; LENGTH_HEADER_SIZE],
+    ) -> Result<usize, NoiseError> {
+        let length = self.receiving.decrypt(header)?;
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(&length);
+        let length = u32::from_be_bytes(buf) as usize;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(NoiseError::MessageTooLarge);
+        }
+        Ok(length + TAG_SIZE)
+    }
+
+    /// Decrypts the body of a frame whose length prefix was decrypted using
+    /// [`NoiseSession::decrypt_length_header`].
+    pub fn decrypt_body(&mut self, body: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        self.receiving.decrypt(body)
+    }
+
+    /// Reads and decrypts a frame from the given reader.
+    pub fn read_message<R: Read>(&mut self, reader: &mut R) -> Result<Vec<u8>, NoiseError> {
+        let mut header = [0u8; LENGTH_HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        let length = self.decrypt_length_header(&header)?;
+        let mut body = vec![0u8; length];
+        reader.read_exact(&mut body)?;
+        self.decrypt_body(&body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn get_key(i: u8) -> SecretKey {
+        SecretKey::from_slice(&[i; 32]).unwrap()
+    }
+
+    fn handshake() -> (NoiseSession, NoiseSession) {
+        let secp = Secp256k1::new();
+        let responder_id = PublicKey::from_secret_key(&secp, &get_key(2));
+        let (initiator, act_one) = OutboundHandshake::new(&get_key(1), &responder_id);
+        let mut responder = InboundHandshake::new(&get_key(2));
+        let act_two = responder.process_act_one(&act_one).unwrap();
+        let (act_three, initiator_session) = initiator.process_act_two(&act_two).unwrap();
+        let responder_session = responder.process_act_three(&act_three).unwrap();
+        (initiator_session, responder_session)
+    }
+
+    #[test]
+    fn handshake_authenticates_peers() {
+        let secp = Secp256k1::new();
+        let (initiator, responder) = handshake();
+        assert_eq!(
+            PublicKey::from_secret_key(&secp, &get_key(2)),
+            initiator.remote_node_id()
+        );
+        assert_eq!(
+            PublicKey::from_secret_key(&secp, &get_key(1)),
+            responder.remote_node_id()
+        );
+    }
+
+    #[test]
+    fn handshake_with_wrong_responder_key_fails() {
+        let secp = Secp256k1::new();
+        let (_, act_one) =
+            OutboundHandshake::new(&get_key(1), &PublicKey::from_secret_key(&secp, &get_key(3)));
+        let mut responder = InboundHandshake::new(&get_key(2));
+        assert!(matches!(
+            responder.process_act_one(&act_one),
+            Err(NoiseError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn messages_are_exchanged_across_key_rotations() {
+        let (mut initiator, mut responder) = handshake();
+        for i in 0..(KEY_ROTATION_INTERVAL as usize + 10) {
+            let msg = vec![(i % 256) as u8; i % 100];
+            let frame = initiator.encrypt_message(&msg).unwrap();
+            let read = responder.read_message(&mut Cursor::new(frame)).unwrap();
+            assert_eq!(msg, read);
+
+            let frame = responder.encrypt_message(&msg).unwrap();
+            let read = initiator.read_message(&mut Cursor::new(frame)).unwrap();
+            assert_eq!(msg, read);
+        }
+    }
+
+    #[test]
+    fn tampered_frame_is_rejected() {
+        let (mut initiator, mut responder) = handshake();
+        let mut frame = initiator.encrypt_message(b"hello").unwrap();
+        let last = frame.len() - 1;
+        frame[last] ^= 1;
+        assert!(matches!(
+            responder.read_message(&mut Cursor::new(frame)),
+            Err(NoiseError::DecryptionFailed)
+        ));
+    }
+}
@@ -0,0 +1,327 @@
+//! Contains messages used to transfer the side of a party in a DLC, the
+//! transferor, to a new party, the transferee, with the cooperation of the
+//! counter party of the transferor, the remaining party. See
+//! [`dlc::novation`] for the transactions involved. The transfer goes as
+//! follows:
+//! * the transferor sends a [`NovationOffer`] to the transferee, describing
+//!   the contract and the terms of the transfer,
+//! * the transferee replies with a [`NovationAccept`] containing its
+//!   parameters together with its CET adaptor signatures and refund signature
+//!   for the novated contract,
+//! * the transferor forwards them to the remaining party together with the
+//!   offer in a [`NovationRequest`],
+//! * the remaining party replies with a [`NovationConfirm`] containing its
+//!   signatures for the novated contract and for the fund input of the
+//!   novation transaction,
+//! * the transferor sends a [`NovationFinalize`] with the signatures of both
+//!   parties to the transferee, which signs its funding inputs and broadcasts
+//!   the novation transaction.
+
+use bitcoin::{Script, Txid};
+use dlc::Error;
+use lightning::ln::msgs::DecodeError;
+use lightning::util::ser::{Readable, Writeable, Writer};
+use secp256k1_zkp::{ecdsa::Signature, PublicKey};
+
+use crate::{contract_msgs::ContractInfo, CetAdaptorSignatures, FundingInput};
+
+/// The terms of the transfer of a contract, agreed upon by the transferor and
+/// the transferee.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct NovationTerms {
+    /// The amount paid by the transferee to the transferor.
+    pub price: u64,
+    /// The SPK where the transferor will receive the price.
+    pub price_spk: Script,
+    /// Serial id to order novation transaction outputs.
+    pub price_serial_id: u64,
+    /// Serial id to order novation transaction outputs.
+    pub fund_output_serial_id: u64,
+    /// The fee rate used to compute the fees of the novation transaction,
+    /// paid by the transferee.
+    pub fee_rate_per_vb: u64,
+}
+
+impl_dlc_writeable!(NovationTerms, {
+    (price, writeable),
+    (price_spk, writeable),
+    (price_serial_id, writeable),
+    (fund_output_serial_id, writeable),
+    (fee_rate_per_vb, writeable)
+});
+
+/// Offer to take over the side of the transferor in a contract, sent by the
+/// transferor to the transferee.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct NovationOffer {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The identifier of the chain on which the contract is settled.
+    pub chain_hash: [u8; 32],
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract to transfer.
+    pub contract_id: [u8; 32],
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The temporary id of the contract to transfer.
+    pub temporary_contract_id: [u8; 32],
+    /// The node id of the remaining party.
+    pub remaining_party: PublicKey,
+    /// Whether the transferor is the offer party of the contract.
+    pub transferor_is_offer_party: bool,
+    /// The terms of the contract.
+    pub contract_info: ContractInfo,
+    /// The collateral of the transferor, taken over by the transferee.
+    pub transferor_collateral: u64,
+    /// The public key of the transferor in the current fund output.
+    pub transferor_funding_pubkey: PublicKey,
+    /// Serial id to order CET outputs, taken over by the transferee.
+    pub transferor_payout_serial_id: u64,
+    /// The public key of the remaining party in the fund outputs.
+    pub remaining_funding_pubkey: PublicKey,
+    /// The SPK where the remaining party receives their payout.
+    pub remaining_payout_spk: Script,
+    /// Serial id to order CET outputs.
+    pub remaining_payout_serial_id: u64,
+    /// The id of the fund transaction of the contract.
+    pub fund_txid: Txid,
+    /// The index of the fund output in the fund transaction.
+    pub fund_output_index: u16,
+    /// The value of the fund output.
+    pub fund_output_value: u64,
+    /// The lock time for the CETs.
+    pub cet_locktime: u32,
+    /// The lock time for the refund transactions.
+    pub refund_locktime: u32,
+    /// The terms of the transfer.
+    pub terms: NovationTerms,
+}
+
+impl_dlc_writeable!(NovationOffer, {
+    (protocol_version, writeable),
+    (chain_hash, writeable),
+    (contract_id, writeable),
+    (temporary_contract_id, writeable),
+    (remaining_party, writeable),
+    (transferor_is_offer_party, writeable),
+    (contract_info, writeable),
+    (transferor_collateral, writeable),
+    (transferor_funding_pubkey, writeable),
+    (transferor_payout_serial_id, writeable),
+    (remaining_funding_pubkey, writeable),
+    (remaining_payout_spk, writeable),
+    (remaining_payout_serial_id, writeable),
+    (fund_txid, writeable),
+    (fund_output_index, writeable),
+    (fund_output_value, writeable),
+    (cet_locktime, writeable),
+    (refund_locktime, writeable),
+    (terms, writeable)
+});
+
+impl NovationOffer {
+    /// Returns whether the message satisfies validity requirements, without
+    /// verifying the oracle announcements it contains.
+    pub fn validate(&self) -> Result<(), Error> {
+        dlc::util::validate_fee_rate(self.terms.fee_rate_per_vb)?;
+        if self.transferor_collateral > self.contract_info.get_total_collateral()
+            || self.transferor_funding_pubkey == self.remaining_funding_pubkey
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(())
+    }
+}
+
+/// The parameters and signatures of the transferee for the novated contract,
+/// sent to the transferor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct NovationAccept {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract to transfer.
+    pub contract_id: [u8; 32],
+    /// The public key of the transferee in the new fund output.
+    pub funding_pubkey: PublicKey,
+    /// The SPK where the transferee will receive their payout.
+    pub payout_spk: Script,
+    /// Inputs used by the transferee to fund the novation transaction.
+    pub funding_inputs: Vec<FundingInput>,
+    /// The SPK where the transferee will receive their change.
+    pub change_spk: Script,
+    /// Serial id to order novation transaction outputs.
+    pub change_serial_id: u64,
+    /// The CET adaptor signatures of the transferee.
+    pub cet_adaptor_signatures: CetAdaptorSignatures,
+    /// The refund signature of the transferee.
+    pub refund_signature: Signature,
+}
+
+impl_dlc_writeable!(NovationAccept, {
+    (protocol_version, writeable),
+    (contract_id, writeable),
+    (funding_pubkey, writeable),
+    (payout_spk, writeable),
+    (funding_inputs, vec),
+    (change_spk, writeable),
+    (change_serial_id, writeable),
+    (cet_adaptor_signatures, writeable),
+    (refund_signature, writeable)
+});
+
+/// Request to transfer the side of the transferor to the transferee, sent by
+/// the transferor to the remaining party.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct NovationRequest {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract to transfer.
+    pub contract_id: [u8; 32],
+    /// The node id of the transferee.
+    pub transferee: PublicKey,
+    /// The offer sent to the transferee, which the remaining party checks
+    /// against its own view of the contract.
+    pub offer: NovationOffer,
+    /// The parameters and signatures of the transferee.
+    pub accept: NovationAccept,
+}
+
+impl_dlc_writeable!(NovationRequest, {
+    (protocol_version, writeable),
+    (contract_id, writeable),
+    (transferee, writeable),
+    (offer, writeable),
+    (accept, writeable)
+});
+
+/// The signatures of the remaining party for the novated contract and the
+/// novation transaction, sent to the transferor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct NovationConfirm {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract to transfer.
+    pub contract_id: [u8; 32],
+    /// The CET adaptor signatures of the remaining party.
+    pub cet_adaptor_signatures: CetAdaptorSignatures,
+    /// The refund signature of the remaining party.
+    pub refund_signature: Signature,
+    /// The signature of the remaining party for the fund input of the
+    /// novation transaction.
+    pub fund_input_signature: Signature,
+}
+
+impl_dlc_writeable!(NovationConfirm, {
+    (protocol_version, writeable),
+    (contract_id, writeable),
+    (cet_adaptor_signatures, writeable),
+    (refund_signature, writeable),
+    (fund_input_signature, writeable)
+});
+
+/// The signatures of the remaining party for the novated contract and of both
+/// parties to the contract for the fund input of the novation transaction,
+/// sent by the transferor to the transferee.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct NovationFinalize {
+    /// The version of the protocol used by the peer.
+    pub protocol_version: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "crate::serde_utils::serialize_hex",
+            deserialize_with = "crate::serde_utils::deserialize_hex_array"
+        )
+    )]
+    /// The id of the contract to transfer.
+    pub contract_id: [u8; 32],
+    /// The CET adaptor signatures of the remaining party.
+    pub cet_adaptor_signatures: CetAdaptorSignatures,
+    /// The refund signature of the remaining party.
+    pub refund_signature: Signature,
+    /// The signature of the transferor for the fund input of the novation
+    /// transaction.
+    pub transferor_fund_input_signature: Signature,
+    /// The signature of the remaining party for the fund input of the
+    /// novation transaction.
+    pub remaining_fund_input_signature: Signature,
+}
+
+impl_dlc_writeable!(NovationFinalize, {
+    (protocol_version, writeable),
+    (contract_id, writeable),
+    (cet_adaptor_signatures, writeable),
+    (refund_signature, writeable),
+    (transferor_fund_input_signature, writeable),
+    (remaining_fund_input_signature, writeable)
+});
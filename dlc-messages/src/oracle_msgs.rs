@@ -12,6 +12,7 @@ use secp256k1_zkp::Verification;
 use secp256k1_zkp::{schnorr::Signature, Message, Secp256k1, XOnlyPublicKey};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 /// The type of the announcement struct.
 pub const ANNOUNCEMENT_TYPE: u16 = 55332;
@@ -61,11 +62,15 @@ impl OracleInfo {
     /// Checks that the info satisfies the validity conditions.
     pub fn validate<C: Verification>(&self, secp: &Secp256k1<C>) -> Result<(), Error> {
         match self {
-            OracleInfo::Single(s) => s.oracle_announcement.validate(secp)?,
+            OracleInfo::Single(s) => {
+                s.oracle_announcement.validate(secp)?;
+                check_nonce_reuse(std::slice::from_ref(&s.oracle_announcement))?;
+            }
             OracleInfo::Multi(m) => {
                 for o in &m.oracle_announcements {
                     o.validate(secp)?;
                 }
+                check_nonce_reuse(&m.oracle_announcements)?;
             }
         };
 
@@ -305,6 +310,11 @@ impl_dlc_writeable!(DigitDecompositionEventDescriptor, {
 
 /// An attestation from an oracle providing signatures over an outcome value.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct OracleAttestation {
     /// The public key of the oracle.
     pub oracle_public_key: XOnlyPublicKey,
@@ -320,6 +330,116 @@ impl Type for OracleAttestation {
     }
 }
 
+impl OracleAttestation {
+    /// Checks that the attestation was produced by the oracle of the given
+    /// announcement, using the announced nonces, and that each attested
+    /// outcome is valid for the announced event.
+    pub fn validate<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        announcement: &OracleAnnouncement,
+    ) -> Result<(), Error> {
+        let nonces = &announcement.oracle_event.oracle_nonces;
+        if nonces.is_empty()
+            || self.oracle_public_key != announcement.oracle_public_key
+            || self.signatures.len() != nonces.len()
+            || self.outcomes.len() != nonces.len()
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        match &announcement.oracle_event.event_descriptor {
+            EventDescriptor::EnumEvent(e) => {
+                if !e.outcomes.contains(&self.outcomes[0]) {
+                    return Err(Error::InvalidArgument);
+                }
+            }
+            EventDescriptor::DigitDecompositionEvent(_) => {
+                self.get_numeric_outcome(announcement)?;
+            }
+        }
+
+        for ((signature, nonce), outcome) in self
+            .signatures
+            .iter()
+            .zip(nonces.iter())
+            .zip(self.outcomes.iter())
+        {
+            let (sig_nonce, _) = dlc::secp_utils::schnorrsig_decompose(signature)?;
+            if &sig_nonce != nonce {
+                return Err(Error::InvalidArgument);
+            }
+            let msg = Message::from_hashed_data::<secp256k1_zkp::hashes::sha256::Hash>(
+                outcome.as_bytes(),
+            );
+            secp.verify_schnorr(signature, &msg, &self.oracle_public_key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the numeric value attested to for the digit decomposition
+    /// event of the given announcement. Errors if the event is not a digit
+    /// decomposition one, or if a digit is not valid in the event base.
+    pub fn get_numeric_outcome(&self, announcement: &OracleAnnouncement) -> Result<i64, Error> {
+        let descriptor = match &announcement.oracle_event.event_descriptor {
+            EventDescriptor::DigitDecompositionEvent(d) => d,
+            _ => return Err(Error::InvalidArgument),
+        };
+        let (is_negative, digits) = if descriptor.is_signed {
+            match self.outcomes.split_first() {
+                Some((sign, digits)) if sign == "+" => (false, digits),
+                Some((sign, digits)) if sign == "-" => (true, digits),
+                _ => return Err(Error::InvalidArgument),
+            }
+        } else {
+            (false, &self.outcomes[..])
+        };
+
+        if digits.len() != descriptor.nb_digits as usize {
+            return Err(Error::InvalidArgument);
+        }
+
+        let base = descriptor.base as i64;
+        let mut value: i64 = 0;
+        for digit in digits {
+            let digit = digit.parse::<i64>().map_err(|_| Error::InvalidArgument)?;
+            if digit < 0 || digit >= base {
+                return Err(Error::InvalidArgument);
+            }
+            value = value
+                .checked_mul(base)
+                .and_then(|v| v.checked_add(digit))
+                .ok_or(Error::InvalidArgument)?;
+        }
+
+        Ok(if is_negative { -value } else { value })
+    }
+}
+
+/// Returns an error if a nonce is used more than once by the same oracle,
+/// either within an event or across different events of the given
+/// announcements, which would enable recovering the private key of the oracle
+/// once it attests to the affected outcomes.
+pub fn check_nonce_reuse(announcements: &[OracleAnnouncement]) -> Result<(), Error> {
+    let mut seen = HashMap::new();
+    for announcement in announcements {
+        let event_id = &announcement.oracle_event.event_id;
+        let mut event_nonces = HashSet::new();
+        for nonce in &announcement.oracle_event.oracle_nonces {
+            if !event_nonces.insert(nonce) {
+                return Err(Error::InvalidArgument);
+            }
+            match seen.insert((announcement.oracle_public_key, *nonce), event_id) {
+                Some(prev) if prev != event_id => return Err(Error::InvalidArgument),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
 impl_dlc_writeable!(OracleAttestation, {
     (oracle_public_key, {cb_writeable, write_schnorr_pubkey, read_schnorr_pubkey}),
     (signatures, {vec_u16_cb, write_schnorrsig, read_schnorrsig}),
@@ -441,4 +561,86 @@ mod tests {
 
         assert!(invalid_announcement.validate(SECP256K1).is_err());
     }
+
+    fn attested_digit_event(outcomes: &[&str]) -> (OracleAnnouncement, OracleAttestation) {
+        let key_pair = KeyPair::new(SECP256K1, &mut thread_rng());
+        let oracle_public_key = XOnlyPublicKey::from_keypair(&key_pair).0;
+        let nonce_keys: Vec<_> = outcomes
+            .iter()
+            .map(|_| secp256k1_zkp::SecretKey::new(&mut thread_rng()))
+            .collect();
+        let mut event = digit_event(0);
+        event.oracle_nonces = nonce_keys
+            .iter()
+            .map(|k| XOnlyPublicKey::from_keypair(&KeyPair::from_secret_key(SECP256K1, k)).0)
+            .collect();
+        event.event_descriptor =
+            EventDescriptor::DigitDecompositionEvent(DigitDecompositionEventDescriptor {
+                base: 10,
+                nb_digits: outcomes.len() as u16,
+                ..digit_descriptor()
+            });
+        let signatures = outcomes
+            .iter()
+            .zip(nonce_keys.iter())
+            .map(|(outcome, nonce)| {
+                let msg = Message::from_hashed_data::<secp256k1_zkp::hashes::sha256::Hash>(
+                    outcome.as_bytes(),
+                );
+                dlc::secp_utils::schnorrsig_sign_with_nonce(
+                    SECP256K1,
+                    &msg,
+                    &key_pair,
+                    &nonce.secret_bytes(),
+                )
+            })
+            .collect();
+        let announcement = OracleAnnouncement {
+            announcement_signature: SECP256K1
+                .sign_schnorr(&Message::from_slice(&[1; 32]).unwrap(), &key_pair),
+            oracle_public_key,
+            oracle_event: event,
+        };
+        let attestation = OracleAttestation {
+            oracle_public_key,
+            signatures,
+            outcomes: outcomes.iter().map(|x| x.to_string()).collect(),
+        };
+        (announcement, attestation)
+    }
+
+    #[test]
+    fn valid_attestation_is_decoded_test() {
+        let (announcement, attestation) = attested_digit_event(&["0", "4", "2"]);
+
+        attestation
+            .validate(SECP256K1, &announcement)
+            .expect("attestation to be valid");
+        assert_eq!(42, attestation.get_numeric_outcome(&announcement).unwrap());
+    }
+
+    #[test]
+    fn invalid_attestation_fails_validation_test() {
+        let (announcement, mut attestation) = attested_digit_event(&["0", "4", "2"]);
+        attestation.outcomes[2] = "3".to_string();
+        attestation
+            .validate(SECP256K1, &announcement)
+            .expect_err("signature over a different outcome to be invalid");
+
+        let (announcement, attestation) = attested_digit_event(&["0", "4", "12"]);
+        attestation
+            .validate(SECP256K1, &announcement)
+            .expect_err("out of base digit to be invalid");
+    }
+
+    #[test]
+    fn nonce_reuse_is_detected_test() {
+        let (announcement, _) = attested_digit_event(&["0", "1"]);
+        let mut other = announcement.clone();
+
+        check_nonce_reuse(&[announcement.clone(), other.clone()])
+            .expect("same event to be allowed multiple times");
+        other.oracle_event.event_id = "other".to_string();
+        check_nonce_reuse(&[announcement, other]).expect_err("nonce reuse to be detected");
+    }
 }
@@ -1,5 +1,7 @@
 //! Module used when working with message segmentation.
 
+use std::time::Duration;
+
 use lightning::ln::msgs::DecodeError;
 use lightning::ln::wire::Type;
 use lightning::util::ser::{Readable, Writeable, Writer};
@@ -21,8 +23,38 @@ const MAX_CHUNK_SIZE: usize = 65528;
 
 const MAX_SEGMENTS: usize = 1000;
 
+// Time after which a partially received message is discarded.
+const DEFAULT_REASSEMBLY_TIMEOUT_SECS: u64 = 60;
+
+// Maximum number of peers from which partial messages are kept at once.
+const DEFAULT_MAX_PENDING_REASSEMBLIES: usize = 100;
+
 pub mod segment_reader;
 
+/// Limits applied when reassembling segmented messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SegmentationLimits {
+    /// The maximum number of segments that a message can be split into. Values
+    /// above the protocol maximum of 1000 have no effect.
+    pub max_segments: u16,
+    /// The maximum time to wait for all the chunks of a message to be
+    /// received after its [`SegmentStart`].
+    pub reassembly_timeout: Duration,
+    /// The maximum number of peers whose segmented messages can be in the
+    /// process of being reassembled at the same time.
+    pub max_pending_reassemblies: usize,
+}
+
+impl Default for SegmentationLimits {
+    fn default() -> Self {
+        SegmentationLimits {
+            max_segments: MAX_SEGMENTS as u16,
+            reassembly_timeout: Duration::from_secs(DEFAULT_REASSEMBLY_TIMEOUT_SECS),
+            max_pending_reassemblies: DEFAULT_MAX_PENDING_REASSEMBLIES,
+        }
+    }
+}
+
 #[cfg_attr(
     feature = "serde",
     derive(serde::Serialize, serde::Deserialize),
@@ -69,18 +101,31 @@ impl Type for SegmentChunk {
     }
 }
 
-/// Split the given data into multiple segments, pre-pending the message type
-/// to enable decoding on the receiving side.
-pub fn get_segments(mut data: Vec<u8>, msg_type: u16) -> (SegmentStart, Vec<SegmentChunk>) {
-    debug_assert!(data.len() > MAX_DATA_SIZE);
+/// Returns the number of segments into which a message whose serialization
+/// (without type prefix) is `data_len` bytes long is split by [`get_segments`],
+/// or 1 if the message fits in a single wire message.
+pub fn get_nb_segments(data_len: usize) -> usize {
+    if data_len <= MAX_DATA_SIZE {
+        return 1;
+    }
 
-    let len_minus_start = data.len() - MAX_START_DATA_SIZE + 2;
-    let mut nb_segments = (len_minus_start / MAX_CHUNK_SIZE + 1) as u16;
+    let len_minus_start = data_len - MAX_START_DATA_SIZE + 2;
+    let mut nb_segments = len_minus_start / MAX_CHUNK_SIZE + 1;
 
     if len_minus_start % MAX_CHUNK_SIZE != 0 {
         nb_segments += 1;
     }
 
+    nb_segments
+}
+
+/// Split the given data into multiple segments, pre-pending the message type
+/// to enable decoding on the receiving side.
+pub fn get_segments(mut data: Vec<u8>, msg_type: u16) -> (SegmentStart, Vec<SegmentChunk>) {
+    debug_assert!(data.len() > MAX_DATA_SIZE);
+
+    let nb_segments = get_nb_segments(data.len()) as u16;
+
     debug_assert!(nb_segments > 1);
 
     let mut start_data = Vec::with_capacity(MAX_START_DATA_SIZE);
@@ -127,4 +172,14 @@ mod tests {
         assert_eq!(MAX_CHUNK_SIZE, segment_chunks[1].data.len());
         assert_eq!(1236, segment_chunks[2].data.len());
     }
+
+    #[test]
+    fn get_nb_segments_test() {
+        assert_eq!(1, get_nb_segments(MAX_DATA_SIZE));
+        assert_eq!(2, get_nb_segments(MAX_DATA_SIZE + 1));
+        assert_eq!(
+            4,
+            get_nb_segments(MAX_START_DATA_SIZE + 2 * MAX_CHUNK_SIZE + 1234)
+        );
+    }
 }
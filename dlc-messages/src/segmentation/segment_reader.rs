@@ -1,11 +1,18 @@
 //! Module helping with processing message segmentation related messages.
 
-use super::{SegmentChunk, SegmentStart, MAX_CHUNK_SIZE, MAX_SEGMENTS, MAX_START_DATA_SIZE};
+use std::time::Instant;
+
+use super::{
+    SegmentChunk, SegmentStart, SegmentationLimits, MAX_CHUNK_SIZE, MAX_SEGMENTS,
+    MAX_START_DATA_SIZE,
+};
 
 /// Struct helping with processing message segmentation related messages.
 pub struct SegmentReader {
     cur_data: Vec<u8>,
     remaining_segments: u16,
+    started_at: Option<Instant>,
+    limits: SegmentationLimits,
 }
 
 #[derive(Debug)]
@@ -15,6 +22,8 @@ pub enum Error {
     InvalidState(String),
     /// A parameter received by the reader was not in accordance with its state.
     InvalidParameter(String),
+    /// The chunks of a segmented message were not all received in time.
+    Timeout,
 }
 
 impl std::fmt::Display for Error {
@@ -22,6 +31,7 @@ impl std::fmt::Display for Error {
         match *self {
             Error::InvalidState(ref s) => write!(f, "Invalid state {}", s),
             Error::InvalidParameter(ref s) => write!(f, "Invalid parameters were provided: {}", s),
+            Error::Timeout => write!(f, "Timed out waiting for segment chunks"),
         }
     }
 }
@@ -31,6 +41,7 @@ impl std::error::Error for Error {
         match self {
             Error::InvalidState(_) => None,
             Error::InvalidParameter(_) => None,
+            Error::Timeout => None,
         }
     }
 }
@@ -44,9 +55,16 @@ impl Default for SegmentReader {
 impl SegmentReader {
     /// Returns a new instance of [`Self`].
     pub fn new() -> Self {
+        Self::with_limits(SegmentationLimits::default())
+    }
+
+    /// Returns a new instance of [`Self`] enforcing the given limits.
+    pub fn with_limits(limits: SegmentationLimits) -> Self {
         SegmentReader {
             cur_data: Vec::new(),
             remaining_segments: 0,
+            started_at: None,
+            limits,
         }
     }
 
@@ -54,6 +72,7 @@ impl SegmentReader {
     pub fn reset(&mut self) {
         self.cur_data = Vec::new();
         self.remaining_segments = 0;
+        self.started_at = None;
     }
 
     /// Whether the reader is waiting for an incoming chunk.
@@ -61,6 +80,17 @@ impl SegmentReader {
         self.remaining_segments != 0
     }
 
+    /// Whether the reader has been waiting for chunks for longer than the
+    /// reassembly timeout at time `now`.
+    pub fn has_timed_out(&self, now: Instant) -> bool {
+        match self.started_at {
+            Some(started_at) if self.expecting_chunk() => {
+                now.saturating_duration_since(started_at) > self.limits.reassembly_timeout
+            }
+            _ => false,
+        }
+    }
+
     /// Process a [`super::SegmentStart`] message.
     pub fn process_segment_start(&mut self, segment_start: SegmentStart) -> Result<(), Error> {
         if !self.cur_data.is_empty() {
@@ -76,6 +106,13 @@ impl SegmentReader {
             ));
         }
 
+        if segment_start.nb_segments > self.limits.max_segments {
+            return Err(Error::InvalidParameter(format!(
+                "Segment start specifies {} chunks but at most {} are accepted.",
+                segment_start.nb_segments, self.limits.max_segments
+            )));
+        }
+
         if segment_start.data.len() < MAX_START_DATA_SIZE {
             return Err(Error::InvalidParameter(
                 "Segment start data should be filled to its maximum capacity.".to_string(),
@@ -87,6 +124,7 @@ impl SegmentReader {
         self.remaining_segments = nb_segments - 1;
 
         self.cur_data = data;
+        self.started_at = Some(Instant::now());
 
         Ok(())
     }
@@ -102,6 +140,11 @@ impl SegmentReader {
             ));
         }
 
+        if self.has_timed_out(Instant::now()) {
+            self.reset();
+            return Err(Error::Timeout);
+        }
+
         if self.remaining_segments > 1 && segment_chunk.data.len() != MAX_CHUNK_SIZE {
             return Err(Error::InvalidParameter(
                 "Receive non final segment chunk that was not not filled.".to_string(),
@@ -114,6 +157,7 @@ impl SegmentReader {
         if self.remaining_segments == 0 {
             let mut res = Vec::new();
             std::mem::swap(&mut self.cur_data, &mut res);
+            self.started_at = None;
             Ok(Some(res))
         } else {
             Ok(None)
@@ -123,6 +167,8 @@ impl SegmentReader {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use crate::segmentation::MAX_DATA_SIZE;
 
     use super::*;
@@ -240,4 +286,39 @@ mod tests {
             .process_segment_chunk(segment_chunks[0].clone())
             .expect_err("should not accept not full segment that is not the last one");
     }
+
+    #[test]
+    fn chunks_above_limit_fails_test() {
+        let mut segment_reader = SegmentReader::with_limits(SegmentationLimits {
+            max_segments: 3,
+            ..Default::default()
+        });
+        let (segment_start, _) = segments();
+        segment_reader
+            .process_segment_start(segment_start)
+            .expect_err("should not accept more segments than the configured limit");
+    }
+
+    #[test]
+    fn chunk_after_timeout_fails_test() {
+        let mut segment_reader = SegmentReader::with_limits(SegmentationLimits {
+            reassembly_timeout: Duration::from_secs(10),
+            ..Default::default()
+        });
+        let (segment_start, segment_chunks) = segments();
+        segment_reader
+            .process_segment_start(segment_start)
+            .expect("to be able to process the segment start");
+
+        let now = Instant::now();
+        assert!(!segment_reader.has_timed_out(now));
+        assert!(segment_reader.has_timed_out(now + Duration::from_secs(11)));
+
+        segment_reader.started_at = Some(now - Duration::from_secs(11));
+        assert!(matches!(
+            segment_reader.process_segment_chunk(segment_chunks[0].clone()),
+            Err(Error::Timeout)
+        ));
+        assert!(!segment_reader.expecting_chunk());
+    }
 }
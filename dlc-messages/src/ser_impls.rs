@@ -2,7 +2,7 @@
 
 use bitcoin::network::constants::Network;
 use bitcoin::Address;
-use dlc::{EnumerationPayout, PartyParams, Payout, TxInputInfo};
+use dlc::{EnumerationPayout, PartyParams, Payout, PayoutOutput, TxInputInfo};
 use lightning::ln::msgs::DecodeError;
 use lightning::ln::wire::Type;
 use lightning::util::ser::{Readable, Writeable, Writer};
@@ -10,6 +10,7 @@ use secp256k1_zkp::{ffi::ECDSA_ADAPTOR_SIGNATURE_LENGTH, EcdsaAdaptorSignature};
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::io::Read;
+use UnknownTlv;
 
 const MAX_VEC_SIZE: u64 = 1000000;
 
@@ -578,6 +579,85 @@ pub fn read_as_tlv<T: Type + Readable, R: ::std::io::Read>(
     Readable::read(reader)
 }
 
+/// A [`Writer`] that only counts the number of bytes written to it, used to
+/// compute the serialized length of values without allocating a buffer.
+pub struct LengthCalculatingWriter(pub usize);
+
+impl Writer for LengthCalculatingWriter {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), ::std::io::Error> {
+        self.0 += buf.len();
+        Ok(())
+    }
+}
+
+/// Returns the serialized length of a TLV record with the given type and
+/// value length.
+pub fn tlv_record_length(tlv_type: u64, value_length: usize) -> usize {
+    BigSize(tlv_type).serialized_length()
+        + BigSize(value_length as u64).serialized_length()
+        + value_length
+}
+
+/// Writes the given TLV records as a TLV stream.
+pub fn write_tlv_stream<W: Writer>(
+    tlvs: &[UnknownTlv],
+    writer: &mut W,
+) -> Result<(), ::std::io::Error> {
+    for tlv in tlvs {
+        BigSize(tlv.tlv_type).write(writer)?;
+        BigSize(tlv.value.len() as u64).write(writer)?;
+        writer.write_all(&tlv.value)?;
+    }
+    Ok(())
+}
+
+/// Reads a TLV stream until the end of the reader is reached. Records with
+/// an unknown odd type are returned along with the known ones so that they
+/// can be re-serialized, while records with an unknown even type are rejected.
+pub fn read_tlv_stream<R: ::std::io::Read>(
+    reader: &mut R,
+    known_types: &[u64],
+) -> Result<Vec<UnknownTlv>, DecodeError> {
+    let mut tlvs: Vec<UnknownTlv> = Vec::new();
+    loop {
+        let mut first = [0u8; 1];
+        if reader.read(&mut first)? == 0 {
+            return Ok(tlvs);
+        }
+        let tlv_type: BigSize = Readable::read(&mut (&first[..]).chain(&mut *reader))?;
+        if let Some(prev) = tlvs.last() {
+            if tlv_type.0 <= prev.tlv_type {
+                return Err(DecodeError::InvalidValue);
+            }
+        }
+        if tlv_type.0 % 2 == 0 && !known_types.contains(&tlv_type.0) {
+            return Err(DecodeError::UnknownRequiredFeature);
+        }
+        let len: BigSize = Readable::read(reader)?;
+        let mut value = Vec::new();
+        reader.by_ref().take(len.0).read_to_end(&mut value)?;
+        if value.len() as u64 != len.0 {
+            return Err(DecodeError::ShortRead);
+        }
+        tlvs.push(UnknownTlv {
+            tlv_type: tlv_type.0,
+            value,
+        });
+    }
+}
+
+/// Reads a value from the content of a TLV record, failing if it is not
+/// entirely consumed.
+pub fn read_tlv_value<T: Readable>(value: &[u8]) -> Result<T, DecodeError> {
+    let mut cursor = ::std::io::Cursor::new(value);
+    let res = Readable::read(&mut cursor)?;
+    if cursor.position() != value.len() as u64 {
+        return Err(DecodeError::InvalidValue);
+    }
+    Ok(res)
+}
+
 /// Writes a [`HashMap`].
 pub fn write_hash_map<W: Writer, T, V>(
     input: &HashMap<T, V>,
@@ -617,6 +697,7 @@ where
 impl_dlc_writeable_external!(Payout, payout, { (offer, writeable), (accept, writeable) });
 impl_dlc_writeable_external!(EnumerationPayout, enum_payout, { (outcome, string), (payout, { cb_writeable, payout::write, payout::read} )});
 impl_dlc_writeable_external!(TxInputInfo, tx_input_info, { (outpoint, writeable), (max_witness_len, usize), (redeem_script, writeable), (serial_id, writeable)});
+impl_dlc_writeable_external!(PayoutOutput, payout_output, { (script_pubkey, writeable), (serial_id, writeable), (share_bps, writeable) });
 impl_dlc_writeable_external!(PartyParams, party_params, {
     (fund_pubkey, writeable),
     (change_script_pubkey, writeable),
@@ -625,5 +706,6 @@ impl_dlc_writeable_external!(PartyParams, party_params, {
     (payout_serial_id, writeable),
     (inputs, { vec_cb, tx_input_info::write, tx_input_info::read }),
     (input_amount, writeable),
-    (collateral, writeable)
+    (collateral, writeable),
+    (additional_payout_outputs, { vec_cb, payout_output::write, payout_output::read })
 });
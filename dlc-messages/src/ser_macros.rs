@@ -100,6 +100,81 @@ macro_rules! impl_dlc_writeable {
             }
         }
     };
+    ($st:ident, {$(($field: ident, $fieldty: tt)), *}, tlv_stream: $tlvs: ident $(, {$(($tlv_type: expr, $tlv_field: ident)),*})?) => {
+        impl Writeable for $st {
+			fn write<W: Writer>(&self, w: &mut W) -> Result<(), ::std::io::Error> {
+                self.write_without_tlv_stream(w)?;
+                let mut records = self.$tlvs.clone();
+                $($(
+                    if let Some(ref value) = self.$tlv_field {
+                        records.push($crate::UnknownTlv {
+                            tlv_type: $tlv_type,
+                            value: value.encode(),
+                        });
+                    }
+                )*)?
+                records.sort_by_key(|x| x.tlv_type);
+                $crate::ser_impls::write_tlv_stream(&records, w)
+            }
+
+            fn serialized_length(&self) -> usize {
+                let mut length = $crate::ser_impls::LengthCalculatingWriter(0);
+                self.write_without_tlv_stream(&mut length)
+                    .expect("to be able to compute the length");
+                let mut total = length.0;
+                for record in &self.$tlvs {
+                    total += $crate::ser_impls::tlv_record_length(record.tlv_type, record.value.len());
+                }
+                $($(
+                    if let Some(ref value) = self.$tlv_field {
+                        total += $crate::ser_impls::tlv_record_length($tlv_type, value.serialized_length());
+                    }
+                )*)?
+                total
+            }
+        }
+
+        impl Readable for $st {
+			fn read<R: std::io::Read>(r: &mut R) -> Result<Self, DecodeError> {
+                let mut res = Self::read_without_tlv_stream(r)?;
+                let known_types: &[u64] = &[$($($tlv_type),*)?];
+                for record in $crate::ser_impls::read_tlv_stream(r, known_types)? {
+                    $($(
+                        if record.tlv_type == $tlv_type {
+                            res.$tlv_field = Some($crate::ser_impls::read_tlv_value(&record.value)?);
+                            continue;
+                        }
+                    )*)?
+                    res.$tlvs.push(record);
+                }
+                Ok(res)
+            }
+        }
+
+        impl $st {
+            /// Writes the message without its trailing TLV stream, so that
+            /// it can be followed by other data.
+            pub fn write_without_tlv_stream<W: Writer>(&self, w: &mut W) -> Result<(), ::std::io::Error> {
+				$(
+                    field_write!(w, self.$field, $fieldty);
+                )*
+				Ok(())
+            }
+
+            /// Reads a message written using `write_without_tlv_stream`.
+            pub fn read_without_tlv_stream<R: std::io::Read>(r: &mut R) -> Result<Self, DecodeError> {
+                Ok(Self {
+                    $(
+                        $field: field_read!(r, $fieldty),
+                    )*
+                    $($(
+                        $tlv_field: None,
+                    )*)?
+                    $tlvs: Vec::new(),
+                })
+            }
+        }
+    };
 }
 
 /// Implements the [`lightning::util::ser::Writeable`] trait for a struct external
@@ -0,0 +1,189 @@
+//! Tracking of the messages exchanged with each peer, enabling applications
+//! to display the progress of the protocol and to detect stalled sessions.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lightning::ln::wire::Type;
+
+use Message;
+
+/// The number of messages kept in the history of a session.
+pub const SESSION_HISTORY_SIZE: usize = 32;
+
+/// Whether a message was sent or received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageDirection {
+    /// The message was sent to the peer.
+    Sent,
+    /// The message was received from the peer.
+    Received,
+}
+
+/// A message exchanged with a peer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageRecord {
+    /// The type of the message.
+    pub msg_type: u16,
+    /// Whether the message was sent or received.
+    pub direction: MessageDirection,
+    /// The unix time at which the message was sent or received.
+    pub timestamp: u64,
+    /// Whether the peer acknowledged the message, always `true` for
+    /// received messages.
+    pub acknowledged: bool,
+    /// Whether the protocol expects the other party to reply to the message.
+    pub expects_reply: bool,
+}
+
+/// The state of the protocol session with a peer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerSession {
+    /// Whether the peer is currently connected.
+    pub connected: bool,
+    /// The unix time of the last connection or disconnection of the peer.
+    pub last_connection_change: Option<u64>,
+    /// The unix time at which the peer last acknowledged sent messages.
+    pub last_acknowledged: Option<u64>,
+    /// The most recent messages exchanged with the peer, oldest first.
+    pub history: VecDeque<MessageRecord>,
+}
+
+impl PeerSession {
+    /// Returns the last message sent to the peer.
+    pub fn last_sent(&self) -> Option<&MessageRecord> {
+        self.last_with_direction(MessageDirection::Sent)
+    }
+
+    /// Returns the last message received from the peer.
+    pub fn last_received(&self) -> Option<&MessageRecord> {
+        self.last_with_direction(MessageDirection::Received)
+    }
+
+    /// Returns the message the peer is expected to reply to, if the last
+    /// exchanged message was sent to the peer and expects a reply.
+    pub fn awaiting_reply(&self) -> Option<&MessageRecord> {
+        self.history
+            .back()
+            .filter(|r| r.direction == MessageDirection::Sent && r.expects_reply)
+    }
+
+    /// Returns the message the peer should reply to, if the last exchanged
+    /// message was received from the peer and expects a reply.
+    pub fn awaiting_local_reply(&self) -> Option<&MessageRecord> {
+        self.history
+            .back()
+            .filter(|r| r.direction == MessageDirection::Received && r.expects_reply)
+    }
+
+    /// Whether the peer did not reply to a message within `timeout` seconds
+    /// of it being sent.
+    pub fn is_stalled(&self, now: u64, timeout: u64) -> bool {
+        self.awaiting_reply()
+            .map(|r| r.timestamp + timeout < now)
+            .unwrap_or(false)
+    }
+
+    fn last_with_direction(&self, direction: MessageDirection) -> Option<&MessageRecord> {
+        self.history.iter().rev().find(|r| r.direction == direction)
+    }
+
+    pub(crate) fn record_message(&mut self, msg: &Message, direction: MessageDirection) {
+        self.history.push_back(MessageRecord {
+            msg_type: msg.type_id(),
+            direction,
+            timestamp: unix_time_now(),
+            acknowledged: direction == MessageDirection::Received,
+            expects_reply: expects_reply(msg),
+        });
+        if self.history.len() > SESSION_HISTORY_SIZE {
+            self.history.pop_front();
+        }
+    }
+
+    pub(crate) fn record_acknowledgement(&mut self) {
+        self.last_acknowledged = Some(unix_time_now());
+        for record in self.history.iter_mut() {
+            record.acknowledged = true;
+        }
+    }
+
+    pub(crate) fn record_connection_change(&mut self, connected: bool) {
+        self.connected = connected;
+        self.last_connection_change = Some(unix_time_now());
+    }
+}
+
+fn expects_reply(msg: &Message) -> bool {
+    matches!(
+        msg,
+        Message::Offer(_)
+            | Message::Accept(_)
+            | Message::OfferChannel(_)
+            | Message::AcceptChannel(_)
+            | Message::SettleOffer(_)
+            | Message::SettleAccept(_)
+            | Message::SettleConfirm(_)
+            | Message::RenewOffer(_)
+            | Message::RenewAccept(_)
+            | Message::RenewConfirm(_)
+            | Message::MultiPartyOffer(_)
+            | Message::MultiPartySetup(_)
+            | Message::MultiPartyFinalize(_)
+            | Message::NovationOffer(_)
+            | Message::NovationAccept(_)
+            | Message::NovationRequest(_)
+    )
+}
+
+pub(crate) fn unix_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Unexpected time error")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use channel::Reject;
+
+    fn get_offer() -> Message {
+        Message::Offer(serde_json::from_str(include_str!("./test_inputs/offer_msg.json")).unwrap())
+    }
+
+    #[test]
+    fn awaiting_reply_test() {
+        let mut session = PeerSession::default();
+        assert!(session.awaiting_reply().is_none());
+
+        session.record_message(&get_offer(), MessageDirection::Sent);
+        let record = session.awaiting_reply().expect("to be awaiting a reply");
+        assert!(!record.acknowledged);
+        assert!(!session.is_stalled(record.timestamp, 10));
+        assert!(session.is_stalled(record.timestamp + 11, 10));
+
+        session.record_acknowledgement();
+        assert!(session.last_sent().unwrap().acknowledged);
+
+        session.record_message(
+            &Message::Reject(Reject {
+                channel_id: [0; 32],
+            }),
+            MessageDirection::Received,
+        );
+        assert!(session.awaiting_reply().is_none());
+        assert!(session.awaiting_local_reply().is_none());
+        assert_eq!(2, session.history.len());
+    }
+
+    #[test]
+    fn history_is_bounded_test() {
+        let mut session = PeerSession::default();
+        for _ in 0..SESSION_HISTORY_SIZE + 1 {
+            session.record_message(&get_offer(), MessageDirection::Received);
+        }
+        assert_eq!(SESSION_HISTORY_SIZE, session.history.len());
+        assert!(session.awaiting_local_reply().is_some());
+    }
+}
@@ -0,0 +1,268 @@
+//! # Test vectors
+//! Types to emit and check test vectors in the JSON format used by the DLC
+//! specification, enabling downstream users to assert the compatibility of
+//! their messages and transactions with other implementations.
+//!
+//! Field names are kept in snake case to match the specification vectors.
+
+use std::fmt;
+use std::io::Cursor;
+
+use bitcoin::consensus::encode::serialize;
+use bitcoin::Transaction;
+use lightning::ln::wire::Type;
+use lightning::util::ser::{Readable, Writeable};
+
+use {AcceptDlc, OfferDlc, SignDlc};
+
+/// An error returned when a test vector does not match the expected value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestVectorError {
+    /// The serialized message could not be decoded.
+    Decode(String),
+    /// A value differs from the one in the test vector.
+    Mismatch(String),
+}
+
+impl fmt::Display for TestVectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestVectorError::Decode(s) => write!(f, "Could not decode message: {}", s),
+            TestVectorError::Mismatch(s) => write!(f, "Test vector mismatch: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for TestVectorError {}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+/// A message together with its expected serialization, including its type
+/// prefix.
+pub struct MessageTestVector<T> {
+    /// The message.
+    pub message: T,
+    #[serde(
+        serialize_with = "crate::serde_utils::serialize_hex",
+        deserialize_with = "crate::serde_utils::deserialize_hex_string"
+    )]
+    /// The serialization of the message.
+    pub serialized: Vec<u8>,
+}
+
+impl<T: Writeable + Readable + Type + PartialEq + fmt::Debug> MessageTestVector<T> {
+    /// Creates a test vector for the given message.
+    pub fn new(message: T) -> Self {
+        let serialized = encode_with_type(&message);
+        MessageTestVector {
+            message,
+            serialized,
+        }
+    }
+
+    /// Checks that the message serializes to the expected bytes and that
+    /// decoding them yields the message.
+    pub fn check(&self) -> Result<(), TestVectorError> {
+        if encode_with_type(&self.message) != self.serialized {
+            return Err(TestVectorError::Mismatch(format!(
+                "serialization of message with type {}",
+                self.message.type_id()
+            )));
+        }
+
+        let mut cursor = Cursor::new(&self.serialized);
+        let msg_type: u16 =
+            Readable::read(&mut cursor).map_err(|e| TestVectorError::Decode(format!("{:?}", e)))?;
+        if msg_type != self.message.type_id() {
+            return Err(TestVectorError::Mismatch(format!(
+                "expected type {} but got {}",
+                self.message.type_id(),
+                msg_type
+            )));
+        }
+        let decoded: T =
+            Readable::read(&mut cursor).map_err(|e| TestVectorError::Decode(format!("{:?}", e)))?;
+        if decoded != self.message {
+            return Err(TestVectorError::Mismatch(format!(
+                "decoded message with type {}",
+                msg_type
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_with_type<T: Writeable + Type>(message: &T) -> Vec<u8> {
+    let mut buf = message.type_id().encode();
+    buf.extend(message.encode());
+    buf
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+/// The serialized transactions of a contract.
+pub struct TransactionsTestVector {
+    #[serde(
+        serialize_with = "crate::serde_utils::serialize_hex",
+        deserialize_with = "crate::serde_utils::deserialize_hex_string"
+    )]
+    /// The funding transaction.
+    pub fund_tx: Vec<u8>,
+    #[serde(with = "hex_vec")]
+    /// The contract execution transactions.
+    pub cets: Vec<Vec<u8>>,
+    #[serde(
+        serialize_with = "crate::serde_utils::serialize_hex",
+        deserialize_with = "crate::serde_utils::deserialize_hex_string"
+    )]
+    /// The refund transaction.
+    pub refund_tx: Vec<u8>,
+}
+
+impl TransactionsTestVector {
+    /// Creates a test vector for the given transactions.
+    pub fn new(fund_tx: &Transaction, cets: &[Transaction], refund_tx: &Transaction) -> Self {
+        TransactionsTestVector {
+            fund_tx: serialize(fund_tx),
+            cets: cets.iter().map(serialize).collect(),
+            refund_tx: serialize(refund_tx),
+        }
+    }
+
+    /// Checks that the given transactions match the test vector.
+    pub fn check(
+        &self,
+        fund_tx: &Transaction,
+        cets: &[Transaction],
+        refund_tx: &Transaction,
+    ) -> Result<(), TestVectorError> {
+        if self.fund_tx != serialize(fund_tx) {
+            return Err(TestVectorError::Mismatch("funding transaction".to_string()));
+        }
+        if self.cets.len() != cets.len() {
+            return Err(TestVectorError::Mismatch(format!(
+                "expected {} CETs but got {}",
+                self.cets.len(),
+                cets.len()
+            )));
+        }
+        for (i, (expected, cet)) in self.cets.iter().zip(cets.iter()).enumerate() {
+            if *expected != serialize(cet) {
+                return Err(TestVectorError::Mismatch(format!("CET at index {}", i)));
+            }
+        }
+        if self.refund_tx != serialize(refund_tx) {
+            return Err(TestVectorError::Mismatch("refund transaction".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+/// A test vector for the establishment of a contract.
+pub struct DlcTestVector {
+    /// The offer message.
+    pub offer_message: MessageTestVector<OfferDlc>,
+    /// The accept message.
+    pub accept_message: MessageTestVector<AcceptDlc>,
+    /// The sign message.
+    pub sign_message: MessageTestVector<SignDlc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The contract transactions before the funding transaction is signed.
+    pub unsigned_txs: Option<TransactionsTestVector>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The contract transactions with the funding transaction signed.
+    pub signed_txs: Option<TransactionsTestVector>,
+}
+
+impl DlcTestVector {
+    /// Checks the serialization of all the messages of the test vector.
+    pub fn check_messages(&self) -> Result<(), TestVectorError> {
+        self.offer_message.check()?;
+        self.accept_message.check()?;
+        self.sign_message.check()
+    }
+}
+
+mod hex_vec {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[Vec<u8>], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(
+            values
+                .iter()
+                .map(|v| v.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        let values: Vec<String> = Deserialize::deserialize(d)?;
+        values
+            .iter()
+            .map(|s| {
+                if s.len() % 2 != 0 {
+                    return Err(D::Error::custom("odd length hex string"));
+                }
+                (0..s.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(D::Error::custom))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_vector() -> DlcTestVector {
+        let offer: OfferDlc =
+            serde_json::from_str(include_str!("./test_inputs/offer_msg.json")).unwrap();
+        let accept: AcceptDlc =
+            serde_json::from_str(include_str!("./test_inputs/accept_msg.json")).unwrap();
+        let sign: SignDlc =
+            serde_json::from_str(include_str!("./test_inputs/sign_msg.json")).unwrap();
+        DlcTestVector {
+            offer_message: MessageTestVector::new(offer),
+            accept_message: MessageTestVector::new(accept),
+            sign_message: MessageTestVector::new(sign),
+            unsigned_txs: None,
+            signed_txs: None,
+        }
+    }
+
+    #[test]
+    fn test_vector_json_roundtrip_and_check() {
+        let test_vector = get_test_vector();
+        let json = serde_json::to_string(&test_vector).unwrap();
+        let parsed: DlcTestVector = serde_json::from_str(&json).unwrap();
+        assert_eq!(test_vector, parsed);
+        parsed.check_messages().expect("valid test vector");
+    }
+
+    #[test]
+    fn modified_test_vector_fails_check() {
+        let mut test_vector = get_test_vector();
+        test_vector.accept_message.message.accept_collateral += 1;
+        assert!(matches!(
+            test_vector.check_messages(),
+            Err(TestVectorError::Mismatch(_))
+        ));
+    }
+
+    #[test]
+    fn transactions_test_vector_check() {
+        let tx: Transaction = bitcoin::consensus::deserialize(
+            &bitcoin_test_utils::str_to_hex("0200000001b34e4345b6a07226f1aa91f2515096574fd6099eef1cd9885c8d069e5198cf760000000000000000000100c2eb0b00000000160014c1e616cb35cdb08332d9608f3d9d2dbf4ec34be800000000"),
+        )
+        .unwrap();
+        let test_vector = TransactionsTestVector::new(&tx, &[tx.clone()], &tx);
+        let json = serde_json::to_string(&test_vector).unwrap();
+        let parsed: TransactionsTestVector = serde_json::from_str(&json).unwrap();
+        parsed
+            .check(&tx, &[tx.clone()], &tx)
+            .expect("matching transactions");
+        parsed.check(&tx, &[], &tx).expect_err("missing CET");
+    }
+}
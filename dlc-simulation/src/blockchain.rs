@@ -0,0 +1,343 @@
+//! #SimBlockchain
+//! An in-memory chain whose blocks are only mined, and possibly disconnected,
+//! when requested by the scenario.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{
+    Address, Block, BlockHash, BlockHeader, OutPoint, PackedLockTime, Script, Sequence,
+    Transaction, TxIn, TxMerkleNode, TxOut, Txid, Witness,
+};
+use dlc_manager::{error::Error, Blockchain, Time, Utxo};
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+use simple_wallet::WalletBlockchainProvider;
+
+use crate::clock::SimClock;
+
+/// The fee rate, in satoshis per virtual byte, returned until changed using
+/// [`SimBlockchain::set_fee_rate`].
+pub const DEFAULT_FEE_RATE: u64 = 2;
+
+struct ChainState {
+    blocks: Vec<Block>,
+    mempool: Vec<Transaction>,
+    transactions: HashMap<Txid, Transaction>,
+    fee_rate: u64,
+    nb_mined: u32,
+    nb_funding_txs: u32,
+}
+
+impl ChainState {
+    fn confirmed_transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.blocks.iter().flat_map(|x| x.txdata.iter())
+    }
+
+    fn all_transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.confirmed_transactions().chain(self.mempool.iter())
+    }
+
+    fn get_spend(&self, outpoint: &OutPoint) -> Option<&Transaction> {
+        self.all_transactions()
+            .find(|tx| tx.input.iter().any(|x| x.previous_output == *outpoint))
+    }
+
+    fn get_height(&self, txid: &Txid) -> Option<u64> {
+        self.blocks
+            .iter()
+            .position(|x| x.txdata.iter().any(|tx| tx.txid() == *txid))
+            .map(|x| x as u64)
+    }
+}
+
+/// A chain starting with an empty genesis block, timestamped using the
+/// shared [`SimClock`]. Transactions are accepted in the mempool as long as
+/// they do not conflict with known ones, without checking their scripts,
+/// amounts or lock times.
+pub struct SimBlockchain {
+    clock: Rc<SimClock>,
+    state: RefCell<ChainState>,
+}
+
+impl SimBlockchain {
+    /// Creates a chain containing only a genesis block.
+    pub fn new(clock: Rc<SimClock>) -> Self {
+        let blockchain = SimBlockchain {
+            clock,
+            state: RefCell::new(ChainState {
+                blocks: Vec::new(),
+                mempool: Vec::new(),
+                transactions: HashMap::new(),
+                fee_rate: DEFAULT_FEE_RATE,
+                nb_mined: 0,
+                nb_funding_txs: 0,
+            }),
+        };
+        blockchain.mine_block();
+        blockchain
+    }
+
+    /// Mines a block including all the transactions of the mempool, on top of
+    /// the current tip.
+    pub fn mine_block(&self) {
+        let mut state = self.state.borrow_mut();
+        let prev_blockhash = state
+            .blocks
+            .last()
+            .map_or_else(BlockHash::all_zeros, |x| x.block_hash());
+        let header = BlockHeader {
+            version: 1,
+            prev_blockhash,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: self.clock.unix_time_now() as u32,
+            bits: 0,
+            // Differentiates blocks replacing disconnected ones.
+            nonce: state.nb_mined,
+        };
+        let txdata = std::mem::take(&mut state.mempool);
+        state.blocks.push(Block { header, txdata });
+        state.nb_mined += 1;
+    }
+
+    /// Disconnects the given number of blocks from the tip, their
+    /// transactions going back to the mempool.
+    pub fn reorg(&self, depth: u64) {
+        let mut state = self.state.borrow_mut();
+        assert!(
+            (depth as usize) < state.blocks.len(),
+            "Cannot disconnect the genesis block"
+        );
+        let new_len = state.blocks.len() - depth as usize;
+        let mut txs = state
+            .blocks
+            .split_off(new_len)
+            .into_iter()
+            .flat_map(|x| x.txdata)
+            .collect::<Vec<_>>();
+        txs.append(&mut state.mempool);
+        state.mempool = txs;
+    }
+
+    /// Removes the transaction with the given id from the mempool, as if it
+    /// had been evicted. Returns whether the transaction was in the mempool.
+    pub fn evict(&self, txid: &Txid) -> bool {
+        let mut state = self.state.borrow_mut();
+        let len = state.mempool.len();
+        state.mempool.retain(|x| x.txid() != *txid);
+        state.mempool.len() != len
+    }
+
+    /// Whether the transaction with the given id is in the mempool.
+    pub fn is_in_mempool(&self, txid: &Txid) -> bool {
+        self.state
+            .borrow()
+            .mempool
+            .iter()
+            .any(|x| x.txid() == *txid)
+    }
+
+    /// Sets the fee rate returned by [`Blockchain::get_fee_rate`].
+    pub fn set_fee_rate(&self, fee_rate: u64) {
+        self.state.borrow_mut().fee_rate = fee_rate;
+    }
+
+    /// Adds to the mempool a transaction paying the given value to the given
+    /// address, spending an output unknown to the chain.
+    pub fn fund_address(&self, address: &Address, value: u64) -> Txid {
+        let mut state = self.state.borrow_mut();
+        let tx = Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), state.nb_funding_txs),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+        state.nb_funding_txs += 1;
+        let txid = tx.txid();
+        state.transactions.insert(txid, tx.clone());
+        state.mempool.push(tx);
+        txid
+    }
+}
+
+impl Blockchain for SimBlockchain {
+    fn send_transaction(&self, transaction: &Transaction) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        let txid = transaction.txid();
+        if state.all_transactions().any(|x| x.txid() == txid) {
+            return Ok(());
+        }
+        if let Some(conflict) = transaction
+            .input
+            .iter()
+            .find_map(|x| state.get_spend(&x.previous_output))
+        {
+            return Err(Error::BlockchainError(format!(
+                "Transaction {} conflicts with {}",
+                txid,
+                conflict.txid()
+            )));
+        }
+        state.transactions.insert(txid, transaction.clone());
+        state.mempool.push(transaction.clone());
+        Ok(())
+    }
+
+    fn get_network(&self) -> Result<bitcoin::Network, Error> {
+        Ok(bitcoin::Network::Regtest)
+    }
+
+    fn get_blockchain_height(&self) -> Result<u64, Error> {
+        Ok(self.state.borrow().blocks.len() as u64 - 1)
+    }
+
+    fn get_block_at_height(&self, height: u64) -> Result<Block, Error> {
+        self.state
+            .borrow()
+            .blocks
+            .get(height as usize)
+            .cloned()
+            .ok_or_else(|| Error::BlockchainError(format!("No block at height {}", height)))
+    }
+
+    fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, Error> {
+        self.state
+            .borrow()
+            .transactions
+            .get(tx_id)
+            .cloned()
+            .ok_or_else(|| Error::BlockchainError(format!("Unknown transaction {}", tx_id)))
+    }
+
+    fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32, Error> {
+        let state = self.state.borrow();
+        Ok(state
+            .get_height(tx_id)
+            .map_or(0, |h| (state.blocks.len() as u64 - h) as u32))
+    }
+
+    fn get_fee_rate(&self, _confirmation_target: u32) -> Result<u64, Error> {
+        Ok(self.state.borrow().fee_rate)
+    }
+
+    fn get_mempool_spend(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, Error> {
+        Ok(self
+            .state
+            .borrow()
+            .mempool
+            .iter()
+            .find(|tx| tx.input.iter().any(|x| x.previous_output == *outpoint))
+            .cloned())
+    }
+}
+
+impl WalletBlockchainProvider for SimBlockchain {
+    fn get_utxos_for_address(&self, address: &Address) -> Result<Vec<Utxo>, Error> {
+        let state = self.state.borrow();
+        let script_pubkey = address.script_pubkey();
+        Ok(state
+            .all_transactions()
+            .flat_map(|tx| {
+                let txid = tx.txid();
+                tx.output
+                    .iter()
+                    .enumerate()
+                    .map(move |(vout, x)| (OutPoint::new(txid, vout as u32), x))
+            })
+            .filter(|(outpoint, x)| {
+                x.script_pubkey == script_pubkey && state.get_spend(outpoint).is_none()
+            })
+            .map(|(outpoint, x)| Utxo {
+                tx_out: x.clone(),
+                outpoint,
+                address: address.clone(),
+                redeem_script: Script::new(),
+                reserved: false,
+            })
+            .collect())
+    }
+
+    fn is_output_spent(&self, txid: &Txid, vout: u32) -> Result<bool, Error> {
+        Ok(self
+            .state
+            .borrow()
+            .get_spend(&OutPoint::new(*txid, vout))
+            .is_some())
+    }
+}
+
+impl FeeEstimator for SimBlockchain {
+    fn get_est_sat_per_1000_weight(&self, _confirmation_target: ConfirmationTarget) -> u32 {
+        // 250 satoshis per 1000 weight units for each satoshi per virtual
+        // byte, never below the minimum relay fee.
+        std::cmp::max(self.state.borrow().fee_rate as u32 * 250, 253)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_address() -> Address {
+        let secp = secp256k1_zkp::Secp256k1::new();
+        let (_, public_key) = secp.generate_keypair(&mut secp256k1_zkp::rand::thread_rng());
+        Address::p2wpkh(
+            &bitcoin::PublicKey::new(public_key),
+            bitcoin::Network::Regtest,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn reorg_returns_transactions_to_mempool() {
+        let blockchain = SimBlockchain::new(Rc::new(SimClock::new(1000)));
+        let txid = blockchain.fund_address(&get_address(), 1000);
+        blockchain.mine_block();
+        blockchain.mine_block();
+        assert_eq!(2, blockchain.get_transaction_confirmations(&txid).unwrap());
+
+        blockchain.reorg(2);
+        assert_eq!(0, blockchain.get_blockchain_height().unwrap());
+        assert_eq!(0, blockchain.get_transaction_confirmations(&txid).unwrap());
+        assert!(blockchain.is_in_mempool(&txid));
+
+        blockchain.mine_block();
+        assert_eq!(1, blockchain.get_transaction_confirmations(&txid).unwrap());
+    }
+
+    #[test]
+    fn conflicting_transaction_is_rejected() {
+        let blockchain = SimBlockchain::new(Rc::new(SimClock::new(1000)));
+        let address = get_address();
+        let txid = blockchain.fund_address(&address, 1000);
+        let spend = |value| Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(txid, 0),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+
+        blockchain.send_transaction(&spend(900)).unwrap();
+        blockchain
+            .send_transaction(&spend(800))
+            .expect_err("to reject a double spend");
+        assert_eq!(1, blockchain.get_utxos_for_address(&address).unwrap().len());
+    }
+}
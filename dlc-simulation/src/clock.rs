@@ -0,0 +1,32 @@
+//! #SimClock
+//! The time shared by all the nodes of a simulation, only moving forward when
+//! advanced by the scenario.
+
+use std::cell::Cell;
+
+use dlc_manager::Time;
+
+/// A clock returning a unix time set by the scenario.
+pub struct SimClock {
+    now: Cell<u64>,
+}
+
+impl SimClock {
+    /// Creates a clock starting at the given unix time.
+    pub fn new(now: u64) -> Self {
+        SimClock {
+            now: Cell::new(now),
+        }
+    }
+
+    /// Moves the clock forward by the given number of seconds.
+    pub fn advance(&self, seconds: u64) {
+        self.now.set(self.now.get() + seconds);
+    }
+}
+
+impl Time for SimClock {
+    fn unix_time_now(&self) -> u64 {
+        self.now.get()
+    }
+}
@@ -0,0 +1,407 @@
+//! # dlc-simulation
+//! Harness running deterministic end to end scenarios between several
+//! [`Manager`] instances without bitcoind. The nodes share a [`SimClock`], a
+//! [`SimBlockchain`] and a [`SimOracle`] scripted by the scenario, and
+//! exchange messages through an in-memory [`Network`] whose messages are only
+//! delivered when requested. Scenarios such as missed attestations, chain
+//! reorganizations or disconnections in the middle of a protocol can thus be
+//! replayed step by step, e.g.:
+//!
+//! ```ignore
+//! let mut sim = Simulation::new(2);
+//! let input = sim.enum_contract_input("event", &payouts, 1000, 1000);
+//! let temporary_id = sim.offer(0, 1, &input)?;
+//! sim.deliver_all();
+//! sim.accept(1, &temporary_id)?;
+//! sim.deliver_all();
+//! sim.mine_blocks(NB_CONFIRMATIONS as u64);
+//! sim.periodic_check_all();
+//! ```
+
+#![deny(missing_docs)]
+
+pub mod blockchain;
+pub mod clock;
+pub mod network;
+pub mod oracle;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use bitcoin::Network as BitcoinNetwork;
+use dlc::EnumerationPayout;
+use dlc_manager::contract::contract_input::{ContractInput, ContractInputInfo, OracleInput};
+use dlc_manager::contract::enum_descriptor::EnumDescriptor;
+use dlc_manager::contract::multi_party_contract::{MultiPartyContract, MultiPartyContractInput};
+use dlc_manager::contract::{Contract, ContractDescriptor, ContractState};
+use dlc_manager::error::Error;
+use dlc_manager::manager::Manager;
+use dlc_manager::{ContractId, Oracle, Storage, Wallet};
+use dlc_messages::oracle_msgs::{EnumEventDescriptor, EventDescriptor};
+use dlc_messages::Message;
+use mocks::memory_storage_provider::MemoryStorage;
+use secp256k1_zkp::rand::thread_rng;
+use secp256k1_zkp::{PublicKey, Secp256k1, SecretKey};
+use simple_wallet::SimpleWallet;
+
+pub use crate::blockchain::SimBlockchain;
+pub use crate::clock::SimClock;
+pub use crate::network::{InFlightMessage, Network};
+pub use crate::oracle::SimOracle;
+
+/// The unix time at which simulations start.
+pub const START_TIME: u64 = 1700000000;
+/// The number of seconds between two blocks mined by
+/// [`Simulation::mine_blocks`].
+pub const BLOCK_INTERVAL: u64 = 600;
+/// The number of blocks mined when starting a simulation, enough for the
+/// median time past to be defined.
+const NB_INITIAL_BLOCKS: u64 = 11;
+/// The number of UTXOs held by each node at the start of a simulation.
+pub const NB_FUNDING_UTXOS: usize = 5;
+/// The value of each of the UTXOs held by the nodes at the start of a
+/// simulation.
+pub const FUNDING_UTXO_VALUE: u64 = 100000000;
+
+/// The wallet of a simulated node.
+pub type SimWallet = SimpleWallet<Rc<SimBlockchain>, Rc<MemoryStorage>>;
+
+/// The manager of a simulated node.
+pub type SimManager = Manager<
+    Rc<SimWallet>,
+    Rc<SimBlockchain>,
+    Rc<MemoryStorage>,
+    Rc<SimOracle>,
+    Rc<SimClock>,
+    Rc<SimBlockchain>,
+>;
+
+/// A participant to the simulation.
+pub struct Node {
+    /// The id with which the node is known to its peers.
+    pub node_id: PublicKey,
+    /// The manager of the node.
+    pub manager: SimManager,
+    /// The wallet funding the contracts of the node.
+    pub wallet: Rc<SimWallet>,
+    /// The storage of both the manager and the wallet.
+    pub storage: Rc<MemoryStorage>,
+}
+
+/// A set of nodes together with the environment they run in.
+pub struct Simulation {
+    /// The clock shared by all nodes.
+    pub clock: Rc<SimClock>,
+    /// The chain shared by all nodes.
+    pub blockchain: Rc<SimBlockchain>,
+    /// The oracle known to all nodes.
+    pub oracle: Rc<SimOracle>,
+    /// The transport between the nodes.
+    pub network: Network,
+    nodes: Vec<Node>,
+}
+
+impl Simulation {
+    /// Creates a simulation with the given number of nodes, each holding
+    /// [`NB_FUNDING_UTXOS`] confirmed UTXOs.
+    pub fn new(nb_nodes: usize) -> Self {
+        let clock = Rc::new(SimClock::new(START_TIME));
+        let blockchain = Rc::new(SimBlockchain::new(clock.clone()));
+        let oracle = Rc::new(SimOracle::new());
+        let secp = Secp256k1::new();
+
+        let nodes = (0..nb_nodes)
+            .map(|_| {
+                let storage = Rc::new(MemoryStorage::new());
+                let wallet = Rc::new(SimpleWallet::new(
+                    blockchain.clone(),
+                    storage.clone(),
+                    BitcoinNetwork::Regtest,
+                ));
+                for _ in 0..NB_FUNDING_UTXOS {
+                    let address = wallet.get_new_address().expect("to get an address");
+                    blockchain.fund_address(&address, FUNDING_UTXO_VALUE);
+                }
+                let mut oracles = HashMap::new();
+                oracles.insert(oracle.get_public_key(), oracle.clone());
+                let manager = Manager::new(
+                    wallet.clone(),
+                    blockchain.clone(),
+                    storage.clone(),
+                    oracles,
+                    clock.clone(),
+                    blockchain.clone(),
+                )
+                .expect("to create a manager");
+                Node {
+                    node_id: PublicKey::from_secret_key(&secp, &SecretKey::new(&mut thread_rng())),
+                    manager,
+                    wallet,
+                    storage,
+                }
+            })
+            .collect();
+
+        let mut simulation = Simulation {
+            clock,
+            blockchain,
+            oracle,
+            network: Network::default(),
+            nodes,
+        };
+        simulation.mine_blocks(NB_INITIAL_BLOCKS);
+        simulation
+    }
+
+    /// Returns the node with the given index.
+    pub fn node(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+
+    /// Returns the node with the given index, e.g. to call its manager.
+    pub fn node_mut(&mut self, index: usize) -> &mut Node {
+        &mut self.nodes[index]
+    }
+
+    /// Returns the index of the node with the given id.
+    pub fn get_node_index(&self, node_id: &PublicKey) -> Option<usize> {
+        self.nodes.iter().position(|x| x.node_id == *node_id)
+    }
+
+    /// Returns the contract with the given id stored by the given node.
+    pub fn get_contract(&self, node: usize, contract_id: &ContractId) -> Option<Contract> {
+        self.nodes[node]
+            .storage
+            .get_contract(contract_id)
+            .expect("the memory storage not to fail")
+    }
+
+    /// Returns the state of the contract with the given id stored by the
+    /// given node.
+    pub fn get_contract_state(
+        &self,
+        node: usize,
+        contract_id: &ContractId,
+    ) -> Option<ContractState> {
+        self.get_contract(node, contract_id).map(|x| x.get_state())
+    }
+
+    /// Announces an enumerated event with the given outcomes, maturing at the
+    /// given unix time.
+    pub fn add_enum_event(&self, event_id: &str, outcomes: &[String], maturity: u64) {
+        self.oracle.add_event(
+            event_id,
+            &EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: outcomes.to_vec(),
+            }),
+            maturity as u32,
+        );
+    }
+
+    /// Returns the input of a contract on an event announced using
+    /// [`Simulation::add_enum_event`].
+    pub fn enum_contract_input(
+        &self,
+        event_id: &str,
+        outcome_payouts: &[EnumerationPayout],
+        offer_collateral: u64,
+        accept_collateral: u64,
+    ) -> ContractInput {
+        ContractInput {
+            offer_collateral,
+            accept_collateral,
+            fee_rate: 0,
+            contract_infos: vec![ContractInputInfo {
+                contract_descriptor: ContractDescriptor::Enum(EnumDescriptor {
+                    outcome_payouts: outcome_payouts.to_vec(),
+                }),
+                oracles: OracleInput {
+                    public_keys: vec![self.oracle.get_public_key()],
+                    event_id: event_id.to_string(),
+                    event_ids: Vec::new(),
+                    threshold: 1,
+                },
+            }],
+            metadata: None,
+            payout_script_pubkey: None,
+            change_script_pubkey: None,
+            additional_payout_outputs: Vec::new(),
+        }
+    }
+
+    /// Creates an offer from `from` to `to` and sends it, returning the
+    /// temporary id of the contract.
+    pub fn offer(
+        &mut self,
+        from: usize,
+        to: usize,
+        contract_input: &ContractInput,
+    ) -> Result<ContractId, Error> {
+        let counter_party = self.nodes[to].node_id;
+        let offer = self.nodes[from]
+            .manager
+            .send_offer(contract_input, counter_party)?;
+        let temporary_contract_id = offer.temporary_contract_id;
+        self.network.send(from, to, Message::Offer(offer));
+        Ok(temporary_contract_id)
+    }
+
+    /// Accepts the offer with the given temporary id received by the given
+    /// node and sends the accept message, returning the id of the contract.
+    pub fn accept(
+        &mut self,
+        node: usize,
+        temporary_contract_id: &ContractId,
+    ) -> Result<ContractId, Error> {
+        let (contract_id, counter_party, accept) = self.nodes[node]
+            .manager
+            .accept_contract_offer(temporary_contract_id)?;
+        let to = self
+            .get_node_index(&counter_party)
+            .expect("the counter party to be part of the simulation");
+        self.network.send(node, to, Message::Accept(accept));
+        Ok(contract_id)
+    }
+
+    /// Accepts the offer rolling over a contract with the given temporary id
+    /// received by the given node, which expects to be attributed
+    /// `counter_payout` out of the rolled over contract, and sends the accept
+    /// message, returning the id of the contract.
+    pub fn accept_rollover(
+        &mut self,
+        node: usize,
+        temporary_contract_id: &ContractId,
+        counter_payout: u64,
+    ) -> Result<ContractId, Error> {
+        let (contract_id, counter_party, accept) = self.nodes[node]
+            .manager
+            .accept_rollover_offer(temporary_contract_id, counter_payout)?;
+        let to = self
+            .get_node_index(&counter_party)
+            .expect("the counter party to be part of the simulation");
+        self.network.send(node, to, Message::Accept(accept));
+        Ok(contract_id)
+    }
+
+    /// Creates a contract coordinated by `coordinator` between it and the
+    /// given participants and sends them the offers, returning the temporary
+    /// id of the contract.
+    pub fn offer_multi_party(
+        &mut self,
+        coordinator: usize,
+        participants: &[usize],
+        contract_input: &MultiPartyContractInput,
+    ) -> Result<ContractId, Error> {
+        let participant_ids: Vec<_> = participants
+            .iter()
+            .map(|x| self.nodes[*x].node_id)
+            .collect();
+        let offers = self.nodes[coordinator]
+            .manager
+            .send_multi_party_offer(contract_input, &participant_ids)?;
+        let temporary_contract_id = offers[0].1.temporary_contract_id;
+        for (participant, offer) in offers {
+            let to = self
+                .get_node_index(&participant)
+                .expect("the participant to be part of the simulation");
+            self.network
+                .send(coordinator, to, Message::MultiPartyOffer(offer));
+        }
+        Ok(temporary_contract_id)
+    }
+
+    /// Accepts the multi party contract offer with the given temporary id
+    /// received by the given node and sends the accept message to the
+    /// coordinator.
+    pub fn accept_multi_party(
+        &mut self,
+        node: usize,
+        temporary_contract_id: &ContractId,
+    ) -> Result<(), Error> {
+        let (coordinator, accept) = self.nodes[node]
+            .manager
+            .accept_multi_party_offer(temporary_contract_id)?;
+        let to = self
+            .get_node_index(&coordinator)
+            .expect("the coordinator to be part of the simulation");
+        self.network
+            .send(node, to, Message::MultiPartyAccept(accept));
+        Ok(())
+    }
+
+    /// Returns the multi party contracts stored by the given node.
+    pub fn get_multi_party_contracts(&self, node: usize) -> Vec<MultiPartyContract> {
+        self.nodes[node]
+            .storage
+            .get_multi_party_contracts()
+            .expect("the memory storage not to fail")
+    }
+
+    /// Delivers the next message in flight, queuing the replies of the
+    /// receiving node. Returns `None` if no message was in flight, or the
+    /// result of processing the message otherwise.
+    pub fn deliver_next(&mut self) -> Option<Result<(), Error>> {
+        let InFlightMessage { from, to, message } = self.network.pop()?;
+        let from_id = self.nodes[from].node_id;
+        let res = self.nodes[to].manager.on_dlc_message(&message, from_id);
+        let res = res.map(|reply| {
+            if let Some(reply) = reply {
+                self.network.send(to, from, reply);
+            }
+        });
+        let mut pending = self.nodes[to]
+            .manager
+            .get_and_clear_pending_multi_party_messages();
+        pending.extend(
+            self.nodes[to]
+                .manager
+                .get_and_clear_pending_novation_messages(),
+        );
+        for (peer, msg) in pending {
+            if let Some(peer) = self.get_node_index(&peer) {
+                self.network.send(to, peer, msg);
+            }
+        }
+        Some(res)
+    }
+
+    /// Delivers messages until none is in flight, returning the errors
+    /// raised while processing them.
+    pub fn deliver_all(&mut self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        while let Some(res) = self.deliver_next() {
+            if let Err(e) = res {
+                errors.push(e);
+            }
+        }
+        errors
+    }
+
+    /// Mines the given number of blocks, [`BLOCK_INTERVAL`] seconds apart,
+    /// and refreshes the wallets of the nodes.
+    pub fn mine_blocks(&mut self, nb_blocks: u64) {
+        for _ in 0..nb_blocks {
+            self.clock.advance(BLOCK_INTERVAL);
+            self.blockchain.mine_block();
+        }
+        for node in &self.nodes {
+            node.wallet.refresh().expect("to refresh the wallet");
+        }
+    }
+
+    /// Disconnects the given number of blocks from the tip of the chain, their
+    /// transactions going back to the mempool.
+    pub fn reorg(&mut self, depth: u64) {
+        self.blockchain.reorg(depth);
+    }
+
+    /// Calls [`Manager::periodic_check`] on each node, returning the errors
+    /// along with the index of the node that raised them.
+    pub fn periodic_check_all(&mut self) -> Vec<(usize, Error)> {
+        self.nodes
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, node)| node.manager.periodic_check().err().map(|e| (i, e)))
+            .collect()
+    }
+}
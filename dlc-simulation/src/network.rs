@@ -0,0 +1,71 @@
+//! #Network
+//! In-memory transport between the nodes of a simulation. Messages are queued
+//! until the scenario delivers them, so that their order, loss and the
+//! connectivity between nodes are fully controlled.
+
+use std::collections::{HashSet, VecDeque};
+
+use dlc_messages::Message;
+
+/// A message sent by a node that was not delivered yet.
+#[derive(Clone, Debug)]
+pub struct InFlightMessage {
+    /// The index of the sending node.
+    pub from: usize,
+    /// The index of the receiving node.
+    pub to: usize,
+    /// The message.
+    pub message: Message,
+}
+
+/// The queue of messages in flight, delivered in the order they were sent.
+#[derive(Default)]
+pub struct Network {
+    in_flight: VecDeque<InFlightMessage>,
+    disconnected: HashSet<(usize, usize)>,
+}
+
+fn get_link(a: usize, b: usize) -> (usize, usize) {
+    (std::cmp::min(a, b), std::cmp::max(a, b))
+}
+
+impl Network {
+    /// Queues the given message, unless the nodes are disconnected in which
+    /// case it is lost. Returns whether the message was queued.
+    pub fn send(&mut self, from: usize, to: usize, message: Message) -> bool {
+        if !self.is_connected(from, to) {
+            return false;
+        }
+        self.in_flight
+            .push_back(InFlightMessage { from, to, message });
+        true
+    }
+
+    /// Removes the next message to deliver from the queue.
+    pub fn pop(&mut self) -> Option<InFlightMessage> {
+        self.in_flight.pop_front()
+    }
+
+    /// Returns the messages waiting to be delivered.
+    pub fn in_flight(&self) -> &VecDeque<InFlightMessage> {
+        &self.in_flight
+    }
+
+    /// Whether messages sent between the given nodes are delivered.
+    pub fn is_connected(&self, a: usize, b: usize) -> bool {
+        !self.disconnected.contains(&get_link(a, b))
+    }
+
+    /// Disconnects the given nodes, losing the messages in flight between
+    /// them as well as the ones sent until they are reconnected.
+    pub fn disconnect(&mut self, a: usize, b: usize) {
+        self.disconnected.insert(get_link(a, b));
+        self.in_flight
+            .retain(|x| get_link(x.from, x.to) != get_link(a, b));
+    }
+
+    /// Reconnects the given nodes.
+    pub fn reconnect(&mut self, a: usize, b: usize) {
+        self.disconnected.remove(&get_link(a, b));
+    }
+}
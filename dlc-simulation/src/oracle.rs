@@ -0,0 +1,76 @@
+//! #SimOracle
+//! An oracle whose events and attestations are added by the scenario, and
+//! which can be made unreachable.
+
+use std::cell::{Cell, RefCell};
+
+use dlc_manager::error::Error;
+use dlc_manager::Oracle;
+use dlc_messages::oracle_msgs::{EventDescriptor, OracleAnnouncement, OracleAttestation};
+use mocks::mock_oracle_provider::MockOracle;
+use secp256k1_zkp::XOnlyPublicKey;
+
+/// Wraps a [`MockOracle`] so that it can be scripted while shared with the
+/// managers.
+pub struct SimOracle {
+    inner: RefCell<MockOracle>,
+    is_available: Cell<bool>,
+}
+
+impl SimOracle {
+    /// Creates an oracle with a random key and no events.
+    pub fn new() -> Self {
+        SimOracle {
+            inner: RefCell::new(MockOracle::new()),
+            is_available: Cell::new(true),
+        }
+    }
+
+    /// Announces an event maturing at the given unix time.
+    pub fn add_event(&self, event_id: &str, event_descriptor: &EventDescriptor, maturity: u32) {
+        self.inner
+            .borrow_mut()
+            .add_event(event_id, event_descriptor, maturity);
+    }
+
+    /// Attests to the given outcomes of a previously announced event.
+    pub fn add_attestation(&self, event_id: &str, outcomes: &[String]) {
+        self.inner.borrow_mut().add_attestation(event_id, outcomes);
+    }
+
+    /// Sets whether requests to the oracle succeed, to simulate an outage.
+    pub fn set_available(&self, is_available: bool) {
+        self.is_available.set(is_available);
+    }
+
+    fn check_available(&self) -> Result<(), Error> {
+        if self.is_available.get() {
+            Ok(())
+        } else {
+            Err(Error::OracleError("Oracle is unreachable".to_string()))
+        }
+    }
+}
+
+impl Default for SimOracle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Oracle for SimOracle {
+    fn get_public_key(&self) -> XOnlyPublicKey {
+        // `MockOracle` also implements `AsyncOracle`, hence the explicit calls.
+        Oracle::get_public_key(&*self.inner.borrow())
+    }
+
+    fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement, Error> {
+        self.check_available()?;
+        Oracle::get_announcement(&*self.inner.borrow(), event_id)
+    }
+
+    fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation, Error> {
+        self.check_available()?;
+        Oracle::get_attestation(&*self.inner.borrow(), event_id)
+    }
+}
@@ -0,0 +1,538 @@
+use bitcoin::hashes::Hash;
+use bitcoin::{Script, WScriptHash};
+use dlc::{EnumerationPayout, Payout};
+use dlc_manager::contract::contract_input::PayoutOutputInput;
+use dlc_manager::contract::multi_party_contract::{
+    MultiPartyContractInput, MultiPartyContractState,
+};
+use dlc_manager::contract::novation_contract::NovationState;
+use dlc_manager::contract::{Contract, ContractState};
+use dlc_manager::manager::{NB_CONFIRMATIONS, REFUND_DELAY};
+use dlc_manager::{Blockchain, ContractId, Oracle, Storage};
+use dlc_messages::multi_party::MultiPartyOutcome;
+use dlc_messages::Message;
+use dlc_simulation::{Simulation, START_TIME};
+
+const EVENT_ID: &str = "event";
+const COLLATERAL: u64 = 50000000;
+/// Leaves enough time to establish and confirm the contracts before the
+/// event matures.
+const MATURITY: u64 = START_TIME + 86400;
+
+fn outcomes() -> Vec<String> {
+    vec!["offer_wins".to_string(), "accept_wins".to_string()]
+}
+
+fn payouts() -> Vec<EnumerationPayout> {
+    vec![
+        EnumerationPayout {
+            outcome: "offer_wins".to_string(),
+            payout: Payout {
+                offer: 2 * COLLATERAL,
+                accept: 0,
+            },
+        },
+        EnumerationPayout {
+            outcome: "accept_wins".to_string(),
+            payout: Payout {
+                offer: 0,
+                accept: 2 * COLLATERAL,
+            },
+        },
+    ]
+}
+
+fn assert_states(sim: &Simulation, contract_id: &ContractId, state: ContractState) {
+    for node in 0..2 {
+        assert_eq!(Some(state), sim.get_contract_state(node, contract_id));
+    }
+}
+
+/// Offers a contract from node 0 to node 1 and delivers all the messages,
+/// returning the id of the established contract.
+fn establish_contract(sim: &mut Simulation) -> ContractId {
+    sim.add_enum_event(EVENT_ID, &outcomes(), MATURITY);
+    let contract_input = sim.enum_contract_input(EVENT_ID, &payouts(), COLLATERAL, COLLATERAL);
+    let temporary_id = sim.offer(0, 1, &contract_input).unwrap();
+    assert!(sim.deliver_all().is_empty());
+    let contract_id = sim.accept(1, &temporary_id).unwrap();
+    assert!(sim.deliver_all().is_empty());
+    assert_states(sim, &contract_id, ContractState::Signed);
+    contract_id
+}
+
+fn confirm_contract(sim: &mut Simulation, contract_id: &ContractId) {
+    sim.mine_blocks(NB_CONFIRMATIONS as u64);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_states(sim, contract_id, ContractState::Confirmed);
+}
+
+fn get_fund_txid(sim: &Simulation, contract_id: &ContractId) -> bitcoin::Txid {
+    match sim.get_contract(0, contract_id) {
+        Some(Contract::Signed(c)) | Some(Contract::Confirmed(c)) => {
+            c.accepted_contract.dlc_transactions.fund.txid()
+        }
+        _ => panic!("Expected a signed contract"),
+    }
+}
+
+#[test]
+fn attested_contract_is_closed() {
+    let mut sim = Simulation::new(2);
+    let contract_id = establish_contract(&mut sim);
+    confirm_contract(&mut sim, &contract_id);
+
+    sim.clock.advance(MATURITY - START_TIME);
+    sim.oracle
+        .add_attestation(EVENT_ID, &["accept_wins".to_string()]);
+    assert!(sim.periodic_check_all().is_empty());
+    sim.mine_blocks(NB_CONFIRMATIONS as u64);
+    assert!(sim.periodic_check_all().is_empty());
+
+    assert_states(&sim, &contract_id, ContractState::Closed);
+}
+
+#[test]
+fn split_payout_is_sent_to_additional_output() {
+    let mut sim = Simulation::new(2);
+    sim.add_enum_event(EVENT_ID, &outcomes(), MATURITY);
+    let mut contract_input = sim.enum_contract_input(EVENT_ID, &payouts(), COLLATERAL, COLLATERAL);
+    let cold_spk = Script::new_v0_p2wsh(&WScriptHash::hash(&[1]));
+    contract_input.additional_payout_outputs = vec![PayoutOutputInput {
+        script_pubkey: cold_spk.clone(),
+        share_bps: 2500,
+    }];
+    let temporary_id = sim.offer(0, 1, &contract_input).unwrap();
+    assert!(sim.deliver_all().is_empty());
+    let contract_id = sim.accept(1, &temporary_id).unwrap();
+    assert!(sim.deliver_all().is_empty());
+    assert_states(&sim, &contract_id, ContractState::Signed);
+    confirm_contract(&mut sim, &contract_id);
+
+    sim.clock.advance(MATURITY - START_TIME);
+    sim.oracle
+        .add_attestation(EVENT_ID, &["offer_wins".to_string()]);
+    assert!(sim.periodic_check_all().is_empty());
+    sim.mine_blocks(NB_CONFIRMATIONS as u64);
+    assert!(sim.periodic_check_all().is_empty());
+
+    assert_states(&sim, &contract_id, ContractState::Closed);
+    let signed_cet = match sim.get_contract(0, &contract_id) {
+        Some(Contract::Closed(c)) => c.signed_cet.expect("a signed CET"),
+        _ => unreachable!(),
+    };
+    let cold_output = signed_cet
+        .output
+        .iter()
+        .find(|x| x.script_pubkey == cold_spk)
+        .expect("an output paying the additional payout output");
+    assert_eq!(2 * COLLATERAL / 4, cold_output.value);
+}
+
+#[test]
+fn missed_attestation_leads_to_refund() {
+    let mut sim = Simulation::new(2);
+    let contract_id = establish_contract(&mut sim);
+    confirm_contract(&mut sim, &contract_id);
+
+    sim.clock.advance(MATURITY - START_TIME);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_states(&sim, &contract_id, ContractState::Confirmed);
+
+    // The median time past of the chain has to be after the refund lock time.
+    sim.clock.advance(REFUND_DELAY as u64);
+    sim.mine_blocks(11);
+    assert!(sim.periodic_check_all().is_empty());
+
+    assert_states(&sim, &contract_id, ContractState::Refunded);
+    let refund_txid = match sim.get_contract(0, &contract_id) {
+        Some(Contract::Refunded(c)) => c.accepted_contract.dlc_transactions.refund.txid(),
+        _ => unreachable!(),
+    };
+    assert!(sim.blockchain.is_in_mempool(&refund_txid));
+}
+
+#[test]
+fn oracle_outage_delays_closing() {
+    let mut sim = Simulation::new(2);
+    let contract_id = establish_contract(&mut sim);
+    confirm_contract(&mut sim, &contract_id);
+
+    sim.clock.advance(MATURITY - START_TIME);
+    sim.oracle
+        .add_attestation(EVENT_ID, &["offer_wins".to_string()]);
+    sim.oracle.set_available(false);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_states(&sim, &contract_id, ContractState::Confirmed);
+
+    sim.oracle.set_available(true);
+    assert!(sim.periodic_check_all().is_empty());
+    for node in 0..2 {
+        assert!(matches!(
+            sim.get_contract_state(node, &contract_id),
+            Some(ContractState::PreClosed) | Some(ContractState::Closed)
+        ));
+    }
+}
+
+#[test]
+fn reorged_fund_transaction_needs_new_confirmations() {
+    let mut sim = Simulation::new(2);
+    let contract_id = establish_contract(&mut sim);
+    let fund_txid = get_fund_txid(&sim, &contract_id);
+
+    sim.mine_blocks(NB_CONFIRMATIONS as u64 - 1);
+    sim.reorg(NB_CONFIRMATIONS as u64 - 1);
+    assert_eq!(
+        0,
+        sim.blockchain
+            .get_transaction_confirmations(&fund_txid)
+            .unwrap()
+    );
+    sim.mine_blocks(1);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_states(&sim, &contract_id, ContractState::Signed);
+
+    sim.mine_blocks(NB_CONFIRMATIONS as u64 - 1);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_states(&sim, &contract_id, ContractState::Confirmed);
+}
+
+#[test]
+fn lost_sign_message_leaves_accepting_party_waiting() {
+    let mut sim = Simulation::new(2);
+    sim.add_enum_event(EVENT_ID, &outcomes(), MATURITY);
+    let contract_input = sim.enum_contract_input(EVENT_ID, &payouts(), COLLATERAL, COLLATERAL);
+    let temporary_id = sim.offer(0, 1, &contract_input).unwrap();
+    assert!(sim.deliver_all().is_empty());
+    let contract_id = sim.accept(1, &temporary_id).unwrap();
+
+    // The offering party processes the accept message, but its sign message
+    // is lost as the nodes disconnect.
+    assert!(sim.deliver_next().unwrap().is_ok());
+    assert_eq!(1, sim.network.in_flight().len());
+    sim.network.disconnect(0, 1);
+    assert!(sim.deliver_all().is_empty());
+
+    assert_eq!(
+        Some(ContractState::Signed),
+        sim.get_contract_state(0, &contract_id)
+    );
+    assert_eq!(
+        Some(ContractState::Accepted),
+        sim.get_contract_state(1, &contract_id)
+    );
+    let fund_txid = get_fund_txid(&sim, &contract_id);
+    assert!(!sim.blockchain.is_in_mempool(&fund_txid));
+}
+
+#[test]
+fn transferred_contract_is_settled_with_transferee() {
+    let mut sim = Simulation::new(3);
+    let contract_id = establish_contract(&mut sim);
+    confirm_contract(&mut sim, &contract_id);
+
+    let transferee = sim.node(2).node_id;
+    let offer = sim
+        .node_mut(0)
+        .manager
+        .send_novation_offer(&contract_id, &transferee, COLLATERAL / 2, 2)
+        .unwrap();
+    sim.network.send(0, 2, Message::NovationOffer(offer));
+    assert!(sim.deliver_all().is_empty());
+    let (transferor, accept) = sim
+        .node_mut(2)
+        .manager
+        .accept_novation_offer(&contract_id)
+        .unwrap();
+    assert_eq!(sim.node(0).node_id, transferor);
+    sim.network.send(2, 0, Message::NovationAccept(accept));
+    assert!(sim.deliver_all().is_empty());
+
+    let novation = sim
+        .node(2)
+        .storage
+        .get_novation_contract(&contract_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(NovationState::Broadcast, novation.state);
+    let novation_txid = novation.novation_transaction.unwrap().txid();
+    assert!(sim.blockchain.is_in_mempool(&novation_txid));
+    let novated_id = novation
+        .novated_contract
+        .unwrap()
+        .accepted_contract
+        .get_contract_id();
+
+    sim.mine_blocks(NB_CONFIRMATIONS as u64);
+    assert!(sim.periodic_check_all().is_empty());
+
+    for node in 0..3 {
+        let novation = sim
+            .node(node)
+            .storage
+            .get_novation_contract(&contract_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(NovationState::Completed, novation.state);
+    }
+    for node in 0..2 {
+        assert_eq!(
+            Some(ContractState::Closed),
+            sim.get_contract_state(node, &contract_id)
+        );
+    }
+    match sim.get_contract(0, &contract_id) {
+        Some(Contract::Closed(c)) => assert_eq!(-(COLLATERAL as i64) / 2, c.pnl),
+        _ => unreachable!(),
+    }
+    for node in 1..3 {
+        assert_eq!(
+            Some(ContractState::Confirmed),
+            sim.get_contract_state(node, &novated_id)
+        );
+    }
+
+    sim.clock.advance(MATURITY - START_TIME);
+    sim.oracle
+        .add_attestation(EVENT_ID, &["offer_wins".to_string()]);
+    assert!(sim.periodic_check_all().is_empty());
+    sim.mine_blocks(NB_CONFIRMATIONS as u64);
+    assert!(sim.periodic_check_all().is_empty());
+
+    for node in 1..3 {
+        assert_eq!(
+            Some(ContractState::Closed),
+            sim.get_contract_state(node, &novated_id)
+        );
+    }
+    assert_eq!(None, sim.get_contract_state(2, &contract_id));
+}
+
+#[test]
+fn rolled_over_contract_is_closed_once_new_contract_is_confirmed() {
+    let mut sim = Simulation::new(2);
+    let contract_id = establish_contract(&mut sim);
+    confirm_contract(&mut sim, &contract_id);
+
+    let event_id = "rollover_event";
+    sim.add_enum_event(event_id, &outcomes(), MATURITY + 86400);
+    let payouts = payouts()
+        .into_iter()
+        .map(|mut x| {
+            x.payout.offer /= 2;
+            x.payout.accept /= 2;
+            x
+        })
+        .collect::<Vec<_>>();
+    let contract_input =
+        sim.enum_contract_input(event_id, &payouts, COLLATERAL / 2, COLLATERAL / 2);
+    let offer = sim
+        .node_mut(0)
+        .manager
+        .send_rollover_offer(&contract_id, &contract_input, COLLATERAL)
+        .unwrap();
+    let temporary_id = offer.temporary_contract_id;
+    sim.network.send(0, 1, Message::Offer(offer));
+    assert!(sim.deliver_all().is_empty());
+    sim.accept(1, &temporary_id)
+        .expect_err("the split of the rolled over contract to be approved");
+    sim.accept_rollover(1, &temporary_id, COLLATERAL / 2)
+        .expect_err("an unexpected split to be rejected");
+    let rolled_id = sim.accept_rollover(1, &temporary_id, COLLATERAL).unwrap();
+    assert!(sim.deliver_all().is_empty());
+    assert_states(&sim, &rolled_id, ContractState::Signed);
+    assert_states(&sim, &contract_id, ContractState::Confirmed);
+
+    let fund = match sim.get_contract(0, &rolled_id) {
+        Some(Contract::Signed(c)) => c.accepted_contract.dlc_transactions.fund,
+        _ => unreachable!(),
+    };
+    assert_eq!(1, fund.input.len());
+    assert_eq!(
+        get_fund_txid(&sim, &contract_id),
+        fund.input[0].previous_output.txid
+    );
+
+    confirm_contract(&mut sim, &rolled_id);
+    assert_states(&sim, &contract_id, ContractState::Closed);
+    match sim.get_contract(1, &contract_id) {
+        Some(Contract::Closed(c)) => assert_eq!(0, c.pnl),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn topped_up_contract_is_replaced_by_larger_contract() {
+    let mut sim = Simulation::new(2);
+    let contract_id = establish_contract(&mut sim);
+    confirm_contract(&mut sim, &contract_id);
+
+    let event_id = "top_up_event";
+    sim.add_enum_event(event_id, &outcomes(), MATURITY + 86400);
+    let payouts = vec![
+        EnumerationPayout {
+            outcome: "offer_wins".to_string(),
+            payout: Payout {
+                offer: 3 * COLLATERAL,
+                accept: 0,
+            },
+        },
+        EnumerationPayout {
+            outcome: "accept_wins".to_string(),
+            payout: Payout {
+                offer: COLLATERAL,
+                accept: 2 * COLLATERAL,
+            },
+        },
+    ];
+    // The counter party is attributed its collateral and a profit of 1% when
+    // settling the topped up contract.
+    let counter_payout = COLLATERAL + COLLATERAL / 100;
+    let contract_input = sim.enum_contract_input(event_id, &payouts, COLLATERAL, 2 * COLLATERAL);
+    sim.node_mut(0)
+        .manager
+        .send_top_up_offer(&contract_id, &contract_input, counter_payout)
+        .expect_err("a top-up changing the collateral of the counter party");
+
+    let contract_input = sim.enum_contract_input(event_id, &payouts, 2 * COLLATERAL, COLLATERAL);
+    sim.node_mut(0)
+        .manager
+        .send_top_up_offer(&contract_id, &contract_input, COLLATERAL / 2)
+        .expect_err("a payout not covering the collateral of the counter party");
+    let offer = sim
+        .node_mut(0)
+        .manager
+        .send_top_up_offer(&contract_id, &contract_input, counter_payout)
+        .unwrap();
+    let temporary_id = offer.temporary_contract_id;
+    sim.network.send(0, 1, Message::Offer(offer));
+    assert!(sim.deliver_all().is_empty());
+    let topped_up_id = sim
+        .accept_rollover(1, &temporary_id, counter_payout)
+        .unwrap();
+    assert!(sim.deliver_all().is_empty());
+    assert_states(&sim, &topped_up_id, ContractState::Signed);
+
+    let accepted_contract = match sim.get_contract(1, &topped_up_id) {
+        Some(Contract::Signed(c)) => c.accepted_contract,
+        _ => unreachable!(),
+    };
+    let fund = &accepted_contract.dlc_transactions.fund;
+    assert!(fund.input.len() > 1);
+    assert!(fund
+        .input
+        .iter()
+        .any(|x| x.previous_output.txid == get_fund_txid(&sim, &contract_id)));
+    assert_eq!(
+        3 * COLLATERAL,
+        accepted_contract.offered_contract.total_collateral
+    );
+
+    confirm_contract(&mut sim, &topped_up_id);
+    assert_states(&sim, &contract_id, ContractState::Closed);
+    match sim.get_contract(1, &contract_id) {
+        Some(Contract::Closed(c)) => assert_eq!((COLLATERAL / 100) as i64, c.pnl),
+        _ => unreachable!(),
+    }
+}
+
+fn assert_multi_party_states(sim: &Simulation, states: &[MultiPartyContractState]) {
+    for (node, state) in states.iter().enumerate() {
+        let contracts = sim.get_multi_party_contracts(node);
+        assert_eq!(1, contracts.len());
+        assert_eq!(*state, contracts[0].state);
+    }
+}
+
+/// Establishes and confirms a contract between the three nodes of the
+/// simulation, coordinated by node 0, each outcome paying out the total
+/// collateral to one of the nodes.
+fn establish_multi_party_contract(sim: &mut Simulation) {
+    let outcomes: Vec<_> = (0..3).map(|i| format!("node_{}_wins", i)).collect();
+    sim.add_enum_event(EVENT_ID, &outcomes, MATURITY);
+    let contract_input = MultiPartyContractInput {
+        collaterals: vec![COLLATERAL; 3],
+        fee_rate: 0,
+        outcomes: outcomes
+            .iter()
+            .enumerate()
+            .map(|(i, outcome)| {
+                let mut payouts = vec![0; 3];
+                payouts[i] = 3 * COLLATERAL;
+                MultiPartyOutcome {
+                    outcome: outcome.clone(),
+                    payouts,
+                }
+            })
+            .collect(),
+        oracle_public_key: sim.oracle.get_public_key(),
+        event_id: EVENT_ID.to_string(),
+    };
+
+    let temporary_id = sim.offer_multi_party(0, &[1, 2], &contract_input).unwrap();
+    assert!(sim.deliver_all().is_empty());
+    for node in 1..3 {
+        sim.accept_multi_party(node, &temporary_id).unwrap();
+    }
+    assert!(sim.deliver_all().is_empty());
+    assert_multi_party_states(
+        sim,
+        &[
+            MultiPartyContractState::Broadcast,
+            MultiPartyContractState::Signed,
+            MultiPartyContractState::Signed,
+        ],
+    );
+
+    sim.mine_blocks(NB_CONFIRMATIONS as u64);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_multi_party_states(sim, &[MultiPartyContractState::Confirmed; 3]);
+}
+
+#[test]
+fn attested_multi_party_contract_is_closed() {
+    let mut sim = Simulation::new(3);
+    establish_multi_party_contract(&mut sim);
+
+    sim.clock.advance(MATURITY - START_TIME);
+    sim.oracle
+        .add_attestation(EVENT_ID, &["node_2_wins".to_string()]);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_multi_party_states(&sim, &[MultiPartyContractState::Closed; 3]);
+
+    let contract = sim.get_multi_party_contracts(1).remove(0);
+    let cet = contract.closing_tx.expect("a CET");
+    assert!(sim.blockchain.is_in_mempool(&cet.txid()));
+    let payout_spk = &contract.party_params[2].as_ref().unwrap().payout_spk;
+    assert!(cet
+        .output
+        .iter()
+        .any(|x| &x.script_pubkey == payout_spk && x.value == 3 * COLLATERAL));
+}
+
+#[test]
+fn missed_attestation_leads_to_multi_party_refund() {
+    let mut sim = Simulation::new(3);
+    establish_multi_party_contract(&mut sim);
+
+    sim.clock.advance(MATURITY - START_TIME);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_multi_party_states(&sim, &[MultiPartyContractState::Confirmed; 3]);
+
+    // The median time past of the chain has to be after the refund lock time.
+    sim.clock.advance(REFUND_DELAY as u64);
+    sim.mine_blocks(11);
+    assert!(sim.periodic_check_all().is_empty());
+    assert_multi_party_states(&sim, &[MultiPartyContractState::Refunded; 3]);
+
+    let contract = sim.get_multi_party_contracts(0).remove(0);
+    let refund = contract.closing_tx.expect("a refund transaction");
+    assert!(sim.blockchain.is_in_mempool(&refund.txid()));
+    for params in contract.party_params.iter().flatten() {
+        assert!(refund
+            .output
+            .iter()
+            .any(|x| x.script_pubkey == params.payout_spk && x.value == COLLATERAL));
+    }
+}
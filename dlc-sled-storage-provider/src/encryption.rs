@@ -0,0 +1,177 @@
+//! # Encryption
+//! Authenticated encryption of the values persisted in the data base. Keys
+//! are left in clear to enable lookups.
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use dlc_manager::error::Error;
+use secp256k1_zkp::rand::{thread_rng, RngCore};
+
+const KEY_ID_LEN: usize = 4;
+const NONCE_LEN: usize = 24;
+
+/// Provides the keys used to encrypt the values stored in the data base.
+/// Each encrypted value records the identifier of the key used to encrypt it,
+/// so that keys can be rotated by returning a new current key identifier
+/// while still providing the previous keys.
+pub trait KeyProvider: Send + Sync {
+    /// Returns the identifier of the key to use to encrypt new values.
+    fn current_key_id(&self) -> u32;
+    /// Returns the key with the given identifier, or `None` if it is not
+    /// available.
+    fn get_key(&self, key_id: u32) -> Option<[u8; 32]>;
+}
+
+/// A single key, with identifier zero.
+impl KeyProvider for [u8; 32] {
+    fn current_key_id(&self) -> u32 {
+        0
+    }
+
+    fn get_key(&self, key_id: u32) -> Option<[u8; 32]> {
+        if key_id == 0 {
+            Some(*self)
+        } else {
+            None
+        }
+    }
+}
+
+/// Encrypts and decrypts values using XChaCha20-Poly1305 with random nonces.
+/// Values are bound to the tree and key they are stored under so that they
+/// cannot be swapped by someone with access to the data base.
+pub(crate) struct Cipher {
+    key_provider: Box<dyn KeyProvider>,
+}
+
+impl Cipher {
+    pub(crate) fn new(key_provider: Box<dyn KeyProvider>) -> Self {
+        Cipher { key_provider }
+    }
+
+    pub(crate) fn current_key_id(&self) -> u32 {
+        self.key_provider.current_key_id()
+    }
+
+    /// Returns the identifier of the key used to encrypt the given value.
+    pub(crate) fn get_key_id(value: &[u8]) -> Result<u32, Error> {
+        let mut key_id = [0u8; KEY_ID_LEN];
+        key_id.copy_from_slice(
+            value
+                .get(..KEY_ID_LEN)
+                .ok_or_else(|| Error::StorageError("Invalid encrypted value".to_string()))?,
+        );
+        Ok(u32::from_be_bytes(key_id))
+    }
+
+    pub(crate) fn encrypt(&self, tree: &[u8], key: &[u8], value: &[u8]) -> Result<Vec<u8>, Error> {
+        let key_id = self.current_key_id();
+        let mut nonce = [0u8; NONCE_LEN];
+        thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .get_cipher(key_id)?
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: value,
+                    aad: &get_aad(tree, key),
+                },
+            )
+            .map_err(|_| Error::StorageError("Could not encrypt value".to_string()))?;
+
+        let mut res = Vec::with_capacity(KEY_ID_LEN + NONCE_LEN + ciphertext.len());
+        res.extend_from_slice(&key_id.to_be_bytes());
+        res.extend_from_slice(&nonce);
+        res.extend(ciphertext);
+        Ok(res)
+    }
+
+    pub(crate) fn decrypt(&self, tree: &[u8], key: &[u8], value: &[u8]) -> Result<Vec<u8>, Error> {
+        if value.len() < KEY_ID_LEN + NONCE_LEN {
+            return Err(Error::StorageError("Invalid encrypted value".to_string()));
+        }
+        let key_id = Self::get_key_id(value)?;
+        let (nonce, ciphertext) = value[KEY_ID_LEN..].split_at(NONCE_LEN);
+        self.get_cipher(key_id)?
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &get_aad(tree, key),
+                },
+            )
+            .map_err(|_| Error::StorageError("Could not decrypt value".to_string()))
+    }
+
+    fn get_cipher(&self, key_id: u32) -> Result<XChaCha20Poly1305, Error> {
+        let key = self
+            .key_provider
+            .get_key(key_id)
+            .ok_or_else(|| Error::StorageError(format!("Unknown encryption key {}", key_id)))?;
+        Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+}
+
+fn get_aad(tree: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(tree.len() + key.len() + 1);
+    aad.push(tree.len() as u8);
+    aad.extend_from_slice(tree);
+    aad.extend_from_slice(key);
+    aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct RotatedKeys;
+
+    impl KeyProvider for RotatedKeys {
+        fn current_key_id(&self) -> u32 {
+            1
+        }
+
+        fn get_key(&self, key_id: u32) -> Option<[u8; 32]> {
+            if key_id <= 1 {
+                Some([key_id as u8; 32])
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn values_are_bound_to_their_tree_and_key() {
+        let cipher = Cipher::new(Box::new([1u8; 32]));
+        let encrypted = cipher.encrypt(&[1], &[2, 3], b"value").unwrap();
+
+        assert_eq!(
+            b"value".to_vec(),
+            cipher.decrypt(&[1], &[2, 3], &encrypted).unwrap()
+        );
+        cipher
+            .decrypt(&[1, 2], &[3], &encrypted)
+            .expect_err("a different tree and key");
+        cipher
+            .decrypt(&[1], &[2, 4], &encrypted)
+            .expect_err("a different key");
+    }
+
+    #[test]
+    fn previous_keys_can_decrypt() {
+        let old_cipher = Cipher::new(Box::new([0u8; 32]));
+        let encrypted = old_cipher.encrypt(&[1], &[2], b"value").unwrap();
+        let cipher = Cipher::new(Box::new(RotatedKeys));
+
+        assert_eq!(0, Cipher::get_key_id(&encrypted).unwrap());
+        assert_eq!(
+            b"value".to_vec(),
+            cipher.decrypt(&[1], &[2], &encrypted).unwrap()
+        );
+        let encrypted = cipher.encrypt(&[1], &[2], b"value").unwrap();
+        assert_eq!(1, Cipher::get_key_id(&encrypted).unwrap());
+        old_cipher
+            .decrypt(&[1], &[2], &encrypted)
+            .expect_err("an unknown key");
+    }
+}
@@ -12,35 +12,48 @@
 #![deny(missing_docs)]
 
 extern crate dlc_manager;
+extern crate dlc_messages;
 extern crate sled;
 
+#[cfg(feature = "encryption")]
+pub mod encryption;
+
 #[cfg(feature = "wallet")]
 use bitcoin::{Address, Txid};
 use dlc_manager::chain_monitor::ChainMonitor;
-use dlc_manager::channel::accepted_channel::AcceptedChannel;
 use dlc_manager::channel::offered_channel::OfferedChannel;
 use dlc_manager::channel::signed_channel::{SignedChannel, SignedChannelStateType};
-use dlc_manager::channel::{Channel, FailedAccept, FailedSign};
-use dlc_manager::contract::accepted_contract::AcceptedContract;
+use dlc_manager::channel::Channel;
+use dlc_manager::contract::multi_party_contract::MultiPartyContract;
+use dlc_manager::contract::novation_contract::NovationContract;
 use dlc_manager::contract::offered_contract::OfferedContract;
 use dlc_manager::contract::ser::Serializable;
 use dlc_manager::contract::signed_contract::SignedContract;
-use dlc_manager::contract::{
-    ClosedContract, Contract, FailedAcceptContract, FailedSignContract, PreClosedContract,
+use dlc_manager::contract::versioned_ser::{
+    VersionedSerializable, INITIAL_VERSION, SERIALIZATION_VERSION,
 };
+use dlc_manager::contract::{ArchivedContract, Contract, ContractState, PreClosedContract};
 #[cfg(feature = "wallet")]
 use dlc_manager::Utxo;
-use dlc_manager::{error::Error, ContractId, Storage};
+use dlc_manager::{error::Error, ContractId, ContractPage, Storage, StorageWrite};
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
+use dlc_messages::Message;
+#[cfg(feature = "encryption")]
+use encryption::{Cipher, KeyProvider};
 #[cfg(feature = "wallet")]
 use lightning::util::ser::{Readable, Writeable};
 #[cfg(feature = "wallet")]
-use secp256k1_zkp::{PublicKey, SecretKey};
+use secp256k1_zkp::SecretKey;
+use secp256k1_zkp::{PublicKey, XOnlyPublicKey};
 #[cfg(feature = "wallet")]
 use simple_wallet::WalletStorage;
-use sled::transaction::{ConflictableTransactionResult, UnabortableTransactionError};
-use sled::{Db, Transactional, Tree};
+use sled::transaction::{
+    ConflictableTransactionResult, TransactionalTree, UnabortableTransactionError,
+};
+use sled::{Db, IVec, Transactional, Tree};
 use std::convert::TryInto;
 use std::io::{Cursor, Read};
+use std::ops::Bound;
 
 const CONTRACT_TREE: u8 = 1;
 const CHANNEL_TREE: u8 = 2;
@@ -52,10 +65,38 @@ const UTXO_TREE: u8 = 6;
 const KEY_PAIR_TREE: u8 = 7;
 #[cfg(feature = "wallet")]
 const ADDRESS_TREE: u8 = 8;
+const ORACLE_ANNOUNCEMENT_TREE: u8 = 9;
+const ORACLE_ATTESTATION_TREE: u8 = 10;
+const OUTBOUND_MESSAGE_TREE: u8 = 11;
+#[cfg(feature = "encryption")]
+const ENCRYPTION_TREE: u8 = 12;
+#[cfg(feature = "encryption")]
+const ENCRYPTION_CHECK_KEY: u8 = 1;
+const CONTRACT_INDEX_TREE: u8 = 13;
+const ARCHIVED_CONTRACT_TREE: u8 = 14;
+const MULTI_PARTY_CONTRACT_TREE: u8 = 15;
+const NOVATION_CONTRACT_TREE: u8 = 16;
+
+// Keys of the contract index tree. Index keys end with the id of the indexed
+// contract. The index keys of each contract are also stored under its id so
+// that they can be removed when the contract is updated.
+const INDEX_KEYS_PREFIX: u8 = 0;
+const STATE_INDEX_PREFIX: u8 = 1;
+const COUNTER_PARTY_INDEX_PREFIX: u8 = 2;
+const EVENT_INDEX_PREFIX: u8 = 3;
+// Set once the existing contracts have been indexed.
+const INDEX_BUILT_KEY: u8 = 0xff;
+
+// Contract and channel records start with this marker followed by the version
+// of the format used to serialize them. Records written before versioning was
+// introduced start directly with their state prefix, which is never zero.
+const RECORD_VERSION_MARKER: u8 = 0;
 
 /// Implementation of Storage interface using the sled DB backend.
 pub struct SledStorageProvider {
     db: Db,
+    #[cfg(feature = "encryption")]
+    cipher: Option<Cipher>,
 }
 
 macro_rules! convertible_enum {
@@ -157,34 +198,179 @@ where
 impl SledStorageProvider {
     /// Creates a new instance of a SledStorageProvider.
     pub fn new(path: &str) -> Result<Self, sled::Error> {
-        Ok(SledStorageProvider {
-            db: sled::open(path)?,
-        })
+        let db = sled::open(path)?;
+        #[cfg(feature = "encryption")]
+        {
+            if db
+                .open_tree([ENCRYPTION_TREE])?
+                .contains_key([ENCRYPTION_CHECK_KEY])?
+            {
+                return Err(sled::Error::Unsupported(
+                    "The data base is encrypted".to_string(),
+                ));
+            }
+        }
+        let storage = SledStorageProvider {
+            db,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        };
+        storage
+            .build_contract_index()
+            .map_err(|e| sled::Error::Unsupported(e.to_string()))?;
+        Ok(storage)
+    }
+
+    /// Creates a new instance of a SledStorageProvider encrypting the stored
+    /// values with the keys given by `key_provider`. Returns an error if the
+    /// data base contains unencrypted data or if the keys cannot decrypt it.
+    #[cfg(feature = "encryption")]
+    pub fn new_encrypted<K: KeyProvider + 'static>(
+        path: &str,
+        key_provider: K,
+    ) -> Result<Self, Error> {
+        let storage = SledStorageProvider {
+            db: sled::open(path).map_err(to_storage_error)?,
+            cipher: Some(Cipher::new(Box::new(key_provider))),
+        };
+        let tree = storage.open_tree(&[ENCRYPTION_TREE])?;
+        match tree.get([ENCRYPTION_CHECK_KEY]).map_err(to_storage_error)? {
+            Some(check) => {
+                storage.unseal(&tree, &[ENCRYPTION_CHECK_KEY], check)?;
+            }
+            None => {
+                for name in storage.get_tree_names() {
+                    if !storage
+                        .db
+                        .open_tree(name)
+                        .map_err(to_storage_error)?
+                        .is_empty()
+                    {
+                        return Err(Error::StorageError(
+                            "The data base contains unencrypted data".to_string(),
+                        ));
+                    }
+                }
+                let check = storage.seal(&tree, &[ENCRYPTION_CHECK_KEY], Vec::new())?;
+                tree.insert([ENCRYPTION_CHECK_KEY], check)
+                    .map_err(to_storage_error)?;
+            }
+        }
+        storage.build_contract_index()?;
+        Ok(storage)
+    }
+
+    /// Re-encrypts all the values that were not encrypted with the current
+    /// key of the key provider, so that previous keys can be discarded.
+    #[cfg(feature = "encryption")]
+    pub fn rotate_key(&self) -> Result<(), Error> {
+        let cipher = self
+            .cipher
+            .as_ref()
+            .ok_or_else(|| Error::InvalidState("The data base is not encrypted".to_string()))?;
+        let current_key_id = cipher.current_key_id();
+        for name in self.get_tree_names() {
+            let tree = self.db.open_tree(name).map_err(to_storage_error)?;
+            for res in tree.iter() {
+                let (key, value) = res.map_err(to_storage_error)?;
+                if Cipher::get_key_id(&value)? == current_key_id {
+                    continue;
+                }
+                let decrypted = cipher.decrypt(&tree.name(), &key, &value)?;
+                let encrypted = cipher.encrypt(&tree.name(), &key, &decrypted)?;
+                // A failure means that the value was concurrently rewritten,
+                // and thus encrypted with the current key.
+                let _ = tree
+                    .compare_and_swap(key, Some(value), Some(encrypted))
+                    .map_err(to_storage_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the names of the trees whose values are encrypted.
+    #[cfg(feature = "encryption")]
+    fn get_tree_names(&self) -> Vec<IVec> {
+        let default_name = self.db.name();
+        self.db
+            .tree_names()
+            .into_iter()
+            .filter(|name| name != &default_name && name[..] != [CONTRACT_INDEX_TREE])
+            .collect()
+    }
+
+    /// Encrypts the given value to be stored under the given key of the given
+    /// tree if encryption is enabled.
+    #[cfg(feature = "encryption")]
+    fn seal(&self, tree: &Tree, key: &[u8], value: Vec<u8>) -> Result<Vec<u8>, Error> {
+        match &self.cipher {
+            Some(cipher) => cipher.encrypt(&tree.name(), key, &value),
+            None => Ok(value),
+        }
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn seal(&self, _: &Tree, _: &[u8], value: Vec<u8>) -> Result<Vec<u8>, Error> {
+        Ok(value)
     }
 
-    fn get_data_with_prefix<T: Serializable>(
+    /// Decrypts the given value stored under the given key of the given tree
+    /// if encryption is enabled.
+    #[cfg(feature = "encryption")]
+    fn unseal(&self, tree: &Tree, key: &[u8], value: IVec) -> Result<IVec, Error> {
+        match &self.cipher {
+            Some(cipher) => Ok(cipher.decrypt(&tree.name(), key, &value)?.into()),
+            None => Ok(value),
+        }
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn unseal(&self, _: &Tree, _: &[u8], value: IVec) -> Result<IVec, Error> {
+        Ok(value)
+    }
+
+    fn get_value(&self, tree: &Tree, key: &[u8]) -> Result<Option<IVec>, Error> {
+        match tree.get(key).map_err(to_storage_error)? {
+            Some(value) => Ok(Some(self.unseal(tree, key, value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn insert_value(&self, tree: &Tree, key: &[u8], value: Vec<u8>) -> Result<(), Error> {
+        tree.insert(key, self.seal(tree, key, value)?)
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    fn get_values(&self, tree: &Tree) -> Result<Vec<(IVec, IVec)>, Error> {
+        tree.iter()
+            .map(|res| {
+                let (key, value) = res.map_err(to_storage_error)?;
+                let value = self.unseal(tree, &key, value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    fn get_data_with_prefix<T: VersionedSerializable>(
         &self,
         tree: &Tree,
         prefix: &[u8],
         consume: Option<u64>,
     ) -> Result<Vec<T>, Error> {
-        let iter = tree.iter();
-        iter.values()
-            .filter_map(|res| {
-                let value = res.unwrap();
-                let mut cursor = Cursor::new(&value);
-                let mut pref = vec![0u8; prefix.len()];
-                cursor.read_exact(&mut pref).expect("Error reading prefix");
-                if pref == prefix {
-                    if let Some(c) = consume {
-                        cursor.set_position(cursor.position() + c);
-                    }
-                    Some(Ok(T::deserialize(&mut cursor).ok()?))
+        Ok(self
+            .get_values(tree)?
+            .into_iter()
+            .filter_map(|(_, value)| {
+                let (version, record) = split_record(&value);
+                if record.starts_with(prefix) {
+                    let start = prefix.len() + consume.unwrap_or(0) as usize;
+                    deserialize_record(&record[start..], version).ok()
                 } else {
                     None
                 }
             })
-            .collect()
+            .collect())
     }
 
     fn open_tree(&self, tree_id: &[u8; 1]) -> Result<Tree, Error> {
@@ -197,9 +383,91 @@ impl SledStorageProvider {
         self.open_tree(&[CONTRACT_TREE])
     }
 
+    fn contract_index_tree(&self) -> Result<Tree, Error> {
+        self.open_tree(&[CONTRACT_INDEX_TREE])
+    }
+
+    /// Indexes the contracts stored before the index was introduced.
+    fn build_contract_index(&self) -> Result<(), Error> {
+        let index_tree = self.contract_index_tree()?;
+        if index_tree
+            .contains_key([INDEX_BUILT_KEY])
+            .map_err(to_storage_error)?
+        {
+            return Ok(());
+        }
+
+        index_tree.clear().map_err(to_storage_error)?;
+        for (_, value) in self.get_values(&self.contract_tree()?)? {
+            let contract = deserialize_contract(&value)?;
+            let keys = get_index_keys(&contract);
+            index_tree
+                .transaction::<_, _, UnabortableTransactionError>(|db| {
+                    insert_index_keys(db, &contract.get_id(), &keys)?;
+                    Ok(())
+                })
+                .map_err(to_storage_error)?;
+        }
+        index_tree
+            .insert([INDEX_BUILT_KEY], Vec::new())
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    /// Returns the ids of the contracts indexed under the given prefix.
+    fn get_indexed_contract_ids(&self, prefix: &[u8]) -> Result<Vec<ContractId>, Error> {
+        self.contract_index_tree()?
+            .scan_prefix(prefix)
+            .keys()
+            .map(|key| get_indexed_contract_id(&key.map_err(to_storage_error)?))
+            .collect()
+    }
+
+    fn get_contracts_with_ids(&self, ids: &[ContractId]) -> Result<Vec<Contract>, Error> {
+        let contract_tree = self.contract_tree()?;
+        let mut contracts = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(value) = self.get_value(&contract_tree, id)? {
+                contracts.push(deserialize_contract(&value)?);
+            }
+        }
+        Ok(contracts)
+    }
+
+    fn get_contracts_in_state<T: VersionedSerializable>(
+        &self,
+        prefix: ContractPrefix,
+    ) -> Result<Vec<T>, Error> {
+        let contract_tree = self.contract_tree()?;
+        let mut contracts = Vec::new();
+        for id in self.get_indexed_contract_ids(&get_state_index_prefix(prefix.into()))? {
+            if let Some(value) = self.get_value(&contract_tree, &id)? {
+                let (version, record) = split_record(&value);
+                let data = record
+                    .get(1..)
+                    .ok_or_else(|| Error::StorageError("Empty contract record".to_string()))?;
+                contracts.push(deserialize_record(data, version)?);
+            }
+        }
+        Ok(contracts)
+    }
+
     fn channel_tree(&self) -> Result<Tree, Error> {
         self.open_tree(&[CHANNEL_TREE])
     }
+
+    fn get_oracle_data<T: Serializable>(
+        &self,
+        tree_id: u8,
+        key: &[u8],
+    ) -> Result<Option<T>, Error> {
+        match self.get_value(&self.open_tree(&[tree_id])?, key)? {
+            Some(res) => Ok(Some(
+                T::deserialize(&mut Cursor::new(&res)).map_err(to_storage_error)?,
+            )),
+            None => Ok(None),
+        }
+    }
 }
 
 #[cfg(feature = "wallet")]
@@ -219,112 +487,186 @@ impl SledStorageProvider {
 
 impl Storage for SledStorageProvider {
     fn get_contract(&self, contract_id: &ContractId) -> Result<Option<Contract>, Error> {
-        match self
-            .contract_tree()?
-            .get(contract_id)
-            .map_err(to_storage_error)?
-        {
+        match self.get_value(&self.contract_tree()?, contract_id)? {
             Some(res) => Ok(Some(deserialize_contract(&res)?)),
             None => Ok(None),
         }
     }
 
     fn get_contracts(&self) -> Result<Vec<Contract>, Error> {
-        self.contract_tree()?
+        self.get_values(&self.contract_tree()?)?
             .iter()
-            .values()
-            .map(|x| deserialize_contract(&x.unwrap()))
+            .map(|(_, value)| deserialize_contract(value))
             .collect::<Result<Vec<Contract>, Error>>()
     }
 
-    fn create_contract(&self, contract: &OfferedContract) -> Result<(), Error> {
-        let serialized = serialize_contract(&Contract::Offered(contract.clone()))?;
-        self.contract_tree()?
-            .insert(contract.id, serialized)
+    fn get_contracts_page(
+        &self,
+        state: Option<ContractState>,
+        cursor: Option<ContractId>,
+        limit: usize,
+    ) -> Result<ContractPage, Error> {
+        let keys = match state {
+            Some(state) => {
+                let prefix = get_state_index_prefix(get_contract_prefix(state));
+                let start = match cursor {
+                    Some(cursor) => Bound::Excluded([&prefix[..], &cursor[..]].concat()),
+                    None => Bound::Included(prefix.clone()),
+                };
+                self.contract_index_tree()?
+                    .range((start, Bound::Unbounded))
+                    .keys()
+                    .take_while(|key| key.as_ref().map_or(true, |k| k.starts_with(&prefix)))
+                    .take(limit + 1)
+                    .collect::<Result<Vec<_>, _>>()
+            }
+            None => {
+                let contract_tree = self.contract_tree()?;
+                let iter = match cursor {
+                    Some(cursor) => {
+                        contract_tree.range((Bound::Excluded(cursor), Bound::Unbounded))
+                    }
+                    None => contract_tree.iter(),
+                };
+                iter.keys().take(limit + 1).collect::<Result<Vec<_>, _>>()
+            }
+        }
+        .map_err(to_storage_error)?;
+        let mut ids = keys
+            .iter()
+            .map(|key| get_indexed_contract_id(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let next_cursor = if ids.len() > limit {
+            ids.truncate(limit);
+            ids.last().copied()
+        } else {
+            None
+        };
+        Ok(ContractPage {
+            contracts: self.get_contracts_with_ids(&ids)?,
+            next_cursor,
+        })
+    }
+
+    fn get_contracts_by_counter_party(
+        &self,
+        counter_party: &PublicKey,
+    ) -> Result<Vec<Contract>, Error> {
+        let ids = self.get_indexed_contract_ids(&get_counter_party_index_prefix(counter_party))?;
+        self.get_contracts_with_ids(&ids)
+    }
+
+    fn get_contracts_by_event_id(&self, event_id: &str) -> Result<Vec<Contract>, Error> {
+        let ids = self.get_indexed_contract_ids(&get_event_index_prefix(event_id))?;
+        self.get_contracts_with_ids(&ids)
+    }
+
+    fn archive_contracts(&self, archives: &[ArchivedContract]) -> Result<(), Error> {
+        let archive_tree = self.open_tree(&[ARCHIVED_CONTRACT_TREE])?;
+        let serialized = archives
+            .iter()
+            .map(|archive| {
+                let id = archive.contract_id;
+                Ok((id, self.seal(&archive_tree, &id, archive.serialize()?)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        (&self.contract_tree()?, &self.contract_index_tree()?, &archive_tree)
+            .transaction::<_, ()>(
+                |(contract_db, index_db, archive_db)| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
+                    for (id, value) in &serialized {
+                        contract_db.remove(id)?;
+                        remove_index_keys(index_db, id)?;
+                        archive_db.insert(id, value.clone())?;
+                    }
+                    Ok(())
+                },
+            )
             .map_err(to_storage_error)?;
         Ok(())
     }
 
+    fn get_archived_contracts(&self) -> Result<Vec<ArchivedContract>, Error> {
+        self.get_values(&self.open_tree(&[ARCHIVED_CONTRACT_TREE])?)?
+            .into_iter()
+            .map(|(_, value)| {
+                ArchivedContract::deserialize(&mut Cursor::new(&value)).map_err(to_storage_error)
+            })
+            .collect()
+    }
+
+    fn create_contract(&self, contract: &OfferedContract) -> Result<(), Error> {
+        self.update_contract(&Contract::Offered(contract.clone()))
+    }
+
     fn delete_contract(&self, contract_id: &ContractId) -> Result<(), Error> {
-        self.contract_tree()?
-            .remove(contract_id)
+        (&self.contract_tree()?, &self.contract_index_tree()?)
+            .transaction::<_, ()>(
+                |(contract_db, index_db)| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
+                    contract_db.remove(contract_id)?;
+                    remove_index_keys(index_db, contract_id)?;
+                    Ok(())
+                },
+            )
             .map_err(to_storage_error)?;
         Ok(())
     }
 
     fn update_contract(&self, contract: &Contract) -> Result<(), Error> {
-        let serialized = serialize_contract(contract)?;
-        self.contract_tree()?
-            .transaction::<_, _, UnabortableTransactionError>(|db| {
-                match contract {
-                    a @ Contract::Accepted(_) | a @ Contract::Signed(_) => {
-                        db.remove(&a.get_temporary_id())?;
-                    }
-                    _ => {}
-                };
-
-                db.insert(&contract.get_id(), serialized.clone())?;
-                Ok(())
-            })
+        let contract_tree = self.contract_tree()?;
+        let serialized = self.seal(
+            &contract_tree,
+            &contract.get_id(),
+            serialize_contract(contract)?,
+        )?;
+        (&contract_tree, &self.contract_index_tree()?)
+            .transaction::<_, ()>(
+                |(contract_db, index_db)| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
+                    insert_contract(contract_db, index_db, serialized.clone(), contract)?;
+                    Ok(())
+                },
+            )
             .map_err(to_storage_error)?;
         Ok(())
     }
 
     fn get_signed_contracts(&self) -> Result<Vec<SignedContract>, Error> {
-        self.get_data_with_prefix(
-            &self.contract_tree()?,
-            &[ContractPrefix::Signed.into()],
-            None,
-        )
+        self.get_contracts_in_state(ContractPrefix::Signed)
     }
 
     fn get_confirmed_contracts(&self) -> Result<Vec<SignedContract>, Error> {
-        self.get_data_with_prefix(
-            &self.contract_tree()?,
-            &[ContractPrefix::Confirmed.into()],
-            None,
-        )
+        self.get_contracts_in_state(ContractPrefix::Confirmed)
     }
 
     fn get_contract_offers(&self) -> Result<Vec<OfferedContract>, Error> {
-        self.get_data_with_prefix(
-            &self.contract_tree()?,
-            &[ContractPrefix::Offered.into()],
-            None,
-        )
+        self.get_contracts_in_state(ContractPrefix::Offered)
     }
 
     fn get_preclosed_contracts(&self) -> Result<Vec<PreClosedContract>, Error> {
-        self.get_data_with_prefix(
-            &self.contract_tree()?,
-            &[ContractPrefix::PreClosed.into()],
-            None,
-        )
+        self.get_contracts_in_state(ContractPrefix::PreClosed)
     }
 
     fn upsert_channel(&self, channel: Channel, contract: Option<Contract>) -> Result<(), Error> {
-        let serialized = serialize_channel(&channel)?;
+        let channel_tree = self.channel_tree()?;
+        let contract_tree = self.contract_tree()?;
+        let index_tree = self.contract_index_tree()?;
+        let serialized = self.seal(
+            &channel_tree,
+            &channel.get_id(),
+            serialize_channel(&channel)?,
+        )?;
         let serialized_contract = match contract.as_ref() {
-            Some(c) => Some(serialize_contract(c)?),
+            Some(c) => Some(self.seal(&contract_tree, &c.get_id(), serialize_contract(c)?)?),
             None => None,
         };
-        let channel_tree = self.channel_tree()?;
-        let contract_tree = self.contract_tree()?;
-        (&channel_tree, &contract_tree)
+        (&channel_tree, &contract_tree, &index_tree)
             .transaction::<_, ()>(
-                |(channel_db, contract_db)| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
-                    match &channel {
-                        a @ Channel::Accepted(_) | a @ Channel::Signed(_) => {
-                            channel_db.remove(&a.get_temporary_id())?;
-                        }
-                        _ => {}
-                    };
-
-                    channel_db.insert(&channel.get_id(), serialized.clone())?;
+                |(channel_db, contract_db, index_db)| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
+                    insert_channel(channel_db, serialized.clone(), &channel)?;
 
                     if let Some(c) = contract.as_ref() {
                         insert_contract(
                             contract_db,
+                            index_db,
                             serialized_contract
                                 .clone()
                                 .expect("to have the serialized version"),
@@ -346,11 +688,7 @@ impl Storage for SledStorageProvider {
     }
 
     fn get_channel(&self, channel_id: &dlc_manager::ChannelId) -> Result<Option<Channel>, Error> {
-        match self
-            .channel_tree()?
-            .get(channel_id)
-            .map_err(to_storage_error)?
-        {
+        match self.get_value(&self.channel_tree()?, channel_id)? {
             Some(res) => Ok(Some(deserialize_channel(&res)?)),
             None => Ok(None),
         }
@@ -383,16 +721,62 @@ impl Storage for SledStorageProvider {
         )
     }
 
-    fn persist_chain_monitor(&self, monitor: &ChainMonitor) -> Result<(), Error> {
-        self.open_tree(&[CHAIN_MONITOR_TREE])?
-            .insert([CHAIN_MONITOR_KEY], monitor.serialize()?)
-            .map_err(|e| Error::StorageError(format!("Error writing chain monitor: {}", e)))?;
+    fn write_batch(&self, writes: &[StorageWrite]) -> Result<(), Error> {
+        let contract_tree = self.contract_tree()?;
+        let channel_tree = self.channel_tree()?;
+        let chain_monitor_tree = self.open_tree(&[CHAIN_MONITOR_TREE])?;
+        let index_tree = self.contract_index_tree()?;
+        let serialized = writes
+            .iter()
+            .map(|w| match w {
+                StorageWrite::Contract(c) => {
+                    self.seal(&contract_tree, &c.get_id(), serialize_contract(c)?)
+                }
+                StorageWrite::Channel(c) => {
+                    self.seal(&channel_tree, &c.get_id(), serialize_channel(c)?)
+                }
+                StorageWrite::ChainMonitor(m) => {
+                    self.seal(&chain_monitor_tree, &[CHAIN_MONITOR_KEY], m.serialize()?)
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        (&contract_tree, &channel_tree, &chain_monitor_tree, &index_tree)
+            .transaction::<_, ()>(
+                |(contract_db, channel_db, chain_monitor_db, index_db)| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
+                    for (write, serialized) in writes.iter().zip(serialized.iter()) {
+                        match write {
+                            StorageWrite::Contract(c) => {
+                                insert_contract(contract_db, index_db, serialized.clone(), c)?;
+                            }
+                            StorageWrite::Channel(c) => {
+                                insert_channel(channel_db, serialized.clone(), c)?;
+                            }
+                            StorageWrite::ChainMonitor(_) => {
+                                chain_monitor_db.insert([CHAIN_MONITOR_KEY], serialized.clone())?;
+                            }
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        .map_err(to_storage_error)?;
         Ok(())
     }
+
+    fn persist_chain_monitor(&self, monitor: &ChainMonitor) -> Result<(), Error> {
+        self.insert_value(
+            &self.open_tree(&[CHAIN_MONITOR_TREE])?,
+            &[CHAIN_MONITOR_KEY],
+            monitor.serialize()?,
+        )
+        .map_err(|e| Error::StorageError(format!("Error writing chain monitor: {}", e)))
+    }
     fn get_chain_monitor(&self) -> Result<Option<ChainMonitor>, dlc_manager::error::Error> {
         let serialized = self
-            .open_tree(&[CHAIN_MONITOR_TREE])?
-            .get([CHAIN_MONITOR_KEY])
+            .get_value(
+                &self.open_tree(&[CHAIN_MONITOR_TREE])?,
+                &[CHAIN_MONITOR_KEY],
+            )
             .map_err(|e| Error::StorageError(format!("Error reading chain monitor: {}", e)))?;
         let deserialized = match serialized {
             Some(s) => Some(
@@ -403,16 +787,168 @@ impl Storage for SledStorageProvider {
         };
         Ok(deserialized)
     }
+
+    fn persist_oracle_announcement(&self, announcement: &OracleAnnouncement) -> Result<(), Error> {
+        let key = oracle_event_key(
+            &announcement.oracle_public_key,
+            &announcement.oracle_event.event_id,
+        );
+        self.insert_value(
+            &self.open_tree(&[ORACLE_ANNOUNCEMENT_TREE])?,
+            &key,
+            announcement.serialize()?,
+        )
+    }
+
+    fn get_oracle_announcement(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAnnouncement>, Error> {
+        self.get_oracle_data(
+            ORACLE_ANNOUNCEMENT_TREE,
+            &oracle_event_key(oracle_public_key, event_id),
+        )
+    }
+
+    fn persist_oracle_attestation(
+        &self,
+        event_id: &str,
+        attestation: &OracleAttestation,
+    ) -> Result<(), Error> {
+        let key = oracle_event_key(&attestation.oracle_public_key, event_id);
+        self.insert_value(
+            &self.open_tree(&[ORACLE_ATTESTATION_TREE])?,
+            &key,
+            attestation.serialize()?,
+        )
+    }
+
+    fn get_oracle_attestation(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAttestation>, Error> {
+        self.get_oracle_data(
+            ORACLE_ATTESTATION_TREE,
+            &oracle_event_key(oracle_public_key, event_id),
+        )
+    }
+
+    fn persist_outbound_messages(
+        &self,
+        node_id: &PublicKey,
+        messages: &[Message],
+    ) -> Result<(), Error> {
+        let tree = self.open_tree(&[OUTBOUND_MESSAGE_TREE])?;
+        if messages.is_empty() {
+            tree.remove(node_id.serialize()).map_err(to_storage_error)?;
+        } else {
+            self.insert_value(&tree, &node_id.serialize(), serialize_messages(messages))?;
+        }
+        Ok(())
+    }
+
+    fn migrate(&self) -> Result<(), Error> {
+        let contract_tree = self.contract_tree()?;
+        for (key, value) in self.get_values(&contract_tree)? {
+            if split_record(&value).0 != Some(SERIALIZATION_VERSION) {
+                let contract = deserialize_contract(&value)?;
+                self.insert_value(&contract_tree, &key, serialize_contract(&contract)?)?;
+            }
+        }
+
+        let channel_tree = self.channel_tree()?;
+        for (key, value) in self.get_values(&channel_tree)? {
+            if split_record(&value).0 != Some(SERIALIZATION_VERSION) {
+                let channel = deserialize_channel(&value)?;
+                self.insert_value(&channel_tree, &key, serialize_channel(&channel)?)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, Error> {
+        self.get_values(&self.open_tree(&[OUTBOUND_MESSAGE_TREE])?)?
+            .into_iter()
+            .map(|(key, value)| {
+                let node_id = PublicKey::from_slice(&key).map_err(to_storage_error)?;
+                Ok((node_id, deserialize_messages(&value)?))
+            })
+            .collect()
+    }
+
+    fn upsert_multi_party_contract(&self, contract: &MultiPartyContract) -> Result<(), Error> {
+        let tree = self.open_tree(&[MULTI_PARTY_CONTRACT_TREE])?;
+        let serialized = self.seal(&tree, &contract.id, contract.serialize()?)?;
+        tree.transaction::<_, ()>(
+            |db| -> ConflictableTransactionResult<(), UnabortableTransactionError> {
+                if contract.id != contract.temporary_id {
+                    db.remove(&contract.temporary_id)?;
+                }
+                db.insert(&contract.id, serialized.clone())?;
+                Ok(())
+            },
+        )
+        .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    fn get_multi_party_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<MultiPartyContract>, Error> {
+        match self.get_value(&self.open_tree(&[MULTI_PARTY_CONTRACT_TREE])?, id)? {
+            Some(res) => Ok(Some(
+                MultiPartyContract::deserialize(&mut Cursor::new(&res))
+                    .map_err(to_storage_error)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn get_multi_party_contracts(&self) -> Result<Vec<MultiPartyContract>, Error> {
+        self.get_values(&self.open_tree(&[MULTI_PARTY_CONTRACT_TREE])?)?
+            .into_iter()
+            .map(|(_, value)| {
+                MultiPartyContract::deserialize(&mut Cursor::new(&value)).map_err(to_storage_error)
+            })
+            .collect()
+    }
+
+    fn upsert_novation_contract(&self, contract: &NovationContract) -> Result<(), Error> {
+        self.insert_value(
+            &self.open_tree(&[NOVATION_CONTRACT_TREE])?,
+            &contract.contract_id,
+            contract.serialize()?,
+        )
+    }
+
+    fn get_novation_contract(&self, id: &ContractId) -> Result<Option<NovationContract>, Error> {
+        match self.get_value(&self.open_tree(&[NOVATION_CONTRACT_TREE])?, id)? {
+            Some(res) => Ok(Some(
+                NovationContract::deserialize(&mut Cursor::new(&res)).map_err(to_storage_error)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn get_novation_contracts(&self) -> Result<Vec<NovationContract>, Error> {
+        self.get_values(&self.open_tree(&[NOVATION_CONTRACT_TREE])?)?
+            .into_iter()
+            .map(|(_, value)| {
+                NovationContract::deserialize(&mut Cursor::new(&value)).map_err(to_storage_error)
+            })
+            .collect()
+    }
 }
 
 #[cfg(feature = "wallet")]
 impl WalletStorage for SledStorageProvider {
     fn upsert_address(&self, address: &Address, privkey: &SecretKey) -> Result<(), Error> {
-        let db = self.address_tree()?;
         let key = get_address_key(address);
-        db.insert(key, &privkey.secret_bytes())
-            .map_err(to_storage_error)?;
-        Ok(())
+        self.insert_value(&self.address_tree()?, &key, privkey.secret_bytes().to_vec())
     }
 
     fn delete_address(&self, address: &Address) -> Result<(), Error> {
@@ -436,9 +972,8 @@ impl WalletStorage for SledStorageProvider {
     }
 
     fn get_priv_key_for_address(&self, address: &Address) -> Result<Option<SecretKey>, Error> {
-        let db = self.address_tree()?;
         let key = get_address_key(address);
-        let raw_key = match db.get(key).map_err(to_storage_error)? {
+        let raw_key = match self.get_value(&self.address_tree()?, &key)? {
             Some(res) => res,
             None => return Ok(None),
         };
@@ -449,16 +984,16 @@ impl WalletStorage for SledStorageProvider {
     }
 
     fn upsert_key_pair(&self, public_key: &PublicKey, privkey: &SecretKey) -> Result<(), Error> {
-        self.key_pair_tree()?
-            .insert(public_key.serialize(), &privkey.secret_bytes())
-            .map_err(to_storage_error)?;
-        Ok(())
+        self.insert_value(
+            &self.key_pair_tree()?,
+            &public_key.serialize(),
+            privkey.secret_bytes().to_vec(),
+        )
     }
 
     fn get_priv_key_for_pubkey(&self, public_key: &PublicKey) -> Result<Option<SecretKey>, Error> {
-        let db = self.key_pair_tree()?;
         let key = public_key.serialize();
-        let raw_key = match db.get(key).map_err(to_storage_error)? {
+        let raw_key = match self.get_value(&self.key_pair_tree()?, &key)? {
             Some(res) => res,
             None => return Ok(None),
         };
@@ -470,11 +1005,9 @@ impl WalletStorage for SledStorageProvider {
 
     fn upsert_utxo(&self, utxo: &Utxo) -> Result<(), Error> {
         let key = get_utxo_key(&utxo.outpoint.txid, utxo.outpoint.vout);
-        let db = self.utxo_tree()?;
         let mut buf = Vec::new();
         utxo.write(&mut buf)?;
-        db.insert(key, buf).map_err(to_storage_error)?;
-        Ok(())
+        self.insert_value(&self.utxo_tree()?, &key, buf)
     }
 
     fn has_utxo(&self, utxo: &Utxo) -> Result<bool, Error> {
@@ -491,12 +1024,10 @@ impl WalletStorage for SledStorageProvider {
     }
 
     fn get_utxos(&self) -> Result<Vec<Utxo>, Error> {
-        self.utxo_tree()?
+        self.get_values(&self.utxo_tree()?)?
             .iter()
-            .values()
-            .map(|x| {
-                let ivec = x.map_err(to_storage_error)?;
-                let mut cursor = Cursor::new(&ivec);
+            .map(|(_, value)| {
+                let mut cursor = Cursor::new(value);
                 let res =
                     Utxo::read(&mut cursor).map_err(|x| Error::InvalidState(format!("{}", x)))?;
                 Ok(res)
@@ -507,7 +1038,7 @@ impl WalletStorage for SledStorageProvider {
     fn unreserve_utxo(&self, txid: &Txid, vout: u32) -> Result<(), Error> {
         let utxo_tree = self.utxo_tree()?;
         let key = get_utxo_key(txid, vout);
-        let mut utxo = match utxo_tree.get(&key).map_err(to_storage_error)? {
+        let mut utxo = match self.get_value(&utxo_tree, &key)? {
             Some(res) => Utxo::read(&mut Cursor::new(&res))
                 .map_err(|_| Error::InvalidState("Could not read UTXO".to_string()))?,
             None => {
@@ -521,24 +1052,142 @@ impl WalletStorage for SledStorageProvider {
         utxo.reserved = false;
         let mut buf = Vec::new();
         utxo.write(&mut buf)?;
-        utxo_tree.insert(key, buf).map_err(to_storage_error)?;
-        Ok(())
+        self.insert_value(&utxo_tree, &key, buf)
     }
 }
 
 fn insert_contract(
-    db: &sled::transaction::TransactionalTree,
+    contract_db: &TransactionalTree,
+    index_db: &TransactionalTree,
     serialized: Vec<u8>,
     contract: &Contract,
+) -> Result<(), UnabortableTransactionError> {
+    let mut previous_keys = Vec::new();
+    if let Contract::Accepted(_) | Contract::Signed(_) = contract {
+        contract_db.remove(&contract.get_temporary_id())?;
+        previous_keys.extend(remove_index_keys(index_db, &contract.get_temporary_id())?);
+    }
+    previous_keys.extend(remove_index_keys(index_db, &contract.get_id())?);
+
+    let mut keys = get_index_keys(contract);
+    if let Contract::Closed(_) = contract {
+        // Closed contracts do not retain the events they depended on, so they
+        // are kept from the previous state.
+        keys.extend(
+            previous_keys
+                .into_iter()
+                .filter(|key| key.first() == Some(&EVENT_INDEX_PREFIX)),
+        );
+    }
+    insert_index_keys(index_db, &contract.get_id(), &keys)?;
+
+    contract_db.insert(&contract.get_id(), serialized)?;
+    Ok(())
+}
+
+fn get_state_index_prefix(contract_prefix: u8) -> Vec<u8> {
+    vec![STATE_INDEX_PREFIX, contract_prefix]
+}
+
+fn get_counter_party_index_prefix(counter_party: &PublicKey) -> Vec<u8> {
+    let mut prefix = vec![COUNTER_PARTY_INDEX_PREFIX];
+    prefix.extend_from_slice(&counter_party.serialize());
+    prefix
+}
+
+fn get_event_index_prefix(event_id: &str) -> Vec<u8> {
+    // Event ids are length prefixed so that no id is a prefix of another.
+    let mut prefix = vec![EVENT_INDEX_PREFIX];
+    prefix.extend_from_slice(&(event_id.len() as u32).to_be_bytes());
+    prefix.extend_from_slice(event_id.as_bytes());
+    prefix
+}
+
+fn get_index_keys_key(contract_id: &ContractId) -> Vec<u8> {
+    let mut key = vec![INDEX_KEYS_PREFIX];
+    key.extend_from_slice(contract_id);
+    key
+}
+
+fn get_index_keys(contract: &Contract) -> Vec<Vec<u8>> {
+    let mut prefixes = vec![
+        get_state_index_prefix(ContractPrefix::get_prefix(contract)),
+        get_counter_party_index_prefix(&contract.get_counter_party_id()),
+    ];
+    prefixes.extend(
+        contract
+            .get_oracle_event_ids()
+            .iter()
+            .map(|event_id| get_event_index_prefix(event_id)),
+    );
+    let contract_id = contract.get_id();
+    prefixes
+        .into_iter()
+        .map(|mut key| {
+            key.extend_from_slice(&contract_id);
+            key
+        })
+        .collect()
+}
+
+fn get_indexed_contract_id(key: &[u8]) -> Result<ContractId, Error> {
+    key.len()
+        .checked_sub(32)
+        .and_then(|start| key[start..].try_into().ok())
+        .ok_or_else(|| Error::StorageError("Invalid index key".to_string()))
+}
+
+fn insert_index_keys(
+    index_db: &TransactionalTree,
+    contract_id: &ContractId,
+    keys: &[Vec<u8>],
+) -> Result<(), UnabortableTransactionError> {
+    let mut encoded = Vec::new();
+    for key in keys {
+        index_db.insert(key.as_slice(), Vec::new())?;
+        encoded.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        encoded.extend_from_slice(key);
+    }
+    index_db.insert(get_index_keys_key(contract_id), encoded)?;
+    Ok(())
+}
+
+/// Removes the index keys of the contract with the given id, returning them.
+fn remove_index_keys(
+    index_db: &TransactionalTree,
+    contract_id: &ContractId,
+) -> Result<Vec<Vec<u8>>, UnabortableTransactionError> {
+    let encoded = match index_db.remove(get_index_keys_key(contract_id))? {
+        Some(encoded) => encoded,
+        None => return Ok(Vec::new()),
+    };
+    let mut keys = Vec::new();
+    let mut remaining = &encoded[..];
+    while remaining.len() >= 4 {
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&remaining[..4]);
+        let end = (4 + u32::from_be_bytes(len) as usize).min(remaining.len());
+        let key = remaining[4..end].to_vec();
+        index_db.remove(key.as_slice())?;
+        keys.push(key);
+        remaining = &remaining[end..];
+    }
+    Ok(keys)
+}
+
+fn insert_channel(
+    db: &TransactionalTree,
+    serialized: Vec<u8>,
+    channel: &Channel,
 ) -> Result<Option<sled::IVec>, UnabortableTransactionError> {
-    match contract {
-        a @ Contract::Accepted(_) | a @ Contract::Signed(_) => {
+    match channel {
+        a @ Channel::Accepted(_) | a @ Channel::Signed(_) => {
             db.remove(&a.get_temporary_id())?;
         }
         _ => {}
     };
 
-    db.insert(&contract.get_id(), serialized)
+    db.insert(&channel.get_id(), serialized)
 }
 
 fn serialize_contract(contract: &Contract) -> Result<Vec<u8>, ::std::io::Error> {
@@ -552,48 +1201,49 @@ fn serialize_contract(contract: &Contract) -> Result<Vec<u8>, ::std::io::Error>
         Contract::Closed(c) => c.serialize(),
     };
     let mut serialized = serialized?;
-    let mut res = Vec::with_capacity(serialized.len() + 1);
+    let mut res = Vec::with_capacity(serialized.len() + 3);
+    res.push(RECORD_VERSION_MARKER);
+    res.push(SERIALIZATION_VERSION);
     res.push(ContractPrefix::get_prefix(contract));
     res.append(&mut serialized);
     Ok(res)
 }
 
-fn deserialize_contract(buff: &sled::IVec) -> Result<Contract, Error> {
-    let mut cursor = ::std::io::Cursor::new(buff);
-    let mut prefix = [0u8; 1];
-    cursor.read_exact(&mut prefix)?;
-    let contract_prefix: ContractPrefix = prefix[0].try_into()?;
+fn get_contract_prefix(state: ContractState) -> u8 {
+    let prefix = match state {
+        ContractState::Offered => ContractPrefix::Offered,
+        ContractState::Accepted => ContractPrefix::Accepted,
+        ContractState::Signed => ContractPrefix::Signed,
+        ContractState::Confirmed => ContractPrefix::Confirmed,
+        ContractState::PreClosed => ContractPrefix::PreClosed,
+        ContractState::Closed => ContractPrefix::Closed,
+        ContractState::Refunded => ContractPrefix::Refunded,
+        ContractState::FailedAccept => ContractPrefix::FailedAccept,
+        ContractState::FailedSign => ContractPrefix::FailedSign,
+        ContractState::Rejected => ContractPrefix::Rejected,
+    };
+    prefix.into()
+}
+
+fn deserialize_contract(buff: &[u8]) -> Result<Contract, Error> {
+    let (version, record) = split_record(buff);
+    let contract_prefix: ContractPrefix = record
+        .first()
+        .ok_or_else(|| Error::StorageError("Empty contract record".to_string()))?
+        .to_owned()
+        .try_into()?;
+    let data = &record[1..];
     let contract = match contract_prefix {
-        ContractPrefix::Offered => {
-            Contract::Offered(OfferedContract::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
-        ContractPrefix::Accepted => Contract::Accepted(
-            AcceptedContract::deserialize(&mut cursor).map_err(to_storage_error)?,
-        ),
-        ContractPrefix::Signed => {
-            Contract::Signed(SignedContract::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
-        ContractPrefix::Confirmed => {
-            Contract::Confirmed(SignedContract::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
-        ContractPrefix::PreClosed => Contract::PreClosed(
-            PreClosedContract::deserialize(&mut cursor).map_err(to_storage_error)?,
-        ),
-        ContractPrefix::Closed => {
-            Contract::Closed(ClosedContract::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
-        ContractPrefix::FailedAccept => Contract::FailedAccept(
-            FailedAcceptContract::deserialize(&mut cursor).map_err(to_storage_error)?,
-        ),
-        ContractPrefix::FailedSign => Contract::FailedSign(
-            FailedSignContract::deserialize(&mut cursor).map_err(to_storage_error)?,
-        ),
-        ContractPrefix::Refunded => {
-            Contract::Refunded(SignedContract::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
-        ContractPrefix::Rejected => {
-            Contract::Rejected(OfferedContract::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
+        ContractPrefix::Offered => Contract::Offered(deserialize_record(data, version)?),
+        ContractPrefix::Accepted => Contract::Accepted(deserialize_record(data, version)?),
+        ContractPrefix::Signed => Contract::Signed(deserialize_record(data, version)?),
+        ContractPrefix::Confirmed => Contract::Confirmed(deserialize_record(data, version)?),
+        ContractPrefix::PreClosed => Contract::PreClosed(deserialize_record(data, version)?),
+        ContractPrefix::Closed => Contract::Closed(deserialize_record(data, version)?),
+        ContractPrefix::FailedAccept => Contract::FailedAccept(deserialize_record(data, version)?),
+        ContractPrefix::FailedSign => Contract::FailedSign(deserialize_record(data, version)?),
+        ContractPrefix::Refunded => Contract::Refunded(deserialize_record(data, version)?),
+        ContractPrefix::Rejected => Contract::Rejected(deserialize_record(data, version)?),
     };
     Ok(contract)
 }
@@ -607,7 +1257,9 @@ fn serialize_channel(channel: &Channel) -> Result<Vec<u8>, ::std::io::Error> {
         Channel::FailedSign(f) => f.serialize(),
     };
     let mut serialized = serialized?;
-    let mut res = Vec::with_capacity(serialized.len() + 1);
+    let mut res = Vec::with_capacity(serialized.len() + 4);
+    res.push(RECORD_VERSION_MARKER);
+    res.push(SERIALIZATION_VERSION);
     res.push(ChannelPrefix::get_prefix(channel));
     if let Channel::Signed(s) = channel {
         res.push(SignedChannelPrefix::get_prefix(&s.state.get_type()))
@@ -616,33 +1268,64 @@ fn serialize_channel(channel: &Channel) -> Result<Vec<u8>, ::std::io::Error> {
     Ok(res)
 }
 
-fn deserialize_channel(buff: &sled::IVec) -> Result<Channel, Error> {
-    let mut cursor = ::std::io::Cursor::new(buff);
-    let mut prefix = [0u8; 1];
-    cursor.read_exact(&mut prefix)?;
-    let channel_prefix: ChannelPrefix = prefix[0].try_into()?;
+fn deserialize_channel(buff: &[u8]) -> Result<Channel, Error> {
+    let (version, record) = split_record(buff);
+    let channel_prefix: ChannelPrefix = record
+        .first()
+        .ok_or_else(|| Error::StorageError("Empty channel record".to_string()))?
+        .to_owned()
+        .try_into()?;
+    let data = &record[1..];
     let channel = match channel_prefix {
-        ChannelPrefix::Offered => {
-            Channel::Offered(OfferedChannel::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
-        ChannelPrefix::Accepted => {
-            Channel::Accepted(AcceptedChannel::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
+        ChannelPrefix::Offered => Channel::Offered(deserialize_record(data, version)?),
+        ChannelPrefix::Accepted => Channel::Accepted(deserialize_record(data, version)?),
         ChannelPrefix::Signed => {
             // Skip the channel state prefix.
-            cursor.set_position(cursor.position() + 1);
-            Channel::Signed(SignedChannel::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
-        ChannelPrefix::FailedAccept => {
-            Channel::FailedAccept(FailedAccept::deserialize(&mut cursor).map_err(to_storage_error)?)
-        }
-        ChannelPrefix::FailedSign => {
-            Channel::FailedSign(FailedSign::deserialize(&mut cursor).map_err(to_storage_error)?)
+            let data = data
+                .get(1..)
+                .ok_or_else(|| Error::StorageError("Invalid channel record".to_string()))?;
+            Channel::Signed(deserialize_record(data, version)?)
         }
+        ChannelPrefix::FailedAccept => Channel::FailedAccept(deserialize_record(data, version)?),
+        ChannelPrefix::FailedSign => Channel::FailedSign(deserialize_record(data, version)?),
     };
     Ok(channel)
 }
 
+/// Returns the version of the format of a contract or channel record if it is
+/// tagged, along with the record without its version tag.
+fn split_record(buff: &[u8]) -> (Option<u8>, &[u8]) {
+    match buff {
+        [RECORD_VERSION_MARKER, version, record @ ..] => (Some(*version), record),
+        _ => (None, buff),
+    }
+}
+
+/// Deserializes the given data written with the given format version. As the
+/// version of untagged records is unknown, every supported version is tried
+/// from the most recent one, until one can read the whole record.
+fn deserialize_record<T: VersionedSerializable>(
+    data: &[u8],
+    version: Option<u8>,
+) -> Result<T, Error> {
+    if let Some(version) = version {
+        return T::deserialize_versioned(&mut Cursor::new(data), version).map_err(to_storage_error);
+    }
+
+    for version in (INITIAL_VERSION..=SERIALIZATION_VERSION).rev() {
+        let mut cursor = Cursor::new(data);
+        if let Ok(res) = T::deserialize_versioned(&mut cursor, version) {
+            if cursor.position() == data.len() as u64 {
+                return Ok(res);
+            }
+        }
+    }
+
+    Err(Error::StorageError(
+        "Could not read record with any supported format version".to_string(),
+    ))
+}
+
 #[cfg(feature = "wallet")]
 fn get_address_key(address: &Address) -> Vec<u8> {
     address.to_string().into_bytes()
@@ -656,9 +1339,42 @@ fn get_utxo_key(txid: &Txid, vout: u32) -> Vec<u8> {
     key
 }
 
+// Messages ending with a TLV stream are read until the end of their reader,
+// so each message is prefixed with its length.
+fn serialize_messages(messages: &[Message]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for message in messages {
+        let encoded = message.encode_with_type();
+        buf.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        buf.extend(encoded);
+    }
+    buf
+}
+
+fn deserialize_messages(data: &[u8]) -> Result<Vec<Message>, Error> {
+    let mut cursor = Cursor::new(data);
+    let mut messages = Vec::new();
+    while (cursor.position() as usize) < data.len() {
+        let mut len = [0u8; 4];
+        cursor.read_exact(&mut len).map_err(to_storage_error)?;
+        let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+        cursor.read_exact(&mut buf).map_err(to_storage_error)?;
+        messages.push(Message::read_with_type(&mut Cursor::new(buf)).map_err(to_storage_error)?);
+    }
+    Ok(messages)
+}
+
+fn oracle_event_key(oracle_public_key: &XOnlyPublicKey, event_id: &str) -> Vec<u8> {
+    let mut key = oracle_public_key.serialize().to_vec();
+    key.extend_from_slice(event_id.as_bytes());
+    key
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use dlc_manager::channel::accepted_channel::AcceptedChannel;
+    use dlc_manager::contract::ClosedContract;
 
     macro_rules! sled_test {
         ($name: ident, $body: expr) => {
@@ -751,6 +1467,70 @@ mod tests {
         }
     );
 
+    sled_test!(
+        oracle_announcement_can_be_retrieved,
+        |storage: SledStorageProvider| {
+            let serialized = include_bytes!("../test_files/Offered");
+            let contract: OfferedContract = deserialize_object(serialized);
+            let announcement = &contract.contract_info[0].oracle_announcements[0];
+            let public_key = announcement.oracle_public_key;
+            let event_id = &announcement.oracle_event.event_id;
+
+            assert!(storage
+                .get_oracle_announcement(&public_key, event_id)
+                .expect("Error querying announcement")
+                .is_none());
+
+            storage
+                .persist_oracle_announcement(announcement)
+                .expect("Error persisting announcement");
+
+            let retrieved = storage
+                .get_oracle_announcement(&public_key, event_id)
+                .expect("Error querying announcement");
+            assert_eq!(Some(announcement), retrieved.as_ref());
+        }
+    );
+
+    sled_test!(
+        outbound_messages_can_be_retrieved,
+        |storage: SledStorageProvider| {
+            let serialized = include_bytes!("../test_files/Offered");
+            let contract: OfferedContract = deserialize_object(serialized);
+            let offer: dlc_messages::OfferDlc = (&contract).into();
+            let messages = vec![Message::Offer(offer.clone()), Message::Offer(offer)];
+
+            storage
+                .persist_outbound_messages(&contract.counter_party, &messages)
+                .expect("Error persisting messages");
+
+            let retrieved = storage
+                .get_outbound_messages()
+                .expect("Error retrieving messages");
+            assert_eq!(1, retrieved.len());
+            assert_eq!(contract.counter_party, retrieved[0].0);
+            assert_eq!(
+                messages
+                    .iter()
+                    .map(|m| m.encode_with_type())
+                    .collect::<Vec<_>>(),
+                retrieved[0]
+                    .1
+                    .iter()
+                    .map(|m| m.encode_with_type())
+                    .collect::<Vec<_>>()
+            );
+
+            storage
+                .persist_outbound_messages(&contract.counter_party, &[])
+                .expect("Error persisting messages");
+            assert!(storage
+                .get_outbound_messages()
+                .expect("Error retrieving messages")
+                .is_empty());
+        }
+    );
+
     fn insert_offered_signed_and_confirmed(storage: &mut SledStorageProvider) {
         let serialized = include_bytes!("../test_files/Offered");
         let offered_contract = deserialize_object(serialized);
@@ -874,6 +1654,167 @@ mod tests {
         }
     );
 
+    sled_test!(
+        get_contracts_page_returns_contracts_after_cursor,
+        |mut storage: SledStorageProvider| {
+            insert_offered_signed_and_confirmed(&mut storage);
+
+            let first_page = storage
+                .get_contracts_page(None, None, 4)
+                .expect("Error retrieving contracts");
+            assert_eq!(4, first_page.contracts.len());
+            let second_page = storage
+                .get_contracts_page(None, first_page.next_cursor, 4)
+                .expect("Error retrieving contracts");
+            assert_eq!(2, second_page.contracts.len());
+            assert!(second_page.next_cursor.is_none());
+
+            let ids = first_page
+                .contracts
+                .iter()
+                .chain(second_page.contracts.iter())
+                .map(|c| c.get_id())
+                .collect::<Vec<_>>();
+            let mut sorted_ids = ids.clone();
+            sorted_ids.sort();
+            assert_eq!(sorted_ids, ids);
+
+            let signed_page = storage
+                .get_contracts_page(Some(ContractState::Signed), None, 1)
+                .expect("Error retrieving contracts");
+            assert_eq!(1, signed_page.contracts.len());
+            assert!(signed_page.next_cursor.is_some());
+        }
+    );
+
+    sled_test!(
+        iter_contracts_yields_contracts_in_state,
+        |mut storage: SledStorageProvider| {
+            insert_offered_signed_and_confirmed(&mut storage);
+
+            let confirmed = storage
+                .iter_contracts(Some(ContractState::Confirmed), 1)
+                .collect::<Result<Vec<_>, _>>()
+                .expect("Error retrieving contracts");
+            assert_eq!(2, confirmed.len());
+            assert!(confirmed
+                .iter()
+                .all(|c| c.get_state() == ContractState::Confirmed));
+            assert_eq!(6, storage.iter_contracts(None, 4).count());
+        }
+    );
+
+    sled_test!(
+        contracts_are_indexed_by_counter_party_and_event,
+        |mut storage: SledStorageProvider| {
+            insert_offered_signed_and_confirmed(&mut storage);
+            let contracts = storage.get_contracts().unwrap();
+            let contract = &contracts[0];
+
+            let counter_party = contract.get_counter_party_id();
+            let expected = contracts
+                .iter()
+                .filter(|c| c.get_counter_party_id() == counter_party)
+                .count();
+            assert_eq!(
+                expected,
+                storage
+                    .get_contracts_by_counter_party(&counter_party)
+                    .unwrap()
+                    .len()
+            );
+
+            let event_id = &contract.get_oracle_event_ids()[0];
+            let with_event = storage.get_contracts_by_event_id(event_id).unwrap();
+            assert!(with_event.iter().any(|c| c.get_id() == contract.get_id()));
+            assert!(storage
+                .get_contracts_by_event_id("unknown event")
+                .unwrap()
+                .is_empty());
+
+            let closed = Contract::Closed(ClosedContract {
+                attestations: None,
+                signed_cet: None,
+                contract_id: contract.get_id(),
+                temporary_contract_id: contract.get_temporary_id(),
+                counter_party_id: counter_party,
+                pnl: 0,
+                closed_at: 0,
+            });
+            storage.update_contract(&closed).unwrap();
+            let with_event = storage.get_contracts_by_event_id(event_id).unwrap();
+            assert!(
+                with_event
+                    .iter()
+                    .any(|c| c.get_id() == contract.get_id()
+                        && c.get_state() == ContractState::Closed)
+            );
+
+            storage.delete_contract(&contract.get_id()).unwrap();
+            assert!(storage
+                .get_contracts_by_event_id(event_id)
+                .unwrap()
+                .iter()
+                .all(|c| c.get_id() != contract.get_id()));
+            assert_eq!(
+                expected - 1,
+                storage
+                    .get_contracts_by_counter_party(&counter_party)
+                    .unwrap()
+                    .len()
+            );
+        }
+    );
+
+    sled_test!(
+        archived_contracts_are_removed_from_the_contracts,
+        |mut storage: SledStorageProvider| {
+            insert_offered_signed_and_confirmed(&mut storage);
+            let offered: OfferedContract =
+                deserialize_object(include_bytes!("../test_files/Offered"));
+            let rejected = Contract::Rejected(offered.clone());
+            storage.update_contract(&rejected).unwrap();
+            let nb_contracts = storage.get_contracts().unwrap().len();
+
+            let archive = ArchivedContract::from_contract(&rejected).unwrap();
+            storage.archive_contracts(&[archive]).unwrap();
+
+            assert!(storage.get_contract(&offered.id).unwrap().is_none());
+            assert_eq!(nb_contracts - 1, storage.get_contracts().unwrap().len());
+            assert!(storage
+                .get_contracts_by_counter_party(&offered.counter_party)
+                .unwrap()
+                .iter()
+                .all(|c| c.get_id() != offered.id));
+            let archived = storage.get_archived_contracts().unwrap();
+            assert_eq!(1, archived.len());
+            assert_eq!(offered.id, archived[0].contract_id);
+            assert_eq!(ContractState::Rejected, archived[0].state);
+        }
+    );
+
+    sled_test!(
+        state_index_is_updated_on_transitions,
+        |mut storage: SledStorageProvider| {
+            insert_offered_signed_and_confirmed(&mut storage);
+            let signed = storage.get_signed_contracts().unwrap();
+            assert_eq!(2, signed.len());
+
+            storage
+                .update_contract(&Contract::Confirmed(signed[0].clone()))
+                .unwrap();
+
+            assert_eq!(1, storage.get_signed_contracts().unwrap().len());
+            assert_eq!(3, storage.get_confirmed_contracts().unwrap().len());
+            assert_eq!(
+                3,
+                storage
+                    .iter_contracts(Some(ContractState::Confirmed), 2)
+                    .count()
+            );
+        }
+    );
+
     sled_test!(
         get_offered_channels_only_offered,
         |mut storage: SledStorageProvider| {
@@ -973,4 +1914,303 @@ mod tests {
             assert_eq!(chain_monitor, retrieved);
         }
     );
+
+    sled_test!(
+        write_batch_persists_all_writes,
+        |storage: SledStorageProvider| {
+            let serialized = include_bytes!("../test_files/Offered");
+            let offered_contract = deserialize_object(serialized);
+            storage
+                .create_contract(&offered_contract)
+                .expect("Error creating contract");
+
+            let serialized = include_bytes!("../test_files/Accepted");
+            let accepted_contract = Contract::Accepted(deserialize_object(serialized));
+            let serialized = include_bytes!("../test_files/AcceptedChannel");
+            let accepted_channel: AcceptedChannel = deserialize_object(serialized);
+            let channel_id = accepted_channel.channel_id;
+            let chain_monitor = ChainMonitor::new(123);
+
+            storage
+                .write_batch(&[
+                    StorageWrite::Contract(&accepted_contract),
+                    StorageWrite::Channel(&Channel::Accepted(accepted_channel)),
+                    StorageWrite::ChainMonitor(&chain_monitor),
+                ])
+                .expect("Error writing batch");
+
+            assert!(storage
+                .get_contract(&offered_contract.id)
+                .expect("Error retrieving contract")
+                .is_none());
+            assert!(storage
+                .get_contract(&accepted_contract.get_id())
+                .expect("Error retrieving contract")
+                .is_some());
+            assert!(storage
+                .get_channel(&channel_id)
+                .expect("Error retrieving channel")
+                .is_some());
+            assert_eq!(Some(chain_monitor), storage.get_chain_monitor().unwrap());
+        }
+    );
+
+    sled_test!(
+        legacy_records_are_read_and_migrated,
+        |storage: SledStorageProvider| {
+            let serialized = include_bytes!("../test_files/v0/Offered");
+            let offered_contract =
+                OfferedContract::deserialize_versioned(&mut Cursor::new(&serialized), 0)
+                    .expect("to be able to read the initial format");
+            let mut record = vec![ContractPrefix::Offered.into()];
+            record.extend_from_slice(serialized);
+            let contract_tree = storage.contract_tree().unwrap();
+            contract_tree.insert(offered_contract.id, record).unwrap();
+
+            let serialized = include_bytes!("../test_files/v0/SignedChannelEstablished");
+            let signed_channel =
+                SignedChannel::deserialize_versioned(&mut Cursor::new(&serialized), 0)
+                    .expect("to be able to read the initial format");
+            let mut record = vec![
+                ChannelPrefix::Signed.into(),
+                SignedChannelPrefix::Established.into(),
+            ];
+            record.extend_from_slice(serialized);
+            let channel_tree = storage.channel_tree().unwrap();
+            channel_tree
+                .insert(signed_channel.channel_id, record)
+                .unwrap();
+            // Data bases written by previous versions were not indexed.
+            storage
+                .contract_index_tree()
+                .unwrap()
+                .remove([INDEX_BUILT_KEY])
+                .unwrap();
+            storage.build_contract_index().unwrap();
+
+            assert_eq!(1, storage.get_contract_offers().unwrap().len());
+            assert_eq!(1, storage.get_signed_channels(None).unwrap().len());
+
+            storage.migrate().expect("to be able to migrate");
+
+            let value = contract_tree.get(offered_contract.id).unwrap().unwrap();
+            assert_eq!(Some(SERIALIZATION_VERSION), split_record(&value).0);
+            let value = channel_tree
+                .get(signed_channel.channel_id)
+                .unwrap()
+                .unwrap();
+            assert_eq!(Some(SERIALIZATION_VERSION), split_record(&value).0);
+
+            if let Some(Contract::Offered(retrieved)) =
+                storage.get_contract(&offered_contract.id).unwrap()
+            {
+                assert_eq!(
+                    offered_contract.serialize().unwrap(),
+                    retrieved.serialize().unwrap()
+                );
+            } else {
+                unreachable!();
+            }
+            assert_eq!(1, storage.get_signed_channels(None).unwrap().len());
+        }
+    );
+
+    sled_test!(
+        multi_party_contract_is_rekeyed_on_upsert,
+        |storage: SledStorageProvider| {
+            use dlc_messages::multi_party::{MultiPartyOffer, MultiPartyOutcome, MultiPartyParams};
+
+            let offered: OfferedContract =
+                deserialize_object(include_bytes!("../test_files/Offered"));
+            let params = &offered.offer_params;
+            let offer = MultiPartyOffer {
+                protocol_version: 1,
+                chain_hash: [0; 32],
+                temporary_contract_id: offered.id,
+                participants: vec![offered.counter_party, params.fund_pubkey],
+                party_index: 0,
+                collaterals: vec![100000, 100000, 100000],
+                outcomes: vec![MultiPartyOutcome {
+                    outcome: "a".to_string(),
+                    payouts: vec![300000, 0, 0],
+                }],
+                oracle_announcement: offered.contract_info[0].oracle_announcements[0].clone(),
+                offer_params: MultiPartyParams {
+                    funding_pubkey: params.fund_pubkey,
+                    payout_spk: params.payout_script_pubkey.clone(),
+                    payout_serial_id: params.payout_serial_id,
+                    funding_inputs: Vec::new(),
+                    change_spk: params.change_script_pubkey.clone(),
+                    change_serial_id: params.change_serial_id,
+                },
+                fund_output_serial_id: offered.fund_output_serial_id,
+                fee_rate_per_vb: offered.fee_rate_per_vb,
+                cet_locktime: offered.cet_locktime,
+                refund_locktime: offered.refund_locktime,
+            };
+            let mut contract = MultiPartyContract::new(offer, None);
+
+            storage
+                .upsert_multi_party_contract(&contract)
+                .expect("to be able to store the contract");
+            assert!(storage
+                .get_multi_party_contract(&contract.temporary_id)
+                .unwrap()
+                .is_some());
+
+            contract.id = [1; 32];
+            storage
+                .upsert_multi_party_contract(&contract)
+                .expect("to be able to update the contract");
+
+            assert!(storage
+                .get_multi_party_contract(&contract.temporary_id)
+                .unwrap()
+                .is_none());
+            let retrieved = storage
+                .get_multi_party_contract(&contract.id)
+                .unwrap()
+                .expect("to find the contract under its id");
+            assert_eq!(
+                contract.serialize().unwrap(),
+                retrieved.serialize().unwrap()
+            );
+            assert_eq!(1, storage.get_multi_party_contracts().unwrap().len());
+        }
+    );
+
+    sled_test!(
+        novation_contract_can_be_updated_and_retrieved,
+        |storage: SledStorageProvider| {
+            use dlc_manager::contract::novation_contract::NovationState;
+            use dlc_messages::novation::NovationTerms;
+
+            let signed: SignedContract =
+                deserialize_object(include_bytes!("../test_files/Confirmed"));
+            let offered = &signed.accepted_contract.offered_contract;
+            let terms = NovationTerms {
+                price: 50000,
+                price_spk: offered.offer_params.payout_script_pubkey.clone(),
+                price_serial_id: 1,
+                fund_output_serial_id: 2,
+                fee_rate_per_vb: 2,
+            };
+            let offer = dlc_manager::novation_updater::get_novation_offer(
+                &signed,
+                true,
+                offered.counter_party,
+                terms,
+            );
+            let mut contract = NovationContract {
+                contract_id: offer.contract_id,
+                state: NovationState::Offered,
+                transferor: None,
+                remaining_party: Some(offered.counter_party),
+                transferee: Some(offered.offer_params.fund_pubkey),
+                offer,
+                accept: None,
+                novation_transaction: None,
+                novated_contract: None,
+            };
+
+            storage
+                .upsert_novation_contract(&contract)
+                .expect("to be able to store the contract");
+
+            contract.state = NovationState::Confirmed;
+            contract.novated_contract = Some(signed.clone());
+            storage
+                .upsert_novation_contract(&contract)
+                .expect("to be able to update the contract");
+
+            let retrieved = storage
+                .get_novation_contract(&contract.contract_id)
+                .unwrap()
+                .expect("to find the contract");
+            assert_eq!(NovationState::Confirmed, retrieved.state);
+            assert_eq!(
+                contract.serialize().unwrap(),
+                retrieved.serialize().unwrap()
+            );
+            assert_eq!(1, storage.get_novation_contracts().unwrap().len());
+            assert!(storage.get_novation_contract(&[1; 32]).unwrap().is_none());
+        }
+    );
+
+    #[cfg(feature = "encryption")]
+    struct TestKeys {
+        current_key_id: u32,
+        keys: Vec<(u32, [u8; 32])>,
+    }
+
+    #[cfg(feature = "encryption")]
+    impl KeyProvider for TestKeys {
+        fn current_key_id(&self) -> u32 {
+            self.current_key_id
+        }
+
+        fn get_key(&self, key_id: u32) -> Option<[u8; 32]> {
+            self.keys.iter().find(|(id, _)| *id == key_id).map(|x| x.1)
+        }
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_values_can_be_read_after_key_rotation() {
+        let path = "test_files/sleddb/encrypted_values_can_be_read_after_key_rotation";
+        let offered_contract: OfferedContract =
+            deserialize_object(include_bytes!("../test_files/Offered"));
+        {
+            let storage = SledStorageProvider::new_encrypted(path, [1u8; 32])
+                .expect("to be able to create an encrypted data base");
+            storage
+                .create_contract(&offered_contract)
+                .expect("Error creating contract");
+            let raw = storage
+                .contract_tree()
+                .unwrap()
+                .get(offered_contract.id)
+                .unwrap()
+                .unwrap();
+            assert!(deserialize_contract(&raw).is_err());
+        }
+
+        assert!(SledStorageProvider::new(path).is_err());
+        assert!(SledStorageProvider::new_encrypted(path, [2u8; 32]).is_err());
+
+        {
+            let storage = SledStorageProvider::new_encrypted(
+                path,
+                TestKeys {
+                    current_key_id: 1,
+                    keys: vec![(0, [1u8; 32]), (1, [2u8; 32])],
+                },
+            )
+            .expect("to be able to open the data base with the previous key");
+            assert!(storage
+                .get_contract(&offered_contract.id)
+                .unwrap()
+                .is_some());
+            storage.rotate_key().expect("to be able to rotate the key");
+        }
+
+        {
+            let storage = SledStorageProvider::new_encrypted(
+                path,
+                TestKeys {
+                    current_key_id: 1,
+                    keys: vec![(1, [2u8; 32])],
+                },
+            )
+            .expect("to be able to open the data base without the previous key");
+            let retrieved = storage.get_contract_offers().unwrap();
+            assert_eq!(1, retrieved.len());
+            assert_eq!(
+                offered_contract.serialize().unwrap(),
+                retrieved[0].serialize().unwrap()
+            );
+        }
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
 }
@@ -0,0 +1,1372 @@
+//! # dlc-sqlite-storage-provider
+//! Storage provider for dlc-manager using SQLite as underlying storage.
+
+#![crate_name = "dlc_sqlite_storage_provider"]
+// Coding conventions
+#![deny(non_upper_case_globals)]
+#![deny(non_camel_case_types)]
+#![deny(non_snake_case)]
+#![deny(unused_mut)]
+#![deny(dead_code)]
+#![deny(unused_imports)]
+#![deny(missing_docs)]
+
+extern crate dlc_manager;
+extern crate dlc_messages;
+extern crate rusqlite;
+
+use dlc_manager::chain_monitor::ChainMonitor;
+use dlc_manager::channel::offered_channel::OfferedChannel;
+use dlc_manager::channel::signed_channel::{SignedChannel, SignedChannelStateType};
+use dlc_manager::channel::Channel;
+use dlc_manager::contract::multi_party_contract::MultiPartyContract;
+use dlc_manager::contract::novation_contract::NovationContract;
+use dlc_manager::contract::offered_contract::OfferedContract;
+use dlc_manager::contract::ser::Serializable;
+use dlc_manager::contract::signed_contract::SignedContract;
+use dlc_manager::contract::versioned_ser::{VersionedSerializable, SERIALIZATION_VERSION};
+use dlc_manager::contract::{ArchivedContract, Contract, PreClosedContract};
+use dlc_manager::{error::Error, ChannelId, ContractId, ContractPage, Storage, StorageWrite};
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
+use dlc_messages::Message;
+use rusqlite::{params, Connection, OptionalExtension, Params, Transaction};
+use secp256k1_zkp::{PublicKey, XOnlyPublicKey};
+use std::convert::TryInto;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// The migrations creating and upgrading the schema of the data base. The
+/// schema version stored in the `user_version` pragma is the number of
+/// migrations applied, so new migrations must only ever be appended.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE contracts (
+        id BLOB PRIMARY KEY NOT NULL,
+        state INTEGER NOT NULL,
+        counter_party BLOB NOT NULL,
+        data BLOB NOT NULL
+    );
+    CREATE INDEX contracts_state ON contracts (state);
+    CREATE INDEX contracts_counter_party ON contracts (counter_party);
+    CREATE TABLE channels (
+        id BLOB PRIMARY KEY NOT NULL,
+        state INTEGER NOT NULL,
+        signed_state INTEGER,
+        counter_party BLOB NOT NULL,
+        data BLOB NOT NULL
+    );
+    CREATE INDEX channels_state ON channels (state, signed_state);
+    CREATE INDEX channels_counter_party ON channels (counter_party);
+    CREATE TABLE chain_monitor (
+        id INTEGER PRIMARY KEY NOT NULL CHECK (id = 0),
+        data BLOB NOT NULL
+    );
+    CREATE TABLE oracle_announcements (
+        oracle_public_key BLOB NOT NULL,
+        event_id TEXT NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (oracle_public_key, event_id)
+    );
+    CREATE TABLE oracle_attestations (
+        oracle_public_key BLOB NOT NULL,
+        event_id TEXT NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (oracle_public_key, event_id)
+    );
+    CREATE TABLE outbound_messages (
+        node_id BLOB PRIMARY KEY NOT NULL,
+        data BLOB NOT NULL
+    );",
+    "ALTER TABLE contracts ADD COLUMN version INTEGER NOT NULL DEFAULT 2;
+    ALTER TABLE channels ADD COLUMN version INTEGER NOT NULL DEFAULT 2;",
+    "DROP INDEX contracts_state;
+    CREATE INDEX contracts_state ON contracts (state, id);
+    CREATE TABLE contract_events (
+        event_id TEXT NOT NULL,
+        contract_id BLOB NOT NULL,
+        PRIMARY KEY (event_id, contract_id)
+    );
+    CREATE INDEX contract_events_contract_id ON contract_events (contract_id);",
+    "CREATE TABLE archived_contracts (
+        id BLOB PRIMARY KEY NOT NULL,
+        data BLOB NOT NULL
+    );",
+    "CREATE TABLE multi_party_contracts (
+        id BLOB PRIMARY KEY NOT NULL,
+        data BLOB NOT NULL
+    );",
+    "CREATE TABLE novation_contracts (
+        id BLOB PRIMARY KEY NOT NULL,
+        data BLOB NOT NULL
+    );",
+];
+
+/// The schema version introducing the `contract_events` table, which gets
+/// populated from the existing contracts when migrating to it.
+const CONTRACT_EVENTS_VERSION: usize = 3;
+
+/// Implementation of Storage interface using an SQLite data base.
+pub struct SqliteStorageProvider {
+    connection: Mutex<Connection>,
+}
+
+macro_rules! convertible_enum {
+    (enum $name:ident {
+        $($vname:ident $(= $val:expr)?,)*;
+        $($tname:ident $(= $tval:expr)?,)*
+    }, $input:ident) => {
+        #[derive(Debug)]
+        enum $name {
+            $($vname $(= $val)?,)*
+            $($tname $(= $tval)?,)*
+        }
+
+        impl From<$name> for u8 {
+            fn from(prefix: $name) -> u8 {
+                prefix as u8
+            }
+        }
+
+        impl std::convert::TryFrom<u8> for $name {
+            type Error = Error;
+
+            fn try_from(v: u8) -> Result<Self, Self::Error> {
+                match v {
+                    $(x if x == u8::from($name::$vname) => Ok($name::$vname),)*
+                    $(x if x == u8::from($name::$tname) => Ok($name::$tname),)*
+                    _ => Err(Error::StorageError("Unknown state".to_string())),
+                }
+            }
+        }
+
+        impl $name {
+            fn get_state(input: &$input) -> u8 {
+                let state = match input {
+                    $($input::$vname(_) => $name::$vname,)*
+                    $($input::$tname{..} => $name::$tname,)*
+                };
+                state.into()
+            }
+        }
+    }
+}
+
+convertible_enum!(
+    enum ContractState {
+        Offered = 1,
+        Accepted,
+        Signed,
+        Confirmed,
+        PreClosed,
+        Closed,
+        FailedAccept,
+        FailedSign,
+        Refunded,
+        Rejected,;
+    },
+    Contract
+);
+
+convertible_enum!(
+    enum ChannelState {
+        Offered = 1,
+        Accepted,
+        Signed,
+        FailedAccept,
+        FailedSign,;
+    },
+    Channel
+);
+
+convertible_enum!(
+    enum SignedChannelState {;
+        Established = 1,
+        SettledOffered,
+        SettledReceived,
+        SettledAccepted,
+        SettledConfirmed,
+        Settled,
+        Closing,
+        Closed,
+        CounterClosed,
+        ClosedPunished,
+        CollaborativeCloseOffered,
+        CollaborativelyClosed,
+        RenewAccepted,
+        RenewOffered,
+        RenewConfirmed,
+    },
+    SignedChannelStateType
+);
+
+fn to_storage_error<T>(e: T) -> Error
+where
+    T: std::fmt::Display,
+{
+    Error::StorageError(e.to_string())
+}
+
+impl SqliteStorageProvider {
+    /// Opens the data base at the given path, creating it if it does not
+    /// exist, and applies the pending migrations.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let connection = Connection::open(path).map_err(to_storage_error)?;
+        connection
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .map_err(to_storage_error)?;
+        Self::from_connection(connection)
+    }
+
+    /// Creates a new instance backed by an in memory data base.
+    pub fn new_in_memory() -> Result<Self, Error> {
+        Self::from_connection(Connection::open_in_memory().map_err(to_storage_error)?)
+    }
+
+    fn from_connection(mut connection: Connection) -> Result<Self, Error> {
+        migrate_schema(&mut connection)?;
+        Ok(SqliteStorageProvider {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Returns the version of the schema of the data base.
+    pub fn get_schema_version(&self) -> Result<u32, Error> {
+        get_schema_version(&self.connection())
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap()
+    }
+
+    fn get_data<T: Serializable, P: Params>(
+        &self,
+        query: &str,
+        params: P,
+    ) -> Result<Vec<T>, Error> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(query).map_err(to_storage_error)?;
+        let rows = statement
+            .query_map(params, |row| row.get::<_, Vec<u8>>(0))
+            .map_err(to_storage_error)?;
+        let mut res = Vec::new();
+        for data in rows {
+            res.push(deserialize(&data.map_err(to_storage_error)?)?);
+        }
+        Ok(res)
+    }
+
+    fn query_contracts<P: Params>(&self, query: &str, params: P) -> Result<Vec<Contract>, Error> {
+        query_contracts(&self.connection(), query, params)
+    }
+
+    fn get_versioned_data<T: VersionedSerializable, P: Params>(
+        &self,
+        query: &str,
+        params: P,
+    ) -> Result<Vec<T>, Error> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(query).map_err(to_storage_error)?;
+        let rows = statement
+            .query_map(params, |row| {
+                Ok((row.get::<_, u8>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(to_storage_error)?;
+        let mut res = Vec::new();
+        for row in rows {
+            let (version, data) = row.map_err(to_storage_error)?;
+            res.push(deserialize_versioned(version, &data)?);
+        }
+        Ok(res)
+    }
+
+    fn get_contracts_with_state<T: VersionedSerializable>(
+        &self,
+        state: ContractState,
+    ) -> Result<Vec<T>, Error> {
+        self.get_versioned_data(
+            "SELECT version, data FROM contracts WHERE state = ?1",
+            params![u8::from(state)],
+        )
+    }
+
+    fn get_oracle_data<T: Serializable>(
+        &self,
+        table: &str,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<T>, Error> {
+        let query = format!(
+            "SELECT data FROM {} WHERE oracle_public_key = ?1 AND event_id = ?2",
+            table
+        );
+        self.get_data(
+            &query,
+            params![&oracle_public_key.serialize()[..], event_id],
+        )
+        .map(|mut res: Vec<T>| res.pop())
+    }
+
+    fn persist_oracle_data(
+        &self,
+        table: &str,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+        data: Vec<u8>,
+    ) -> Result<(), Error> {
+        let query = format!(
+            "INSERT OR REPLACE INTO {} (oracle_public_key, event_id, data) VALUES (?1, ?2, ?3)",
+            table
+        );
+        self.connection()
+            .execute(
+                &query,
+                params![&oracle_public_key.serialize()[..], event_id, data],
+            )
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorageProvider {
+    fn get_contract(&self, contract_id: &ContractId) -> Result<Option<Contract>, Error> {
+        self.connection()
+            .query_row(
+                "SELECT state, version, data FROM contracts WHERE id = ?1",
+                params![&contract_id[..]],
+                |row| {
+                    Ok((
+                        row.get::<_, u8>(0)?,
+                        row.get::<_, u8>(1)?,
+                        row.get::<_, Vec<u8>>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(to_storage_error)?
+            .map(|(state, version, data)| deserialize_contract(state, version, &data))
+            .transpose()
+    }
+
+    fn get_contracts(&self) -> Result<Vec<Contract>, Error> {
+        self.query_contracts("SELECT state, version, data FROM contracts", [])
+    }
+
+    fn get_contracts_page(
+        &self,
+        state: Option<dlc_manager::contract::ContractState>,
+        cursor: Option<ContractId>,
+        limit: usize,
+    ) -> Result<ContractPage, Error> {
+        // Any id is greater than the empty blob.
+        let cursor = cursor.map(|c| c.to_vec()).unwrap_or_default();
+        let limit_param = limit as i64 + 1;
+        let mut contracts = match state {
+            Some(state) => self.query_contracts(
+                "SELECT state, version, data FROM contracts WHERE state = ?1 AND id > ?2 ORDER BY id LIMIT ?3",
+                params![get_stored_state(state), cursor, limit_param],
+            )?,
+            None => self.query_contracts(
+                "SELECT state, version, data FROM contracts WHERE id > ?1 ORDER BY id LIMIT ?2",
+                params![cursor, limit_param],
+            )?,
+        };
+        let next_cursor = if contracts.len() > limit {
+            contracts.truncate(limit);
+            contracts.last().map(Contract::get_id)
+        } else {
+            None
+        };
+        Ok(ContractPage {
+            contracts,
+            next_cursor,
+        })
+    }
+
+    fn get_contracts_by_counter_party(
+        &self,
+        counter_party: &PublicKey,
+    ) -> Result<Vec<Contract>, Error> {
+        self.query_contracts(
+            "SELECT state, version, data FROM contracts WHERE counter_party = ?1",
+            params![&counter_party.serialize()[..]],
+        )
+    }
+
+    fn get_contracts_by_event_id(&self, event_id: &str) -> Result<Vec<Contract>, Error> {
+        self.query_contracts(
+            "SELECT c.state, c.version, c.data FROM contract_events e JOIN contracts c ON c.id = e.contract_id WHERE e.event_id = ?1",
+            params![event_id],
+        )
+    }
+
+    fn create_contract(&self, contract: &OfferedContract) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let tx = connection.transaction().map_err(to_storage_error)?;
+        insert_contract(&tx, &Contract::Offered(contract.clone()))?;
+        tx.commit().map_err(to_storage_error)
+    }
+
+    fn delete_contract(&self, contract_id: &ContractId) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let tx = connection.transaction().map_err(to_storage_error)?;
+        tx.execute(
+            "DELETE FROM contracts WHERE id = ?1",
+            params![&contract_id[..]],
+        )
+        .map_err(to_storage_error)?;
+        tx.execute(
+            "DELETE FROM contract_events WHERE contract_id = ?1",
+            params![&contract_id[..]],
+        )
+        .map_err(to_storage_error)?;
+        tx.commit().map_err(to_storage_error)
+    }
+
+    fn archive_contracts(&self, archives: &[ArchivedContract]) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let tx = connection.transaction().map_err(to_storage_error)?;
+        for archive in archives {
+            let id = &archive.contract_id[..];
+            tx.execute("DELETE FROM contracts WHERE id = ?1", params![id])
+                .map_err(to_storage_error)?;
+            tx.execute(
+                "DELETE FROM contract_events WHERE contract_id = ?1",
+                params![id],
+            )
+            .map_err(to_storage_error)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO archived_contracts (id, data) VALUES (?1, ?2)",
+                params![id, archive.serialize()?],
+            )
+            .map_err(to_storage_error)?;
+        }
+        tx.commit().map_err(to_storage_error)
+    }
+
+    fn get_archived_contracts(&self) -> Result<Vec<ArchivedContract>, Error> {
+        self.get_data("SELECT data FROM archived_contracts", [])
+    }
+
+    fn update_contract(&self, contract: &Contract) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let tx = connection.transaction().map_err(to_storage_error)?;
+        insert_contract(&tx, contract)?;
+        tx.commit().map_err(to_storage_error)
+    }
+
+    fn get_signed_contracts(&self) -> Result<Vec<SignedContract>, Error> {
+        self.get_contracts_with_state(ContractState::Signed)
+    }
+
+    fn get_confirmed_contracts(&self) -> Result<Vec<SignedContract>, Error> {
+        self.get_contracts_with_state(ContractState::Confirmed)
+    }
+
+    fn get_contract_offers(&self) -> Result<Vec<OfferedContract>, Error> {
+        self.get_contracts_with_state(ContractState::Offered)
+    }
+
+    fn get_preclosed_contracts(&self) -> Result<Vec<PreClosedContract>, Error> {
+        self.get_contracts_with_state(ContractState::PreClosed)
+    }
+
+    fn upsert_channel(&self, channel: Channel, contract: Option<Contract>) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let tx = connection.transaction().map_err(to_storage_error)?;
+        insert_channel(&tx, &channel)?;
+        if let Some(c) = contract.as_ref() {
+            insert_contract(&tx, c)?;
+        }
+        tx.commit().map_err(to_storage_error)
+    }
+
+    fn delete_channel(&self, channel_id: &ChannelId) -> Result<(), Error> {
+        self.connection()
+            .execute(
+                "DELETE FROM channels WHERE id = ?1",
+                params![&channel_id[..]],
+            )
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    fn get_channel(&self, channel_id: &ChannelId) -> Result<Option<Channel>, Error> {
+        self.connection()
+            .query_row(
+                "SELECT state, version, data FROM channels WHERE id = ?1",
+                params![&channel_id[..]],
+                |row| {
+                    Ok((
+                        row.get::<_, u8>(0)?,
+                        row.get::<_, u8>(1)?,
+                        row.get::<_, Vec<u8>>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(to_storage_error)?
+            .map(|(state, version, data)| deserialize_channel(state, version, &data))
+            .transpose()
+    }
+
+    fn get_signed_channels(
+        &self,
+        channel_state: Option<SignedChannelStateType>,
+    ) -> Result<Vec<SignedChannel>, Error> {
+        let state = u8::from(ChannelState::Signed);
+        match channel_state {
+            Some(s) => self.get_versioned_data(
+                "SELECT version, data FROM channels WHERE state = ?1 AND signed_state = ?2",
+                params![state, SignedChannelState::get_state(&s)],
+            ),
+            None => self.get_versioned_data(
+                "SELECT version, data FROM channels WHERE state = ?1",
+                params![state],
+            ),
+        }
+    }
+
+    fn get_offered_channels(&self) -> Result<Vec<OfferedChannel>, Error> {
+        self.get_versioned_data(
+            "SELECT version, data FROM channels WHERE state = ?1",
+            params![u8::from(ChannelState::Offered)],
+        )
+    }
+
+    fn write_batch(&self, writes: &[StorageWrite]) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let tx = connection.transaction().map_err(to_storage_error)?;
+        for write in writes {
+            match write {
+                StorageWrite::Contract(contract) => insert_contract(&tx, contract)?,
+                StorageWrite::Channel(channel) => insert_channel(&tx, channel)?,
+                StorageWrite::ChainMonitor(monitor) => insert_chain_monitor(&tx, monitor)?,
+            }
+        }
+        tx.commit().map_err(to_storage_error)
+    }
+
+    fn migrate(&self) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let tx = connection.transaction().map_err(to_storage_error)?;
+        let contracts = select_outdated(&tx, "contracts")?
+            .into_iter()
+            .map(|(state, version, data)| deserialize_contract(state, version, &data))
+            .collect::<Result<Vec<_>, Error>>()?;
+        for contract in &contracts {
+            insert_contract(&tx, contract)?;
+        }
+        let channels = select_outdated(&tx, "channels")?
+            .into_iter()
+            .map(|(state, version, data)| deserialize_channel(state, version, &data))
+            .collect::<Result<Vec<_>, Error>>()?;
+        for channel in &channels {
+            insert_channel(&tx, channel)?;
+        }
+        tx.commit().map_err(to_storage_error)
+    }
+
+    fn persist_chain_monitor(&self, monitor: &ChainMonitor) -> Result<(), Error> {
+        insert_chain_monitor(&self.connection(), monitor)
+    }
+
+    fn get_chain_monitor(&self) -> Result<Option<ChainMonitor>, Error> {
+        self.get_data("SELECT data FROM chain_monitor WHERE id = 0", [])
+            .map(|mut res: Vec<ChainMonitor>| res.pop())
+    }
+
+    fn persist_oracle_announcement(&self, announcement: &OracleAnnouncement) -> Result<(), Error> {
+        self.persist_oracle_data(
+            "oracle_announcements",
+            &announcement.oracle_public_key,
+            &announcement.oracle_event.event_id,
+            announcement.serialize()?,
+        )
+    }
+
+    fn get_oracle_announcement(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAnnouncement>, Error> {
+        self.get_oracle_data("oracle_announcements", oracle_public_key, event_id)
+    }
+
+    fn persist_oracle_attestation(
+        &self,
+        event_id: &str,
+        attestation: &OracleAttestation,
+    ) -> Result<(), Error> {
+        self.persist_oracle_data(
+            "oracle_attestations",
+            &attestation.oracle_public_key,
+            event_id,
+            attestation.serialize()?,
+        )
+    }
+
+    fn get_oracle_attestation(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAttestation>, Error> {
+        self.get_oracle_data("oracle_attestations", oracle_public_key, event_id)
+    }
+
+    fn persist_outbound_messages(
+        &self,
+        node_id: &PublicKey,
+        messages: &[Message],
+    ) -> Result<(), Error> {
+        let connection = self.connection();
+        let res = if messages.is_empty() {
+            connection.execute(
+                "DELETE FROM outbound_messages WHERE node_id = ?1",
+                params![&node_id.serialize()[..]],
+            )
+        } else {
+            connection.execute(
+                "INSERT OR REPLACE INTO outbound_messages (node_id, data) VALUES (?1, ?2)",
+                params![&node_id.serialize()[..], serialize_messages(messages)],
+            )
+        };
+        res.map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, Error> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare("SELECT node_id, data FROM outbound_messages")
+            .map_err(to_storage_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(to_storage_error)?;
+        let mut res = Vec::new();
+        for row in rows {
+            let (node_id, data) = row.map_err(to_storage_error)?;
+            let node_id = PublicKey::from_slice(&node_id).map_err(to_storage_error)?;
+            res.push((node_id, deserialize_messages(&data)?));
+        }
+        Ok(res)
+    }
+
+    fn upsert_multi_party_contract(&self, contract: &MultiPartyContract) -> Result<(), Error> {
+        let mut connection = self.connection();
+        let tx = connection.transaction().map_err(to_storage_error)?;
+        if contract.id != contract.temporary_id {
+            tx.execute(
+                "DELETE FROM multi_party_contracts WHERE id = ?1",
+                params![&contract.temporary_id[..]],
+            )
+            .map_err(to_storage_error)?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO multi_party_contracts (id, data) VALUES (?1, ?2)",
+            params![&contract.id[..], contract.serialize()?],
+        )
+        .map_err(to_storage_error)?;
+        tx.commit().map_err(to_storage_error)
+    }
+
+    fn get_multi_party_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<MultiPartyContract>, Error> {
+        self.get_data(
+            "SELECT data FROM multi_party_contracts WHERE id = ?1",
+            params![&id[..]],
+        )
+        .map(|mut res: Vec<MultiPartyContract>| res.pop())
+    }
+
+    fn get_multi_party_contracts(&self) -> Result<Vec<MultiPartyContract>, Error> {
+        self.get_data("SELECT data FROM multi_party_contracts", [])
+    }
+
+    fn upsert_novation_contract(&self, contract: &NovationContract) -> Result<(), Error> {
+        self.connection()
+            .execute(
+                "INSERT OR REPLACE INTO novation_contracts (id, data) VALUES (?1, ?2)",
+                params![&contract.contract_id[..], contract.serialize()?],
+            )
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    fn get_novation_contract(&self, id: &ContractId) -> Result<Option<NovationContract>, Error> {
+        self.get_data(
+            "SELECT data FROM novation_contracts WHERE id = ?1",
+            params![&id[..]],
+        )
+        .map(|mut res: Vec<NovationContract>| res.pop())
+    }
+
+    fn get_novation_contracts(&self) -> Result<Vec<NovationContract>, Error> {
+        self.get_data("SELECT data FROM novation_contracts", [])
+    }
+}
+
+fn get_schema_version(connection: &Connection) -> Result<u32, Error> {
+    connection
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(to_storage_error)
+}
+
+fn migrate_schema(connection: &mut Connection) -> Result<(), Error> {
+    let version = get_schema_version(connection)? as usize;
+    if version > MIGRATIONS.len() {
+        return Err(Error::StorageError(format!(
+            "Data base schema version {} is more recent than the supported version {}",
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+
+    let tx = connection.transaction().map_err(to_storage_error)?;
+    for migration in &MIGRATIONS[version..] {
+        tx.execute_batch(migration).map_err(to_storage_error)?;
+    }
+    if version > 0 && version < CONTRACT_EVENTS_VERSION {
+        let contracts = query_contracts(&tx, "SELECT state, version, data FROM contracts", [])?;
+        for contract in &contracts {
+            insert_contract_events(&tx, contract)?;
+        }
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len() as u32)
+        .map_err(to_storage_error)?;
+    tx.commit().map_err(to_storage_error)
+}
+
+/// Returns the state, version and data of the records of the given table that
+/// were serialized using a previous version of the format.
+fn select_outdated(tx: &Transaction, table: &str) -> Result<Vec<(u8, u8, Vec<u8>)>, Error> {
+    let query = format!(
+        "SELECT state, version, data FROM {} WHERE version < ?1",
+        table
+    );
+    let mut statement = tx.prepare(&query).map_err(to_storage_error)?;
+    let rows = statement
+        .query_map(params![SERIALIZATION_VERSION], |row| {
+            Ok((
+                row.get::<_, u8>(0)?,
+                row.get::<_, u8>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+            ))
+        })
+        .map_err(to_storage_error)?;
+    let mut res = Vec::new();
+    for row in rows {
+        res.push(row.map_err(to_storage_error)?);
+    }
+    Ok(res)
+}
+
+fn query_contracts<P: Params>(
+    connection: &Connection,
+    query: &str,
+    params: P,
+) -> Result<Vec<Contract>, Error> {
+    let mut statement = connection.prepare(query).map_err(to_storage_error)?;
+    let rows = statement
+        .query_map(params, |row| {
+            Ok((
+                row.get::<_, u8>(0)?,
+                row.get::<_, u8>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+            ))
+        })
+        .map_err(to_storage_error)?;
+    let mut contracts = Vec::new();
+    for row in rows {
+        let (state, version, data) = row.map_err(to_storage_error)?;
+        contracts.push(deserialize_contract(state, version, &data)?);
+    }
+    Ok(contracts)
+}
+
+fn insert_chain_monitor(connection: &Connection, monitor: &ChainMonitor) -> Result<(), Error> {
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO chain_monitor (id, data) VALUES (0, ?1)",
+            params![monitor.serialize()?],
+        )
+        .map_err(|e| Error::StorageError(format!("Error writing chain monitor: {}", e)))?;
+    Ok(())
+}
+
+fn insert_contract(tx: &Transaction, contract: &Contract) -> Result<(), Error> {
+    if let Contract::Accepted(_) | Contract::Signed(_) = contract {
+        let temporary_id = &contract.get_temporary_id()[..];
+        tx.execute("DELETE FROM contracts WHERE id = ?1", params![temporary_id])
+            .map_err(to_storage_error)?;
+        tx.execute(
+            "DELETE FROM contract_events WHERE contract_id = ?1",
+            params![temporary_id],
+        )
+        .map_err(to_storage_error)?;
+    }
+
+    // Closed contracts do not retain the events they depended on, so the
+    // ones recorded for their previous state are kept.
+    if !matches!(contract, Contract::Closed(_)) {
+        insert_contract_events(tx, contract)?;
+    }
+
+    tx.execute(
+        "INSERT OR REPLACE INTO contracts (id, state, counter_party, version, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            &contract.get_id()[..],
+            ContractState::get_state(contract),
+            &contract.get_counter_party_id().serialize()[..],
+            SERIALIZATION_VERSION,
+            serialize_contract(contract)?
+        ],
+    )
+    .map_err(to_storage_error)?;
+    Ok(())
+}
+
+fn insert_contract_events(tx: &Transaction, contract: &Contract) -> Result<(), Error> {
+    let contract_id = &contract.get_id()[..];
+    tx.execute(
+        "DELETE FROM contract_events WHERE contract_id = ?1",
+        params![contract_id],
+    )
+    .map_err(to_storage_error)?;
+    for event_id in contract.get_oracle_event_ids() {
+        tx.execute(
+            "INSERT INTO contract_events (event_id, contract_id) VALUES (?1, ?2)",
+            params![event_id, contract_id],
+        )
+        .map_err(to_storage_error)?;
+    }
+    Ok(())
+}
+
+fn insert_channel(tx: &Transaction, channel: &Channel) -> Result<(), Error> {
+    if let Channel::Accepted(_) | Channel::Signed(_) = channel {
+        tx.execute(
+            "DELETE FROM channels WHERE id = ?1",
+            params![&channel.get_temporary_id()[..]],
+        )
+        .map_err(to_storage_error)?;
+    }
+
+    let signed_state = match channel {
+        Channel::Signed(s) => Some(SignedChannelState::get_state(&s.state.get_type())),
+        _ => None,
+    };
+    tx.execute(
+        "INSERT OR REPLACE INTO channels (id, state, signed_state, counter_party, version, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            &channel.get_id()[..],
+            ChannelState::get_state(channel),
+            signed_state,
+            &channel.get_counter_party_id().serialize()[..],
+            SERIALIZATION_VERSION,
+            serialize_channel(channel)?
+        ],
+    )
+    .map_err(to_storage_error)?;
+    Ok(())
+}
+
+fn deserialize<T: Serializable>(data: &[u8]) -> Result<T, Error> {
+    T::deserialize(&mut Cursor::new(data)).map_err(to_storage_error)
+}
+
+fn deserialize_versioned<T: VersionedSerializable>(version: u8, data: &[u8]) -> Result<T, Error> {
+    T::deserialize_versioned(&mut Cursor::new(data), version).map_err(to_storage_error)
+}
+
+fn serialize_contract(contract: &Contract) -> Result<Vec<u8>, ::std::io::Error> {
+    match contract {
+        Contract::Offered(o) | Contract::Rejected(o) => o.serialize(),
+        Contract::Accepted(o) => o.serialize(),
+        Contract::Signed(o) | Contract::Confirmed(o) | Contract::Refunded(o) => o.serialize(),
+        Contract::FailedAccept(c) => c.serialize(),
+        Contract::FailedSign(c) => c.serialize(),
+        Contract::PreClosed(c) => c.serialize(),
+        Contract::Closed(c) => c.serialize(),
+    }
+}
+
+fn get_stored_state(state: dlc_manager::contract::ContractState) -> u8 {
+    use dlc_manager::contract::ContractState as State;
+    let stored_state = match state {
+        State::Offered => ContractState::Offered,
+        State::Accepted => ContractState::Accepted,
+        State::Signed => ContractState::Signed,
+        State::Confirmed => ContractState::Confirmed,
+        State::PreClosed => ContractState::PreClosed,
+        State::Closed => ContractState::Closed,
+        State::Refunded => ContractState::Refunded,
+        State::FailedAccept => ContractState::FailedAccept,
+        State::FailedSign => ContractState::FailedSign,
+        State::Rejected => ContractState::Rejected,
+    };
+    stored_state.into()
+}
+
+fn deserialize_contract(state: u8, version: u8, data: &[u8]) -> Result<Contract, Error> {
+    let contract_state: ContractState = state.try_into()?;
+    let contract = match contract_state {
+        ContractState::Offered => Contract::Offered(deserialize_versioned(version, data)?),
+        ContractState::Accepted => Contract::Accepted(deserialize_versioned(version, data)?),
+        ContractState::Signed => Contract::Signed(deserialize_versioned(version, data)?),
+        ContractState::Confirmed => Contract::Confirmed(deserialize_versioned(version, data)?),
+        ContractState::PreClosed => Contract::PreClosed(deserialize_versioned(version, data)?),
+        ContractState::Closed => Contract::Closed(deserialize_versioned(version, data)?),
+        ContractState::FailedAccept => {
+            Contract::FailedAccept(deserialize_versioned(version, data)?)
+        }
+        ContractState::FailedSign => Contract::FailedSign(deserialize_versioned(version, data)?),
+        ContractState::Refunded => Contract::Refunded(deserialize_versioned(version, data)?),
+        ContractState::Rejected => Contract::Rejected(deserialize_versioned(version, data)?),
+    };
+    Ok(contract)
+}
+
+fn serialize_channel(channel: &Channel) -> Result<Vec<u8>, ::std::io::Error> {
+    match channel {
+        Channel::Offered(o) => o.serialize(),
+        Channel::Accepted(a) => a.serialize(),
+        Channel::Signed(s) => s.serialize(),
+        Channel::FailedAccept(f) => f.serialize(),
+        Channel::FailedSign(f) => f.serialize(),
+    }
+}
+
+fn deserialize_channel(state: u8, version: u8, data: &[u8]) -> Result<Channel, Error> {
+    let channel_state: ChannelState = state.try_into()?;
+    let channel = match channel_state {
+        ChannelState::Offered => Channel::Offered(deserialize_versioned(version, data)?),
+        ChannelState::Accepted => Channel::Accepted(deserialize_versioned(version, data)?),
+        ChannelState::Signed => Channel::Signed(deserialize_versioned(version, data)?),
+        ChannelState::FailedAccept => Channel::FailedAccept(deserialize_versioned(version, data)?),
+        ChannelState::FailedSign => Channel::FailedSign(deserialize_versioned(version, data)?),
+    };
+    Ok(channel)
+}
+
+// Messages ending with a TLV stream are read until the end of their reader,
+// so each message is prefixed with its length.
+fn serialize_messages(messages: &[Message]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for message in messages {
+        let encoded = message.encode_with_type();
+        buf.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        buf.extend(encoded);
+    }
+    buf
+}
+
+fn deserialize_messages(data: &[u8]) -> Result<Vec<Message>, Error> {
+    let mut cursor = Cursor::new(data);
+    let mut messages = Vec::new();
+    while (cursor.position() as usize) < data.len() {
+        let mut len = [0u8; 4];
+        cursor.read_exact(&mut len).map_err(to_storage_error)?;
+        let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+        cursor.read_exact(&mut buf).map_err(to_storage_error)?;
+        messages.push(Message::read_with_type(&mut Cursor::new(buf)).map_err(to_storage_error)?);
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dlc_manager::channel::accepted_channel::AcceptedChannel;
+    use dlc_manager::contract::versioned_ser::PRE_METADATA_VERSION;
+    use dlc_manager::contract::ClosedContract;
+
+    macro_rules! sqlite_test {
+        ($name: ident, $body: expr) => {
+            #[test]
+            fn $name() {
+                let dir = format!("{}{}", "test_files/sqlitedb/", std::stringify!($name));
+                std::fs::create_dir_all(&dir).unwrap();
+                {
+                    let storage = SqliteStorageProvider::new(format!("{}/dlc.db", dir))
+                        .expect("Error opening SQLite DB");
+                    $body(storage);
+                }
+                std::fs::remove_dir_all(dir).unwrap();
+            }
+        };
+    }
+
+    macro_rules! test_file {
+        ($name: literal) => {
+            include_bytes!(concat!(
+                "../../dlc-sled-storage-provider/test_files/",
+                $name
+            ))
+        };
+    }
+
+    fn deserialize_object<T>(serialized: &[u8]) -> T
+    where
+        T: Serializable,
+    {
+        let mut cursor = std::io::Cursor::new(&serialized);
+        T::deserialize(&mut cursor).unwrap()
+    }
+
+    fn insert_offered_signed_and_confirmed(storage: &SqliteStorageProvider) {
+        let offered_contract = deserialize_object(test_file!("Offered"));
+        storage
+            .create_contract(&offered_contract)
+            .expect("Error creating contract");
+
+        for serialized in [&test_file!("Signed")[..], &test_file!("Signed1")[..]] {
+            let signed_contract = Contract::Signed(deserialize_object(serialized));
+            storage
+                .update_contract(&signed_contract)
+                .expect("Error updating contract");
+        }
+
+        for serialized in [&test_file!("Confirmed")[..], &test_file!("Confirmed1")[..]] {
+            let confirmed_contract = Contract::Confirmed(deserialize_object(serialized));
+            storage
+                .update_contract(&confirmed_contract)
+                .expect("Error updating contract");
+        }
+
+        let preclosed_contract = Contract::PreClosed(deserialize_object(test_file!("PreClosed")));
+        storage
+            .update_contract(&preclosed_contract)
+            .expect("Error updating contract");
+    }
+
+    fn insert_offered_and_signed_channels(storage: &SqliteStorageProvider) {
+        let offered_contract = deserialize_object(test_file!("Offered"));
+        let offered_channel = deserialize_object(test_file!("OfferedChannel"));
+        storage
+            .upsert_channel(
+                Channel::Offered(offered_channel),
+                Some(Contract::Offered(offered_contract)),
+            )
+            .expect("Error creating channel");
+
+        for serialized in [
+            &test_file!("SignedChannelEstablished")[..],
+            &test_file!("SignedChannelSettled")[..],
+        ] {
+            let signed_channel = Channel::Signed(deserialize_object(serialized));
+            storage
+                .upsert_channel(signed_channel, None)
+                .expect("Error creating channel");
+        }
+    }
+
+    sqlite_test!(
+        migrations_are_applied_once,
+        |storage: SqliteStorageProvider| {
+            assert_eq!(
+                MIGRATIONS.len() as u32,
+                storage.get_schema_version().unwrap()
+            );
+            let mut connection = storage.connection();
+            migrate_schema(&mut connection).expect("migrations to be idempotent");
+            let journal_mode: String = connection
+                .pragma_query_value(None, "journal_mode", |row| row.get(0))
+                .unwrap();
+            assert_eq!("wal", journal_mode);
+        }
+    );
+
+    sqlite_test!(
+        outdated_records_are_migrated,
+        |storage: SqliteStorageProvider| {
+            let offered_contract: OfferedContract = deserialize_object(test_file!("Offered"));
+            assert!(offered_contract.metadata.is_none());
+            let mut serialized = offered_contract.serialize().unwrap();
+            // Offered contracts did not include metadata, the shared funding
+            // share nor the rollover information before.
+            serialized.truncate(serialized.len() - 3);
+            storage
+                .connection()
+                .execute(
+                    "INSERT INTO contracts (id, state, counter_party, version, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        &offered_contract.id[..],
+                        u8::from(ContractState::Offered),
+                        &offered_contract.counter_party.serialize()[..],
+                        PRE_METADATA_VERSION,
+                        serialized
+                    ],
+                )
+                .unwrap();
+
+            assert_eq!(1, storage.get_contract_offers().unwrap().len());
+
+            storage.migrate().expect("to be able to migrate");
+
+            let version: u8 = storage
+                .connection()
+                .query_row(
+                    "SELECT version FROM contracts WHERE id = ?1",
+                    params![&offered_contract.id[..]],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(SERIALIZATION_VERSION, version);
+            if let Some(Contract::Offered(retrieved)) =
+                storage.get_contract(&offered_contract.id).unwrap()
+            {
+                assert_eq!(
+                    offered_contract.serialize().unwrap(),
+                    retrieved.serialize().unwrap()
+                );
+            } else {
+                unreachable!();
+            }
+        }
+    );
+
+    sqlite_test!(contracts_are_paginated, |storage: SqliteStorageProvider| {
+        insert_offered_signed_and_confirmed(&storage);
+
+        let first_page = storage
+            .get_contracts_page(None, None, 4)
+            .expect("Error retrieving contracts");
+        assert_eq!(4, first_page.contracts.len());
+        let second_page = storage
+            .get_contracts_page(None, first_page.next_cursor, 4)
+            .expect("Error retrieving contracts");
+        assert_eq!(2, second_page.contracts.len());
+        assert!(second_page.next_cursor.is_none());
+
+        let signed = storage
+            .iter_contracts(Some(dlc_manager::contract::ContractState::Signed), 1)
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Error retrieving contracts");
+        assert_eq!(2, signed.len());
+    });
+
+    sqlite_test!(
+        contracts_can_be_queried_by_counter_party_and_event,
+        |storage: SqliteStorageProvider| {
+            insert_offered_signed_and_confirmed(&storage);
+            let contracts = storage.get_contracts().unwrap();
+            let contract = &contracts[0];
+            let counter_party = contract.get_counter_party_id();
+            assert_eq!(
+                contracts
+                    .iter()
+                    .filter(|c| c.get_counter_party_id() == counter_party)
+                    .count(),
+                storage
+                    .get_contracts_by_counter_party(&counter_party)
+                    .unwrap()
+                    .len()
+            );
+
+            let event_id = &contract.get_oracle_event_ids()[0];
+            let closed = Contract::Closed(ClosedContract {
+                attestations: None,
+                signed_cet: None,
+                contract_id: contract.get_id(),
+                temporary_contract_id: contract.get_temporary_id(),
+                counter_party_id: counter_party,
+                pnl: 0,
+                closed_at: 0,
+            });
+            storage.update_contract(&closed).unwrap();
+            assert!(storage
+                .get_contracts_by_event_id(event_id)
+                .unwrap()
+                .iter()
+                .any(|c| matches!(c, Contract::Closed(_))));
+
+            storage.delete_contract(&contract.get_id()).unwrap();
+            assert!(storage
+                .get_contracts_by_event_id(event_id)
+                .unwrap()
+                .iter()
+                .all(|c| c.get_id() != contract.get_id()));
+        }
+    );
+
+    sqlite_test!(
+        archived_contracts_are_removed_from_the_contracts,
+        |storage: SqliteStorageProvider| {
+            insert_offered_signed_and_confirmed(&storage);
+            let offered: OfferedContract = deserialize_object(test_file!("Offered"));
+            let rejected = Contract::Rejected(offered.clone());
+            storage.update_contract(&rejected).unwrap();
+            let nb_contracts = storage.get_contracts().unwrap().len();
+
+            let archive = ArchivedContract::from_contract(&rejected).unwrap();
+            storage.archive_contracts(&[archive]).unwrap();
+
+            assert!(storage.get_contract(&offered.id).unwrap().is_none());
+            assert_eq!(nb_contracts - 1, storage.get_contracts().unwrap().len());
+            let archived = storage.get_archived_contracts().unwrap();
+            assert_eq!(1, archived.len());
+            assert_eq!(offered.id, archived[0].contract_id);
+        }
+    );
+
+    sqlite_test!(
+        update_contract_replaces_temporary_id,
+        |storage: SqliteStorageProvider| {
+            let offered_contract: OfferedContract = deserialize_object(test_file!("Offered"));
+            storage
+                .create_contract(&offered_contract)
+                .expect("Error creating contract");
+
+            let accepted_contract = Contract::Accepted(deserialize_object(test_file!("Accepted")));
+            storage
+                .update_contract(&accepted_contract)
+                .expect("Error updating contract");
+
+            if let Some(Contract::Accepted(_)) = storage
+                .get_contract(&accepted_contract.get_id())
+                .expect("Error retrieving contract")
+            {
+            } else {
+                unreachable!();
+            }
+            assert!(storage
+                .get_contract(&offered_contract.id)
+                .expect("Error retrieving contract")
+                .is_none());
+
+            storage
+                .delete_contract(&accepted_contract.get_id())
+                .expect("Error deleting contract");
+            assert!(storage.get_contracts().unwrap().is_empty());
+        }
+    );
+
+    sqlite_test!(get_contracts_by_state, |storage: SqliteStorageProvider| {
+        insert_offered_signed_and_confirmed(&storage);
+
+        assert_eq!(6, storage.get_contracts().unwrap().len());
+        assert_eq!(1, storage.get_contract_offers().unwrap().len());
+        assert_eq!(2, storage.get_signed_contracts().unwrap().len());
+        assert_eq!(2, storage.get_confirmed_contracts().unwrap().len());
+        assert_eq!(1, storage.get_preclosed_contracts().unwrap().len());
+    });
+
+    sqlite_test!(get_channels_by_state, |storage: SqliteStorageProvider| {
+        insert_offered_and_signed_channels(&storage);
+
+        assert_eq!(1, storage.get_offered_channels().unwrap().len());
+        assert_eq!(2, storage.get_signed_channels(None).unwrap().len());
+        let established = storage
+            .get_signed_channels(Some(SignedChannelStateType::Established))
+            .expect("Error retrieving signed channels");
+        assert_eq!(1, established.len());
+
+        let accepted_channel: AcceptedChannel = deserialize_object(test_file!("AcceptedChannel"));
+        let channel_id = accepted_channel.channel_id;
+        storage
+            .upsert_channel(Channel::Accepted(accepted_channel), None)
+            .expect("Error creating channel");
+        storage
+            .get_channel(&channel_id)
+            .expect("Error retrieving channel")
+            .expect("to have found the channel");
+        storage
+            .delete_channel(&channel_id)
+            .expect("Error deleting channel");
+        assert!(storage.get_channel(&channel_id).unwrap().is_none());
+    });
+
+    sqlite_test!(
+        chain_monitor_and_oracle_data_are_persisted,
+        |storage: SqliteStorageProvider| {
+            assert!(storage.get_chain_monitor().unwrap().is_none());
+            let chain_monitor = ChainMonitor::new(123);
+            storage
+                .persist_chain_monitor(&chain_monitor)
+                .expect("Error persisting chain monitor");
+            assert_eq!(Some(chain_monitor), storage.get_chain_monitor().unwrap());
+
+            let contract: OfferedContract = deserialize_object(test_file!("Offered"));
+            let announcement = &contract.contract_info[0].oracle_announcements[0];
+            let public_key = announcement.oracle_public_key;
+            let event_id = &announcement.oracle_event.event_id;
+            assert!(storage
+                .get_oracle_announcement(&public_key, event_id)
+                .unwrap()
+                .is_none());
+            storage
+                .persist_oracle_announcement(announcement)
+                .expect("Error persisting announcement");
+            assert_eq!(
+                Some(announcement),
+                storage
+                    .get_oracle_announcement(&public_key, event_id)
+                    .unwrap()
+                    .as_ref()
+            );
+        }
+    );
+
+    sqlite_test!(
+        write_batch_persists_all_writes,
+        |storage: SqliteStorageProvider| {
+            let offered_contract: OfferedContract = deserialize_object(test_file!("Offered"));
+            storage
+                .create_contract(&offered_contract)
+                .expect("Error creating contract");
+
+            let accepted_contract = Contract::Accepted(deserialize_object(test_file!("Accepted")));
+            let accepted_channel: AcceptedChannel =
+                deserialize_object(test_file!("AcceptedChannel"));
+            let channel_id = accepted_channel.channel_id;
+            let chain_monitor = ChainMonitor::new(123);
+
+            storage
+                .write_batch(&[
+                    StorageWrite::Contract(&accepted_contract),
+                    StorageWrite::Channel(&Channel::Accepted(accepted_channel)),
+                    StorageWrite::ChainMonitor(&chain_monitor),
+                ])
+                .expect("Error writing batch");
+
+            assert!(storage
+                .get_contract(&offered_contract.id)
+                .unwrap()
+                .is_none());
+            assert!(storage
+                .get_contract(&accepted_contract.get_id())
+                .unwrap()
+                .is_some());
+            assert!(storage.get_channel(&channel_id).unwrap().is_some());
+            assert_eq!(Some(chain_monitor), storage.get_chain_monitor().unwrap());
+        }
+    );
+
+    sqlite_test!(
+        outbound_messages_can_be_retrieved,
+        |storage: SqliteStorageProvider| {
+            let contract: OfferedContract = deserialize_object(test_file!("Offered"));
+            let offer: dlc_messages::OfferDlc = (&contract).into();
+            let messages = vec![Message::Offer(offer.clone()), Message::Offer(offer)];
+
+            storage
+                .persist_outbound_messages(&contract.counter_party, &messages)
+                .expect("Error persisting messages");
+            let retrieved = storage
+                .get_outbound_messages()
+                .expect("Error retrieving messages");
+            assert_eq!(1, retrieved.len());
+            assert_eq!(contract.counter_party, retrieved[0].0);
+            assert_eq!(2, retrieved[0].1.len());
+
+            storage
+                .persist_outbound_messages(&contract.counter_party, &[])
+                .expect("Error persisting messages");
+            assert!(storage.get_outbound_messages().unwrap().is_empty());
+        }
+    );
+}
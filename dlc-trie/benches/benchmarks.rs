@@ -0,0 +1,154 @@
+extern crate criterion;
+extern crate dlc;
+extern crate dlc_trie;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dlc::{Payout, RangePayout};
+use dlc_trie::digit_decomposition::{decompose_value, group_by_ignoring_digits};
+use dlc_trie::digit_trie::{DigitTrie, DigitTrieIter};
+use dlc_trie::multi_oracle_trie::MultiOracleTrie;
+use dlc_trie::multi_oracle_trie_with_diff::MultiOracleTrieWithDiff;
+use dlc_trie::{DlcTrie, OracleNumericInfo};
+
+/// The base in which the outcome values are decomposed.
+const BASE: usize = 2;
+/// The number of digits used to represent outcome values.
+const NB_DIGITS: usize = 17;
+/// The number of outcomes covered by each payout range.
+const RANGE_SIZE: usize = 100;
+/// The number of oracles used for the multi oracle tries.
+const NB_ORACLES: usize = 3;
+/// The number of oracles required to be in agreement to close the contract.
+const THRESHOLD: usize = 2;
+/// The minimum difference between oracle supported (as a power of 2).
+const MIN_SUPPORT_EXP: usize = 7;
+/// The maximum difference between oracle supported (as a power of 2).
+const MAX_ERROR_EXP: usize = 8;
+
+fn max_value() -> usize {
+    BASE.pow(NB_DIGITS as u32) - 1
+}
+
+fn get_range_payouts() -> Vec<RangePayout> {
+    let total = 200000000;
+    let nb_ranges = (max_value() + 1) / RANGE_SIZE;
+    (0..=nb_ranges)
+        .map(|i| {
+            let start = i * RANGE_SIZE;
+            let count = if i == nb_ranges {
+                max_value() + 1 - start
+            } else {
+                RANGE_SIZE
+            };
+            let offer = (total * i / nb_ranges) as u64;
+            RangePayout {
+                start,
+                count,
+                payout: Payout {
+                    offer,
+                    accept: total as u64 - offer,
+                },
+            }
+        })
+        .filter(|x| x.count > 0)
+        .collect()
+}
+
+fn get_paths() -> Vec<Vec<usize>> {
+    get_range_payouts()
+        .iter()
+        .flat_map(|x| group_by_ignoring_digits(x.start, x.start + x.count - 1, BASE, NB_DIGITS))
+        .collect()
+}
+
+fn get_oracle_numeric_infos() -> OracleNumericInfo {
+    OracleNumericInfo {
+        base: BASE,
+        nb_digits: vec![NB_DIGITS; NB_ORACLES],
+    }
+}
+
+fn build_digit_trie(paths: &[Vec<usize>]) -> DigitTrie<usize> {
+    let mut trie = DigitTrie::new(BASE);
+    for (i, path) in paths.iter().enumerate() {
+        trie.insert(path, &mut |_| Ok(i)).unwrap();
+    }
+    trie
+}
+
+/// Benchmark to measure the construction time of a digit trie.
+pub fn digit_trie_insert_bench(c: &mut Criterion) {
+    let paths = get_paths();
+    c.bench_function("digit_trie_insert", |b| {
+        b.iter(|| black_box(build_digit_trie(&paths)))
+    });
+}
+
+/// Benchmark to measure the look up time of all the outcomes in a digit trie.
+pub fn digit_trie_look_up_bench(c: &mut Criterion) {
+    let trie = build_digit_trie(&get_paths());
+    let outcomes: Vec<_> = (0..=max_value())
+        .step_by(7)
+        .map(|x| decompose_value(x, BASE, NB_DIGITS))
+        .collect();
+    c.bench_function("digit_trie_look_up", |b| {
+        b.iter(|| {
+            for outcome in &outcomes {
+                black_box(trie.look_up(outcome));
+            }
+        })
+    });
+}
+
+/// Benchmark to measure the iteration time over a digit trie.
+pub fn digit_trie_iter_bench(c: &mut Criterion) {
+    let trie = build_digit_trie(&get_paths());
+    c.bench_function("digit_trie_iter", |b| {
+        b.iter(|| black_box(DigitTrieIter::new(&trie).count()))
+    });
+}
+
+/// Benchmark to measure the generation time of a multi oracle trie.
+pub fn multi_oracle_trie_generate_bench(c: &mut Criterion) {
+    let range_payouts = get_range_payouts();
+    let oracle_numeric_infos = get_oracle_numeric_infos();
+    c.bench_function("multi_oracle_trie_generate", |b| {
+        b.iter(|| {
+            let mut trie = MultiOracleTrie::new(&oracle_numeric_infos, THRESHOLD).unwrap();
+            black_box(trie.generate(0, &range_payouts).unwrap())
+        })
+    });
+}
+
+/// Benchmark to measure the generation time of a multi oracle trie with
+/// difference support.
+pub fn multi_oracle_trie_with_diff_generate_bench(c: &mut Criterion) {
+    let range_payouts = get_range_payouts();
+    let oracle_numeric_infos = get_oracle_numeric_infos();
+    let mut group = c.benchmark_group("multi_oracle_trie_with_diff");
+    group.sample_size(10);
+    group.bench_function("generate", |b| {
+        b.iter(|| {
+            let mut trie = MultiOracleTrieWithDiff::new(
+                &oracle_numeric_infos,
+                THRESHOLD,
+                MIN_SUPPORT_EXP,
+                MAX_ERROR_EXP,
+            )
+            .unwrap();
+            black_box(trie.generate(0, &range_payouts).unwrap())
+        })
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default();
+    targets = digit_trie_insert_bench,
+        digit_trie_look_up_bench,
+        digit_trie_iter_bench,
+        multi_oracle_trie_generate_bench,
+        multi_oracle_trie_with_diff_generate_bench
+}
+criterion_main!(benches);
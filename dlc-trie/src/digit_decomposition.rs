@@ -1,6 +1,7 @@
 //! Utility functions to decompose numeric outcome values
 
 use dlc::{Payout, RangePayout};
+use std::ops::RangeInclusive;
 
 /// Decompose a numeric value into digits in the specified base. If the decomposed
 /// value contains less than `nb_digits`, zeroes will be prepended to reach `nb_digits`
@@ -10,7 +11,7 @@ pub fn decompose_value(mut value: usize, base: usize, nb_digits: usize) -> Vec<u
 
     while value > 0 {
         res.push(value % base);
-        value = ((value as f64) / (base as f64)).floor() as usize;
+        value /= base;
     }
 
     while res.len() < nb_digits {
@@ -34,6 +35,15 @@ pub fn compose_value(values: &[usize], base: usize) -> usize {
     composed
 }
 
+/// Returns the range of values (inclusive) whose decomposition in the given base
+/// using `nb_digits` digits starts with `prefix`.
+pub fn prefix_to_range(prefix: &[usize], base: usize, nb_digits: usize) -> RangeInclusive<usize> {
+    assert!(prefix.len() <= nb_digits);
+    let start = compose_value(prefix, base);
+    let nb_values = base.pow((nb_digits - prefix.len()) as u32);
+    start * nb_values..=(start + 1) * nb_values - 1
+}
+
 /// Takes a vector or `RangePayout` and (if necessary) updates the first element
 /// to cover the range [0, first_end] where first_end is the end value of the
 /// first element, and updates the last element to cover the range
@@ -637,4 +647,12 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn prefix_to_range_test() {
+        assert_eq!(4..=7, super::prefix_to_range(&[0, 1], 2, 4));
+        assert_eq!(0..=15, super::prefix_to_range(&[], 2, 4));
+        assert_eq!(120..=129, super::prefix_to_range(&[1, 2], 10, 3));
+        assert_eq!(123..=123, super::prefix_to_range(&[1, 2, 3], 10, 3));
+    }
 }
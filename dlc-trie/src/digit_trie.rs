@@ -1,19 +1,42 @@
 //! # DigitTrie
 //! Data structure to store and lookup digit decomposition data.
-
-use crate::{LookupResult, Node};
+//!
+//! Nodes are kept in a flat array and refer to each other through indexes.
+//! The prefixes of all the nodes are stored in a single buffer, and the
+//! children of a node are stored contiguously in a shared array as a block of
+//! `base` entries, so that building and traversing a trie does not require an
+//! allocation per node.
+
+use crate::node_store::{NodeStore, StoredDigitTrie};
+use crate::LookupResult;
 use dlc::Error;
 
 /// Structure to store data inserted and looked-up based on digit paths.
 #[derive(Clone)]
 pub struct DigitTrie<T> {
-    /// Use the arena allocated approach which makes it easier to
-    /// satisfy the borrow checker.  
-    store: Vec<Node<DigitLeaf<T>, DigitNode<T>>>,
+    nodes: Vec<DigitNode<T>>,
+    /// Buffer containing the prefixes of all the nodes.
+    prefixes: Vec<usize>,
+    /// Buffer containing the children of all the nodes, in blocks of `base`
+    /// entries.
+    children: Vec<Option<usize>>,
     root: Option<usize>,
     pub(crate) base: usize,
 }
 
+#[derive(Clone)]
+struct DigitNode<T> {
+    /// Start of the node prefix in the prefix buffer.
+    prefix_start: usize,
+    /// End (exclusive) of the node prefix in the prefix buffer.
+    prefix_end: usize,
+    /// Start of the block of children of the node in the children buffer, if
+    /// the node is not a leaf.
+    children: Option<usize>,
+    /// The data of the node, always set for leaves.
+    data: Option<T>,
+}
+
 /// Container for a dump of a DigitTrie used for serialization purpose.
 pub struct DigitTrieDump<T>
 where
@@ -33,7 +56,7 @@ where
 {
     /// Dump the content of the trie for the purpose of serialization.
     pub fn dump(&self) -> DigitTrieDump<T> {
-        let node_data = self.store.iter().map(|x| x.get_data()).collect();
+        let node_data = (0..self.nodes.len()).map(|i| self.get_data(i)).collect();
         DigitTrieDump {
             root: self.root,
             base: self.base,
@@ -48,12 +71,80 @@ where
             base,
             node_data,
         } = dump;
-        let store = node_data.into_iter().map(|x| Node::from_data(x)).collect();
-        DigitTrie { store, root, base }
+        let mut trie = DigitTrie::new(base);
+        trie.nodes.reserve(node_data.len());
+        for data in node_data {
+            let prefix_start = trie.prefixes.len();
+            trie.prefixes.extend_from_slice(&data.prefix);
+            let children = data.children.map(|c| {
+                let start = trie.children.len();
+                trie.children.extend(c);
+                trie.children.resize(start + base, None);
+                start
+            });
+            trie.nodes.push(DigitNode {
+                prefix_start,
+                prefix_end: trie.prefixes.len(),
+                children,
+                data: data.data,
+            });
+        }
+        trie.root = root;
+        trie
+    }
+
+    /// Write the nodes of the trie to `store` and return a trie backed by it.
+    pub fn write_to_store<S>(&self, mut store: S) -> Result<StoredDigitTrie<T, S>, S::Error>
+    where
+        S: NodeStore<T>,
+    {
+        let root = self.write_nodes(&mut store, 0, |x| x)?;
+        Ok(StoredDigitTrie::new(store, root))
+    }
+
+    /// Write the nodes of the trie to `store`, shifting their indexes by
+    /// `offset` and converting their values using `map`. Returns the index of
+    /// the root in the store.
+    pub(crate) fn write_nodes<V, S, F>(
+        &self,
+        store: &mut S,
+        offset: usize,
+        map: F,
+    ) -> Result<Option<usize>, S::Error>
+    where
+        S: NodeStore<V>,
+        F: Fn(T) -> V,
+    {
+        for i in 0..self.nodes.len() {
+            let data = self.get_data(i);
+            store.put_node(
+                offset + i,
+                DigitNodeData {
+                    data: data.data.map(&map),
+                    prefix: data.prefix,
+                    children: data
+                        .children
+                        .map(|c| c.into_iter().map(|x| x.map(|y| y + offset)).collect()),
+                },
+            )?;
+        }
+        Ok(self.root.map(|x| x + offset))
+    }
+
+    fn get_data(&self, index: usize) -> DigitNodeData<T> {
+        let node = &self.nodes[index];
+        DigitNodeData {
+            data: node.data.clone(),
+            prefix: self.node_prefix(node).to_vec(),
+            children: node
+                .children
+                .map(|c| self.children[c..c + self.base].to_vec()),
+        }
     }
 }
 
 /// External representation of a node used for serialization purpose.
+#[derive(Clone, Debug)]
 pub struct DigitNodeData<T> {
     /// The data contained in the node.
     pub data: Option<T>,
@@ -63,127 +154,52 @@ pub struct DigitNodeData<T> {
     pub children: Option<Vec<Option<usize>>>,
 }
 
-impl<T> Node<DigitLeaf<T>, DigitNode<T>>
-where
-    T: Clone,
-{
-    fn get_data(&self) -> DigitNodeData<T> {
-        match self {
-            Node::Leaf(l) => DigitNodeData {
-                data: Some(l.data.clone()),
-                prefix: l.prefix.clone(),
-                children: None,
-            },
-            Node::Node(n) => DigitNodeData {
-                data: n.data.clone(),
-                prefix: n.prefix.clone(),
-                children: Some(n.children.clone()),
-            },
-            Node::None => unreachable!(),
-        }
-    }
-
-    fn from_data(data: DigitNodeData<T>) -> Node<DigitLeaf<T>, DigitNode<T>> {
-        match data.children {
-            Some(c) => Node::Node(DigitNode {
-                children: c,
-                prefix: data.prefix,
-                data: data.data,
-            }),
-            None => Node::Leaf(DigitLeaf {
-                prefix: data.prefix,
-                data: data.data.unwrap(),
-            }),
-        }
-    }
-}
-
 /// Structure used to iterated through a `DigitTrie` values. The iterator performs
 /// a pre-order traversal of the trie.
 pub struct DigitTrieIter<'a, T> {
     trie: &'a DigitTrie<T>,
-    /// Stack storing the node index of parents of the node currently being
-    /// visited (first item in the tuple), as well as the index of the child that
-    /// was last visited. An `isize` is used as the value -1 is used to indicate
-    /// that the node value has not yet been yield.
-    index_stack: Vec<(Option<usize>, isize)>,
-    cur_prefix: Vec<Vec<usize>>,
+    /// Stack storing the index of the nodes on the path to the node currently
+    /// being visited, together with the index of the next child to visit. The
+    /// child index is `None` when the node value has not yet been yield.
+    index_stack: Vec<(usize, Option<usize>)>,
+    /// The concatenation of the prefixes of the nodes in `index_stack`.
+    cur_path: Vec<usize>,
 }
 
 impl<'a, T> DigitTrieIter<'a, T> {
     /// Create a new `DigitTrieIter` struct.
     pub fn new(trie: &'a DigitTrie<T>) -> DigitTrieIter<'a, T> {
-        DigitTrieIter {
-            index_stack: vec![(trie.root, -1)],
+        let mut iter = DigitTrieIter {
             trie,
-            cur_prefix: Vec::new(),
+            index_stack: Vec::new(),
+            cur_path: Vec::new(),
+        };
+        if let Some(root) = trie.root {
+            iter.push_node(root);
         }
+        iter
     }
 
-    fn cur_prefix_append(&mut self, to_append: &[usize]) {
-        self.cur_prefix.push(to_append.to_vec());
-    }
-    fn cur_prefix_drop(&mut self) {
-        self.cur_prefix.pop();
+    fn push_node(&mut self, index: usize) {
+        self.cur_path
+            .extend_from_slice(self.trie.node_prefix(&self.trie.nodes[index]));
+        self.index_stack.push((index, None));
     }
-}
 
-#[derive(Clone)]
-struct DigitLeaf<T> {
-    data: T,
-    prefix: Vec<usize>,
-}
-
-#[derive(Clone)]
-struct DigitNode<T> {
-    children: Vec<Option<usize>>,
-    prefix: Vec<usize>,
-    data: Option<T>,
-}
-
-trait NodePrefix {
-    fn get_node_prefix(&self) -> Vec<usize>;
-    fn set_node_prefix(&mut self, prefix: Vec<usize>);
-}
-
-impl<T> NodePrefix for Node<DigitLeaf<T>, DigitNode<T>> {
-    fn get_node_prefix(&self) -> Vec<usize> {
-        match self {
-            Node::None => unreachable!(),
-            Node::Leaf(digit_leaf) => digit_leaf.prefix.clone(),
-            Node::Node(digit_node) => digit_node.prefix.clone(),
+    fn pop_node(&mut self) {
+        if let Some((index, _)) = self.index_stack.pop() {
+            let node = &self.trie.nodes[index];
+            let new_len = self.cur_path.len() - (node.prefix_end - node.prefix_start);
+            self.cur_path.truncate(new_len);
         }
     }
-
-    fn set_node_prefix(&mut self, prefix: Vec<usize>) {
-        let pref = match self {
-            Node::None => unreachable!(),
-            Node::Leaf(digit_leaf) => &mut digit_leaf.prefix,
-            Node::Node(digit_node) => &mut digit_node.prefix,
-        };
-
-        *pref = prefix;
-    }
 }
 
-fn get_common_prefix(a: &[usize], b: &[usize]) -> Vec<usize> {
-    a.iter()
-        .zip(b.iter())
-        .take_while(|(x, y)| x == y)
-        .map(|(x, _)| x)
-        .cloned()
-        .collect()
+fn get_common_prefix_len(a: &[usize], b: &[usize]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count()
 }
 
-fn insert_new_leaf<T>(trie: &mut DigitTrie<T>, path: &[usize], data: T) -> usize {
-    trie.store.push(Node::Leaf(DigitLeaf {
-        prefix: path.to_vec(),
-        data,
-    }));
-    trie.store.len() - 1
-}
-
-fn is_prefix_of(prefix: &[usize], value: &[usize]) -> bool {
+pub(crate) fn is_prefix_of(prefix: &[usize], value: &[usize]) -> bool {
     if prefix.len() > value.len() {
         return false;
     }
@@ -200,80 +216,27 @@ fn is_prefix_of(prefix: &[usize], value: &[usize]) -> bool {
 impl<'a, T> Iterator for DigitTrieIter<'a, T> {
     type Item = LookupResult<'a, T, usize>;
     fn next(&mut self) -> Option<Self::Item> {
-        let popped = self.index_stack.pop();
-        let (cur_index, mut cur_child) = match popped {
-            None => return None,
-            Some((cur_index, cur_child)) => match cur_index {
-                None => {
-                    return self.next();
-                }
-                Some(cur_index) => (cur_index, cur_child),
-            },
-        };
-
-        match &self.trie.store[cur_index] {
-            Node::None => unreachable!(),
-            Node::Leaf(digit_leaf) => Some(LookupResult {
-                value: &digit_leaf.data,
-                path: self
-                    .cur_prefix
-                    .iter()
-                    .filter(|x| !x.is_empty())
-                    .flatten()
-                    .chain(digit_leaf.prefix.iter())
-                    .cloned()
-                    .collect(),
-            }),
-            Node::Node(digit_node) => {
-                let node_prefix = digit_node.prefix.clone();
-
-                if cur_child >= (self.trie.base as isize) {
-                    self.cur_prefix_drop();
-                    self.next()
-                } else {
-                    let cur_children = digit_node.children.clone();
-                    if cur_child == -1 {
-                        match &digit_node.data {
-                            Some(data) => {
-                                self.index_stack.push((Some(cur_index), cur_child + 1));
-                                return Some(LookupResult {
-                                    value: data,
-                                    path: self
-                                        .cur_prefix
-                                        .iter()
-                                        .filter(|x| !x.is_empty())
-                                        .flatten()
-                                        .chain(digit_node.prefix.iter())
-                                        .cloned()
-                                        .collect(),
-                                });
-                            }
-                            _ => {
-                                cur_child += 1;
-                            }
-                        }
-                    }
-                    if cur_child == 0 {
-                        self.cur_prefix_append(&node_prefix);
+        let trie = self.trie;
+        loop {
+            let (cur_index, cur_child) = *self.index_stack.last()?;
+            let node = &trie.nodes[cur_index];
+            match (cur_child, node.children) {
+                (None, _) => {
+                    self.index_stack.last_mut().unwrap().1 = Some(0);
+                    if let Some(data) = &node.data {
+                        return Some(LookupResult {
+                            value: data,
+                            path: self.cur_path.clone(),
+                        });
                     }
-                    while cur_child < (self.trie.base as isize) {
-                        self.index_stack.push((Some(cur_index), cur_child + 1));
-                        self.index_stack
-                            .push((cur_children[(cur_child as usize)], -1));
-                        match self.next() {
-                            None => {
-                                self.index_stack.pop();
-                                cur_child += 1;
-                            }
-                            Some(res) => {
-                                return Some(res);
-                            }
-                        };
+                }
+                (Some(child), Some(children)) if child < trie.base => {
+                    self.index_stack.last_mut().unwrap().1 = Some(child + 1);
+                    if let Some(child_index) = trie.children[children + child] {
+                        self.push_node(child_index);
                     }
-                    self.cur_prefix_drop();
-                    self.index_stack.pop();
-                    None
                 }
+                _ => self.pop_node(),
             }
         }
     }
@@ -283,18 +246,64 @@ impl<T> DigitTrie<T> {
     /// Create a new `DigitTrie`.
     pub fn new(base: usize) -> DigitTrie<T> {
         DigitTrie {
-            store: Vec::new(),
+            nodes: Vec::new(),
+            prefixes: Vec::new(),
+            children: Vec::new(),
             root: None,
             base,
         }
     }
 
+    /// Returns the number of nodes in the trie.
+    pub fn nb_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns an estimate of the heap memory used by the trie in bytes,
+    /// `value_heap_size` giving the heap memory used by each stored value.
+    pub fn heap_size<F>(&self, value_heap_size: F) -> usize
+    where
+        F: Fn(&T) -> usize,
+    {
+        self.nodes.capacity() * std::mem::size_of::<DigitNode<T>>()
+            + self.prefixes.capacity() * std::mem::size_of::<usize>()
+            + self.children.capacity() * std::mem::size_of::<Option<usize>>()
+            + self
+                .nodes
+                .iter()
+                .filter_map(|x| x.data.as_ref())
+                .map(value_heap_size)
+                .sum::<usize>()
+    }
+
+    fn node_prefix(&self, node: &DigitNode<T>) -> &[usize] {
+        &self.prefixes[node.prefix_start..node.prefix_end]
+    }
+
+    fn push_node(&mut self, prefix: &[usize], children: Option<usize>, data: Option<T>) -> usize {
+        let prefix_start = self.prefixes.len();
+        self.prefixes.extend_from_slice(prefix);
+        self.nodes.push(DigitNode {
+            prefix_start,
+            prefix_end: self.prefixes.len(),
+            children,
+            data,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn push_children(&mut self) -> usize {
+        let start = self.children.len();
+        self.children.resize(start + self.base, None);
+        start
+    }
+
     /// Insert or update data at `path`.
     pub fn insert<F>(&mut self, path: &[usize], get_data: &mut F) -> Result<(), Error>
     where
         F: FnMut(Option<T>) -> Result<T, Error>,
     {
-        if path.is_empty() || path.iter().any(|x| x > &self.base) {
+        if path.is_empty() || path.iter().any(|x| x >= &self.base) {
             panic!("Invalid path");
         }
 
@@ -311,171 +320,101 @@ impl<T> DigitTrie<T> {
     where
         F: FnMut(Option<T>) -> Result<T, Error>,
     {
-        match cur_index {
-            None => Ok(insert_new_leaf(self, path, get_data(None)?)),
-            Some(cur_index) => {
-                self.store.push(Node::None);
-                let mut cur_node = self.store.swap_remove(cur_index);
-                let prefix = cur_node.get_node_prefix();
-                if prefix == path {
-                    match cur_node {
-                        Node::Leaf(digit_leaf) => {
-                            self.store[cur_index] = Node::Leaf(DigitLeaf {
-                                data: get_data(Some(digit_leaf.data))?,
-                                prefix: digit_leaf.prefix.to_vec(),
-                            });
-                            Ok(cur_index)
-                        }
-                        Node::Node(mut node) => {
-                            node.data = Some(get_data(node.data)?);
-                            self.store[cur_index] = Node::Node(node);
-                            Ok(cur_index)
-                        }
-                        Node::None => unreachable!(),
-                    }
-                } else {
-                    let common_prefix = get_common_prefix(&prefix, path);
-                    let suffix: Vec<_> = path.iter().skip(common_prefix.len()).cloned().collect();
-                    if prefix == common_prefix {
-                        match cur_node {
-                            Node::Node(mut digit_node) => {
-                                digit_node.children[suffix[0]] = Some(self.insert_internal(
-                                    digit_node.children[suffix[0]],
-                                    &suffix,
-                                    get_data,
-                                )?);
-                                self.store[cur_index] = Node::Node(DigitNode {
-                                    children: digit_node.children,
-                                    prefix: digit_node.prefix,
-                                    data: digit_node.data,
-                                });
-                                return Ok(cur_index);
-                            }
-                            Node::None => unreachable!(),
-                            Node::Leaf(digit_leaf) => {
-                                let mut new_children = Vec::new();
-                                new_children.resize_with(self.base, || None);
-                                new_children[suffix[0]] =
-                                    Some(insert_new_leaf(self, &suffix, get_data(None)?));
-                                self.store[cur_index] = Node::Node(DigitNode {
-                                    prefix: digit_leaf.prefix,
-                                    children: new_children,
-                                    data: Some(digit_leaf.data),
-                                });
-                                return Ok(cur_index);
-                            }
-                        }
-                    }
+        let cur_index = match cur_index {
+            None => {
+                let data = get_data(None)?;
+                return Ok(self.push_node(path, None, Some(data)));
+            }
+            Some(cur_index) => cur_index,
+        };
 
-                    let mut new_children = Vec::new();
-                    new_children.resize_with(self.base, || None);
-
-                    let data = if path == common_prefix {
-                        Some(get_data(None)?)
-                    } else {
-                        new_children[path[common_prefix.len()]] =
-                            Some(insert_new_leaf(self, &suffix, get_data(None)?));
-                        None
-                    };
-
-                    new_children[prefix[common_prefix.len()]] = Some(cur_index);
-                    cur_node.set_node_prefix(
-                        prefix.iter().skip(common_prefix.len()).cloned().collect(),
-                    );
-                    self.store.push(Node::Node(DigitNode {
-                        children: new_children,
-                        prefix: common_prefix,
-                        data,
-                    }));
-                    self.store[cur_index] = cur_node;
-                    Ok(self.store.len() - 1)
+        let DigitNode {
+            prefix_start,
+            prefix_end,
+            children,
+            ..
+        } = self.nodes[cur_index];
+        let prefix_len = prefix_end - prefix_start;
+        let common_len = get_common_prefix_len(&self.prefixes[prefix_start..prefix_end], path);
+
+        if common_len == prefix_len && common_len == path.len() {
+            let data = self.nodes[cur_index].data.take();
+            self.nodes[cur_index].data = Some(get_data(data)?);
+            return Ok(cur_index);
+        }
+
+        let suffix = &path[common_len..];
+
+        if common_len == prefix_len {
+            match children {
+                Some(children) => {
+                    let child_index = children + suffix[0];
+                    let child =
+                        self.insert_internal(self.children[child_index], suffix, get_data)?;
+                    self.children[child_index] = Some(child);
+                }
+                None => {
+                    let data = get_data(None)?;
+                    let leaf = self.push_node(suffix, None, Some(data));
+                    let children = self.push_children();
+                    self.children[children + suffix[0]] = Some(leaf);
+                    self.nodes[cur_index].children = Some(children);
                 }
             }
+            return Ok(cur_index);
         }
+
+        let new_children = self.push_children();
+        let data = if suffix.is_empty() {
+            Some(get_data(None)?)
+        } else {
+            let data = get_data(None)?;
+            let leaf = self.push_node(suffix, None, Some(data));
+            self.children[new_children + suffix[0]] = Some(leaf);
+            None
+        };
+
+        let split_digit = self.prefixes[prefix_start + common_len];
+        self.children[new_children + split_digit] = Some(cur_index);
+        self.nodes[cur_index].prefix_start += common_len;
+        Ok(self.push_node(&path[..common_len], Some(new_children), data))
     }
 
     /// Lookup for nodes whose path is either equal or a prefix of `path`.
     pub fn look_up(&self, path: &[usize]) -> Option<Vec<LookupResult<T, usize>>> {
-        self.look_up_internal(self.root, path)
-    }
-
-    fn look_up_internal(
-        &self,
-        cur_index: Option<usize>,
-        path: &[usize],
-    ) -> Option<Vec<LookupResult<T, usize>>> {
-        match cur_index {
-            None => None,
-            Some(cur_index) => match &self.store[cur_index] {
-                Node::None => unreachable!(),
-                Node::Leaf(digit_leaf) => {
-                    let common_prefix = get_common_prefix(&digit_leaf.prefix, path);
-                    if digit_leaf.prefix == common_prefix {
-                        Some(vec![LookupResult {
-                            path: digit_leaf.prefix.to_vec(),
-                            value: &digit_leaf.data,
-                        }])
-                    } else {
-                        None
-                    }
-                }
-                Node::Node(digit_node) => {
-                    if digit_node.prefix.len() > path.len()
-                        || !is_prefix_of(&digit_node.prefix, path)
-                    {
-                        return None;
-                    }
+        let mut results = Vec::new();
+        let mut cur_index = self.root;
+        let mut depth = 0;
+
+        while let Some(index) = cur_index {
+            let node = &self.nodes[index];
+            if !is_prefix_of(self.node_prefix(node), &path[depth..]) {
+                break;
+            }
+            depth += node.prefix_end - node.prefix_start;
 
-                    if digit_node.prefix.len() == path.len() {
-                        return digit_node.data.as_ref().map(|data| {
-                            vec![LookupResult {
-                                value: data,
-                                path: digit_node.prefix.clone(),
-                            }]
-                        });
-                    }
+            if let Some(data) = &node.data {
+                results.push(LookupResult {
+                    value: data,
+                    path: path[..depth].to_vec(),
+                });
+            }
 
-                    let prefix = path[digit_node.prefix.len()];
-                    let suffix: Vec<_> =
-                        path.iter().skip(digit_node.prefix.len()).cloned().collect();
-                    let res = self.look_up_internal(digit_node.children[prefix], &suffix);
-                    match res {
-                        None => digit_node.data.as_ref().map(|data| {
-                            vec![LookupResult {
-                                value: data,
-                                path: digit_node.prefix.clone(),
-                            }]
-                        }),
-                        Some(l_res) => match &digit_node.data {
-                            None => Some(extend_lookup_res_paths(l_res, &digit_node.prefix)),
-                            Some(data) => {
-                                let mut up_res = extend_lookup_res_paths(l_res, &digit_node.prefix);
-                                let mut final_res = vec![LookupResult {
-                                    value: data,
-                                    path: digit_node.prefix.clone(),
-                                }];
-                                final_res.append(&mut up_res);
-                                Some(final_res)
-                            }
-                        },
-                    }
+            cur_index = match node.children {
+                // Digits outside of the base (from an invalid attestation) have no child.
+                Some(children) if depth < path.len() && path[depth] < self.base => {
+                    self.children[children + path[depth]]
                 }
-            },
+                _ => None,
+            };
         }
-    }
-}
 
-fn extend_lookup_res_paths<'a, T>(
-    l_res: Vec<LookupResult<'a, T, usize>>,
-    path: &[usize],
-) -> Vec<LookupResult<'a, T, usize>> {
-    l_res
-        .into_iter()
-        .map(|x| LookupResult {
-            value: x.value,
-            path: path.iter().chain(x.path.iter()).cloned().collect(),
-        })
-        .collect()
+        if results.is_empty() {
+            None
+        } else {
+            Some(results)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -533,6 +472,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn digit_trie_from_dump_returns_same_elements() {
+        for test_case in digit_trie_test_cases() {
+            let mut digit_trie = DigitTrie::<usize>::new(16);
+            for (i, path) in test_case.iter().enumerate() {
+                digit_trie.insert(path, &mut |_| Ok(i)).unwrap();
+            }
+
+            let restored = DigitTrie::from_dump(digit_trie.dump());
+
+            let expected: Vec<_> = DigitTrieIter::new(&digit_trie)
+                .map(|x| (x.path, *x.value))
+                .collect();
+            let actual: Vec<_> = DigitTrieIter::new(&restored)
+                .map(|x| (x.path, *x.value))
+                .collect();
+            assert_eq!(expected, actual);
+            for path in &test_case {
+                assert_eq!(
+                    digit_trie.look_up(path).unwrap()[0].value,
+                    restored.look_up(path).unwrap()[0].value
+                );
+            }
+        }
+    }
+
     #[test]
     fn digit_trie_return_value_with_longer_path_query() {
         let mut digit_trie = DigitTrie::new(5);
@@ -20,6 +20,9 @@ extern crate secp256k1_zkp;
 #[cfg(feature = "use-serde")]
 extern crate serde;
 
+#[cfg(all(feature = "parallel", target_arch = "wasm32"))]
+compile_error!("The `parallel` feature requires threads and is not available on wasm32.");
+
 use bitcoin::{Script, Transaction};
 use dlc::{Error, RangePayout};
 #[cfg(feature = "parallel")]
@@ -35,6 +38,7 @@ pub mod multi_oracle;
 pub mod multi_oracle_trie;
 pub mod multi_oracle_trie_with_diff;
 pub mod multi_trie;
+pub mod node_store;
 #[cfg(test)]
 mod test_utils;
 mod utils;
@@ -101,6 +105,81 @@ impl OracleNumericInfo {
             .skip(1)
             .any(|x| *x != self.nb_digits[0])
     }
+
+    /// Returns the numeric information obtained when ignoring the given number
+    /// of least significant digits of each oracle, so that oracles attesting
+    /// with different precisions can be combined. An empty slice leaves the
+    /// information unchanged.
+    pub fn truncate(&self, truncated_digits: &[usize]) -> Result<OracleNumericInfo, Error> {
+        if truncated_digits.is_empty() {
+            return Ok(self.clone());
+        }
+        if truncated_digits.len() != self.nb_digits.len()
+            || truncated_digits
+                .iter()
+                .zip(self.nb_digits.iter())
+                .any(|(t, n)| t >= n)
+        {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(OracleNumericInfo {
+            base: self.base,
+            nb_digits: self
+                .nb_digits
+                .iter()
+                .zip(truncated_digits.iter())
+                .map(|(n, t)| n - t)
+                .collect(),
+        })
+    }
+}
+
+/// Drops the least significant digits of the given attested outcomes according
+/// to the number of digits truncated for each oracle (see
+/// [`OracleNumericInfo::truncate`]). Outcomes are given as oracle index and
+/// digits pairs.
+pub fn truncate_outcomes(
+    outcomes: &[(usize, Vec<usize>)],
+    truncated_digits: &[usize],
+) -> Vec<(usize, Vec<usize>)> {
+    outcomes
+        .iter()
+        .map(|(i, digits)| {
+            let to_drop = truncated_digits.get(*i).cloned().unwrap_or(0);
+            let len = digits.len().saturating_sub(to_drop);
+            (*i, digits[..len].to_vec())
+        })
+        .collect()
+}
+
+/// Progress of a trie generation performed in several steps. It can be
+/// persisted together with a dump of the trie and the adaptor signatures
+/// created so far in order to resume the generation later on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "use-serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct GenerationProgress {
+    /// The index of the next outcome to insert in the trie.
+    pub next_outcome_index: usize,
+    /// The adaptor index to assign to the next inserted value.
+    pub next_adaptor_index: usize,
+    /// Whether all the outcomes were inserted in the trie.
+    pub is_complete: bool,
+}
+
+impl GenerationProgress {
+    /// Create a new progress for a generation whose first adaptor index is
+    /// `adaptor_index_start`.
+    pub fn new(adaptor_index_start: usize) -> Self {
+        GenerationProgress {
+            next_outcome_index: 0,
+            next_adaptor_index: adaptor_index_start,
+            is_complete: false,
+        }
+    }
 }
 
 /// A common trait for trie data structures that store DLC adaptor signature
@@ -113,6 +192,22 @@ pub trait DlcTrie<'a, TrieIterator: Iterator<Item = TrieIterInfo>> {
         &'a mut self,
         adaptor_index_start: usize,
         outcomes: &[RangePayout],
+    ) -> Result<Vec<TrieIterInfo>, Error> {
+        let mut progress = GenerationProgress::new(adaptor_index_start);
+        self.generate_step(&mut progress, outcomes, outcomes.len())
+    }
+
+    /// Insert at most `max_outcomes` outcomes in the trie, starting from the
+    /// one at `progress.next_outcome_index`, and update `progress`
+    /// accordingly. Calling this function until the progress is complete
+    /// produces the same trie as a single call to `generate`. If an error is
+    /// returned, the trie is left in an unspecified state and should not be
+    /// used anymore.
+    fn generate_step(
+        &'a mut self,
+        progress: &mut GenerationProgress,
+        outcomes: &[RangePayout],
+        max_outcomes: usize,
     ) -> Result<Vec<TrieIterInfo>, Error>;
 
     /// Returns an iterator to this trie.
@@ -168,6 +263,34 @@ pub trait DlcTrie<'a, TrieIterator: Iterator<Item = TrieIterInfo>> {
         )
     }
 
+    /// Perform a generation step as with `generate_step` while creating the
+    /// adaptor signatures for the inserted values. Concatenating the
+    /// signatures returned by successive steps gives the same result as
+    /// `generate_sign`.
+    fn generate_sign_step(
+        &'a mut self,
+        secp: &Secp256k1<All>,
+        fund_privkey: &SecretKey,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+        outcomes: &[RangePayout],
+        cets: &[Transaction],
+        precomputed_points: &[Vec<Vec<PublicKey>>],
+        progress: &mut GenerationProgress,
+        max_outcomes: usize,
+    ) -> Result<Vec<EcdsaAdaptorSignature>, Error> {
+        let trie_info = self.generate_step(progress, outcomes, max_outcomes)?;
+        sign_helper(
+            secp,
+            cets,
+            fund_privkey,
+            funding_script_pubkey,
+            fund_output_value,
+            precomputed_points,
+            trie_info.into_iter(),
+        )
+    }
+
     /// Verify that the provided signatures are valid with respect to the
     /// information stored in the trie.
     fn verify(
@@ -215,6 +338,18 @@ pub trait DlcTrie<'a, TrieIterator: Iterator<Item = TrieIterInfo>> {
     }
 }
 
+/// Information about the size of a trie, which can be used to enforce limits
+/// before creating adaptor signatures.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrieStats {
+    /// The total number of nodes in the trie.
+    pub nb_nodes: usize,
+    /// An estimate of the heap memory used by the trie in bytes.
+    pub heap_size: usize,
+    /// The number of adaptor signatures corresponding to the trie values.
+    pub nb_adaptor_signatures: usize,
+}
+
 #[derive(Debug)]
 /// Holds information provided when iterating a DlcTrie.
 pub struct TrieIterInfo {
@@ -233,6 +368,7 @@ fn sign_helper<T: Iterator<Item = TrieIterInfo>>(
     precomputed_points: &[Vec<Vec<PublicKey>>],
     trie_info: T,
 ) -> Result<Vec<EcdsaAdaptorSignature>, Error> {
+    let sig_hashes = dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
     let mut unsorted = trie_info
         .map(|x| {
             let adaptor_point = utils::get_adaptor_point_for_indexed_paths(
@@ -240,14 +376,12 @@ fn sign_helper<T: Iterator<Item = TrieIterInfo>>(
                 &x.paths,
                 precomputed_points,
             )?;
-            let adaptor_sig = dlc::create_cet_adaptor_sig_from_point(
+            let adaptor_sig = dlc::create_cet_adaptor_sig_from_sig_hash(
                 secp,
-                &cets[x.value.cet_index],
+                &sig_hashes[x.value.cet_index],
                 &adaptor_point,
                 fund_privkey,
-                funding_script_pubkey,
-                fund_output_value,
-            )?;
+            );
             Ok((x.value.adaptor_index, adaptor_sig))
         })
         .collect::<Result<Vec<(usize, EcdsaAdaptorSignature)>, Error>>()?;
@@ -265,6 +399,7 @@ fn sign_helper<T: Iterator<Item = TrieIterInfo>>(
     precomputed_points: &[Vec<Vec<PublicKey>>],
     trie_info: T,
 ) -> Result<Vec<EcdsaAdaptorSignature>, Error> {
+    let sig_hashes = dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
     let trie_info: Vec<TrieIterInfo> = trie_info.collect();
     let mut unsorted = trie_info
         .par_iter()
@@ -274,14 +409,12 @@ fn sign_helper<T: Iterator<Item = TrieIterInfo>>(
                 &x.paths,
                 precomputed_points,
             )?;
-            let adaptor_sig = dlc::create_cet_adaptor_sig_from_point(
+            let adaptor_sig = dlc::create_cet_adaptor_sig_from_sig_hash(
                 secp,
-                &cets[x.value.cet_index],
+                &sig_hashes[x.value.cet_index],
                 &adaptor_point,
                 fund_privkey,
-                funding_script_pubkey,
-                fund_output_value,
-            )?;
+            );
             Ok((x.value.adaptor_index, adaptor_sig))
         })
         .collect::<Result<Vec<(usize, EcdsaAdaptorSignature)>, Error>>()?;
@@ -300,23 +433,22 @@ fn verify_helper<T: Iterator<Item = TrieIterInfo>>(
     precomputed_points: &[Vec<Vec<PublicKey>>],
     trie_info: T,
 ) -> Result<usize, Error> {
+    let sig_hashes = dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
     let mut max_adaptor_index = 0;
     for x in trie_info {
         let adaptor_point =
             utils::get_adaptor_point_for_indexed_paths(&x.indexes, &x.paths, precomputed_points)?;
         let adaptor_sig = adaptor_sigs[x.value.adaptor_index];
-        let cet = &cets[x.value.cet_index];
+        let sig_hash = &sig_hashes[x.value.cet_index];
         if x.value.adaptor_index > max_adaptor_index {
             max_adaptor_index = x.value.adaptor_index;
         }
-        dlc::verify_cet_adaptor_sig_from_point(
+        dlc::verify_cet_adaptor_sig_from_sig_hash(
             secp,
             &adaptor_sig,
-            cet,
+            sig_hash,
             &adaptor_point,
             fund_pubkey,
-            funding_script_pubkey,
-            fund_output_value,
         )?;
     }
     Ok(max_adaptor_index + 1)
@@ -333,6 +465,7 @@ fn verify_helper<T: Iterator<Item = TrieIterInfo>>(
     precomputed_points: &[Vec<Vec<PublicKey>>],
     trie_info: T,
 ) -> Result<usize, Error> {
+    let sig_hashes = dlc::get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
     let trie_info: Vec<TrieIterInfo> = trie_info.collect();
     let max_adaptor_index = trie_info
         .iter()
@@ -342,17 +475,48 @@ fn verify_helper<T: Iterator<Item = TrieIterInfo>>(
         let adaptor_point =
             utils::get_adaptor_point_for_indexed_paths(&x.indexes, &x.paths, precomputed_points)?;
         let adaptor_sig = adaptor_sigs[x.value.adaptor_index];
-        let cet = &cets[x.value.cet_index];
-        dlc::verify_cet_adaptor_sig_from_point(
+        let sig_hash = &sig_hashes[x.value.cet_index];
+        dlc::verify_cet_adaptor_sig_from_sig_hash(
             secp,
             &adaptor_sig,
-            cet,
+            sig_hash,
             &adaptor_point,
             fund_pubkey,
-            funding_script_pubkey,
-            fund_output_value,
         )
     })?;
 
     Ok(max_adaptor_index.value.adaptor_index + 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_oracle_numeric_info_test() {
+        let info = OracleNumericInfo {
+            base: 10,
+            nb_digits: vec![6, 4, 5],
+        };
+
+        let truncated = info.truncate(&[2, 0, 1]).unwrap();
+
+        assert_eq!(vec![4, 4, 4], truncated.nb_digits);
+        assert!(!truncated.has_diff_nb_digits());
+        assert_eq!(info.nb_digits, info.truncate(&[]).unwrap().nb_digits);
+        info.truncate(&[0, 4, 0])
+            .expect_err("cannot truncate all digits");
+        info.truncate(&[1, 0])
+            .expect_err("one value per oracle is required");
+    }
+
+    #[test]
+    fn truncate_outcomes_test() {
+        let outcomes = vec![(0, vec![1, 2, 3, 4, 5, 6]), (2, vec![1, 2, 3, 4, 5])];
+
+        assert_eq!(
+            vec![(0, vec![1, 2, 3, 4]), (2, vec![1, 2, 3, 4])],
+            truncate_outcomes(&outcomes, &[2, 0, 1])
+        );
+    }
+}
@@ -4,12 +4,16 @@
 //! need to sign the same outcome for the contract to be able to close.
 
 use crate::combination_iterator::CombinationIterator;
-use crate::digit_decomposition::group_by_ignoring_digits;
+use crate::digit_decomposition::prefix_to_range;
 use crate::digit_trie::{DigitTrie, DigitTrieDump, DigitTrieIter};
 use crate::multi_trie::{MultiTrie, MultiTrieDump, MultiTrieIterator};
-use crate::utils::{get_value_callback, pre_pad_vec};
-use crate::{DlcTrie, IndexedPath, LookupResult, OracleNumericInfo, RangeInfo, TrieIterInfo};
+use crate::utils::{get_outcome_groups, get_value_callback, pre_pad_vec};
+use crate::{
+    DlcTrie, GenerationProgress, IndexedPath, LookupResult, OracleNumericInfo, RangeInfo,
+    TrieIterInfo, TrieStats,
+};
 use dlc::{Error, RangePayout};
+use std::ops::RangeInclusive;
 
 /// Data structure used to store adaptor signature information for numerical
 /// outcome DLC with t of n oracles where at least t oracles need to sign the
@@ -36,6 +40,38 @@ pub struct MultiOracleTrieDump {
 }
 
 impl MultiOracleTrie {
+    /// Returns an iterator over the range information stored in the trie
+    /// together with the range of outcomes they cover, as attested by the
+    /// first oracle of each entry.
+    pub fn range_iter<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (RangeInclusive<usize>, &'a RangeInfo)> + 'a {
+        let base = self.oracle_numeric_infos.base;
+        let min_nb_digits = self.oracle_numeric_infos.get_min_nb_digits();
+        let main_iter = DigitTrieIter::new(&self.digit_trie).flat_map(move |res| {
+            let range = prefix_to_range(&res.path, base, min_nb_digits);
+            res.value.iter().map(move |x| (range.clone(), x))
+        });
+        let extra_iter = self
+            .extra_cover_trie
+            .iter()
+            .flat_map(|trie| trie.range_iter());
+        main_iter.chain(extra_iter)
+    }
+
+    /// Returns information about the size of the trie.
+    pub fn stats(&self) -> TrieStats {
+        let range_infos_size = |x: &Vec<RangeInfo>| x.capacity() * std::mem::size_of::<RangeInfo>();
+        let extra_cover_trie = self.extra_cover_trie.as_ref();
+        TrieStats {
+            nb_nodes: self.digit_trie.nb_nodes()
+                + extra_cover_trie.map_or(0, |trie| trie.nb_nodes()),
+            heap_size: self.digit_trie.heap_size(range_infos_size)
+                + extra_cover_trie.map_or(0, |trie| trie.heap_size(|_| 0)),
+            nb_adaptor_signatures: self.iter().count(),
+        }
+    }
+
     /// Dump the trie information.
     pub fn dump(&self) -> MultiOracleTrieDump {
         MultiOracleTrieDump {
@@ -167,7 +203,7 @@ impl MultiOracleTrie {
 
     /// Creates a new MultiOracleTrie
     pub fn new(oracle_numeric_infos: &OracleNumericInfo, threshold: usize) -> Result<Self, Error> {
-        if oracle_numeric_infos.nb_digits.is_empty() {
+        if oracle_numeric_infos.nb_digits.is_empty() || oracle_numeric_infos.base < 2 {
             return Err(Error::InvalidArgument);
         }
         let digit_trie = DigitTrie::new(oracle_numeric_infos.base);
@@ -188,24 +224,28 @@ impl MultiOracleTrie {
 }
 
 impl<'a> DlcTrie<'a, MultiOracleTrieIter<'a>> for MultiOracleTrie {
-    fn generate(
+    fn generate_step(
         &mut self,
-        adaptor_index_start: usize,
+        progress: &mut GenerationProgress,
         outcomes: &[RangePayout],
+        max_outcomes: usize,
     ) -> Result<Vec<TrieIterInfo>, Error> {
+        if progress.is_complete {
+            return Ok(Vec::new());
+        }
+
         let threshold = self.threshold;
         let nb_oracles = self.oracle_numeric_infos.nb_digits.len();
         let min_nb_digits = self.oracle_numeric_infos.get_min_nb_digits();
-        let mut adaptor_index = adaptor_index_start;
+        let mut adaptor_index = progress.next_adaptor_index;
         let mut trie_infos = Vec::new();
         let oracle_numeric_infos = &self.oracle_numeric_infos;
-        for (cet_index, outcome) in outcomes.iter().enumerate() {
-            let groups = group_by_ignoring_digits(
-                outcome.start,
-                outcome.start + outcome.count - 1,
-                self.digit_trie.base,
-                min_nb_digits,
-            );
+        let start = progress.next_outcome_index.min(outcomes.len());
+        let end = start.saturating_add(max_outcomes).min(outcomes.len());
+        let outcome_groups =
+            get_outcome_groups(&outcomes[start..end], self.digit_trie.base, min_nb_digits);
+        for (i, groups) in outcome_groups.into_iter().enumerate() {
+            let cet_index = start + i;
             for group in groups {
                 let mut get_value = |_: Option<Vec<RangeInfo>>| -> Result<Vec<RangeInfo>, Error> {
                     let combination_iterator = CombinationIterator::new(nb_oracles, threshold);
@@ -242,20 +282,28 @@ impl<'a> DlcTrie<'a, MultiOracleTrieIter<'a>> for MultiOracleTrie {
             }
         }
 
-        if let Some(extra_cover_trie) = &mut self.extra_cover_trie {
-            let mut get_value =
-                |paths: &[Vec<usize>], oracle_indexes: &[usize]| -> Result<RangeInfo, Error> {
-                    get_value_callback(
-                        paths,
-                        oracle_indexes,
-                        outcomes.len() - 1,
-                        &mut adaptor_index,
-                        &mut trie_infos,
-                    )
-                };
-            extra_cover_trie.insert_max_paths(&mut get_value)?;
+        let is_complete = end == outcomes.len();
+
+        if is_complete {
+            if let Some(extra_cover_trie) = &mut self.extra_cover_trie {
+                let mut get_value =
+                    |paths: &[Vec<usize>], oracle_indexes: &[usize]| -> Result<RangeInfo, Error> {
+                        get_value_callback(
+                            paths,
+                            oracle_indexes,
+                            outcomes.len() - 1,
+                            &mut adaptor_index,
+                            &mut trie_infos,
+                        )
+                    };
+                extra_cover_trie.insert_max_paths(&mut get_value)?;
+            }
         }
 
+        progress.next_outcome_index = end;
+        progress.next_adaptor_index = adaptor_index;
+        progress.is_complete = is_complete;
+
         Ok(trie_infos)
     }
 
@@ -384,6 +432,75 @@ mod tests {
             .expect("Could not retrieve path with extra len.");
     }
 
+    #[test]
+    fn test_stats() {
+        let range_payouts = vec![
+            RangePayout {
+                start: 0,
+                count: 300,
+                payout: Payout {
+                    offer: 0,
+                    accept: 200000000,
+                },
+            },
+            RangePayout {
+                start: 300,
+                count: 724,
+                payout: Payout {
+                    offer: 200000000,
+                    accept: 0,
+                },
+            },
+        ];
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[10, 11, 10], 2);
+        let mut multi_oracle_trie = MultiOracleTrie::new(&oracle_numeric_infos, 2).unwrap();
+        let empty_stats = multi_oracle_trie.stats();
+        let infos = multi_oracle_trie.generate(0, &range_payouts).unwrap();
+        let stats = multi_oracle_trie.stats();
+
+        assert_eq!(0, empty_stats.nb_adaptor_signatures);
+        assert_eq!(infos.len(), stats.nb_adaptor_signatures);
+        assert!(stats.nb_nodes > empty_stats.nb_nodes);
+        assert!(stats.heap_size > empty_stats.heap_size);
+    }
+
+    #[test]
+    fn test_base_ten_diff_nb_digits() {
+        let range_payouts = vec![
+            RangePayout {
+                start: 0,
+                count: 500,
+                payout: Payout {
+                    offer: 0,
+                    accept: 200000000,
+                },
+            },
+            RangePayout {
+                start: 500,
+                count: 500,
+                payout: Payout {
+                    offer: 200000000,
+                    accept: 0,
+                },
+            },
+        ];
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[3, 4, 5], 10);
+        let mut multi_oracle_trie = MultiOracleTrie::new(&oracle_numeric_infos, 2).unwrap();
+        multi_oracle_trie.generate(0, &range_payouts).unwrap();
+
+        let res = multi_oracle_trie
+            .look_up(&[(0, vec![4, 9, 9]), (2, vec![0, 0, 4, 9, 9])])
+            .expect("Could not retrieve in bound path.");
+        assert_eq!(0, res.0.cet_index);
+        let res = multi_oracle_trie
+            .look_up(&[(1, vec![7, 5, 0, 0]), (2, vec![0, 3, 0, 0, 0])])
+            .expect("Could not retrieve out of bound path.");
+        assert_eq!(1, res.0.cet_index);
+        assert!(multi_oracle_trie
+            .look_up(&[(0, vec![12, 0, 0]), (1, vec![0, 12, 0, 0])])
+            .is_none());
+    }
+
     #[test]
     fn test_over_bound_outcome() {
         let range_payouts = vec![RangePayout {
@@ -404,4 +521,30 @@ mod tests {
             ])
             .expect("Could not retrieve path with extra len.");
     }
+
+    #[test]
+    fn test_range_iter_covers_outcomes() {
+        let range_payouts: Vec<_> = (0..4)
+            .map(|i| RangePayout {
+                start: i * 100,
+                count: if i == 3 { 724 } else { 100 },
+                payout: Payout {
+                    offer: i as u64,
+                    accept: 3 - i as u64,
+                },
+            })
+            .collect();
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[10], 2);
+        let mut multi_oracle_trie = MultiOracleTrie::new(&oracle_numeric_infos, 1).unwrap();
+        multi_oracle_trie.generate(0, &range_payouts).unwrap();
+
+        let mut nb_covered = 0;
+        for (range, range_info) in multi_oracle_trie.range_iter() {
+            let payout = &range_payouts[range_info.cet_index];
+            assert!(payout.start <= *range.start());
+            assert!(*range.end() < payout.start + payout.count);
+            nb_covered += range.end() - range.start() + 1;
+        }
+        assert_eq!(1024, nb_covered);
+    }
 }
@@ -3,12 +3,13 @@
 //! for numerical outcome DLC with t of n oracles where some difference
 //! between the outcomes of each oracle can be supported.
 
-use crate::digit_decomposition::group_by_ignoring_digits;
-use crate::multi_trie::{MultiTrie, MultiTrieDump, MultiTrieIterator};
-use crate::utils::get_value_callback;
+use crate::multi_trie::{MultiTrie, MultiTrieDump, MultiTrieIterator, OraclePairParams};
+use crate::node_store::{MultiTrieNodeValue, NodeStore, StoredMultiTrie};
+use crate::utils::{get_outcome_groups, get_value_callback};
 
-use crate::{DlcTrie, OracleNumericInfo, RangeInfo, TrieIterInfo};
+use crate::{DlcTrie, GenerationProgress, OracleNumericInfo, RangeInfo, TrieIterInfo, TrieStats};
 use dlc::{Error, RangePayout};
+use std::ops::RangeInclusive;
 
 /// Data structure used to store adaptor signature information for numerical
 /// outcome DLC with multiple oracles where some difference between the outcomes
@@ -28,20 +29,52 @@ impl MultiOracleTrieWithDiff {
         threshold: usize,
         min_support_exp: usize,
         max_error_exp: usize,
+    ) -> Result<Self, Error> {
+        Self::new_with_pair_params(
+            oracle_numeric_infos,
+            threshold,
+            min_support_exp,
+            max_error_exp,
+            Vec::new(),
+        )
+    }
+
+    /// Create a new MultiOracleTrieWithDiff using specific difference
+    /// parameters between some pairs of oracles, for example to allow a larger
+    /// difference with an oracle that is known to lag behind the others. When
+    /// a set of oracles contains several pairs, the largest parameters among
+    /// them are used.
+    pub fn new_with_pair_params(
+        oracle_numeric_infos: &OracleNumericInfo,
+        threshold: usize,
+        min_support_exp: usize,
+        max_error_exp: usize,
+        pair_params: Vec<OraclePairParams>,
     ) -> Result<Self, Error> {
         let nb_oracles = oracle_numeric_infos.nb_digits.len();
-        let is_valid =
-            nb_oracles >= 1 && threshold <= nb_oracles && min_support_exp < max_error_exp;
+        // Support and error bounds are expressed as powers of two, so that
+        // only binary decomposition is supported.
+        let is_valid = nb_oracles >= 1
+            && threshold <= nb_oracles
+            && min_support_exp < max_error_exp
+            && oracle_numeric_infos.base == 2
+            && pair_params.iter().all(|p| {
+                p.first < nb_oracles
+                    && p.second < nb_oracles
+                    && p.first != p.second
+                    && p.min_support_exp < p.max_error_exp
+            });
         if !is_valid {
             return Err(Error::InvalidArgument);
         }
-        let multi_trie = MultiTrie::new(
+        let mut multi_trie = MultiTrie::new(
             oracle_numeric_infos,
             threshold,
             min_support_exp,
             max_error_exp,
             true,
         );
+        multi_trie.set_pair_params(pair_params);
         Ok(MultiOracleTrieWithDiff {
             multi_trie,
             oracle_numeric_infos: oracle_numeric_infos.clone(),
@@ -50,21 +83,29 @@ impl MultiOracleTrieWithDiff {
 }
 
 impl<'a> DlcTrie<'a, MultiOracleTrieWithDiffIter<'a>> for MultiOracleTrieWithDiff {
-    fn generate(
+    fn generate_step(
         &mut self,
-        adaptor_index_start: usize,
+        progress: &mut GenerationProgress,
         outcomes: &[RangePayout],
+        max_outcomes: usize,
     ) -> Result<Vec<TrieIterInfo>, Error> {
-        let mut adaptor_index = adaptor_index_start;
+        if progress.is_complete {
+            return Ok(Vec::new());
+        }
+
+        let mut adaptor_index = progress.next_adaptor_index;
         let mut trie_infos = Vec::new();
+        let start = progress.next_outcome_index.min(outcomes.len());
+        let end = start.saturating_add(max_outcomes).min(outcomes.len());
 
-        for (cet_index, outcome) in outcomes.iter().enumerate() {
-            let groups = group_by_ignoring_digits(
-                outcome.start,
-                outcome.start + outcome.count - 1,
-                self.oracle_numeric_infos.base,
-                self.oracle_numeric_infos.get_min_nb_digits(),
-            );
+        let outcome_groups = get_outcome_groups(
+            &outcomes[start..end],
+            self.oracle_numeric_infos.base,
+            self.oracle_numeric_infos.get_min_nb_digits(),
+        );
+
+        for (i, groups) in outcome_groups.into_iter().enumerate() {
+            let cet_index = start + i;
             for group in groups {
                 let mut get_value =
                     |paths: &[Vec<usize>], oracle_indexes: &[usize]| -> Result<RangeInfo, Error> {
@@ -80,7 +121,9 @@ impl<'a> DlcTrie<'a, MultiOracleTrieWithDiffIter<'a>> for MultiOracleTrieWithDif
             }
         }
 
-        if self.oracle_numeric_infos.has_diff_nb_digits() {
+        let is_complete = end == outcomes.len();
+
+        if is_complete && self.oracle_numeric_infos.has_diff_nb_digits() {
             let mut get_value =
                 |paths: &[Vec<usize>], oracle_indexes: &[usize]| -> Result<RangeInfo, Error> {
                     get_value_callback(
@@ -94,6 +137,10 @@ impl<'a> DlcTrie<'a, MultiOracleTrieWithDiffIter<'a>> for MultiOracleTrieWithDif
             self.multi_trie.insert_max_paths(&mut get_value)?;
         }
 
+        progress.next_outcome_index = end;
+        progress.next_adaptor_index = adaptor_index;
+        progress.is_complete = is_complete;
+
         Ok(trie_infos)
     }
 
@@ -114,6 +161,15 @@ pub struct MultiOracleTrieWithDiffDump {
 }
 
 impl MultiOracleTrieWithDiff {
+    /// Returns information about the size of the trie.
+    pub fn stats(&self) -> TrieStats {
+        TrieStats {
+            nb_nodes: self.multi_trie.nb_nodes(),
+            heap_size: self.multi_trie.heap_size(|_| 0),
+            nb_adaptor_signatures: self.iter().count(),
+        }
+    }
+
     /// Dump the content of the trie for the purpose of serialization.
     pub fn dump(&self) -> MultiOracleTrieWithDiffDump {
         let multi_trie_dump = self.multi_trie.dump();
@@ -123,6 +179,25 @@ impl MultiOracleTrieWithDiff {
         }
     }
 
+    /// Write the content of the trie to `store` and return a trie backed by it,
+    /// that can be used to look up adaptor information without loading the
+    /// whole trie in memory.
+    pub fn write_to_store<S>(&self, store: S) -> Result<StoredMultiTrie<RangeInfo, S>, S::Error>
+    where
+        S: NodeStore<MultiTrieNodeValue<RangeInfo>>,
+    {
+        self.multi_trie.write_to_store(store)
+    }
+
+    /// Returns an iterator over the range information stored in the trie
+    /// together with the range of outcomes they cover, as attested by the
+    /// first oracle of each entry.
+    pub fn range_iter<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (RangeInclusive<usize>, &'a RangeInfo)> + 'a {
+        self.multi_trie.range_iter()
+    }
+
     /// Restore a trie from a dump.
     pub fn from_dump(dump: MultiOracleTrieWithDiffDump) -> MultiOracleTrieWithDiff {
         let MultiOracleTrieWithDiffDump {
@@ -169,9 +244,168 @@ impl<'a> Iterator for MultiOracleTrieWithDiffIter<'a> {
 mod tests {
     use dlc::{Payout, RangePayout};
 
-    use crate::{test_utils::get_variable_oracle_numeric_infos, DlcTrie};
+    use crate::{
+        multi_trie::OraclePairParams, test_utils::get_variable_oracle_numeric_infos, DlcTrie,
+        GenerationProgress, TrieIterInfo,
+    };
 
     use super::MultiOracleTrieWithDiff;
+
+    fn get_selector_paths(
+        trie: &MultiOracleTrieWithDiff,
+        selector: &[usize],
+    ) -> Vec<Vec<Vec<usize>>> {
+        let mut paths = trie
+            .iter()
+            .filter(|info| info.indexes == selector)
+            .map(|info| info.paths)
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_non_binary_base_is_rejected() {
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[4, 4], 10);
+        assert!(MultiOracleTrieWithDiff::new(&oracle_numeric_infos, 2, 1, 2).is_err());
+    }
+
+    fn to_tuples(infos: Vec<TrieIterInfo>) -> Vec<(Vec<usize>, Vec<Vec<usize>>, usize, usize)> {
+        infos
+            .into_iter()
+            .map(|x| (x.indexes, x.paths, x.value.cet_index, x.value.adaptor_index))
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_in_steps_matches_generate() {
+        let range_payouts = (0..10)
+            .map(|i| RangePayout {
+                start: i * 100,
+                count: if i == 9 { 124 } else { 100 },
+                payout: Payout {
+                    offer: i as u64 * 1000,
+                    accept: 10000 - i as u64 * 1000,
+                },
+            })
+            .collect::<Vec<_>>();
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[11, 10, 10], 2);
+
+        let mut trie = MultiOracleTrieWithDiff::new(&oracle_numeric_infos, 2, 1, 2).unwrap();
+        let expected = to_tuples(trie.generate(3, &range_payouts).unwrap());
+
+        let mut step_trie = MultiOracleTrieWithDiff::new(&oracle_numeric_infos, 2, 1, 2).unwrap();
+        let mut progress = GenerationProgress::new(3);
+        let mut actual = Vec::new();
+        let mut nb_steps = 0;
+        while !progress.is_complete {
+            let infos = step_trie
+                .generate_step(&mut progress, &range_payouts, 3)
+                .unwrap();
+            actual.extend(to_tuples(infos));
+            nb_steps += 1;
+        }
+
+        assert_eq!(4, nb_steps);
+        assert_eq!(expected, actual);
+        assert_eq!(expected.len() + 3, progress.next_adaptor_index);
+        assert_eq!(
+            to_tuples(trie.iter().collect()),
+            to_tuples(step_trie.iter().collect())
+        );
+        assert!(step_trie
+            .generate_step(&mut progress, &range_payouts, 3)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_invalid_pair_params_are_rejected() {
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[10, 10], 2);
+        let invalid_pairs = vec![(0, 2, 1, 4), (1, 1, 1, 4), (0, 1, 4, 4)];
+        for (first, second, min_support_exp, max_error_exp) in invalid_pairs {
+            let pair_params = vec![OraclePairParams {
+                first,
+                second,
+                min_support_exp,
+                max_error_exp,
+            }];
+            assert!(MultiOracleTrieWithDiff::new_with_pair_params(
+                &oracle_numeric_infos,
+                2,
+                1,
+                2,
+                pair_params
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_pair_params_are_used_for_oracle_pairs() {
+        let range_payouts = vec![
+            RangePayout {
+                start: 0,
+                count: 500,
+                payout: Payout {
+                    offer: 0,
+                    accept: 200000000,
+                },
+            },
+            RangePayout {
+                start: 500,
+                count: 524,
+                payout: Payout {
+                    offer: 200000000,
+                    accept: 0,
+                },
+            },
+        ];
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[10, 10, 10], 2);
+        let pair_params = vec![
+            OraclePairParams {
+                first: 0,
+                second: 2,
+                min_support_exp: 1,
+                max_error_exp: 4,
+            },
+            OraclePairParams {
+                first: 2,
+                second: 1,
+                min_support_exp: 1,
+                max_error_exp: 4,
+            },
+        ];
+        let mut trie = MultiOracleTrieWithDiff::new_with_pair_params(
+            &oracle_numeric_infos,
+            2,
+            1,
+            2,
+            pair_params,
+        )
+        .unwrap();
+        trie.generate(0, &range_payouts).unwrap();
+        let mut tight_trie = MultiOracleTrieWithDiff::new(&oracle_numeric_infos, 2, 1, 2).unwrap();
+        tight_trie.generate(0, &range_payouts).unwrap();
+        let mut loose_trie = MultiOracleTrieWithDiff::new(&oracle_numeric_infos, 2, 1, 4).unwrap();
+        loose_trie.generate(0, &range_payouts).unwrap();
+
+        assert_eq!(
+            get_selector_paths(&tight_trie, &[0, 1]),
+            get_selector_paths(&trie, &[0, 1])
+        );
+        for selector in [[0, 2], [1, 2]].iter() {
+            assert_eq!(
+                get_selector_paths(&loose_trie, selector),
+                get_selector_paths(&trie, selector)
+            );
+            assert_ne!(
+                get_selector_paths(&tight_trie, selector),
+                get_selector_paths(&trie, selector)
+            );
+        }
+    }
+
     #[test]
     fn test_is_ordered() {
         let range_payouts = vec![
@@ -6,9 +6,14 @@ use crate::{
     LookupResult, Node, OracleNumericInfo,
 };
 use combination_iterator::CombinationIterator;
+use digit_decomposition::prefix_to_range;
 use digit_trie::{DigitTrie, DigitTrieDump, DigitTrieIter};
 use dlc::Error;
 use multi_oracle::compute_outcome_combinations;
+use node_store::{MultiTrieNodeValue, NodeStore, StoredMultiTrie};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::ops::RangeInclusive;
 
 #[derive(Clone, Debug)]
 /// Information stored in a node.
@@ -86,6 +91,22 @@ impl<'a, T> MultiTrieIterator<'a, T> {
     }
 }
 
+impl<T> MultiTrie<T> {
+    /// Returns an iterator over the values of the trie together with the range
+    /// of outcomes they cover, as attested by the first oracle of each entry.
+    pub fn range_iter<'a>(&'a self) -> impl Iterator<Item = (RangeInclusive<usize>, &'a T)> + 'a {
+        MultiTrieIterator::new(self).map(move |res| {
+            let (oracle_index, prefix) = &res.path[0];
+            let range = prefix_to_range(
+                prefix,
+                self.oracle_numeric_infos.base,
+                self.oracle_numeric_infos.nb_digits[*oracle_index],
+            );
+            (range, res.value)
+        })
+    }
+}
+
 /// Implements the Iterator trait for MultiTrieIterator.
 impl<'a, T> Iterator for MultiTrieIterator<'a, T> {
     type Item = LookupResult<'a, T, (usize, Vec<usize>)>;
@@ -164,6 +185,27 @@ impl<'a, T> Iterator for MultiTrieIterator<'a, T> {
     }
 }
 
+/// Difference parameters used between two given oracles in place of the
+/// ones of the trie.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OraclePairParams {
+    /// The index of the first oracle of the pair.
+    pub first: usize,
+    /// The index of the second oracle of the pair.
+    pub second: usize,
+    /// The guaranteed support as a power of 2.
+    pub min_support_exp: usize,
+    /// The maximum support as a power of 2.
+    pub max_error_exp: usize,
+}
+
+impl OraclePairParams {
+    fn matches(&self, first: usize, second: usize) -> bool {
+        (self.first == first && self.second == second)
+            || (self.first == second && self.second == first)
+    }
+}
+
 /// Struct used to store DLC outcome information for multi oracle cases.  
 #[derive(Clone)]
 pub struct MultiTrie<T> {
@@ -174,6 +216,7 @@ pub struct MultiTrie<T> {
     max_error_exp: usize,
     maximize_coverage: bool,
     oracle_numeric_infos: OracleNumericInfo,
+    pair_params: Vec<OraclePairParams>,
 }
 
 impl<T> MultiTrie<T> {
@@ -214,9 +257,48 @@ impl<T> MultiTrie<T> {
             max_error_exp,
             maximize_coverage,
             oracle_numeric_infos: oracle_numeric_infos.clone(),
+            pair_params: Vec::new(),
         }
     }
 
+    /// Returns the total number of nodes of the sub-tries.
+    pub fn nb_nodes(&self) -> usize {
+        self.store
+            .iter()
+            .map(|x| match x {
+                MultiTrieNode::None => 0,
+                MultiTrieNode::Leaf(d_trie) => d_trie.nb_nodes(),
+                MultiTrieNode::Node(d_trie) => d_trie.nb_nodes(),
+            })
+            .sum()
+    }
+
+    /// Returns an estimate of the heap memory used by the trie in bytes,
+    /// `value_heap_size` giving the heap memory used by each stored value.
+    pub fn heap_size<F>(&self, value_heap_size: F) -> usize
+    where
+        F: Fn(&T) -> usize,
+    {
+        let node_infos_size =
+            |x: &Vec<TrieNodeInfo>| x.capacity() * std::mem::size_of::<TrieNodeInfo>();
+        self.store.capacity() * std::mem::size_of::<MultiTrieNode<T>>()
+            + self
+                .store
+                .iter()
+                .map(|x| match x {
+                    MultiTrieNode::None => 0,
+                    MultiTrieNode::Leaf(d_trie) => d_trie.heap_size(&value_heap_size),
+                    MultiTrieNode::Node(d_trie) => d_trie.heap_size(node_infos_size),
+                })
+                .sum::<usize>()
+    }
+
+    /// Set the difference parameters to use between specific pairs of oracles.
+    /// Must be called before inserting any value.
+    pub(crate) fn set_pair_params(&mut self, pair_params: Vec<OraclePairParams>) {
+        self.pair_params = pair_params;
+    }
+
     fn swap_remove(&mut self, index: usize) -> MultiTrieNode<T> {
         self.store.push(MultiTrieNode::None);
         self.store.swap_remove(index)
@@ -242,47 +324,15 @@ impl<T> MultiTrie<T> {
     where
         F: FnMut(&[Vec<usize>], &[usize]) -> Result<T, Error>,
     {
-        let combination_iter = CombinationIterator::new(self.nb_tries, self.nb_required);
-        let min_nb_digits = self.oracle_numeric_infos.get_min_nb_digits();
-
-        for selector in combination_iter {
-            let combinations = if self.nb_required > 1 {
-                let mut digit_infos = self
-                    .oracle_numeric_infos
-                    .nb_digits
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(i, x)| {
-                        if selector.contains(&i) {
-                            Some(*x)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                let min_index = reorder_to_min_first(&mut digit_infos);
-                let to_pad = digit_infos[0] - min_nb_digits;
-                let padded_path = pre_pad_vec(path.to_vec(), path.len() + to_pad);
-                let mut combinations = compute_outcome_combinations(
-                    &digit_infos,
-                    &padded_path,
-                    self.max_error_exp,
-                    self.min_support_exp,
-                    self.maximize_coverage,
-                );
-                if min_index != 0 {
-                    for combination in &mut combinations {
-                        let to_reorder = combination.remove(0);
-                        combination.insert(min_index, to_reorder);
-                    }
-                }
-                combinations
-            } else {
-                vec![vec![path.to_vec()]]
-            };
-
-            for combination in combinations {
-                self.insert_internal(selector[0], &combination, 0, &selector, get_value)?;
+        let selectors =
+            CombinationIterator::new(self.nb_tries, self.nb_required).collect::<Vec<_>>();
+        let params = CombinationParams::from_trie(self);
+        let (key_indexes, keys) = params.get_keys(&selectors);
+        let combinations = params.compute_combinations(&keys, path);
+
+        for (selector, key_index) in selectors.iter().zip(key_indexes) {
+            for combination in &combinations[key_index] {
+                self.insert_internal(selector[0], combination, 0, selector, get_value)?;
             }
         }
 
@@ -440,7 +490,157 @@ impl<T> MultiTrie<T> {
     }
 }
 
-fn find_store_index(children: &[TrieNodeInfo], trie_index: usize) -> Option<usize> {
+/// Parameters used to compute the outcome combinations covering a path for a
+/// set of oracles. Kept separate from the trie so that it can be shared across
+/// threads regardless of the type of the stored values.
+struct CombinationParams<'a> {
+    oracle_numeric_infos: &'a OracleNumericInfo,
+    nb_required: usize,
+    min_support_exp: usize,
+    max_error_exp: usize,
+    maximize_coverage: bool,
+    pair_params: &'a [OraclePairParams],
+}
+
+impl<'a> CombinationParams<'a> {
+    fn from_trie<T>(trie: &'a MultiTrie<T>) -> CombinationParams<'a> {
+        CombinationParams {
+            oracle_numeric_infos: &trie.oracle_numeric_infos,
+            nb_required: trie.nb_required,
+            min_support_exp: trie.min_support_exp,
+            max_error_exp: trie.max_error_exp,
+            maximize_coverage: trie.maximize_coverage,
+            pair_params: &trie.pair_params,
+        }
+    }
+
+    /// Returns the support and error exponents to use for the given set of
+    /// oracles, which are the largest ones among the pairs of oracles of the
+    /// set, the trie parameters being used for pairs without specific ones.
+    fn get_exps(&self, selector: &[usize]) -> (usize, usize) {
+        selector
+            .iter()
+            .enumerate()
+            .flat_map(|(i, first)| {
+                selector[i + 1..]
+                    .iter()
+                    .map(move |second| (*first, *second))
+            })
+            .map(|(first, second)| {
+                self.pair_params
+                    .iter()
+                    .find(|p| p.matches(first, second))
+                    .map_or((self.min_support_exp, self.max_error_exp), |p| {
+                        (p.min_support_exp, p.max_error_exp)
+                    })
+            })
+            .reduce(|(s1, e1), (s2, e2)| (s1.max(s2), e1.max(e2)))
+            .unwrap_or((self.min_support_exp, self.max_error_exp))
+    }
+
+    fn get_key(&self, selector: &[usize]) -> CombinationKey {
+        let nb_digits = self
+            .oracle_numeric_infos
+            .nb_digits
+            .iter()
+            .enumerate()
+            .filter_map(|(i, x)| {
+                if selector.contains(&i) {
+                    Some(*x)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let (min_support_exp, max_error_exp) = self.get_exps(selector);
+        CombinationKey {
+            nb_digits,
+            min_support_exp,
+            max_error_exp,
+        }
+    }
+
+    /// Returns the distinct keys of the given selectors, together with the
+    /// index of the key of each selector, so that the combinations of
+    /// selectors sharing the same key are only computed once.
+    fn get_keys(&self, selectors: &[Vec<usize>]) -> (Vec<usize>, Vec<CombinationKey>) {
+        let mut keys: Vec<CombinationKey> = Vec::new();
+        let key_indexes = selectors
+            .iter()
+            .map(|selector| {
+                let key = self.get_key(selector);
+                match keys.iter().position(|x| *x == key) {
+                    Some(index) => index,
+                    None => {
+                        keys.push(key);
+                        keys.len() - 1
+                    }
+                }
+            })
+            .collect();
+        (key_indexes, keys)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn compute_combinations(
+        &self,
+        keys: &[CombinationKey],
+        path: &[usize],
+    ) -> Vec<Vec<Vec<Vec<usize>>>> {
+        keys.iter()
+            .map(|key| self.get_combinations(key, path))
+            .collect()
+    }
+
+    #[cfg(feature = "parallel")]
+    fn compute_combinations(
+        &self,
+        keys: &[CombinationKey],
+        path: &[usize],
+    ) -> Vec<Vec<Vec<Vec<usize>>>> {
+        keys.par_iter()
+            .map(|key| self.get_combinations(key, path))
+            .collect()
+    }
+
+    fn get_combinations(&self, key: &CombinationKey, path: &[usize]) -> Vec<Vec<Vec<usize>>> {
+        if self.nb_required <= 1 {
+            return vec![vec![path.to_vec()]];
+        }
+
+        let min_nb_digits = self.oracle_numeric_infos.get_min_nb_digits();
+        let mut digit_infos = key.nb_digits.clone();
+        let min_index = reorder_to_min_first(&mut digit_infos);
+        let to_pad = digit_infos[0] - min_nb_digits;
+        let padded_path = pre_pad_vec(path.to_vec(), path.len() + to_pad);
+        let mut combinations = compute_outcome_combinations(
+            &digit_infos,
+            &padded_path,
+            key.max_error_exp,
+            key.min_support_exp,
+            self.maximize_coverage,
+        );
+        if min_index != 0 {
+            for combination in &mut combinations {
+                let to_reorder = combination.remove(0);
+                combination.insert(min_index, to_reorder);
+            }
+        }
+        combinations
+    }
+}
+
+/// The values that the outcome combinations of a set of oracles depend on.
+/// Sets of oracles using the same number of digits and difference parameters
+/// have the same combinations.
+#[derive(PartialEq)]
+struct CombinationKey {
+    nb_digits: Vec<usize>,
+    min_support_exp: usize,
+    max_error_exp: usize,
+}
+
+pub(crate) fn find_store_index(children: &[TrieNodeInfo], trie_index: usize) -> Option<usize> {
     for info in children {
         if trie_index == info.trie_index {
             return Some(info.store_index);
@@ -483,6 +683,8 @@ where
     pub maximize_coverage: bool,
     /// Information about the numerical representation of oracles
     pub oracle_numeric_infos: OracleNumericInfo,
+    /// The difference parameters specific to some pairs of oracles.
+    pub pair_params: Vec<OraclePairParams>,
 }
 
 impl<T> MultiTrie<T>
@@ -500,9 +702,41 @@ where
             max_error_exp: self.max_error_exp,
             maximize_coverage: self.maximize_coverage,
             oracle_numeric_infos: self.oracle_numeric_infos.clone(),
+            pair_params: self.pair_params.clone(),
         }
     }
 
+    /// Write the nodes of all the sub-tries to `store` and return a trie backed
+    /// by it.
+    pub fn write_to_store<S>(&self, mut store: S) -> Result<StoredMultiTrie<T, S>, S::Error>
+    where
+        S: NodeStore<MultiTrieNodeValue<T>>,
+    {
+        let mut offset = 0;
+        let mut roots = Vec::with_capacity(self.store.len());
+        for node in &self.store {
+            let (root, nb_nodes) = match node {
+                MultiTrieNode::None => unreachable!(),
+                MultiTrieNode::Leaf(d_trie) => (
+                    d_trie.write_nodes(&mut store, offset, MultiTrieNodeValue::Leaf)?,
+                    d_trie.nb_nodes(),
+                ),
+                MultiTrieNode::Node(d_trie) => (
+                    d_trie.write_nodes(&mut store, offset, MultiTrieNodeValue::Node)?,
+                    d_trie.nb_nodes(),
+                ),
+            };
+            roots.push(root);
+            offset += nb_nodes;
+        }
+        Ok(StoredMultiTrie::new(
+            store,
+            roots,
+            self.nb_tries,
+            self.nb_required,
+        ))
+    }
+
     /// Restore a trie from a dump.
     pub fn from_dump(dump: MultiTrieDump<T>) -> MultiTrie<T> {
         let MultiTrieDump {
@@ -513,6 +747,7 @@ where
             max_error_exp,
             maximize_coverage,
             oracle_numeric_infos,
+            pair_params,
         } = dump;
 
         let store = node_data
@@ -528,6 +763,7 @@ where
             max_error_exp,
             maximize_coverage,
             oracle_numeric_infos,
+            pair_params,
         }
     }
 }
@@ -608,6 +844,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn selectors_with_same_digits_share_combinations_test() {
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[10, 10, 11, 10, 10], 2);
+        let m_trie = MultiTrie::<usize>::new(&oracle_numeric_infos, 3, 1, 2, true);
+        let selectors = CombinationIterator::new(5, 3).collect::<Vec<_>>();
+        let params = CombinationParams::from_trie(&m_trie);
+
+        let (key_indexes, keys) = params.get_keys(&selectors);
+
+        assert_eq!(selectors.len(), key_indexes.len());
+        assert_eq!(4, keys.len());
+        let combinations = params.compute_combinations(&keys, &[0, 1, 1, 0, 1, 0, 0]);
+        for (selector, key_index) in selectors.iter().zip(key_indexes) {
+            assert_eq!(
+                params.get_combinations(&params.get_key(selector), &[0, 1, 1, 0, 1, 0, 0]),
+                combinations[key_index]
+            );
+        }
+    }
+
     #[test]
     fn multi_trie_1_of_1_test() {
         let mut m_trie = MultiTrie::<usize>::new(
@@ -0,0 +1,323 @@
+//! # NodeStore
+//! Abstraction over the storage of trie nodes, enabling tries to be kept in
+//! external storage and paged in when looking up values.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use combination_iterator::CombinationIterator;
+use digit_trie::{is_prefix_of, DigitNodeData};
+use dlc::Error;
+use multi_trie::{find_store_index, TrieNodeInfo};
+
+/// Storage for the nodes of a trie, addressed by node index.
+pub trait NodeStore<T> {
+    /// The error returned by the store.
+    type Error: From<Error>;
+
+    /// Returns the node at the given index if any.
+    fn get_node(&self, index: usize) -> Result<Option<DigitNodeData<T>>, Self::Error>;
+
+    /// Stores the node at the given index, replacing any existing one.
+    fn put_node(&mut self, index: usize, node: DigitNodeData<T>) -> Result<(), Self::Error>;
+}
+
+impl<T: Clone> NodeStore<T> for HashMap<usize, DigitNodeData<T>> {
+    type Error = Error;
+
+    fn get_node(&self, index: usize) -> Result<Option<DigitNodeData<T>>, Error> {
+        Ok(self.get(&index).cloned())
+    }
+
+    fn put_node(&mut self, index: usize, node: DigitNodeData<T>) -> Result<(), Error> {
+        self.insert(index, node);
+        Ok(())
+    }
+}
+
+/// The value of a node of a stored `MultiTrie`, which is either
+/// a reference to the following sub-tries or a leaf value.
+#[derive(Clone, Debug)]
+pub enum MultiTrieNodeValue<T> {
+    /// Information about the sub-tries following the node.
+    Node(Vec<TrieNodeInfo>),
+    /// A value stored in a leaf sub-trie.
+    Leaf(T),
+}
+
+/// A digit trie whose nodes are read from a [`NodeStore`] when looked up.
+pub struct StoredDigitTrie<T, S: NodeStore<T>> {
+    store: S,
+    root: Option<usize>,
+    phantom: PhantomData<T>,
+}
+
+impl<T, S: NodeStore<T>> StoredDigitTrie<T, S> {
+    /// Create a trie backed by `store` whose root node is at index `root`.
+    pub fn new(store: S, root: Option<usize>) -> Self {
+        StoredDigitTrie {
+            store,
+            root,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the index of the root node.
+    pub fn root(&self) -> Option<usize> {
+        self.root
+    }
+
+    /// Lookup for nodes whose path is either equal or a prefix of `path`,
+    /// returning their paths and values.
+    pub fn look_up(&self, path: &[usize]) -> Result<Option<Vec<(Vec<usize>, T)>>, S::Error> {
+        look_up_stored(&self.store, self.root, path)
+    }
+}
+
+/// A trie of trie whose nodes are read from a [`NodeStore`] when looked up.
+pub struct StoredMultiTrie<T, S: NodeStore<MultiTrieNodeValue<T>>> {
+    store: S,
+    roots: Vec<Option<usize>>,
+    nb_tries: usize,
+    nb_required: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T, S: NodeStore<MultiTrieNodeValue<T>>> StoredMultiTrie<T, S> {
+    /// Create a trie backed by `store`, where `roots` contains the index of
+    /// the root node of each sub-trie.
+    pub fn new(store: S, roots: Vec<Option<usize>>, nb_tries: usize, nb_required: usize) -> Self {
+        StoredMultiTrie {
+            store,
+            roots,
+            nb_tries,
+            nb_required,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the index of the root node of each sub-trie.
+    pub fn roots(&self) -> &[Option<usize>] {
+        &self.roots
+    }
+
+    /// Lookup in the trie for a value that matches with `paths`.
+    pub fn look_up(
+        &self,
+        paths: &[(usize, Vec<usize>)],
+    ) -> Result<Option<(T, Vec<(usize, Vec<usize>)>)>, S::Error> {
+        if paths.len() < self.nb_required {
+            return Ok(None);
+        }
+
+        let nb_roots = self.nb_tries - self.nb_required + 1;
+
+        for selector in CombinationIterator::new(paths.len(), self.nb_required) {
+            let first_index = paths[selector[0]].0;
+            if first_index >= nb_roots {
+                continue;
+            }
+
+            let selected_paths = paths
+                .iter()
+                .enumerate()
+                .filter_map(|(i, x)| {
+                    if selector.contains(&i) {
+                        return Some(x);
+                    }
+                    None
+                })
+                .collect::<Vec<_>>();
+            if let Some((value, mut path)) =
+                self.look_up_internal(first_index, &selected_paths, 0)?
+            {
+                path.reverse();
+                return Ok(Some((value, path)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn look_up_internal(
+        &self,
+        sub_trie_index: usize,
+        paths: &[&(usize, Vec<usize>)],
+        path_index: usize,
+    ) -> Result<Option<(T, Vec<(usize, Vec<usize>)>)>, S::Error> {
+        let trie_index = paths[path_index].0;
+        let root = *self
+            .roots
+            .get(sub_trie_index)
+            .ok_or(Error::InvalidArgument)?;
+        let results = match look_up_stored(&self.store, root, &paths[path_index].1)? {
+            None => return Ok(None),
+            Some(results) => results,
+        };
+
+        for (path, value) in results {
+            match value {
+                MultiTrieNodeValue::Leaf(value) => {
+                    return Ok(Some((value, vec![(trie_index, path)])));
+                }
+                MultiTrieNodeValue::Node(children) => {
+                    if path_index + 1 >= paths.len() {
+                        return Err(Error::InvalidArgument.into());
+                    }
+                    if let Some(index) = find_store_index(&children, paths[path_index + 1].0) {
+                        if let Some((value, mut child_path)) =
+                            self.look_up_internal(index, paths, path_index + 1)?
+                        {
+                            child_path.push((trie_index, path));
+                            return Ok(Some((value, child_path)));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn look_up_stored<T, S: NodeStore<T>>(
+    store: &S,
+    cur_index: Option<usize>,
+    path: &[usize],
+) -> Result<Option<Vec<(Vec<usize>, T)>>, S::Error> {
+    let cur_index = match cur_index {
+        None => return Ok(None),
+        Some(cur_index) => cur_index,
+    };
+    let node = store.get_node(cur_index)?.ok_or(Error::InvalidArgument)?;
+
+    let children = match node.children {
+        None => {
+            let data = node.data.ok_or(Error::InvalidArgument)?;
+            return Ok(if is_prefix_of(&node.prefix, path) {
+                Some(vec![(node.prefix, data)])
+            } else {
+                None
+            });
+        }
+        Some(children) => children,
+    };
+
+    if !is_prefix_of(&node.prefix, path) {
+        return Ok(None);
+    }
+
+    if node.prefix.len() == path.len() {
+        return Ok(node.data.map(|data| vec![(node.prefix, data)]));
+    }
+
+    // Digits outside of the base (from an invalid attestation) have no child.
+    let child = children
+        .get(path[node.prefix.len()])
+        .cloned()
+        .unwrap_or(None);
+    let child_results = look_up_stored(store, child, &path[node.prefix.len()..])?;
+
+    let mut results = Vec::new();
+    if let Some(data) = node.data {
+        results.push((node.prefix.clone(), data));
+    }
+    if let Some(child_results) = child_results {
+        results.extend(child_results.into_iter().map(|(child_path, value)| {
+            (
+                node.prefix
+                    .iter()
+                    .chain(child_path.iter())
+                    .cloned()
+                    .collect(),
+                value,
+            )
+        }));
+    }
+
+    Ok(if results.is_empty() {
+        None
+    } else {
+        Some(results)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use digit_trie::DigitTrie;
+    use dlc::{Payout, RangePayout};
+    use multi_oracle_trie_with_diff::MultiOracleTrieWithDiff;
+    use test_utils::get_variable_oracle_numeric_infos;
+    use DlcTrie;
+
+    #[test]
+    fn stored_digit_trie_look_up_matches_in_memory_test() {
+        let paths = vec![
+            vec![0, 1, 2, 0, 10, 11],
+            vec![0, 1, 2, 0, 10, 12],
+            vec![0, 1, 2, 0, 11],
+            vec![0, 1, 2],
+            vec![0, 1, 2, 0, 13, 0],
+        ];
+        let mut digit_trie = DigitTrie::<usize>::new(16);
+        for (i, path) in paths.iter().enumerate() {
+            digit_trie.insert(path, &mut |_| Ok(i)).unwrap();
+        }
+
+        let stored = digit_trie.write_to_store(HashMap::new()).unwrap();
+
+        for path in paths
+            .iter()
+            .chain(vec![vec![0, 1, 3], vec![0, 1, 2, 0, 10, 13]].iter())
+        {
+            let expected = digit_trie.look_up(path).map(|res| {
+                res.into_iter()
+                    .map(|x| (x.path, *x.value))
+                    .collect::<Vec<_>>()
+            });
+            assert_eq!(expected, stored.look_up(path).unwrap());
+        }
+    }
+
+    #[test]
+    fn stored_multi_trie_look_up_matches_in_memory_test() {
+        let range_payouts = vec![
+            RangePayout {
+                start: 0,
+                count: 10,
+                payout: Payout {
+                    offer: 0,
+                    accept: 200000000,
+                },
+            },
+            RangePayout {
+                start: 10,
+                count: 1014,
+                payout: Payout {
+                    offer: 200000000,
+                    accept: 0,
+                },
+            },
+        ];
+        let oracle_numeric_infos = get_variable_oracle_numeric_infos(&[11, 10, 10], 2);
+        let mut trie = MultiOracleTrieWithDiff::new(&oracle_numeric_infos, 2, 1, 2).unwrap();
+        trie.generate(0, &range_payouts).unwrap();
+
+        let stored = trie.write_to_store(HashMap::new()).unwrap();
+
+        for info in trie.iter() {
+            let paths = info
+                .indexes
+                .iter()
+                .cloned()
+                .zip(info.paths.iter().cloned())
+                .collect::<Vec<_>>();
+            let expected = trie
+                .multi_trie
+                .look_up(&paths)
+                .map(|(value, path)| (value.clone(), path));
+            assert_eq!(expected, stored.look_up(&paths).unwrap());
+        }
+    }
+}
@@ -1,12 +1,43 @@
 //! Utility functions when working with DLC trie
 
-use dlc::Error;
+use dlc::{Error, RangePayout};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use secp256k1_zkp::PublicKey;
 
 use crate::{
-    combination_iterator::CombinationIterator, OracleNumericInfo, RangeInfo, TrieIterInfo,
+    combination_iterator::CombinationIterator, digit_decomposition::group_by_ignoring_digits,
+    OracleNumericInfo, RangeInfo, TrieIterInfo,
 };
 
+/// Returns the digit prefixes covering the range of each of the given outcomes,
+/// in the order of the outcomes.
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn get_outcome_groups(
+    outcomes: &[RangePayout],
+    base: usize,
+    nb_digits: usize,
+) -> Vec<Vec<Vec<usize>>> {
+    outcomes
+        .iter()
+        .map(|x| group_by_ignoring_digits(x.start, x.start + x.count - 1, base, nb_digits))
+        .collect()
+}
+
+/// Returns the digit prefixes covering the range of each of the given outcomes,
+/// in the order of the outcomes.
+#[cfg(feature = "parallel")]
+pub(crate) fn get_outcome_groups(
+    outcomes: &[RangePayout],
+    base: usize,
+    nb_digits: usize,
+) -> Vec<Vec<Vec<usize>>> {
+    outcomes
+        .par_iter()
+        .map(|x| group_by_ignoring_digits(x.start, x.start + x.count - 1, base, nb_digits))
+        .collect()
+}
+
 /// Creates an adaptor point using the provided oracle infos and paths, selecting
 /// the oracle info at the provided indexes only. The paths are converted to
 /// strings and hashed to be used as messages in adaptor signature creation.
@@ -54,6 +85,7 @@ pub(crate) fn get_max_covering_paths(
 ) -> Vec<Vec<(usize, Vec<usize>)>> {
     let mut paths: Vec<Vec<(usize, Vec<usize>)>> = Vec::new();
     let min_nb_digits = oracle_numeric_infos.get_min_nb_digits();
+    let base = oracle_numeric_infos.base;
     let combination_iter =
         CombinationIterator::new(oracle_numeric_infos.nb_digits.len(), threshold);
     for combination in combination_iter {
@@ -85,25 +117,29 @@ pub(crate) fn get_max_covering_paths(
             .collect::<Vec<_>>();
         let mut i = 0;
         loop {
-            let path = counters
+            // For oracles with `min_nb_digits` we just generate the max value.
+            // For others we generate the prefixes based on their current counter
+            // value, inserting `counter - 1` zeros followed by a non zero digit.
+            let candidates = counters
                 .iter()
-                .zip(combination.iter())
-                .map(|(x, i)| {
-                    // For oracles with `min_nb_digits` we just generate the max value.
-                    let p = if *x == 0 {
-                        std::iter::repeat(1).take(min_nb_digits).collect()
+                .map(|x| {
+                    if *x == 0 {
+                        vec![vec![base - 1; min_nb_digits]]
                     } else {
-                        // For others we generate the prefix based on their current
-                        // counter value. We insert `counter - 1` zero and then a 1.
-                        let mut p = Vec::with_capacity(*x);
-                        p.resize(x - 1, 0);
-                        p.push(1);
-                        p
-                    };
-                    (*i, p)
+                        (1..base)
+                            .map(|d| {
+                                let mut p = Vec::with_capacity(*x);
+                                p.resize(x - 1, 0);
+                                p.push(d);
+                                p
+                            })
+                            .collect()
+                    }
                 })
                 .collect::<Vec<_>>();
-            paths.push(path);
+            for prefixes in cartesian_product(&candidates) {
+                paths.push(combination.iter().cloned().zip(prefixes).collect());
+            }
 
             // If all counters have reached their max prefix size value, we're done.
             if counters.iter().zip(max.iter()).all(|(x, y)| x == y) {
@@ -126,6 +162,22 @@ pub(crate) fn get_max_covering_paths(
     paths
 }
 
+/// Returns all the combinations made of one element of each of the given
+/// lists, in lexicographic order.
+fn cartesian_product<T: Clone>(lists: &[Vec<T>]) -> Vec<Vec<T>> {
+    lists.iter().fold(vec![Vec::new()], |acc, list| {
+        acc.into_iter()
+            .flat_map(|prefix| {
+                list.iter().map(move |x| {
+                    let mut combination = prefix.clone();
+                    combination.push(x.clone());
+                    combination
+                })
+            })
+            .collect()
+    })
+}
+
 pub(crate) fn get_value_callback(
     paths: &[Vec<usize>],
     oracle_indexes: &[usize],
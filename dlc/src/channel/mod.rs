@@ -1,7 +1,6 @@
 //! Module for working with DLC channels
 
-use std::collections::HashMap;
-
+use crate::prelude::*;
 use crate::{signatures_to_secret, util::get_sig_hash_msg, DlcTransactions, PartyParams, Payout};
 
 use super::Error;
@@ -357,16 +356,15 @@ pub fn sign_cet<C: Signing>(
     )?;
     let own_pk = SecpPublicKey::from_secret_key(secp, own_sk);
 
-    let sigs = HashMap::from([
-        (
-            PublicKey {
-                inner: own_pk,
-                compressed: true,
-            },
-            EcdsaSig::sighash_all(own_sig),
-        ),
-        (*counter_pubkey, EcdsaSig::sighash_all(adapted_sig)),
-    ]);
+    let mut sigs = HashMap::new();
+    sigs.insert(
+        PublicKey {
+            inner: own_pk,
+            compressed: true,
+        },
+        EcdsaSig::sighash_all(own_sig),
+    );
+    sigs.insert(*counter_pubkey, EcdsaSig::sighash_all(adapted_sig));
 
     descriptor
         .satisfy(&mut cet.input[0], sigs)
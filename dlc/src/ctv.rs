@@ -0,0 +1,366 @@
+//! Experimental CET commitment scheme using `OP_CHECKTEMPLATEVERIFY` (BIP119).
+//!
+//! Instead of exchanging one adaptor signature per CET and oracle outcome, the
+//! fund output is a taproot output whose script tree contains one leaf per
+//! adaptor point. Each leaf commits to the template of the CET to use for the
+//! outcome and can only be spent with a signature under the adaptor point,
+//! which only the oracle attestation can produce. Contiguous outcomes with the
+//! same payout are collapsed into a single CET.
+//!
+//! This only works on networks where CTV is active (e.g. some signets or
+//! regtest with a patched node).
+
+use bitcoin::consensus::Encodable;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::util::sighash::{Prevouts, SighashCache};
+use bitcoin::util::taproot::{LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::{
+    blockdata::{
+        opcodes,
+        script::{Builder, Script},
+        transaction::{Transaction, TxOut},
+    },
+    SchnorrSig, SchnorrSighashType, Witness,
+};
+use secp256k1_zkp::schnorr::Signature as SchnorrSignature;
+use secp256k1_zkp::{
+    KeyPair, Message, PublicKey, Secp256k1, Signing, Verification, XOnlyPublicKey,
+};
+
+use crate::prelude::*;
+use crate::{signatures_to_secret, Error, Payout};
+
+/// `OP_CHECKTEMPLATEVERIFY` redefines `OP_NOP4`.
+const OP_CHECKTEMPLATEVERIFY: opcodes::All = opcodes::all::OP_NOP4;
+
+/// The "Nothing Up My Sleeve" point from BIP341 used as taproot internal key so
+/// that the fund output can only be spent through the script tree.
+const NUMS_INTERNAL_KEY: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Information required to fund, execute and refund a CTV based DLC.
+#[derive(Clone, Debug)]
+pub struct CtvDlcInfo {
+    /// The spend info of the taproot fund output.
+    pub spend_info: TaprootSpendInfo,
+    /// The leaf script for each adaptor point, in the same order as the
+    /// adaptor points used to create the info.
+    pub cet_scripts: Vec<Script>,
+    /// The leaf script enabling the refund transaction.
+    pub refund_script: Script,
+}
+
+impl CtvDlcInfo {
+    /// Returns the script pubkey to use for the fund output.
+    pub fn script_pubkey(&self) -> Script {
+        Script::new_v1_p2tr_tweaked(self.spend_info.output_key())
+    }
+}
+
+/// Collapses contiguous identical payouts, returning the deduplicated payouts
+/// and, for each original payout, the index of its collapsed counterpart.
+pub fn compress_payouts(payouts: &[Payout]) -> (Vec<Payout>, Vec<usize>) {
+    let mut compressed: Vec<Payout> = Vec::new();
+    let mut indexes = Vec::with_capacity(payouts.len());
+
+    for payout in payouts {
+        if compressed.last() != Some(payout) {
+            compressed.push(payout.clone());
+        }
+        indexes.push(compressed.len() - 1);
+    }
+
+    (compressed, indexes)
+}
+
+/// Computes the BIP119 default template hash of the given transaction for the
+/// given input index.
+pub fn get_default_template_hash(tx: &Transaction, input_index: u32) -> sha256::Hash {
+    let mut engine = sha256::Hash::engine();
+    tx.version
+        .consensus_encode(&mut engine)
+        .expect("engines don't error");
+    tx.lock_time
+        .consensus_encode(&mut engine)
+        .expect("engines don't error");
+
+    if tx.input.iter().any(|x| !x.script_sig.is_empty()) {
+        let mut script_sigs_engine = sha256::Hash::engine();
+        for input in &tx.input {
+            input
+                .script_sig
+                .consensus_encode(&mut script_sigs_engine)
+                .expect("engines don't error");
+        }
+        engine.input(&sha256::Hash::from_engine(script_sigs_engine)[..]);
+    }
+
+    (tx.input.len() as u32)
+        .consensus_encode(&mut engine)
+        .expect("engines don't error");
+    let mut sequences_engine = sha256::Hash::engine();
+    for input in &tx.input {
+        input
+            .sequence
+            .consensus_encode(&mut sequences_engine)
+            .expect("engines don't error");
+    }
+    engine.input(&sha256::Hash::from_engine(sequences_engine)[..]);
+
+    (tx.output.len() as u32)
+        .consensus_encode(&mut engine)
+        .expect("engines don't error");
+    let mut outputs_engine = sha256::Hash::engine();
+    for output in &tx.output {
+        output
+            .consensus_encode(&mut outputs_engine)
+            .expect("engines don't error");
+    }
+    engine.input(&sha256::Hash::from_engine(outputs_engine)[..]);
+
+    input_index
+        .consensus_encode(&mut engine)
+        .expect("engines don't error");
+
+    sha256::Hash::from_engine(engine)
+}
+
+/// Creates a leaf script that can only be spent by the transaction with the
+/// given template hash and a signature valid for the given adaptor point.
+pub fn make_cet_leaf_script(template_hash: &sha256::Hash, adaptor_point: &PublicKey) -> Script {
+    Builder::new()
+        .push_slice(&template_hash[..])
+        .push_opcode(OP_CHECKTEMPLATEVERIFY)
+        .push_opcode(opcodes::all::OP_DROP)
+        .push_slice(&adaptor_point.x_only_public_key().0.serialize())
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .into_script()
+}
+
+/// Creates a leaf script that can only be spent by the transaction with the
+/// given template hash. As the template commits to the lock time of the
+/// transaction, no signature is required.
+pub fn make_refund_leaf_script(template_hash: &sha256::Hash) -> Script {
+    Builder::new()
+        .push_slice(&template_hash[..])
+        .push_opcode(OP_CHECKTEMPLATEVERIFY)
+        .into_script()
+}
+
+/// Creates the taproot tree for the fund output of a CTV based DLC. Each element
+/// of `adaptor_points` contains the index of the CET (within `cets`) to use for
+/// the outcome(s) represented by the associated adaptor point.
+pub fn create_ctv_dlc_info<C: Verification>(
+    secp: &Secp256k1<C>,
+    cets: &[Transaction],
+    refund: &Transaction,
+    adaptor_points: &[(usize, PublicKey)],
+) -> Result<CtvDlcInfo, Error> {
+    let template_hashes = cets
+        .iter()
+        .map(|x| get_default_template_hash(x, 0))
+        .collect::<Vec<_>>();
+
+    let cet_scripts = adaptor_points
+        .iter()
+        .map(|(cet_index, point)| {
+            let template_hash = template_hashes
+                .get(*cet_index)
+                .ok_or(Error::InvalidArgument)?;
+            Ok(make_cet_leaf_script(template_hash, point))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let refund_script = make_refund_leaf_script(&get_default_template_hash(refund, 0));
+
+    let builder = TaprootBuilder::with_huffman_tree(
+        cet_scripts
+            .iter()
+            .chain(core::iter::once(&refund_script))
+            .map(|x| (1, x.clone())),
+    )
+    .map_err(|_| Error::InvalidArgument)?;
+    let internal_key =
+        XOnlyPublicKey::from_slice(&NUMS_INTERNAL_KEY).expect("NUMS point to be valid");
+    let spend_info = builder
+        .finalize(secp, internal_key)
+        .map_err(|_| Error::InvalidArgument)?;
+
+    Ok(CtvDlcInfo {
+        spend_info,
+        cet_scripts,
+        refund_script,
+    })
+}
+
+/// Signs the given CET for the given leaf script using the oracle signatures
+/// and places the signature, the script and its control block on the witness
+/// stack.
+pub fn sign_ctv_cet<C: Signing>(
+    secp: &Secp256k1<C>,
+    cet: &mut Transaction,
+    info: &CtvDlcInfo,
+    leaf_script: &Script,
+    oracle_signatures: &[Vec<SchnorrSignature>],
+    fund_output: &TxOut,
+) -> Result<(), Error> {
+    let adaptor_secret = signatures_to_secret(oracle_signatures)?;
+    let key_pair = KeyPair::from_seckey_slice(secp, &adaptor_secret.secret_bytes())?;
+
+    let leaf_hash = TapLeafHash::from_script(leaf_script, LeafVersion::TapScript);
+    let sig_hash = SighashCache::new(&*cet).taproot_script_spend_signature_hash(
+        0,
+        &Prevouts::All(&[fund_output]),
+        leaf_hash,
+        SchnorrSighashType::Default,
+    )?;
+    let msg = Message::from_slice(&sig_hash[..])?;
+    let sig = SchnorrSig {
+        sig: secp.sign_schnorr_no_aux_rand(&msg, &key_pair),
+        hash_ty: SchnorrSighashType::Default,
+    };
+
+    cet.input[0].witness = Witness::from_vec(vec![
+        sig.to_vec(),
+        leaf_script.to_bytes(),
+        get_control_block(info, leaf_script)?,
+    ]);
+
+    Ok(())
+}
+
+/// Places the refund leaf script and its control block on the witness stack of
+/// the refund transaction.
+pub fn finalize_ctv_refund(refund: &mut Transaction, info: &CtvDlcInfo) -> Result<(), Error> {
+    refund.input[0].witness = Witness::from_vec(vec![
+        info.refund_script.to_bytes(),
+        get_control_block(info, &info.refund_script)?,
+    ]);
+
+    Ok(())
+}
+
+fn get_control_block(info: &CtvDlcInfo, script: &Script) -> Result<Vec<u8>, Error> {
+    Ok(info
+        .spend_info
+        .control_block(&(script.clone(), LeafVersion::TapScript))
+        .ok_or(Error::InvalidArgument)?
+        .serialize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_adaptor_point_from_oracle_info, secp_utils, OracleInfo};
+    use bitcoin::{OutPoint, PackedLockTime, Sequence, TxIn};
+    use secp256k1_zkp::rand::RngCore;
+
+    fn create_tx(value: u64, lock_time: u32) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime(lock_time),
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: Sequence(0xfffffffe),
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn compress_payouts_test() {
+        let payout = |offer: u64| Payout {
+            offer,
+            accept: 10 - offer,
+        };
+        let payouts = vec![payout(0), payout(0), payout(5), payout(10), payout(10)];
+
+        let (compressed, indexes) = compress_payouts(&payouts);
+
+        assert_eq!(vec![payout(0), payout(5), payout(10)], compressed);
+        assert_eq!(vec![0, 0, 1, 2, 2], indexes);
+    }
+
+    #[test]
+    fn template_hash_commits_to_outputs_and_lock_time_test() {
+        let tx = create_tx(1000, 10);
+        let hash = get_default_template_hash(&tx, 0);
+
+        let mut other = tx.clone();
+        other.input[0].previous_output.vout = 1;
+        assert_eq!(hash, get_default_template_hash(&other, 0));
+        assert_ne!(hash, get_default_template_hash(&create_tx(1001, 10), 0));
+        assert_ne!(hash, get_default_template_hash(&create_tx(1000, 11), 0));
+        assert_ne!(hash, get_default_template_hash(&tx, 1));
+    }
+
+    #[test]
+    fn sign_ctv_cet_test() {
+        let secp = Secp256k1::new();
+        let mut rng = secp256k1_zkp::rand::thread_rng();
+        let oracle_kp = KeyPair::new(&secp, &mut rng);
+        let mut sk_nonce = [0u8; 32];
+        rng.fill_bytes(&mut sk_nonce);
+        let nonce =
+            XOnlyPublicKey::from_keypair(&KeyPair::from_seckey_slice(&secp, &sk_nonce).unwrap()).0;
+        let msg = Message::from_hashed_data::<secp256k1_zkp::hashes::sha256::Hash>(&[1]);
+        let oracle_sig: SchnorrSignature =
+            secp_utils::schnorrsig_sign_with_nonce(&secp, &msg, &oracle_kp, &sk_nonce);
+        let oracle_infos = vec![OracleInfo {
+            public_key: oracle_kp.x_only_public_key().0,
+            nonces: vec![nonce],
+        }];
+        let adaptor_point =
+            get_adaptor_point_from_oracle_info(&secp, &oracle_infos, &[vec![msg]]).unwrap();
+
+        let cets = vec![create_tx(1000, 10), create_tx(2000, 10)];
+        let refund = create_tx(1500, 100);
+        let info = create_ctv_dlc_info(&secp, &cets, &refund, &[(1, adaptor_point)]).unwrap();
+        let fund_output = TxOut {
+            value: 3000,
+            script_pubkey: info.script_pubkey(),
+        };
+
+        let mut cet = cets[1].clone();
+        sign_ctv_cet(
+            &secp,
+            &mut cet,
+            &info,
+            &info.cet_scripts[0],
+            &[vec![oracle_sig]],
+            &fund_output,
+        )
+        .unwrap();
+
+        let witness = cet.input[0].witness.to_vec();
+        assert_eq!(3, witness.len());
+        assert_eq!(64, witness[0].len());
+        assert_eq!(info.cet_scripts[0].to_bytes(), witness[1]);
+
+        let leaf_hash = TapLeafHash::from_script(&info.cet_scripts[0], LeafVersion::TapScript);
+        let sig_hash = SighashCache::new(&cet)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[&fund_output]),
+                leaf_hash,
+                SchnorrSighashType::Default,
+            )
+            .unwrap();
+        secp.verify_schnorr(
+            &SchnorrSignature::from_slice(&witness[0]).unwrap(),
+            &Message::from_slice(&sig_hash[..]).unwrap(),
+            &adaptor_point.x_only_public_key().0,
+        )
+        .expect("Invalid CET signature");
+
+        let mut refund = refund;
+        finalize_ctv_refund(&mut refund, &info).unwrap();
+        assert_eq!(2, refund.input[0].witness.len());
+    }
+}
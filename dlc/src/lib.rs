@@ -2,7 +2,11 @@
 //! Library for creating, signing and verifying transactions for the
 //! Discreet Log Contract protocol.
 //!
+//! The `std` feature is enabled by default. Disabling it and enabling the
+//! `no-std` feature instead makes the crate usable in `no_std` environments
+//! providing an allocator, e.g. embedded signers.
 
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 // Coding conventions
 #![deny(non_upper_case_globals)]
 #![deny(non_camel_case_types)]
@@ -19,7 +23,17 @@ extern crate secp256k1_sys;
 extern crate secp256k1_zkp;
 #[cfg(feature = "serde")]
 extern crate serde;
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "no-std")]
+extern crate hashbrown;
 
+#[cfg(not(any(feature = "std", feature = "no-std")))]
+compile_error!("at least one of the `std` or `no-std` features must be enabled");
+
+use bitcoin::consensus::Encodable;
+use bitcoin::hashes::{sha256, sha256d, Hash};
 use bitcoin::secp256k1::Scalar;
 use bitcoin::{
     blockdata::{
@@ -27,8 +41,10 @@ use bitcoin::{
         script::{Builder, Script},
         transaction::{OutPoint, Transaction, TxIn, TxOut},
     },
-    PackedLockTime, Sequence, Witness,
+    EcdsaSighashType, PackedLockTime, Sequence, Witness,
 };
+use core::fmt;
+use prelude::*;
 use secp256k1_zkp::schnorr::Signature as SchnorrSignature;
 use secp256k1_zkp::{
     ecdsa::Signature, EcdsaAdaptorSignature, Message, PublicKey, Secp256k1, SecretKey,
@@ -36,12 +52,29 @@ use secp256k1_zkp::{
 };
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::fmt;
 
 pub mod channel;
+#[cfg(feature = "ctv")]
+pub mod ctv;
+pub mod multi_party;
+pub mod novation;
+pub mod ownership_proof;
 pub mod secp_utils;
 pub mod util;
 
+mod prelude {
+    #[cfg(not(feature = "std"))]
+    pub use alloc::{string::String, vec::Vec};
+    #[cfg(feature = "std")]
+    pub use std::{string::String, vec::Vec};
+
+    // Same map type as the one for which miniscript implements `Satisfier`.
+    #[cfg(feature = "no-std")]
+    pub use hashbrown::HashMap;
+    #[cfg(not(feature = "no-std"))]
+    pub use std::collections::HashMap;
+}
+
 /// Minimum value that can be included in a transaction output. Under this value,
 /// outputs are discarded
 /// See: https://github.com/discreetlogcontracts/dlcspecs/blob/master/Transactions.md#change-outputs
@@ -63,6 +96,9 @@ const CET_BASE_WEIGHT: usize = 500;
 /// See: <https://github.com/discreetlogcontracts/dlcspecs/blob/master/Transactions.md#fees>
 const TX_INPUT_BASE_WEIGHT: usize = 164;
 
+/// The denominator used to express payout output shares in basis points.
+const BASIS_POINTS: u64 = 10000;
+
 /// The witness size of a P2WPKH input
 /// See: <https://github.com/discreetlogcontracts/dlcspecs/blob/master/Transactions.md#fees>
 pub const P2WPKH_WITNESS_SIZE: usize = 107;
@@ -222,6 +258,7 @@ impl fmt::Display for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
@@ -233,6 +270,52 @@ impl std::error::Error for Error {
     }
 }
 
+/// An additional output receiving a share of a party's payout in CETs and refund
+/// transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct PayoutOutput {
+    /// The script pubkey receiving the share of the payout
+    pub script_pubkey: Script,
+    /// Id used to order CET outputs
+    pub serial_id: u64,
+    /// The share of the party payout sent to this output, in basis points
+    pub share_bps: u16,
+}
+
+/// Checks that the shares of the given additional payout outputs are non zero
+/// and do not exceed the full payout, that their script pubkeys are standard
+/// and that their serial ids are unique and differ from `payout_serial_id`, so
+/// that both parties build the same transactions from them.
+pub fn validate_payout_outputs(
+    outputs: &[PayoutOutput],
+    payout_serial_id: u64,
+) -> Result<(), Error> {
+    let mut total: u64 = 0;
+    let mut serial_ids = Vec::with_capacity(outputs.len() + 1);
+    serial_ids.push(payout_serial_id);
+    for output in outputs {
+        if output.share_bps == 0
+            || !util::is_standard_payout_script(&output.script_pubkey)
+            || serial_ids.contains(&output.serial_id)
+        {
+            return Err(Error::InvalidArgument);
+        }
+        serial_ids.push(output.serial_id);
+        total += output.share_bps as u64;
+    }
+
+    if total > BASIS_POINTS {
+        return Err(Error::InvalidArgument);
+    }
+
+    Ok(())
+}
+
 /// Contains the parameters required for creating DLC transactions for a single
 /// party. Specifically these are the common fields between Offer and Accept
 /// messages.
@@ -259,6 +342,10 @@ pub struct PartyParams {
     pub input_amount: u64,
     /// The collateral put in the contract by the party
     pub collateral: u64,
+    /// Additional outputs receiving a share of the party payout, the remainder
+    /// being sent to `payout_script_pubkey`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub additional_payout_outputs: Vec<PayoutOutput>,
 }
 
 impl PartyParams {
@@ -271,6 +358,25 @@ impl PartyParams {
         &self,
         fee_rate_per_vb: u64,
         extra_fee: u64,
+    ) -> Result<(TxOut, u64, u64), Error> {
+        // Base weights (nLocktime, nVersion, funding input ...) are distributed
+        // among parties independently of inputs contributed and output types
+        self.get_change_output_and_fees_with_base_weights(
+            fee_rate_per_vb,
+            extra_fee,
+            FUND_TX_BASE_WEIGHT / 2,
+            CET_BASE_WEIGHT / 2,
+        )
+    }
+
+    /// Same as [`PartyParams::get_change_output_and_fees`] but using the given
+    /// share of the fund transaction and CET base weights for this party.
+    pub(crate) fn get_change_output_and_fees_with_base_weights(
+        &self,
+        fee_rate_per_vb: u64,
+        extra_fee: u64,
+        this_party_fund_base_weight: usize,
+        this_party_cet_base_weight: usize,
     ) -> Result<(TxOut, u64, u64), Error> {
         let mut inputs_weight: usize = 0;
 
@@ -292,10 +398,6 @@ impl PartyParams {
         // Change size is scaled by 4 from vBytes to weight units
         let change_weight = change_size.checked_mul(4).ok_or(Error::InvalidArgument)?;
 
-        // Base weight (nLocktime, nVersion, ...) is distributed among parties
-        // independently of inputs contributed
-        let this_party_fund_base_weight = FUND_TX_BASE_WEIGHT / 2;
-
         let total_fund_weight = checked_add!(
             this_party_fund_base_weight,
             inputs_weight,
@@ -304,17 +406,28 @@ impl PartyParams {
         )?;
         let fund_fee = util::weight_to_fee(total_fund_weight, fee_rate_per_vb)?;
 
-        // Base weight (nLocktime, nVersion, funding input ...) is distributed
-        // among parties independently of output types
-        let this_party_cet_base_weight = CET_BASE_WEIGHT / 2;
-
         // size of the payout script pubkey scaled by 4 from vBytes to weight units
         let output_spk_weight = self
             .payout_script_pubkey
             .len()
             .checked_mul(4)
             .ok_or(Error::InvalidArgument)?;
-        let total_cet_weight = checked_add!(this_party_cet_base_weight, output_spk_weight)?;
+        let mut additional_outputs_weight: usize = 0;
+        for output in &self.additional_payout_outputs {
+            // Value size + script length var_int + ouput script pubkey size
+            let output_size = 8
+                + util::compute_var_int_prefix_size(output.script_pubkey.len())
+                + output.script_pubkey.len();
+            additional_outputs_weight = checked_add!(
+                additional_outputs_weight,
+                output_size.checked_mul(4).ok_or(Error::InvalidArgument)?
+            )?;
+        }
+        let total_cet_weight = checked_add!(
+            this_party_cet_base_weight,
+            output_spk_weight,
+            additional_outputs_weight
+        )?;
         let cet_or_refund_fee = util::weight_to_fee(total_cet_weight, fee_rate_per_vb)?;
         let required_input_funds =
             checked_add!(self.collateral, fund_fee, cet_or_refund_fee, extra_fee)?;
@@ -330,7 +443,45 @@ impl PartyParams {
         Ok((change_output, fund_fee, cet_or_refund_fee))
     }
 
-    fn get_unsigned_tx_inputs_and_serial_ids(&self, sequence: Sequence) -> (Vec<TxIn>, Vec<u64>) {
+    /// Checks that the additional payout outputs of the party are valid, see
+    /// [`crate::validate_payout_outputs`].
+    pub fn validate_payout_outputs(&self) -> Result<(), Error> {
+        validate_payout_outputs(&self.additional_payout_outputs, self.payout_serial_id)
+    }
+
+    /// Returns the outputs paying `amount` to the party together with their
+    /// serial ids, splitting it across the additional payout outputs if any.
+    pub(crate) fn get_payout_outputs(&self, amount: u64) -> (Vec<TxOut>, Vec<u64>) {
+        let mut outputs = Vec::with_capacity(self.additional_payout_outputs.len() + 1);
+        let mut serial_ids = Vec::with_capacity(self.additional_payout_outputs.len() + 1);
+        let mut remainder = amount;
+
+        for output in &self.additional_payout_outputs {
+            let value = (amount as u128 * output.share_bps as u128 / BASIS_POINTS as u128) as u64;
+            remainder -= value;
+            outputs.push(TxOut {
+                value,
+                script_pubkey: output.script_pubkey.clone(),
+            });
+            serial_ids.push(output.serial_id);
+        }
+
+        outputs.insert(
+            0,
+            TxOut {
+                value: remainder,
+                script_pubkey: self.payout_script_pubkey.clone(),
+            },
+        );
+        serial_ids.insert(0, self.payout_serial_id);
+
+        (outputs, serial_ids)
+    }
+
+    pub(crate) fn get_unsigned_tx_inputs_and_serial_ids(
+        &self,
+        sequence: Sequence,
+    ) -> (Vec<TxIn>, Vec<u64>) {
         let mut tx_ins = Vec::with_capacity(self.inputs.len());
         let mut serial_ids = Vec::with_capacity(self.inputs.len());
 
@@ -487,25 +638,16 @@ pub(crate) fn create_cets_and_refund_tx(
         sequence: cet_nsequence.unwrap_or_else(|| util::get_sequence(cet_lock_time)),
     };
 
-    let cets = create_cets(
+    let cets = create_party_cets(
         &cet_input,
-        &offer_params.payout_script_pubkey,
-        offer_params.payout_serial_id,
-        &accept_params.payout_script_pubkey,
-        accept_params.payout_serial_id,
+        offer_params,
+        accept_params,
         payouts,
         cet_lock_time,
-    );
-
-    let offer_refund_output = TxOut {
-        value: offer_params.collateral,
-        script_pubkey: offer_params.payout_script_pubkey.clone(),
-    };
+    )?;
 
-    let accept_refund_ouput = TxOut {
-        value: accept_params.collateral,
-        script_pubkey: accept_params.payout_script_pubkey.clone(),
-    };
+    let (offer_refund_outputs, _) = offer_params.get_payout_outputs(offer_params.collateral);
+    let (accept_refund_outputs, _) = accept_params.get_payout_outputs(accept_params.collateral);
 
     let refund_input = TxIn {
         previous_output: prev_outpoint,
@@ -514,16 +656,54 @@ pub(crate) fn create_cets_and_refund_tx(
         sequence: util::ENABLE_LOCKTIME,
     };
 
-    let refund_tx = create_refund_transaction(
-        offer_refund_output,
-        accept_refund_ouput,
-        refund_input,
-        refund_lock_time,
-    );
+    let refund_tx = Transaction {
+        version: TX_VERSION,
+        lock_time: PackedLockTime(refund_lock_time),
+        input: vec![refund_input],
+        output: util::discard_dust(
+            [offer_refund_outputs, accept_refund_outputs].concat(),
+            DUST_LIMIT,
+        ),
+    };
 
     Ok((cets, refund_tx))
 }
 
+/// Create a set of contract execution transactions for each provided outcome,
+/// splitting the payout of each party across its payout outputs.
+pub fn create_party_cets(
+    fund_tx_input: &TxIn,
+    offer_params: &PartyParams,
+    accept_params: &PartyParams,
+    payouts: &[Payout],
+    lock_time: u32,
+) -> Result<Vec<Transaction>, Error> {
+    offer_params.validate_payout_outputs()?;
+    accept_params.validate_payout_outputs()?;
+
+    Ok(payouts
+        .iter()
+        .map(|payout| {
+            let (offer_outputs, offer_serial_ids) = offer_params.get_payout_outputs(payout.offer);
+            let (accept_outputs, accept_serial_ids) =
+                accept_params.get_payout_outputs(payout.accept);
+            // Accept outputs come first so that they are placed before the
+            // offer ones in case of identical serial ids, as in `create_cet`.
+            let output = util::order_by_serial_ids(
+                [accept_outputs, offer_outputs].concat(),
+                &[accept_serial_ids, offer_serial_ids].concat(),
+            );
+
+            Transaction {
+                version: TX_VERSION,
+                lock_time: PackedLockTime(lock_time),
+                input: vec![fund_tx_input.clone()],
+                output: util::discard_dust(output, DUST_LIMIT),
+            }
+        })
+        .collect())
+}
+
 /// Create a contract execution transaction
 pub fn create_cet(
     offer_output: TxOut,
@@ -702,6 +882,117 @@ pub fn get_adaptor_point_from_oracle_info<C: Verification>(
     )?)
 }
 
+/// Caches the parts of the BIP143 signature hash that are shared by all the
+/// CETs spending a given fund output, so that only the outputs of each CET
+/// need to be hashed when computing their signature hash.
+#[derive(Clone)]
+pub struct CetSighashCache {
+    version: i32,
+    outpoint: OutPoint,
+    sequence: Sequence,
+    funding_script_pubkey: Script,
+    fund_output_value: u64,
+    prefix_engine: sha256::HashEngine,
+}
+
+impl CetSighashCache {
+    /// Creates a cache for CETs spending the same fund output as the given CET.
+    pub fn new(
+        cet: &Transaction,
+        funding_script_pubkey: &Script,
+        fund_output_value: u64,
+    ) -> Result<Self, Error> {
+        if cet.input.len() != 1 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let outpoint = cet.input[0].previous_output;
+        let sequence = cet.input[0].sequence;
+
+        let mut prevouts_engine = sha256d::Hash::engine();
+        outpoint
+            .consensus_encode(&mut prevouts_engine)
+            .expect("Error writing hash.");
+        let mut sequences_engine = sha256d::Hash::engine();
+        sequence
+            .consensus_encode(&mut sequences_engine)
+            .expect("Error writing hash.");
+
+        let mut prefix_engine = sha256d::Hash::engine();
+        cet.version
+            .consensus_encode(&mut prefix_engine)
+            .and_then(|_| {
+                sha256d::Hash::from_engine(prevouts_engine).consensus_encode(&mut prefix_engine)
+            })
+            .and_then(|_| {
+                sha256d::Hash::from_engine(sequences_engine).consensus_encode(&mut prefix_engine)
+            })
+            .and_then(|_| outpoint.consensus_encode(&mut prefix_engine))
+            .and_then(|_| funding_script_pubkey.consensus_encode(&mut prefix_engine))
+            .and_then(|_| fund_output_value.consensus_encode(&mut prefix_engine))
+            .and_then(|_| sequence.consensus_encode(&mut prefix_engine))
+            .expect("Error writing hash.");
+
+        Ok(CetSighashCache {
+            version: cet.version,
+            outpoint,
+            sequence,
+            funding_script_pubkey: funding_script_pubkey.clone(),
+            fund_output_value,
+            prefix_engine,
+        })
+    }
+
+    /// Returns the SIGHASH_ALL signature hash of the given CET as a Message
+    /// instance. Falls back to computing the full signature hash if the CET
+    /// does not spend the cached fund output.
+    pub fn get_sig_hash_msg(&self, cet: &Transaction) -> Result<Message, Error> {
+        if cet.version != self.version
+            || cet.input.len() != 1
+            || cet.input[0].previous_output != self.outpoint
+            || cet.input[0].sequence != self.sequence
+        {
+            return util::get_sig_hash_msg(
+                cet,
+                0,
+                &self.funding_script_pubkey,
+                self.fund_output_value,
+            );
+        }
+
+        let mut outputs_engine = sha256d::Hash::engine();
+        for output in &cet.output {
+            output
+                .consensus_encode(&mut outputs_engine)
+                .expect("Error writing hash.");
+        }
+
+        let mut engine = self.prefix_engine.clone();
+        sha256d::Hash::from_engine(outputs_engine)
+            .consensus_encode(&mut engine)
+            .and_then(|_| cet.lock_time.consensus_encode(&mut engine))
+            .and_then(|_| EcdsaSighashType::All.to_u32().consensus_encode(&mut engine))
+            .expect("Error writing hash.");
+
+        Ok(Message::from_slice(&sha256d::Hash::from_engine(engine)[..]).unwrap())
+    }
+}
+
+/// Computes the signature hashes of the given CETs, which are all expected to
+/// spend the same fund output.
+pub fn get_cets_sig_hash_msgs(
+    cets: &[Transaction],
+    funding_script_pubkey: &Script,
+    fund_output_value: u64,
+) -> Result<Vec<Message>, Error> {
+    let cache = match cets.first() {
+        Some(cet) => CetSighashCache::new(cet, funding_script_pubkey, fund_output_value)?,
+        None => return Ok(Vec::new()),
+    };
+
+    cets.iter().map(|cet| cache.get_sig_hash_msg(cet)).collect()
+}
+
 /// Create an adaptor signature for the given cet using the provided adaptor point.
 pub fn create_cet_adaptor_sig_from_point<C: secp256k1_zkp::Signing>(
     secp: &secp256k1_zkp::Secp256k1<C>,
@@ -713,14 +1004,25 @@ pub fn create_cet_adaptor_sig_from_point<C: secp256k1_zkp::Signing>(
 ) -> Result<EcdsaAdaptorSignature, Error> {
     let sig_hash = util::get_sig_hash_msg(cet, 0, funding_script_pubkey, fund_output_value)?;
 
-    Ok(secp256k1_zkp::EcdsaAdaptorSignature::encrypt(
+    Ok(create_cet_adaptor_sig_from_sig_hash(
         secp,
         &sig_hash,
-        funding_sk,
         adaptor_point,
+        funding_sk,
     ))
 }
 
+/// Create an adaptor signature for a cet with the given signature hash using
+/// the provided adaptor point.
+pub fn create_cet_adaptor_sig_from_sig_hash<C: secp256k1_zkp::Signing>(
+    secp: &secp256k1_zkp::Secp256k1<C>,
+    sig_hash: &Message,
+    adaptor_point: &PublicKey,
+    funding_sk: &SecretKey,
+) -> EcdsaAdaptorSignature {
+    secp256k1_zkp::EcdsaAdaptorSignature::encrypt(secp, sig_hash, funding_sk, adaptor_point)
+}
+
 /// Create an adaptor signature for the given cet using the provided oracle infos.
 pub fn create_cet_adaptor_sig_from_oracle_info(
     secp: &secp256k1_zkp::Secp256k1<secp256k1_zkp::All>,
@@ -750,17 +1052,21 @@ pub fn create_cet_adaptor_sigs_from_points<C: secp256k1_zkp::Signing>(
     funding_script_pubkey: &Script,
     fund_output_value: u64,
 ) -> Result<Vec<EcdsaAdaptorSignature>, Error> {
+    let cache = match inputs.first() {
+        Some((cet, _)) => CetSighashCache::new(cet, funding_script_pubkey, fund_output_value)?,
+        None => return Ok(Vec::new()),
+    };
+
     inputs
         .iter()
         .map(|(cet, adaptor_point)| {
-            create_cet_adaptor_sig_from_point(
+            let sig_hash = cache.get_sig_hash_msg(cet)?;
+            Ok(create_cet_adaptor_sig_from_sig_hash(
                 secp,
-                cet,
+                &sig_hash,
                 adaptor_point,
                 funding_sk,
-                funding_script_pubkey,
-                fund_output_value,
-            )
+            ))
         })
         .collect()
 }
@@ -779,23 +1085,26 @@ pub fn create_cet_adaptor_sigs_from_oracle_info(
         return Err(Error::InvalidArgument);
     }
 
-    cets.iter()
+    let sig_hashes = get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
+
+    sig_hashes
+        .iter()
         .zip(msgs.iter())
-        .map(|(cet, msg)| {
-            create_cet_adaptor_sig_from_oracle_info(
+        .map(|(sig_hash, msg)| {
+            let adaptor_point = get_adaptor_point_from_oracle_info(secp, oracle_infos, msg)?;
+            Ok(create_cet_adaptor_sig_from_sig_hash(
                 secp,
-                cet,
-                oracle_infos,
+                sig_hash,
+                &adaptor_point,
                 funding_sk,
-                funding_script_pubkey,
-                fund_output_value,
-                msg,
-            )
+            ))
         })
         .collect()
 }
 
-fn signatures_to_secret(signatures: &[Vec<SchnorrSignature>]) -> Result<SecretKey, Error> {
+pub(crate) fn signatures_to_secret(
+    signatures: &[Vec<SchnorrSignature>],
+) -> Result<SecretKey, Error> {
     let s_values = signatures
         .iter()
         .flatten()
@@ -856,7 +1165,19 @@ pub fn verify_cet_adaptor_sig_from_point(
     total_collateral: u64,
 ) -> Result<(), Error> {
     let sig_hash = util::get_sig_hash_msg(cet, 0, funding_script_pubkey, total_collateral)?;
-    adaptor_sig.verify(secp, &sig_hash, pubkey, adaptor_point)?;
+    verify_cet_adaptor_sig_from_sig_hash(secp, adaptor_sig, &sig_hash, adaptor_point, pubkey)
+}
+
+/// Verify that a given adaptor signature for a cet with the given signature
+/// hash is valid with respect to an adaptor point.
+pub fn verify_cet_adaptor_sig_from_sig_hash(
+    secp: &Secp256k1<secp256k1_zkp::All>,
+    adaptor_sig: &EcdsaAdaptorSignature,
+    sig_hash: &Message,
+    adaptor_point: &PublicKey,
+    pubkey: &PublicKey,
+) -> Result<(), Error> {
+    adaptor_sig.verify(secp, sig_hash, pubkey, adaptor_point)?;
     Ok(())
 }
 
@@ -1212,6 +1533,7 @@ mod tests {
                     },
                     serial_id,
                 }],
+                additional_payout_outputs: Vec::new(),
             },
             fund_privkey,
         )
@@ -1281,6 +1603,178 @@ mod tests {
         assert!(dlc_txs.cets.iter().all(|x| x.lock_time.0 == 10));
     }
 
+    #[test]
+    fn create_dlc_transactions_with_split_payouts() {
+        // Arrange
+        let secp = Secp256k1::new();
+        let mut rng = secp256k1_zkp::rand::thread_rng();
+        let (mut offer_party_params, _) = get_party_params(1000000000, 100000000, Some(1));
+        let (accept_party_params, _) = get_party_params(1000000000, 100000000, Some(2));
+        let cold_spk = get_p2wpkh_script_pubkey(&secp, &mut rng);
+        offer_party_params.additional_payout_outputs = vec![PayoutOutput {
+            script_pubkey: cold_spk.clone(),
+            serial_id: 3,
+            share_bps: 7500,
+        }];
+
+        // Act
+        let dlc_txs = create_dlc_transactions(
+            &offer_party_params,
+            &accept_party_params,
+            &payouts(),
+            100,
+            4,
+            10,
+            10,
+            0,
+        )
+        .unwrap();
+
+        // Assert
+        let cet = &dlc_txs.cets[0];
+        assert_eq!(2, cet.output.len());
+        assert_eq!(
+            offer_party_params.payout_script_pubkey,
+            cet.output[0].script_pubkey
+        );
+        assert_eq!(50000000, cet.output[0].value);
+        assert_eq!(cold_spk, cet.output[1].script_pubkey);
+        assert_eq!(150000000, cet.output[1].value);
+        assert_eq!(1, dlc_txs.cets[1].output.len());
+        assert_eq!(3, dlc_txs.refund.output.len());
+        assert_eq!(
+            200000000,
+            dlc_txs.refund.output.iter().map(|x| x.value).sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn create_dlc_transactions_invalid_payout_shares_error() {
+        // Arrange
+        let (mut offer_party_params, _) = get_party_params(1000000000, 100000000, None);
+        let (accept_party_params, _) = get_party_params(1000000000, 100000000, None);
+        offer_party_params.additional_payout_outputs = vec![
+            PayoutOutput {
+                script_pubkey: offer_party_params.payout_script_pubkey.clone(),
+                serial_id: 3,
+                share_bps: 6000,
+            },
+            PayoutOutput {
+                script_pubkey: offer_party_params.payout_script_pubkey.clone(),
+                serial_id: 4,
+                share_bps: 6000,
+            },
+        ];
+
+        // Act
+        let res = create_dlc_transactions(
+            &offer_party_params,
+            &accept_party_params,
+            &payouts(),
+            100,
+            4,
+            10,
+            10,
+            0,
+        );
+
+        // Assert
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn validate_payout_outputs_rejects_invalid_outputs() {
+        let (mut party_params, _) = get_party_params(1000000000, 100000000, None);
+        let output = PayoutOutput {
+            script_pubkey: party_params.payout_script_pubkey.clone(),
+            serial_id: 3,
+            share_bps: 5000,
+        };
+        party_params.additional_payout_outputs = vec![output.clone()];
+        party_params
+            .validate_payout_outputs()
+            .expect("valid outputs to be accepted");
+
+        party_params.additional_payout_outputs = vec![PayoutOutput {
+            serial_id: party_params.payout_serial_id,
+            ..output.clone()
+        }];
+        party_params
+            .validate_payout_outputs()
+            .expect_err("duplicate serial ids to be rejected");
+
+        party_params.additional_payout_outputs = vec![PayoutOutput {
+            script_pubkey: Script::new_op_return(&[1, 2, 3]),
+            ..output.clone()
+        }];
+        party_params
+            .validate_payout_outputs()
+            .expect_err("non standard script pubkeys to be rejected");
+
+        party_params.additional_payout_outputs = vec![PayoutOutput {
+            share_bps: 0,
+            ..output
+        }];
+        party_params
+            .validate_payout_outputs()
+            .expect_err("empty shares to be rejected");
+    }
+
+    #[test]
+    fn cet_sighash_cache_matches_full_sighash() {
+        // Arrange
+        let (offer_party_params, _) = get_party_params(1000000000, 100000000, None);
+        let (accept_party_params, _) = get_party_params(1000000000, 100000000, None);
+
+        let dlc_txs = create_dlc_transactions(
+            &offer_party_params,
+            &accept_party_params,
+            &payouts(),
+            100,
+            4,
+            10,
+            10,
+            0,
+        )
+        .unwrap();
+        let fund_output_value = dlc_txs.get_fund_output().value;
+        let mut other_tx = dlc_txs.refund.clone();
+        other_tx.input[0].previous_output.vout += 1;
+
+        // Act
+        let sig_hashes = get_cets_sig_hash_msgs(
+            &dlc_txs.cets,
+            &dlc_txs.funding_script_pubkey,
+            fund_output_value,
+        )
+        .unwrap();
+        let cache = CetSighashCache::new(
+            &dlc_txs.cets[0],
+            &dlc_txs.funding_script_pubkey,
+            fund_output_value,
+        )
+        .unwrap();
+
+        // Assert
+        for (cet, sig_hash) in dlc_txs.cets.iter().zip(sig_hashes.iter()) {
+            assert_eq!(
+                util::get_sig_hash_msg(cet, 0, &dlc_txs.funding_script_pubkey, fund_output_value)
+                    .unwrap(),
+                *sig_hash
+            );
+        }
+        assert_eq!(
+            util::get_sig_hash_msg(
+                &other_tx,
+                0,
+                &dlc_txs.funding_script_pubkey,
+                fund_output_value
+            )
+            .unwrap(),
+            cache.get_sig_hash_msg(&other_tx).unwrap()
+        );
+    }
+
     #[test]
     fn create_cet_adaptor_sig_is_valid() {
         // Arrange
@@ -0,0 +1,504 @@
+//! Module for creating, signing and verifying the transactions of DLC contracts
+//! involving more than two participants. The fund output is locked to an n-of-n
+//! multisig script and each CET pays out to every party.
+//! Adaptor signatures for CETs are computed in the same way as for two party
+//! contracts, so [`crate::create_cet_adaptor_sigs_from_oracle_info`] can be used
+//! by each party to generate theirs.
+
+use bitcoin::{
+    blockdata::{
+        opcodes,
+        script::{Builder, Script},
+        transaction::{OutPoint, Transaction, TxIn, TxOut},
+    },
+    EcdsaSighashType, PackedLockTime, Witness,
+};
+use secp256k1_zkp::{
+    ecdsa::Signature, schnorr::Signature as SchnorrSignature, All, EcdsaAdaptorSignature, Message,
+    PublicKey, Secp256k1, SecretKey, Signing,
+};
+
+use crate::prelude::*;
+use crate::{
+    get_adaptor_point_from_oracle_info, get_cets_sig_hash_msgs, signatures_to_secret, util,
+    verify_cet_adaptor_sig_from_sig_hash, DlcTransactions, Error, OracleInfo, PartyParams,
+    CET_BASE_WEIGHT, DUST_LIMIT, FUND_TX_BASE_WEIGHT, TX_VERSION,
+};
+
+/// The maximum number of parties supported, bound by the number of public keys
+/// allowed in a standard `OP_CHECKMULTISIG` script.
+pub const MAX_PARTIES: usize = 20;
+
+/// Returns the weight of a CET excluding payout outputs for a contract with
+/// `nb_parties` participants. The two party CET witness (220 bytes) included in
+/// `CET_BASE_WEIGHT` is replaced with the n-of-n one:
+/// * witness item count -> 1
+/// * empty item for the `OP_CHECKMULTISIG` bug -> 1
+/// * n signatures -> 73 * n
+/// * script length -> var_int
+/// * script -> 3 + 34 * n
+pub fn get_cet_base_weight(nb_parties: usize) -> usize {
+    let script_size = 3 + 34 * nb_parties;
+    let witness_size =
+        2 + 73 * nb_parties + util::compute_var_int_prefix_size(script_size) + script_size;
+    CET_BASE_WEIGHT - 220 + witness_size
+}
+
+/// Create the n-of-n multisig redeem script for the funding output. Public keys
+/// are sorted so that all parties derive the same script.
+pub fn make_funding_redeemscript(pubkeys: &[PublicKey]) -> Result<Script, Error> {
+    if pubkeys.len() < 2 || pubkeys.len() > MAX_PARTIES {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut sorted = pubkeys.to_vec();
+    sorted.sort();
+
+    let builder = sorted
+        .iter()
+        .fold(Builder::new().push_int(pubkeys.len() as i64), |b, pk| {
+            b.push_slice(&pk.serialize())
+        });
+
+    Ok(builder
+        .push_int(pubkeys.len() as i64)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .into_script())
+}
+
+/// Create the transactions for a DLC contract between `parties`. Each element of
+/// `payouts` contains the amount to be paid to each party for a given outcome,
+/// in the same order as `parties`.
+pub fn create_dlc_transactions(
+    parties: &[PartyParams],
+    payouts: &[Vec<u64>],
+    refund_lock_time: u32,
+    fee_rate_per_vb: u64,
+    fund_lock_time: u32,
+    cet_lock_time: u32,
+    fund_output_serial_id: u64,
+) -> Result<DlcTransactions, Error> {
+    let nb_parties = parties.len();
+    if nb_parties < 2 || nb_parties > MAX_PARTIES {
+        return Err(Error::InvalidArgument);
+    }
+
+    // Base weights are distributed equally among parties, rounding up so that
+    // the total fee is never lower than required.
+    let fund_base_weight = (FUND_TX_BASE_WEIGHT + nb_parties - 1) / nb_parties;
+    let cet_base_weight = (get_cet_base_weight(nb_parties) + nb_parties - 1) / nb_parties;
+
+    let mut total_collateral: u64 = 0;
+    let mut fund_output_value: u64 = 0;
+    let mut outputs = Vec::with_capacity(nb_parties + 1);
+    let mut output_serial_ids = Vec::with_capacity(nb_parties + 1);
+    let mut inputs = Vec::new();
+    let mut input_serial_ids = Vec::new();
+    let fund_sequence = util::get_sequence(fund_lock_time);
+
+    for party in parties {
+        party.validate_payout_outputs()?;
+        let (change_output, _, cet_fee) = party.get_change_output_and_fees_with_base_weights(
+            fee_rate_per_vb,
+            0,
+            fund_base_weight,
+            cet_base_weight,
+        )?;
+        total_collateral = total_collateral
+            .checked_add(party.collateral)
+            .ok_or(Error::InvalidArgument)?;
+        fund_output_value = fund_output_value
+            .checked_add(party.collateral)
+            .and_then(|x| x.checked_add(cet_fee))
+            .ok_or(Error::InvalidArgument)?;
+        outputs.push(change_output);
+        output_serial_ids.push(party.change_serial_id);
+        let (tx_ins, serial_ids) = party.get_unsigned_tx_inputs_and_serial_ids(fund_sequence);
+        inputs.extend(tx_ins);
+        input_serial_ids.extend(serial_ids);
+    }
+
+    let funding_script_pubkey =
+        make_funding_redeemscript(&parties.iter().map(|x| x.fund_pubkey).collect::<Vec<_>>())?;
+
+    outputs.push(TxOut {
+        value: fund_output_value,
+        script_pubkey: funding_script_pubkey.to_v0_p2wsh(),
+    });
+    output_serial_ids.push(fund_output_serial_id);
+
+    let fund = Transaction {
+        version: TX_VERSION,
+        lock_time: PackedLockTime(fund_lock_time),
+        input: util::order_by_serial_ids(inputs, &input_serial_ids),
+        output: util::discard_dust(
+            util::order_by_serial_ids(outputs, &output_serial_ids),
+            DUST_LIMIT,
+        ),
+    };
+
+    let fund_output_index = fund
+        .output
+        .iter()
+        .position(|x| x.script_pubkey == funding_script_pubkey.to_v0_p2wsh())
+        .ok_or(Error::InvalidArgument)?;
+    let prev_outpoint = OutPoint {
+        txid: fund.txid(),
+        vout: fund_output_index as u32,
+    };
+
+    let cet_input = TxIn {
+        previous_output: prev_outpoint,
+        witness: Witness::default(),
+        script_sig: Script::default(),
+        sequence: util::get_sequence(cet_lock_time),
+    };
+
+    let cets = payouts
+        .iter()
+        .map(|payout| {
+            if payout.len() != nb_parties {
+                return Err(Error::InvalidArgument);
+            }
+            let total = payout
+                .iter()
+                .try_fold(0u64, |acc, x| acc.checked_add(*x))
+                .ok_or(Error::InvalidArgument)?;
+            if total != total_collateral {
+                return Err(Error::InvalidArgument);
+            }
+            Ok(create_transaction(
+                parties,
+                payout,
+                cet_input.clone(),
+                cet_lock_time,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let refund_input = TxIn {
+        previous_output: prev_outpoint,
+        witness: Witness::default(),
+        script_sig: Script::default(),
+        sequence: util::ENABLE_LOCKTIME,
+    };
+
+    let refund = create_transaction(
+        parties,
+        &parties.iter().map(|x| x.collateral).collect::<Vec<_>>(),
+        refund_input,
+        refund_lock_time,
+    );
+
+    Ok(DlcTransactions {
+        fund,
+        cets,
+        refund,
+        funding_script_pubkey,
+    })
+}
+
+fn create_transaction(
+    parties: &[PartyParams],
+    payout: &[u64],
+    input: TxIn,
+    lock_time: u32,
+) -> Transaction {
+    let mut outputs = Vec::with_capacity(parties.len());
+    let mut serial_ids = Vec::with_capacity(parties.len());
+    for (party, value) in parties.iter().zip(payout.iter()) {
+        let (party_outputs, party_serial_ids) = party.get_payout_outputs(*value);
+        outputs.extend(party_outputs);
+        serial_ids.extend(party_serial_ids);
+    }
+
+    Transaction {
+        version: TX_VERSION,
+        lock_time: PackedLockTime(lock_time),
+        input: vec![input],
+        output: util::discard_dust(util::order_by_serial_ids(outputs, &serial_ids), DUST_LIMIT),
+    }
+}
+
+/// Generates a signature for the given n-of-n multisig input using the given
+/// secret key, and places it together with the provided signatures of the other
+/// parties on the input's witness stack, ordered by public key.
+pub fn sign_multi_sig_input<C: Signing>(
+    secp: &Secp256k1<C>,
+    transaction: &mut Transaction,
+    other_sigs: &[(PublicKey, Signature)],
+    sk: &SecretKey,
+    script_pubkey: &Script,
+    input_value: u64,
+    input_index: usize,
+) -> Result<(), Error> {
+    let own_sig = util::get_raw_sig_for_tx_input(
+        secp,
+        transaction,
+        input_index,
+        script_pubkey,
+        input_value,
+        sk,
+    )?;
+
+    let mut sigs = other_sigs.to_vec();
+    sigs.push((PublicKey::from_secret_key(secp, sk), own_sig));
+    sigs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut witness = Vec::with_capacity(sigs.len() + 2);
+    witness.push(Vec::new());
+    witness.extend(
+        sigs.iter()
+            .map(|(_, sig)| util::finalize_sig(sig, EcdsaSighashType::All)),
+    );
+    witness.push(script_pubkey.to_bytes());
+
+    transaction.input[input_index].witness = Witness::from_vec(witness);
+
+    Ok(())
+}
+
+/// Sign the given cet using own private key, decrypt the adaptor signatures of
+/// the other parties using the oracle signatures and place all signatures and
+/// the funding multisig script on the witness stack.
+pub fn sign_cet<C: Signing>(
+    secp: &Secp256k1<C>,
+    cet: &mut Transaction,
+    adaptor_signatures: &[(PublicKey, EcdsaAdaptorSignature)],
+    oracle_signatures: &[Vec<SchnorrSignature>],
+    funding_sk: &SecretKey,
+    funding_script_pubkey: &Script,
+    fund_output_value: u64,
+) -> Result<(), Error> {
+    let adaptor_secret = signatures_to_secret(oracle_signatures)?;
+    let other_sigs = adaptor_signatures
+        .iter()
+        .map(|(pk, sig)| Ok((*pk, sig.decrypt(&adaptor_secret)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    sign_multi_sig_input(
+        secp,
+        cet,
+        &other_sigs,
+        funding_sk,
+        funding_script_pubkey,
+        fund_output_value,
+        0,
+    )
+}
+
+/// Verify that the adaptor signatures provided by a party for the given cets are
+/// valid with respect to the oracle information and messages of each cet.
+pub fn verify_cet_adaptor_sigs_from_oracle_info(
+    secp: &Secp256k1<All>,
+    adaptor_sigs: &[EcdsaAdaptorSignature],
+    cets: &[Transaction],
+    oracle_infos: &[OracleInfo],
+    pubkey: &PublicKey,
+    funding_script_pubkey: &Script,
+    fund_output_value: u64,
+    msgs: &[Vec<Vec<Message>>],
+) -> Result<(), Error> {
+    if adaptor_sigs.len() != cets.len() || msgs.len() != cets.len() {
+        return Err(Error::InvalidArgument);
+    }
+
+    let sig_hashes = get_cets_sig_hash_msgs(cets, funding_script_pubkey, fund_output_value)?;
+
+    for ((adaptor_sig, sig_hash), msg) in adaptor_sigs.iter().zip(sig_hashes.iter()).zip(msgs) {
+        let adaptor_point = get_adaptor_point_from_oracle_info(secp, oracle_infos, msg)?;
+        verify_cet_adaptor_sig_from_sig_hash(secp, adaptor_sig, sig_hash, &adaptor_point, pubkey)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        create_cet_adaptor_sigs_from_oracle_info, secp_utils, verify_tx_input_sig, TxInputInfo,
+    };
+    use bitcoin::{hashes::hex::FromHex, Address, Network, Txid};
+    use secp256k1_zkp::{rand::RngCore, KeyPair, XOnlyPublicKey};
+
+    fn get_party_params(serial_id: u64, collateral: u64) -> (PartyParams, SecretKey) {
+        let secp = Secp256k1::new();
+        let mut rng = secp256k1_zkp::rand::thread_rng();
+        let fund_privkey = SecretKey::new(&mut rng);
+        let payout_pk = bitcoin::PublicKey::from_private_key(
+            &secp,
+            &bitcoin::PrivateKey::new(SecretKey::new(&mut rng), Network::Testnet),
+        );
+        let change_pk = bitcoin::PublicKey::from_private_key(
+            &secp,
+            &bitcoin::PrivateKey::new(SecretKey::new(&mut rng), Network::Testnet),
+        );
+        (
+            PartyParams {
+                fund_pubkey: PublicKey::from_secret_key(&secp, &fund_privkey),
+                change_script_pubkey: Address::p2wpkh(&change_pk, Network::Testnet)
+                    .unwrap()
+                    .script_pubkey(),
+                change_serial_id: serial_id,
+                payout_script_pubkey: Address::p2wpkh(&payout_pk, Network::Testnet)
+                    .unwrap()
+                    .script_pubkey(),
+                payout_serial_id: serial_id,
+                input_amount: 1000000000,
+                collateral,
+                inputs: vec![TxInputInfo {
+                    max_witness_len: 108,
+                    redeem_script: Script::new(),
+                    outpoint: OutPoint {
+                        txid: Txid::from_hex(
+                            "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456",
+                        )
+                        .unwrap(),
+                        vout: serial_id as u32,
+                    },
+                    serial_id,
+                }],
+                additional_payout_outputs: Vec::new(),
+            },
+            fund_privkey,
+        )
+    }
+
+    #[test]
+    fn cet_base_weight_matches_two_party_test() {
+        assert_eq!(CET_BASE_WEIGHT, get_cet_base_weight(2));
+    }
+
+    #[test]
+    fn make_funding_redeemscript_invalid_nb_parties_test() {
+        let secp = Secp256k1::new();
+        let pk = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[1; 32]).unwrap());
+        assert!(make_funding_redeemscript(&[pk]).is_err());
+        assert!(make_funding_redeemscript(&vec![pk; MAX_PARTIES + 1]).is_err());
+    }
+
+    #[test]
+    fn create_dlc_transactions_three_parties_test() {
+        let parties = (0..3)
+            .map(|i| get_party_params(i + 1, 100000000).0)
+            .collect::<Vec<_>>();
+        let payouts = vec![vec![300000000, 0, 0], vec![100000000, 100000000, 100000000]];
+
+        let dlc_txs = create_dlc_transactions(&parties, &payouts, 100, 4, 10, 10, 0).unwrap();
+
+        let fund_output = dlc_txs.get_fund_output();
+        assert_eq!(4, dlc_txs.fund.output.len());
+        assert_eq!(3, dlc_txs.fund.input.len());
+        assert!(fund_output.value > 300000000);
+        assert_eq!(1, dlc_txs.cets[0].output.len());
+        assert_eq!(3, dlc_txs.cets[1].output.len());
+        assert_eq!(3, dlc_txs.refund.output.len());
+        assert_eq!(100, dlc_txs.refund.lock_time.0);
+        assert!(dlc_txs.cets.iter().all(|x| x.lock_time.0 == 10));
+    }
+
+    #[test]
+    fn create_dlc_transactions_invalid_payout_test() {
+        let parties = (0..3)
+            .map(|i| get_party_params(i + 1, 100000000).0)
+            .collect::<Vec<_>>();
+
+        assert!(
+            create_dlc_transactions(&parties, &[vec![300000000, 0]], 100, 4, 10, 10, 0).is_err()
+        );
+        assert!(
+            create_dlc_transactions(&parties, &[vec![300000000, 0, 1]], 100, 4, 10, 10, 0).is_err()
+        );
+    }
+
+    #[test]
+    fn sign_cet_three_parties_test() {
+        let secp = Secp256k1::new();
+        let mut rng = secp256k1_zkp::rand::thread_rng();
+        let (parties, sks): (Vec<_>, Vec<_>) =
+            (0..3).map(|i| get_party_params(i + 1, 100000000)).unzip();
+        let payouts = vec![vec![300000000, 0, 0]];
+        let dlc_txs = create_dlc_transactions(&parties, &payouts, 100, 4, 10, 10, 0).unwrap();
+        let fund_output_value = dlc_txs.get_fund_output().value;
+
+        let oracle_kp = KeyPair::new(&secp, &mut rng);
+        let mut sk_nonce = [0u8; 32];
+        rng.fill_bytes(&mut sk_nonce);
+        let nonce =
+            XOnlyPublicKey::from_keypair(&KeyPair::from_seckey_slice(&secp, &sk_nonce).unwrap()).0;
+        let msg = Message::from_hashed_data::<secp256k1_zkp::hashes::sha256::Hash>(&[1]);
+        let oracle_sig = secp_utils::schnorrsig_sign_with_nonce(&secp, &msg, &oracle_kp, &sk_nonce);
+        let oracle_infos = vec![OracleInfo {
+            public_key: oracle_kp.x_only_public_key().0,
+            nonces: vec![nonce],
+        }];
+        let msgs = vec![vec![vec![msg]]];
+
+        let adaptor_sigs = sks
+            .iter()
+            .map(|sk| {
+                create_cet_adaptor_sigs_from_oracle_info(
+                    &secp,
+                    &dlc_txs.cets,
+                    &oracle_infos,
+                    sk,
+                    &dlc_txs.funding_script_pubkey,
+                    fund_output_value,
+                    &msgs,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        for (party, sigs) in parties.iter().zip(adaptor_sigs.iter()) {
+            verify_cet_adaptor_sigs_from_oracle_info(
+                &secp,
+                sigs,
+                &dlc_txs.cets,
+                &oracle_infos,
+                &party.fund_pubkey,
+                &dlc_txs.funding_script_pubkey,
+                fund_output_value,
+                &msgs,
+            )
+            .expect("Invalid adaptor signatures");
+        }
+
+        let others = parties
+            .iter()
+            .zip(adaptor_sigs.iter())
+            .skip(1)
+            .map(|(p, s)| (p.fund_pubkey, s[0]))
+            .collect::<Vec<_>>();
+        let mut cet = dlc_txs.cets[0].clone();
+        sign_cet(
+            &secp,
+            &mut cet,
+            &others,
+            &[vec![oracle_sig]],
+            &sks[0],
+            &dlc_txs.funding_script_pubkey,
+            fund_output_value,
+        )
+        .unwrap();
+
+        let witness = cet.input[0].witness.to_vec();
+        assert_eq!(5, witness.len());
+        assert!(witness[0].is_empty());
+        assert_eq!(dlc_txs.funding_script_pubkey.to_bytes(), witness[4]);
+
+        let adaptor_secret = signatures_to_secret(&[vec![oracle_sig]]).unwrap();
+        let adapted_sig = adaptor_sigs[1][0].decrypt(&adaptor_secret).unwrap();
+        verify_tx_input_sig(
+            &secp,
+            &adapted_sig,
+            &cet,
+            0,
+            &dlc_txs.funding_script_pubkey,
+            fund_output_value,
+            &parties[1].fund_pubkey,
+        )
+        .expect("Invalid decrypted adaptor signature");
+    }
+}
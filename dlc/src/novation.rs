@@ -0,0 +1,391 @@
+//! Module for creating the transactions used to transfer the side of a party
+//! in a DLC to a new party, referred to as novation. The transferor and the
+//! remaining party jointly spend the fund output of the contract to a new fund
+//! output of the same value, locked to the keys of the remaining party and of
+//! the transferee. The transferee funds the fees of the novation transaction
+//! and pays the transferor for the position.
+//! The CETs and refund transaction are then recreated to spend the new fund
+//! output, the transferee taking over the payout serial id of the transferor
+//! so that outputs keep the same order.
+
+use bitcoin::{
+    blockdata::{
+        script::Script,
+        transaction::{OutPoint, Transaction, TxIn, TxOut},
+    },
+    EcdsaSighashType, PackedLockTime, Witness,
+};
+use secp256k1_zkp::{ecdsa::Signature, PublicKey};
+
+use crate::prelude::*;
+use crate::{
+    create_cets_and_refund_tx, make_funding_redeemscript, util, Error, PartyParams, Payout,
+    DUST_LIMIT, FUND_TX_BASE_WEIGHT, TX_INPUT_BASE_WEIGHT, TX_VERSION,
+};
+
+/// The index of the input spending the original fund output in the novation
+/// transaction.
+pub const FUND_INPUT_INDEX: usize = 0;
+
+/// The weight of the witness of a 2-of-2 multisig input.
+const MULTISIG_WITNESS_WEIGHT: usize = 220;
+
+/// The weight of the novation transaction excluding the inputs and change
+/// output of the transferee and the output paying the transferor.
+const NOVATION_TX_BASE_WEIGHT: usize =
+    FUND_TX_BASE_WEIGHT + TX_INPUT_BASE_WEIGHT + MULTISIG_WITNESS_WEIGHT;
+
+/// Returns the fee paid by the transferee for the novation transaction.
+pub fn get_novation_fee(
+    transferee_params: &PartyParams,
+    transferor_payout_script_pubkey: &Script,
+    fee_rate_per_vb: u64,
+) -> Result<u64, Error> {
+    let mut weight = NOVATION_TX_BASE_WEIGHT;
+    for input in &transferee_params.inputs {
+        let script_weight = util::redeem_script_to_script_sig(&input.redeem_script).len() * 4;
+        weight = weight
+            .checked_add(TX_INPUT_BASE_WEIGHT + script_weight)
+            .and_then(|x| x.checked_add(input.max_witness_len))
+            .ok_or(Error::InvalidArgument)?;
+    }
+
+    // Value size + script length var_int + output script pubkey size, for both
+    // the change output and the output paying the transferor.
+    for script_pubkey in &[
+        &transferee_params.change_script_pubkey,
+        transferor_payout_script_pubkey,
+    ] {
+        weight = weight
+            .checked_add((9 + script_pubkey.len()) * 4)
+            .ok_or(Error::InvalidArgument)?;
+    }
+
+    util::weight_to_fee(weight, fee_rate_per_vb)
+}
+
+/// Create the novation transaction spending `fund_outpoint`, of value
+/// `fund_output_value`, to a new fund output of the same value locked to
+/// `remaining_fund_pubkey` and to the fund public key of the transferee. The
+/// transferee pays `transferor_payout` and the transaction fees from its
+/// inputs, the collateral of `transferee_params` being ignored. The fund
+/// input is placed at [`FUND_INPUT_INDEX`], followed by the inputs of the
+/// transferee. Returns the transaction and the redeem script of the new fund
+/// output.
+pub fn create_novation_transaction(
+    fund_outpoint: OutPoint,
+    fund_output_value: u64,
+    remaining_fund_pubkey: &PublicKey,
+    transferee_params: &PartyParams,
+    transferor_payout: TxOut,
+    transferor_payout_serial_id: u64,
+    fee_rate_per_vb: u64,
+    fund_output_serial_id: u64,
+) -> Result<(Transaction, Script), Error> {
+    if remaining_fund_pubkey == &transferee_params.fund_pubkey {
+        return Err(Error::InvalidArgument);
+    }
+
+    let fee = get_novation_fee(
+        transferee_params,
+        &transferor_payout.script_pubkey,
+        fee_rate_per_vb,
+    )?;
+    let change_value = transferee_params
+        .input_amount
+        .checked_sub(transferor_payout.value)
+        .and_then(|x| x.checked_sub(fee))
+        .ok_or(Error::InvalidArgument)?;
+
+    let funding_script_pubkey =
+        make_funding_redeemscript(remaining_fund_pubkey, &transferee_params.fund_pubkey);
+    let fund_output = TxOut {
+        value: fund_output_value,
+        script_pubkey: funding_script_pubkey.to_v0_p2wsh(),
+    };
+    let change_output = TxOut {
+        value: change_value,
+        script_pubkey: transferee_params.change_script_pubkey.clone(),
+    };
+    let output = util::discard_dust(
+        util::order_by_serial_ids(
+            vec![fund_output, transferor_payout, change_output],
+            &[
+                fund_output_serial_id,
+                transferor_payout_serial_id,
+                transferee_params.change_serial_id,
+            ],
+        ),
+        DUST_LIMIT,
+    );
+
+    let (tx_ins, serial_ids) =
+        transferee_params.get_unsigned_tx_inputs_and_serial_ids(util::DISABLE_LOCKTIME);
+    let mut input = vec![TxIn {
+        previous_output: fund_outpoint,
+        script_sig: Script::new(),
+        sequence: util::DISABLE_LOCKTIME,
+        witness: Witness::new(),
+    }];
+    input.extend(util::order_by_serial_ids(tx_ins, &serial_ids));
+
+    let novation_tx = Transaction {
+        version: TX_VERSION,
+        lock_time: PackedLockTime::ZERO,
+        input,
+        output,
+    };
+
+    Ok((novation_tx, funding_script_pubkey))
+}
+
+/// Create the CETs and refund transaction of a novated contract, spending the
+/// output of `novation_tx` locked by `funding_script_pubkey`. The CETs are
+/// returned in the order of `payouts`.
+pub fn create_novated_cets_and_refund_tx(
+    offer_params: &PartyParams,
+    accept_params: &PartyParams,
+    novation_tx: &Transaction,
+    funding_script_pubkey: &Script,
+    payouts: &[Payout],
+    refund_lock_time: u32,
+    cet_lock_time: u32,
+) -> Result<(Vec<Transaction>, Transaction), Error> {
+    let vout =
+        util::get_output_for_script_pubkey(novation_tx, &funding_script_pubkey.to_v0_p2wsh())
+            .ok_or(Error::InvalidArgument)?
+            .0;
+    let fund_outpoint = OutPoint {
+        txid: novation_tx.txid(),
+        vout: vout as u32,
+    };
+
+    create_cets_and_refund_tx(
+        offer_params,
+        accept_params,
+        fund_outpoint,
+        payouts,
+        refund_lock_time,
+        cet_lock_time,
+        None,
+    )
+}
+
+/// Sets the witness of the fund input of the novation transaction from the
+/// signatures of both parties to the original contract, which must be
+/// provided together with their fund public keys.
+pub fn finalize_novation_fund_input(
+    novation_tx: &mut Transaction,
+    funding_script_pubkey: &Script,
+    signatures: [(&PublicKey, &Signature); 2],
+) {
+    let mut signatures = signatures;
+    signatures.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut witness = vec![Vec::new()];
+    witness.extend(
+        signatures
+            .iter()
+            .map(|(_, sig)| util::finalize_sig(sig, EcdsaSighashType::All)),
+    );
+    witness.push(funding_script_pubkey.to_bytes());
+
+    novation_tx.input[FUND_INPUT_INDEX].witness = Witness::from_vec(witness);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_dlc_transactions, verify_tx_input_sig, TxInputInfo};
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::{Address, Network, Txid};
+    use secp256k1_zkp::{Secp256k1, SecretKey};
+
+    fn get_party_params(
+        serial_id: u64,
+        input_amount: u64,
+        collateral: u64,
+    ) -> (PartyParams, SecretKey) {
+        let secp = Secp256k1::new();
+        let mut rng = secp256k1_zkp::rand::thread_rng();
+        let fund_privkey = SecretKey::new(&mut rng);
+        let mut get_script_pubkey = || {
+            let pk = bitcoin::PublicKey::from_private_key(
+                &secp,
+                &bitcoin::PrivateKey::new(SecretKey::new(&mut rng), Network::Testnet),
+            );
+            Address::p2wpkh(&pk, Network::Testnet)
+                .unwrap()
+                .script_pubkey()
+        };
+        (
+            PartyParams {
+                fund_pubkey: PublicKey::from_secret_key(&secp, &fund_privkey),
+                change_script_pubkey: get_script_pubkey(),
+                change_serial_id: serial_id,
+                payout_script_pubkey: get_script_pubkey(),
+                payout_serial_id: serial_id,
+                input_amount,
+                collateral,
+                inputs: vec![TxInputInfo {
+                    max_witness_len: 108,
+                    redeem_script: Script::new(),
+                    outpoint: OutPoint {
+                        txid: Txid::from_hex(
+                            "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456",
+                        )
+                        .unwrap(),
+                        vout: serial_id as u32,
+                    },
+                    serial_id,
+                }],
+                additional_payout_outputs: Vec::new(),
+            },
+            fund_privkey,
+        )
+    }
+
+    #[test]
+    fn novation_transactions_replace_transferor_test() {
+        let secp = Secp256k1::new();
+        let (offer_params, offer_sk) = get_party_params(1, 1000000000, 100000000);
+        let (accept_params, accept_sk) = get_party_params(2, 1000000000, 100000000);
+        let payouts = vec![
+            Payout {
+                offer: 200000000,
+                accept: 0,
+            },
+            Payout {
+                offer: 100000000,
+                accept: 100000000,
+            },
+        ];
+        let dlc_txs =
+            create_dlc_transactions(&offer_params, &accept_params, &payouts, 100, 4, 10, 10, 0)
+                .unwrap();
+        let fund_output_value = dlc_txs.get_fund_output().value;
+        let fund_outpoint = OutPoint {
+            txid: dlc_txs.fund.txid(),
+            vout: dlc_txs.get_fund_output_index() as u32,
+        };
+
+        // The accepting party transfers its side for 0.5 BTC.
+        let (mut transferee_params, _) = get_party_params(3, 100000000, 0);
+        assert!(create_novation_transaction(
+            fund_outpoint,
+            fund_output_value,
+            &offer_params.fund_pubkey,
+            &transferee_params,
+            TxOut {
+                value: 100000000,
+                script_pubkey: accept_params.payout_script_pubkey.clone(),
+            },
+            4,
+            4,
+            5,
+        )
+        .is_err());
+
+        let price_output = TxOut {
+            value: 50000000,
+            script_pubkey: accept_params.payout_script_pubkey.clone(),
+        };
+        let (mut novation_tx, funding_script_pubkey) = create_novation_transaction(
+            fund_outpoint,
+            fund_output_value,
+            &offer_params.fund_pubkey,
+            &transferee_params,
+            price_output.clone(),
+            4,
+            4,
+            5,
+        )
+        .unwrap();
+
+        let fee = get_novation_fee(&transferee_params, &price_output.script_pubkey, 4).unwrap();
+        assert_eq!(
+            fund_outpoint,
+            novation_tx.input[FUND_INPUT_INDEX].previous_output
+        );
+        assert_eq!(2, novation_tx.input.len());
+        assert_eq!(3, novation_tx.output.len());
+        assert_eq!(
+            fund_output_value + transferee_params.input_amount,
+            novation_tx.output.iter().map(|x| x.value).sum::<u64>() + fee
+        );
+        assert_eq!(fund_output_value, novation_tx.output[2].value);
+
+        transferee_params.payout_serial_id = accept_params.payout_serial_id;
+        transferee_params.collateral = accept_params.collateral;
+        let (cets, refund) = create_novated_cets_and_refund_tx(
+            &offer_params,
+            &transferee_params,
+            &novation_tx,
+            &funding_script_pubkey,
+            &payouts,
+            100,
+            10,
+        )
+        .unwrap();
+        assert_eq!(dlc_txs.cets.len(), cets.len());
+        for (old, new) in dlc_txs
+            .cets
+            .iter()
+            .zip(cets.iter())
+            .chain(core::iter::once((&dlc_txs.refund, &refund)))
+        {
+            assert_eq!(novation_tx.txid(), new.input[0].previous_output.txid);
+            assert_eq!(old.lock_time, new.lock_time);
+            assert_eq!(old.output.len(), new.output.len());
+            for (old_output, new_output) in old.output.iter().zip(new.output.iter()) {
+                assert_eq!(old_output.value, new_output.value);
+                if old_output.script_pubkey == accept_params.payout_script_pubkey {
+                    assert_eq!(
+                        transferee_params.payout_script_pubkey,
+                        new_output.script_pubkey
+                    );
+                } else {
+                    assert_eq!(old_output.script_pubkey, new_output.script_pubkey);
+                }
+            }
+        }
+
+        let signatures = [&offer_sk, &accept_sk]
+            .iter()
+            .map(|sk| {
+                util::get_raw_sig_for_tx_input(
+                    &secp,
+                    &novation_tx,
+                    FUND_INPUT_INDEX,
+                    &dlc_txs.funding_script_pubkey,
+                    fund_output_value,
+                    sk,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        verify_tx_input_sig(
+            &secp,
+            &signatures[1],
+            &novation_tx,
+            FUND_INPUT_INDEX,
+            &dlc_txs.funding_script_pubkey,
+            fund_output_value,
+            &accept_params.fund_pubkey,
+        )
+        .expect("Invalid fund input signature");
+
+        finalize_novation_fund_input(
+            &mut novation_tx,
+            &dlc_txs.funding_script_pubkey,
+            [
+                (&accept_params.fund_pubkey, &signatures[1]),
+                (&offer_params.fund_pubkey, &signatures[0]),
+            ],
+        );
+        let witness = novation_tx.input[FUND_INPUT_INDEX].witness.to_vec();
+        assert_eq!(4, witness.len());
+        assert!(witness[0].is_empty());
+        assert_eq!(dlc_txs.funding_script_pubkey.to_bytes(), witness[3]);
+    }
+}
@@ -0,0 +1,210 @@
+//! # Funding input ownership proofs
+//! Proofs that a party controls the funding inputs it contributes to a DLC,
+//! so that a peer cannot make its counter party perform expensive adaptor
+//! signature computations using inputs that it does not own.
+//!
+//! A proof is the witness of a virtual transaction that commits to the
+//! temporary contract id and the outpoint of the input, following the
+//! construction used by BIP 322. Only P2WPKH and P2SH-P2WPKH inputs are
+//! supported.
+
+use bitcoin::blockdata::opcodes;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::{
+    EcdsaSighashType, OutPoint, PackedLockTime, PubkeyHash, Script, Sequence, Transaction, TxIn,
+    TxOut, Txid, WPubkeyHash, Witness,
+};
+use secp256k1_zkp::{ecdsa::Signature, PublicKey, Secp256k1, Verification};
+
+use util::get_sig_hash_msg;
+use Error;
+
+const OWNERSHIP_PROOF_TAG: &[u8] = b"DLC/funding_input_ownership";
+
+/// Returns the message committed to by the ownership proof of the input
+/// spending `outpoint` in the contract with the given temporary id.
+pub fn get_ownership_proof_message(
+    temporary_contract_id: &[u8; 32],
+    outpoint: &OutPoint,
+) -> sha256::Hash {
+    let tag = sha256::Hash::hash(OWNERSHIP_PROOF_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag[..]);
+    engine.input(&tag[..]);
+    engine.input(temporary_contract_id);
+    engine.input(&outpoint.txid[..]);
+    engine.input(&outpoint.vout.to_le_bytes());
+    sha256::Hash::from_engine(engine)
+}
+
+/// Returns the virtual transaction whose first input must be signed to prove
+/// ownership of an output locked by `script_pubkey`, together with the
+/// (zero valued) output it spends.
+pub fn get_ownership_proof_tx(
+    message: &sha256::Hash,
+    script_pubkey: &Script,
+) -> (Transaction, TxOut) {
+    let to_spend_out = TxOut {
+        value: 0,
+        script_pubkey: script_pubkey.clone(),
+    };
+    let to_spend = Transaction {
+        version: 0,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0xFFFFFFFF,
+            },
+            script_sig: Builder::new()
+                .push_int(0)
+                .push_slice(&message[..])
+                .into_script(),
+            sequence: Sequence(0),
+            witness: Witness::new(),
+        }],
+        output: vec![to_spend_out.clone()],
+    };
+    let to_sign = Transaction {
+        version: 0,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: to_spend.txid(),
+                vout: 0,
+            },
+            script_sig: Script::new(),
+            sequence: Sequence(0),
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: Builder::new()
+                .push_opcode(opcodes::all::OP_RETURN)
+                .into_script(),
+        }],
+    };
+    (to_sign, to_spend_out)
+}
+
+/// Returns whether ownership proofs can be produced for outputs locked by
+/// `script_pubkey`, using `redeem_script` for P2SH outputs.
+pub fn is_ownership_proof_supported(script_pubkey: &Script, redeem_script: &Script) -> bool {
+    script_pubkey.is_v0_p2wpkh()
+        || (script_pubkey.is_p2sh()
+            && redeem_script.is_v0_p2wpkh()
+            && *script_pubkey == Script::new_p2sh(&redeem_script.script_hash()))
+}
+
+/// Verifies that `witness` is a valid ownership proof for `message` of an
+/// output locked by `script_pubkey`. `redeem_script` must be empty for
+/// P2WPKH outputs.
+pub fn verify_ownership_proof<C: Verification>(
+    secp: &Secp256k1<C>,
+    message: &sha256::Hash,
+    script_pubkey: &Script,
+    redeem_script: &Script,
+    witness: &Witness,
+) -> Result<(), Error> {
+    if !is_ownership_proof_supported(script_pubkey, redeem_script) {
+        return Err(Error::InvalidArgument);
+    }
+
+    let program = if script_pubkey.is_v0_p2wpkh() {
+        script_pubkey
+    } else {
+        redeem_script
+    };
+
+    let elements = witness.to_vec();
+    if elements.len() != 2 {
+        return Err(Error::InvalidArgument);
+    }
+    let (sig, pubkey) = (&elements[0], &elements[1]);
+    match sig.split_last() {
+        Some((sighash_type, der_sig)) if *sighash_type == EcdsaSighashType::All as u8 => {
+            let public_key = PublicKey::from_slice(pubkey)?;
+            if *program != Script::new_v0_p2wpkh(&WPubkeyHash::hash(pubkey)) {
+                return Err(Error::InvalidArgument);
+            }
+            let (tx, prev_out) = get_ownership_proof_tx(message, script_pubkey);
+            let script_code = Script::new_p2pkh(&PubkeyHash::hash(pubkey));
+            let sig_hash_msg = get_sig_hash_msg(&tx, 0, &script_code, prev_out.value)?;
+            let sig = Signature::from_der(der_sig)?;
+            secp.verify_ecdsa(&sig_hash_msg, &sig, &public_key)?;
+            Ok(())
+        }
+        _ => Err(Error::InvalidArgument),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hex::FromHex;
+    use secp256k1_zkp::SecretKey;
+    use util::sign_p2wpkh_input;
+
+    fn sign_proof(sk: &SecretKey, message: &sha256::Hash, script_pubkey: &Script) -> Witness {
+        let secp = Secp256k1::new();
+        let (mut tx, prev_out) = get_ownership_proof_tx(message, script_pubkey);
+        sign_p2wpkh_input(&secp, sk, &mut tx, 0, EcdsaSighashType::All, prev_out.value).unwrap();
+        tx.input[0].witness.clone()
+    }
+
+    fn get_p2wpkh_script(sk: &SecretKey) -> Script {
+        let secp = Secp256k1::new();
+        let pk = PublicKey::from_secret_key(&secp, sk);
+        Script::new_v0_p2wpkh(&WPubkeyHash::hash(&pk.serialize()))
+    }
+
+    fn get_message(vout: u32) -> sha256::Hash {
+        let outpoint = OutPoint {
+            txid: Txid::from_hex(
+                "83266d6b22a9babf6ee469b88fd0d3a0c690525f7c903aff22ec8ee44214604f",
+            )
+            .unwrap(),
+            vout,
+        };
+        get_ownership_proof_message(&[1u8; 32], &outpoint)
+    }
+
+    #[test]
+    fn p2wpkh_ownership_proof_test() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let script_pubkey = get_p2wpkh_script(&sk);
+        let message = get_message(0);
+        let witness = sign_proof(&sk, &message, &script_pubkey);
+
+        verify_ownership_proof(&secp, &message, &script_pubkey, &Script::new(), &witness)
+            .expect("a valid proof");
+        verify_ownership_proof(
+            &secp,
+            &get_message(1),
+            &script_pubkey,
+            &Script::new(),
+            &witness,
+        )
+        .expect_err("a proof for another outpoint");
+        let other_script = get_p2wpkh_script(&SecretKey::from_slice(&[3u8; 32]).unwrap());
+        verify_ownership_proof(&secp, &message, &other_script, &Script::new(), &witness)
+            .expect_err("a proof for another script");
+    }
+
+    #[test]
+    fn p2sh_p2wpkh_ownership_proof_test() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let redeem_script = get_p2wpkh_script(&sk);
+        let script_pubkey = Script::new_p2sh(&redeem_script.script_hash());
+        let message = get_message(0);
+        let witness = sign_proof(&sk, &message, &script_pubkey);
+
+        verify_ownership_proof(&secp, &message, &script_pubkey, &redeem_script, &witness)
+            .expect("a valid proof");
+        verify_ownership_proof(&secp, &message, &script_pubkey, &Script::new(), &witness)
+            .expect_err("a missing redeem script");
+    }
+}
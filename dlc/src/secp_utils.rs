@@ -1,6 +1,7 @@
 //! Crypto utilities providing necessary DLC specific functions not available in
 //! rust-secp256k1 or rust-secp256k1-zkp.
 
+use crate::prelude::*;
 use crate::Error;
 use core::ptr;
 use secp256k1_sys::{
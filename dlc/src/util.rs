@@ -8,6 +8,7 @@ use bitcoin::{
 use bitcoin::{Sequence, Witness};
 use secp256k1_zkp::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey, Signing};
 
+use crate::prelude::*;
 use crate::Error;
 
 // Setting the nSequence for every input of a transaction to this value disables
@@ -25,12 +26,24 @@ pub(crate) fn get_sig_hash_msg(
     input_index: usize,
     script_pubkey: &Script,
     value: u64,
+) -> Result<Message, Error> {
+    get_sig_hash_msg_with_sighash_type(tx, input_index, script_pubkey, value, EcdsaSighashType::All)
+}
+
+/// Get a BIP143 signature hash using the given sighash type for a segwit
+/// transaction input as a Message instance
+pub(crate) fn get_sig_hash_msg_with_sighash_type(
+    tx: &Transaction,
+    input_index: usize,
+    script_pubkey: &Script,
+    value: u64,
+    sig_hash_type: EcdsaSighashType,
 ) -> Result<Message, Error> {
     let sig_hash = SighashCache::new(tx).segwit_signature_hash(
         input_index,
         script_pubkey,
         value,
-        EcdsaSighashType::All,
+        sig_hash_type,
     )?;
     Ok(Message::from_slice(&sig_hash).unwrap())
 }
@@ -69,7 +82,9 @@ pub fn get_sig_for_tx_input<C: Signing>(
     sig_hash_type: EcdsaSighashType,
     sk: &SecretKey,
 ) -> Result<Vec<u8>, Error> {
-    let sig = get_raw_sig_for_tx_input(secp, tx, input_index, script_pubkey, value, sk)?;
+    let sig_hash_msg =
+        get_sig_hash_msg_with_sighash_type(tx, input_index, script_pubkey, value, sig_hash_type)?;
+    let sig = secp.sign_ecdsa_low_r(&sig_hash_msg, sk);
     Ok(finalize_sig(&sig, sig_hash_type))
 }
 
@@ -95,7 +110,7 @@ pub fn get_sig_for_p2wpkh_input<C: Signing>(
 }
 
 pub(crate) fn weight_to_fee(weight: usize, fee_rate: u64) -> Result<u64, Error> {
-    (f64::ceil((weight as f64) / 4.0) as u64)
+    ((weight as u64 + 3) / 4)
         .checked_mul(fee_rate)
         .ok_or(Error::InvalidArgument)
 }
@@ -219,6 +234,16 @@ pub fn get_output_for_script_pubkey<'a>(
         .find(|(_, x)| &x.script_pubkey == script_pubkey)
 }
 
+/// Returns whether the given script pubkey is of a standard type that can
+/// receive a payout.
+pub(crate) fn is_standard_payout_script(script_pubkey: &Script) -> bool {
+    script_pubkey.is_p2pkh()
+        || script_pubkey.is_p2sh()
+        || script_pubkey.is_v0_p2wpkh()
+        || script_pubkey.is_v0_p2wsh()
+        || script_pubkey.is_v1_p2tr()
+}
+
 /// Filters the outputs that have a value lower than the given `dust_limit`.
 pub(crate) fn discard_dust(txs: Vec<TxOut>, dust_limit: u64) -> Vec<TxOut> {
     txs.into_iter().filter(|x| x.value >= dust_limit).collect()
@@ -236,6 +261,36 @@ pub(crate) fn compute_var_int_prefix_size(len: usize) -> usize {
     bitcoin::VarInt(len as u64).len()
 }
 
+/// Validate that the given sighash type can be used to sign a funding input.
+/// Only `SIGHASH_ALL` and `SIGHASH_ALL|SIGHASH_ANYONECANPAY` are accepted as
+/// they both commit to every output of the funding transaction. Although the
+/// latter keeps the signature valid if inputs are attached, doing so changes
+/// the txid of the funding transaction, invalidating the transactions already
+/// signed spending its outputs.
+pub fn validate_funding_sighash_type(sig_hash_type: EcdsaSighashType) -> Result<(), Error> {
+    match sig_hash_type {
+        EcdsaSighashType::All | EcdsaSighashType::AllPlusAnyoneCanPay => Ok(()),
+        _ => Err(Error::InvalidArgument),
+    }
+}
+
+/// Checks that all the signatures present in the given funding input witness
+/// use a sighash type allowed by [`validate_funding_sighash_type`].
+pub fn validate_funding_witness_sighash_types(witness: &Witness) -> Result<(), Error> {
+    for element in witness.iter() {
+        if let Some((sig_hash_type, der_sig)) = element.split_last() {
+            if Signature::from_der(der_sig).is_err() {
+                continue;
+            }
+            let sig_hash_type = EcdsaSighashType::from_standard(*sig_hash_type as u32)
+                .map_err(|_| Error::InvalidArgument)?;
+            validate_funding_sighash_type(sig_hash_type)?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Validate that the fee rate is not too high
 pub fn validate_fee_rate(fee_rate_per_vb: u64) -> Result<(), Error> {
     if fee_rate_per_vb > 25 * 250 {
@@ -244,3 +299,132 @@ pub fn validate_fee_rate(fee_rate_per_vb: u64) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{OutPoint, PackedLockTime, TxIn};
+    use secp256k1_zkp::SECP256K1;
+
+    fn get_test_tx() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: DISABLE_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: 10000,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn anyone_can_pay_signature_commits_to_sighash_type() {
+        let sk = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(SECP256K1, &sk);
+        let mut tx = get_test_tx();
+        sign_p2wpkh_input(
+            SECP256K1,
+            &sk,
+            &mut tx,
+            0,
+            EcdsaSighashType::AllPlusAnyoneCanPay,
+            100000,
+        )
+        .unwrap();
+
+        let witness_sig = tx.input[0].witness.to_vec()[0].clone();
+        let (sig_hash_type, der_sig) = witness_sig.split_last().unwrap();
+        assert_eq!(
+            EcdsaSighashType::AllPlusAnyoneCanPay.to_u32() as u8,
+            *sig_hash_type
+        );
+
+        let script_pubkey = get_pkh_script_pubkey_from_sk(SECP256K1, &sk);
+        let sig = Signature::from_der(der_sig).unwrap();
+        let acp_msg = get_sig_hash_msg_with_sighash_type(
+            &tx,
+            0,
+            &script_pubkey,
+            100000,
+            EcdsaSighashType::AllPlusAnyoneCanPay,
+        )
+        .unwrap();
+        let all_msg = get_sig_hash_msg(&tx, 0, &script_pubkey, 100000).unwrap();
+
+        assert!(SECP256K1.verify_ecdsa(&acp_msg, &sig, &pk).is_ok());
+        assert!(SECP256K1.verify_ecdsa(&all_msg, &sig, &pk).is_err());
+    }
+
+    #[test]
+    fn attaching_input_to_anyone_can_pay_funding_changes_txid() {
+        let sk = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(SECP256K1, &sk);
+        let script_pubkey = get_pkh_script_pubkey_from_sk(SECP256K1, &sk);
+        let mut fund = get_test_tx();
+        sign_p2wpkh_input(
+            SECP256K1,
+            &sk,
+            &mut fund,
+            0,
+            EcdsaSighashType::AllPlusAnyoneCanPay,
+            100000,
+        )
+        .unwrap();
+        // Outpoint spent by the CETs and refund transaction.
+        let fund_outpoint = OutPoint {
+            txid: fund.txid(),
+            vout: 0,
+        };
+
+        fund.input.push(TxIn {
+            previous_output: OutPoint {
+                txid: fund_outpoint.txid,
+                vout: 1,
+            },
+            script_sig: Script::new(),
+            sequence: DISABLE_LOCKTIME,
+            witness: Witness::new(),
+        });
+
+        let witness_sig = fund.input[0].witness.to_vec()[0].clone();
+        let sig = Signature::from_der(&witness_sig[..witness_sig.len() - 1]).unwrap();
+        let acp_msg = get_sig_hash_msg_with_sighash_type(
+            &fund,
+            0,
+            &script_pubkey,
+            100000,
+            EcdsaSighashType::AllPlusAnyoneCanPay,
+        )
+        .unwrap();
+        assert!(SECP256K1.verify_ecdsa(&acp_msg, &sig, &pk).is_ok());
+        assert_ne!(fund_outpoint.txid, fund.txid());
+    }
+
+    #[test]
+    fn validate_funding_witness_sighash_types_test() {
+        let sk = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let tx = get_test_tx();
+
+        for (sig_hash_type, is_valid) in [
+            (EcdsaSighashType::All, true),
+            (EcdsaSighashType::AllPlusAnyoneCanPay, true),
+            (EcdsaSighashType::None, false),
+            (EcdsaSighashType::Single, false),
+            (EcdsaSighashType::SinglePlusAnyoneCanPay, false),
+        ] {
+            let witness =
+                get_witness_for_p2wpkh_input(SECP256K1, &sk, &tx, 0, sig_hash_type, 100000)
+                    .unwrap();
+            assert_eq!(
+                is_valid,
+                validate_funding_witness_sighash_types(&witness).is_ok()
+            );
+        }
+    }
+}
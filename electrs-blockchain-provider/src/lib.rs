@@ -1,15 +1,19 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use bitcoin::consensus::Decodable;
-use bitcoin::hashes::hex::FromHex;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, Hash};
 use bitcoin::util::uint::Uint256;
 use bitcoin::{Block, BlockHash, BlockHeader, Network, OutPoint, Script, Transaction, TxOut, Txid};
 use bitcoin_test_utils::tx_to_string;
-use dlc_manager::{error::Error, Blockchain, Utxo};
+use dlc_manager::retry::{CircuitBreaker, CircuitState, RequestError, RetryConfig};
+use dlc_manager::{error::Error, AsyncBlockchain, Blockchain, ChainTip, Utxo};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use lightning::chain::chaininterface::{BroadcasterInterface, ConfirmationTarget, FeeEstimator};
 use lightning_block_sync::{BlockData, BlockHeaderData, BlockSource, BlockSourceError};
 use reqwest::blocking::Response;
@@ -25,48 +29,138 @@ pub enum Target {
     HighPriority = 6,
 }
 
+/// Configuration of the Esplora HTTP API servers used by an
+/// [`ElectrsBlockchainProvider`].
+#[derive(Clone, Debug)]
+pub struct EsploraConfig {
+    /// The base urls of the API of the servers, tried in order until one of
+    /// them responds.
+    pub endpoints: Vec<String>,
+    /// The maximum duration of each request.
+    pub timeout: Duration,
+    /// How blocking requests are retried when none of the servers could be
+    /// reached.
+    pub retry: RetryConfig,
+    /// The url of a SOCKS5 proxy through which all requests are sent, e.g.
+    /// `socks5h://127.0.0.1:9050` for a local Tor daemon. The `socks5h`
+    /// scheme, resolving host names through the proxy, is required to reach
+    /// `.onion` endpoints.
+    pub proxy: Option<String>,
+}
+
+impl EsploraConfig {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoints: vec![endpoint],
+            timeout: Duration::from_secs(30),
+            retry: RetryConfig::default(),
+            proxy: None,
+        }
+    }
+}
+
 pub struct ElectrsBlockchainProvider {
-    host: String,
+    hosts: Vec<String>,
     client: reqwest::blocking::Client,
     async_client: reqwest::Client,
     network: Network,
     fees: Arc<HashMap<Target, AtomicU32>>,
+    breaker: CircuitBreaker,
 }
 
 impl ElectrsBlockchainProvider {
     pub fn new(host: String, network: Network) -> Self {
+        Self::with_config(EsploraConfig::new(host), network)
+            .expect("to be able to create the http clients")
+    }
+
+    pub fn with_config(config: EsploraConfig, network: Network) -> Result<Self, Error> {
+        if config.endpoints.is_empty() {
+            return Err(Error::InvalidParameters(
+                "At least one endpoint is required".to_string(),
+            ));
+        }
+        let hosts = config
+            .endpoints
+            .into_iter()
+            .map(|mut host| {
+                if !host.ends_with('/') {
+                    host.push('/');
+                }
+                host
+            })
+            .collect::<Vec<_>>();
+        let proxy = get_proxy(config.proxy.as_deref(), &hosts)?;
+        let mut client_builder = reqwest::blocking::Client::builder().timeout(config.timeout);
+        let mut async_client_builder = reqwest::Client::builder().timeout(config.timeout);
+        if let Some(proxy) = proxy {
+            client_builder = client_builder.proxy(proxy.clone());
+            async_client_builder = async_client_builder.proxy(proxy);
+        }
+        let client = client_builder
+            .build()
+            .map_err(|e| Error::BlockchainError(e.to_string()))?;
+        let async_client = async_client_builder
+            .build()
+            .map_err(|e| Error::BlockchainError(e.to_string()))?;
         let mut fees: HashMap<Target, AtomicU32> = HashMap::new();
         fees.insert(Target::Background, AtomicU32::new(MIN_FEERATE));
         fees.insert(Target::Normal, AtomicU32::new(2000));
         fees.insert(Target::HighPriority, AtomicU32::new(5000));
         let fees = Arc::new(fees);
-        poll_for_fee_estimates(fees.clone(), &host);
-        Self {
-            host,
+        poll_for_fee_estimates(fees.clone(), client.clone(), hosts.clone());
+        Ok(Self {
+            hosts,
             network,
-            client: reqwest::blocking::Client::new(),
-            async_client: reqwest::Client::new(),
+            client,
+            async_client,
             fees,
-        }
+            breaker: CircuitBreaker::new(config.retry),
+        })
     }
 
+    /// Sends the request to each endpoint in turn, until one of them returns
+    /// a response that is not a server error. The whole round is retried if
+    /// none of them does.
     fn get(&self, sub_url: &str) -> Result<Response, Error> {
-        self.client
-            .get(format!("{}{}", self.host, sub_url))
-            .send()
-            .map_err(|x| {
-                dlc_manager::error::Error::IOError(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    x,
-                ))
-            })
+        self.breaker.call(|| {
+            let mut last_error = None;
+            for host in &self.hosts {
+                match self.client.get(format!("{host}{sub_url}")).send() {
+                    Ok(res) if !res.status().is_server_error() => {
+                        return res.error_for_status().map_err(|e| {
+                            RequestError::Permanent(Error::BlockchainError(e.to_string()))
+                        })
+                    }
+                    Ok(res) => last_error = Some(format!("{host} returned {}", res.status())),
+                    Err(e) => last_error = Some(e.to_string()),
+                }
+            }
+            Err(RequestError::Transient(Error::IOError(
+                std::io::Error::new(std::io::ErrorKind::Other, last_error.unwrap_or_default()),
+            )))
+        })
     }
 
     async fn get_async(&self, sub_url: &str) -> Result<reqwest::Response, reqwest::Error> {
-        self.async_client
-            .get(format!("{}{}", self.host, sub_url))
-            .send()
-            .await
+        let mut result = None;
+        for host in &self.hosts {
+            let res = self
+                .async_client
+                .get(format!("{host}{sub_url}"))
+                .send()
+                .await
+                .and_then(|res| res.error_for_status());
+            let is_unavailable = matches!(
+                &res,
+                Err(e) if e.is_connect() || e.is_timeout() || is_server_error(e)
+            );
+            if !is_unavailable {
+                return res;
+            }
+            result = Some(res);
+        }
+        result.expect("to have at least one endpoint")
     }
 
     fn get_text(&self, sub_url: &str) -> Result<String, Error> {
@@ -77,6 +171,7 @@ impl ElectrsBlockchainProvider {
 
     fn get_u64(&self, sub_url: &str) -> Result<u64, Error> {
         self.get_text(sub_url)?
+            .trim()
             .parse()
             .map_err(|e: std::num::ParseIntError| Error::BlockchainError(e.to_string()))
     }
@@ -101,28 +196,146 @@ impl ElectrsBlockchainProvider {
     pub fn get_outspends(&self, txid: &Txid) -> Result<Vec<OutSpendResp>, Error> {
         self.get_from_json(&format!("tx/{txid}/outspends"))
     }
+
+    async fn get_async_text(&self, sub_url: &str) -> Result<String, Error> {
+        self.get_async(sub_url)
+            .await
+            .map_err(to_blockchain_error)?
+            .text()
+            .await
+            .map_err(to_blockchain_error)
+    }
+
+    async fn get_async_bytes(&self, sub_url: &str) -> Result<Vec<u8>, Error> {
+        Ok(self
+            .get_async(sub_url)
+            .await
+            .map_err(to_blockchain_error)?
+            .bytes()
+            .await
+            .map_err(to_blockchain_error)?
+            .to_vec())
+    }
+
+    async fn get_async_json<T>(&self, sub_url: String) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.get_async(&sub_url)
+            .await
+            .map_err(to_blockchain_error)?
+            .json::<T>()
+            .await
+            .map_err(to_blockchain_error)
+    }
+
+    /// Returns the most recent transactions involving each of the given
+    /// scripts, in the same order, retrieving them concurrently.
+    pub async fn get_script_histories(
+        &self,
+        scripts: &[Script],
+    ) -> Result<Vec<Vec<ScriptTx>>, Error> {
+        batch(scripts, |script| {
+            let script_hash = sha256::Hash::hash(script.as_bytes());
+            self.get_async_json(format!("scripthash/{}/txs", script_hash.to_hex()))
+        })
+        .await?
+        .into_iter()
+        .map(|txs: Vec<ScriptTxResp>| {
+            txs.into_iter()
+                .map(|tx| {
+                    if tx.status.confirmed && tx.status.block_height.is_none() {
+                        return Err(Error::BlockchainError(format!(
+                            "Missing block height for confirmed {}",
+                            tx.txid
+                        )));
+                    }
+                    Ok(ScriptTx {
+                        txid: tx.txid,
+                        block_height: tx.status.block_height,
+                    })
+                })
+                .collect()
+        })
+        .collect()
+    }
+
+    fn get_header(&self, block_hash: &BlockHash) -> Result<BlockHeader, Error> {
+        let header_hex = self.get_text(&format!("block/{block_hash}/header"))?;
+        decode_header(header_hex.trim(), block_hash)
+    }
+
+    fn get_header_at_height(&self, height: u64) -> Result<BlockHeader, Error> {
+        let block_hash =
+            BlockHash::from_hex(self.get_text(&format!("block-height/{height}"))?.trim())
+                .map_err(to_blockchain_error)?;
+        self.get_header(&block_hash)
+    }
+
+    async fn get_async_header(&self, block_hash: &BlockHash) -> Result<BlockHeader, Error> {
+        let header_hex = self
+            .get_async_text(&format!("block/{block_hash}/header"))
+            .await?;
+        decode_header(header_hex.trim(), block_hash)
+    }
+
+    async fn get_async_header_at_height(&self, height: u64) -> Result<BlockHeader, Error> {
+        let block_hash = BlockHash::from_hex(
+            self.get_async_text(&format!("block-height/{height}"))
+                .await?
+                .trim(),
+        )
+        .map_err(to_blockchain_error)?;
+        self.get_async_header(&block_hash).await
+    }
 }
 
 impl Blockchain for ElectrsBlockchainProvider {
     fn send_transaction(&self, transaction: &Transaction) -> Result<(), dlc_manager::error::Error> {
-        let res = self
-            .client
-            .post(format!("{}tx", self.host))
-            .body(tx_to_string(transaction))
-            .send()
-            .map_err(|x| {
-                dlc_manager::error::Error::IOError(std::io::Error::new(
+        let body = tx_to_string(transaction);
+        self.breaker.call(|| {
+            let mut last_error = None;
+            for host in &self.hosts {
+                let res = match self
+                    .client
+                    .post(format!("{host}tx"))
+                    .body(body.clone())
+                    .send()
+                {
+                    Ok(res) => res,
+                    Err(e) => {
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                if res.status().is_server_error() {
+                    continue;
+                }
+                if let Err(error) = res.error_for_status_ref() {
+                    let body = res.text().unwrap_or_default();
+                    return Err(RequestError::Permanent(Error::InvalidParameters(format!(
+                        "Server returned error: {error} {body}"
+                    ))));
+                }
+                let txid = res
+                    .text()
+                    .map_err(|e| RequestError::Permanent(Error::BlockchainError(e.to_string())))?;
+                if txid.trim() != transaction.txid().to_string() {
+                    return Err(RequestError::Permanent(Error::BlockchainError(format!(
+                        "Server returned unexpected txid {txid}"
+                    ))));
+                }
+                return Ok(());
+            }
+            Err(RequestError::Transient(Error::IOError(
+                std::io::Error::new(
                     std::io::ErrorKind::Other,
-                    x,
-                ))
-            })?;
-        if let Err(error) = res.error_for_status_ref() {
-            let body = res.text().unwrap_or_default();
-            return Err(dlc_manager::error::Error::InvalidParameters(format!(
-                "Server returned error: {error} {body}"
-            )));
-        }
-        Ok(())
+                    last_error.map_or("All servers returned an error".to_string(), |e| {
+                        e.to_string()
+                    }),
+                ),
+            )))
+        })
     }
 
     fn get_network(
@@ -136,16 +349,16 @@ impl Blockchain for ElectrsBlockchainProvider {
     }
 
     fn get_block_at_height(&self, height: u64) -> Result<Block, dlc_manager::error::Error> {
-        let hash_at_height = self.get_text(&format!("block-height/{height}"))?;
+        let hash_at_height =
+            BlockHash::from_hex(self.get_text(&format!("block-height/{height}"))?.trim())
+                .map_err(|e| Error::BlockchainError(e.to_string()))?;
         let raw_block = self.get_bytes(&format!("block/{hash_at_height}/raw"))?;
-        Block::consensus_decode(&mut std::io::Cursor::new(&*raw_block))
-            .map_err(|e| Error::BlockchainError(e.to_string()))
+        decode_block(&raw_block, &hash_at_height)
     }
 
     fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, dlc_manager::error::Error> {
         let raw_tx = self.get_bytes(&format!("tx/{tx_id}/raw"))?;
-        Transaction::consensus_decode(&mut std::io::Cursor::new(&*raw_tx))
-            .map_err(|e| Error::BlockchainError(e.to_string()))
+        decode_transaction(&raw_tx, tx_id)
     }
 
     fn get_transaction_confirmations(
@@ -153,14 +366,196 @@ impl Blockchain for ElectrsBlockchainProvider {
         tx_id: &Txid,
     ) -> Result<u32, dlc_manager::error::Error> {
         let tx_status = self.get_from_json::<TxStatus>(&format!("tx/{tx_id}/status"))?;
-        if tx_status.confirmed {
-            let block_chain_height = self.get_blockchain_height()?;
-            if let Some(block_height) = tx_status.block_height {
-                return Ok((block_chain_height - block_height + 1) as u32);
+        if !tx_status.confirmed {
+            return Ok(0);
+        }
+        get_confirmations(&tx_status, Blockchain::get_blockchain_height(self)?, tx_id)
+    }
+
+    fn get_fee_rate(&self, confirmation_target: u32) -> Result<u64, dlc_manager::error::Error> {
+        let fee_estimates = self.get_from_json::<FeeEstimates>("fee-estimates")?;
+        get_fee_rate_for_target(&fee_estimates, confirmation_target)
+    }
+
+    fn get_mempool_spend(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<Option<Transaction>, dlc_manager::error::Error> {
+        let OutPoint { txid, vout } = outpoint;
+        let outspend = self.get_from_json::<OutSpendResp>(&format!("tx/{txid}/outspend/{vout}"))?;
+        match get_unconfirmed_spend(outspend) {
+            Some(spending_txid) => Ok(Some(Blockchain::get_transaction(self, &spending_txid)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn get_blockchain_tip(&self) -> Result<ChainTip, dlc_manager::error::Error> {
+        let block_hash = BlockHash::from_hex(self.get_text("blocks/tip/hash")?.trim())
+            .map_err(to_blockchain_error)?;
+        let block_info: BlockInfo = self.get_from_json(&format!("block/{block_hash}"))?;
+        Ok(ChainTip {
+            height: block_info.height as u64,
+            header: self.get_header(&block_hash)?,
+        })
+    }
+
+    fn get_headers_since(
+        &self,
+        height: u64,
+    ) -> Result<Vec<BlockHeader>, dlc_manager::error::Error> {
+        let tip_height = Blockchain::get_blockchain_height(self)?;
+        (height + 1..=tip_height)
+            .map(|h| self.get_header_at_height(h))
+            .collect()
+    }
+
+    fn get_circuit_state(&self) -> CircuitState {
+        self.breaker.get_state()
+    }
+}
+
+/// Requests for several transactions or scripts are sent concurrently through
+/// the shared client, which reuses its connections to the server (and
+/// multiplexes them over a single one when the server supports HTTP/2).
+#[async_trait::async_trait]
+impl AsyncBlockchain for ElectrsBlockchainProvider {
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<(), Error> {
+        let body = tx_to_string(transaction);
+        let mut last_error = None;
+        for host in &self.hosts {
+            let res = match self
+                .async_client
+                .post(format!("{host}tx"))
+                .body(body.clone())
+                .send()
+                .await
+            {
+                Ok(res) => res,
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+            if res.status().is_server_error() {
+                continue;
             }
+            if let Err(error) = res.error_for_status_ref() {
+                let body = res.text().await.unwrap_or_default();
+                return Err(Error::InvalidParameters(format!(
+                    "Server returned error: {error} {body}"
+                )));
+            }
+            let txid = res.text().await.map_err(to_blockchain_error)?;
+            if txid.trim() != transaction.txid().to_string() {
+                return Err(Error::BlockchainError(format!(
+                    "Server returned unexpected txid {txid}"
+                )));
+            }
+            return Ok(());
         }
+        Err(Error::IOError(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            last_error.map_or("All servers returned an error".to_string(), |e| {
+                e.to_string()
+            }),
+        )))
+    }
+
+    fn get_network(&self) -> Result<Network, Error> {
+        Ok(self.network)
+    }
+
+    async fn get_blockchain_height(&self) -> Result<u64, Error> {
+        self.get_async_text("blocks/tip/height")
+            .await?
+            .trim()
+            .parse()
+            .map_err(to_blockchain_error)
+    }
+
+    async fn get_block_at_height(&self, height: u64) -> Result<Block, Error> {
+        let hash_at_height = BlockHash::from_hex(
+            self.get_async_text(&format!("block-height/{height}"))
+                .await?
+                .trim(),
+        )
+        .map_err(to_blockchain_error)?;
+        let raw_block = self
+            .get_async_bytes(&format!("block/{hash_at_height}/raw"))
+            .await?;
+        decode_block(&raw_block, &hash_at_height)
+    }
+
+    async fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, Error> {
+        let raw_tx = self.get_async_bytes(&format!("tx/{tx_id}/raw")).await?;
+        decode_transaction(&raw_tx, tx_id)
+    }
+
+    async fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32, Error> {
+        Ok(self.get_transactions_confirmations(&[*tx_id]).await?[0])
+    }
 
-        Ok(0)
+    async fn get_fee_rate(&self, confirmation_target: u32) -> Result<u64, Error> {
+        let fee_estimates: FeeEstimates = self.get_async_json("fee-estimates".to_string()).await?;
+        get_fee_rate_for_target(&fee_estimates, confirmation_target)
+    }
+
+    async fn get_mempool_spend(&self, outpoint: &OutPoint) -> Result<Option<Transaction>, Error> {
+        let OutPoint { txid, vout } = outpoint;
+        let outspend: OutSpendResp = self
+            .get_async_json(format!("tx/{txid}/outspend/{vout}"))
+            .await?;
+        match get_unconfirmed_spend(outspend) {
+            Some(spending_txid) => Ok(Some(
+                AsyncBlockchain::get_transaction(self, &spending_txid).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_blockchain_tip(&self) -> Result<ChainTip, Error> {
+        let block_hash = BlockHash::from_hex(self.get_async_text("blocks/tip/hash").await?.trim())
+            .map_err(to_blockchain_error)?;
+        let block_info: BlockInfo = self.get_async_json(format!("block/{block_hash}")).await?;
+        Ok(ChainTip {
+            height: block_info.height as u64,
+            header: self.get_async_header(&block_hash).await?,
+        })
+    }
+
+    async fn get_headers_since(&self, height: u64) -> Result<Vec<BlockHeader>, Error> {
+        let tip_height = AsyncBlockchain::get_blockchain_height(self).await?;
+        let heights: Vec<u64> = (height + 1..=tip_height).collect();
+        batch(&heights, |h| self.get_async_header_at_height(*h)).await
+    }
+
+    async fn get_transactions(&self, tx_ids: &[Txid]) -> Result<Vec<Transaction>, Error> {
+        batch(tx_ids, |tx_id| {
+            AsyncBlockchain::get_transaction(self, tx_id)
+        })
+        .await
+    }
+
+    async fn get_transactions_confirmations(&self, tx_ids: &[Txid]) -> Result<Vec<u32>, Error> {
+        let tx_statuses: Vec<TxStatus> = batch(tx_ids, |tx_id| {
+            self.get_async_json(format!("tx/{tx_id}/status"))
+        })
+        .await?;
+        if tx_statuses.iter().all(|status| !status.confirmed) {
+            return Ok(vec![0; tx_ids.len()]);
+        }
+        let block_chain_height = AsyncBlockchain::get_blockchain_height(self).await?;
+        tx_statuses
+            .iter()
+            .zip(tx_ids)
+            .map(|(status, tx_id)| {
+                if status.confirmed {
+                    get_confirmations(status, block_chain_height, tx_id)
+                } else {
+                    Ok(0)
+                }
+            })
+            .collect()
     }
 }
 
@@ -171,6 +566,12 @@ impl simple_wallet::WalletBlockchainProvider for ElectrsBlockchainProvider {
         utxos
             .into_iter()
             .map(|x| {
+                if !x.status.is_valid() {
+                    return Err(Error::BlockchainError(format!(
+                        "Invalid status for utxo {}:{}",
+                        x.txid, x.vout
+                    )));
+                }
                 Ok(Utxo {
                     address: address.clone(),
                     outpoint: OutPoint {
@@ -306,22 +707,105 @@ impl BlockSource for ElectrsBlockchainProvider {
 impl BroadcasterInterface for ElectrsBlockchainProvider {
     fn broadcast_transaction(&self, tx: &Transaction) {
         let client = self.client.clone();
-        let host = self.host.clone();
+        let hosts = self.hosts.clone();
         let body = bitcoin_test_utils::tx_to_string(tx);
         std::thread::spawn(move || {
-            match client.post(format!("{host}tx")).body(body).send() {
-                Err(_) => {}
-                Ok(res) => {
-                    if res.error_for_status_ref().is_err() {
+            for host in hosts {
+                match client.post(format!("{host}tx")).body(body.clone()).send() {
+                    Err(_) => {}
+                    Ok(res) => {
+                        if res.error_for_status_ref().is_ok() {
+                            break;
+                        }
                         // let body = res.text().unwrap_or_default();
                         // TODO(tibo): log
                     }
-                }
-            };
+                };
+            }
         });
     }
 }
 
+/// A transaction involving a script.
+#[derive(Clone, Debug)]
+pub struct ScriptTx {
+    pub txid: Txid,
+    /// The height of the block including the transaction, `None` if it is
+    /// not confirmed.
+    pub block_height: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ScriptTxResp {
+    txid: Txid,
+    status: TxStatus,
+}
+
+/// The maximum number of requests in flight when batching requests.
+const MAX_CONCURRENT_REQUESTS: usize = 16;
+
+async fn batch<'a, T, R, F, Fut>(items: &'a [T], f: F) -> Result<Vec<R>, Error>
+where
+    F: FnMut(&'a T) -> Fut,
+    Fut: Future<Output = Result<R, Error>>,
+{
+    stream::iter(items.iter().map(f))
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .try_collect()
+        .await
+}
+
+fn to_blockchain_error<T: std::fmt::Display>(e: T) -> Error {
+    Error::BlockchainError(e.to_string())
+}
+
+fn decode_header(header_hex: &str, block_hash: &BlockHash) -> Result<BlockHeader, Error> {
+    let raw_header = Vec::<u8>::from_hex(header_hex).map_err(to_blockchain_error)?;
+    let header = BlockHeader::consensus_decode(&mut std::io::Cursor::new(&raw_header))
+        .map_err(to_blockchain_error)?;
+    if header.block_hash() != *block_hash {
+        return Err(Error::BlockchainError(format!(
+            "Server returned an invalid header for {block_hash}"
+        )));
+    }
+    Ok(header)
+}
+
+fn decode_block(raw_block: &[u8], block_hash: &BlockHash) -> Result<Block, Error> {
+    let block = Block::consensus_decode(&mut std::io::Cursor::new(raw_block))
+        .map_err(to_blockchain_error)?;
+    if block.block_hash() != *block_hash || !block.check_merkle_root() {
+        return Err(Error::BlockchainError(format!(
+            "Server returned an invalid block for {block_hash}"
+        )));
+    }
+    Ok(block)
+}
+
+fn decode_transaction(raw_tx: &[u8], tx_id: &Txid) -> Result<Transaction, Error> {
+    let tx = Transaction::consensus_decode(&mut std::io::Cursor::new(raw_tx))
+        .map_err(to_blockchain_error)?;
+    if tx.txid() != *tx_id {
+        return Err(Error::BlockchainError(format!(
+            "Server returned a transaction not matching {tx_id}"
+        )));
+    }
+    Ok(tx)
+}
+
+fn get_confirmations(
+    tx_status: &TxStatus,
+    block_chain_height: u64,
+    tx_id: &Txid,
+) -> Result<u32, Error> {
+    let block_height = tx_status.block_height.ok_or_else(|| {
+        Error::BlockchainError(format!("Missing block height for confirmed {tx_id}"))
+    })?;
+    // The tip may be retrieved from a server lagging behind the one that
+    // returned the status.
+    Ok((block_chain_height.max(block_height) - block_height + 1) as u32)
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct TxStatus {
     confirmed: bool,
@@ -351,6 +835,58 @@ pub enum UtxoStatus {
     },
 }
 
+impl UtxoStatus {
+    /// Returns whether the status is consistent, confirmed outputs having a
+    /// valid block hash.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            UtxoStatus::Confirmed {
+                confirmed,
+                block_hash,
+                ..
+            } => *confirmed && BlockHash::from_hex(block_hash).is_ok(),
+            UtxoStatus::Unconfirmed { confirmed } => !confirmed,
+        }
+    }
+}
+
+/// Returns the proxy to send the requests to the given hosts through,
+/// checking that `.onion` hosts are resolved by the proxy.
+fn get_proxy(proxy: Option<&str>, hosts: &[String]) -> Result<Option<reqwest::Proxy>, Error> {
+    let has_onion_host = hosts.iter().any(|host| {
+        reqwest::Url::parse(host)
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.ends_with(".onion")))
+            .unwrap_or(false)
+    });
+    if has_onion_host && !proxy.map_or(false, |p| p.starts_with("socks5h://")) {
+        return Err(Error::InvalidParameters(
+            "Onion endpoints require a socks5h proxy".to_string(),
+        ));
+    }
+    proxy
+        .map(|p| reqwest::Proxy::all(p).map_err(|e| Error::InvalidParameters(e.to_string())))
+        .transpose()
+}
+
+/// Returns the id of the spending transaction if it is not yet confirmed.
+fn get_unconfirmed_spend(outspend: OutSpendResp) -> Option<Txid> {
+    match outspend {
+        OutSpendResp::Spent(OutSpendInfo {
+            txid,
+            status: UtxoStatus::Unconfirmed { confirmed: false },
+            ..
+        }) => Some(txid),
+        _ => None,
+    }
+}
+
+fn is_server_error(error: &reqwest::Error) -> bool {
+    error
+        .status()
+        .map_or(false, |status| status.is_server_error())
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct SpentResp {
     spent: bool,
@@ -370,14 +906,20 @@ fn store_estimate_for_target(
         .store(val, std::sync::atomic::Ordering::Relaxed);
 }
 
-fn poll_for_fee_estimates(fees: Arc<HashMap<Target, AtomicU32>>, host: &str) {
-    let host = host.to_owned();
+fn poll_for_fee_estimates(
+    fees: Arc<HashMap<Target, AtomicU32>>,
+    client: reqwest::blocking::Client,
+    hosts: Vec<String>,
+) {
     std::thread::spawn(move || loop {
-        if let Ok(res) = reqwest::blocking::get(format!("{host}fee-estimates")) {
-            if let Ok(fee_estimates) = res.json::<FeeEstimates>() {
-                store_estimate_for_target(&fees, &fee_estimates, Target::Background);
-                store_estimate_for_target(&fees, &fee_estimates, Target::HighPriority);
-                store_estimate_for_target(&fees, &fee_estimates, Target::Normal);
+        for host in &hosts {
+            if let Ok(res) = client.get(format!("{host}fee-estimates")).send() {
+                if let Ok(fee_estimates) = res.json::<FeeEstimates>() {
+                    store_estimate_for_target(&fees, &fee_estimates, Target::Background);
+                    store_estimate_for_target(&fees, &fee_estimates, Target::HighPriority);
+                    store_estimate_for_target(&fees, &fee_estimates, Target::Normal);
+                    break;
+                }
             }
         }
 
@@ -385,6 +927,18 @@ fn poll_for_fee_estimates(fees: Arc<HashMap<Target, AtomicU32>>, host: &str) {
     });
 }
 
+/// Returns the estimate of the largest target not above the requested one, the
+/// server only providing estimates for some targets.
+fn get_fee_rate_for_target(fee_estimates: &FeeEstimates, target: u32) -> Result<u64, Error> {
+    fee_estimates
+        .iter()
+        .filter(|(t, _)| u32::from(**t) <= target)
+        .max_by_key(|(t, _)| **t)
+        .or_else(|| fee_estimates.iter().min_by_key(|(t, _)| **t))
+        .map(|(_, sats_per_vbyte)| std::cmp::max(sats_per_vbyte.ceil() as u64, 1))
+        .ok_or_else(|| Error::BlockchainError("No fee estimate available".to_string()))
+}
+
 fn get_estimate_for_target(fee_estimates: &FeeEstimates, target: &u16) -> u32 {
     match fee_estimates.get(target) {
         Some(sats_per_vbytes) => sats_per_vbyte_to_sats_per_1000_weight(*sats_per_vbytes),
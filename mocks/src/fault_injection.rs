@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use dlc_manager::error::Error;
+
+/// The type of error returned by a failing call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultKind {
+    Wallet,
+    Blockchain,
+    Storage,
+    Oracle,
+    InvalidState,
+    InvalidParameters,
+    Io,
+    ServiceUnavailable,
+}
+
+impl FaultKind {
+    fn to_error(self, method: &str) -> Error {
+        let msg = format!("Injected failure of {}", method);
+        match self {
+            FaultKind::Wallet => Error::WalletError(msg.into()),
+            FaultKind::Blockchain => Error::BlockchainError(msg),
+            FaultKind::Storage => Error::StorageError(msg),
+            FaultKind::Oracle => Error::OracleError(msg),
+            FaultKind::InvalidState => Error::InvalidState(msg),
+            FaultKind::InvalidParameters => Error::InvalidParameters(msg),
+            FaultKind::Io => Error::IOError(std::io::Error::new(std::io::ErrorKind::Other, msg)),
+            FaultKind::ServiceUnavailable => Error::ServiceUnavailable(msg),
+        }
+    }
+}
+
+/// The calls a fault applies to, numbered from one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallFilter {
+    All,
+    Nth(usize),
+    From(usize),
+}
+
+impl CallFilter {
+    fn matches(&self, call_number: usize) -> bool {
+        match self {
+            CallFilter::All => true,
+            CallFilter::Nth(n) => call_number == *n,
+            CallFilter::From(n) => call_number >= *n,
+        }
+    }
+}
+
+/// A failure or delay to apply to the calls made to a mock.
+#[derive(Clone, Debug)]
+pub struct Fault {
+    /// The name of the method whose calls are affected, e.g.
+    /// `get_utxos_for_amount`, or `None` to affect all the methods of the
+    /// mock, in which case calls are numbered across methods.
+    pub method: Option<String>,
+    pub calls: CallFilter,
+    /// The error returned by the affected calls, if any.
+    pub error: Option<FaultKind>,
+    /// How long the affected calls block before returning.
+    pub delay: Option<Duration>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct InjectorState {
+    faults: Vec<Fault>,
+    call_counts: HashMap<String, usize>,
+    nb_calls: usize,
+}
+
+/// Keeps track of the calls made to a mock and of the faults to apply to
+/// them.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    state: Mutex<InjectorState>,
+}
+
+impl Clone for FaultInjector {
+    fn clone(&self) -> Self {
+        FaultInjector {
+            state: Mutex::new(self.state.lock().unwrap().clone()),
+        }
+    }
+}
+
+impl FaultInjector {
+    pub fn inject(&self, fault: Fault) {
+        self.state.lock().unwrap().faults.push(fault);
+    }
+
+    /// Makes all the calls to the given method fail.
+    pub fn fail_method(&self, method: &str, kind: FaultKind) {
+        self.inject(Fault {
+            method: Some(method.to_string()),
+            calls: CallFilter::All,
+            error: Some(kind),
+            delay: None,
+        });
+    }
+
+    /// Makes the `n`th call to the given method fail, counting the calls made
+    /// before this one.
+    pub fn fail_nth_call(&self, method: &str, n: usize, kind: FaultKind) {
+        self.inject(Fault {
+            method: Some(method.to_string()),
+            calls: CallFilter::Nth(n),
+            error: Some(kind),
+            delay: None,
+        });
+    }
+
+    /// Makes all the calls to the given method block for the given duration.
+    pub fn delay_method(&self, method: &str, delay: Duration) {
+        self.inject(Fault {
+            method: Some(method.to_string()),
+            calls: CallFilter::All,
+            error: None,
+            delay: Some(delay),
+        });
+    }
+
+    /// Removes all the faults, keeping the call counts.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().faults.clear();
+    }
+
+    /// Returns the number of calls made to the given method.
+    pub fn get_call_count(&self, method: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .call_counts
+            .get(method)
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /// Records a call to the given method, blocking for the delays of the
+    /// matching faults and returning the error of the first one that has one.
+    pub fn on_call(&self, method: &str) -> Result<(), Error> {
+        let (delay, error) = {
+            let mut state = self.state.lock().unwrap();
+            state.nb_calls += 1;
+            let count = state.call_counts.entry(method.to_string()).or_insert(0);
+            *count += 1;
+            let method_calls = *count;
+            let matching = state.faults.iter().filter(|x| match &x.method {
+                Some(m) => m == method && x.calls.matches(method_calls),
+                None => x.calls.matches(state.nb_calls),
+            });
+            matching.fold((Duration::ZERO, None), |(delay, error), x| {
+                (delay + x.delay.unwrap_or_default(), error.or(x.error))
+            })
+        };
+
+        // The lock is released so that concurrent calls are not delayed.
+        if delay > Duration::ZERO {
+            std::thread::sleep(delay);
+        }
+
+        match error {
+            Some(kind) => Err(kind.to_error(method)),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod fault_injection;
 pub mod memory_storage_provider;
 pub mod mock_blockchain;
 pub mod mock_oracle_provider;
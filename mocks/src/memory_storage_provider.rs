@@ -6,23 +6,153 @@ use dlc_manager::channel::{
     Channel,
 };
 use dlc_manager::contract::{
-    offered_contract::OfferedContract, signed_contract::SignedContract, Contract, PreClosedContract,
+    multi_party_contract::MultiPartyContract, novation_contract::NovationContract,
+    offered_contract::OfferedContract, signed_contract::SignedContract, ArchivedContract, Contract,
+    ContractState, PreClosedContract,
 };
-use dlc_manager::Storage;
 use dlc_manager::{error::Error as DaemonError, ChannelId, ContractId, Utxo};
-use secp256k1_zkp::{PublicKey, SecretKey};
+use dlc_manager::{ContractPage, Storage, StorageWrite};
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
+use dlc_messages::Message;
+use lightning::util::ser::Writeable;
+use secp256k1_zkp::{PublicKey, SecretKey, XOnlyPublicKey};
 use simple_wallet::WalletStorage;
+
+use crate::fault_injection::FaultInjector;
 use std::collections::HashMap;
+use std::mem::discriminant;
 use std::sync::{Mutex, RwLock};
 
+/// A copy of the full state of a [`MemoryStorage`], which can be restored
+/// later on to replay a scenario from a given point.
+#[derive(Clone, Default)]
+pub struct StorageSnapshot {
+    contracts: HashMap<ContractId, Contract>,
+    channels: HashMap<ChannelId, Channel>,
+    archived_contracts: HashMap<ContractId, ArchivedContract>,
+    multi_party_contracts: HashMap<ContractId, MultiPartyContract>,
+    novation_contracts: HashMap<ContractId, NovationContract>,
+    addresses: HashMap<Address, SecretKey>,
+    utxos: HashMap<OutPoint, Utxo>,
+    key_pairs: HashMap<PublicKey, SecretKey>,
+    announcements: HashMap<(XOnlyPublicKey, String), OracleAnnouncement>,
+    attestations: HashMap<(XOnlyPublicKey, String), OracleAttestation>,
+    outbound_messages: HashMap<PublicKey, Vec<Message>>,
+}
+
+/// The contracts and channels that differ between two [`StorageSnapshot`],
+/// each list being sorted by id.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageDiff {
+    pub added_contracts: Vec<ContractId>,
+    pub removed_contracts: Vec<ContractId>,
+    pub updated_contracts: Vec<ContractId>,
+    pub added_channels: Vec<ChannelId>,
+    pub removed_channels: Vec<ChannelId>,
+    pub updated_channels: Vec<ChannelId>,
+}
+
+impl StorageDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == StorageDiff::default()
+    }
+}
+
+impl StorageSnapshot {
+    pub fn get_contracts(&self) -> Vec<Contract> {
+        self.contracts.values().cloned().collect()
+    }
+
+    pub fn get_channels(&self) -> Vec<Channel> {
+        self.channels.values().cloned().collect()
+    }
+
+    /// Returns the changes required to go from this snapshot to `other`.
+    pub fn diff(&self, other: &StorageSnapshot) -> StorageDiff {
+        let (added_contracts, removed_contracts, updated_contracts) =
+            diff_maps(&self.contracts, &other.contracts, |a, b| {
+                a.get_state() == b.get_state() && encode_contract(a) == encode_contract(b)
+            });
+        let (added_channels, removed_channels, updated_channels) =
+            diff_maps(&self.channels, &other.channels, |a, b| {
+                discriminant(a) == discriminant(b) && encode_channel(a) == encode_channel(b)
+            });
+        StorageDiff {
+            added_contracts,
+            removed_contracts,
+            updated_contracts,
+            added_channels,
+            removed_channels,
+            updated_channels,
+        }
+    }
+}
+
+fn diff_maps<V, F: Fn(&V, &V) -> bool>(
+    old: &HashMap<[u8; 32], V>,
+    new: &HashMap<[u8; 32], V>,
+    is_same: F,
+) -> (Vec<[u8; 32]>, Vec<[u8; 32]>, Vec<[u8; 32]>) {
+    let mut added: Vec<_> = new
+        .keys()
+        .filter(|k| !old.contains_key(*k))
+        .cloned()
+        .collect();
+    let mut removed: Vec<_> = old
+        .keys()
+        .filter(|k| !new.contains_key(*k))
+        .cloned()
+        .collect();
+    let mut updated: Vec<_> = old
+        .iter()
+        .filter_map(|(k, v)| match new.get(k) {
+            Some(n) if !is_same(v, n) => Some(*k),
+            _ => None,
+        })
+        .collect();
+    added.sort();
+    removed.sort();
+    updated.sort();
+    (added, removed, updated)
+}
+
+fn encode_contract(contract: &Contract) -> Vec<u8> {
+    match contract {
+        Contract::Offered(o) | Contract::Rejected(o) => o.encode(),
+        Contract::Accepted(a) => a.encode(),
+        Contract::Signed(s) | Contract::Confirmed(s) | Contract::Refunded(s) => s.encode(),
+        Contract::FailedAccept(c) => c.encode(),
+        Contract::FailedSign(c) => c.encode(),
+        Contract::PreClosed(c) => c.encode(),
+        Contract::Closed(c) => c.encode(),
+    }
+}
+
+fn encode_channel(channel: &Channel) -> Vec<u8> {
+    match channel {
+        Channel::Offered(o) => o.encode(),
+        Channel::Accepted(a) => a.encode(),
+        Channel::Signed(s) => s.encode(),
+        Channel::FailedAccept(f) => f.encode(),
+        Channel::FailedSign(f) => f.encode(),
+    }
+}
+
 pub struct MemoryStorage {
     contracts: RwLock<HashMap<ContractId, Contract>>,
     channels: RwLock<HashMap<ChannelId, Channel>>,
     contracts_saved: Mutex<Option<HashMap<ContractId, Contract>>>,
     channels_saved: Mutex<Option<HashMap<ChannelId, Channel>>>,
+    archived_contracts: RwLock<HashMap<ContractId, ArchivedContract>>,
+    multi_party_contracts: RwLock<HashMap<ContractId, MultiPartyContract>>,
+    novation_contracts: RwLock<HashMap<ContractId, NovationContract>>,
     addresses: RwLock<HashMap<Address, SecretKey>>,
     utxos: RwLock<HashMap<OutPoint, Utxo>>,
     key_pairs: RwLock<HashMap<PublicKey, SecretKey>>,
+    announcements: RwLock<HashMap<(XOnlyPublicKey, String), OracleAnnouncement>>,
+    attestations: RwLock<HashMap<(XOnlyPublicKey, String), OracleAttestation>>,
+    outbound_messages: RwLock<HashMap<PublicKey, Vec<Message>>>,
+    faults: FaultInjector,
 }
 
 impl MemoryStorage {
@@ -32,12 +162,24 @@ impl MemoryStorage {
             channels: RwLock::new(HashMap::new()),
             contracts_saved: Mutex::new(None),
             channels_saved: Mutex::new(None),
+            archived_contracts: RwLock::new(HashMap::new()),
+            multi_party_contracts: RwLock::new(HashMap::new()),
+            novation_contracts: RwLock::new(HashMap::new()),
             addresses: RwLock::new(HashMap::new()),
             utxos: RwLock::new(HashMap::new()),
             key_pairs: RwLock::new(HashMap::new()),
+            announcements: RwLock::new(HashMap::new()),
+            attestations: RwLock::new(HashMap::new()),
+            outbound_messages: RwLock::new(HashMap::new()),
+            faults: FaultInjector::default(),
         }
     }
 
+    /// Returns the faults applied to the calls made to the storage.
+    pub fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+
     pub fn save(&self) {
         let mut contracts_saved = self.contracts_saved.lock().unwrap();
 
@@ -71,6 +213,52 @@ impl MemoryStorage {
     }
 }
 
+impl MemoryStorage {
+    pub fn from_snapshot(snapshot: StorageSnapshot) -> Self {
+        let storage = Self::new();
+        storage.restore(snapshot);
+        storage
+    }
+
+    /// Returns a copy of the current state of the storage.
+    pub fn snapshot(&self) -> StorageSnapshot {
+        StorageSnapshot {
+            contracts: self.contracts.read().unwrap().clone(),
+            channels: self.channels.read().unwrap().clone(),
+            archived_contracts: self.archived_contracts.read().unwrap().clone(),
+            multi_party_contracts: self.multi_party_contracts.read().unwrap().clone(),
+            novation_contracts: self.novation_contracts.read().unwrap().clone(),
+            addresses: self.addresses.read().unwrap().clone(),
+            utxos: self.utxos.read().unwrap().clone(),
+            key_pairs: self.key_pairs.read().unwrap().clone(),
+            announcements: self.announcements.read().unwrap().clone(),
+            attestations: self.attestations.read().unwrap().clone(),
+            outbound_messages: self.outbound_messages.read().unwrap().clone(),
+        }
+    }
+
+    /// Replaces the whole state of the storage with the given snapshot.
+    pub fn restore(&self, snapshot: StorageSnapshot) {
+        *self.contracts.write().unwrap() = snapshot.contracts;
+        *self.channels.write().unwrap() = snapshot.channels;
+        *self.archived_contracts.write().unwrap() = snapshot.archived_contracts;
+        *self.multi_party_contracts.write().unwrap() = snapshot.multi_party_contracts;
+        *self.novation_contracts.write().unwrap() = snapshot.novation_contracts;
+        *self.addresses.write().unwrap() = snapshot.addresses;
+        *self.utxos.write().unwrap() = snapshot.utxos;
+        *self.key_pairs.write().unwrap() = snapshot.key_pairs;
+        *self.announcements.write().unwrap() = snapshot.announcements;
+        *self.attestations.write().unwrap() = snapshot.attestations;
+        *self.outbound_messages.write().unwrap() = snapshot.outbound_messages;
+    }
+
+    /// Returns the changes made to the storage since the given snapshot was
+    /// taken.
+    pub fn diff(&self, snapshot: &StorageSnapshot) -> StorageDiff {
+        snapshot.diff(&self.snapshot())
+    }
+}
+
 impl Default for MemoryStorage {
     fn default() -> Self {
         Self::new()
@@ -79,11 +267,13 @@ impl Default for MemoryStorage {
 
 impl Storage for MemoryStorage {
     fn get_contract(&self, id: &ContractId) -> Result<Option<Contract>, DaemonError> {
+        self.faults.on_call("get_contract")?;
         let map = self.contracts.read().expect("Could not get read lock");
         Ok(map.get(id).cloned())
     }
 
     fn get_contracts(&self) -> Result<Vec<Contract>, DaemonError> {
+        self.faults.on_call("get_contracts")?;
         Ok(self
             .contracts
             .read()
@@ -93,7 +283,88 @@ impl Storage for MemoryStorage {
             .collect())
     }
 
+    fn get_contracts_page(
+        &self,
+        state: Option<ContractState>,
+        cursor: Option<ContractId>,
+        limit: usize,
+    ) -> Result<ContractPage, DaemonError> {
+        self.faults.on_call("get_contracts_page")?;
+        let map = self.contracts.read().expect("Could not get read lock");
+        let mut contracts: Vec<_> = map
+            .iter()
+            .filter(|(id, c)| {
+                cursor.map_or(true, |cursor| **id > cursor)
+                    && state.map_or(true, |state| c.get_state() == state)
+            })
+            .collect();
+        contracts.sort_by_key(|(id, _)| **id);
+        let next_cursor = if contracts.len() > limit {
+            contracts.truncate(limit);
+            contracts.last().map(|(id, _)| **id)
+        } else {
+            None
+        };
+        Ok(ContractPage {
+            contracts: contracts.into_iter().map(|(_, c)| c.clone()).collect(),
+            next_cursor,
+        })
+    }
+
+    fn get_contracts_by_counter_party(
+        &self,
+        counter_party: &PublicKey,
+    ) -> Result<Vec<Contract>, DaemonError> {
+        self.faults.on_call("get_contracts_by_counter_party")?;
+        Ok(self
+            .contracts
+            .read()
+            .expect("Could not get read lock")
+            .values()
+            .filter(|c| &c.get_counter_party_id() == counter_party)
+            .cloned()
+            .collect())
+    }
+
+    fn get_contracts_by_event_id(&self, event_id: &str) -> Result<Vec<Contract>, DaemonError> {
+        self.faults.on_call("get_contracts_by_event_id")?;
+        Ok(self
+            .contracts
+            .read()
+            .expect("Could not get read lock")
+            .values()
+            .filter(|c| c.get_oracle_event_ids().iter().any(|id| id == event_id))
+            .cloned()
+            .collect())
+    }
+
+    fn archive_contracts(&self, archives: &[ArchivedContract]) -> Result<(), DaemonError> {
+        self.faults.on_call("archive_contracts")?;
+        let mut contracts = self.contracts.write().expect("Could not get write lock");
+        let mut archived_contracts = self
+            .archived_contracts
+            .write()
+            .expect("Could not get write lock");
+        for archive in archives {
+            contracts.remove(&archive.contract_id);
+            archived_contracts.insert(archive.contract_id, archive.clone());
+        }
+        Ok(())
+    }
+
+    fn get_archived_contracts(&self) -> Result<Vec<ArchivedContract>, DaemonError> {
+        self.faults.on_call("get_archived_contracts")?;
+        Ok(self
+            .archived_contracts
+            .read()
+            .expect("Could not get read lock")
+            .values()
+            .cloned()
+            .collect())
+    }
+
     fn create_contract(&self, contract: &OfferedContract) -> Result<(), DaemonError> {
+        self.faults.on_call("create_contract")?;
         let mut map = self.contracts.write().expect("Could not get write lock");
         let res = map.insert(contract.id, Contract::Offered(contract.clone()));
         match res {
@@ -105,12 +376,14 @@ impl Storage for MemoryStorage {
     }
 
     fn delete_contract(&self, id: &ContractId) -> Result<(), DaemonError> {
+        self.faults.on_call("delete_contract")?;
         let mut map = self.contracts.write().expect("Could not get write lock");
         map.remove(id);
         Ok(())
     }
 
     fn update_contract(&self, contract: &Contract) -> Result<(), DaemonError> {
+        self.faults.on_call("update_contract")?;
         let mut map = self.contracts.write().expect("Could not get write lock");
         match contract {
             a @ Contract::Accepted(_) | a @ Contract::Signed(_) => {
@@ -123,6 +396,7 @@ impl Storage for MemoryStorage {
     }
 
     fn get_signed_contracts(&self) -> Result<Vec<SignedContract>, DaemonError> {
+        self.faults.on_call("get_signed_contracts")?;
         let map = self.contracts.read().expect("Could not get read lock");
 
         let mut res: Vec<SignedContract> = Vec::new();
@@ -137,6 +411,7 @@ impl Storage for MemoryStorage {
     }
 
     fn get_confirmed_contracts(&self) -> Result<Vec<SignedContract>, DaemonError> {
+        self.faults.on_call("get_confirmed_contracts")?;
         let map = self.contracts.read().expect("Could not get read lock");
 
         let mut res: Vec<SignedContract> = Vec::new();
@@ -151,6 +426,7 @@ impl Storage for MemoryStorage {
     }
 
     fn get_contract_offers(&self) -> Result<Vec<OfferedContract>, DaemonError> {
+        self.faults.on_call("get_contract_offers")?;
         let map = self.contracts.read().expect("Could not get read lock");
 
         let mut res: Vec<OfferedContract> = Vec::new();
@@ -165,6 +441,7 @@ impl Storage for MemoryStorage {
     }
 
     fn get_preclosed_contracts(&self) -> Result<Vec<PreClosedContract>, DaemonError> {
+        self.faults.on_call("get_preclosed_contracts")?;
         let map = self.contracts.read().expect("Could not get read lock");
 
         let mut res: Vec<PreClosedContract> = Vec::new();
@@ -181,6 +458,7 @@ impl Storage for MemoryStorage {
         channel: Channel,
         contract: Option<Contract>,
     ) -> Result<(), DaemonError> {
+        self.faults.on_call("upsert_channel")?;
         {
             let mut map = self.channels.write().expect("Could not get write lock");
             match &channel {
@@ -198,12 +476,14 @@ impl Storage for MemoryStorage {
     }
 
     fn delete_channel(&self, channel_id: &ChannelId) -> Result<(), DaemonError> {
+        self.faults.on_call("delete_channel")?;
         let mut map = self.channels.write().expect("Could not get write lock");
         map.remove(channel_id);
         Ok(())
     }
 
     fn get_channel(&self, channel_id: &ChannelId) -> Result<Option<Channel>, DaemonError> {
+        self.faults.on_call("get_channel")?;
         let map = self.channels.read().expect("could not get read lock");
         Ok(map.get(channel_id).cloned())
     }
@@ -212,6 +492,7 @@ impl Storage for MemoryStorage {
         &self,
         channel_state: Option<SignedChannelStateType>,
     ) -> Result<Vec<SignedChannel>, DaemonError> {
+        self.faults.on_call("get_signed_channels")?;
         let map = self.channels.read().expect("Could not get read lock");
 
         let mut res: Vec<SignedChannel> = Vec::new();
@@ -233,6 +514,7 @@ impl Storage for MemoryStorage {
     }
 
     fn get_offered_channels(&self) -> Result<Vec<OfferedChannel>, DaemonError> {
+        self.faults.on_call("get_offered_channels")?;
         let map = self.channels.read().expect("Could not get read lock");
 
         let mut res: Vec<OfferedChannel> = Vec::new();
@@ -246,14 +528,211 @@ impl Storage for MemoryStorage {
         Ok(res)
     }
 
+    fn write_batch(&self, writes: &[StorageWrite]) -> Result<(), DaemonError> {
+        self.faults.on_call("write_batch")?;
+        let mut contracts = self.contracts.write().expect("Could not get write lock");
+        let mut channels = self.channels.write().expect("Could not get write lock");
+        for write in writes {
+            match write {
+                StorageWrite::Contract(contract) => {
+                    if let Contract::Accepted(_) | Contract::Signed(_) = contract {
+                        contracts.remove(&contract.get_temporary_id());
+                    }
+                    contracts.insert(contract.get_id(), (*contract).clone());
+                }
+                StorageWrite::Channel(channel) => {
+                    if let Channel::Accepted(_) | Channel::Signed(_) = channel {
+                        channels.remove(&channel.get_temporary_id());
+                    }
+                    channels.insert(channel.get_id(), (*channel).clone());
+                }
+                // No need to persist for mocks
+                StorageWrite::ChainMonitor(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn migrate(&self) -> Result<(), DaemonError> {
+        self.faults.on_call("migrate")?;
+        // Nothing is serialized in memory.
+        Ok(())
+    }
+
     fn persist_chain_monitor(&self, _: &ChainMonitor) -> Result<(), DaemonError> {
+        self.faults.on_call("persist_chain_monitor")?;
         // No need to persist for mocks
         Ok(())
     }
 
     fn get_chain_monitor(&self) -> Result<Option<ChainMonitor>, DaemonError> {
+        self.faults.on_call("get_chain_monitor")?;
         Ok(None)
     }
+
+    fn persist_oracle_announcement(
+        &self,
+        announcement: &OracleAnnouncement,
+    ) -> Result<(), DaemonError> {
+        self.faults.on_call("persist_oracle_announcement")?;
+        self.announcements
+            .write()
+            .expect("Could not get write lock")
+            .insert(
+                (
+                    announcement.oracle_public_key,
+                    announcement.oracle_event.event_id.clone(),
+                ),
+                announcement.clone(),
+            );
+        Ok(())
+    }
+
+    fn get_oracle_announcement(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAnnouncement>, DaemonError> {
+        self.faults.on_call("get_oracle_announcement")?;
+        Ok(self
+            .announcements
+            .read()
+            .expect("Could not get read lock")
+            .get(&(*oracle_public_key, event_id.to_string()))
+            .cloned())
+    }
+
+    fn persist_oracle_attestation(
+        &self,
+        event_id: &str,
+        attestation: &OracleAttestation,
+    ) -> Result<(), DaemonError> {
+        self.faults.on_call("persist_oracle_attestation")?;
+        self.attestations
+            .write()
+            .expect("Could not get write lock")
+            .insert(
+                (attestation.oracle_public_key, event_id.to_string()),
+                attestation.clone(),
+            );
+        Ok(())
+    }
+
+    fn get_oracle_attestation(
+        &self,
+        oracle_public_key: &XOnlyPublicKey,
+        event_id: &str,
+    ) -> Result<Option<OracleAttestation>, DaemonError> {
+        self.faults.on_call("get_oracle_attestation")?;
+        Ok(self
+            .attestations
+            .read()
+            .expect("Could not get read lock")
+            .get(&(*oracle_public_key, event_id.to_string()))
+            .cloned())
+    }
+
+    fn persist_outbound_messages(
+        &self,
+        node_id: &PublicKey,
+        messages: &[Message],
+    ) -> Result<(), DaemonError> {
+        self.faults.on_call("persist_outbound_messages")?;
+        let mut outbound_messages = self
+            .outbound_messages
+            .write()
+            .expect("Could not get write lock");
+        if messages.is_empty() {
+            outbound_messages.remove(node_id);
+        } else {
+            outbound_messages.insert(*node_id, messages.to_vec());
+        }
+        Ok(())
+    }
+
+    fn get_outbound_messages(&self) -> Result<Vec<(PublicKey, Vec<Message>)>, DaemonError> {
+        self.faults.on_call("get_outbound_messages")?;
+        Ok(self
+            .outbound_messages
+            .read()
+            .expect("Could not get read lock")
+            .iter()
+            .map(|(node_id, messages)| (*node_id, messages.clone()))
+            .collect())
+    }
+
+    fn upsert_multi_party_contract(
+        &self,
+        contract: &MultiPartyContract,
+    ) -> Result<(), DaemonError> {
+        self.faults.on_call("upsert_multi_party_contract")?;
+        let mut map = self
+            .multi_party_contracts
+            .write()
+            .expect("Could not get write lock");
+        if contract.id != contract.temporary_id {
+            map.remove(&contract.temporary_id);
+        }
+        map.insert(contract.id, contract.clone());
+        Ok(())
+    }
+
+    fn get_multi_party_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<MultiPartyContract>, DaemonError> {
+        self.faults.on_call("get_multi_party_contract")?;
+        Ok(self
+            .multi_party_contracts
+            .read()
+            .expect("Could not get read lock")
+            .get(id)
+            .cloned())
+    }
+
+    fn get_multi_party_contracts(&self) -> Result<Vec<MultiPartyContract>, DaemonError> {
+        self.faults.on_call("get_multi_party_contracts")?;
+        Ok(self
+            .multi_party_contracts
+            .read()
+            .expect("Could not get read lock")
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    fn upsert_novation_contract(&self, contract: &NovationContract) -> Result<(), DaemonError> {
+        self.faults.on_call("upsert_novation_contract")?;
+        self.novation_contracts
+            .write()
+            .expect("Could not get write lock")
+            .insert(contract.contract_id, contract.clone());
+        Ok(())
+    }
+
+    fn get_novation_contract(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<NovationContract>, DaemonError> {
+        self.faults.on_call("get_novation_contract")?;
+        Ok(self
+            .novation_contracts
+            .read()
+            .expect("Could not get read lock")
+            .get(id)
+            .cloned())
+    }
+
+    fn get_novation_contracts(&self) -> Result<Vec<NovationContract>, DaemonError> {
+        self.faults.on_call("get_novation_contracts")?;
+        Ok(self
+            .novation_contracts
+            .read()
+            .expect("Could not get read lock")
+            .values()
+            .cloned()
+            .collect())
+    }
 }
 
 impl WalletStorage for MemoryStorage {
@@ -262,6 +741,7 @@ impl WalletStorage for MemoryStorage {
         address: &Address,
         privkey: &secp256k1_zkp::SecretKey,
     ) -> Result<(), DaemonError> {
+        self.faults.on_call("upsert_address")?;
         self.addresses
             .write()
             .expect("Could not get write lock")
@@ -270,6 +750,7 @@ impl WalletStorage for MemoryStorage {
     }
 
     fn delete_address(&self, address: &Address) -> Result<(), DaemonError> {
+        self.faults.on_call("delete_address")?;
         self.addresses
             .write()
             .expect("Could not get write lock")
@@ -278,6 +759,7 @@ impl WalletStorage for MemoryStorage {
     }
 
     fn get_addresses(&self) -> Result<Vec<Address>, DaemonError> {
+        self.faults.on_call("get_addresses")?;
         Ok(self
             .addresses
             .read()
@@ -291,6 +773,7 @@ impl WalletStorage for MemoryStorage {
         &self,
         address: &Address,
     ) -> Result<Option<secp256k1_zkp::SecretKey>, DaemonError> {
+        self.faults.on_call("get_priv_key_for_address")?;
         Ok(self
             .addresses
             .read()
@@ -304,6 +787,7 @@ impl WalletStorage for MemoryStorage {
         public_key: &secp256k1_zkp::PublicKey,
         privkey: &secp256k1_zkp::SecretKey,
     ) -> Result<(), DaemonError> {
+        self.faults.on_call("upsert_key_pair")?;
         self.key_pairs
             .write()
             .expect("Could not get write lock")
@@ -316,6 +800,7 @@ impl WalletStorage for MemoryStorage {
         &self,
         public_key: &secp256k1_zkp::PublicKey,
     ) -> Result<Option<secp256k1_zkp::SecretKey>, DaemonError> {
+        self.faults.on_call("get_priv_key_for_pubkey")?;
         Ok(self
             .key_pairs
             .read()
@@ -325,6 +810,7 @@ impl WalletStorage for MemoryStorage {
     }
 
     fn upsert_utxo(&self, utxo: &Utxo) -> Result<(), DaemonError> {
+        self.faults.on_call("upsert_utxo")?;
         self.utxos
             .write()
             .expect("Could not get write lock")
@@ -333,6 +819,7 @@ impl WalletStorage for MemoryStorage {
     }
 
     fn has_utxo(&self, utxo: &Utxo) -> Result<bool, DaemonError> {
+        self.faults.on_call("has_utxo")?;
         Ok(self
             .utxos
             .read()
@@ -341,6 +828,7 @@ impl WalletStorage for MemoryStorage {
     }
 
     fn delete_utxo(&self, utxo: &Utxo) -> Result<(), DaemonError> {
+        self.faults.on_call("delete_utxo")?;
         self.utxos
             .write()
             .expect("Could not get write lock")
@@ -349,6 +837,7 @@ impl WalletStorage for MemoryStorage {
     }
 
     fn get_utxos(&self) -> Result<Vec<Utxo>, DaemonError> {
+        self.faults.on_call("get_utxos")?;
         Ok(self
             .utxos
             .read()
@@ -359,6 +848,7 @@ impl WalletStorage for MemoryStorage {
     }
 
     fn unreserve_utxo(&self, txid: &Txid, vout: u32) -> Result<(), DaemonError> {
+        self.faults.on_call("unreserve_utxo")?;
         let outpoint = OutPoint { txid: *txid, vout };
         self.utxos
             .write()
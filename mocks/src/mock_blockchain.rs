@@ -1,29 +1,98 @@
-use bitcoin::{Block, Transaction, Txid};
-use dlc_manager::{error::Error, Blockchain, Utxo};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{Block, BlockHash, BlockHeader, OutPoint, Transaction, TxMerkleNode, Txid};
+use dlc_manager::{error::Error, Blockchain, ChainTip, Time, Utxo};
 use lightning::chain::chaininterface::FeeEstimator;
 use simple_wallet::WalletBlockchainProvider;
 
-pub struct MockBlockchain {}
+use crate::fault_injection::FaultInjector;
+
+/// Blockchain keeping the transactions sent to it so that they can be
+/// retrieved, e.g. as previous transactions of funding inputs.
+#[derive(Default)]
+pub struct MockBlockchain {
+    transactions: Mutex<HashMap<Txid, Transaction>>,
+    faults: FaultInjector,
+}
+
+impl MockBlockchain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the faults applied to the calls made to the blockchain.
+    pub fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+}
 
 impl Blockchain for MockBlockchain {
-    fn send_transaction(&self, _transaction: &Transaction) -> Result<(), Error> {
+    fn send_transaction(&self, transaction: &Transaction) -> Result<(), Error> {
+        self.faults.on_call("send_transaction")?;
+        self.transactions
+            .lock()
+            .unwrap()
+            .insert(transaction.txid(), transaction.clone());
         Ok(())
     }
     fn get_network(&self) -> Result<bitcoin::network::constants::Network, Error> {
+        self.faults.on_call("get_network")?;
         Ok(bitcoin::Network::Regtest)
     }
     fn get_blockchain_height(&self) -> Result<u64, Error> {
+        self.faults.on_call("get_blockchain_height")?;
         Ok(10)
     }
     fn get_block_at_height(&self, _height: u64) -> Result<Block, Error> {
+        self.faults.on_call("get_block_at_height")?;
         unimplemented!();
     }
-    fn get_transaction(&self, _tx_id: &Txid) -> Result<Transaction, Error> {
-        unimplemented!();
+    fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, Error> {
+        self.faults.on_call("get_transaction")?;
+        self.transactions
+            .lock()
+            .unwrap()
+            .get(tx_id)
+            .cloned()
+            .ok_or_else(|| Error::BlockchainError(format!("Unknown transaction {}", tx_id)))
     }
     fn get_transaction_confirmations(&self, _tx_id: &Txid) -> Result<u32, Error> {
+        self.faults.on_call("get_transaction_confirmations")?;
         Ok(6)
     }
+    fn get_fee_rate(&self, _confirmation_target: u32) -> Result<u64, Error> {
+        self.faults.on_call("get_fee_rate")?;
+        Ok(2)
+    }
+    fn get_mempool_spend(&self, _outpoint: &OutPoint) -> Result<Option<Transaction>, Error> {
+        self.faults.on_call("get_mempool_spend")?;
+        Ok(None)
+    }
+    fn get_blockchain_tip(&self) -> Result<ChainTip, Error> {
+        self.faults.on_call("get_blockchain_tip")?;
+        Ok(ChainTip {
+            height: 10,
+            header: get_header(),
+        })
+    }
+    fn get_headers_since(&self, height: u64) -> Result<Vec<BlockHeader>, Error> {
+        self.faults.on_call("get_headers_since")?;
+        Ok((height..10).map(|_| get_header()).collect())
+    }
+}
+
+/// Returns a header timestamped with the mock time.
+fn get_header() -> BlockHeader {
+    BlockHeader {
+        version: 1,
+        prev_blockhash: BlockHash::all_zeros(),
+        merkle_root: TxMerkleNode::all_zeros(),
+        time: crate::mock_time::MockTime {}.unix_time_now() as u32,
+        bits: 0,
+        nonce: 0,
+    }
 }
 
 impl WalletBlockchainProvider for MockBlockchain {
@@ -1,5 +1,5 @@
 use dlc_manager::error::Error as DaemonError;
-use dlc_manager::Oracle;
+use dlc_manager::{AsyncOracle, Oracle};
 use dlc_messages::oracle_msgs::{
     EventDescriptor, OracleAnnouncement, OracleAttestation, OracleEvent,
 };
@@ -11,6 +11,8 @@ use secp256k1_zkp::{KeyPair, XOnlyPublicKey};
 
 use std::collections::HashMap;
 
+use crate::fault_injection::FaultInjector;
+
 #[derive(Clone, Debug)]
 pub struct MockOracle {
     key_pair: KeyPair,
@@ -18,6 +20,7 @@ pub struct MockOracle {
     announcements: HashMap<String, OracleAnnouncement>,
     attestations: HashMap<String, OracleAttestation>,
     nonces: HashMap<String, Vec<SecretKey>>,
+    faults: FaultInjector,
 }
 
 impl MockOracle {
@@ -31,6 +34,7 @@ impl MockOracle {
             announcements: HashMap::new(),
             attestations: HashMap::new(),
             nonces: HashMap::new(),
+            faults: FaultInjector::default(),
         }
     }
 
@@ -44,8 +48,14 @@ impl MockOracle {
             announcements: HashMap::new(),
             attestations: HashMap::new(),
             nonces: HashMap::new(),
+            faults: FaultInjector::default(),
         }
     }
+
+    /// Returns the faults applied to the calls made to the oracle.
+    pub fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
 }
 
 impl Default for MockOracle {
@@ -60,6 +70,7 @@ impl Oracle for MockOracle {
     }
 
     fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement, DaemonError> {
+        self.faults.on_call("get_announcement")?;
         let res = self
             .announcements
             .get(event_id)
@@ -68,6 +79,7 @@ impl Oracle for MockOracle {
     }
 
     fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation, DaemonError> {
+        self.faults.on_call("get_attestation")?;
         let res = self
             .attestations
             .get(event_id)
@@ -76,6 +88,21 @@ impl Oracle for MockOracle {
     }
 }
 
+#[async_trait::async_trait]
+impl AsyncOracle for MockOracle {
+    fn get_public_key(&self) -> XOnlyPublicKey {
+        Oracle::get_public_key(self)
+    }
+
+    async fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement, DaemonError> {
+        Oracle::get_announcement(self, event_id)
+    }
+
+    async fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation, DaemonError> {
+        Oracle::get_attestation(self, event_id)
+    }
+}
+
 impl MockOracle {
     fn generate_nonces_for_event(
         &mut self,
@@ -121,7 +148,7 @@ impl MockOracle {
         let sig = self.secp.sign_schnorr(&msg, &self.key_pair);
         let announcement = OracleAnnouncement {
             oracle_event,
-            oracle_public_key: self.get_public_key(),
+            oracle_public_key: Oracle::get_public_key(self),
             announcement_signature: sig,
         };
         self.announcements
@@ -145,7 +172,7 @@ impl MockOracle {
             })
             .collect();
         let attestation = OracleAttestation {
-            oracle_public_key: self.get_public_key(),
+            oracle_public_key: Oracle::get_public_key(self),
             signatures,
             outcomes: outcomes.to_vec(),
         };
@@ -1,17 +1,20 @@
-use std::rc::Rc;
+use std::time::Duration;
 
-use bitcoin::{Address, PackedLockTime, Script, Transaction, TxOut};
-use dlc_manager::{error::Error, Blockchain, Signer, Utxo, Wallet};
+use bitcoin::{Address, OutPoint, PackedLockTime, Script, Transaction, TxOut};
+use dlc_manager::zeroizing::ZeroizingSecretKey;
+use dlc_manager::{error::Error, Blockchain, ContractId, ContractSigner, Utxo, Wallet};
 use secp256k1_zkp::{rand::seq::SliceRandom, SecretKey};
 
+use crate::fault_injection::FaultInjector;
 use crate::mock_blockchain::MockBlockchain;
 
 pub struct MockWallet {
     utxos: Vec<Utxo>,
+    faults: FaultInjector,
 }
 
 impl MockWallet {
-    pub fn new(blockchain: &Rc<MockBlockchain>, nb_utxo: u16) -> Self {
+    pub fn new(blockchain: &MockBlockchain, nb_utxo: u16) -> Self {
         let mut utxos = Vec::with_capacity(nb_utxo as usize);
 
         for i in 0..nb_utxo {
@@ -40,11 +43,24 @@ impl MockWallet {
             utxos.push(utxo);
         }
 
-        Self { utxos }
+        Self {
+            utxos,
+            faults: FaultInjector::default(),
+        }
+    }
+
+    /// Returns the faults applied to the calls made to the wallet.
+    pub fn faults(&self) -> &FaultInjector {
+        &self.faults
     }
 }
 
-impl Signer for MockWallet {
+impl ContractSigner for MockWallet {
+    fn get_new_secret_key(&self) -> Result<SecretKey, dlc_manager::error::Error> {
+        self.faults.on_call("get_new_secret_key")?;
+        Ok(get_secret_key())
+    }
+
     fn sign_tx_input(
         &self,
         _tx: &mut bitcoin::Transaction,
@@ -52,32 +68,32 @@ impl Signer for MockWallet {
         _tx_out: &bitcoin::TxOut,
         _redeem_script: Option<bitcoin::Script>,
     ) -> Result<(), dlc_manager::error::Error> {
+        self.faults.on_call("sign_tx_input")?;
         Ok(())
     }
 
     fn get_secret_key_for_pubkey(
         &self,
         _pubkey: &secp256k1_zkp::PublicKey,
-    ) -> Result<SecretKey, dlc_manager::error::Error> {
-        Ok(get_secret_key())
+    ) -> Result<ZeroizingSecretKey, dlc_manager::error::Error> {
+        self.faults.on_call("get_secret_key_for_pubkey")?;
+        Ok(get_secret_key().into())
     }
 }
 
 impl Wallet for MockWallet {
     fn get_new_address(&self) -> Result<Address, dlc_manager::error::Error> {
+        self.faults.on_call("get_new_address")?;
         Ok(get_address())
     }
 
-    fn get_new_secret_key(&self) -> Result<SecretKey, dlc_manager::error::Error> {
-        Ok(get_secret_key())
-    }
-
     fn get_utxos_for_amount(
         &self,
         amount: u64,
         _fee_rate: Option<u64>,
         _lock_utxos: bool,
     ) -> Result<Vec<dlc_manager::Utxo>, Error> {
+        self.faults.on_call("get_utxos_for_amount")?;
         let mut utxo_pool = self.utxos.clone();
         let seed = 1;
         utxo_pool.shuffle(&mut secp256k1_zkp::rand::rngs::mock::StepRng::new(
@@ -106,6 +122,22 @@ impl Wallet for MockWallet {
     }
 
     fn import_address(&self, _address: &Address) -> Result<(), dlc_manager::error::Error> {
+        self.faults.on_call("import_address")?;
+        Ok(())
+    }
+
+    fn reserve_utxos(
+        &self,
+        _temporary_id: &ContractId,
+        _utxos: &[OutPoint],
+        _ttl: Duration,
+    ) -> Result<(), Error> {
+        self.faults.on_call("reserve_utxos")?;
+        Ok(())
+    }
+
+    fn release_utxos(&self, _temporary_id: &ContractId) -> Result<(), Error> {
+        self.faults.on_call("release_utxos")?;
         Ok(())
     }
 }
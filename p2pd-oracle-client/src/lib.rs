@@ -28,6 +28,7 @@ use secp256k1_zkp::{schnorr::Signature, XOnlyPublicKey};
 pub struct P2PDOracleClient {
     host: String,
     public_key: XOnlyPublicKey,
+    client: reqwest::blocking::Client,
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -69,11 +70,13 @@ struct AttestationResponse {
     values: Vec<String>,
 }
 
-fn get<T>(path: &str) -> Result<T, DlcManagerError>
+fn get<T>(client: &reqwest::blocking::Client, path: &str) -> Result<T, DlcManagerError>
 where
     T: serde::de::DeserializeOwned,
 {
-    reqwest::blocking::get(path)
+    client
+        .get(path)
+        .send()
         .map_err(|x| {
             dlc_manager::error::Error::IOError(std::io::Error::new(std::io::ErrorKind::Other, x))
         })?
@@ -108,6 +111,38 @@ impl P2PDOracleClient {
     /// host. Returns an error if the host could not be reached. Panics if the
     /// oracle uses an incompatible format.
     pub fn new(host: &str) -> Result<P2PDOracleClient, DlcManagerError> {
+        Self::with_client(host, reqwest::blocking::Client::new())
+    }
+
+    /// Same as [`P2PDOracleClient::new`] but sending the requests through the
+    /// SOCKS5 proxy at the given url. Oracles with an `.onion` host can only
+    /// be reached through a proxy using the `socks5h` scheme, so that the host
+    /// name is resolved by the proxy.
+    pub fn with_proxy(host: &str, proxy: &str) -> Result<P2PDOracleClient, DlcManagerError> {
+        let is_onion = reqwest::Url::parse(host)
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.ends_with(".onion")))
+            .unwrap_or(false);
+        if is_onion && !proxy.starts_with("socks5h://") {
+            return Err(DlcManagerError::InvalidParameters(
+                "Onion oracles require a socks5h proxy".to_string(),
+            ));
+        }
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| DlcManagerError::InvalidParameters(e.to_string()))?;
+        let client = reqwest::blocking::Client::builder()
+            .proxy(proxy)
+            .build()
+            .map_err(|e| {
+                DlcManagerError::IOError(std::io::Error::new(std::io::ErrorKind::Other, e))
+            })?;
+        Self::with_client(host, client)
+    }
+
+    fn with_client(
+        host: &str,
+        client: reqwest::blocking::Client,
+    ) -> Result<P2PDOracleClient, DlcManagerError> {
         if host.is_empty() {
             return Err(DlcManagerError::InvalidParameters(
                 "Invalid host".to_string(),
@@ -119,8 +154,12 @@ impl P2PDOracleClient {
             host.to_string()
         };
         let path = pubkey_path(&host);
-        let public_key = get::<PublicKeyResponse>(&path)?.public_key;
-        Ok(P2PDOracleClient { host, public_key })
+        let public_key = get::<PublicKeyResponse>(&client, &path)?.public_key;
+        Ok(P2PDOracleClient {
+            host,
+            public_key,
+            client,
+        })
     }
 }
 
@@ -145,7 +184,7 @@ impl Oracle for P2PDOracleClient {
     fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement, DlcManagerError> {
         let (asset_id, date_time) = parse_event_id(event_id)?;
         let path = announcement_path(&self.host, &asset_id, &date_time);
-        let announcement = get(&path)?;
+        let announcement = get(&self.client, &path)?;
         Ok(announcement)
     }
 
@@ -159,7 +198,7 @@ impl Oracle for P2PDOracleClient {
             event_id: _,
             signatures,
             values,
-        } = get::<AttestationResponse>(&path)?;
+        } = get::<AttestationResponse>(&self.client, &path)?;
 
         Ok(OracleAttestation {
             oracle_public_key: self.public_key,
@@ -638,6 +638,17 @@ fn process_incoming_messages(
         }
     }
 
+    let pending_messages = {
+        let mut manager = dlc_manager.lock().unwrap();
+        let mut pending_messages = manager.get_and_clear_pending_multi_party_messages();
+        pending_messages.extend(manager.get_and_clear_pending_novation_messages());
+        pending_messages
+    };
+    for (node_id, msg) in pending_messages {
+        println!("Sending message to {}", node_id);
+        dlc_message_handler.send_message(node_id, msg);
+    }
+
     if dlc_message_handler.has_pending_messages() {
         peer_manager.process_events();
     }
@@ -0,0 +1,219 @@
+//! Strategies used by the [`crate::SimpleWallet`] to select the UTXOs funding a
+//! contract. Selections are made on the effective value of the UTXOs, that is
+//! their value minus the fee required to spend them, assuming P2WPKH inputs.
+
+use dlc_manager::Utxo;
+
+/// The virtual size of a P2WPKH input.
+const P2WPKH_INPUT_VBYTES: u64 = 68;
+/// The virtual size of a P2WPKH change output.
+const CHANGE_OUTPUT_VBYTES: u64 = 31;
+/// The maximum number of selections explored by [`BranchAndBound`].
+const MAX_TRIES: usize = 100_000;
+
+/// A strategy selecting UTXOs to fund a contract.
+pub trait CoinSelection {
+    /// Returns UTXOs from `utxos` whose effective value at `fee_rate` (in
+    /// satoshis per virtual byte) covers `target`, or `None` if no such
+    /// selection was found.
+    fn select_coins(&self, utxos: &[Utxo], target: u64, fee_rate: u64) -> Option<Vec<Utxo>>;
+}
+
+fn get_effective_value(utxo: &Utxo, fee_rate: u64) -> u64 {
+    utxo.tx_out
+        .value
+        .saturating_sub(fee_rate * P2WPKH_INPUT_VBYTES)
+}
+
+/// Returns the UTXOs with a positive effective value, sorted by decreasing
+/// effective value.
+fn get_sorted_pool(utxos: &[Utxo], fee_rate: u64) -> Vec<(u64, &Utxo)> {
+    let mut pool = utxos
+        .iter()
+        .map(|x| (get_effective_value(x, fee_rate), x))
+        .filter(|(value, _)| *value > 0)
+        .collect::<Vec<_>>();
+    pool.sort_by(|a, b| b.0.cmp(&a.0));
+    pool
+}
+
+/// Selects the UTXOs with the largest values until the target is reached.
+/// Uses as few inputs as possible but usually creates change.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LargestFirst;
+
+impl CoinSelection for LargestFirst {
+    fn select_coins(&self, utxos: &[Utxo], target: u64, fee_rate: u64) -> Option<Vec<Utxo>> {
+        let mut total = 0;
+        let mut selection = Vec::new();
+        for (value, utxo) in get_sorted_pool(utxos, fee_rate) {
+            if total >= target {
+                break;
+            }
+            total += value;
+            selection.push(utxo.clone());
+        }
+        if total < target {
+            return None;
+        }
+        Some(selection)
+    }
+}
+
+/// Searches for a selection exceeding the target by less than the cost of
+/// creating and later spending a change output, so that no change is needed.
+/// Returns the selection wasting the least amount in fees, or `None` if there
+/// is no such selection.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BranchAndBound;
+
+struct Search<'a> {
+    pool: &'a [(u64, &'a Utxo)],
+    target: u64,
+    upper_bound: u64,
+    tries: usize,
+    selection: Vec<usize>,
+    best: Option<(u64, Vec<usize>)>,
+}
+
+impl<'a> Search<'a> {
+    fn explore(&mut self, index: usize, current: u64, remaining: u64) {
+        if self.tries == 0 || current > self.upper_bound {
+            return;
+        }
+        self.tries -= 1;
+        if current >= self.target {
+            let excess = current - self.target;
+            if self.best.as_ref().map_or(true, |(best, _)| excess < *best) {
+                self.best = Some((excess, self.selection.clone()));
+            }
+            return;
+        }
+        if index == self.pool.len() || current + remaining < self.target {
+            return;
+        }
+        let value = self.pool[index].0;
+        self.selection.push(index);
+        self.explore(index + 1, current + value, remaining - value);
+        self.selection.pop();
+        self.explore(index + 1, current, remaining - value);
+    }
+}
+
+impl CoinSelection for BranchAndBound {
+    fn select_coins(&self, utxos: &[Utxo], target: u64, fee_rate: u64) -> Option<Vec<Utxo>> {
+        let pool = get_sorted_pool(utxos, fee_rate);
+        let cost_of_change = fee_rate * (CHANGE_OUTPUT_VBYTES + P2WPKH_INPUT_VBYTES);
+        let mut search = Search {
+            pool: &pool,
+            target,
+            upper_bound: target + cost_of_change,
+            tries: MAX_TRIES,
+            selection: Vec::new(),
+            best: None,
+        };
+        search.explore(0, 0, pool.iter().map(|x| x.0).sum());
+        search
+            .best
+            .map(|(_, selection)| selection.iter().map(|i| pool[*i].1.clone()).collect())
+    }
+}
+
+/// Selects UTXOs avoiding change using [`BranchAndBound`] when possible, and
+/// using the `fallback` strategy otherwise.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AvoidChange<F: CoinSelection = LargestFirst> {
+    fallback: F,
+}
+
+impl<F: CoinSelection> AvoidChange<F> {
+    /// Creates a strategy avoiding change, using `fallback` when no changeless
+    /// selection exists.
+    pub fn new(fallback: F) -> Self {
+        Self { fallback }
+    }
+}
+
+impl<F: CoinSelection> CoinSelection for AvoidChange<F> {
+    fn select_coins(&self, utxos: &[Utxo], target: u64, fee_rate: u64) -> Option<Vec<Utxo>> {
+        BranchAndBound
+            .select_coins(utxos, target, fee_rate)
+            .or_else(|| self.fallback.select_coins(utxos, target, fee_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{Address, Network, OutPoint, Script, TxOut, Txid};
+
+    fn get_utxos(values: &[u64]) -> Vec<Utxo> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Utxo {
+                tx_out: TxOut {
+                    value: *value,
+                    script_pubkey: Script::new(),
+                },
+                outpoint: OutPoint::new(Txid::from_inner([i as u8; 32]), 0),
+                address: Address::p2wsh(&Script::new(), Network::Regtest),
+                redeem_script: Script::new(),
+                reserved: false,
+            })
+            .collect()
+    }
+
+    fn get_values(selection: Option<Vec<Utxo>>) -> Option<Vec<u64>> {
+        selection.map(|x| x.iter().map(|u| u.tx_out.value).collect())
+    }
+
+    #[test]
+    fn largest_first_uses_fewest_inputs() {
+        let utxos = get_utxos(&[1000, 5000, 3000, 2000]);
+
+        assert_eq!(
+            Some(vec![5000, 3000]),
+            get_values(LargestFirst.select_coins(&utxos, 6000, 0))
+        );
+        assert_eq!(None, LargestFirst.select_coins(&utxos, 12000, 0));
+    }
+
+    #[test]
+    fn branch_and_bound_finds_changeless_selection() {
+        let utxos = get_utxos(&[1000, 5000, 3000, 2000]);
+
+        assert_eq!(
+            Some(vec![5000, 1000]),
+            get_values(BranchAndBound.select_coins(&utxos, 6000, 0))
+        );
+        assert_eq!(None, BranchAndBound.select_coins(&utxos, 500, 0));
+    }
+
+    #[test]
+    fn branch_and_bound_accounts_for_input_fees() {
+        let utxos = get_utxos(&[1068, 2068, 10000]);
+
+        // 1000 + 2000 of effective value at 1 sat/vbyte.
+        assert_eq!(
+            Some(vec![2068, 1068]),
+            get_values(BranchAndBound.select_coins(&utxos, 3000, 1))
+        );
+    }
+
+    #[test]
+    fn avoid_change_falls_back_when_needed() {
+        let utxos = get_utxos(&[1000, 5000, 3000, 2000]);
+        let strategy = AvoidChange::<LargestFirst>::default();
+
+        assert_eq!(
+            Some(vec![5000, 1000]),
+            get_values(strategy.select_coins(&utxos, 6000, 0))
+        );
+        assert_eq!(
+            Some(vec![5000]),
+            get_values(strategy.select_coins(&utxos, 500, 0))
+        );
+    }
+}
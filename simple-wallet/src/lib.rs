@@ -1,12 +1,24 @@
 use std::ops::Deref;
+use std::sync::Mutex;
+use std::time::Duration;
 
+use bitcoin::util::schnorr::TapTweak;
+use bitcoin::util::sighash::{Prevouts, SighashCache};
 use bitcoin::{
-    Address, Network, PackedLockTime, Script, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+    Address, EcdsaSighashType, Network, OutPoint, PackedLockTime, SchnorrSig, SchnorrSighashType,
+    Script, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
 };
-use dlc_manager::{error::Error, Blockchain, Signer, Utxo, Wallet};
+use dlc_manager::utxo_reservation::UtxoReservations;
+use dlc_manager::zeroizing::ZeroizingSecretKey;
+use dlc_manager::{error::Error, Blockchain, ContractId, ContractSigner, Utxo, Wallet};
 use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
-use rust_bitcoin_coin_selection::select_coins;
-use secp256k1_zkp::{rand::thread_rng, All, PublicKey, Secp256k1, SecretKey};
+use secp256k1_zkp::{
+    rand::thread_rng, All, KeyPair, Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey,
+};
+
+pub mod coin_selection;
+
+use coin_selection::{AvoidChange, CoinSelection};
 
 type Result<T> = core::result::Result<T, Error>;
 
@@ -31,6 +43,16 @@ pub trait WalletStorage {
     fn unreserve_utxo(&self, txid: &Txid, vout: u32) -> Result<()>;
 }
 
+/// The type of the addresses generated by a [`SimpleWallet`], used for payout
+/// and change outputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressType {
+    /// Native segwit v0 addresses.
+    P2wpkh,
+    /// Taproot addresses spendable using the key path only.
+    P2tr,
+}
+
 /// Basic wallet mainly meant for testing purposes.
 pub struct SimpleWallet<B: Deref, W: Deref>
 where
@@ -41,6 +63,9 @@ where
     storage: W,
     secp_ctx: Secp256k1<All>,
     network: Network,
+    reservations: UtxoReservations,
+    coin_selection: Mutex<Box<dyn CoinSelection>>,
+    address_type: AddressType,
 }
 
 impl<B: Deref, W: Deref> SimpleWallet<B, W>
@@ -48,16 +73,120 @@ where
     B::Target: WalletBlockchainProvider,
     W::Target: WalletStorage,
 {
-    /// Create a new wallet instance.
+    /// Create a new wallet instance, selecting coins using [`AvoidChange`].
     pub fn new(blockchain: B, storage: W, network: Network) -> Self {
         Self {
             blockchain,
             storage,
             secp_ctx: Secp256k1::new(),
             network,
+            reservations: UtxoReservations::new(),
+            coin_selection: Mutex::new(Box::new(AvoidChange::default())),
+            address_type: AddressType::P2wpkh,
         }
     }
 
+    /// Set the type of the addresses generated by the wallet, P2WPKH by
+    /// default. Addresses of both types can be spent whatever the setting.
+    pub fn set_address_type(&mut self, address_type: AddressType) {
+        self.address_type = address_type;
+    }
+
+    /// Returns an output descriptor for each address of the wallet, e.g. to
+    /// watch them from another wallet.
+    pub fn get_descriptors(&self) -> Result<Vec<String>> {
+        self.storage
+            .get_addresses()?
+            .iter()
+            .map(|address| {
+                let seckey = self
+                    .storage
+                    .get_priv_key_for_address(address)?
+                    .ok_or_else(|| {
+                        Error::InvalidState(format!("Missing private key for {}", address))
+                    })?;
+                let pubkey = PublicKey::from_secret_key(&self.secp_ctx, &seckey);
+                if address.script_pubkey().is_v1_p2tr() {
+                    Ok(format!("tr({})", pubkey.x_only_public_key().0))
+                } else {
+                    Ok(format!("wpkh({})", pubkey))
+                }
+            })
+            .collect()
+    }
+
+    /// Signs a taproot key path spend. Signing with `SIGHASH_ALL` commits to
+    /// all the spent outputs, which are retrieved from the blockchain for the
+    /// inputs other than the one being signed.
+    fn sign_p2tr_input(
+        &self,
+        seckey: &SecretKey,
+        tx: &mut Transaction,
+        input_index: usize,
+        tx_out: &TxOut,
+        sighash_type: EcdsaSighashType,
+    ) -> Result<()> {
+        let (hash_ty, prevouts) = match sighash_type {
+            EcdsaSighashType::All => {
+                let prevouts = tx
+                    .input
+                    .iter()
+                    .enumerate()
+                    .map(|(i, input)| {
+                        if i == input_index {
+                            return Ok(tx_out.clone());
+                        }
+                        let outpoint = input.previous_output;
+                        self.blockchain
+                            .get_transaction(&outpoint.txid)?
+                            .output
+                            .get(outpoint.vout as usize)
+                            .cloned()
+                            .ok_or_else(|| {
+                                Error::InvalidParameters(format!("Unknown output {}", outpoint))
+                            })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                (SchnorrSighashType::Default, prevouts)
+            }
+            EcdsaSighashType::AllPlusAnyoneCanPay => (
+                SchnorrSighashType::AllPlusAnyoneCanPay,
+                vec![tx_out.clone()],
+            ),
+            _ => {
+                return Err(Error::InvalidParameters(format!(
+                    "Unsupported sighash type {} for taproot inputs",
+                    sighash_type
+                )))
+            }
+        };
+        let prevouts = if hash_ty == SchnorrSighashType::Default {
+            Prevouts::All(&prevouts)
+        } else {
+            Prevouts::One(input_index, prevouts[0].clone())
+        };
+        let sighash = SighashCache::new(&*tx)
+            .taproot_key_spend_signature_hash(input_index, &prevouts, hash_ty)
+            .map_err(|e| Error::WalletError(Box::new(e)))?;
+        let keypair = KeyPair::from_secret_key(&self.secp_ctx, seckey)
+            .tap_tweak(&self.secp_ctx, None)
+            .to_inner();
+        let msg = Message::from_slice(&sighash[..])?;
+        let sig = SchnorrSig {
+            sig: self.secp_ctx.sign_schnorr_no_aux_rand(&msg, &keypair),
+            hash_ty,
+        };
+        tx.input[input_index].witness = Witness::from_vec(vec![sig.to_vec()]);
+        Ok(())
+    }
+
+    /// Set the strategy used to select the UTXOs funding contracts. It can be
+    /// changed before offering or accepting each contract to use a different
+    /// strategy per contract.
+    pub fn set_coin_selection(&self, coin_selection: Box<dyn CoinSelection>) {
+        *self.coin_selection.lock().unwrap() = coin_selection;
+    }
+
     /// Refresh the wallet checking and updating the UTXO states.
     pub fn refresh(&self) -> Result<()> {
         let utxos: Vec<Utxo> = self.storage.get_utxos()?;
@@ -156,40 +285,69 @@ where
     }
 }
 
-impl<B: Deref, W: Deref> Signer for SimpleWallet<B, W>
+impl<B: Deref, W: Deref> ContractSigner for SimpleWallet<B, W>
 where
     B::Target: WalletBlockchainProvider,
     W::Target: WalletStorage,
 {
+    fn get_new_secret_key(&self) -> Result<SecretKey> {
+        let seckey = SecretKey::new(&mut thread_rng());
+        let pubkey = PublicKey::from_secret_key(&self.secp_ctx, &seckey);
+        self.storage.upsert_key_pair(&pubkey, &seckey)?;
+        Ok(seckey)
+    }
+
     fn sign_tx_input(
+        &self,
+        tx: &mut bitcoin::Transaction,
+        input_index: usize,
+        tx_out: &bitcoin::TxOut,
+        redeem_script: Option<bitcoin::Script>,
+    ) -> Result<()> {
+        self.sign_tx_input_with_sighash_type(
+            tx,
+            input_index,
+            tx_out,
+            redeem_script,
+            bitcoin::EcdsaSighashType::All,
+        )
+    }
+
+    fn sign_tx_input_with_sighash_type(
         &self,
         tx: &mut bitcoin::Transaction,
         input_index: usize,
         tx_out: &bitcoin::TxOut,
         _: Option<bitcoin::Script>,
+        sighash_type: bitcoin::EcdsaSighashType,
     ) -> Result<()> {
         let address = Address::from_script(&tx_out.script_pubkey, self.network)
             .expect("a valid scriptpubkey");
-        let seckey = self
+        let seckey: ZeroizingSecretKey = self
             .storage
             .get_priv_key_for_address(&address)?
-            .expect("to have the requested private key");
+            .expect("to have the requested private key")
+            .into();
+        if tx_out.script_pubkey.is_v1_p2tr() {
+            return self.sign_p2tr_input(&seckey, tx, input_index, tx_out, sighash_type);
+        }
         dlc::util::sign_p2wpkh_input(
             &self.secp_ctx,
             &seckey,
             tx,
             input_index,
-            bitcoin::EcdsaSighashType::All,
+            sighash_type,
             tx_out.value,
         )?;
         Ok(())
     }
 
-    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<SecretKey> {
+    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<ZeroizingSecretKey> {
         Ok(self
             .storage
             .get_priv_key_for_pubkey(pubkey)?
-            .expect("to have the requested private key"))
+            .expect("to have the requested private key")
+            .into())
     }
 }
 
@@ -199,67 +357,76 @@ where
     W::Target: WalletStorage,
 {
     fn get_new_address(&self) -> Result<Address> {
-        let seckey = SecretKey::new(&mut thread_rng());
+        let seckey: ZeroizingSecretKey = SecretKey::new(&mut thread_rng()).into();
         let pubkey = PublicKey::from_secret_key(&self.secp_ctx, &seckey);
-        let address = Address::p2wpkh(
-            &bitcoin::PublicKey {
-                inner: pubkey,
-                compressed: true,
-            },
-            self.network,
-        )
-        .map_err(|x| Error::WalletError(Box::new(x)))?;
+        let address = match self.address_type {
+            AddressType::P2wpkh => Address::p2wpkh(
+                &bitcoin::PublicKey {
+                    inner: pubkey,
+                    compressed: true,
+                },
+                self.network,
+            )
+            .map_err(|x| Error::WalletError(Box::new(x)))?,
+            AddressType::P2tr => Address::p2tr(
+                &self.secp_ctx,
+                pubkey.x_only_public_key().0,
+                None,
+                self.network,
+            ),
+        };
         self.storage.upsert_address(&address, &seckey)?;
         Ok(address)
     }
 
-    fn get_new_secret_key(&self) -> Result<SecretKey> {
-        let seckey = SecretKey::new(&mut thread_rng());
-        let pubkey = PublicKey::from_secret_key(&self.secp_ctx, &seckey);
-        self.storage.upsert_key_pair(&pubkey, &seckey)?;
-        Ok(seckey)
-    }
-
     fn get_utxos_for_amount(
         &self,
         amount: u64,
-        _: Option<u64>,
+        fee_rate: Option<u64>,
         lock_utxos: bool,
     ) -> Result<Vec<Utxo>> {
-        let mut utxos = self
+        let utxos = self
             .storage
             .get_utxos()?
             .into_iter()
-            .filter(|x| !x.reserved)
-            .map(|x| UtxoWrap { utxo: x })
+            .filter(|x| !x.reserved && !self.reservations.is_reserved(&x.outpoint))
             .collect::<Vec<_>>();
-        let selection = select_coins(amount, 20, &mut utxos)
+        let selection = self
+            .coin_selection
+            .lock()
+            .unwrap()
+            .select_coins(&utxos, amount, fee_rate.unwrap_or(0))
             .ok_or_else(|| Error::InvalidState("Not enough fund in utxos".to_string()))?;
         if lock_utxos {
             for utxo in selection.clone() {
                 let updated = Utxo {
                     reserved: true,
-                    ..utxo.utxo
+                    ..utxo
                 };
                 self.storage.upsert_utxo(&updated)?;
             }
         }
-        Ok(selection.into_iter().map(|x| x.utxo).collect::<Vec<_>>())
+        Ok(selection)
     }
 
     fn import_address(&self, _: &Address) -> Result<()> {
         Ok(())
     }
-}
 
-#[derive(Clone)]
-struct UtxoWrap {
-    utxo: Utxo,
-}
+    fn reserve_utxos(
+        &self,
+        temporary_id: &ContractId,
+        utxos: &[OutPoint],
+        ttl: Duration,
+    ) -> Result<()> {
+        self.reservations.remove_expired();
+        self.reservations.reserve(temporary_id, utxos, ttl);
+        Ok(())
+    }
 
-impl rust_bitcoin_coin_selection::Utxo for UtxoWrap {
-    fn get_value(&self) -> u64 {
-        self.utxo.tx_out.value
+    fn release_utxos(&self, temporary_id: &ContractId) -> Result<()> {
+        self.reservations.release(temporary_id);
+        Ok(())
     }
 }
 
@@ -267,13 +434,18 @@ impl rust_bitcoin_coin_selection::Utxo for UtxoWrap {
 mod tests {
     use std::rc::Rc;
 
-    use dlc_manager::{Signer, Wallet};
-    use mocks::simple_wallet::SimpleWallet;
+    use bitcoin::util::sighash::{Prevouts, SighashCache};
+    use bitcoin::{
+        OutPoint, PackedLockTime, SchnorrSighashType, Script, Sequence, Transaction, TxIn, TxOut,
+        Witness,
+    };
+    use dlc_manager::{ContractSigner, Wallet};
+    use mocks::simple_wallet::{AddressType, SimpleWallet};
     use mocks::{memory_storage_provider::MemoryStorage, mock_blockchain::MockBlockchain};
-    use secp256k1_zkp::{PublicKey, SECP256K1};
+    use secp256k1_zkp::{schnorr::Signature, Message, PublicKey, XOnlyPublicKey, SECP256K1};
 
     fn get_wallet() -> SimpleWallet<Rc<MockBlockchain>, Rc<MemoryStorage>> {
-        let blockchain = Rc::new(MockBlockchain {});
+        let blockchain = Rc::new(MockBlockchain::new());
         let storage = Rc::new(MemoryStorage::new());
         SimpleWallet::new(blockchain, storage, bitcoin::Network::Regtest)
     }
@@ -286,6 +458,52 @@ mod tests {
 
         let sk2 = wallet.get_secret_key_for_pubkey(&pk).unwrap();
 
-        assert_eq!(sk, sk2);
+        assert_eq!(sk, *sk2);
+    }
+
+    #[test]
+    fn taproot_address_can_be_spent() {
+        let mut wallet = get_wallet();
+        wallet.set_address_type(AddressType::P2tr);
+        let address = wallet.get_new_address().unwrap();
+        let tx_out = TxOut {
+            value: 10000,
+            script_pubkey: address.script_pubkey(),
+        };
+        assert!(tx_out.script_pubkey.is_v1_p2tr());
+        assert!(wallet.get_descriptors().unwrap()[0].starts_with("tr("));
+
+        let mut tx = Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: 9000,
+                script_pubkey: Script::new(),
+            }],
+        };
+        wallet.sign_tx_input(&mut tx, 0, &tx_out, None).unwrap();
+
+        let sighash = SighashCache::new(&tx)
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::All(&[tx_out.clone()]),
+                SchnorrSighashType::Default,
+            )
+            .unwrap();
+        let output_key = XOnlyPublicKey::from_slice(&tx_out.script_pubkey[2..]).unwrap();
+        let sig = Signature::from_slice(tx.input[0].witness.last().unwrap()).unwrap();
+        SECP256K1
+            .verify_schnorr(
+                &sig,
+                &Message::from_slice(&sighash[..]).unwrap(),
+                &output_key,
+            )
+            .unwrap();
     }
 }